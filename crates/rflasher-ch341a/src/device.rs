@@ -17,8 +17,8 @@ use nusb::Endpoint;
 use nusb::MaybeFuture;
 use nusb::transfer::{Buffer, Bulk, In, Out};
 use rflasher_core::error::{Error as CoreError, Result as CoreResult};
-use rflasher_core::programmer::{SpiFeatures, SpiMaster};
-use rflasher_core::spi::{SpiCommand, check_io_mode_supported};
+use rflasher_core::programmer::{CsLevel, SpiFeatures, SpiMaster};
+use rflasher_core::spi::{IoMode, SpiCommand, check_io_mode_supported};
 
 use crate::error::{Ch341aError, Result};
 use crate::protocol::*;
@@ -91,6 +91,39 @@ pub struct Ch341a {
     in_ep: Endpoint<Bulk, In>,
     /// Accumulated delay for CS handling
     stored_delay_us: u32,
+    /// Current SPI clock speed
+    speed: SpiSpeed,
+    /// Whether dual-output reads are enabled
+    dual: bool,
+    /// Output levels of the spare UIO pins, see [`UIO_GPIO_MASK`]
+    gpio: u8,
+}
+
+/// Check that a UIO pin is one of the spare ones usable as a GPIO
+fn check_gpio_pin(pin: u8) -> Result<()> {
+    if UIO_GPIO_PINS.contains(&pin) {
+        Ok(())
+    } else {
+        Err(Ch341aError::ConfigError(format!(
+            "D{} can't be used as a GPIO in SPI mode (spare pins: D1, D2, D4)",
+            pin
+        )))
+    }
+}
+
+/// Combine configured GPIO levels into UIO output bits, other spare pins
+/// staying high
+fn gpio_levels(pins: &[(u8, bool)]) -> Result<u8> {
+    let mut levels = UIO_GPIO_MASK;
+    for &(pin, high) in pins {
+        check_gpio_pin(pin)?;
+        if high {
+            levels |= 1 << pin;
+        } else {
+            levels &= !(1 << pin);
+        }
+    }
+    Ok(levels)
 }
 
 // ---------------------------------------------------------------------------
@@ -104,13 +137,23 @@ impl Ch341a {
     /// Searches for a CH341A device (VID:1a86 PID:5512) and opens it.
     /// Returns an error if no device is found or if the device cannot be opened.
     pub fn open() -> Result<Self> {
-        Self::open_nth(0)
+        Self::open_with_config(SpiConfig::default())
+    }
+
+    /// Open a CH341A device with custom configuration
+    pub fn open_with_config(config: SpiConfig) -> Result<Self> {
+        Self::open_nth_with_config(0, config)
     }
 
-    /// Open the nth CH341A device (0-indexed)
+    /// Open the nth CH341A device (0-indexed) with default configuration
     ///
     /// Useful when multiple CH341A devices are connected.
     pub fn open_nth(index: usize) -> Result<Self> {
+        Self::open_nth_with_config(index, SpiConfig::default())
+    }
+
+    /// Open the nth CH341A device with custom configuration
+    pub fn open_nth_with_config(index: usize, config: SpiConfig) -> Result<Self> {
         let devices: Vec<_> = nusb::list_devices()
             .wait()
             .map_err(|e| Ch341aError::OpenFailed(e.to_string()))?
@@ -158,6 +201,9 @@ impl Ch341a {
             out_ep,
             in_ep,
             stored_delay_us: 0,
+            speed: config.speed,
+            dual: config.dual,
+            gpio: gpio_levels(&config.gpio)?,
         };
 
         // Configure the device for SPI mode
@@ -270,6 +316,14 @@ impl Ch341a {
 
     /// Open a CH341A device from a DeviceInfo
     pub async fn open(device_info: nusb::DeviceInfo) -> Result<Self> {
+        Self::open_with_config(device_info, SpiConfig::default()).await
+    }
+
+    /// Open a CH341A device from a DeviceInfo with custom configuration
+    pub async fn open_with_config(
+        device_info: nusb::DeviceInfo,
+        config: SpiConfig,
+    ) -> Result<Self> {
         log::info!(
             "Opening CH341A device VID={:04X} PID={:04X}",
             device_info.vendor_id(),
@@ -298,6 +352,9 @@ impl Ch341a {
             out_ep,
             in_ep,
             stored_delay_us: 0,
+            speed: config.speed,
+            dual: config.dual,
+            gpio: gpio_levels(&config.gpio)?,
         };
 
         ch341a.configure().await?;
@@ -321,16 +378,81 @@ impl Ch341a {
     /// Configure the CH341A for SPI mode
     #[maybe_async]
     async fn configure(&mut self) -> Result<()> {
-        // Set I2C/SPI mode to 100kHz base (the actual SPI speed is ~2MHz)
-        self.config_stream(CH341A_STM_I2C_100K).await?;
+        // The SPI clock follows the I2C speed setting
+        self.config_stream(self.speed.bits()).await?;
 
         // Enable output pins
         self.enable_pins(true).await?;
 
-        log::info!("CH341A configured for SPI mode");
+        log::info!(
+            "CH341A configured for SPI mode at ~{} kHz",
+            self.speed.to_khz()
+        );
         Ok(())
     }
 
+    /// Get the current SPI clock speed
+    pub fn speed(&self) -> SpiSpeed {
+        self.speed
+    }
+
+    /// Change the SPI clock speed
+    #[maybe_async]
+    pub async fn set_speed(&mut self, speed: SpiSpeed) -> Result<()> {
+        self.config_stream(speed.bits()).await?;
+        self.speed = speed;
+        log::debug!("CH341A SPI speed set to ~{} kHz", speed.to_khz());
+        Ok(())
+    }
+
+    /// Drive a spare UIO pin (D1, D2 or D4) high or low
+    ///
+    /// Useful for switching target power through a MOSFET or holding WP#
+    /// or HOLD# lines. The level holds across SPI transfers until the
+    /// device is closed, when all pins are released to inputs.
+    #[maybe_async]
+    pub async fn set_gpio(&mut self, pin: u8, high: bool) -> Result<()> {
+        check_gpio_pin(pin)?;
+        if high {
+            self.gpio |= 1 << pin;
+        } else {
+            self.gpio &= !(1 << pin);
+        }
+
+        let buf = vec![
+            CH341A_CMD_UIO_STREAM,
+            CH341A_CMD_UIO_STM_OUT | uio_output(false, self.gpio),
+            CH341A_CMD_UIO_STM_END,
+        ];
+
+        self.usb_write(&buf).await?;
+        log::debug!("CH341A D{} set {}", pin, if high { "high" } else { "low" });
+        Ok(())
+    }
+
+    /// Switch the SPI stream between one and two data lanes
+    ///
+    /// In dual mode DOUT is released so the flash can drive IO0 back.
+    #[maybe_async]
+    async fn set_dual_lanes(&mut self, dual: bool) -> Result<()> {
+        let (mode, dir) = if dual {
+            (self.speed.bits() | CH341A_STM_SPI_DBL, UIO_DIR_DUAL_READ)
+        } else {
+            (self.speed.bits(), UIO_DIR_OUTPUT)
+        };
+
+        let buf = vec![
+            CH341A_CMD_I2C_STREAM,
+            CH341A_CMD_I2C_STM_SET | mode,
+            CH341A_CMD_I2C_STM_END,
+            CH341A_CMD_UIO_STREAM,
+            CH341A_CMD_UIO_STM_DIR | dir,
+            CH341A_CMD_UIO_STM_END,
+        ];
+
+        self.usb_write(&buf).await
+    }
+
     /// Configure the stream interface speed
     #[maybe_async]
     async fn config_stream(&mut self, speed: u8) -> Result<()> {
@@ -355,8 +477,8 @@ impl Ch341a {
 
         let buf = vec![
             CH341A_CMD_UIO_STREAM,
-            CH341A_CMD_UIO_STM_OUT | UIO_CS_DEASSERT, // CS high, SCK=0, DOUT*=1
-            CH341A_CMD_UIO_STM_DIR | dir,             // Output enable/disable
+            CH341A_CMD_UIO_STM_OUT | uio_output(false, self.gpio), // CS high, SCK=0, DOUT=1
+            CH341A_CMD_UIO_STM_DIR | dir,                          // Output enable/disable
             CH341A_CMD_UIO_STM_END,
         ];
 
@@ -382,7 +504,57 @@ impl Ch341a {
         Ok(())
     }
 
-    /// Perform an SPI transfer using pipelined async USB transfers.
+    /// Perform an SPI transfer, asserting CS first and returning the bytes
+    /// read after `write_data`
+    #[maybe_async]
+    async fn spi_transfer(&mut self, write_data: &[u8], read_len: usize) -> Result<Vec<u8>> {
+        let raw = self.stream_transfer(write_data, read_len, true).await?;
+        Ok(raw.into_iter().map(reverse_byte).collect())
+    }
+
+    /// Clock `bytes` bytes with DOUT high and CS held at `cs`
+    ///
+    /// With CS asserted it stays so until the next transfer's CS packet,
+    /// like after any other transfer.
+    #[maybe_async]
+    async fn idle_clocks(&mut self, bytes: usize, cs: CsLevel) -> Result<()> {
+        let data = vec![0xFF; bytes];
+        if cs == CsLevel::Deasserted {
+            let buf = [
+                CH341A_CMD_UIO_STREAM,
+                CH341A_CMD_UIO_STM_OUT | uio_output(false, self.gpio),
+                CH341A_CMD_UIO_STM_END,
+            ];
+            self.usb_write(&buf).await?;
+        }
+        self.stream_transfer(&data, 0, cs == CsLevel::Asserted)
+            .await?;
+        Ok(())
+    }
+
+    /// Perform a dual-output (1-1-2) read
+    ///
+    /// The header goes out on one lane, then the stream is switched to dual
+    /// mode for the data phase. CS stays asserted in between, since it is
+    /// only released by the next transfer's CS packet.
+    #[maybe_async]
+    async fn dual_read(&mut self, header: &[u8], buf: &mut [u8]) -> Result<()> {
+        self.spi_transfer(header, 0).await?;
+
+        self.set_dual_lanes(true).await?;
+        let result = self.stream_transfer(&[], buf.len(), false).await;
+        // Always return to single-lane mode, even if the read failed
+        let restore = self.set_dual_lanes(false).await;
+        let raw = result?;
+        restore?;
+
+        for (dst, &src) in buf.iter_mut().zip(&raw) {
+            *dst = decode_dual_byte(src);
+        }
+        Ok(())
+    }
+
+    /// Stream SPI bytes using pipelined async USB transfers.
     ///
     /// This mirrors flashprog's `usb_transfer()` approach for maximum throughput:
     /// 1. Build all OUT data (CS packet + SPI_STREAM packets) into one contiguous buffer
@@ -393,8 +565,17 @@ impl Ch341a {
     /// This pipelining is critical for USB 1.1 performance: the device produces
     /// IN responses as it processes each SPI_STREAM packet from the OUT data,
     /// and having multiple IN transfers pre-queued ensures we never miss data.
+    ///
+    /// With `assert_cs` unset the CS packet is skipped, continuing the
+    /// transaction left open by the previous transfer. Returns the raw
+    /// (not bit-reversed) bytes received after `write_data`.
     #[maybe_async]
-    async fn spi_transfer(&mut self, write_data: &[u8], read_len: usize) -> Result<Vec<u8>> {
+    async fn stream_transfer(
+        &mut self,
+        write_data: &[u8],
+        read_len: usize,
+        assert_cs: bool,
+    ) -> Result<Vec<u8>> {
         let writecnt = write_data.len();
         let readcnt = read_len;
         let total_spi_bytes = writecnt + readcnt;
@@ -403,11 +584,14 @@ impl Ch341a {
         let packets = (total_spi_bytes + CH341_PACKET_LENGTH - 2) / (CH341_PACKET_LENGTH - 1);
 
         // Build the entire OUT buffer: CS packet + all SPI_STREAM packets
-        let out_total = CH341_PACKET_LENGTH + packets * CH341_PACKET_LENGTH;
+        let cs_len = if assert_cs { CH341_PACKET_LENGTH } else { 0 };
+        let out_total = cs_len + packets * CH341_PACKET_LENGTH;
         let mut wbuf = vec![0u8; out_total];
 
         // First 32-byte slot: CS assertion packet
-        self.build_cs_packet(&mut wbuf[..CH341_PACKET_LENGTH]);
+        if assert_cs {
+            self.build_cs_packet(&mut wbuf[..CH341_PACKET_LENGTH]);
+        }
 
         // Following slots: SPI_STREAM packets
         let mut write_left = writecnt;
@@ -418,7 +602,7 @@ impl Ch341a {
             let write_now = std::cmp::min(CH341_PACKET_LENGTH - 1, write_left);
             let read_now = std::cmp::min((CH341_PACKET_LENGTH - 1) - write_now, read_left);
 
-            let offset = CH341_PACKET_LENGTH + p * CH341_PACKET_LENGTH;
+            let offset = cs_len + p * CH341_PACKET_LENGTH;
             wbuf[offset] = CH341A_CMD_SPI_STREAM;
             for i in 0..write_now {
                 wbuf[offset + 1 + i] = reverse_byte(write_data[write_idx + i]);
@@ -434,7 +618,7 @@ impl Ch341a {
         }
 
         // Actual OUT length: CS packet (32) + for each SPI packet: 1 cmd byte + payload bytes
-        let out_len = CH341_PACKET_LENGTH + packets + total_spi_bytes;
+        let out_len = cs_len + packets + total_spi_bytes;
 
         // Allocate read result buffer
         let mut rbuf = vec![0u8; total_spi_bytes];
@@ -533,13 +717,8 @@ impl Ch341a {
         // Drain any extra pending transfers
         self.drain_all_pending().await;
 
-        // Extract and bit-reverse the read data
-        let mut result = Vec::with_capacity(readcnt);
-        for i in 0..readcnt {
-            result.push(reverse_byte(rbuf[writecnt + i]));
-        }
-
-        Ok(result)
+        rbuf.drain(..writecnt);
+        Ok(rbuf)
     }
 
     /// Cancel and drain all pending transfers on both endpoints.
@@ -569,20 +748,22 @@ impl Ch341a {
         packet[idx] = CH341A_CMD_UIO_STREAM;
         idx += 1;
 
+        let deassert = CH341A_CMD_UIO_STM_OUT | uio_output(false, self.gpio);
+
         // Deassert CS
-        packet[idx] = CH341A_CMD_UIO_STM_OUT | UIO_CS_DEASSERT;
+        packet[idx] = deassert;
         idx += 1;
 
         // Add delay cycles (limited by packet size)
         let max_delay = CH341_PACKET_LENGTH - 4; // Leave room for CS assert and end
         let actual_delay = std::cmp::min(delay_cnt, max_delay);
         for _ in 0..actual_delay {
-            packet[idx] = CH341A_CMD_UIO_STM_OUT | UIO_CS_DEASSERT;
+            packet[idx] = deassert;
             idx += 1;
         }
 
         // Assert CS
-        packet[idx] = CH341A_CMD_UIO_STM_OUT | UIO_CS_ASSERT;
+        packet[idx] = CH341A_CMD_UIO_STM_OUT | uio_output(true, self.gpio);
         idx += 1;
 
         // End UIO stream
@@ -598,7 +779,11 @@ impl Ch341a {
 impl SpiMaster for Ch341a {
     fn features(&self) -> SpiFeatures {
         // CH341A supports 4-byte addressing (software handled)
-        SpiFeatures::FOUR_BYTE_ADDR
+        let mut features = SpiFeatures::FOUR_BYTE_ADDR;
+        if self.dual {
+            features |= SpiFeatures::DUAL_IN;
+        }
+        features
     }
 
     fn max_read_len(&self) -> usize {
@@ -625,6 +810,13 @@ impl SpiMaster for Ch341a {
         // Append write data (for write commands)
         write_data[header_len..].copy_from_slice(cmd.write_data);
 
+        if cmd.io_mode == IoMode::DualOut && !cmd.read_buf.is_empty() {
+            return self
+                .dual_read(&write_data, cmd.read_buf)
+                .await
+                .map_err(|_e| CoreError::ProgrammerError);
+        }
+
         // Perform the transfer
         let read_len = cmd.read_buf.len();
         let result = self
@@ -670,4 +862,91 @@ impl SpiMaster for Ch341a {
             self.stored_delay_us += us;
         }
     }
+
+    async fn send_clocks(&mut self, cycles: u32, cs: CsLevel) -> CoreResult<()> {
+        // The SPI stream only clocks whole bytes
+        if !cycles.is_multiple_of(8) {
+            return Err(CoreError::OpcodeNotSupported);
+        }
+        self.idle_clocks(cycles as usize / 8, cs)
+            .await
+            .map_err(|_e| CoreError::ProgrammerError)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Option parsing (native only)
+// ---------------------------------------------------------------------------
+
+/// Parse programmer options for CH341A
+///
+/// Supported options:
+/// - `spispeed=<khz>`: SPI clock speed in kHz (default: 2000). The CH341A
+///   only supports ~750 kHz, ~2 MHz, ~3 MHz and ~4 MHz; other values are
+///   rounded to the nearest of these.
+/// - `dual=<on|off>`: Use dual-output (1-1-2) reads, roughly doubling read
+///   throughput (default: off). Needs flash IO0 wired to DIN2 (D6) as well.
+/// - `gpio1`, `gpio2`, `gpio4=<high|low>`: Drive the spare D1, D2 or D4 pin
+///   when the device is opened, e.g. to power the target before probing
+///   (default: high). The other pins are taken by SPI.
+///
+/// # Example
+///
+/// ```ignore
+/// let options = [("spispeed", "750")];
+/// let config = parse_options(&options)?;
+/// ```
+#[cfg(feature = "std")]
+pub fn parse_options(options: &[(&str, &str)]) -> Result<SpiConfig> {
+    let mut config = SpiConfig::default();
+
+    for (key, value) in options {
+        match *key {
+            "spispeed" => {
+                let khz: u32 = value.parse().map_err(|_| {
+                    Ch341aError::ConfigError(format!("Invalid spispeed value: {}", value))
+                })?;
+                config.speed = SpiSpeed::from_khz(khz);
+                log::debug!(
+                    "Setting SPI speed to {}kHz (actual: ~{}kHz)",
+                    khz,
+                    config.speed.to_khz()
+                );
+            }
+            gpio if gpio.starts_with("gpio") => {
+                let pin = gpio[4..].parse().map_err(|_| {
+                    Ch341aError::ConfigError(format!("Invalid GPIO option: {}", gpio))
+                })?;
+                check_gpio_pin(pin)?;
+                let high = match *value {
+                    "high" | "1" => true,
+                    "low" | "0" => false,
+                    _ => {
+                        return Err(Ch341aError::ConfigError(format!(
+                            "Invalid {}: {} (must be high or low)",
+                            key, value
+                        )));
+                    }
+                };
+                config = config.with_gpio(pin, high);
+            }
+            "dual" => {
+                config.dual = match *value {
+                    "on" => true,
+                    "off" => false,
+                    _ => {
+                        return Err(Ch341aError::ConfigError(format!(
+                            "Invalid dual: {} (must be on or off)",
+                            value
+                        )));
+                    }
+                };
+            }
+            _ => {
+                log::warn!("Unknown CH341A option: {}={}", key, value);
+            }
+        }
+    }
+
+    Ok(config)
 }
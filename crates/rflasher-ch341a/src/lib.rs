@@ -41,6 +41,8 @@ mod protocol;
 #[cfg(any(feature = "std", feature = "wasm"))]
 pub use device::Ch341a;
 #[cfg(feature = "std")]
-pub use device::Ch341aDeviceInfo;
+pub use device::{Ch341aDeviceInfo, parse_options};
 #[cfg(any(feature = "std", feature = "wasm"))]
 pub use error::{Ch341aError, Result};
+#[cfg(any(feature = "std", feature = "wasm"))]
+pub use protocol::{SpiConfig, SpiSpeed};
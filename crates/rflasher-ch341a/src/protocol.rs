@@ -57,13 +57,120 @@ pub const CH341A_STM_I2C_400K: u8 = 0x02;
 pub const CH341A_STM_I2C_750K: u8 = 0x03;
 pub const CH341A_STM_SPI_DBL: u8 = 0x04;
 
+/// SPI clock speed
+///
+/// The CH341A has no SPI clock divider of its own; the SPI clock follows
+/// the I2C speed bits of the stream mode register (bits 0-1 of
+/// `I2C_STM_SET`, with bit 2 selecting dual I/O). Only four clocks are
+/// reachable, and the frequencies below are approximate since they vary
+/// between chip revisions and clones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpiSpeed {
+    /// ~750 kHz (I2C 20 kHz setting)
+    Speed750K = 0,
+    /// ~2 MHz (I2C 100 kHz setting, default)
+    #[default]
+    Speed2M = 1,
+    /// ~3 MHz (I2C 400 kHz setting)
+    Speed3M = 2,
+    /// ~4 MHz (I2C 750 kHz setting)
+    Speed4M = 3,
+}
+
+impl SpiSpeed {
+    /// All SPI speed variants, from slowest to fastest
+    pub const ALL: &[SpiSpeed] = &[
+        SpiSpeed::Speed750K,
+        SpiSpeed::Speed2M,
+        SpiSpeed::Speed3M,
+        SpiSpeed::Speed4M,
+    ];
+
+    /// Convert a frequency in kHz to the nearest supported speed
+    ///
+    /// Requests outside the supported range are clamped to the slowest or
+    /// fastest setting.
+    pub fn from_khz(khz: u32) -> Self {
+        Self::ALL
+            .iter()
+            .copied()
+            .min_by_key(|speed| speed.to_khz().abs_diff(khz))
+            .unwrap_or_default()
+    }
+
+    /// Get the approximate speed in kHz
+    pub fn to_khz(self) -> u32 {
+        match self {
+            SpiSpeed::Speed750K => 750,
+            SpiSpeed::Speed2M => 2_000,
+            SpiSpeed::Speed3M => 3_000,
+            SpiSpeed::Speed4M => 4_000,
+        }
+    }
+
+    /// Get the speed bits for the stream mode register
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+}
+
+/// SPI configuration for CH341A
+#[derive(Debug, Clone, Default)]
+pub struct SpiConfig {
+    /// SPI clock speed
+    pub speed: SpiSpeed,
+    /// Use dual-output (1-1-2) reads
+    ///
+    /// Only works on adapters that also connect the flash IO0 (SI) pin to
+    /// DIN2 (D6), since the CH341A samples the second lane there.
+    pub dual: bool,
+    /// Levels for spare UIO pins, as (pin, high), applied when the device
+    /// is opened
+    ///
+    /// Only D1, D2 and D4 are free in SPI mode, see [`UIO_GPIO_PINS`].
+    /// Pins not listed stay high.
+    pub gpio: Vec<(u8, bool)>,
+}
+
+impl SpiConfig {
+    /// Create a new SPI configuration with default values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the SPI clock speed
+    pub fn with_speed(mut self, speed: SpiSpeed) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Set the SPI clock speed from a frequency in kHz
+    pub fn with_speed_khz(mut self, khz: u32) -> Self {
+        self.speed = SpiSpeed::from_khz(khz);
+        self
+    }
+
+    /// Enable or disable dual-output (1-1-2) reads
+    pub fn with_dual(mut self, dual: bool) -> Self {
+        self.dual = dual;
+        self
+    }
+
+    /// Drive a spare UIO pin high or low once the device is opened
+    pub fn with_gpio(mut self, pin: u8, high: bool) -> Self {
+        self.gpio.retain(|&(p, _)| p != pin);
+        self.gpio.push((pin, high));
+        self
+    }
+}
+
 // Pin mappings (from CH341A to SPI):
 // UIO  CH341A  SPI     CH341A SPI name
 // 0    D0/15   CS/1    (CS0)
-// 1    D1/16   unused  (CS1)
-// 2    D2/17   unused  (CS2)
+// 1    D1/16   GPIO    (CS1)
+// 2    D2/17   GPIO    (CS2)
 // 3    D3/18   SCK/6   (DCK)
-// 4    D4/19   unused  (DOUT2)
+// 4    D4/19   GPIO    (DOUT2)
 // 5    D5/20   SI/5    (DOUT)
 // 6    D6/21   unused  (DIN2)
 // 7    D7/22   SO/2    (DIN)
@@ -76,6 +183,26 @@ pub const UIO_CS_ASSERT: u8 = 0x36;
 pub const UIO_DIR_OUTPUT: u8 = 0x3F;
 /// All pins as input (disabled)
 pub const UIO_DIR_INPUT: u8 = 0x00;
+/// All pins but DOUT (D5) as output, so the flash can drive IO0 during
+/// a dual read
+pub const UIO_DIR_DUAL_READ: u8 = 0x1F;
+
+/// Spare UIO pins that are free in SPI mode and can be driven as GPIOs,
+/// e.g. to switch target power or hold WP# high
+pub const UIO_GPIO_PINS: [u8; 3] = [1, 2, 4];
+/// Mask of the spare UIO pins, driven high unless configured otherwise
+pub const UIO_GPIO_MASK: u8 = 0x16;
+
+/// UIO output levels with CS deasserted or asserted and the spare pins at
+/// `gpio` (only the bits in [`UIO_GPIO_MASK`] are used)
+pub fn uio_output(cs_asserted: bool, gpio: u8) -> u8 {
+    let spi = if cs_asserted {
+        UIO_CS_ASSERT
+    } else {
+        UIO_CS_DEASSERT
+    };
+    (spi & !UIO_GPIO_MASK) | (gpio & UIO_GPIO_MASK)
+}
 
 /// Reverse the bits in a byte
 ///
@@ -107,6 +234,25 @@ pub fn reverse_byte(b: u8) -> u8 {
     REVERSE_TABLE[b as usize]
 }
 
+/// Decode a byte received in dual (SPI_DBL) stream mode
+///
+/// In dual mode each SPI_STREAM byte covers four clocks. The CH341A shifts
+/// DIN (D7, flash IO1) into bits 0-3 and DIN2 (D6, flash IO0) into bits 4-7,
+/// earliest clock in the lowest bit. A 1-1-2 flash drives bit 7-2k of the
+/// data byte on IO1 and bit 6-2k on IO0 during clock k, so the lanes have
+/// to be split and interleaved back into MSB-first order.
+#[inline]
+pub fn decode_dual_byte(raw: u8) -> u8 {
+    let mut b = 0;
+    for clock in 0..4 {
+        let io1 = (raw >> clock) & 1;
+        let io0 = (raw >> (4 + clock)) & 1;
+        b |= io1 << (7 - 2 * clock);
+        b |= io0 << (6 - 2 * clock);
+    }
+    b
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -126,4 +272,57 @@ mod tests {
             assert_eq!(reverse_byte(reverse_byte(b)), b);
         }
     }
+
+    #[test]
+    fn test_uio_output() {
+        // All spare pins high matches the plain SPI idle pattern
+        assert_eq!(uio_output(false, UIO_GPIO_MASK), UIO_CS_DEASSERT);
+        assert_eq!(uio_output(true, UIO_GPIO_MASK), UIO_CS_ASSERT);
+
+        // D4 low, D1 and D2 high
+        assert_eq!(uio_output(false, 0x06), 0x27);
+        assert_eq!(uio_output(true, 0x06), 0x26);
+
+        // SPI bits can't be overridden
+        assert_eq!(uio_output(false, 0xFF), UIO_CS_DEASSERT);
+        assert_eq!(uio_output(true, 0x00), 0x20);
+    }
+
+    #[test]
+    fn test_spi_speed_from_khz() {
+        assert_eq!(SpiSpeed::from_khz(2000), SpiSpeed::Speed2M);
+        assert_eq!(SpiSpeed::from_khz(2400), SpiSpeed::Speed2M);
+        assert_eq!(SpiSpeed::from_khz(2600), SpiSpeed::Speed3M);
+        // Out-of-range requests clamp to the nearest end
+        assert_eq!(SpiSpeed::from_khz(0), SpiSpeed::Speed750K);
+        assert_eq!(SpiSpeed::from_khz(100_000), SpiSpeed::Speed4M);
+        for &speed in SpiSpeed::ALL {
+            assert_eq!(SpiSpeed::from_khz(speed.to_khz()), speed);
+            assert_eq!(speed.bits() & !0x03, 0);
+        }
+    }
+
+    /// Lane samples the CH341A collects while a flash sends `b` in 1-1-2 mode
+    fn dual_lanes(b: u8) -> u8 {
+        let mut raw = 0;
+        for clock in 0..4 {
+            raw |= ((b >> (7 - 2 * clock)) & 1) << clock;
+            raw |= ((b >> (6 - 2 * clock)) & 1) << (4 + clock);
+        }
+        raw
+    }
+
+    #[test]
+    fn test_decode_dual_byte() {
+        // IO1 carries the odd bits, IO0 the even bits
+        assert_eq!(decode_dual_byte(0x0F), 0xAA);
+        assert_eq!(decode_dual_byte(0xF0), 0x55);
+        assert_eq!(decode_dual_byte(0x01), 0x80);
+        assert_eq!(decode_dual_byte(0x10), 0x40);
+        assert_eq!(decode_dual_byte(0x80), 0x01);
+        // Loopback: every byte sent over two lanes decodes to itself
+        for b in 0..=255u8 {
+            assert_eq!(decode_dual_byte(dual_lanes(b)), b);
+        }
+    }
 }
@@ -483,8 +483,7 @@ impl Ch347 {
         let mut bytes_written = 0;
         let mut resp_buf = [0u8; 4];
 
-        while bytes_written < data.len() {
-            let chunk_len = std::cmp::min(CH347_MAX_DATA_LEN, data.len() - bytes_written);
+        for chunk_len in packet_chunks(data.len()) {
             let packet_len = chunk_len + 3;
 
             let mut buffer = vec![0u8; packet_len];
@@ -580,7 +579,7 @@ impl Ch347 {
 
     /// Write data to USB endpoint
     #[maybe_async]
-    async fn usb_write(&mut self, data: &[u8]) -> Result<()> {
+    pub(crate) async fn usb_write(&mut self, data: &[u8]) -> Result<()> {
         let mut buf = Buffer::new(data.len());
         buf.extend_from_slice(data);
 
@@ -599,7 +598,7 @@ impl Ch347 {
 
     /// Read data from USB endpoint
     #[maybe_async]
-    async fn usb_read(&mut self, buffer: &mut [u8]) -> Result<usize> {
+    pub(crate) async fn usb_read(&mut self, buffer: &mut [u8]) -> Result<usize> {
         let max_packet_size = self.in_ep.max_packet_size();
         // Request length must be multiple of max packet size
         let request_len = buffer.len().div_ceil(max_packet_size) * max_packet_size;
@@ -705,6 +704,7 @@ impl SpiMaster for Ch347 {
 /// - `spispeed=<khz>`: SPI clock speed in kHz (default: 7500)
 /// - `spimode=<0-3>`: SPI mode (default: 0)
 /// - `cs=<0|1>`: Which chip select to use (default: 0)
+/// - `iomode=single`: Only single I/O is supported, see the crate docs
 ///
 /// # Example
 ///
@@ -761,6 +761,14 @@ pub fn parse_options(options: &[(&str, &str)]) -> Result<SpiConfig> {
                     }
                 };
             }
+            "iomode" => {
+                if !matches!(value.to_lowercase().as_str(), "single" | "1") {
+                    return Err(Ch347Error::ConfigError(format!(
+                        "Unsupported iomode: {} (only single I/O is supported)",
+                        value
+                    )));
+                }
+            }
             _ => {
                 log::warn!("Unknown CH347 option: {}={}", key, value);
             }
@@ -22,6 +22,8 @@ pub enum Ch347Error {
     Timeout,
     /// Configuration error
     ConfigError(String),
+    /// I2C device at this 7-bit address didn't acknowledge
+    I2cNak(u8),
     /// Core library error
     Core(rflasher_core::error::Error),
 }
@@ -40,6 +42,9 @@ impl fmt::Display for Ch347Error {
             }
             Ch347Error::Timeout => write!(f, "Timeout during USB transfer"),
             Ch347Error::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
+            Ch347Error::I2cNak(addr) => {
+                write!(f, "I2C device 0x{:02X} did not acknowledge", addr)
+            }
             Ch347Error::Core(e) => write!(f, "Core error: {}", e),
         }
     }
@@ -0,0 +1,132 @@
+//! CH347 I2C master
+//!
+//! The CH347's I2C interface shares the vendor USB interface with SPI and
+//! takes a CH341A-style command stream (CH347_CMD_I2C_STREAM). `Ch347I2c`
+//! wraps an opened [`Ch347`] and reuses its USB endpoints, so it is kept
+//! separate from the `SpiMaster` implementation.
+//!
+//! Only 7-bit addresses are supported. Each transaction writes and reads
+//! at most [`Ch347I2c::MAX_TRANSFER`] bytes, which is enough for the page
+//! writes and sequential reads of 24Cxx EEPROMs when split up by the
+//! caller.
+
+use maybe_async::maybe_async;
+
+use crate::device::Ch347;
+use crate::error::{Ch347Error, Result};
+use crate::protocol::*;
+
+/// Largest valid 7-bit I2C address
+const I2C_MAX_ADDR: u8 = 0x7F;
+
+/// I2C master on a CH347
+///
+/// On native (with `is_sync`), all methods are synchronous and blocking.
+/// On WASM (without `is_sync`), methods are async and use WebUSB.
+pub struct Ch347I2c {
+    /// Device whose USB endpoints carry the I2C command streams
+    device: Ch347,
+    /// Current I2C clock
+    speed: I2cSpeed,
+}
+
+impl Ch347I2c {
+    /// Maximum number of bytes written or read by one transaction
+    pub const MAX_TRANSFER: usize = CH347_I2C_MAX_TRANSFER;
+
+    /// Use an opened CH347 as I2C master at `speed`
+    #[maybe_async]
+    pub async fn new(device: Ch347, speed: I2cSpeed) -> Result<Self> {
+        let mut i2c = Self { device, speed };
+        i2c.set_speed(speed).await?;
+        Ok(i2c)
+    }
+
+    /// Get the current I2C clock
+    pub fn speed(&self) -> I2cSpeed {
+        self.speed
+    }
+
+    /// Give back the CH347, e.g. to use it for SPI again
+    pub fn into_inner(self) -> Ch347 {
+        self.device
+    }
+
+    /// Set the I2C clock
+    #[maybe_async]
+    pub async fn set_speed(&mut self, speed: I2cSpeed) -> Result<()> {
+        self.device.usb_write(&speed.build_command()).await?;
+        self.speed = speed;
+        log::debug!("CH347 I2C speed set to {}kHz", speed.to_khz());
+        Ok(())
+    }
+
+    /// Write `data` to the device at `addr`
+    #[maybe_async]
+    pub async fn write(&mut self, addr: u8, data: &[u8]) -> Result<()> {
+        self.transfer(addr, data, &mut []).await
+    }
+
+    /// Read `buf.len()` bytes from the device at `addr`
+    #[maybe_async]
+    pub async fn read(&mut self, addr: u8, buf: &mut [u8]) -> Result<()> {
+        self.transfer(addr, &[], buf).await
+    }
+
+    /// Write `data`, then read into `buf` after a repeated start
+    ///
+    /// This is the random read of an EEPROM: `data` holds the offset.
+    #[maybe_async]
+    pub async fn write_read(&mut self, addr: u8, data: &[u8], buf: &mut [u8]) -> Result<()> {
+        self.transfer(addr, data, buf).await
+    }
+
+    /// Check whether a device ACKs `addr`
+    ///
+    /// Also useful to poll an EEPROM until its write cycle is done.
+    #[maybe_async]
+    pub async fn probe(&mut self, addr: u8) -> Result<bool> {
+        match self.transfer(addr, &[], &mut []).await {
+            Ok(()) => Ok(true),
+            Err(Ch347Error::I2cNak(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Run one transaction and check that every written byte was ACKed
+    #[maybe_async]
+    async fn transfer(&mut self, addr: u8, write: &[u8], read: &mut [u8]) -> Result<()> {
+        if addr > I2C_MAX_ADDR {
+            return Err(Ch347Error::ConfigError(format!(
+                "I2C address 0x{:02X} is not a 7-bit address",
+                addr
+            )));
+        }
+        if write.len() > Self::MAX_TRANSFER || read.len() > Self::MAX_TRANSFER {
+            return Err(Ch347Error::ConfigError(format!(
+                "I2C transfers are limited to {} bytes each way",
+                Self::MAX_TRANSFER
+            )));
+        }
+
+        let (stream, written) = build_i2c_stream(addr, write, read.len());
+        self.device.usb_write(&stream).await?;
+
+        // One ACK status byte per written byte, then the read data
+        let mut response = vec![0u8; written + read.len()];
+        let received = self.device.usb_read(&mut response).await?;
+        if received < response.len() {
+            return Err(Ch347Error::InvalidResponse(format!(
+                "I2C: expected {} bytes, got {}",
+                response.len(),
+                received
+            )));
+        }
+
+        if response[..written].iter().any(|&status| status & 0x01 == 0) {
+            return Err(Ch347Error::I2cNak(addr));
+        }
+        read.copy_from_slice(&response[written..]);
+        Ok(())
+    }
+}
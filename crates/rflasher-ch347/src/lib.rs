@@ -1,6 +1,7 @@
 //! rflasher-ch347 - CH347 USB programmer support
 //!
-//! This crate provides support for the CH347 USB-to-SPI programmer.
+//! This crate provides support for the CH347 USB-to-SPI programmer and its
+//! I2C interface.
 //! The CH347 is a high-speed USB 2.0 (480 Mbps) device that supports
 //! SPI, I2C, UART, and JTAG interfaces.
 //!
@@ -50,15 +51,26 @@
 //!
 //! # Limitations
 //!
-//! This driver currently only implements standard single-bit SPI mode.
-//! The CH347 hardware may support dual and quad I/O modes, but:
+//! This driver only implements standard single-bit SPI mode. The CH347
+//! hardware may support dual and quad I/O, but the USB commands to switch
+//! the number of data lines are not documented, so `iomode=dual` and
+//! `iomode=quad` are refused rather than guessed at.
 //!
-//! - **Dual I/O (2-bit)**: Not supported - requires additional hardware investigation
-//! - **Quad I/O (4-bit)**: Not supported - requires additional hardware investigation
+//! # I2C
 //!
-//! The USB protocol commands for these modes are not documented, and it's unclear
-//! if the CH347 hardware actually supports them. Standard SPI mode covers the vast
-//! majority of flash chip programming use cases.
+//! [`Ch347I2c`] drives the I2C interface with 7-bit addresses, e.g. to read
+//! or program a 24Cxx EEPROM on the same adapter:
+//!
+//! ```no_run
+//! use rflasher_ch347::{Ch347, Ch347I2c, I2cSpeed};
+//!
+//! let mut i2c = Ch347I2c::new(Ch347::open()?, I2cSpeed::Speed400K)?;
+//!
+//! // Random read of 16 bytes at offset 0 of an EEPROM at 0x50
+//! let mut data = [0u8; 16];
+//! i2c.write_read(0x50, &[0x00], &mut data)?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
 
 #![cfg_attr(not(any(feature = "std", feature = "wasm")), no_std)]
 
@@ -67,6 +79,8 @@ mod device;
 #[cfg(any(feature = "std", feature = "wasm"))]
 mod error;
 #[cfg(any(feature = "std", feature = "wasm"))]
+mod i2c;
+#[cfg(any(feature = "std", feature = "wasm"))]
 mod protocol;
 
 #[cfg(any(feature = "std", feature = "wasm"))]
@@ -76,4 +90,6 @@ pub use device::{Ch347DeviceInfo, parse_options};
 #[cfg(any(feature = "std", feature = "wasm"))]
 pub use error::{Ch347Error, Result};
 #[cfg(any(feature = "std", feature = "wasm"))]
-pub use protocol::{Ch347Variant, ChipSelect, SpiConfig, SpiMode, SpiSpeed};
+pub use i2c::Ch347I2c;
+#[cfg(any(feature = "std", feature = "wasm"))]
+pub use protocol::{Ch347Variant, ChipSelect, I2cSpeed, SpiConfig, SpiMode, SpiSpeed};
@@ -44,6 +44,29 @@ pub const CH347_CMD_SPI_OUT: u8 = 0xC4;
 /// Get current SPI configuration
 pub const CH347_CMD_SPI_GET_CFG: u8 = 0xCA;
 
+// I2C stream command and its sub-commands, the same stream encoding as the
+// CH341A's I2C interface
+/// I2C command stream, followed by the sub-commands below
+pub const CH347_CMD_I2C_STREAM: u8 = 0xAA;
+/// Set the I2C clock, speed in bits 1:0
+pub const CH347_CMD_I2C_STM_SET: u8 = 0x60;
+/// Generate a start (or repeated start) condition
+pub const CH347_CMD_I2C_STM_STA: u8 = 0x74;
+/// Generate a stop condition
+pub const CH347_CMD_I2C_STM_STO: u8 = 0x75;
+/// Write bytes, count in bits 5:0
+pub const CH347_CMD_I2C_STM_OUT: u8 = 0x80;
+/// Read bytes with ACK, count in bits 5:0. A count of 0 reads one byte and
+/// NAKs it, which ends a read
+pub const CH347_CMD_I2C_STM_IN: u8 = 0xC0;
+/// End of the command stream
+pub const CH347_CMD_I2C_STM_END: u8 = 0x00;
+/// Maximum count of a single STM_OUT or STM_IN sub-command
+pub const CH347_I2C_STM_MAX: usize = 63;
+/// Maximum number of bytes written or read by one I2C transaction, so that
+/// both the command stream and the response fit in one packet
+pub const CH347_I2C_MAX_TRANSFER: usize = 128;
+
 // Chip select control flags
 /// Assert (activate) chip select
 pub const CH347_CS_ASSERT: u8 = 0x00;
@@ -185,6 +208,16 @@ pub enum ChipSelect {
     CS1 = 1,
 }
 
+/// Split a transfer of `len` bytes into per-packet data lengths
+///
+/// Packet lengths count bytes, not clocks, so the split is the same for
+/// every I/O mode.
+pub fn packet_chunks(len: usize) -> impl Iterator<Item = usize> {
+    (0..len)
+        .step_by(CH347_MAX_DATA_LEN)
+        .map(move |offset| std::cmp::min(CH347_MAX_DATA_LEN, len - offset))
+}
+
 /// SPI configuration for CH347
 #[derive(Debug, Clone, Default)]
 pub struct SpiConfig {
@@ -294,6 +327,86 @@ impl Ch347Variant {
     }
 }
 
+/// I2C clock speed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum I2cSpeed {
+    /// 20 kHz
+    Speed20K = 0,
+    /// 100 kHz (default, standard mode)
+    #[default]
+    Speed100K = 1,
+    /// 400 kHz (fast mode)
+    Speed400K = 2,
+    /// 750 kHz
+    Speed750K = 3,
+}
+
+impl I2cSpeed {
+    /// Get the speed in kHz
+    pub fn to_khz(self) -> u32 {
+        match self {
+            I2cSpeed::Speed20K => 20,
+            I2cSpeed::Speed100K => 100,
+            I2cSpeed::Speed400K => 400,
+            I2cSpeed::Speed750K => 750,
+        }
+    }
+
+    /// Build the command stream that sets this speed
+    pub fn build_command(self) -> [u8; 3] {
+        [
+            CH347_CMD_I2C_STREAM,
+            CH347_CMD_I2C_STM_SET | self as u8,
+            CH347_CMD_I2C_STM_END,
+        ]
+    }
+}
+
+/// Build the command stream for one I2C transaction
+///
+/// Writes `write` to the 7-bit address `addr`, then, if `read_len` is not
+/// zero, reads `read_len` bytes after a repeated start. With both empty
+/// this only addresses the device, which is enough to see whether it ACKs.
+///
+/// Returns the stream and the number of bytes written on the bus,
+/// including the address bytes. The device answers with one ACK status
+/// byte per written byte, followed by the read data.
+pub fn build_i2c_stream(addr: u8, write: &[u8], read_len: usize) -> (Vec<u8>, usize) {
+    let mut stream = vec![CH347_CMD_I2C_STREAM];
+    let mut written = 0;
+
+    if !write.is_empty() || read_len == 0 {
+        let mut bytes = Vec::with_capacity(write.len() + 1);
+        bytes.push(addr << 1);
+        bytes.extend_from_slice(write);
+
+        stream.push(CH347_CMD_I2C_STM_STA);
+        for chunk in bytes.chunks(CH347_I2C_STM_MAX) {
+            stream.push(CH347_CMD_I2C_STM_OUT | chunk.len() as u8);
+            stream.extend_from_slice(chunk);
+        }
+        written += bytes.len();
+    }
+
+    if read_len > 0 {
+        stream.push(CH347_CMD_I2C_STM_STA);
+        stream.extend_from_slice(&[CH347_CMD_I2C_STM_OUT | 1, (addr << 1) | 1]);
+        written += 1;
+
+        // ACK all but the last byte, which is NAKed to end the read
+        let mut remaining = read_len - 1;
+        while remaining > 0 {
+            let count = std::cmp::min(remaining, CH347_I2C_STM_MAX);
+            stream.push(CH347_CMD_I2C_STM_IN | count as u8);
+            remaining -= count;
+        }
+        stream.push(CH347_CMD_I2C_STM_IN);
+    }
+
+    stream.extend_from_slice(&[CH347_CMD_I2C_STM_STO, CH347_CMD_I2C_STM_END]);
+    (stream, written)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -346,4 +459,41 @@ mod tests {
         assert_eq!(buf[5], 4); // mystery byte
         assert_eq!(buf[6], 1); // mystery byte
     }
+
+    #[test]
+    fn test_packet_chunks() {
+        let chunks: Vec<usize> = packet_chunks(1200).collect();
+        assert_eq!(chunks, [507, 507, 186]);
+        assert_eq!(packet_chunks(507).collect::<Vec<_>>(), [507]);
+        assert_eq!(packet_chunks(0).count(), 0);
+    }
+
+    #[test]
+    fn test_i2c_stream() {
+        // EEPROM random read: write the offset, then read 2 bytes
+        let (stream, written) = build_i2c_stream(0x50, &[0x10], 2);
+        assert_eq!(
+            stream,
+            [
+                0xAA, 0x74, 0x82, 0xA0, 0x10, 0x74, 0x81, 0xA1, 0xC1, 0xC0, 0x75, 0x00
+            ]
+        );
+        assert_eq!(written, 3);
+
+        // Address-only probe
+        let (stream, written) = build_i2c_stream(0x50, &[], 0);
+        assert_eq!(stream, [0xAA, 0x74, 0x81, 0xA0, 0x75, 0x00]);
+        assert_eq!(written, 1);
+
+        // Long writes and reads are split into sub-commands of 63 bytes
+        let (stream, written) = build_i2c_stream(0x50, &[0; 100], 128);
+        assert_eq!(&stream[2..3], [0x80 | 63]);
+        assert_eq!(stream[66], 0x80 | 38);
+        assert_eq!(written, 102);
+        assert_eq!(
+            &stream[stream.len() - 7..],
+            [0xA1, 0xC0 | 63, 0xC0 | 63, 0xC1, 0xC0, 0x75, 0x00]
+        );
+        assert!(stream.len() <= CH347_PACKET_SIZE);
+    }
 }
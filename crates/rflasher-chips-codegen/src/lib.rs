@@ -7,6 +7,7 @@ use proc_macro2::{Literal, TokenStream};
 use quote::quote;
 use serde::Deserialize;
 
+use std::collections::BTreeMap;
 use std::fs;
 use std::io;
 use std::path::Path;
@@ -143,6 +144,12 @@ pub struct FeaturesDef {
     /// SST26-style per-block protection register (requires WREN + ULBPR to unlock)
     pub sst26_bpr: bool,
 
+    // Vendor-specific configuration registers
+    /// Macronix configuration register (RDCR 0x15, written as the second WRSR byte)
+    pub config_reg: bool,
+    /// ISSI function register (RDFR 0x48 / WRFR 0x42)
+    pub function_reg: bool,
+
     // Status register features
     /// Has status register 2
     pub status_reg_2: bool,
@@ -253,6 +260,12 @@ impl FeaturesDef {
         if self.sst26_bpr {
             flags.push(quote!(Features::SST26_BPR));
         }
+        if self.config_reg {
+            flags.push(quote!(Features::CONFIG_REG));
+        }
+        if self.function_reg {
+            flags.push(quote!(Features::FUNCTION_REG));
+        }
         if self.status_reg_2 {
             flags.push(quote!(Features::STATUS_REG_2));
         }
@@ -402,11 +415,208 @@ impl Default for VoltageDef {
     }
 }
 
+/// Status register holding a write protection bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum StatusRegDef {
+    SR1,
+    SR2,
+    SR3,
+}
+
+impl StatusRegDef {
+    fn to_tokens(self) -> TokenStream {
+        match self {
+            StatusRegDef::SR1 => quote!(crate::wp::StatusRegister::Status1),
+            StatusRegDef::SR2 => quote!(crate::wp::StatusRegister::Status2),
+            StatusRegDef::SR3 => quote!(crate::wp::StatusRegister::Status3),
+        }
+    }
+}
+
+/// Writability of a write protection bit
+#[derive(Debug, Clone, Copy, Deserialize, Default)]
+pub enum BitAccessDef {
+    #[default]
+    ReadWrite,
+    ReadOnly,
+    Otp,
+}
+
+impl BitAccessDef {
+    fn to_tokens(self) -> TokenStream {
+        match self {
+            BitAccessDef::ReadWrite => quote!(crate::wp::BitWritability::ReadWrite),
+            BitAccessDef::ReadOnly => quote!(crate::wp::BitWritability::ReadOnly),
+            BitAccessDef::Otp => quote!(crate::wp::BitWritability::Otp),
+        }
+    }
+}
+
+/// Location of a single write protection bit
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct WpBitDef {
+    /// Status register holding the bit
+    pub reg: StatusRegDef,
+    /// Bit index within the register (0-7)
+    pub bit: u8,
+    /// Whether the bit can be written
+    #[serde(default)]
+    pub access: BitAccessDef,
+}
+
+impl WpBitDef {
+    fn to_tokens(bit: Option<&Self>) -> TokenStream {
+        match bit {
+            Some(def) => {
+                let reg = def.reg.to_tokens();
+                let index = Literal::u8_unsuffixed(def.bit);
+                let access = def.access.to_tokens();
+                quote!(crate::wp::RegBitInfo::new(#reg, #index, #access))
+            }
+            None => quote!(crate::wp::RegBitInfo::not_present()),
+        }
+    }
+}
+
+/// Write protection range decoding algorithm (matches `wp::RangeDecoder`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum RangeDecoderDef {
+    #[default]
+    Spi25,
+    Spi25_64kBlock,
+    Spi25BitCmp,
+    Spi25_2xBlock,
+    Atmel,
+}
+
+impl RangeDecoderDef {
+    fn to_tokens(self) -> TokenStream {
+        match self {
+            RangeDecoderDef::Spi25 => quote!(crate::wp::RangeDecoder::Spi25),
+            RangeDecoderDef::Spi25_64kBlock => quote!(crate::wp::RangeDecoder::Spi25_64kBlock),
+            RangeDecoderDef::Spi25BitCmp => quote!(crate::wp::RangeDecoder::Spi25BitCmp),
+            RangeDecoderDef::Spi25_2xBlock => quote!(crate::wp::RangeDecoder::Spi25_2xBlock),
+            RangeDecoderDef::Atmel => quote!(crate::wp::RangeDecoder::Atmel),
+        }
+    }
+}
+
+/// Maximum number of block protect bits (matches `wp::MAX_BP_BITS`)
+const MAX_BP_BITS: usize = 4;
+
+/// Write protection register layout in RON format
+///
+/// Chips without one use the standard Winbond layout.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct WpBitsDef {
+    /// Range decoding algorithm
+    pub decoder: RangeDecoderDef,
+    /// Block protect bits, BP0 first
+    pub bp: Vec<WpBitDef>,
+    /// Top/Bottom bit
+    pub tb: Option<WpBitDef>,
+    /// Sector/Block bit
+    pub sec: Option<WpBitDef>,
+    /// Complement bit
+    pub cmp: Option<WpBitDef>,
+    /// Status Register Protect 0
+    pub srp0: Option<WpBitDef>,
+    /// Status Register Protect 1 / Status Register Lock
+    pub srp1: Option<WpBitDef>,
+}
+
+impl WpBitsDef {
+    /// Iterate over all defined bits with their names
+    fn bits(&self) -> impl Iterator<Item = (&'static str, &WpBitDef)> {
+        const BP_NAMES: [&str; MAX_BP_BITS] = ["BP0", "BP1", "BP2", "BP3"];
+        let bp = self.bp.iter().zip(BP_NAMES).map(|(bit, name)| (name, bit));
+        let named = [
+            ("TB", &self.tb),
+            ("SEC", &self.sec),
+            ("CMP", &self.cmp),
+            ("SRP0", &self.srp0),
+            ("SRP1", &self.srp1),
+        ];
+        bp.chain(
+            named
+                .into_iter()
+                .filter_map(|(name, bit)| bit.as_ref().map(|bit| (name, bit))),
+        )
+    }
+
+    /// Check the layout against the chip's feature flags
+    fn validate(&self, chip: &str, features: &FeaturesDef) -> Result<(), Error> {
+        if self.bp.is_empty() || self.bp.len() > MAX_BP_BITS {
+            return Err(Error::Validation(format!(
+                "Chip {} wp_bits must define 1 to {} BP bits, found {}",
+                chip,
+                MAX_BP_BITS,
+                self.bp.len()
+            )));
+        }
+        if self.decoder == RangeDecoderDef::Atmel && self.bp.len() != 2 {
+            return Err(Error::Validation(format!(
+                "Chip {} wp_bits with the Atmel decoder must define the two SWP bits as BP0/BP1, found {}",
+                chip,
+                self.bp.len()
+            )));
+        }
+
+        for (name, bit) in self.bits() {
+            if bit.bit > 7 {
+                return Err(Error::Validation(format!(
+                    "Chip {} wp_bits {} has invalid bit index {}",
+                    chip, name, bit.bit
+                )));
+            }
+            let (present, flag) = match bit.reg {
+                StatusRegDef::SR1 => (true, ""),
+                StatusRegDef::SR2 => (features.status_reg_2, "status_reg_2"),
+                StatusRegDef::SR3 => (features.status_reg_3, "status_reg_3"),
+            };
+            if !present {
+                return Err(Error::Validation(format!(
+                    "Chip {} wp_bits {} is in {:?} but the chip lacks the {} feature",
+                    chip, name, bit.reg, flag
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Generate a `WpRegBitMap` expression
+    fn to_tokens(&self) -> TokenStream {
+        let bp = (0..MAX_BP_BITS).map(|i| WpBitDef::to_tokens(self.bp.get(i)));
+        let srp = WpBitDef::to_tokens(self.srp0.as_ref());
+        let srl = WpBitDef::to_tokens(self.srp1.as_ref());
+        let cmp = WpBitDef::to_tokens(self.cmp.as_ref());
+        let sec = WpBitDef::to_tokens(self.sec.as_ref());
+        let tb = WpBitDef::to_tokens(self.tb.as_ref());
+
+        quote! {
+            crate::wp::WpRegBitMap {
+                srp: #srp,
+                srl: #srl,
+                cmp: #cmp,
+                sec: #sec,
+                tb: #tb,
+                bp: [#(#bp),*],
+                wps: crate::wp::RegBitInfo::not_present(),
+            }
+        }
+    }
+}
+
 /// Single chip definition in RON format
 #[derive(Debug, Clone, Deserialize)]
 pub struct ChipDef {
     /// Chip model name (e.g., "W25Q128FV")
     pub name: String,
+    /// Other names the chip is sold under (e.g., "W25Q128.V")
+    #[serde(default)]
+    pub aliases: Vec<String>,
     /// JEDEC device ID (2 bytes, e.g., 0x4018)
     pub device_id: u16,
     /// Total flash size
@@ -428,6 +638,15 @@ pub struct ChipDef {
     /// Test status
     #[serde(default)]
     pub tested: TestStatusDef,
+    /// Exempt this chip from the unique JEDEC ID check
+    ///
+    /// For variants that share an ID with an earlier entry and can only be
+    /// told apart by SFDP or other means.
+    #[serde(default)]
+    pub allow_duplicate_id: bool,
+    /// Write protection register layout
+    #[serde(default)]
+    pub wp_bits: Option<WpBitsDef>,
 }
 
 fn default_page_size() -> u16 {
@@ -506,9 +725,72 @@ impl ChipDatabase {
                         chip.name, total_size
                     )));
                 }
+
+                if let Some(wp_bits) = &chip.wp_bits {
+                    wp_bits.validate(&chip.name, &chip.features)?;
+                }
+            }
+        }
+
+        self.validate_unique_ids()?;
+        self.validate_unique_names()
+    }
+
+    /// Check that no two chips share a JEDEC ID unless they opt out
+    fn validate_unique_ids(&self) -> Result<(), Error> {
+        let mut ids: BTreeMap<(u8, u16), Vec<&str>> = BTreeMap::new();
+        for vendor in &self.vendors {
+            for chip in vendor.chips.iter().filter(|c| !c.allow_duplicate_id) {
+                ids.entry((vendor.manufacturer_id, chip.device_id))
+                    .or_default()
+                    .push(&chip.name);
+            }
+        }
+
+        let conflicts: Vec<String> = ids
+            .iter()
+            .filter(|(_, names)| names.len() > 1)
+            .map(|((mfr, dev), names)| format!("0x{:02X}/0x{:04X}: {}", mfr, dev, names.join(", ")))
+            .collect();
+
+        if !conflicts.is_empty() {
+            return Err(Error::Validation(format!(
+                "Duplicate JEDEC IDs (set allow_duplicate_id on intended variants): {}",
+                conflicts.join("; ")
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Check that every name and alias refers to a single chip
+    ///
+    /// Names are compared case-insensitively, matching
+    /// `ChipDatabase::find_by_name`.
+    fn validate_unique_names(&self) -> Result<(), Error> {
+        let mut names: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+        for chip in self.vendors.iter().flat_map(|v| &v.chips) {
+            for name in core::iter::once(&chip.name).chain(&chip.aliases) {
+                names
+                    .entry(name.to_ascii_lowercase())
+                    .or_default()
+                    .push(&chip.name);
             }
         }
 
+        let conflicts: Vec<String> = names
+            .iter()
+            .filter(|(_, chips)| chips.len() > 1)
+            .map(|(name, chips)| format!("{}: {}", name, chips.join(", ")))
+            .collect();
+
+        if !conflicts.is_empty() {
+            return Err(Error::Validation(format!(
+                "Duplicate chip names or aliases: {}",
+                conflicts.join("; ")
+            )));
+        }
+
         Ok(())
     }
 
@@ -516,10 +798,18 @@ impl ChipDatabase {
     pub fn generate_code(&self) -> String {
         let mut chip_defs = Vec::new();
 
-        for vendor in &self.vendors {
-            for chip in &vendor.chips {
-                // Generate erase blocks using constructors
-                let erase_blocks: Vec<_> = chip
+        // Sorted by JEDEC ID so lookups can binary search; the sort is
+        // stable, keeping chips that share an ID in definition order
+        let mut chips: Vec<_> = self
+            .vendors
+            .iter()
+            .flat_map(|vendor| vendor.chips.iter().map(move |chip| (vendor, chip)))
+            .collect();
+        chips.sort_by_key(|(vendor, chip)| (vendor.manufacturer_id, chip.device_id));
+
+        for (vendor, chip) in chips {
+            // Generate erase blocks using constructors
+            let erase_blocks: Vec<_> = chip
                     .erase_blocks
                     .iter()
                     .map(|eb| {
@@ -560,36 +850,52 @@ impl ChipDatabase {
                     })
                     .collect();
 
-                // Generate chip definition
-                let vendor_name = &vendor.vendor;
-                let chip_name = &chip.name;
-                let mfr_id = Literal::u8_unsuffixed(vendor.manufacturer_id);
-                let dev_id = Literal::u16_unsuffixed(chip.device_id);
-                let total_size = Literal::u32_unsuffixed(chip.total_size.to_bytes());
-                let page_size = Literal::u16_unsuffixed(chip.page_size);
-                let features = chip.features.to_tokens();
-                let voltage_min = Literal::u16_unsuffixed(chip.voltage.min);
-                let voltage_max = Literal::u16_unsuffixed(chip.voltage.max);
-                let write_gran = chip.write_granularity.to_tokens();
-                let tested = chip.tested.to_tokens();
-
-                chip_defs.push(quote! {
-                    FlashChip {
-                        vendor: #vendor_name.to_string(),
-                        name: #chip_name.to_string(),
-                        jedec_manufacturer: #mfr_id,
-                        jedec_device: #dev_id,
-                        total_size: #total_size,
-                        page_size: #page_size,
-                        features: #features,
-                        voltage_min_mv: #voltage_min,
-                        voltage_max_mv: #voltage_max,
-                        write_granularity: #write_gran,
-                        erase_blocks: vec![#(#erase_blocks),*],
-                        tested: #tested,
-                    }
-                });
-            }
+            // Generate chip definition
+            let vendor_name = &vendor.vendor;
+            let chip_name = &chip.name;
+            let aliases = &chip.aliases;
+            let mfr_id = Literal::u8_unsuffixed(vendor.manufacturer_id);
+            let dev_id = Literal::u16_unsuffixed(chip.device_id);
+            let total_size = Literal::u32_unsuffixed(chip.total_size.to_bytes());
+            let page_size = Literal::u16_unsuffixed(chip.page_size);
+            let features = chip.features.to_tokens();
+            let voltage_min = Literal::u16_unsuffixed(chip.voltage.min);
+            let voltage_max = Literal::u16_unsuffixed(chip.voltage.max);
+            let write_gran = chip.write_granularity.to_tokens();
+            let tested = chip.tested.to_tokens();
+            let wp_bits = chip.wp_bits.as_ref().map_or_else(
+                || quote!(None),
+                |wp_bits| {
+                    let map = wp_bits.to_tokens();
+                    quote!(Some(#map))
+                },
+            );
+            let wp_decoder = chip
+                .wp_bits
+                .as_ref()
+                .map(|wp_bits| wp_bits.decoder)
+                .unwrap_or_default()
+                .to_tokens();
+
+            chip_defs.push(quote! {
+                FlashChip {
+                    vendor: #vendor_name.to_string(),
+                    name: #chip_name.to_string(),
+                    aliases: vec![#(#aliases.to_string()),*],
+                    jedec_manufacturer: #mfr_id,
+                    jedec_device: #dev_id,
+                    total_size: #total_size,
+                    page_size: #page_size,
+                    features: #features,
+                    voltage_min_mv: #voltage_min,
+                    voltage_max_mv: #voltage_max,
+                    write_granularity: #write_gran,
+                    erase_blocks: vec![#(#erase_blocks),*],
+                    tested: #tested,
+                    wp_bits: #wp_bits,
+                    wp_decoder: #wp_decoder,
+                }
+            });
         }
 
         let tokens = quote! {
@@ -598,13 +904,21 @@ impl ChipDatabase {
 
             /// Static chip database
             ///
-            /// Generated from RON files in chips/vendors/
+            /// Generated from RON files in chips/vendors/, sorted by JEDEC ID.
             /// Lazily initialized on first access.
             pub static CHIPS: once_cell::sync::Lazy<Vec<FlashChip>> = once_cell::sync::Lazy::new(|| {
                 vec![
                     #(#chip_defs),*
                 ]
             });
+
+            /// Find all static chips with the given JEDEC ID
+            pub fn find_by_jedec_id(
+                manufacturer: u8,
+                device: u16,
+            ) -> impl Iterator<Item = &'static FlashChip> {
+                jedec_id_range(&CHIPS, manufacturer, device).iter()
+            }
         };
 
         // Format the output with prettyplease
@@ -731,6 +1045,141 @@ mod tests {
         assert_eq!(total, 128 * 1024);
     }
 
+    fn test_db(chips: &str) -> ChipDatabase {
+        let ron = format!(
+            "(vendor: \"Test\", manufacturer_id: 0xEF, chips: [{}])",
+            chips
+        );
+        ChipDatabase {
+            vendors: vec![ron::from_str(&ron).unwrap()],
+        }
+    }
+
+    #[test]
+    fn test_duplicate_jedec_id() {
+        let chip = |name: &str, extra: &str| {
+            format!(
+                "(name: \"{}\", device_id: 0x4018, total_size: MiB(16), {} \
+                 erase_blocks: [(opcode: 0xC7, regions: [(size: MiB(16), count: 1)])]),",
+                name, extra
+            )
+        };
+
+        let db = test_db(&(chip("A", "") + &chip("B", "")));
+        match db.validate() {
+            Err(Error::Validation(msg)) => {
+                assert!(msg.contains("0xEF/0x4018: A, B"), "{}", msg)
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+
+        let db = test_db(&(chip("A", "") + &chip("B", "allow_duplicate_id: true,")));
+        db.validate().unwrap();
+    }
+
+    #[test]
+    fn test_generated_chips_sorted() {
+        let chip = |name: &str, id: u16| {
+            format!(
+                "(name: \"{}\", device_id: 0x{:04X}, total_size: MiB(16), \
+                 erase_blocks: [(opcode: 0xC7, regions: [(size: MiB(16), count: 1)])]),",
+                name, id
+            )
+        };
+
+        let db = test_db(&(chip("C", 0x4018) + &chip("A", 0x4017) + &chip("B", 0x4018)));
+        let code = db.generate_code();
+        let pos = |name: &str| code.find(&format!("\"{}\"", name)).unwrap();
+        assert!(pos("A") < pos("C"));
+        assert!(pos("C") < pos("B"));
+    }
+
+    #[test]
+    fn test_duplicate_names() {
+        let chip = |name: &str, id: u16, extra: &str| {
+            format!(
+                "(name: \"{}\", device_id: 0x{:04X}, total_size: MiB(16), {} \
+                 erase_blocks: [(opcode: 0xC7, regions: [(size: MiB(16), count: 1)])]),",
+                name, id, extra
+            )
+        };
+
+        let db = test_db(
+            &(chip("W25Q128FV", 0x4018, "aliases: [\"W25Q128.V\"],")
+                + &chip("W25Q64FV", 0x4017, "")),
+        );
+        db.validate().unwrap();
+        assert!(db.generate_code().contains("\"W25Q128.V\""));
+
+        // Alias clashing with another chip's name, differing only in case
+        let db = test_db(
+            &(chip("W25Q128FV", 0x4018, "aliases: [\"w25q64fv\"],")
+                + &chip("W25Q64FV", 0x4017, "")),
+        );
+        match db.validate() {
+            Err(Error::Validation(msg)) => {
+                assert!(msg.contains("w25q64fv: W25Q128FV, W25Q64FV"), "{}", msg)
+            }
+            other => panic!("expected validation error, got {:?}", other),
+        }
+
+        let db = test_db(&(chip("A", 0x4018, "") + &chip("A", 0x4017, "")));
+        assert!(matches!(db.validate(), Err(Error::Validation(_))));
+    }
+
+    #[test]
+    fn test_wp_bits() {
+        let chip = |features: &str| {
+            format!(
+                "(name: \"A\", device_id: 0x4018, total_size: MiB(16), features: ({}), \
+                 erase_blocks: [(opcode: 0xC7, regions: [(size: MiB(16), count: 1)])], \
+                 wp_bits: Some((
+                     bp: [(reg: SR1, bit: 2), (reg: SR1, bit: 3), (reg: SR1, bit: 4)],
+                     tb: Some((reg: SR1, bit: 5)),
+                     cmp: Some((reg: SR2, bit: 6)),
+                     srp0: Some((reg: SR1, bit: 7)),
+                     srp1: Some((reg: SR2, bit: 0, access: Otp)),
+                 ))),",
+                features
+            )
+        };
+
+        // SR2 bits without the status_reg_2 feature
+        let db = test_db(&chip(""));
+        assert!(matches!(db.validate(), Err(Error::Validation(_))));
+
+        let db = test_db(&chip("status_reg_2: true"));
+        db.validate().unwrap();
+
+        let wp_bits = db.vendors[0].chips[0].wp_bits.as_ref().unwrap();
+        let s = wp_bits.to_tokens().to_string();
+        assert!(s.contains("Status2"));
+        assert!(s.contains("Otp"));
+        assert!(s.contains("not_present"));
+    }
+
+    #[test]
+    fn test_wp_bits_atmel() {
+        let chip = |bp: &str| {
+            format!(
+                "(name: \"A\", device_id: 0x4701, total_size: MiB(4), \
+                 erase_blocks: [(opcode: 0xC7, regions: [(size: MiB(4), count: 1)])], \
+                 wp_bits: Some((decoder: Atmel, bp: [{}], srp0: Some((reg: SR1, bit: 7))))),",
+                bp
+            )
+        };
+
+        let db = test_db(&chip("(reg: SR1, bit: 2), (reg: SR1, bit: 3)"));
+        db.validate().unwrap();
+        let wp_bits = db.vendors[0].chips[0].wp_bits.as_ref().unwrap();
+        assert_eq!(wp_bits.decoder, RangeDecoderDef::Atmel);
+        assert!(wp_bits.decoder.to_tokens().to_string().contains("Atmel"));
+
+        // The Atmel decoder reads exactly the two SWP bits
+        let db = test_db(&chip("(reg: SR1, bit: 2)"));
+        assert!(matches!(db.validate(), Err(Error::Validation(_))));
+    }
+
     #[test]
     fn test_size_conversion() {
         assert_eq!(Size::B(256).to_bytes(), 256);
@@ -14,6 +14,9 @@ use super::Features;
 use super::types::{
     ChipTestStatus, EraseBlock, EraseRegion, FlashChip, TestStatus, WriteGranularity,
 };
+use crate::wp::{
+    BitWritability, MAX_BP_BITS, RangeDecoder, RegBitInfo, StatusRegister, WpRegBitMap,
+};
 
 /// Error type for chip database operations
 #[derive(Debug, thiserror::Error)]
@@ -87,6 +90,8 @@ struct FeaturesDef {
     write_byte: bool,
     aai_word: bool,
     sst26_bpr: bool,
+    config_reg: bool,
+    function_reg: bool,
     status_reg_2: bool,
     status_reg_3: bool,
     qe_sr2: bool,
@@ -136,6 +141,8 @@ impl From<FeaturesDef> for Features {
             (def.write_byte, Features::WRITE_BYTE),
             (def.aai_word, Features::AAI_WORD),
             (def.sst26_bpr, Features::SST26_BPR),
+            (def.config_reg, Features::CONFIG_REG),
+            (def.function_reg, Features::FUNCTION_REG),
             (def.status_reg_2, Features::STATUS_REG_2),
             (def.status_reg_3, Features::STATUS_REG_3),
             (def.qe_sr2, Features::QE_SR2),
@@ -248,10 +255,96 @@ impl From<WriteGranularityDef> for WriteGranularity {
     }
 }
 
+/// Status register holding a write protection bit (RON format)
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+enum StatusRegDef {
+    SR1,
+    SR2,
+    SR3,
+}
+
+impl From<StatusRegDef> for StatusRegister {
+    fn from(def: StatusRegDef) -> Self {
+        match def {
+            StatusRegDef::SR1 => StatusRegister::Status1,
+            StatusRegDef::SR2 => StatusRegister::Status2,
+            StatusRegDef::SR3 => StatusRegister::Status3,
+        }
+    }
+}
+
+/// Writability of a write protection bit (RON format)
+#[derive(Debug, Clone, Copy, serde::Deserialize, Default)]
+enum BitAccessDef {
+    #[default]
+    ReadWrite,
+    ReadOnly,
+    Otp,
+}
+
+impl From<BitAccessDef> for BitWritability {
+    fn from(def: BitAccessDef) -> Self {
+        match def {
+            BitAccessDef::ReadWrite => BitWritability::ReadWrite,
+            BitAccessDef::ReadOnly => BitWritability::ReadOnly,
+            BitAccessDef::Otp => BitWritability::Otp,
+        }
+    }
+}
+
+/// Location of a single write protection bit (RON format)
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+struct WpBitDef {
+    reg: StatusRegDef,
+    bit: u8,
+    #[serde(default)]
+    access: BitAccessDef,
+}
+
+fn wp_bit(def: Option<WpBitDef>) -> RegBitInfo {
+    match def {
+        Some(def) => RegBitInfo::new(def.reg.into(), def.bit, def.access.into()),
+        None => RegBitInfo::not_present(),
+    }
+}
+
+/// Write protection register layout (RON format)
+#[derive(Debug, Clone, serde::Deserialize, Default)]
+#[serde(default)]
+struct WpBitsDef {
+    decoder: RangeDecoder,
+    bp: Vec<WpBitDef>,
+    tb: Option<WpBitDef>,
+    sec: Option<WpBitDef>,
+    cmp: Option<WpBitDef>,
+    srp0: Option<WpBitDef>,
+    srp1: Option<WpBitDef>,
+}
+
+impl From<WpBitsDef> for WpRegBitMap {
+    fn from(def: WpBitsDef) -> Self {
+        let mut bp = [RegBitInfo::not_present(); MAX_BP_BITS];
+        for (slot, bit) in bp.iter_mut().zip(def.bp) {
+            *slot = wp_bit(Some(bit));
+        }
+        WpRegBitMap {
+            srp: wp_bit(def.srp0),
+            srl: wp_bit(def.srp1),
+            cmp: wp_bit(def.cmp),
+            sec: wp_bit(def.sec),
+            tb: wp_bit(def.tb),
+            bp,
+            wps: RegBitInfo::not_present(),
+        }
+    }
+}
+
 /// Single chip definition in RON format
 #[derive(Debug, Clone, serde::Deserialize)]
 struct ChipDef {
     name: String,
+    #[serde(default)]
+    aliases: Vec<String>,
     device_id: u16,
     total_size: Size,
     #[serde(default = "default_page_size")]
@@ -265,6 +358,8 @@ struct ChipDef {
     erase_blocks: Vec<EraseBlockDef>,
     #[serde(default)]
     tested: TestStatusesDef,
+    #[serde(default)]
+    wp_bits: Option<WpBitsDef>,
 }
 
 fn default_page_size() -> u16 {
@@ -287,9 +382,18 @@ struct VendorDef {
 #[cfg(feature = "static-chips")]
 include!(concat!(env!("OUT_DIR"), "/chips_generated.rs"));
 
+/// Binary search a slice sorted by JEDEC ID for all chips matching one
+fn jedec_id_range(chips: &[FlashChip], manufacturer: u8, device: u16) -> &[FlashChip] {
+    let id = ((manufacturer as u32) << 16) | device as u32;
+    let start = chips.partition_point(|c| c.jedec_id() < id);
+    let end = start + chips[start..].partition_point(|c| c.jedec_id() == id);
+    &chips[start..end]
+}
+
 /// Runtime chip database
 ///
 /// Holds a collection of flash chip definitions that can be loaded from RON files.
+/// Chips are kept sorted by JEDEC ID, with chips sharing an ID in load order.
 #[derive(Debug, Clone, Default)]
 pub struct ChipDatabase {
     chips: Vec<FlashChip>,
@@ -334,6 +438,7 @@ impl ChipDatabase {
             let chip = FlashChip {
                 vendor: vendor_def.vendor.clone(),
                 name: chip_def.name,
+                aliases: chip_def.aliases,
                 jedec_manufacturer: vendor_def.manufacturer_id,
                 jedec_device: chip_def.device_id,
                 total_size: chip_def.total_size.to_bytes(),
@@ -355,9 +460,15 @@ impl ChipDatabase {
                     })
                     .collect(),
                 tested: chip_def.tested.into(),
+                wp_decoder: chip_def
+                    .wp_bits
+                    .as_ref()
+                    .map_or_else(RangeDecoder::default, |def| def.decoder),
+                wp_bits: chip_def.wp_bits.map(Into::into),
             };
             self.chips.push(chip);
         }
+        self.chips.sort_by_key(FlashChip::jedec_id);
 
         Ok(count)
     }
@@ -393,19 +504,32 @@ impl ChipDatabase {
         self.chips.is_empty()
     }
 
-    /// Find a chip by its JEDEC ID
-    pub fn find_by_jedec_id(&self, manufacturer: u8, device: u16) -> Option<&FlashChip> {
-        self.chips
-            .iter()
-            .find(|c| c.matches_jedec_id(manufacturer, device))
+    /// Find all chips with the given JEDEC ID
+    ///
+    /// Several chips can share an ID; SFDP can be used to tell them apart.
+    pub fn find_by_jedec_id(
+        &self,
+        manufacturer: u8,
+        device: u16,
+    ) -> impl Iterator<Item = &FlashChip> {
+        jedec_id_range(&self.chips, manufacturer, device).iter()
+    }
+
+    /// Find a chip by its name or one of its aliases (case-insensitive)
+    pub fn find_by_name(&self, name: &str) -> Option<&FlashChip> {
+        self.chips.iter().find(|c| c.matches_name(name))
     }
 
-    /// Find chips by name (case-insensitive partial match)
-    pub fn find_by_name(&self, name: &str) -> Vec<&FlashChip> {
+    /// Search chips by name or alias (case-insensitive partial match)
+    pub fn search_by_name(&self, name: &str) -> Vec<&FlashChip> {
         let name_lower = name.to_lowercase();
         self.chips
             .iter()
-            .filter(|c| c.name.to_lowercase().contains(&name_lower))
+            .filter(|c| {
+                core::iter::once(&c.name)
+                    .chain(&c.aliases)
+                    .any(|n| n.to_lowercase().contains(&name_lower))
+            })
             .collect()
     }
 
@@ -437,6 +561,7 @@ mod tests {
             chips: [
                 (
                     name: "W25Q128FV",
+                    aliases: ["W25Q128.V"],
                     device_id: 0x4018,
                     total_size: MiB(16),
                     page_size: 256,
@@ -465,12 +590,118 @@ mod tests {
         assert_eq!(count, 1);
         assert_eq!(db.len(), 1);
 
-        let chip = db.find_by_jedec_id(0xEF, 0x4018).unwrap();
+        let chip = db.find_by_jedec_id(0xEF, 0x4018).next().unwrap();
         assert_eq!(chip.name, "W25Q128FV");
         assert_eq!(chip.vendor, "Winbond");
         assert_eq!(chip.total_size, 16 * 1024 * 1024);
         assert!(chip.features.contains(Features::WRSR_WREN));
         assert!(chip.features.contains(Features::FAST_READ));
+
+        assert_eq!(db.find_by_name("w25q128.v").unwrap().name, "W25Q128FV");
+        assert_eq!(db.find_by_name("W25Q128FV").unwrap().name, "W25Q128FV");
+        assert!(db.find_by_name("W25Q128").is_none());
+        assert_eq!(db.search_by_name("q128.").len(), 1);
+    }
+
+    #[test]
+    fn test_load_wp_bits() {
+        let ron = r#"
+        (
+            vendor: "Test",
+            manufacturer_id: 0xEF,
+            chips: [
+                (
+                    name: "A",
+                    device_id: 0x4018,
+                    total_size: MiB(16),
+                    features: (status_reg_2: true),
+                    erase_blocks: [(opcode: 0xC7, regions: [(size: MiB(16), count: 1)])],
+                    wp_bits: Some((
+                        bp: [(reg: SR1, bit: 2), (reg: SR1, bit: 3)],
+                        srp1: Some((reg: SR2, bit: 0, access: Otp)),
+                    )),
+                ),
+                (
+                    name: "B",
+                    device_id: 0x4017,
+                    total_size: MiB(8),
+                    erase_blocks: [(opcode: 0xC7, regions: [(size: MiB(8), count: 1)])],
+                ),
+                (
+                    name: "C",
+                    device_id: 0x4701,
+                    total_size: MiB(4),
+                    erase_blocks: [(opcode: 0xC7, regions: [(size: MiB(4), count: 1)])],
+                    wp_bits: Some((
+                        decoder: Atmel,
+                        bp: [(reg: SR1, bit: 2), (reg: SR1, bit: 3)],
+                        srp0: Some((reg: SR1, bit: 7)),
+                    )),
+                ),
+            ],
+        )
+        "#;
+
+        let mut db = ChipDatabase::empty();
+        db.load_ron(ron).unwrap();
+
+        let map = db
+            .find_by_jedec_id(0xEF, 0x4018)
+            .next()
+            .unwrap()
+            .wp_bit_map();
+        assert_eq!(map.bp_count(), 2);
+        assert_eq!(map.srl.reg, Some(StatusRegister::Status2));
+        assert_eq!(map.srl.writability, BitWritability::Otp);
+        assert!(!map.tb.is_present());
+
+        let chip = db.find_by_jedec_id(0xEF, 0x4017).next().unwrap();
+        assert!(chip.wp_bits.is_none());
+        assert!(chip.wp_bit_map().tb.is_present());
+        assert_eq!(chip.wp_decoder, RangeDecoder::Spi25);
+
+        let chip = db.find_by_jedec_id(0xEF, 0x4701).next().unwrap();
+        assert_eq!(chip.wp_decoder, RangeDecoder::Atmel);
+        assert_eq!(chip.wp_bit_map().bp_count(), 2);
+    }
+
+    #[test]
+    fn test_find_by_jedec_id_shared() {
+        let ron = r#"
+        (
+            vendor: "Test",
+            manufacturer_id: 0xEF,
+            chips: [
+                (name: "C", device_id: 0x4018, total_size: MiB(16), erase_blocks: []),
+                (name: "A", device_id: 0x4017, total_size: MiB(8), erase_blocks: []),
+                (name: "B", device_id: 0x4018, total_size: MiB(16), erase_blocks: []),
+            ],
+        )
+        "#;
+
+        let mut db = ChipDatabase::empty();
+        db.load_ron(ron).unwrap();
+
+        let names: Vec<_> = db
+            .find_by_jedec_id(0xEF, 0x4018)
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["C", "B"]);
+        assert_eq!(db.find_by_jedec_id(0xEF, 0x4017).count(), 1);
+        assert_eq!(db.find_by_jedec_id(0xEF, 0x4016).count(), 0);
+        assert_eq!(db.find_by_jedec_id(0xC2, 0x4018).count(), 0);
+    }
+
+    #[cfg(feature = "static-chips")]
+    #[test]
+    fn test_static_chips_sorted() {
+        assert!(CHIPS.windows(2).all(|w| w[0].jedec_id() <= w[1].jedec_id()));
+
+        let chip = &CHIPS[CHIPS.len() / 2];
+        assert!(
+            find_by_jedec_id(chip.jedec_manufacturer, chip.jedec_device)
+                .any(|c| core::ptr::eq(c, chip))
+        );
     }
 
     #[test]
@@ -58,6 +58,12 @@ bitflags! {
         /// status register.  Set for all SST26VF/SST26WF series chips.
         const SST26_BPR       = 1 << 16;
 
+        // Vendor-specific configuration registers
+        /// Macronix configuration register (RDCR 0x15, written as the second WRSR byte)
+        const CONFIG_REG      = 1 << 17;
+        /// ISSI function register (RDFR 0x48 / WRFR 0x42)
+        const FUNCTION_REG    = 1 << 18;
+
         // Status register features
         /// Has status register 2
         const STATUS_REG_2    = 1 << 19;
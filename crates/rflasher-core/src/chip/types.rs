@@ -4,6 +4,8 @@
 use alloc::{string::String, vec::Vec};
 
 use super::features::Features;
+use crate::otp::OtpLayout;
+use crate::wp::{RangeDecoder, WpRegBitMap};
 
 /// Maximum number of erase regions per erase block (for no_std)
 pub const MAX_ERASE_REGIONS: usize = 8;
@@ -257,6 +259,9 @@ pub struct FlashChip {
     pub vendor: String,
     /// Chip model name (e.g., "W25Q128FV")
     pub name: String,
+    /// Other names the chip is sold under (e.g., "W25Q128.V")
+    #[cfg_attr(feature = "std", serde(default))]
+    pub aliases: Vec<String>,
     /// JEDEC manufacturer ID (first byte of RDID response)
     pub jedec_manufacturer: u8,
     /// JEDEC device ID (second and third bytes of RDID response)
@@ -282,6 +287,12 @@ pub struct FlashChip {
     /// Test status
     #[cfg_attr(feature = "std", serde(default))]
     pub tested: ChipTestStatus,
+    /// Write protection register layout (`None` for the Winbond default)
+    #[cfg_attr(feature = "std", serde(default))]
+    pub wp_bits: Option<WpRegBitMap>,
+    /// Write protection range decoding algorithm
+    #[cfg_attr(feature = "std", serde(default))]
+    pub wp_decoder: RangeDecoder,
 }
 
 #[cfg(feature = "std")]
@@ -304,6 +315,8 @@ pub struct FlashChip {
     pub vendor: &'static str,
     /// Chip model name (e.g., "W25Q128FV")
     pub name: &'static str,
+    /// Other names the chip is sold under (e.g., "W25Q128.V")
+    pub aliases: &'static [&'static str],
     /// JEDEC manufacturer ID (first byte of RDID response)
     pub jedec_manufacturer: u8,
     /// JEDEC device ID (second and third bytes of RDID response)
@@ -324,6 +337,10 @@ pub struct FlashChip {
     pub erase_blocks: &'static [EraseBlock],
     /// Test status
     pub tested: ChipTestStatus,
+    /// Write protection register layout (`None` for the Winbond default)
+    pub wp_bits: Option<WpRegBitMap>,
+    /// Write protection range decoding algorithm
+    pub wp_decoder: RangeDecoder,
 }
 
 impl FlashChip {
@@ -339,6 +356,16 @@ impl FlashChip {
         self.jedec_manufacturer == manufacturer && self.jedec_device == device
     }
 
+    /// Check if the chip's name or one of its aliases matches (case-insensitive)
+    #[must_use]
+    pub fn matches_name(&self, name: &str) -> bool {
+        self.name().eq_ignore_ascii_case(name)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    }
+
     /// Maximum addressable size with 3-byte addresses (16 MiB)
     const MAX_3BYTE_ADDR_SIZE: u32 = 16 * 1024 * 1024;
 
@@ -382,6 +409,27 @@ impl FlashChip {
         }
     }
 
+    /// Get the write protection register layout
+    ///
+    /// Chips without an explicit layout in the database use the standard
+    /// Winbond layout, extended with BP3 if the chip has the `WP_BP3`
+    /// feature.
+    pub fn wp_bit_map(&self) -> WpRegBitMap {
+        match self.wp_bits {
+            Some(map) => map,
+            None if self.features.contains(Features::WP_BP3) => WpRegBitMap::winbond_with_bp3(),
+            None => WpRegBitMap::winbond_standard(),
+        }
+    }
+
+    /// Get the security register layout, if the chip has OTP registers
+    /// with the Winbond-style command set
+    pub fn otp_layout(&self) -> Option<OtpLayout> {
+        self.features
+            .contains(Features::OTP | Features::SECURITY_REG)
+            .then(OtpLayout::winbond_standard)
+    }
+
     /// Get vendor name as a string slice
     #[cfg(feature = "alloc")]
     pub fn vendor(&self) -> &str {
@@ -1,6 +1,8 @@
 //! Flash context - runtime state for flash operations
 
 use crate::chip::FlashChip;
+use crate::protocol::{self, QuadEnableMethod};
+use crate::sfdp::BasicFlashParams;
 
 /// Address mode currently in use
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -12,6 +14,77 @@ pub enum AddressMode {
     FourByte,
 }
 
+/// Busy-wait timing for block erases
+///
+/// Each erase polls the status register until the chip is ready, giving up
+/// after a timeout that depends on the block size. The built-in timeouts
+/// cover typical chips; slow or worn parts may need them scaled up, and
+/// chips with SFDP publish their own worst-case erase times.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EraseTimeouts {
+    /// Multiplier applied to every erase timeout
+    pub scale: f32,
+    /// Maximum erase times published by the chip, as (block size, µs),
+    /// room for the four SFDP erase types and chip erase
+    published: [(u32, u32); 5],
+}
+
+impl Default for EraseTimeouts {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            published: [(0, 0); 5],
+        }
+    }
+}
+
+impl EraseTimeouts {
+    /// Record the chip's maximum erase time for a block size
+    ///
+    /// The timeout for that size becomes the larger of the built-in one and
+    /// `max_time_us`. At most five sizes are kept, extra ones are ignored.
+    pub fn set_published(&mut self, block_size: u32, max_time_us: u32) {
+        if block_size == 0 || max_time_us == 0 {
+            return;
+        }
+        if let Some(slot) = self
+            .published
+            .iter_mut()
+            .find(|(size, _)| *size == block_size || *size == 0)
+        {
+            *slot = (block_size, max_time_us);
+        }
+    }
+
+    /// Record the erase times from the chip's SFDP table
+    pub fn set_sfdp(&mut self, params: &BasicFlashParams) {
+        for erase_type in params.erase_types.iter().filter(|e| e.is_valid()) {
+            self.set_published(erase_type.size, erase_type.max_time_us);
+        }
+        if let Ok(chip_size) = u32::try_from(params.density_bytes) {
+            self.set_published(chip_size, params.chip_erase_max_time_us);
+        }
+    }
+
+    /// Poll delay and timeout in microseconds for erasing a block
+    pub fn for_block(&self, block_size: u32) -> (u32, u32) {
+        let (poll_delay_us, default_us) = match block_size {
+            s if s <= 4096 => (10_000, 1_000_000), // 4KB: 10ms poll, 1s timeout
+            s if s <= 65536 => (100_000, 4_000_000), // 32/64KB: 100ms poll, 4s timeout
+            _ => (500_000, 60_000_000),            // Larger: 500ms poll, 60s timeout
+        };
+        let published_us = self
+            .published
+            .iter()
+            .find(|(size, _)| *size == block_size)
+            .map_or(0, |(_, time)| *time);
+        let timeout_us = default_us.max(published_us);
+
+        // The float to int cast saturates, so huge scales can't wrap
+        (poll_delay_us, (timeout_us as f32 * self.scale) as u32)
+    }
+}
+
 /// Runtime context for flash operations
 ///
 /// This structure holds the state needed to interact with a specific
@@ -23,6 +96,10 @@ pub struct FlashContext {
     pub chip: FlashChip,
     /// Current address mode
     pub address_mode: AddressMode,
+    /// Erase busy-wait timing
+    pub erase_timeouts: EraseTimeouts,
+    /// Quad Enable method reported by SFDP, overriding the chip features
+    pub quad_enable: Option<QuadEnableMethod>,
 }
 
 /// Runtime context for flash operations (no_std version with static reference)
@@ -33,6 +110,10 @@ pub struct FlashContext {
     pub chip: &'static FlashChip,
     /// Current address mode
     pub address_mode: AddressMode,
+    /// Erase busy-wait timing
+    pub erase_timeouts: EraseTimeouts,
+    /// Quad Enable method reported by SFDP, overriding the chip features
+    pub quad_enable: Option<QuadEnableMethod>,
 }
 
 /// Shared methods for FlashContext that are identical across alloc/no_std.
@@ -61,6 +142,23 @@ macro_rules! impl_flash_context_common {
             let end = addr as u64 + len as u64;
             end <= self.chip.total_size as u64
         }
+
+        /// Take the Quad Enable method from the chip's SFDP table, if it
+        /// has one
+        pub fn set_sfdp_quad_enable(&mut self, params: &BasicFlashParams) {
+            if let Some(qer) = params.quad_enable {
+                self.quad_enable = Some(qer.into());
+            }
+        }
+
+        /// Get the method for setting the Quad Enable bit
+        ///
+        /// This is the one from SFDP if known, else the one described by
+        /// the chip features. `None` means the QE bit's location is unknown.
+        pub fn quad_enable_method(&self) -> Option<QuadEnableMethod> {
+            self.quad_enable
+                .or_else(|| protocol::quad_enable_method(self.chip.features))
+        }
     };
 }
 
@@ -74,7 +172,12 @@ impl FlashContext {
             AddressMode::ThreeByte
         };
 
-        Self { chip, address_mode }
+        Self {
+            chip,
+            address_mode,
+            erase_timeouts: EraseTimeouts::default(),
+            quad_enable: None,
+        }
     }
 
     impl_flash_context_common!();
@@ -90,8 +193,89 @@ impl FlashContext {
             AddressMode::ThreeByte
         };
 
-        Self { chip, address_mode }
+        Self {
+            chip,
+            address_mode,
+            erase_timeouts: EraseTimeouts::default(),
+            quad_enable: None,
+        }
     }
 
     impl_flash_context_common!();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_erase_timeouts() {
+        let mut timeouts = EraseTimeouts::default();
+        assert_eq!(timeouts.for_block(4096), (10_000, 1_000_000));
+        assert_eq!(timeouts.for_block(65536), (100_000, 4_000_000));
+
+        // Published times only ever extend the built-in timeout
+        timeouts.set_published(4096, 400_000);
+        timeouts.set_published(65536, 8_000_000);
+        assert_eq!(timeouts.for_block(4096), (10_000, 1_000_000));
+        assert_eq!(timeouts.for_block(65536), (100_000, 8_000_000));
+        assert_eq!(timeouts.for_block(32768), (100_000, 4_000_000));
+
+        timeouts.scale = 2.5;
+        assert_eq!(timeouts.for_block(4096), (10_000, 2_500_000));
+        assert_eq!(timeouts.for_block(65536), (100_000, 20_000_000));
+
+        timeouts.scale = 1e9;
+        assert_eq!(timeouts.for_block(4096).1, u32::MAX);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_sfdp_quad_enable_overrides_features() {
+        use crate::chip::{Features, WriteGranularity};
+        use crate::sfdp::QuadEnableRequirement;
+
+        let mut ctx = FlashContext::new(FlashChip {
+            vendor: "Macronix".into(),
+            name: "MX25L12835F".into(),
+            aliases: Default::default(),
+            jedec_manufacturer: 0xC2,
+            jedec_device: 0x2018,
+            total_size: 16 * 1024 * 1024,
+            page_size: 256,
+            features: Features::QE_SR2,
+            voltage_min_mv: 2700,
+            voltage_max_mv: 3600,
+            write_granularity: WriteGranularity::Page,
+            erase_blocks: Default::default(),
+            tested: Default::default(),
+            wp_bits: None,
+            wp_decoder: Default::default(),
+        });
+        assert_eq!(
+            ctx.quad_enable_method(),
+            Some(QuadEnableMethod::Sr2Bit1WriteSr)
+        );
+
+        // A BFPT without DWORD 15 doesn't say, so the features still apply
+        let mut params = BasicFlashParams::default();
+        ctx.set_sfdp_quad_enable(&params);
+        assert_eq!(
+            ctx.quad_enable_method(),
+            Some(QuadEnableMethod::Sr2Bit1WriteSr)
+        );
+
+        params.quad_enable = Some(QuadEnableRequirement::from_bfpt(0b010));
+        ctx.set_sfdp_quad_enable(&params);
+        assert_eq!(ctx.quad_enable_method(), Some(QuadEnableMethod::Sr1Bit6));
+
+        params.quad_enable = Some(QuadEnableRequirement::from_bfpt(0b011));
+        ctx.set_sfdp_quad_enable(&params);
+        assert_eq!(ctx.quad_enable_method(), Some(QuadEnableMethod::Sr2Bit7));
+
+        // Without QE_SR2 or SFDP, the QE location is unknown
+        ctx.quad_enable = None;
+        ctx.chip.features = Features::empty();
+        assert_eq!(ctx.quad_enable_method(), None);
+    }
+}
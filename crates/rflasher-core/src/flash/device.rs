@@ -7,7 +7,8 @@
 //! Uses `maybe_async` to support both sync and async modes.
 
 use crate::chip::{EraseBlock, WriteGranularity};
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::otp::OtpLayout;
 #[cfg(feature = "alloc")]
 use crate::wp::{WpConfig, WpError, WpMode, WpRange, WpResult, WriteOptions};
 use maybe_async::maybe_async;
@@ -28,6 +29,11 @@ use maybe_async::maybe_async;
 /// `WpError::ChipUnsupported`. SPI-based devices override these to provide
 /// actual WP functionality.
 ///
+/// # Security Registers
+///
+/// OTP security register access is optional as well. The default
+/// implementations return `Error::ChipNotSupported`.
+///
 /// # Example
 ///
 /// ```ignore
@@ -122,6 +128,20 @@ pub trait FlashDevice {
     /// * `EraseError` - If the erase operation fails
     async fn erase(&mut self, addr: u32, len: u32) -> Result<()>;
 
+    /// Erase the whole flash
+    ///
+    /// SPI devices override this to issue a single chip erase command (CE)
+    /// and check that the flash reads back erased, which is much faster
+    /// than a sectored erase on some programmers. The default erases the
+    /// whole device block by block with [`Self::erase`].
+    ///
+    /// # Errors
+    /// * `EraseError` - If the erase operation fails
+    async fn chip_erase(&mut self) -> Result<()> {
+        log::info!("Erasing the whole flash block by block");
+        self.erase(0, self.size()).await
+    }
+
     /// Check if a range is valid for this device
     ///
     /// Uses u64 arithmetic to avoid truncation when `len > u32::MAX`.
@@ -180,6 +200,44 @@ pub trait FlashDevice {
     fn get_available_wp_ranges(&self) -> alloc::vec::Vec<WpRange> {
         alloc::vec::Vec::new()
     }
+
+    // =========================================================================
+    // Security registers (optional, default implementations return unsupported)
+    // =========================================================================
+
+    /// Get the security register layout, if the device has OTP registers
+    fn otp_layout(&self) -> Option<OtpLayout> {
+        None
+    }
+
+    /// Read from security register `register` (numbered from 1)
+    async fn read_otp(&mut self, _register: u8, _offset: u32, _buf: &mut [u8]) -> Result<()> {
+        Err(Error::ChipNotSupported)
+    }
+
+    /// Program security register `register`
+    ///
+    /// The target bytes should be erased first with [`Self::erase_otp`].
+    async fn write_otp(&mut self, _register: u8, _offset: u32, _data: &[u8]) -> Result<()> {
+        Err(Error::ChipNotSupported)
+    }
+
+    /// Erase security register `register`
+    async fn erase_otp(&mut self, _register: u8) -> Result<()> {
+        Err(Error::ChipNotSupported)
+    }
+
+    /// Check whether security register `register` is locked
+    async fn is_otp_locked(&mut self, _register: u8) -> Result<bool> {
+        Err(Error::ChipNotSupported)
+    }
+
+    /// Permanently lock security register `register`
+    ///
+    /// This is irreversible.
+    async fn lock_otp(&mut self, _register: u8) -> Result<()> {
+        Err(Error::ChipNotSupported)
+    }
 }
 
 /// Extension trait for FlashDevice that provides additional capabilities
@@ -238,6 +296,10 @@ impl FlashDevice for alloc::boxed::Box<dyn FlashDevice + Send> {
         (**self).erase(addr, len)
     }
 
+    fn chip_erase(&mut self) -> Result<()> {
+        (**self).chip_erase()
+    }
+
     fn is_valid_range(&self, addr: u32, len: usize) -> bool {
         (**self).is_valid_range(addr, len)
     }
@@ -269,4 +331,28 @@ impl FlashDevice for alloc::boxed::Box<dyn FlashDevice + Send> {
     fn get_available_wp_ranges(&self) -> alloc::vec::Vec<WpRange> {
         (**self).get_available_wp_ranges()
     }
+
+    fn otp_layout(&self) -> Option<OtpLayout> {
+        (**self).otp_layout()
+    }
+
+    fn read_otp(&mut self, register: u8, offset: u32, buf: &mut [u8]) -> Result<()> {
+        (**self).read_otp(register, offset, buf)
+    }
+
+    fn write_otp(&mut self, register: u8, offset: u32, data: &[u8]) -> Result<()> {
+        (**self).write_otp(register, offset, data)
+    }
+
+    fn erase_otp(&mut self, register: u8) -> Result<()> {
+        (**self).erase_otp(register)
+    }
+
+    fn is_otp_locked(&mut self, register: u8) -> Result<bool> {
+        (**self).is_otp_locked(register)
+    }
+
+    fn lock_otp(&mut self, register: u8) -> Result<()> {
+        (**self).lock_otp(register)
+    }
 }
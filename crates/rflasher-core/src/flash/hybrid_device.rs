@@ -16,6 +16,7 @@
 //!   FlashDevice::read()  ──► OpaqueMaster::read()   (CMD_READ + bulk IN)
 //!   FlashDevice::write() ──► OpaqueMaster::write()   (CMD_WRITE + bulk OUT)
 //!   FlashDevice::erase() ──► SpiMaster (WREN + SE/BE + RDSR polling)
+//!   FlashDevice::chip_erase() ──► SpiMaster (WREN + CE + RDSR polling)
 //!   FlashDevice::wp_*()  ──► SpiMaster (status register access)
 //! ```
 
@@ -23,7 +24,10 @@ use crate::chip::{EraseBlock, WriteGranularity};
 use crate::error::{Error, Result};
 use crate::flash::context::{AddressMode, FlashContext};
 use crate::flash::device::FlashDevice;
-use crate::flash::operations::{addressing_for_4byte_operation, select_erase_block};
+use crate::flash::operations::{
+    addressing_for_4byte_operation, check_erased_range, chip_erase_opcode, select_erase_block,
+};
+use crate::otp::{self, OtpLayout};
 use crate::programmer::{OpaqueMaster, SpiFeatures, SpiMaster};
 use crate::protocol::{self, CommandAddressing};
 #[cfg(feature = "alloc")]
@@ -40,13 +44,15 @@ use maybe_async::maybe_async;
 /// # Example
 ///
 /// ```ignore
-/// use rflasher_core::flash::{HybridFlashDevice, probe};
+/// use rflasher_core::flash::{HybridFlashDevice, probe, read_io_mode};
 /// use rflasher_core::chip::ChipDatabase;
 /// use rflasher_dediprog::Dediprog;
 ///
 /// let mut master = Dediprog::open().unwrap();
 /// let ctx = probe(&mut master, &db).unwrap();
 /// master.set_flash_size(ctx.total_size() as u32);
+/// let mode = read_io_mode(&mut master, &ctx);
+/// master.set_read_io_mode(mode);
 /// let mut device = HybridFlashDevice::new(master, ctx);
 /// ```
 pub struct HybridFlashDevice<M: SpiMaster + OpaqueMaster> {
@@ -150,6 +156,41 @@ impl<M: SpiMaster + OpaqueMaster> FlashDevice for HybridFlashDevice<M> {
         HybridFlashDevice::get_available_wp_ranges(self)
     }
 
+    // Security register support (delegates to SpiMaster)
+    fn otp_layout(&self) -> Option<OtpLayout> {
+        self.ctx.chip.otp_layout()
+    }
+
+    async fn read_otp(&mut self, register: u8, offset: u32, buf: &mut [u8]) -> Result<()> {
+        let layout = self.otp_layout().ok_or(Error::ChipNotSupported)?;
+        otp::read_security_register(&mut self.master, &layout, register, offset, buf).await
+    }
+
+    async fn write_otp(&mut self, register: u8, offset: u32, data: &[u8]) -> Result<()> {
+        let layout = self.otp_layout().ok_or(Error::ChipNotSupported)?;
+        otp::program_security_register(&mut self.master, &layout, register, offset, data).await
+    }
+
+    async fn erase_otp(&mut self, register: u8) -> Result<()> {
+        let layout = self.otp_layout().ok_or(Error::ChipNotSupported)?;
+        otp::erase_security_register(&mut self.master, &layout, register).await
+    }
+
+    async fn is_otp_locked(&mut self, register: u8) -> Result<bool> {
+        let layout = self.otp_layout().ok_or(Error::ChipNotSupported)?;
+        otp::is_security_register_locked(&mut self.master, &layout, register).await
+    }
+
+    async fn lock_otp(&mut self, register: u8) -> Result<()> {
+        let layout = self.otp_layout().ok_or(Error::ChipNotSupported)?;
+        let use_ewsr = self
+            .ctx
+            .chip
+            .features
+            .contains(crate::chip::Features::WRSR_EWSR);
+        otp::lock_security_register(&mut self.master, &layout, register, use_ewsr).await
+    }
+
     // =========================================================================
     // Read/Write: use OpaqueMaster (fast bulk path)
     // =========================================================================
@@ -206,6 +247,8 @@ impl<M: SpiMaster + OpaqueMaster> FlashDevice for HybridFlashDevice<M> {
             .ok_or(Error::InvalidAlignment)?;
 
         let chip_features = ctx.chip.features;
+        let max_block_size = erase_block.max_block_size();
+        let (poll_delay_us, timeout_us) = ctx.erase_timeouts.for_block(max_block_size);
         let use_4byte = ctx.address_mode == AddressMode::FourByte;
         let master_features = self.master.features();
         let use_native = use_4byte
@@ -226,14 +269,6 @@ impl<M: SpiMaster + OpaqueMaster> FlashDevice for HybridFlashDevice<M> {
 
         let mut current_addr = addr;
         let end_addr = addr + len;
-        let max_block_size = erase_block.max_block_size();
-
-        let (poll_delay_us, timeout_us) = match max_block_size {
-            s if s <= 4096 => (10_000, 1_000_000),
-            s if s <= 32768 => (100_000, 4_000_000),
-            s if s <= 65536 => (100_000, 4_000_000),
-            _ => (500_000, 60_000_000),
-        };
 
         while current_addr < end_addr {
             let offset_in_layout = current_addr - addr;
@@ -268,6 +303,22 @@ impl<M: SpiMaster + OpaqueMaster> FlashDevice for HybridFlashDevice<M> {
 
         Ok(())
     }
+
+    async fn chip_erase(&mut self) -> Result<()> {
+        let size = self.size();
+        let ctx = self.context();
+        let opcode = chip_erase_opcode(ctx.chip.erase_blocks(), size)
+            .filter(|&opcode| self.master.probe_opcode(opcode));
+        let Some(opcode) = opcode else {
+            log::info!("No usable chip erase opcode, erasing block by block");
+            return FlashDevice::erase(self, 0, size).await;
+        };
+
+        let (_, timeout_us) = self.context().erase_timeouts.for_block(size);
+        log::info!("Erasing the whole chip with chip erase (0x{:02X})", opcode);
+        protocol::chip_erase(self.master(), opcode, timeout_us).await?;
+        check_erased_range(self, 0, size).await
+    }
 }
 
 // =============================================================================
@@ -277,16 +328,11 @@ impl<M: SpiMaster + OpaqueMaster> FlashDevice for HybridFlashDevice<M> {
 #[cfg(feature = "alloc")]
 impl<M: SpiMaster + OpaqueMaster> HybridFlashDevice<M> {
     fn wp_bit_map(&self) -> WpRegBitMap {
-        let features = self.ctx.chip.features;
-        if features.contains(crate::chip::Features::WP_BP3) {
-            WpRegBitMap::winbond_with_bp3()
-        } else {
-            WpRegBitMap::winbond_standard()
-        }
+        self.ctx.chip.wp_bit_map()
     }
 
     fn wp_decoder(&self) -> RangeDecoder {
-        RangeDecoder::Spi25
+        self.ctx.chip.wp_decoder
     }
 
     /// Read current write protection bits
@@ -361,7 +407,8 @@ impl<M: SpiMaster + OpaqueMaster> HybridFlashDevice<M> {
     #[maybe_async]
     pub async fn disable_wp(&mut self, options: WriteOptions) -> WpResult<()> {
         let bit_map = self.wp_bit_map();
-        wp::disable_wp(&mut self.master, &bit_map, options).await
+        let decoder = self.wp_decoder();
+        wp::disable_wp(&mut self.master, &bit_map, decoder, options).await
     }
 
     /// Get all available protection ranges
@@ -45,18 +45,20 @@ mod spi_device;
 #[cfg(feature = "alloc")]
 pub mod unified;
 
-pub use context::FlashContext;
+pub use context::{EraseTimeouts, FlashContext};
 pub use device::FlashDevice;
 #[cfg(feature = "alloc")]
 pub use device::FlashDeviceExt;
 pub use hybrid_device::HybridFlashDevice;
 pub use opaque_device::OpaqueFlashDevice;
 pub use spi_device::SpiFlashDevice;
+#[cfg(feature = "alloc")]
+pub use unified::is_blank;
 
 // Re-export low-level SPI operations (work with SpiMaster directly)
 // For high-level operations that work with any FlashDevice, use the `unified` module
-pub use operations::{read, select_erase_block, write};
+pub use operations::{read, read_io_mode, select_erase_block, write};
 
 // Re-export detailed probe result
 #[cfg(feature = "std")]
-pub use operations::{ProbeResult, probe_detailed};
+pub use operations::{ProbeResult, force_probe, probe_detailed, probe_forced};
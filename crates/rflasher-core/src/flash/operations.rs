@@ -12,12 +12,13 @@ use crate::chip::ChipDatabase;
 #[cfg(feature = "alloc")]
 use crate::chip::WriteGranularity;
 use crate::chip::{EraseBlock, Features};
-use crate::error::{Error, Result};
+use crate::error::{EraseFailure, Error, Result};
 use crate::programmer::{SpiFeatures, SpiMaster};
 use crate::protocol::{self, CommandAddressing};
 use maybe_async::maybe_async;
 
 use super::context::{AddressMode, FlashContext};
+use super::device::FlashDevice;
 
 pub(crate) fn compatible_4byte_addressing(
     chip_features: Features,
@@ -55,8 +56,15 @@ pub(crate) fn addressing_for_4byte_operation(
     }
 }
 
-pub(crate) fn read_dummy_cycles(io_mode: crate::spi::IoMode) -> u8 {
+pub(crate) fn read_dummy_cycles(io_mode: crate::spi::IoMode, opcode: u8) -> u8 {
+    use crate::spi::opcodes;
+
     match io_mode {
+        crate::spi::IoMode::Single
+            if matches!(opcode, opcodes::FAST_READ | opcodes::FAST_READ_4B) =>
+        {
+            8
+        }
         crate::spi::IoMode::Single => 0,
         crate::spi::IoMode::DualOut => 8,
         crate::spi::IoMode::DualIo => 4,
@@ -66,6 +74,23 @@ pub(crate) fn read_dummy_cycles(io_mode: crate::spi::IoMode) -> u8 {
     }
 }
 
+/// Length of the next page program chunk starting at `addr`
+///
+/// Chunks never cross a page boundary and never exceed the master's
+/// `max_write_len()`, so programmers with buffers smaller than a page
+/// (e.g. Intel swseq with 64 bytes) are never overrun. A master limit of
+/// zero is treated as one byte so the write loop always makes progress.
+pub(crate) fn program_chunk_len(
+    addr: u32,
+    remaining: usize,
+    page_size: usize,
+    max_write: usize,
+) -> usize {
+    let page_size = page_size.max(1);
+    let bytes_to_page_end = page_size - (addr as usize) % page_size;
+    bytes_to_page_end.min(remaining).min(max_write.max(1))
+}
+
 // =============================================================================
 // Smart erase/write support
 // =============================================================================
@@ -706,19 +731,99 @@ impl ProbeResult {
         })
     }
 
+    /// Check if SFDP and the database disagree on the erase blocks
+    pub fn has_erase_mismatches(&self) -> bool {
+        self.mismatches.iter().any(|m| m.is_erase_block())
+    }
+
+    /// The chip with the erase blocks SFDP reports instead of the database's
+    ///
+    /// For chip revisions whose database entry is stale. SFDP doesn't
+    /// describe chip erase, so the database's chip erase entries are kept.
+    /// Returns `None` without SFDP data.
+    pub fn chip_with_sfdp_erase_blocks(&self) -> Option<crate::chip::FlashChip> {
+        let sfdp = self.sfdp.as_ref()?;
+        let mut chip = self.chip.clone();
+        let chip_erase = self
+            .chip
+            .erase_blocks
+            .iter()
+            .filter(|eb| eb.is_chip_erase())
+            .cloned();
+        chip.erase_blocks = crate::sfdp::sfdp_erase_blocks(sfdp);
+        chip.erase_blocks.extend(chip_erase);
+        Some(chip)
+    }
+
     /// Create a FlashContext from this probe result
+    ///
+    /// See [`ProbeResult::context_for`].
     pub fn into_context(self) -> FlashContext {
-        FlashContext::new(self.chip)
+        let chip = self.chip.clone();
+        self.context_for(chip)
+    }
+
+    /// Create a FlashContext for `chip` using this probe result's SFDP data
+    ///
+    /// Erase timeouts are extended to the SFDP erase times if there are any,
+    /// and the Quad Enable method is taken from SFDP if it reports one.
+    pub fn context_for(&self, chip: crate::chip::FlashChip) -> FlashContext {
+        let mut ctx = FlashContext::new(chip);
+        if let Some(sfdp) = &self.sfdp {
+            ctx.erase_timeouts.set_sfdp(&sfdp.basic_params);
+            ctx.set_sfdp_quad_enable(&sfdp.basic_params);
+            if let Some(method) = ctx.quad_enable {
+                log::debug!("Quad Enable from SFDP: {}", method);
+            }
+        }
+        ctx
+    }
+}
+
+/// Identify a chip that is in QPI mode and switch it back to SPI mode
+///
+/// Returns the JEDEC ID read on a single line after leaving QPI mode, or
+/// `None` if the chip doesn't answer in QPI mode either.
+#[cfg(feature = "std")]
+#[maybe_async]
+async fn probe_qpi_mode<M: SpiMaster + ?Sized>(master: &mut M) -> Result<Option<(u8, u16)>> {
+    log::debug!("No response to RDID, trying RDID in QPI mode");
+
+    let Some((manufacturer, device)) = protocol::read_jedec_id_qpi(master).await? else {
+        return Ok(None);
+    };
+
+    log::info!(
+        "Chip answered in QPI mode (JEDEC {:02X}:{:04X}), switching it to SPI mode",
+        manufacturer,
+        device
+    );
+    protocol::exit_qpi(master).await?;
+
+    // Everything after probing uses single-line commands, so make sure the
+    // chip really left QPI mode
+    let id = protocol::read_jedec_id(master).await?;
+    if id != (manufacturer, device) {
+        log::warn!(
+            "Chip did not leave QPI mode (RDID now returns {:02X}:{:04X})",
+            id.0,
+            id.1
+        );
+        return Err(Error::ChipNotSupported);
     }
+
+    Ok(Some(id))
 }
 
 /// Probe for a flash chip with detailed results
 ///
 /// This function performs comprehensive probing:
-/// 1. Reads JEDEC ID
-/// 2. Probes SFDP (if supported)
-/// 3. Looks up in database
-/// 4. Compares SFDP with database (if both available)
+/// 1. Waits for any operation interrupted in a previous session to finish
+/// 2. Reads JEDEC ID, retrying in QPI mode if the programmer supports it
+///    and the chip doesn't answer on a single line
+/// 3. Probes SFDP (if supported)
+/// 4. Looks up in database
+/// 5. Compares SFDP with database (if both available)
 ///
 /// Returns detailed information about what was found, allowing the caller
 /// to decide how to handle mismatches or unknown chips.
@@ -728,7 +833,24 @@ pub async fn probe_detailed<M: SpiMaster + ?Sized>(
     master: &mut M,
     db: &ChipDatabase,
 ) -> Result<ProbeResult> {
-    let (jedec_manufacturer, jedec_device) = protocol::read_jedec_id(master).await?;
+    // The chip ignores RDID while busy, so settle any leftover erase first
+    protocol::wait_ready_on_open(master).await;
+
+    let (mut jedec_manufacturer, mut jedec_device) = protocol::read_jedec_id(master).await?;
+
+    // Some chips power up in QPI mode and ignore single-line RDID
+    if !protocol::is_valid_jedec_manufacturer(jedec_manufacturer)
+        && master.features().contains(SpiFeatures::QPI)
+        && let Some(id) = probe_qpi_mode(master).await?
+    {
+        (jedec_manufacturer, jedec_device) = id;
+    }
+
+    if !protocol::is_valid_jedec_manufacturer(jedec_manufacturer) {
+        log::info!(
+            "No valid JEDEC ID; a chip left in deep power-down only answers after a release (0xAB)"
+        );
+    }
 
     log::info!(
         "JEDEC ID: manufacturer=0x{:02X}, device=0x{:04X}",
@@ -755,8 +877,12 @@ pub async fn probe_detailed<M: SpiMaster + ?Sized>(
     };
 
     // Look up in database
-    let db_chip = db.find_by_jedec_id(jedec_manufacturer, jedec_device);
-    if db_chip.is_some() {
+    let candidates: Vec<_> = db
+        .find_by_jedec_id(jedec_manufacturer, jedec_device)
+        .collect();
+    if candidates.len() > 1 {
+        log::debug!("{} chips in database share this JEDEC ID", candidates.len());
+    } else if !candidates.is_empty() {
         log::debug!("Chip found in database");
     } else {
         log::info!(
@@ -766,11 +892,24 @@ pub async fn probe_detailed<M: SpiMaster + ?Sized>(
         );
     }
 
+    resolve_probe(jedec_manufacturer, jedec_device, &candidates, sfdp)
+}
+
+/// Pick the chip for a probed ID from its database candidates and SFDP
+#[cfg(feature = "std")]
+fn resolve_probe(
+    jedec_manufacturer: u8,
+    jedec_device: u16,
+    candidates: &[&crate::chip::FlashChip],
+    sfdp: Option<crate::sfdp::SfdpInfo>,
+) -> Result<ProbeResult> {
     // Determine the chip to use and collect mismatches
-    let (chip, from_database, mismatches) = match (&db_chip, &sfdp) {
-        (Some(db), Some(sfdp_info)) => {
-            let mismatches = crate::sfdp::compare_with_chip(sfdp_info, db);
-            ((*db).clone(), true, mismatches)
+    let (chip, from_database, mismatches) = match (candidates.first(), &sfdp) {
+        (Some(_), Some(sfdp_info)) => {
+            // SFDP picks among chips sharing the JEDEC ID
+            let (db, mismatches) =
+                crate::sfdp::best_match(sfdp_info, candidates.iter().copied()).unwrap();
+            (db.clone(), true, mismatches)
         }
         (Some(db), None) => ((*db).clone(), true, Vec::new()),
         (None, Some(sfdp_info)) => {
@@ -790,6 +929,240 @@ pub async fn probe_detailed<M: SpiMaster + ?Sized>(
     })
 }
 
+/// Probe a chip that doesn't answer a plain RDID, for emergency recovery
+///
+/// Tries each identification method in turn and logs the one that worked:
+/// 1. RDID (0x9F), also in QPI mode if the programmer supports it
+/// 2. REMS (0x90), matched against the database by manufacturer and
+///    capacity, since the legacy device ID is usually the JEDEC capacity
+///    byte minus one
+/// 3. RES (0xAB), which also releases deep power-down, then RDID again
+/// 4. SFDP alone, which gives the size and erase layout but no ID
+///
+/// A method failing with an error, e.g. an opcode the programmer can't
+/// send, moves on to the next one. Only when all of them have failed is
+/// the last error returned.
+///
+/// A chip matched by REMS may be another part of the same vendor and size,
+/// so check the result before writing.
+#[cfg(feature = "std")]
+#[maybe_async]
+pub async fn force_probe<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    db: &ChipDatabase,
+) -> Result<ProbeResult> {
+    // A chip identified by SFDP only, kept in case nothing else works
+    let mut sfdp_only = None;
+    // Each method's failure is only logged, the last one is returned if
+    // none of them works
+    let mut last_error = Error::ChipNotFound;
+
+    match probe_detailed(master, db).await {
+        Ok(result) if protocol::is_valid_jedec_manufacturer(result.jedec_manufacturer) => {
+            log::info!("Chip identified by RDID (0x9F)");
+            return Ok(result);
+        }
+        Ok(result) => sfdp_only = Some(result),
+        Err(e) => {
+            log::debug!("RDID (0x9F) probe failed: {:?}", e);
+            last_error = e;
+        }
+    }
+
+    match probe_rems(master, db).await {
+        Ok(result) => {
+            log::info!(
+                "Chip identified by REMS (0x90) as {} {}",
+                result.chip.vendor,
+                result.chip.name
+            );
+            return Ok(result);
+        }
+        Err(e) => {
+            log::debug!("REMS (0x90) probe failed: {:?}", e);
+            last_error = e;
+        }
+    }
+
+    match protocol::release_power_down(master).await {
+        Ok(signature) => log::debug!("RES: electronic signature 0x{:02X}", signature),
+        Err(e) => {
+            log::debug!("RES (0xAB) failed: {:?}", e);
+            last_error = e;
+        }
+    }
+    match probe_detailed(master, db).await {
+        Ok(result) if protocol::is_valid_jedec_manufacturer(result.jedec_manufacturer) => {
+            log::info!("Chip identified by RDID (0x9F) after release from deep power-down (0xAB)");
+            return Ok(result);
+        }
+        Ok(result) => sfdp_only = sfdp_only.or(Some(result)),
+        Err(e) => {
+            log::debug!("RDID (0x9F) probe after RES (0xAB) failed: {:?}", e);
+            last_error = e;
+        }
+    }
+
+    let result = sfdp_only.ok_or(last_error)?;
+    log::info!(
+        "Chip identified by SFDP only ({} bytes), without a JEDEC ID",
+        result.chip.total_size
+    );
+    Ok(result)
+}
+
+/// Identify a chip by its REMS (0x90) ID for [`force_probe`]
+///
+/// Fails with `ChipNotFound` if the ID matches no chip in the database.
+#[cfg(feature = "std")]
+#[maybe_async]
+async fn probe_rems<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    db: &ChipDatabase,
+) -> Result<ProbeResult> {
+    let (manufacturer, device) = protocol::read_rems(master).await?;
+    log::debug!(
+        "REMS: manufacturer=0x{:02X}, device=0x{:02X}",
+        manufacturer,
+        device
+    );
+    if !protocol::is_valid_jedec_manufacturer(manufacturer) || device == 0xFF {
+        return Err(Error::ChipNotFound);
+    }
+
+    let candidates: Vec<_> = db
+        .iter()
+        .filter(|chip| {
+            chip.jedec_manufacturer == manufacturer && chip.jedec_device & 0xFF == device as u16 + 1
+        })
+        .collect();
+    if candidates.is_empty() {
+        log::info!(
+            "REMS ID {:02X}:{:02X} matches no chip in the database",
+            manufacturer,
+            device
+        );
+        return Err(Error::ChipNotFound);
+    }
+
+    let sfdp = crate::sfdp::probe(master).await.ok();
+    resolve_probe(manufacturer, device as u16, &candidates, sfdp)
+}
+
+/// Probe using a chip definition chosen by the caller
+///
+/// For chips with a missing or generic JEDEC ID, or one shared with other
+/// chips, the caller picks the definition (e.g. by name) instead of looking
+/// it up. The JEDEC ID is still read, and a mismatch with the chip's
+/// declared ID only logged, and SFDP is probed so it can be compared.
+#[cfg(feature = "std")]
+#[maybe_async]
+pub async fn probe_forced<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    chip: &crate::chip::FlashChip,
+) -> Result<ProbeResult> {
+    protocol::wait_ready_on_open(master).await;
+
+    let (jedec_manufacturer, jedec_device) = protocol::read_jedec_id(master).await?;
+    if !chip.matches_jedec_id(jedec_manufacturer, jedec_device) {
+        log::warn!(
+            "JEDEC ID {:02X}:{:04X} does not match {} ({:02X}:{:04X}), using it anyway",
+            jedec_manufacturer,
+            jedec_device,
+            chip.name,
+            chip.jedec_manufacturer,
+            chip.jedec_device
+        );
+    }
+
+    let sfdp = match crate::sfdp::probe(master).await {
+        Ok(info) => Some(info),
+        Err(e) => {
+            log::debug!("SFDP probe failed: {:?}", e);
+            None
+        }
+    };
+    let mismatches = sfdp
+        .as_ref()
+        .map(|info| crate::sfdp::compare_with_chip(info, chip))
+        .unwrap_or_default();
+
+    Ok(ProbeResult {
+        jedec_manufacturer,
+        jedec_device,
+        chip: chip.clone(),
+        from_database: true,
+        sfdp,
+        mismatches,
+    })
+}
+
+/// Select the fastest read mode supported by both the programmer and the chip
+///
+/// Quad modes on chips with a Quad Enable bit only work once it is set, so
+/// it is set here using `qe_method`. If the QE location is unknown, or
+/// setting it fails, e.g. because the status register is locked, the best
+/// mode without quad is used instead.
+#[maybe_async]
+pub(crate) async fn negotiate_read_mode<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    features: Features,
+    qe_method: Option<protocol::QuadEnableMethod>,
+    try_native_4byte: bool,
+) -> (crate::spi::IoMode, u8, bool) {
+    use crate::spi::IoMode;
+
+    let master_features = master.features();
+    let selected =
+        protocol::select_read_mode(master_features, features, try_native_4byte, |opcode| {
+            master.probe_opcode(opcode)
+        });
+    if !matches!(selected.0, IoMode::QuadOut | IoMode::QuadIo) {
+        return selected;
+    }
+
+    match qe_method {
+        Some(method) => {
+            // A locked status register drops the write silently, so read QE back
+            let enabled = match protocol::enable_quad_mode(master, method).await {
+                Ok(()) => protocol::is_quad_enabled(master, method).await,
+                Err(e) => Err(e),
+            };
+            match enabled {
+                Ok(true) => return selected,
+                Ok(false) => log::warn!("Quad Enable bit didn't stick, not using quad reads"),
+                Err(e) => log::warn!("Failed to set Quad Enable, not using quad reads: {}", e),
+            }
+        }
+        None => log::debug!("Quad Enable location unknown, not using quad reads"),
+    }
+
+    protocol::select_read_mode(
+        master_features - SpiFeatures::QUAD,
+        features,
+        try_native_4byte,
+        |opcode| master.probe_opcode(opcode),
+    )
+}
+
+/// Select the read I/O mode for `ctx`'s chip on `master`
+///
+/// For programmers that run reads themselves, e.g. with a fixed set of
+/// opcodes per I/O mode. As in [`read`], quad modes are only picked once the
+/// Quad Enable bit is set.
+#[maybe_async]
+pub async fn read_io_mode<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    ctx: &FlashContext,
+) -> crate::spi::IoMode {
+    let features = ctx.chip.features;
+    let try_native_4byte =
+        ctx.address_mode == AddressMode::FourByte && features.supports_4ba_read();
+    negotiate_read_mode(master, features, ctx.quad_enable_method(), try_native_4byte)
+        .await
+        .0
+}
+
 /// Read flash contents
 ///
 /// Automatically selects the best I/O mode based on programmer and chip capabilities.
@@ -811,9 +1184,7 @@ pub async fn read<M: SpiMaster + ?Sized>(
         ctx.address_mode == AddressMode::FourByte && features.supports_4ba_read();
 
     let (io_mode, opcode, native_4byte) =
-        protocol::select_read_mode(master_features, features, try_native_4byte, |opcode| {
-            master.probe_opcode(opcode)
-        });
+        negotiate_read_mode(master, features, ctx.quad_enable_method(), try_native_4byte).await;
 
     let (addressing, enter_exit_4byte) = if ctx.address_mode == AddressMode::FourByte {
         addressing_for_4byte_operation(native_4byte, features, master_features)?
@@ -832,7 +1203,7 @@ pub async fn read<M: SpiMaster + ?Sized>(
         buf,
         addressing,
         io_mode,
-        read_dummy_cycles(io_mode),
+        read_dummy_cycles(io_mode, opcode),
     )
     .await;
 
@@ -887,6 +1258,14 @@ pub async fn write<M: SpiMaster + ?Sized>(
     // smaller than a full page (e.g., Intel swseq is limited to 64 bytes)
     let max_write = master.max_write_len();
 
+    if max_write < page_size {
+        log::debug!(
+            "Programmer limits writes to {} bytes (page size {}), splitting pages",
+            max_write,
+            page_size
+        );
+    }
+
     if enter_exit_4byte {
         protocol::enter_4byte_mode_with_features(master, features).await?;
     }
@@ -895,12 +1274,8 @@ pub async fn write<M: SpiMaster + ?Sized>(
     let mut current_addr = addr;
 
     while offset < data.len() {
-        // Calculate how many bytes until the next page boundary
-        let page_offset = (current_addr as usize) % page_size;
-        let bytes_to_page_end = page_size - page_offset;
-        let remaining = data.len() - offset;
         // Respect both page boundaries and the master's maximum write length
-        let chunk_size = core::cmp::min(core::cmp::min(bytes_to_page_end, remaining), max_write);
+        let chunk_size = program_chunk_len(current_addr, data.len() - offset, page_size, max_write);
 
         let chunk = &data[offset..offset + chunk_size];
 
@@ -957,6 +1332,53 @@ pub fn select_erase_block(erase_blocks: &[EraseBlock], addr: u32, len: u32) -> O
         .cloned()
 }
 
+/// Find the opcode that erases all `chip_size` bytes at once, if any
+///
+/// This is the CE command (0x60 or 0xC7) for most chips.
+pub(crate) fn chip_erase_opcode(erase_blocks: &[EraseBlock], chip_size: u32) -> Option<u8> {
+    erase_blocks
+        .iter()
+        .find(|eb| eb.is_chip_erase() && eb.max_block_size() == chip_size)
+        .map(|eb| eb.opcode)
+}
+
+/// Check that a range of flash has been erased (all bytes are 0xFF)
+///
+/// Reads through [`FlashDevice::read`], so it works for any device.
+#[maybe_async]
+pub(crate) async fn check_erased_range<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    addr: u32,
+    len: u32,
+) -> Result<()> {
+    const ERASED_VALUE: u8 = 0xFF;
+    const CHUNK_SIZE: usize = 4096;
+    let mut buf = [0u8; CHUNK_SIZE];
+
+    let mut offset = 0u32;
+    while offset < len {
+        let chunk_len = core::cmp::min(CHUNK_SIZE as u32, len - offset) as usize;
+        let chunk_buf = &mut buf[..chunk_len];
+
+        device.read(addr + offset, chunk_buf).await?;
+
+        if let Some((idx, &found)) = chunk_buf
+            .iter()
+            .enumerate()
+            .find(|&(_, &b)| b != ERASED_VALUE)
+        {
+            return Err(Error::EraseError(EraseFailure::VerifyFailed {
+                addr: addr + offset + idx as u32,
+                found,
+            }));
+        }
+
+        offset += chunk_len as u32;
+    }
+
+    Ok(())
+}
+
 // =============================================================================
 // Layout-aware operations
 // =============================================================================
@@ -1540,6 +1962,23 @@ mod tests {
         assert_eq!(ops[0].erase_block.opcode, 0xC7);
     }
 
+    #[test]
+    fn test_chip_erase_opcode() {
+        let flash_size = 1024 * 1024;
+        let mut erase_blocks = test_erase_blocks_4k_64k(flash_size);
+        assert_eq!(chip_erase_opcode(&erase_blocks, flash_size), None);
+
+        // A single block smaller than the chip is not a chip erase
+        erase_blocks.push(EraseBlock::new(opcodes::CE_C7, flash_size / 2));
+        assert_eq!(chip_erase_opcode(&erase_blocks, flash_size), None);
+
+        erase_blocks.push(EraseBlock::new(opcodes::CE_60, flash_size));
+        assert_eq!(
+            chip_erase_opcode(&erase_blocks, flash_size),
+            Some(opcodes::CE_60)
+        );
+    }
+
     #[test]
     fn test_optimal_erase_chip_erase_smart_when_most_needs_erasing() {
         // When >50% needs erasing with smart erase, use chip erase
@@ -1633,4 +2072,24 @@ mod tests {
             "Should not use chip erase for partial region"
         );
     }
+
+    // =========================================================================
+    // Tests for write chunking
+    // =========================================================================
+
+    #[test]
+    fn test_program_chunk_len_respects_page_and_master_limit() {
+        // Full page allowed by the master
+        assert_eq!(program_chunk_len(0, 1024, 256, 4096), 256);
+        // Master limit smaller than a page
+        assert_eq!(program_chunk_len(0, 1024, 256, 64), 64);
+        // Unaligned start stops at the page boundary
+        assert_eq!(program_chunk_len(0xF0, 1024, 256, 4096), 0x10);
+        // Unaligned start with a tighter master limit
+        assert_eq!(program_chunk_len(0x10, 1024, 256, 64), 64);
+        // Remaining data is the limit
+        assert_eq!(program_chunk_len(0, 3, 256, 64), 3);
+        // A zero master limit still makes progress
+        assert_eq!(program_chunk_len(0, 16, 256, 0), 1);
+    }
 }
@@ -4,14 +4,17 @@
 //! `FlashDevice` for SPI-based programmers.
 
 use crate::chip::{EraseBlock, WriteGranularity};
-use crate::error::{EraseFailure, Error, Result};
+use crate::error::{Error, Result};
 use crate::flash::context::{AddressMode, FlashContext};
 use crate::flash::device::FlashDevice;
 use crate::flash::operations::{
-    addressing_for_4byte_operation, read_dummy_cycles, select_erase_block,
+    addressing_for_4byte_operation, check_erased_range, chip_erase_opcode, negotiate_read_mode,
+    program_chunk_len, read_dummy_cycles, select_erase_block,
 };
+use crate::otp::{self, OtpLayout};
 use crate::programmer::{SpiFeatures, SpiMaster};
 use crate::protocol::{self, CommandAddressing};
+use crate::spi::IoMode;
 use crate::wp::{
     self, RangeDecoder, WpBits, WpConfig, WpMode, WpRange, WpRegBitMap, WpResult, WriteOptions,
 };
@@ -41,6 +44,8 @@ pub struct SpiFlashDevice<M: SpiMaster> {
     master: M,
     /// Flash chip context
     ctx: FlashContext,
+    /// Read I/O mode, opcode and native 4BA flag negotiated by the first read
+    read_mode: Option<(IoMode, u8, bool)>,
 }
 
 impl<M: SpiMaster> SpiFlashDevice<M> {
@@ -50,7 +55,11 @@ impl<M: SpiMaster> SpiFlashDevice<M> {
     /// * `master` - The SPI master to take ownership of
     /// * `ctx` - Flash context with chip metadata (from probing)
     pub fn new(master: M, ctx: FlashContext) -> Self {
-        SpiFlashDevice { master, ctx }
+        SpiFlashDevice {
+            master,
+            ctx,
+            read_mode: None,
+        }
     }
 
     /// Get a mutable reference to the underlying SPI master
@@ -64,7 +73,11 @@ impl<M: SpiMaster> SpiFlashDevice<M> {
     }
 
     /// Get a mutable reference to the flash context
+    ///
+    /// The next read negotiates its read mode again, since changes to the
+    /// context can affect it.
     pub fn context_mut(&mut self) -> &mut FlashContext {
+        self.read_mode = None;
         &mut self.ctx
     }
 
@@ -137,6 +150,41 @@ impl<M: SpiMaster> FlashDevice for SpiFlashDevice<M> {
         SpiFlashDevice::get_available_wp_ranges(self)
     }
 
+    // Security register support
+    fn otp_layout(&self) -> Option<OtpLayout> {
+        self.ctx.chip.otp_layout()
+    }
+
+    async fn read_otp(&mut self, register: u8, offset: u32, buf: &mut [u8]) -> Result<()> {
+        let layout = self.otp_layout().ok_or(Error::ChipNotSupported)?;
+        otp::read_security_register(&mut self.master, &layout, register, offset, buf).await
+    }
+
+    async fn write_otp(&mut self, register: u8, offset: u32, data: &[u8]) -> Result<()> {
+        let layout = self.otp_layout().ok_or(Error::ChipNotSupported)?;
+        otp::program_security_register(&mut self.master, &layout, register, offset, data).await
+    }
+
+    async fn erase_otp(&mut self, register: u8) -> Result<()> {
+        let layout = self.otp_layout().ok_or(Error::ChipNotSupported)?;
+        otp::erase_security_register(&mut self.master, &layout, register).await
+    }
+
+    async fn is_otp_locked(&mut self, register: u8) -> Result<bool> {
+        let layout = self.otp_layout().ok_or(Error::ChipNotSupported)?;
+        otp::is_security_register_locked(&mut self.master, &layout, register).await
+    }
+
+    async fn lock_otp(&mut self, register: u8) -> Result<()> {
+        let layout = self.otp_layout().ok_or(Error::ChipNotSupported)?;
+        let use_ewsr = self
+            .ctx
+            .chip
+            .features
+            .contains(crate::chip::Features::WRSR_EWSR);
+        otp::lock_security_register(&mut self.master, &layout, register, use_ewsr).await
+    }
+
     async fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<()> {
         let ctx = self.context();
         if !ctx.is_valid_range(addr, buf.len()) {
@@ -147,14 +195,24 @@ impl<M: SpiMaster> FlashDevice for SpiFlashDevice<M> {
         let address_mode = ctx.address_mode;
         let try_native_4byte =
             address_mode == AddressMode::FourByte && chip_features.supports_4ba_read();
+        let qe_method = ctx.quad_enable_method();
         let master_features = self.master.features();
 
-        let (io_mode, opcode, native_4byte) = protocol::select_read_mode(
-            master_features,
-            chip_features,
-            try_native_4byte,
-            |opcode| self.master.probe_opcode(opcode),
-        );
+        // Setting QE costs a status register write, so only do it once
+        let (io_mode, opcode, native_4byte) = match self.read_mode {
+            Some(mode) => mode,
+            None => {
+                let mode = negotiate_read_mode(
+                    &mut self.master,
+                    chip_features,
+                    qe_method,
+                    try_native_4byte,
+                )
+                .await;
+                self.read_mode = Some(mode);
+                mode
+            }
+        };
 
         let (addressing, enter_exit_4byte) = if address_mode == AddressMode::FourByte {
             addressing_for_4byte_operation(native_4byte, chip_features, master_features)?
@@ -173,7 +231,7 @@ impl<M: SpiMaster> FlashDevice for SpiFlashDevice<M> {
             buf,
             addressing,
             io_mode,
-            read_dummy_cycles(io_mode),
+            read_dummy_cycles(io_mode, opcode),
         )
         .await;
 
@@ -246,9 +304,7 @@ impl<M: SpiMaster> FlashDevice for SpiFlashDevice<M> {
             } else {
                 // Page-granularity program: up to a full page per command, respecting
                 // page boundaries and the master's maximum write length.
-                let page_offset = (current_addr as usize) % page_size;
-                let bytes_to_page_end = page_size - page_offset;
-                core::cmp::min(core::cmp::min(bytes_to_page_end, remaining), max_write)
+                program_chunk_len(current_addr, remaining, page_size, max_write)
             };
 
             let chunk = &data[offset..offset + chunk_size];
@@ -309,6 +365,13 @@ impl<M: SpiMaster> FlashDevice for SpiFlashDevice<M> {
             .ok_or(Error::InvalidAlignment)?;
 
         let chip_features = ctx.chip.features;
+
+        // For non-uniform erase blocks, use the maximum block size for timeout calculation
+        let max_block_size = erase_block.max_block_size();
+
+        // Poll delay and timeout depend on block size
+        let (poll_delay_us, timeout_us) = ctx.erase_timeouts.for_block(max_block_size);
+
         let use_4byte = ctx.address_mode == AddressMode::FourByte;
         let master_features = self.master.features();
         let use_native = use_4byte
@@ -330,17 +393,6 @@ impl<M: SpiMaster> FlashDevice for SpiFlashDevice<M> {
         let mut current_addr = addr;
         let end_addr = addr + len;
 
-        // For non-uniform erase blocks, use the maximum block size for timeout calculation
-        let max_block_size = erase_block.max_block_size();
-
-        // Poll delay and timeout depend on block size
-        let (poll_delay_us, timeout_us) = match max_block_size {
-            s if s <= 4096 => (10_000, 1_000_000), // 4KB: 10ms poll, 1s timeout
-            s if s <= 32768 => (100_000, 4_000_000), // 32KB: 100ms poll, 4s timeout
-            s if s <= 65536 => (100_000, 4_000_000), // 64KB: 100ms poll, 4s timeout
-            _ => (500_000, 60_000_000),            // Larger: 500ms poll, 60s timeout
-        };
-
         while current_addr < end_addr {
             // Get the block size at the current offset within the erase layout
             let offset_in_layout = current_addr - addr;
@@ -369,7 +421,7 @@ impl<M: SpiMaster> FlashDevice for SpiFlashDevice<M> {
             }
 
             // Verify the block was erased
-            if let Err(e) = self.check_erased_range(current_addr, block_size).await {
+            if let Err(e) = check_erased_range(self, current_addr, block_size).await {
                 if enter_exit_4byte
                     && let Err(exit_e) =
                         protocol::exit_4byte_mode_with_features(self.master(), chip_features).await
@@ -388,41 +440,28 @@ impl<M: SpiMaster> FlashDevice for SpiFlashDevice<M> {
 
         Ok(())
     }
-}
 
-impl<M: SpiMaster> SpiFlashDevice<M> {
-    /// Check that a range of flash has been erased (all bytes are 0xFF)
-    ///
-    /// Uses the `FlashDevice::read` trait method, which differs from
-    /// `operations::check_erased_range` that uses the free function `read()`.
-    #[maybe_async]
-    async fn check_erased_range(&mut self, addr: u32, len: u32) -> Result<()> {
-        const ERASED_VALUE: u8 = 0xFF;
-        const CHUNK_SIZE: usize = 4096;
-        let mut buf = [0u8; CHUNK_SIZE];
-
-        let mut offset = 0u32;
-        while offset < len {
-            let chunk_len = core::cmp::min(CHUNK_SIZE as u32, len - offset) as usize;
-            let chunk_buf = &mut buf[..chunk_len];
-
-            FlashDevice::read(self, addr + offset, chunk_buf).await?;
-
-            if let Some((idx, &found)) = chunk_buf
-                .iter()
-                .enumerate()
-                .find(|&(_, &b)| b != ERASED_VALUE)
-            {
-                return Err(Error::EraseError(EraseFailure::VerifyFailed {
-                    addr: addr + offset + idx as u32,
-                    found,
-                }));
-            }
+    async fn chip_erase(&mut self) -> Result<()> {
+        use crate::chip::Features;
+
+        let size = self.size();
+        let ctx = self.context();
+        let opcode = chip_erase_opcode(ctx.chip.erase_blocks(), size)
+            .filter(|&opcode| self.master.probe_opcode(opcode));
+        let Some(opcode) = opcode else {
+            log::info!("No usable chip erase opcode, erasing block by block");
+            return FlashDevice::erase(self, 0, size).await;
+        };
 
-            offset += chunk_len as u32;
+        let ctx = self.context();
+        let (_, timeout_us) = ctx.erase_timeouts.for_block(size);
+        if ctx.chip.features.contains(Features::SST26_BPR) {
+            protocol::sst26_global_unprotect(self.master()).await?;
         }
 
-        Ok(())
+        log::info!("Erasing the whole chip with chip erase (0x{:02X})", opcode);
+        protocol::chip_erase(self.master(), opcode, timeout_us).await?;
+        check_erased_range(self, 0, size).await
     }
 }
 
@@ -432,24 +471,13 @@ impl<M: SpiMaster> SpiFlashDevice<M> {
 
 impl<M: SpiMaster> SpiFlashDevice<M> {
     /// Get the WP register bit map for this chip
-    ///
-    /// Returns a standard Winbond-style bit map. In the future, this could
-    /// be made chip-specific based on the chip database.
     fn wp_bit_map(&self) -> WpRegBitMap {
-        // Check if chip has BP3 (4 BP bits)
-        let features = self.ctx.chip.features;
-        if features.contains(crate::chip::Features::WP_BP3) {
-            WpRegBitMap::winbond_with_bp3()
-        } else {
-            WpRegBitMap::winbond_standard()
-        }
+        self.ctx.chip.wp_bit_map()
     }
 
     /// Get the range decoder for this chip
     fn wp_decoder(&self) -> RangeDecoder {
-        // Default to standard SPI25 decoding
-        // In the future, this could be made chip-specific
-        RangeDecoder::Spi25
+        self.ctx.chip.wp_decoder
     }
 
     /// Read current write protection bits
@@ -543,8 +571,9 @@ impl<M: SpiMaster> SpiFlashDevice<M> {
     #[maybe_async]
     pub async fn disable_wp(&mut self, options: WriteOptions) -> WpResult<()> {
         let bit_map = self.wp_bit_map();
+        let decoder = self.wp_decoder();
         let options = self.chip_write_options(options);
-        wp::disable_wp(&mut self.master, &bit_map, options).await
+        wp::disable_wp(&mut self.master, &bit_map, decoder, options).await
     }
 
     /// Get all available protection ranges
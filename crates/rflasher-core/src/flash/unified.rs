@@ -11,10 +11,11 @@
 use alloc::vec;
 use alloc::vec::Vec;
 
+use crate::chip::{EraseBlock, WriteGranularity};
 use crate::error::{Error, Result};
 use crate::flash::device::FlashDevice;
 use crate::flash::operations::{
-    coalesce_write_ranges, plan_optimal_erase, plan_optimal_erase_region,
+    check_erased_range, coalesce_write_ranges, plan_optimal_erase, plan_optimal_erase_region,
 };
 use crate::layout::{Layout, LayoutError, Region};
 use maybe_async::maybe_async;
@@ -631,7 +632,77 @@ pub async fn erase_region<D: FlashDevice + ?Sized>(device: &mut D, region: &Regi
     Ok(())
 }
 
-/// Verify flash contents match the expected data
+/// Chunk size used when blank-checking, matching the post-erase check
+const BLANK_CHECK_CHUNK_SIZE: usize = 4096;
+
+/// Check whether a range of flash is erased
+///
+/// Reads the range in 4KB chunks into a stack buffer, so no allocation
+/// proportional to the chip size is needed.
+///
+/// # Returns
+/// `None` if every byte reads as 0xFF, otherwise the address and value of
+/// the first byte that doesn't.
+#[maybe_async]
+pub async fn is_blank<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    addr: u32,
+    len: u32,
+) -> Result<Option<(u32, u8)>> {
+    if !device.is_valid_range(addr, len as usize) {
+        return Err(Error::AddressOutOfBounds);
+    }
+
+    let mut buf = [0u8; BLANK_CHECK_CHUNK_SIZE];
+    let mut offset = 0u32;
+
+    while offset < len {
+        let chunk_len = core::cmp::min(BLANK_CHECK_CHUNK_SIZE as u32, len - offset) as usize;
+        let chunk_buf = &mut buf[..chunk_len];
+        device.read(addr + offset, chunk_buf).await?;
+
+        if let Some(idx) = chunk_buf.iter().position(|&b| b != ERASED_VALUE) {
+            return Ok(Some((addr + offset + idx as u32, chunk_buf[idx])));
+        }
+
+        offset += chunk_len as u32;
+    }
+
+    Ok(None)
+}
+
+/// Location and extent of a verify failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyMismatch {
+    /// Absolute address of the first differing byte
+    pub addr: u32,
+    /// Byte the image expected at `addr`
+    pub expected: u8,
+    /// Byte read back from flash at `addr`
+    pub actual: u8,
+    /// Total number of differing bytes in the verified range
+    pub count: usize,
+}
+
+impl VerifyMismatch {
+    /// Combine the results of verifying two consecutive ranges
+    ///
+    /// Keeps the first mismatch and adds up the differing byte counts.
+    pub fn merge(first: Option<Self>, second: Option<Self>) -> Option<Self> {
+        match (first, second) {
+            (Some(a), Some(b)) => Some(Self {
+                count: a.count + b.count,
+                ..a
+            }),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// Compare flash contents against the expected data, reporting where they differ
+///
+/// Unlike [`verify`], this reads the whole range even after the first
+/// mismatch so the total number of differing bytes can be reported.
 ///
 /// # Arguments
 /// * `device` - Flash device to verify
@@ -639,14 +710,20 @@ pub async fn erase_region<D: FlashDevice + ?Sized>(device: &mut D, region: &Regi
 /// * `addr` - Starting address (0 for full flash)
 ///
 /// # Returns
-/// `Ok(())` if verification passes, `Err(VerifyError)` if mismatch detected
+/// `None` if the contents match, otherwise the first mismatch and the
+/// number of differing bytes
 #[maybe_async]
-pub async fn verify<D: FlashDevice>(device: &mut D, expected: &[u8], addr: u32) -> Result<()> {
+pub async fn verify_detailed<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    expected: &[u8],
+    addr: u32,
+) -> Result<Option<VerifyMismatch>> {
     if !device.is_valid_range(addr, expected.len()) {
         return Err(Error::AddressOutOfBounds);
     }
 
-    let mut buf = vec![0u8; READ_CHUNK_SIZE];
+    let mut buf = vec![0u8; core::cmp::min(READ_CHUNK_SIZE, expected.len())];
+    let mut mismatch = None;
     let mut offset = 0usize;
 
     while offset < expected.len() {
@@ -656,24 +733,58 @@ pub async fn verify<D: FlashDevice>(device: &mut D, expected: &[u8], addr: u32)
 
         let expected_chunk = &expected[offset..offset + chunk_size];
         if chunk_buf != expected_chunk {
-            return Err(Error::VerifyError {
-                addr: addr + offset as u32,
-            });
+            let mut diffs = chunk_buf
+                .iter()
+                .zip(expected_chunk)
+                .enumerate()
+                .filter(|(_, (actual, expected))| actual != expected);
+            if let Some((i, (&actual, &expected))) = diffs.next() {
+                let chunk_mismatch = VerifyMismatch {
+                    addr: addr + (offset + i) as u32,
+                    expected,
+                    actual,
+                    count: 1 + diffs.count(),
+                };
+                mismatch = VerifyMismatch::merge(mismatch, Some(chunk_mismatch));
+            }
         }
 
         offset += chunk_size;
     }
 
-    Ok(())
+    Ok(mismatch)
 }
 
-/// Verify all included regions match expected data
+/// Verify flash contents match the expected data
+///
+/// # Arguments
+/// * `device` - Flash device to verify
+/// * `expected` - Expected data
+/// * `addr` - Starting address (0 for full flash)
+///
+/// # Returns
+/// `Ok(())` if verification passes, `Err(VerifyError)` with the address of
+/// the first differing byte if a mismatch is detected
 #[maybe_async]
-pub async fn verify_by_layout<D: FlashDevice>(
+pub async fn verify<D: FlashDevice>(device: &mut D, expected: &[u8], addr: u32) -> Result<()> {
+    match verify_detailed(device, expected, addr).await? {
+        Some(mismatch) => Err(Error::VerifyError {
+            addr: mismatch.addr,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Compare all included regions against expected data
+///
+/// Returns the first mismatch across all regions (in layout order) with the
+/// total number of differing bytes, or `None` if everything matches.
+#[maybe_async]
+pub async fn verify_by_layout_detailed<D: FlashDevice + ?Sized>(
     device: &mut D,
     layout: &Layout,
     expected: &[u8],
-) -> Result<()> {
+) -> Result<Option<VerifyMismatch>> {
     let flash_size = device.size();
 
     layout.validate(flash_size).map_err(|e| match e {
@@ -686,10 +797,686 @@ pub async fn verify_by_layout<D: FlashDevice>(
         return Err(Error::BufferTooSmall);
     }
 
+    let mut mismatch = None;
     for region in layout.included_regions() {
         let expected_region = &expected[region.start as usize..=region.end as usize];
-        verify(device, expected_region, region.start).await?;
+        let region_mismatch = verify_detailed(device, expected_region, region.start).await?;
+        mismatch = VerifyMismatch::merge(mismatch, region_mismatch);
     }
 
-    Ok(())
+    Ok(mismatch)
+}
+
+/// Verify all included regions match expected data
+#[maybe_async]
+pub async fn verify_by_layout<D: FlashDevice>(
+    device: &mut D,
+    layout: &Layout,
+    expected: &[u8],
+) -> Result<()> {
+    match verify_by_layout_detailed(device, layout, expected).await? {
+        Some(mismatch) => Err(Error::VerifyError {
+            addr: mismatch.addr,
+        }),
+        None => Ok(()),
+    }
+}
+
+// =============================================================================
+// Verify-on-write
+// =============================================================================
+
+/// Device wrapper that reads back and checks every write and erase immediately
+///
+/// Wrapping a device in `VerifyingDevice` turns any smart write into a
+/// single write-and-verify pass: each `write()` is followed by a read of the
+/// same range, and the first mismatching byte is reported as
+/// `Error::VerifyError`. Each erase is read back as well, since smart write
+/// never programs the bytes that should stay erased, and a byte left
+/// unerased is reported as `EraseFailure::VerifyFailed`. This is mainly
+/// useful for opaque programmers, where a separate full read-back pass
+/// after writing is as slow as the write itself, and a failure is caught at
+/// the chunk where it happened instead of after the whole image has been
+/// written.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut verifying = VerifyingDevice::new(&mut device);
+/// unified::smart_write(&mut verifying, &data, &mut progress)?;
+/// ```
+pub struct VerifyingDevice<'a, D: FlashDevice + ?Sized> {
+    inner: &'a mut D,
+    /// Read-back buffer, reused across writes
+    buf: Vec<u8>,
+}
+
+impl<'a, D: FlashDevice + ?Sized> VerifyingDevice<'a, D> {
+    /// Wrap a device so that every write is verified by reading it back
+    pub fn new(inner: &'a mut D) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+        }
+    }
+}
+
+#[maybe_async(AFIT)]
+impl<D: FlashDevice + ?Sized> FlashDevice for VerifyingDevice<'_, D> {
+    fn size(&self) -> u32 {
+        self.inner.size()
+    }
+
+    fn erase_granularity(&self) -> u32 {
+        self.inner.erase_granularity()
+    }
+
+    fn write_granularity(&self) -> WriteGranularity {
+        self.inner.write_granularity()
+    }
+
+    fn erase_blocks(&self) -> &[EraseBlock] {
+        self.inner.erase_blocks()
+    }
+
+    fn page_size(&self) -> u32 {
+        self.inner.page_size()
+    }
+
+    async fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<()> {
+        self.inner.read(addr, buf).await
+    }
+
+    async fn write(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        self.inner.write(addr, data).await?;
+
+        let mut offset = 0usize;
+        while offset < data.len() {
+            let chunk_size = core::cmp::min(READ_CHUNK_SIZE, data.len() - offset);
+            self.buf.resize(chunk_size, 0);
+            self.inner
+                .read(addr + offset as u32, &mut self.buf[..chunk_size])
+                .await?;
+
+            let expected = &data[offset..offset + chunk_size];
+            if let Some(pos) = self.buf[..chunk_size]
+                .iter()
+                .zip(expected)
+                .position(|(a, b)| a != b)
+            {
+                return Err(Error::VerifyError {
+                    addr: addr + (offset + pos) as u32,
+                });
+            }
+
+            offset += chunk_size;
+        }
+
+        Ok(())
+    }
+
+    async fn erase(&mut self, addr: u32, len: u32) -> Result<()> {
+        self.inner.erase(addr, len).await?;
+        check_erased_range(&mut *self.inner, addr, len).await
+    }
+
+    async fn chip_erase(&mut self) -> Result<()> {
+        self.inner.chip_erase().await?;
+        let size = self.inner.size();
+        check_erased_range(&mut *self.inner, 0, size).await
+    }
+
+    fn is_valid_range(&self, addr: u32, len: usize) -> bool {
+        self.inner.is_valid_range(addr, len)
+    }
+}
+
+// =============================================================================
+// Dry run
+// =============================================================================
+
+/// Destructive operation recorded by [`DryRunDevice`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedOp {
+    /// Erase `len` bytes starting at `addr`
+    Erase {
+        /// Start address
+        addr: u32,
+        /// Number of bytes
+        len: u32,
+    },
+    /// Program `len` bytes starting at `addr`
+    Write {
+        /// Start address
+        addr: u32,
+        /// Number of bytes
+        len: u32,
+    },
+}
+
+/// Device wrapper that records erases and writes instead of performing them
+///
+/// Reads go to the wrapped device, so smart write and erase plan their
+/// operations from the real flash contents, but `erase()` and `write()` only
+/// append to [`DryRunDevice::ops`]. Reads after a recorded operation still
+/// return the unmodified contents.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut dry_run = DryRunDevice::new(&mut device);
+/// unified::smart_write(&mut dry_run, &data, &mut progress)?;
+/// for op in dry_run.ops() {
+///     println!("{:?}", op);
+/// }
+/// ```
+pub struct DryRunDevice<'a, D: FlashDevice + ?Sized> {
+    inner: &'a mut D,
+    ops: Vec<PlannedOp>,
+}
+
+impl<'a, D: FlashDevice + ?Sized> DryRunDevice<'a, D> {
+    /// Wrap a device so that erases and writes are only recorded
+    pub fn new(inner: &'a mut D) -> Self {
+        Self {
+            inner,
+            ops: Vec::new(),
+        }
+    }
+
+    /// Operations that would have been performed, in order
+    pub fn ops(&self) -> &[PlannedOp] {
+        &self.ops
+    }
+}
+
+#[maybe_async(AFIT)]
+impl<D: FlashDevice + ?Sized> FlashDevice for DryRunDevice<'_, D> {
+    fn size(&self) -> u32 {
+        self.inner.size()
+    }
+
+    fn erase_granularity(&self) -> u32 {
+        self.inner.erase_granularity()
+    }
+
+    fn write_granularity(&self) -> WriteGranularity {
+        self.inner.write_granularity()
+    }
+
+    fn erase_blocks(&self) -> &[EraseBlock] {
+        self.inner.erase_blocks()
+    }
+
+    fn page_size(&self) -> u32 {
+        self.inner.page_size()
+    }
+
+    async fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<()> {
+        self.inner.read(addr, buf).await
+    }
+
+    async fn write(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        if !self.inner.is_valid_range(addr, data.len()) {
+            return Err(Error::AddressOutOfBounds);
+        }
+        self.ops.push(PlannedOp::Write {
+            addr,
+            len: data.len() as u32,
+        });
+        Ok(())
+    }
+
+    async fn erase(&mut self, addr: u32, len: u32) -> Result<()> {
+        if !self.inner.is_valid_range(addr, len as usize) {
+            return Err(Error::AddressOutOfBounds);
+        }
+        self.ops.push(PlannedOp::Erase { addr, len });
+        Ok(())
+    }
+
+    async fn chip_erase(&mut self) -> Result<()> {
+        self.ops.push(PlannedOp::Erase {
+            addr: 0,
+            len: self.inner.size(),
+        });
+        Ok(())
+    }
+
+    fn is_valid_range(&self, addr: u32, len: usize) -> bool {
+        self.inner.is_valid_range(addr, len)
+    }
+}
+
+// =============================================================================
+// Read retries
+// =============================================================================
+
+/// Granularity at which `RetryingReadDevice` compares and votes on copies
+const RETRY_CHUNK_SIZE: usize = 4096;
+
+/// Device wrapper that reads everything at least twice and votes on mismatches
+///
+/// On marginal connections (long cables, slow clocks) a read occasionally
+/// returns corrupted bytes. Each 4 KiB chunk is read twice; if the copies
+/// disagree, it is read again until every byte has a strict majority across
+/// the copies, up to `tries` reads in total. A chunk that still has a byte
+/// without a majority fails with `Error::ReadError` at that byte.
+///
+/// Writes and erases are passed through unchanged.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut retrying = RetryingReadDevice::new(&mut device, 3);
+/// retrying.read(0, &mut buf)?;
+/// if retrying.mismatches() > 0 {
+///     println!("connection is marginal");
+/// }
+/// ```
+pub struct RetryingReadDevice<'a, D: FlashDevice + ?Sized> {
+    inner: &'a mut D,
+    tries: u8,
+    /// Chunks whose first two copies disagreed
+    mismatches: usize,
+    /// Extra copies of the current chunk, reused across reads
+    copies: Vec<Vec<u8>>,
+}
+
+impl<'a, D: FlashDevice + ?Sized> RetryingReadDevice<'a, D> {
+    /// Wrap a device so that each chunk is read up to `tries` times
+    ///
+    /// With `tries` below 2, reads are passed through unchanged.
+    pub fn new(inner: &'a mut D, tries: u8) -> Self {
+        Self {
+            inner,
+            tries,
+            mismatches: 0,
+            copies: Vec::new(),
+        }
+    }
+
+    /// Number of chunks whose copies disagreed and needed extra reads
+    pub fn mismatches(&self) -> usize {
+        self.mismatches
+    }
+
+    /// Read one chunk, re-reading and voting if the copies disagree
+    #[maybe_async]
+    async fn read_chunk(&mut self, addr: u32, buf: &mut [u8]) -> Result<()> {
+        self.inner.read(addr, buf).await?;
+
+        self.copies.truncate(1);
+        if self.copies.is_empty() {
+            self.copies.push(Vec::new());
+        }
+        self.copies[0].resize(buf.len(), 0);
+        self.inner.read(addr, &mut self.copies[0]).await?;
+        if self.copies[0] == buf {
+            return Ok(());
+        }
+
+        self.mismatches += 1;
+        log::warn!(
+            "Reads of 0x{:08X}..0x{:08X} returned different data, re-reading",
+            addr,
+            addr + buf.len() as u32
+        );
+
+        while self.copies.len() + 1 < self.tries as usize {
+            let mut copy = vec![0u8; buf.len()];
+            self.inner.read(addr, &mut copy).await?;
+            self.copies.push(copy);
+
+            if let Some(voted) = majority(buf, &self.copies) {
+                buf.copy_from_slice(&voted);
+                return Ok(());
+            }
+        }
+
+        // No majority for some byte after all tries
+        let bad = (0..buf.len())
+            .find(|&i| self.copies.iter().any(|copy| copy[i] != buf[i]))
+            .unwrap_or(0);
+        log::error!(
+            "No majority for the byte at 0x{:08X} after {} reads",
+            addr + bad as u32,
+            self.copies.len() + 1
+        );
+        Err(Error::ReadError {
+            addr: addr + bad as u32,
+        })
+    }
+}
+
+/// Vote on each byte of `first` and `others`
+///
+/// Returns `None` if any byte has no value shared by more than half of the
+/// copies.
+fn majority(first: &[u8], others: &[Vec<u8>]) -> Option<Vec<u8>> {
+    let total = others.len() + 1;
+    (0..first.len())
+        .map(|i| {
+            let candidates = core::iter::once(first[i]).chain(others.iter().map(|copy| copy[i]));
+            candidates.clone().find(|&value| {
+                candidates.clone().filter(|&other| other == value).count() * 2 > total
+            })
+        })
+        .collect()
+}
+
+#[maybe_async(AFIT)]
+impl<D: FlashDevice + ?Sized> FlashDevice for RetryingReadDevice<'_, D> {
+    fn size(&self) -> u32 {
+        self.inner.size()
+    }
+
+    fn erase_granularity(&self) -> u32 {
+        self.inner.erase_granularity()
+    }
+
+    fn write_granularity(&self) -> WriteGranularity {
+        self.inner.write_granularity()
+    }
+
+    fn erase_blocks(&self) -> &[EraseBlock] {
+        self.inner.erase_blocks()
+    }
+
+    fn page_size(&self) -> u32 {
+        self.inner.page_size()
+    }
+
+    async fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<()> {
+        if self.tries < 2 {
+            return self.inner.read(addr, buf).await;
+        }
+
+        for (i, chunk) in buf.chunks_mut(RETRY_CHUNK_SIZE).enumerate() {
+            self.read_chunk(addr + (i * RETRY_CHUNK_SIZE) as u32, chunk)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        self.inner.write(addr, data).await
+    }
+
+    async fn erase(&mut self, addr: u32, len: u32) -> Result<()> {
+        self.inner.erase(addr, len).await
+    }
+
+    async fn chip_erase(&mut self) -> Result<()> {
+        self.inner.chip_erase().await
+    }
+
+    fn is_valid_range(&self, addr: u32, len: usize) -> bool {
+        self.inner.is_valid_range(addr, len)
+    }
+}
+
+#[cfg(all(test, feature = "is_sync"))]
+mod tests {
+    use super::*;
+    use crate::error::EraseFailure;
+    use crate::flash::OpaqueFlashDevice;
+    use crate::programmer::OpaqueMaster;
+
+    /// Memory-backed opaque programmer that corrupts one byte when written
+    ///
+    /// Reads covering that byte XOR it with the next entry of `read_flips`.
+    /// With `ignore_erase`, erases silently leave the flash unchanged, like
+    /// a locked region.
+    struct CorruptingOpaque {
+        data: Vec<u8>,
+        corrupt_at: Option<u32>,
+        ignore_erase: bool,
+        writes: Vec<(u32, usize)>,
+        read_flips: Vec<u8>,
+        reads: usize,
+    }
+
+    impl OpaqueMaster for CorruptingOpaque {
+        fn size(&self) -> usize {
+            self.data.len()
+        }
+
+        fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<()> {
+            let start = addr as usize;
+            buf.copy_from_slice(&self.data[start..start + buf.len()]);
+            if let Some(bad) = self.corrupt_at
+                && (addr..addr + buf.len() as u32).contains(&bad)
+            {
+                buf[(bad - addr) as usize] ^= self.read_flips.get(self.reads).copied().unwrap_or(0);
+                self.reads += 1;
+            }
+            Ok(())
+        }
+
+        fn write(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+            let start = addr as usize;
+            for (i, b) in data.iter().enumerate() {
+                self.data[start + i] &= *b;
+            }
+            if let Some(bad) = self.corrupt_at
+                && (addr..addr + data.len() as u32).contains(&bad)
+            {
+                self.data[bad as usize] ^= 0x01;
+            }
+            self.writes.push((addr, data.len()));
+            Ok(())
+        }
+
+        fn erase(&mut self, addr: u32, len: u32) -> Result<()> {
+            if !self.ignore_erase {
+                self.data[addr as usize..(addr + len) as usize].fill(ERASED_VALUE);
+            }
+            Ok(())
+        }
+    }
+
+    const SIZE: u32 = 1024 * 1024;
+
+    fn device(corrupt_at: Option<u32>) -> OpaqueFlashDevice<CorruptingOpaque> {
+        let master = CorruptingOpaque {
+            data: vec![ERASED_VALUE; SIZE as usize],
+            corrupt_at,
+            ignore_erase: false,
+            writes: Vec::new(),
+            read_flips: Vec::new(),
+            reads: 0,
+        };
+        OpaqueFlashDevice::new(master, SIZE)
+    }
+
+    fn image() -> Vec<u8> {
+        (0..SIZE).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_verifying_device_passes_clean_write() {
+        let mut dev = device(None);
+        let data = image();
+
+        let stats = smart_write(&mut VerifyingDevice::new(&mut dev), &data, &mut NoProgress)
+            .expect("write should verify");
+
+        assert!(stats.flash_modified);
+        assert_eq!(dev.master().data, data);
+    }
+
+    #[test]
+    fn test_verifying_device_catches_corruption_at_chunk() {
+        let bad = 2 * WRITE_CHUNK_SIZE as u32 + 0x123;
+        let mut dev = device(Some(bad));
+        let data = image();
+
+        let err = smart_write(&mut VerifyingDevice::new(&mut dev), &data, &mut NoProgress)
+            .expect_err("corruption should be detected");
+        assert_eq!(err, Error::VerifyError { addr: bad });
+
+        // The write stopped at the corrupted chunk instead of finishing the image
+        let last = *dev.master().writes.last().unwrap();
+        assert!((last.0..last.0 + last.1 as u32).contains(&bad));
+        assert!(dev.master().writes.iter().all(|(addr, _)| *addr <= bad));
+    }
+
+    #[test]
+    fn test_verifying_device_catches_failed_erase() {
+        let mut dev = device(None);
+        dev.write(0x2000, &[0x00; 16]).unwrap();
+        dev.master().ignore_erase = true;
+
+        // Nothing is programmed, so only the erase read-back sees the 0x00s
+        let data = vec![ERASED_VALUE; SIZE as usize];
+        let err = smart_write(&mut VerifyingDevice::new(&mut dev), &data, &mut NoProgress)
+            .expect_err("failed erase should be detected");
+        assert_eq!(
+            err,
+            Error::EraseError(EraseFailure::VerifyFailed {
+                addr: 0x2000,
+                found: 0x00
+            })
+        );
+        assert_eq!(dev.master().writes.len(), 1);
+    }
+
+    #[test]
+    fn test_chip_erase_falls_back_to_whole_device_erase() {
+        let mut dev = device(None);
+        dev.write(0x1000, &[0x00; 16]).unwrap();
+
+        let mut dry_run = DryRunDevice::new(&mut dev);
+        dry_run.chip_erase().unwrap();
+        assert_eq!(dry_run.ops(), &[PlannedOp::Erase { addr: 0, len: SIZE }]);
+        assert!(dev.master().data[0x1000..0x1010].iter().all(|&b| b == 0));
+
+        dev.chip_erase().unwrap();
+        assert!(dev.master().data.iter().all(|&b| b == ERASED_VALUE));
+    }
+
+    #[test]
+    fn test_dry_run_device_records_without_writing() {
+        let mut dev = device(None);
+        let data = image();
+
+        let mut dry_run = DryRunDevice::new(&mut dev);
+        let stats = smart_write(&mut dry_run, &data, &mut NoProgress).unwrap();
+        let ops = dry_run.ops().to_vec();
+
+        // The plan matches the reported stats, and the flash is untouched
+        let erased: u32 = ops
+            .iter()
+            .filter_map(|op| match op {
+                PlannedOp::Erase { len, .. } => Some(*len),
+                PlannedOp::Write { .. } => None,
+            })
+            .sum();
+        let written: u32 = ops
+            .iter()
+            .filter_map(|op| match op {
+                PlannedOp::Write { len, .. } => Some(*len),
+                PlannedOp::Erase { .. } => None,
+            })
+            .sum();
+        assert_eq!(erased as usize, stats.bytes_erased);
+        assert_eq!(written as usize, stats.bytes_written);
+        assert_eq!(ops.len(), stats.erases_performed + stats.writes_performed);
+        assert!(dev.master().writes.is_empty());
+        assert!(dev.master().data.iter().all(|&b| b == ERASED_VALUE));
+
+        let mut dry_run = DryRunDevice::new(&mut dev);
+        assert_eq!(
+            dry_run.erase(SIZE - 0x1000, 0x2000),
+            Err(Error::AddressOutOfBounds)
+        );
+        assert!(dry_run.ops().is_empty());
+    }
+
+    #[test]
+    fn test_retrying_read_device() {
+        let bad = 0x1_2345;
+        let flaky = |flips: &[u8]| {
+            let mut dev = device(Some(bad));
+            dev.master().data = image();
+            dev.master().read_flips = flips.to_vec();
+            dev
+        };
+        let expected = image();
+        let mut buf = vec![0u8; 0x2_0000];
+
+        // A single bad read is outvoted by the next two
+        let mut dev = flaky(&[0x10]);
+        let mut retrying = RetryingReadDevice::new(&mut dev, 3);
+        retrying.read(0, &mut buf).unwrap();
+        assert_eq!(buf, expected[..buf.len()]);
+        assert_eq!(retrying.mismatches(), 1);
+        assert_eq!(dev.master().reads, 3);
+
+        // Every copy differs: no majority within the tries
+        let mut dev = flaky(&[0x01, 0x02, 0x04, 0x08]);
+        let mut retrying = RetryingReadDevice::new(&mut dev, 4);
+        assert_eq!(
+            retrying.read(0, &mut buf),
+            Err(Error::ReadError { addr: bad })
+        );
+        assert_eq!(dev.master().reads, 4);
+
+        // Without retries the corruption goes through
+        let mut dev = flaky(&[0x10]);
+        RetryingReadDevice::new(&mut dev, 1)
+            .read(0, &mut buf)
+            .unwrap();
+        assert_eq!(buf[bad as usize], expected[bad as usize] ^ 0x10);
+    }
+
+    #[test]
+    fn test_is_blank() {
+        let mut dev = device(None);
+        assert_eq!(is_blank(&mut dev, 0, SIZE), Ok(None));
+
+        // A dirty byte past the first chunk is reported with its value
+        dev.master().data[0x1_2345] = 0x7E;
+        assert_eq!(is_blank(&mut dev, 0, SIZE), Ok(Some((0x1_2345, 0x7E))));
+        assert_eq!(is_blank(&mut dev, 0x1_2346, SIZE - 0x1_2346), Ok(None));
+
+        assert_eq!(
+            is_blank(&mut dev, SIZE - 16, 32),
+            Err(Error::AddressOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn test_verify_detailed_reports_first_mismatch_and_count() {
+        let mut dev = device(None);
+        let data = image();
+        dev.master().data.copy_from_slice(&data);
+        assert_eq!(verify_detailed(&mut dev, &data, 0), Ok(None));
+
+        // Two differences in separate read chunks
+        let late = READ_CHUNK_SIZE as u32 + 0x10;
+        dev.master().data[0x100] ^= 0xFF;
+        dev.master().data[late as usize] = 0x00;
+        let mismatch = verify_detailed(&mut dev, &data, 0).unwrap().unwrap();
+        assert_eq!(
+            mismatch,
+            VerifyMismatch {
+                addr: 0x100,
+                expected: data[0x100],
+                actual: data[0x100] ^ 0xFF,
+                count: 2,
+            }
+        );
+        assert_eq!(
+            verify(&mut dev, &data, 0),
+            Err(Error::VerifyError { addr: 0x100 })
+        );
+
+        // Addresses are absolute when verifying from an offset
+        let tail = &data[late as usize - 4..late as usize + 4];
+        let mismatch = verify_detailed(&mut dev, tail, late - 4).unwrap().unwrap();
+        assert_eq!(mismatch.addr, late);
+        assert_eq!(mismatch.count, 1);
+    }
 }
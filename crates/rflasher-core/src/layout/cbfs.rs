@@ -0,0 +1,363 @@
+//! CBFS (coreboot filesystem) listing
+//!
+//! coreboot images store their stages, payloads and data files in a CBFS,
+//! usually the FMAP "COREBOOT" area inside the BIOS region. Each file starts
+//! with a "LARCHIVE" header followed by its name and data, aligned to 64
+//! bytes. Images without an FMAP locate the CBFS through the master header
+//! ("ORBC" magic) instead.
+//!
+//! This is a read-only listing; files aren't decompressed or modified.
+//!
+//! Reference: coreboot/src/commonlib/bsd/include/commonlib/bsd/cbfs_serialized.h
+
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use zerocopy::byteorder::big_endian::U32 as U32BE;
+use zerocopy::{FromBytes, Immutable, KnownLayout, Unaligned};
+
+use super::{LayoutError, parse_fmap};
+
+/// File header magic
+const FILE_MAGIC: &[u8; 8] = b"LARCHIVE";
+
+/// Master header magic ("ORBC")
+const HEADER_MAGIC: u32 = 0x4F52_4243;
+
+/// Master header versions
+const HEADER_VERSION1: u32 = 0x3131_3131;
+const HEADER_VERSION2: u32 = 0x3131_3132;
+
+/// File alignment used when no master header says otherwise
+const DEFAULT_ALIGN: u32 = 64;
+
+/// FMAP area holding the primary CBFS
+pub const DEFAULT_CBFS_REGION: &str = "COREBOOT";
+
+/// CBFS master header (32 bytes, big-endian)
+#[repr(C)]
+#[derive(FromBytes, KnownLayout, Immutable, Unaligned)]
+struct MasterHeader {
+    magic: U32BE,
+    version: U32BE,
+    romsize: U32BE,
+    bootblocksize: U32BE,
+    align: U32BE,
+    offset: U32BE,
+    #[allow(dead_code)]
+    architecture: U32BE,
+    #[allow(dead_code)]
+    pad: U32BE,
+}
+
+/// CBFS file header (24 bytes, big-endian), followed by the file name
+#[repr(C)]
+#[derive(FromBytes, KnownLayout, Immutable, Unaligned)]
+struct FileHeader {
+    magic: [u8; 8],
+    len: U32BE,
+    file_type: U32BE,
+    #[allow(dead_code)]
+    attributes_offset: U32BE,
+    offset: U32BE,
+}
+
+/// File type values
+pub mod file_type {
+    /// Deleted file, free for reuse
+    pub const DELETED: u32 = 0x0000_0000;
+    /// Bootblock
+    pub const BOOTBLOCK: u32 = 0x01;
+    /// CBFS master header
+    pub const CBFSHEADER: u32 = 0x02;
+    /// Stage in the legacy format
+    pub const LEGACY_STAGE: u32 = 0x10;
+    /// Stage (romstage, ramstage, ...)
+    pub const STAGE: u32 = 0x11;
+    /// Simple ELF payload
+    pub const SELF: u32 = 0x20;
+    /// FIT payload
+    pub const FIT_PAYLOAD: u32 = 0x21;
+    /// PCI option ROM
+    pub const OPTIONROM: u32 = 0x30;
+    /// Boot splash image
+    pub const BOOTSPLASH: u32 = 0x40;
+    /// Raw data
+    pub const RAW: u32 = 0x50;
+    /// VSA blob (Geode)
+    pub const VSA: u32 = 0x51;
+    /// MBI blob
+    pub const MBI: u32 = 0x52;
+    /// CPU microcode
+    pub const MICROCODE: u32 = 0x53;
+    /// Intel Firmware Interface Table
+    pub const INTEL_FIT: u32 = 0x54;
+    /// Intel FSP binary
+    pub const FSP: u32 = 0x60;
+    /// Memory reference code binary
+    pub const MRC: u32 = 0x61;
+    /// Memory margin analysis blob
+    pub const MMA: u32 = 0x62;
+    /// EFI binary
+    pub const EFI: u32 = 0x63;
+    /// Structured data
+    pub const STRUCT: u32 = 0x70;
+    /// CMOS defaults
+    pub const CMOS_DEFAULT: u32 = 0xAA;
+    /// Memory SPD data
+    pub const SPD: u32 = 0xAB;
+    /// Cached memory training data
+    pub const MRC_CACHE: u32 = 0xAC;
+    /// CMOS option layout
+    pub const CMOS_LAYOUT: u32 = 0x01AA;
+    /// Empty space
+    pub const NULL: u32 = 0xFFFF_FFFF;
+}
+
+/// A file in a CBFS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CbfsFile {
+    /// File name
+    pub name: String,
+    /// File type (see [`file_type`])
+    pub file_type: u32,
+    /// Offset of the file data within the image
+    pub offset: u32,
+    /// Size of the file data in bytes
+    pub size: u32,
+}
+
+impl CbfsFile {
+    /// Name of the file type, as printed by cbfstool
+    pub fn type_name(&self) -> &'static str {
+        match self.file_type {
+            file_type::DELETED => "deleted",
+            file_type::BOOTBLOCK => "bootblock",
+            file_type::CBFSHEADER => "cbfs header",
+            file_type::LEGACY_STAGE => "legacy stage",
+            file_type::STAGE => "stage",
+            file_type::SELF => "simple elf",
+            file_type::FIT_PAYLOAD => "fit",
+            file_type::OPTIONROM => "optionrom",
+            file_type::BOOTSPLASH => "bootsplash",
+            file_type::RAW => "raw",
+            file_type::VSA => "vsa",
+            file_type::MBI => "mbi",
+            file_type::MICROCODE => "microcode",
+            file_type::INTEL_FIT => "intel_fit",
+            file_type::FSP => "fsp",
+            file_type::MRC => "mrc",
+            file_type::MMA => "mma",
+            file_type::EFI => "efi",
+            file_type::STRUCT => "struct",
+            file_type::CMOS_DEFAULT => "cmos_default",
+            file_type::SPD => "spd",
+            file_type::MRC_CACHE => "mrc_cache",
+            file_type::CMOS_LAYOUT => "cmos_layout",
+            file_type::NULL => "null",
+            _ => "unknown",
+        }
+    }
+
+    /// Check if this entry is free space rather than a real file
+    pub fn is_empty(&self) -> bool {
+        matches!(self.file_type, file_type::NULL | file_type::DELETED)
+    }
+}
+
+/// List the files of a CBFS occupying `data[start..end]`
+///
+/// Files are walked from `start` until a slot without a file header is
+/// found or `end` is reached, so trailing garbage ends the listing rather
+/// than failing it.
+pub fn parse_cbfs_at(
+    data: &[u8],
+    start: u32,
+    end: u32,
+    align: u32,
+) -> Result<Vec<CbfsFile>, LayoutError> {
+    let end = (end as usize).min(data.len());
+    let align = align.max(1) as usize;
+    let mut files = Vec::new();
+    let mut pos = start as usize;
+
+    while pos < end {
+        let Ok((header, _)) = FileHeader::ref_from_prefix(&data[pos..end]) else {
+            break;
+        };
+        if &header.magic != FILE_MAGIC {
+            break;
+        }
+
+        let data_offset = header.offset.get() as usize;
+        let len = header.len.get() as usize;
+        let name_start = pos + size_of::<FileHeader>();
+        let data_start = pos + data_offset;
+        if data_start < name_start || data_start + len > end {
+            break;
+        }
+
+        let name = &data[name_start..data_start];
+        let name_len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        files.push(CbfsFile {
+            name: String::from_utf8_lossy(&name[..name_len]).to_string(),
+            file_type: header.file_type.get(),
+            offset: data_start as u32,
+            size: len as u32,
+        });
+
+        pos = (data_start + len).next_multiple_of(align);
+    }
+
+    if files.is_empty() {
+        return Err(LayoutError::NoCbfs);
+    }
+    Ok(files)
+}
+
+/// Search the image for a CBFS master header
+fn find_master_header(data: &[u8]) -> Option<&MasterHeader> {
+    (0..=data.len().saturating_sub(size_of::<MasterHeader>()))
+        .step_by(4)
+        .find_map(|offset| {
+            let (header, _) = MasterHeader::ref_from_prefix(&data[offset..]).ok()?;
+            let version = header.version.get();
+            (header.magic.get() == HEADER_MAGIC
+                && (version == HEADER_VERSION1 || version == HEADER_VERSION2))
+                .then_some(header)
+        })
+}
+
+/// Locate a CBFS in a flash image and list its files
+///
+/// With an FMAP, the CBFS is the area named `region` (default
+/// [`DEFAULT_CBFS_REGION`]). Without one, the image is scanned for the
+/// master header, whose `offset` field points at the first file.
+pub fn list_cbfs(data: &[u8], region: Option<&str>) -> Result<Vec<CbfsFile>, LayoutError> {
+    if let Ok(fmap) = parse_fmap(data) {
+        let name = region.unwrap_or(DEFAULT_CBFS_REGION);
+        let area = fmap.find_region(name).ok_or(LayoutError::RegionNotFound)?;
+        return parse_cbfs_at(data, area.start, area.end + 1, DEFAULT_ALIGN);
+    }
+
+    if region.is_some() {
+        // Region names come from the FMAP
+        return Err(LayoutError::InvalidFmapSignature);
+    }
+
+    let header = find_master_header(data).ok_or(LayoutError::NoCbfs)?;
+    let align = match header.align.get() {
+        0 => DEFAULT_ALIGN,
+        align => align,
+    };
+    let end = header
+        .romsize
+        .get()
+        .saturating_sub(header.bootblocksize.get());
+    parse_cbfs_at(data, header.offset.get(), end, align)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{Layout, Region, build_fmap};
+    use std::vec;
+
+    /// Append a CBFS file at `pos`, returning the next aligned position
+    fn put_file(data: &mut [u8], pos: usize, name: &str, file_type: u32, len: usize) -> usize {
+        let data_offset = (24 + name.len() + 1).next_multiple_of(16);
+        data[pos..pos + 8].copy_from_slice(FILE_MAGIC);
+        data[pos + 8..pos + 12].copy_from_slice(&(len as u32).to_be_bytes());
+        data[pos + 12..pos + 16].copy_from_slice(&file_type.to_be_bytes());
+        data[pos + 16..pos + 20].copy_from_slice(&0u32.to_be_bytes());
+        data[pos + 20..pos + 24].copy_from_slice(&(data_offset as u32).to_be_bytes());
+        data[pos + 24..pos + 24 + name.len()].copy_from_slice(name.as_bytes());
+        data[pos + 24 + name.len()..pos + data_offset].fill(0);
+        (pos + data_offset + len).next_multiple_of(64)
+    }
+
+    #[test]
+    fn test_parse_cbfs() {
+        let mut data = vec![0xFF; 0x1000];
+        let config = put_file(
+            &mut data,
+            0x100,
+            "fallback/romstage",
+            file_type::STAGE,
+            0x80,
+        );
+        let pos = put_file(&mut data, config, "config", file_type::RAW, 0x10);
+        put_file(&mut data, pos, "", file_type::NULL, 0x1000 - pos - 0x30);
+
+        let files = parse_cbfs_at(&data, 0x100, 0x1000, 64).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].name, "fallback/romstage");
+        assert_eq!(files[0].type_name(), "stage");
+        assert_eq!(files[0].offset, 0x130);
+        assert_eq!(files[0].size, 0x80);
+        assert_eq!(files[1].name, "config");
+        assert_eq!(files[1].offset, config as u32 + 0x20);
+        assert!(files[2].is_empty());
+
+        assert_eq!(parse_cbfs_at(&data, 0, 0x100, 64), Err(LayoutError::NoCbfs));
+    }
+
+    #[test]
+    fn test_list_cbfs_master_header() {
+        let mut data = vec![0xFF; 0x1000];
+        let header = [
+            HEADER_MAGIC,
+            HEADER_VERSION2,
+            0x1000, // romsize
+            0x200,  // bootblocksize
+            64,     // align
+            0x40,   // offset
+            1,      // architecture
+            0,
+        ];
+        for (i, word) in header.iter().enumerate() {
+            data[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+        }
+        put_file(&mut data, 0x40, "payload", file_type::SELF, 0x100);
+
+        let files = list_cbfs(&data, None).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].name, "payload");
+        assert_eq!(files[0].type_name(), "simple elf");
+
+        assert_eq!(list_cbfs(&[0xFF; 0x100], None), Err(LayoutError::NoCbfs));
+
+        // A master header ending exactly at the end of the image is found
+        let tail = data.len() - size_of::<MasterHeader>();
+        data.copy_within(0..size_of::<MasterHeader>(), tail);
+        assert!(find_master_header(&data[4..]).is_some());
+        assert!(find_master_header(&data[tail..]).is_some());
+    }
+
+    #[test]
+    fn test_list_cbfs_fmap() {
+        let mut layout = Layout::new();
+        layout.chip_size = Some(0x4000);
+        layout.add_region(Region::new("FMAP", 0x0000, 0x0FFF));
+        layout.add_region(Region::new("COREBOOT", 0x1000, 0x2FFF));
+        layout.add_region(Region::new("FW_MAIN_A", 0x3000, 0x3FFF));
+
+        let mut data = vec![0xFF; 0x4000];
+        let fmap = build_fmap(&layout).unwrap();
+        data[..fmap.len()].copy_from_slice(&fmap);
+        put_file(&mut data, 0x1000, "bootblock", file_type::BOOTBLOCK, 0x200);
+        put_file(&mut data, 0x3000, "vbiosA", file_type::OPTIONROM, 0x100);
+
+        let files = list_cbfs(&data, None).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].name, "bootblock");
+
+        let files = list_cbfs(&data, Some("FW_MAIN_A")).unwrap();
+        assert_eq!(files[0].type_name(), "optionrom");
+
+        assert_eq!(
+            list_cbfs(&data, Some("RW_LEGACY")),
+            Err(LayoutError::RegionNotFound)
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! flashrom layout file format
+//!
+//! flashrom's `-l` option takes a plain text layout with one region per
+//! line:
+//!
+//! ```text
+//! 0x000000:0x000fff descriptor
+//! 0x001000:0x7fffff bios
+//! ```
+//!
+//! Addresses are hexadecimal (the `0x` prefix is optional) and inclusive.
+//! Names containing whitespace are quoted, as flashrom and flashprog accept.
+//! The format has no room for flags, so readonly/dangerous markers and the
+//! chip size are lost on export.
+
+use std::format;
+use std::fs;
+use std::path::Path;
+use std::string::{String, ToString};
+
+use super::{Layout, LayoutError, LayoutSource, Region};
+
+/// Parse a hexadecimal address with optional `0x` prefix
+fn parse_address(s: &str) -> Result<u32, LayoutError> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u32::from_str_radix(hex, 16).map_err(|_| LayoutError::ParseError)
+}
+
+impl Layout {
+    /// Load a layout from a flashrom layout file
+    pub fn from_flashrom_file(path: impl AsRef<Path>) -> Result<Self, LayoutError> {
+        let content = fs::read_to_string(path).map_err(|e| LayoutError::IoError(e.to_string()))?;
+        Self::from_flashrom_str(&content)
+    }
+
+    /// Parse a layout in flashrom's `-l` format
+    pub fn from_flashrom_str(content: &str) -> Result<Self, LayoutError> {
+        let mut layout = Layout::with_source(LayoutSource::Flashrom);
+
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (range, name) = line
+                .split_once(char::is_whitespace)
+                .ok_or(LayoutError::ParseError)?;
+            let (start, end) = range.split_once(':').ok_or(LayoutError::ParseError)?;
+            let (start, end) = (parse_address(start)?, parse_address(end)?);
+            if start > end {
+                return Err(LayoutError::InvalidRegion);
+            }
+
+            let name = name.trim();
+            let name = match name.strip_prefix('"') {
+                Some(quoted) => quoted.strip_suffix('"').ok_or(LayoutError::ParseError)?,
+                None => name,
+            };
+            if name.is_empty() {
+                return Err(LayoutError::ParseError);
+            }
+            if layout.find_region(name).is_some() {
+                return Err(LayoutError::DuplicateRegionName);
+            }
+
+            layout.add_region(Region::new(name, start, end));
+        }
+
+        layout.sort_by_address();
+        layout.check_overlaps()?;
+        Ok(layout)
+    }
+
+    /// Convert layout to flashrom's `-l` format
+    ///
+    /// All regions are written, whether or not they are included.
+    pub fn to_flashrom_string(&self) -> String {
+        let mut output = String::new();
+        for region in &self.regions {
+            let name = if region.name.contains(char::is_whitespace) {
+                format!("\"{}\"", region.name)
+            } else {
+                region.name.clone()
+            };
+            output.push_str(&format!(
+                "0x{:06x}:0x{:06x} {}\n",
+                region.start, region.end, name
+            ));
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_flashrom_string() {
+        let mut layout = Layout::new();
+        layout.add_region(Region::new("descriptor", 0x000000, 0x000FFF));
+        layout.add_region(Region::new("bios", 0x001000, 0x0FFFFF));
+        layout.add_region(Region::new("Platform Data", 0x100000, 0x1FFFFFF));
+        layout.include_region("bios").unwrap();
+
+        assert_eq!(
+            layout.to_flashrom_string(),
+            "0x000000:0x000fff descriptor\n\
+             0x001000:0x0fffff bios\n\
+             0x100000:0x1ffffff \"Platform Data\"\n"
+        );
+    }
+
+    #[test]
+    fn test_flashrom_round_trip() {
+        let toml = r#"
+[layout]
+chip_size = "16 MiB"
+
+[[region]]
+name = "descriptor"
+start = 0x000000
+end = 0x000FFF
+readonly = true
+
+[[region]]
+name = "me"
+start = 0x001000
+end = 0x5FFFFF
+
+[[region]]
+name = "bios"
+start = 0x600000
+end = 0xFFFFFF
+"#;
+        let layout = Layout::from_toml_str(toml).unwrap();
+        let parsed = Layout::from_flashrom_str(&layout.to_flashrom_string()).unwrap();
+
+        assert_eq!(parsed.source, LayoutSource::Flashrom);
+        assert_eq!(parsed.len(), layout.len());
+        for (a, b) in parsed.regions.iter().zip(&layout.regions) {
+            assert_eq!((&a.name, a.start, a.end), (&b.name, b.start, b.end));
+        }
+    }
+
+    #[test]
+    fn test_parse_flashrom_layout() {
+        let layout = Layout::from_flashrom_str("00001000:00001fff gbe\n0:fff fd\n\n").unwrap();
+        assert_eq!(layout.regions[0].name, "fd");
+        assert_eq!(layout.regions[1].start, 0x1000);
+
+        assert_eq!(
+            Layout::from_flashrom_str("0x1000:0x0fff bios").map(|_| ()),
+            Err(LayoutError::InvalidRegion)
+        );
+        assert_eq!(
+            Layout::from_flashrom_str("0x0:0xfff").map(|_| ()),
+            Err(LayoutError::ParseError)
+        );
+        assert_eq!(
+            Layout::from_flashrom_str("0x0:0xfff a\n0x1000:0x1fff A").map(|_| ()),
+            Err(LayoutError::DuplicateRegionName)
+        );
+    }
+}
@@ -1,4 +1,4 @@
-//! FMAP (Flash Map) parsing
+//! FMAP (Flash Map) parsing and generation
 //!
 //! FMAP is a format used primarily by Chromebook firmware to describe
 //! flash regions. The FMAP structure can be embedded anywhere in the
@@ -9,6 +9,7 @@
 use std::format;
 use std::string::{String, ToString};
 use std::vec;
+use std::vec::Vec;
 
 use zerocopy::byteorder::little_endian::{U16 as U16LE, U32 as U32LE, U64 as U64LE};
 use zerocopy::{FromBytes, Immutable, KnownLayout, Unaligned};
@@ -21,11 +22,19 @@ const FMAP_SIGNATURE: &[u8; 8] = b"__FMAP__";
 /// Maximum supported FMAP major version
 const FMAP_VER_MAJOR: u8 = 1;
 
+/// FMAP minor version written by `build_fmap`
+const FMAP_VER_MINOR: u8 = 1;
+
+/// Size of the name fields, including the terminating NUL
+const FMAP_NAME_LEN: usize = 32;
+
+/// Maximum number of areas accepted by `validate_fmap`
+const FMAP_MAX_AREAS: usize = 256;
+
 /// Size of FMAP header
 const FMAP_HEADER_SIZE: usize = 56;
 
 /// Size of FMAP area
-#[allow(dead_code)]
 const FMAP_AREA_SIZE: usize = 42;
 
 /// Minimum stride for binary search
@@ -112,7 +121,7 @@ pub fn validate_fmap(data: &[u8]) -> Result<(), LayoutError> {
 
     // Check that nareas is reasonable
     let nareas = header.nareas.get() as usize;
-    if nareas > 256 {
+    if nareas > FMAP_MAX_AREAS {
         // Sanity check - more than 256 areas is unreasonable
         return Err(LayoutError::InvalidFmapSignature);
     }
@@ -314,6 +323,7 @@ pub fn parse_fmap_at(data: &[u8], offset: usize) -> Result<Layout, LayoutError>
             });
 
     layout.sort_by_address();
+    layout.check_overlaps()?;
     Ok(layout)
 }
 
@@ -323,6 +333,60 @@ fn parse_fmap_string(bytes: &[u8]) -> String {
     String::from_utf8_lossy(&bytes[..end]).to_string()
 }
 
+/// Encode an FMAP name field (NUL-terminated, NUL-padded)
+fn fmap_name_field(name: &str) -> Result<[u8; FMAP_NAME_LEN], LayoutError> {
+    if name.len() >= FMAP_NAME_LEN {
+        return Err(LayoutError::NameTooLong(name.to_string()));
+    }
+
+    let mut field = [0u8; FMAP_NAME_LEN];
+    field[..name.len()].copy_from_slice(name.as_bytes());
+    Ok(field)
+}
+
+/// Serialize a layout into an FMAP binary
+///
+/// The FMAP covers `layout.chip_size`, or up to the end of the last region
+/// if the layout doesn't declare one. The regions must fit within that size
+/// and must not overlap. Read-only regions get the `RO` flag. The FMAP base
+/// is 0, as coreboot's fmaptool writes it.
+pub fn build_fmap(layout: &Layout) -> Result<Vec<u8>, LayoutError> {
+    let size = match layout.chip_size {
+        Some(size) => size,
+        // A region ending at the last u32 address has no FMAP size
+        None => layout.regions.iter().try_fold(0, |size: u32, r| {
+            let end = r.end.checked_add(1).ok_or(LayoutError::RegionOutOfBounds)?;
+            Ok(size.max(end))
+        })?,
+    };
+    layout.validate(size)?;
+
+    if layout.regions.len() > FMAP_MAX_AREAS {
+        return Err(LayoutError::ParseError);
+    }
+
+    let name = fmap_name_field(layout.name.as_deref().unwrap_or("FMAP"))?;
+
+    let mut data = Vec::with_capacity(FMAP_HEADER_SIZE + layout.regions.len() * FMAP_AREA_SIZE);
+    data.extend_from_slice(FMAP_SIGNATURE);
+    data.push(FMAP_VER_MAJOR);
+    data.push(FMAP_VER_MINOR);
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&size.to_le_bytes());
+    data.extend_from_slice(&name);
+    data.extend_from_slice(&(layout.regions.len() as u16).to_le_bytes());
+
+    for region in &layout.regions {
+        let area_flags = if region.readonly { flags::RO } else { 0 };
+        data.extend_from_slice(&region.start.to_le_bytes());
+        data.extend_from_slice(&region.size().to_le_bytes());
+        data.extend_from_slice(&fmap_name_field(&region.name)?);
+        data.extend_from_slice(&area_flags.to_le_bytes());
+    }
+
+    Ok(data)
+}
+
 /// Check if data appears to contain an FMAP
 pub fn has_fmap(data: &[u8]) -> bool {
     find_fmap(data).is_some()
@@ -333,6 +397,14 @@ pub fn fmap_offset(data: &[u8]) -> Option<usize> {
     find_fmap(data)
 }
 
+/// Get the flash size recorded in the header of the FMAP in data
+pub fn fmap_size(data: &[u8]) -> Option<u32> {
+    let offset = find_fmap(data)?;
+    FmapHeader::ref_from_prefix(&data[offset..])
+        .ok()
+        .map(|(header, _)| header.size.get())
+}
+
 impl Layout {
     /// Parse layout from FMAP in raw data
     pub fn from_fmap(data: &[u8]) -> Result<Self, LayoutError> {
@@ -410,6 +482,63 @@ mod tests {
         assert_eq!(fmap_offset(&data), Some(0x100));
     }
 
+    #[test]
+    fn test_build_fmap_round_trip() {
+        let mut layout = Layout::new();
+        layout.name = Some("TEST_FMAP".into());
+        layout.chip_size = Some(0x10000);
+        let mut ro = Region::new("RO_SECTION", 0x0000, 0x7FFF);
+        ro.readonly = true;
+        layout.add_region(ro);
+        layout.add_region(Region::new("FMAP", 0x8000, 0x8FFF));
+        layout.add_region(Region::new("RW_SECTION", 0x9000, 0xFFFF));
+
+        let fmap = build_fmap(&layout).unwrap();
+        assert_eq!(fmap.len(), FMAP_HEADER_SIZE + 3 * FMAP_AREA_SIZE);
+
+        assert_eq!(fmap_size(&fmap), Some(0x10000));
+        let parsed = parse_fmap(&fmap).unwrap();
+        // The FMAP may cover less than the chip, so it doesn't set the size
+        assert_eq!(parsed.chip_size, None);
+        assert!(parsed.name.as_ref().unwrap().contains("TEST_FMAP"));
+        assert_eq!(parsed.regions.len(), 3);
+        for (a, b) in parsed.regions.iter().zip(&layout.regions) {
+            assert_eq!(a.name, b.name);
+            assert_eq!((a.start, a.end, a.readonly), (b.start, b.end, b.readonly));
+        }
+    }
+
+    #[test]
+    fn test_build_fmap_invalid() {
+        let mut layout = Layout::new();
+        layout.chip_size = Some(0x10000);
+        layout.add_region(Region::new("A", 0x0000, 0x8FFF));
+        layout.add_region(Region::new("B", 0x8000, 0xFFFF));
+        assert_eq!(
+            build_fmap(&layout),
+            Err(LayoutError::OverlappingRegions {
+                a: "A".into(),
+                b: "B".into(),
+            })
+        );
+
+        layout.regions[0].end = 0x7FFF;
+        layout.regions[1].end = 0x10000;
+        assert_eq!(build_fmap(&layout), Err(LayoutError::RegionOutOfBounds));
+
+        layout.regions[1].end = 0xFFFF;
+        layout.regions[1].name = "X".repeat(FMAP_NAME_LEN);
+        assert!(matches!(
+            build_fmap(&layout),
+            Err(LayoutError::NameTooLong(_))
+        ));
+
+        layout.regions[1].name = "B".into();
+        layout.chip_size = None;
+        layout.regions[1].end = u32::MAX;
+        assert_eq!(build_fmap(&layout), Err(LayoutError::RegionOutOfBounds));
+    }
+
     #[test]
     fn test_parse_fmap() {
         let data = make_test_fmap();
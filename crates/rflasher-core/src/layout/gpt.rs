@@ -0,0 +1,413 @@
+//! GPT (GUID Partition Table) parsing
+//!
+//! Storage-style flash images (eMMC dumps, SPI NOR holding a disk image)
+//! describe their contents with a GPT instead of an IFD or FMAP. The table
+//! starts with a protective MBR at LBA 0, followed by the GPT header at
+//! LBA 1 and the partition entry array it points at. A backup header lives
+//! in the last LBA of the disk.
+//!
+//! Both 512 and 4096 byte logical blocks are supported. If the primary
+//! header is corrupt, the backup header at the end of the data is used.
+//!
+//! Reference: UEFI Specification, chapter 5 "GUID Partition Table (GPT) Disk Layout"
+
+use std::format;
+use std::string::{String, ToString};
+
+use zerocopy::byteorder::little_endian::{U16 as U16LE, U32 as U32LE, U64 as U64LE};
+use zerocopy::{FromBytes, Immutable, KnownLayout, Unaligned};
+
+use super::{Layout, LayoutError, LayoutSource, Region};
+
+/// GPT header signature
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+
+/// Supported logical block sizes, in probe order
+const LBA_SIZES: [usize; 2] = [512, 4096];
+
+/// Bytes needed to find the primary header for any supported block size
+pub const GPT_HEADER_PROBE_SIZE: usize = 2 * 4096;
+
+/// Offset of the MBR partition table
+const MBR_PARTITION_TABLE: usize = 446;
+
+/// MBR partition type of a GPT protective partition
+const MBR_TYPE_PROTECTIVE: u8 = 0xEE;
+
+/// Upper bound on the partition entry array size
+const GPT_MAX_ENTRIES_SIZE: usize = 1024 * 1024;
+
+/// Partition attribute: required by the platform
+const GPT_ATTR_REQUIRED: u64 = 1 << 0;
+
+/// GPT header (92 bytes)
+///
+/// All multi-byte fields are little-endian.
+#[repr(C)]
+#[derive(FromBytes, KnownLayout, Immutable, Unaligned)]
+struct GptHeader {
+    signature: [u8; 8],
+    #[allow(dead_code)]
+    revision: U32LE,
+    header_size: U32LE,
+    header_crc32: U32LE,
+    #[allow(dead_code)]
+    reserved: U32LE,
+    my_lba: U64LE,
+    alternate_lba: U64LE,
+    #[allow(dead_code)]
+    first_usable_lba: U64LE,
+    #[allow(dead_code)]
+    last_usable_lba: U64LE,
+    disk_guid: [u8; 16],
+    partition_entry_lba: U64LE,
+    num_partition_entries: U32LE,
+    partition_entry_size: U32LE,
+    partition_entries_crc32: U32LE,
+}
+
+/// GPT partition entry (128 bytes)
+///
+/// All multi-byte fields are little-endian.
+#[repr(C)]
+#[derive(FromBytes, KnownLayout, Immutable, Unaligned)]
+struct GptEntry {
+    type_guid: [u8; 16],
+    #[allow(dead_code)]
+    unique_guid: [u8; 16],
+    first_lba: U64LE,
+    last_lba: U64LE,
+    attributes: U64LE,
+    name: [U16LE; 36],
+}
+
+/// CRC32 (IEEE 802.3) as used by GPT
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// Format a GUID in its registry form (first three fields little-endian)
+fn format_guid(guid: &[u8; 16]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+        u32::from_le_bytes([guid[0], guid[1], guid[2], guid[3]]),
+        u16::from_le_bytes([guid[4], guid[5]]),
+        u16::from_le_bytes([guid[6], guid[7]]),
+        guid[8],
+        guid[9],
+        guid[10],
+        guid[11],
+        guid[12],
+        guid[13],
+        guid[14],
+        guid[15]
+    )
+}
+
+/// Check for a protective (or hybrid) MBR
+fn has_protective_mbr(data: &[u8]) -> bool {
+    if data.len() < 512 || data[510..512] != [0x55, 0xAA] {
+        return false;
+    }
+    (0..4).any(|i| data[MBR_PARTITION_TABLE + i * 16 + 4] == MBR_TYPE_PROTECTIVE)
+}
+
+/// Find the logical block size from the position of the primary header
+fn find_lba_size(data: &[u8]) -> Option<usize> {
+    LBA_SIZES
+        .into_iter()
+        .find(|&lba| data.get(lba..lba + GPT_SIGNATURE.len()) == Some(GPT_SIGNATURE))
+}
+
+/// Check if data contains a GPT
+///
+/// This only checks for the protective MBR and the header signature; use
+/// [`parse_gpt`] to validate the table.
+pub fn has_gpt(data: &[u8]) -> bool {
+    has_protective_mbr(data) && find_lba_size(data).is_some()
+}
+
+/// Number of bytes from the start of the disk needed to parse the primary GPT
+///
+/// `data` must hold at least [`GPT_HEADER_PROBE_SIZE`] bytes, or the whole
+/// disk if it is smaller. This lets callers reading from a flash chip fetch
+/// the header first and then exactly as much as the partition entries need.
+pub fn gpt_size(data: &[u8]) -> Result<usize, LayoutError> {
+    if !has_protective_mbr(data) {
+        return Err(LayoutError::InvalidGptSignature);
+    }
+    let lba_size = find_lba_size(data).ok_or(LayoutError::InvalidGptSignature)?;
+    let (header, _) =
+        GptHeader::ref_from_prefix(&data[lba_size..]).map_err(|_| LayoutError::ParseError)?;
+    let (start, len) = entries_range(header, lba_size)?;
+    Ok(start.max(2 * lba_size) + len)
+}
+
+/// Location of the partition entry array as (offset, length)
+fn entries_range(header: &GptHeader, lba_size: usize) -> Result<(usize, usize), LayoutError> {
+    let entry_size = header.partition_entry_size.get() as usize;
+    if entry_size < size_of::<GptEntry>() || !entry_size.is_multiple_of(8) {
+        return Err(LayoutError::ParseError);
+    }
+    let len = entry_size
+        .checked_mul(header.num_partition_entries.get() as usize)
+        .filter(|&len| len <= GPT_MAX_ENTRIES_SIZE)
+        .ok_or(LayoutError::ParseError)?;
+    let start = usize::try_from(header.partition_entry_lba.get())
+        .ok()
+        .and_then(|lba| lba.checked_mul(lba_size))
+        .ok_or(LayoutError::ParseError)?;
+    Ok((start, len))
+}
+
+/// Parse a GPT from raw data
+///
+/// The data must start at the beginning of the disk (the protective MBR).
+pub fn parse_gpt(data: &[u8]) -> Result<Layout, LayoutError> {
+    if !has_protective_mbr(data) {
+        return Err(LayoutError::InvalidGptSignature);
+    }
+    let lba_size = find_lba_size(data).ok_or(LayoutError::InvalidGptSignature)?;
+
+    let primary = parse_gpt_at(data, lba_size, lba_size);
+    if primary.is_ok() {
+        return primary;
+    }
+
+    let backup_offset = (data.len() / lba_size).saturating_sub(1) * lba_size;
+    if backup_offset > lba_size
+        && let Ok(layout) = parse_gpt_at(data, backup_offset, lba_size)
+    {
+        log::warn!("Primary GPT header is invalid, using backup header");
+        return Ok(layout);
+    }
+    primary
+}
+
+/// Parse a GPT whose header is at a specific offset
+pub fn parse_gpt_at(data: &[u8], offset: usize, lba_size: usize) -> Result<Layout, LayoutError> {
+    let header_data = data.get(offset..).ok_or(LayoutError::ParseError)?;
+    let (header, _) =
+        GptHeader::ref_from_prefix(header_data).map_err(|_| LayoutError::InvalidGptSignature)?;
+    if &header.signature != GPT_SIGNATURE {
+        return Err(LayoutError::InvalidGptSignature);
+    }
+
+    let header_size = header.header_size.get() as usize;
+    if header_size < size_of::<GptHeader>() || header_size > lba_size.min(header_data.len()) {
+        return Err(LayoutError::ParseError);
+    }
+    let mut raw = header_data[..header_size].to_vec();
+    raw[16..20].fill(0);
+    if crc32(&raw) != header.header_crc32.get() {
+        return Err(LayoutError::GptChecksumMismatch);
+    }
+    if header.my_lba.get().checked_mul(lba_size as u64) != Some(offset as u64) {
+        return Err(LayoutError::ParseError);
+    }
+
+    let (start, len) = entries_range(header, lba_size)?;
+    let entries = start
+        .checked_add(len)
+        .and_then(|end| data.get(start..end))
+        .ok_or(LayoutError::ParseError)?;
+    if crc32(entries) != header.partition_entries_crc32.get() {
+        return Err(LayoutError::GptChecksumMismatch);
+    }
+
+    let mut layout = Layout::with_source(LayoutSource::Gpt);
+    layout.name = Some(format_guid(&header.disk_guid));
+    let last_lba = header.my_lba.get().max(header.alternate_lba.get());
+    layout.chip_size = last_lba
+        .saturating_add(1)
+        .checked_mul(lba_size as u64)
+        .and_then(|size| u32::try_from(size).ok());
+
+    let entry_size = header.partition_entry_size.get() as usize;
+    for (i, raw) in entries.chunks_exact(entry_size).enumerate() {
+        let (entry, _) = GptEntry::ref_from_prefix(raw).map_err(|_| LayoutError::ParseError)?;
+        if entry.type_guid == [0; 16] {
+            continue;
+        }
+
+        let first = entry.first_lba.get();
+        let last = entry.last_lba.get();
+        if last < first {
+            return Err(LayoutError::InvalidRegion);
+        }
+        let to_offset = |lba: u64| {
+            lba.checked_mul(lba_size as u64)
+                .and_then(|addr| u32::try_from(addr).ok())
+                .ok_or(LayoutError::RegionOutOfBounds)
+        };
+        let region_start = to_offset(first)?;
+        let region_end = to_offset(last.saturating_add(1))?.wrapping_sub(1);
+
+        let name_len = entry
+            .name
+            .iter()
+            .position(|c| c.get() == 0)
+            .unwrap_or(entry.name.len());
+        let mut name: String = char::decode_utf16(entry.name[..name_len].iter().map(|c| c.get()))
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+        if name.is_empty() {
+            name = format!("part{}", i + 1);
+        }
+
+        let mut region = Region::new(name, region_start, region_end);
+        region.dangerous = entry.attributes.get() & GPT_ATTR_REQUIRED != 0;
+        layout.add_region(region);
+    }
+
+    layout.sort_by_address();
+    layout.check_overlaps()?;
+    Ok(layout)
+}
+
+impl Layout {
+    /// Parse layout from a GPT in raw data
+    pub fn from_gpt(data: &[u8]) -> Result<Self, LayoutError> {
+        parse_gpt(data)
+    }
+
+    /// Parse layout from a GPT in a file
+    pub fn from_gpt_file(path: impl AsRef<std::path::Path>) -> Result<Self, LayoutError> {
+        let data = std::fs::read(path).map_err(|e| LayoutError::IoError(e.to_string()))?;
+        parse_gpt(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+    use std::vec::Vec;
+
+    const DISK_SIZE: usize = 0x10000;
+    const NUM_ENTRIES: usize = 128;
+
+    fn put_entry(entries: &mut [u8], index: usize, name: &str, first: u64, last: u64) {
+        let entry = &mut entries[index * 128..(index + 1) * 128];
+        entry[..16].fill(0xAB);
+        entry[32..40].copy_from_slice(&first.to_le_bytes());
+        entry[40..48].copy_from_slice(&last.to_le_bytes());
+        for (i, c) in name.encode_utf16().enumerate() {
+            entry[56 + i * 2..58 + i * 2].copy_from_slice(&c.to_le_bytes());
+        }
+    }
+
+    fn put_header(data: &mut [u8], my_lba: u64, alternate_lba: u64, entries_crc: u32) {
+        let offset = my_lba as usize * 512;
+        let header = &mut data[offset..offset + 92];
+        header[..8].copy_from_slice(GPT_SIGNATURE);
+        header[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        header[12..16].copy_from_slice(&92u32.to_le_bytes());
+        header[24..32].copy_from_slice(&my_lba.to_le_bytes());
+        header[32..40].copy_from_slice(&alternate_lba.to_le_bytes());
+        header[40..48].copy_from_slice(&34u64.to_le_bytes());
+        header[48..56].copy_from_slice(&(alternate_lba.min(my_lba) + 0x7F).to_le_bytes());
+        header[56..72].copy_from_slice(&[0x11; 16]);
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        header[80..84].copy_from_slice(&(NUM_ENTRIES as u32).to_le_bytes());
+        header[84..88].copy_from_slice(&128u32.to_le_bytes());
+        header[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+        let crc = crc32(header);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    fn build_disk() -> Vec<u8> {
+        let mut data = vec![0u8; DISK_SIZE];
+        data[MBR_PARTITION_TABLE + 4] = MBR_TYPE_PROTECTIVE;
+        data[510] = 0x55;
+        data[511] = 0xAA;
+
+        let mut entries = vec![0u8; NUM_ENTRIES * 128];
+        put_entry(&mut entries, 0, "boot", 0x40, 0x5F);
+        put_entry(&mut entries, 1, "", 0x22, 0x3F);
+        data[1024..1024 + entries.len()].copy_from_slice(&entries);
+
+        let entries_crc = crc32(&entries);
+        let last_lba = (DISK_SIZE / 512 - 1) as u64;
+        put_header(&mut data, 1, last_lba, entries_crc);
+        put_header(&mut data, last_lba, 1, entries_crc);
+        data
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_parse_gpt() {
+        let data = build_disk();
+        assert!(has_gpt(&data));
+        assert_eq!(gpt_size(&data).unwrap(), 1024 + NUM_ENTRIES * 128);
+
+        let layout = parse_gpt(&data).unwrap();
+        assert_eq!(layout.source, LayoutSource::Gpt);
+        assert_eq!(layout.chip_size, Some(DISK_SIZE as u32));
+        assert_eq!(
+            layout.name.as_deref(),
+            Some("11111111-1111-1111-1111-111111111111")
+        );
+        assert_eq!(layout.len(), 2);
+        assert_eq!(layout.regions[0].name, "part2");
+        assert_eq!(
+            (layout.regions[0].start, layout.regions[0].end),
+            (0x4400, 0x7FFF)
+        );
+        assert_eq!(layout.regions[1].name, "boot");
+        assert_eq!(
+            (layout.regions[1].start, layout.regions[1].end),
+            (0x8000, 0xBFFF)
+        );
+    }
+
+    #[test]
+    fn test_parse_gpt_backup() {
+        let mut data = build_disk();
+        data[512 + 40] ^= 0xFF;
+        assert_eq!(
+            parse_gpt_at(&data, 512, 512).map(|_| ()),
+            Err(LayoutError::GptChecksumMismatch)
+        );
+
+        let layout = parse_gpt(&data).unwrap();
+        assert_eq!(layout.len(), 2);
+
+        data[DISK_SIZE - 512 + 40] ^= 0xFF;
+        assert_eq!(
+            parse_gpt(&data).map(|_| ()),
+            Err(LayoutError::GptChecksumMismatch)
+        );
+    }
+
+    #[test]
+    fn test_parse_gpt_invalid() {
+        let mut data = build_disk();
+        data[1024] ^= 0xFF;
+        assert_eq!(
+            parse_gpt_at(&data, 512, 512).map(|_| ()),
+            Err(LayoutError::GptChecksumMismatch)
+        );
+
+        let mut data = build_disk();
+        data[510] = 0;
+        assert!(!has_gpt(&data));
+        assert_eq!(
+            parse_gpt(&data).map(|_| ()),
+            Err(LayoutError::InvalidGptSignature)
+        );
+
+        assert!(!has_gpt(&[0xFF; 0x2000]));
+    }
+}
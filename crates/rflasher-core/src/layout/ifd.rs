@@ -5,7 +5,9 @@
 //!
 //! Reference: flashprog/ich_descriptors.c
 
-use std::string::ToString;
+use std::format;
+use std::string::{String, ToString};
+use std::vec::Vec;
 
 use super::{Layout, LayoutError, LayoutSource, Region};
 
@@ -127,9 +129,181 @@ pub fn parse_ifd(data: &[u8]) -> Result<Layout, LayoutError> {
     }
 
     layout.sort_by_address();
+    layout.check_overlaps()?;
     Ok(layout)
 }
 
+/// A decoded bit field of a strap dword
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IfdStrapField {
+    /// Field label from the chipset documentation, e.g. `BBBS`
+    pub name: &'static str,
+    /// What the field configures
+    pub description: &'static str,
+    /// Field value, shifted down to bit 0
+    pub value: u32,
+}
+
+/// A single strap dword from the flash descriptor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IfdStrap {
+    /// Strap label, e.g. `PCHSTRP0` or `MCHSTRP1`
+    pub name: String,
+    /// Offset of the strap dword within the descriptor
+    pub offset: u32,
+    /// Raw strap value
+    pub value: u32,
+    /// Decoded fields, empty if the strap isn't known for the platform
+    pub fields: Vec<IfdStrapField>,
+}
+
+/// Platform whose strap layout is decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrapPlatform {
+    /// Intel 5 series (Ibex Peak) PCH
+    IbexPeak,
+}
+
+impl StrapPlatform {
+    /// Guess the platform from the descriptor map, like flashprog does
+    ///
+    /// Only Ibex Peak is recognized: no ICC register init (ICCRIBA) and
+    /// 11 to 16 PCH straps.
+    fn guess(flmap1: u32, flmap2: u32) -> Option<Self> {
+        let isl = flmap1 >> 24;
+        let iccriba = (flmap2 >> 16) & 0xFF;
+        (iccriba == 0 && (11..=16).contains(&isl)).then_some(StrapPlatform::IbexPeak)
+    }
+
+    /// Human readable platform name
+    pub fn name(&self) -> &'static str {
+        match self {
+            StrapPlatform::IbexPeak => "Intel 5 series (Ibex Peak)",
+        }
+    }
+}
+
+/// Bit field of a strap dword: label, description, shift and width
+type StrapFieldDef = (&'static str, &'static str, u32, u32);
+
+/// Ibex Peak PCHSTRP0 (flashprog ich_desc_south_strap.ibex)
+const IBEX_PEAK_PCHSTRP0: &[StrapFieldDef] = &[
+    ("cs_ss2", "Chipset configuration softstrap 2", 1, 1),
+    ("SMB_EN", "Intel ME SMBus select", 7, 1),
+    ("SML0_EN", "SMLink0 segment enable", 8, 1),
+    ("SML1_EN", "SMLink1 segment enable", 9, 1),
+    ("SML1FRQ", "SMLink1 frequency (1 = 100 kHz)", 10, 2),
+    ("SMB0FRQ", "Intel ME SMBus frequency (1 = 100 kHz)", 12, 2),
+    ("SML0FRQ", "SMLink0 frequency (1 = 100 kHz)", 14, 2),
+    (
+        "LANPHYPC_GP12_SEL",
+        "GPIO12 used as LAN_PHY_PWR_CTRL",
+        20,
+        1,
+    ),
+    ("cs_ss1", "Chipset configuration softstrap 1", 21, 1),
+    ("DMI_REQID_DIS", "DMI requester ID checks disabled", 24, 1),
+    ("BBBS", "BIOS boot-block size (64 KiB << n)", 29, 2),
+];
+
+/// Field layout of PCH strap `index` on `platform`, empty if unknown
+fn pch_strap_fields(platform: StrapPlatform, index: usize) -> &'static [StrapFieldDef] {
+    match (platform, index) {
+        (StrapPlatform::IbexPeak, 0) => IBEX_PEAK_PCHSTRP0,
+        _ => &[],
+    }
+}
+
+/// Decode `value` according to `defs`
+fn decode_strap(value: u32, defs: &[StrapFieldDef]) -> Vec<IfdStrapField> {
+    defs.iter()
+        .map(|&(name, description, shift, width)| IfdStrapField {
+            name,
+            description,
+            value: (value >> shift) & ((1 << width) - 1),
+        })
+        .collect()
+}
+
+/// Soft-strap sections of an Intel Flash Descriptor
+///
+/// The PCH (ICH) straps are located by FLMAP1 (FISBA/ISL) and the processor
+/// (MCH) straps by FLMAP2 (FMSBA/MSL). The meaning of the individual bits is
+/// chipset specific. They are decoded into [`IfdStrap::fields`] where the
+/// platform is recognized and its layout known, otherwise only the raw
+/// labeled dwords are provided.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IfdStraps {
+    /// Platform guessed from the descriptor, if recognized
+    pub platform: Option<StrapPlatform>,
+    /// PCH/ICH soft straps
+    pub pch: Vec<IfdStrap>,
+    /// Processor/MCH soft straps
+    pub processor: Vec<IfdStrap>,
+}
+
+/// Read `count` strap dwords starting at `base`
+fn read_straps(
+    data: &[u8],
+    base: usize,
+    count: usize,
+    prefix: &str,
+) -> Result<Vec<IfdStrap>, LayoutError> {
+    if base + count * 4 > data.len() {
+        return Err(LayoutError::InvalidIfdSignature);
+    }
+
+    Ok((0..count)
+        .map(|i| {
+            let offset = base + i * 4;
+            IfdStrap {
+                name: format!("{}{}", prefix, i),
+                offset: offset as u32,
+                value: u32::from_le_bytes([
+                    data[offset],
+                    data[offset + 1],
+                    data[offset + 2],
+                    data[offset + 3],
+                ]),
+                fields: Vec::new(),
+            }
+        })
+        .collect())
+}
+
+/// Parse the soft-strap sections of an Intel Flash Descriptor
+///
+/// Like `parse_ifd`, this expects at least the first 4KB of flash.
+pub fn parse_ifd_straps(data: &[u8]) -> Result<IfdStraps, LayoutError> {
+    if data.len() < 0x1000 || !has_ifd(data) {
+        return Err(LayoutError::InvalidIfdSignature);
+    }
+
+    // FLMAP1 at offset 0x18: FISBA in bits 23:16, ISL in bits 31:24
+    let flmap1 = u32::from_le_bytes([data[0x18], data[0x19], data[0x1A], data[0x1B]]);
+    let fisba = ((flmap1 >> 12) & 0xFF0) as usize;
+    let isl = (flmap1 >> 24) as usize;
+
+    // FLMAP2 at offset 0x1C: FMSBA in bits 7:0, MSL in bits 15:8
+    let flmap2 = u32::from_le_bytes([data[0x1C], data[0x1D], data[0x1E], data[0x1F]]);
+    let fmsba = ((flmap2 << 4) & 0xFF0) as usize;
+    let msl = ((flmap2 >> 8) & 0xFF) as usize;
+
+    let platform = StrapPlatform::guess(flmap1, flmap2);
+    let mut pch = read_straps(data, fisba, isl, "PCHSTRP")?;
+    if let Some(platform) = platform {
+        for (i, strap) in pch.iter_mut().enumerate() {
+            strap.fields = decode_strap(strap.value, pch_strap_fields(platform, i));
+        }
+    }
+
+    Ok(IfdStraps {
+        platform,
+        pch,
+        processor: read_straps(data, fmsba, msl, "MCHSTRP")?,
+    })
+}
+
 /// Check if data appears to contain an Intel Flash Descriptor
 pub fn has_ifd(data: &[u8]) -> bool {
     if data.len() < 0x14 {
@@ -222,4 +396,105 @@ mod tests {
         assert_eq!(layout.regions[2].end, 0xFFFFFF);
         assert!(layout.regions[2].dangerous);
     }
+
+    #[test]
+    fn test_parse_ifd_overlap() {
+        // Region 2 (me) starting inside bios: 0x700000 - 0xFFFFFF
+        let mut data = make_test_ifd();
+        let freg2: u32 = (0x0FFF << 16) | 0x0700;
+        data[0x48..0x4C].copy_from_slice(&freg2.to_le_bytes());
+        assert_eq!(
+            parse_ifd(&data).map(|_| ()),
+            Err(LayoutError::OverlappingRegions {
+                a: "bios".into(),
+                b: "me".into(),
+            })
+        );
+
+        // Unused regions aren't checked against the others
+        let mut data = make_test_ifd();
+        data[0x48..0x4C].copy_from_slice(&FLREG_UNUSED.to_le_bytes());
+        assert_eq!(parse_ifd(&data).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_ifd_straps() {
+        // 18 PCH straps at 0x100, 1 MCH strap at 0x300
+        let mut data = make_test_ifd();
+        let flmap1: u32 = (18 << 24) | (0x10 << 16);
+        data[0x18..0x1C].copy_from_slice(&flmap1.to_le_bytes());
+        let flmap2: u32 = (1 << 8) | 0x30;
+        data[0x1C..0x20].copy_from_slice(&flmap2.to_le_bytes());
+
+        data[0x100..0x104].copy_from_slice(&0x0010_0000u32.to_le_bytes());
+        data[0x104..0x108].copy_from_slice(&0x0000_0002u32.to_le_bytes());
+        data[0x144..0x148].copy_from_slice(&0xDEAD_BEEFu32.to_le_bytes());
+        data[0x300..0x304].copy_from_slice(&0x0000_0001u32.to_le_bytes());
+
+        let straps = parse_ifd_straps(&data).unwrap();
+
+        assert_eq!(straps.pch.len(), 18);
+        assert_eq!(straps.pch[0].name, "PCHSTRP0");
+        assert_eq!(straps.pch[0].offset, 0x100);
+        assert_eq!(straps.pch[0].value, 0x0010_0000);
+        assert_eq!(straps.pch[1].value, 0x0000_0002);
+        assert_eq!(straps.pch[17].name, "PCHSTRP17");
+        assert_eq!(straps.pch[17].offset, 0x144);
+        assert_eq!(straps.pch[17].value, 0xDEAD_BEEF);
+
+        assert_eq!(straps.processor.len(), 1);
+        assert_eq!(straps.processor[0].name, "MCHSTRP0");
+        assert_eq!(straps.processor[0].offset, 0x300);
+        assert_eq!(straps.processor[0].value, 0x0000_0001);
+
+        // Too many PCH straps for Ibex Peak, so nothing is decoded
+        assert_eq!(straps.platform, None);
+        assert!(straps.pch[0].fields.is_empty());
+
+        assert!(parse_ifd_straps(&[0xFF; 0x1000]).is_err());
+    }
+
+    #[test]
+    fn test_parse_ifd_straps_ibex_peak() {
+        // Ibex Peak descriptor: 16 PCH straps at 0x100, no ICC registers
+        let mut data = make_test_ifd();
+        let flmap1: u32 = (16 << 24) | (0x10 << 16);
+        data[0x18..0x1C].copy_from_slice(&flmap1.to_le_bytes());
+        let flmap2: u32 = (1 << 8) | 0x30;
+        data[0x1C..0x20].copy_from_slice(&flmap2.to_le_bytes());
+
+        // SMBus and SMLink0 enabled at 100 kHz, GPIO12 as LAN_PHY_PWR_CTRL,
+        // 256 KiB boot block
+        let pchstrp0: u32 = (1 << 7) | (1 << 8) | (1 << 12) | (1 << 14) | (1 << 20) | (2 << 29);
+        data[0x100..0x104].copy_from_slice(&pchstrp0.to_le_bytes());
+
+        let straps = parse_ifd_straps(&data).unwrap();
+        assert_eq!(straps.platform, Some(StrapPlatform::IbexPeak));
+
+        let fields: Vec<(&str, u32)> = straps.pch[0]
+            .fields
+            .iter()
+            .map(|f| (f.name, f.value))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("cs_ss2", 0),
+                ("SMB_EN", 1),
+                ("SML0_EN", 1),
+                ("SML1_EN", 0),
+                ("SML1FRQ", 0),
+                ("SMB0FRQ", 1),
+                ("SML0FRQ", 1),
+                ("LANPHYPC_GP12_SEL", 1),
+                ("cs_ss1", 0),
+                ("DMI_REQID_DIS", 0),
+                ("BBBS", 2),
+            ]
+        );
+
+        // Only PCHSTRP0 has a known layout
+        assert!(straps.pch[1].fields.is_empty());
+        assert!(straps.processor[0].fields.is_empty());
+    }
 }
@@ -4,10 +4,14 @@
 //! named regions within a flash chip. Layouts can be:
 //!
 //! - Loaded from TOML files
+//! - Loaded from or exported to flashrom layout files
 //! - Parsed from Intel Flash Descriptors (IFD)
-//! - Parsed from FMAP structures (Chromebook-style)
+//! - Parsed from FMAP structures (Chromebook-style), or written out as one
+//! - Parsed from GUID Partition Tables (eMMC or disk images)
 //! - Read directly from the flash chip
 //!
+//! coreboot images can also have the files of their CBFS listed.
+//!
 //! # Region Operations
 //!
 //! Layouts support include/exclude filtering to operate on specific regions:
@@ -33,23 +37,37 @@
 
 mod types;
 
+#[cfg(feature = "std")]
+mod cbfs;
 #[cfg(feature = "std")]
 mod flash;
 #[cfg(feature = "std")]
+mod flashrom;
+#[cfg(feature = "std")]
 mod fmap;
 #[cfg(feature = "std")]
+mod gpt;
+#[cfg(feature = "std")]
 mod ifd;
 #[cfg(feature = "std")]
 mod toml;
 
 pub use types::*;
 
+#[cfg(feature = "std")]
+pub use cbfs::{
+    CbfsFile, DEFAULT_CBFS_REGION, file_type as cbfs_file_type, list_cbfs, parse_cbfs_at,
+};
 #[cfg(feature = "std")]
 pub use flash::{read_fmap_from_flash, read_ifd_from_flash, read_layout_from_flash};
 #[cfg(feature = "std")]
 pub use fmap::{
-    FmapSearchable, fmap_offset, has_fmap, is_valid_fmap_header, parse_fmap, parse_fmap_at,
-    search_fmap, validate_fmap,
+    FmapSearchable, build_fmap, fmap_offset, fmap_size, has_fmap, is_valid_fmap_header, parse_fmap,
+    parse_fmap_at, search_fmap, validate_fmap,
 };
 #[cfg(feature = "std")]
-pub use ifd::{has_ifd, parse_ifd};
+pub use gpt::{GPT_HEADER_PROBE_SIZE, gpt_size, has_gpt, parse_gpt, parse_gpt_at};
+#[cfg(feature = "std")]
+pub use ifd::{
+    IfdStrap, IfdStrapField, IfdStraps, StrapPlatform, has_ifd, parse_ifd, parse_ifd_straps,
+};
@@ -18,6 +18,17 @@
 //! start = 0x001000
 //! end = 0x7FFFFF
 //! ```
+//!
+//! Instead of `end`, a region can give its `size` (a number or a string
+//! like "64 KiB"). Instead of `start`, `after` names an earlier region the
+//! new one directly follows:
+//!
+//! ```toml
+//! [[region]]
+//! name = "me"
+//! after = "descriptor"
+//! size = "2 MiB"
+//! ```
 
 use std::format;
 use std::fs;
@@ -45,34 +56,85 @@ struct TomlLayoutMeta {
 #[derive(Debug, serde::Deserialize)]
 struct TomlRegion {
     name: String,
-    #[serde(deserialize_with = "deserialize_hex_u32")]
-    start: u32,
-    #[serde(deserialize_with = "deserialize_hex_u32")]
-    end: u32,
+    #[serde(default, deserialize_with = "deserialize_hex_u32")]
+    start: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_hex_u32")]
+    end: Option<u32>,
+    /// Size in bytes, as an alternative to `end`
+    #[serde(default, deserialize_with = "deserialize_size")]
+    size: Option<u32>,
+    /// Region this one starts right after, as an alternative to `start`
+    #[serde(default)]
+    after: Option<String>,
     #[serde(default)]
     readonly: bool,
     #[serde(default)]
     dangerous: bool,
 }
 
+/// A number in TOML, either an integer or a string
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum HexOrInt {
+    Int(u32),
+    Str(String),
+}
+
 /// Deserialize a u32 that can be hex (0x...) or decimal
-fn deserialize_hex_u32<'de, D>(deserializer: D) -> Result<u32, D::Error>
+fn deserialize_hex_u32<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
     use serde::Deserialize;
 
-    // Try to deserialize as a number first, then as a string
-    #[derive(Deserialize)]
-    #[serde(untagged)]
-    enum HexOrInt {
-        Int(u32),
-        Str(String),
+    match HexOrInt::deserialize(deserializer)? {
+        HexOrInt::Int(n) => Ok(Some(n)),
+        HexOrInt::Str(s) => parse_number(&s).map(Some).map_err(serde::de::Error::custom),
     }
+}
+
+/// Deserialize a size that can be a number or a string like "64 KiB"
+fn deserialize_size<'de, D>(deserializer: D) -> Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
 
     match HexOrInt::deserialize(deserializer)? {
-        HexOrInt::Int(n) => Ok(n),
-        HexOrInt::Str(s) => parse_number(&s).map_err(serde::de::Error::custom),
+        HexOrInt::Int(n) => Ok(Some(n)),
+        HexOrInt::Str(s) => parse_size(&s).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+impl TomlRegion {
+    /// Resolve the region's start and end from `start`/`after` and
+    /// `end`/`size`, given the regions defined before it
+    fn bounds(&self, earlier: &Layout) -> Result<(u32, u32), String> {
+        let start = match (self.start, &self.after) {
+            (Some(start), None) => start,
+            (None, Some(after)) => {
+                let prev = earlier.find_region(after).ok_or_else(|| {
+                    format!("'after' names '{}', which isn't defined before it", after)
+                })?;
+                prev.end
+                    .checked_add(1)
+                    .ok_or_else(|| format!("'{}' ends at the top of the address space", after))?
+            }
+            (Some(_), Some(_)) => return Err("both 'start' and 'after' given".to_string()),
+            (None, None) => return Err("missing 'start' or 'after'".to_string()),
+        };
+
+        let end = match (self.end, self.size) {
+            (Some(end), None) => end,
+            (None, Some(0)) => return Err("'size' must not be zero".to_string()),
+            (None, Some(size)) => start
+                .checked_add(size - 1)
+                .ok_or_else(|| format!("0x{:X} + 0x{:X} bytes exceeds 4 GiB", start, size))?,
+            (Some(_), Some(_)) => return Err("both 'end' and 'size' given".to_string()),
+            (None, None) => return Err("missing 'end' or 'size'".to_string()),
+        };
+
+        Ok((start, end))
     }
 }
 
@@ -157,10 +219,24 @@ impl Layout {
                 return Err(LayoutError::DuplicateRegionName);
             }
 
+            let invalid = |reason| LayoutError::InvalidRegionDefinition {
+                region: toml_region.name.clone(),
+                reason,
+            };
+            let (start, end) = toml_region.bounds(&layout).map_err(invalid)?;
+            if let Some(chip_size) = layout.chip_size
+                && end >= chip_size
+            {
+                return Err(invalid(format!(
+                    "ends at 0x{:X}, beyond the chip size of 0x{:X} bytes",
+                    end, chip_size
+                )));
+            }
+
             layout.add_region(Region {
                 name: toml_region.name,
-                start: toml_region.start,
-                end: toml_region.end,
+                start,
+                end,
                 readonly: toml_region.readonly,
                 dangerous: toml_region.dangerous,
                 included: false,
@@ -168,6 +244,7 @@ impl Layout {
         }
 
         layout.sort_by_address();
+        layout.check_overlaps()?;
         Ok(layout)
     }
 
@@ -263,4 +340,85 @@ end = 0xFFFFFF
         assert_eq!(layout.regions[1].name, "bios");
         assert!(!layout.regions[1].readonly);
     }
+
+    #[test]
+    fn test_parse_toml_overlap() {
+        let toml = r#"
+[[region]]
+name = "bios"
+start = 0x000000
+end = 0x7FFFFF
+
+[[region]]
+name = "bootblock"
+start = 0x7F0000
+end = 0x7FFFFF
+
+[[region]]
+name = "me"
+start = 0x700000
+end = 0xFFFFFF
+"#;
+        assert_eq!(
+            Layout::from_toml_str(toml).map(|_| ()),
+            Err(LayoutError::OverlappingRegions {
+                a: "bios".to_string(),
+                b: "me".to_string(),
+            })
+        );
+
+        // Nesting is only allowed in FMAPs, here it would write data twice
+        let nested = toml.replace("0x700000", "0x800000");
+        assert_eq!(
+            Layout::from_toml_str(&nested).map(|_| ()),
+            Err(LayoutError::OverlappingRegions {
+                a: "bios".to_string(),
+                b: "bootblock".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_toml_size_and_after() {
+        let toml = r#"
+[layout]
+chip_size = "1 MiB"
+
+[[region]]
+name = "descriptor"
+start = 0
+size = "4 KiB"
+
+[[region]]
+name = "me"
+after = "descriptor"
+size = 0x7000
+
+[[region]]
+name = "bios"
+after = "me"
+end = 0xFFFFF
+"#;
+        let layout = Layout::from_toml_str(toml).unwrap();
+        let bounds: Vec<_> = layout.regions.iter().map(|r| (r.start, r.end)).collect();
+        assert_eq!(bounds, [(0, 0xFFF), (0x1000, 0x7FFF), (0x8000, 0xFFFFF)]);
+
+        let invalid = |toml: &str| match Layout::from_toml_str(toml) {
+            Err(LayoutError::InvalidRegionDefinition { region, .. }) => region,
+            other => panic!("unexpected result: {:?}", other),
+        };
+        assert_eq!(
+            invalid(&toml.replace("end = 0xFFFFF", "size = 0xF9000")),
+            "bios"
+        );
+        assert_eq!(
+            invalid(&toml.replace("after = \"me\"", "after = \"gbe\"")),
+            "bios"
+        );
+        assert_eq!(
+            invalid(&toml.replace("after = \"me\"", "start = 0\nafter = \"me\"")),
+            "bios"
+        );
+        assert_eq!(invalid(&toml.replace("size = 0x7000", "")), "me");
+    }
 }
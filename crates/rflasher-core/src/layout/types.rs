@@ -60,6 +60,11 @@ impl Region {
         self.start <= other.end && other.start <= self.end
     }
 
+    /// Check if this region lies entirely within another
+    pub fn is_within(&self, other: &Region) -> bool {
+        other.start <= self.start && self.end <= other.end
+    }
+
     /// Check if this region is aligned to the given boundary
     pub fn is_aligned(&self, alignment: u32) -> bool {
         self.start.is_multiple_of(alignment) && (self.end + 1).is_multiple_of(alignment)
@@ -75,6 +80,10 @@ pub enum LayoutSource {
     Ifd,
     /// Layout parsed from FMAP structure
     Fmap,
+    /// Layout parsed from a GUID Partition Table
+    Gpt,
+    /// Layout loaded from a flashrom layout file
+    Flashrom,
     /// Layout created manually
     Manual,
 }
@@ -85,6 +94,8 @@ impl core::fmt::Display for LayoutSource {
             LayoutSource::Toml => write!(f, "TOML file"),
             LayoutSource::Ifd => write!(f, "Intel Flash Descriptor"),
             LayoutSource::Fmap => write!(f, "FMAP"),
+            LayoutSource::Gpt => write!(f, "GPT"),
+            LayoutSource::Flashrom => write!(f, "flashrom layout file"),
             LayoutSource::Manual => write!(f, "Manual"),
         }
     }
@@ -145,26 +156,167 @@ impl Layout {
             .find(|r| r.name.eq_ignore_ascii_case(name))
     }
 
-    /// Mark a region as included
-    pub fn include_region(&mut self, name: &str) -> Result<(), LayoutError> {
-        match self.find_region_mut(name) {
-            Some(region) => {
-                region.included = true;
-                Ok(())
+    /// Find the index of a region by name (case-insensitive)
+    pub fn region_index(&self, name: &str) -> Option<usize> {
+        self.regions
+            .iter()
+            .position(|r| r.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Check if region `inner` nests within region `outer`
+    ///
+    /// Regions with identical bounds nest in layout order, so the hierarchy
+    /// stays a tree. Unused regions (start > end) never nest.
+    fn nests_in(&self, inner: usize, outer: usize) -> bool {
+        let (a, b) = (&self.regions[inner], &self.regions[outer]);
+        inner != outer
+            && a.start <= a.end
+            && b.start <= b.end
+            && a.is_within(b)
+            && (a.start != b.start || a.end != b.end || outer < inner)
+    }
+
+    /// Get the index of the smallest region containing region `index`
+    ///
+    /// This is how nested FMAP areas (e.g. FMAP within RO_SECTION within
+    /// WP_RO) form a hierarchy. Returns `None` for top-level regions.
+    pub fn parent(&self, index: usize) -> Option<usize> {
+        (0..self.regions.len())
+            .filter(|&outer| self.nests_in(index, outer))
+            .min_by_key(|&outer| (self.regions[outer].size(), core::cmp::Reverse(outer)))
+    }
+
+    /// Get the indices of the regions directly within region `index`
+    pub fn children(&self, index: usize) -> Vec<usize> {
+        (0..self.regions.len())
+            .filter(|&i| self.parent(i) == Some(index))
+            .collect()
+    }
+
+    /// Get the ranges of region `index` that none of its children cover
+    ///
+    /// Returned as inclusive (start, end) pairs in address order, empty for
+    /// a region without children.
+    pub fn uncovered_by_children(&self, index: usize) -> Vec<(u32, u32)> {
+        let children = self.children(index);
+        if children.is_empty() {
+            return Vec::new();
+        }
+        let mut spans: Vec<(u32, u32)> = children
+            .iter()
+            .map(|&c| (self.regions[c].start, self.regions[c].end))
+            .collect();
+        spans.sort_unstable();
+
+        // Next uncovered address, in u64 so a child ending at 4 GiB fits
+        let region = &self.regions[index];
+        let mut gaps = Vec::new();
+        let mut pos = u64::from(region.start);
+        for (start, end) in spans {
+            if u64::from(start) > pos {
+                gaps.push((pos as u32, start - 1));
             }
-            None => Err(LayoutError::RegionNotFound),
+            pos = pos.max(u64::from(end) + 1);
+        }
+        if pos <= u64::from(region.end) {
+            gaps.push((pos as u32, region.end));
+        }
+        gaps
+    }
+
+    /// Get the nesting depth of region `index` (0 for top-level regions)
+    pub fn depth(&self, index: usize) -> usize {
+        let mut depth = 0;
+        let mut current = index;
+        while let Some(parent) = self.parent(current) {
+            depth += 1;
+            current = parent;
+        }
+        depth
+    }
+
+    /// Get all regions as (index, depth) pairs, each parent followed by its
+    /// children
+    ///
+    /// Siblings keep their layout order.
+    pub fn tree_order(&self) -> Vec<(usize, usize)> {
+        let mut order = Vec::with_capacity(self.regions.len());
+        let mut stack: Vec<(usize, usize)> = (0..self.regions.len())
+            .rev()
+            .filter(|&i| self.parent(i).is_none())
+            .map(|i| (i, 0))
+            .collect();
+        while let Some((index, depth)) = stack.pop() {
+            order.push((index, depth));
+            stack.extend(
+                self.children(index)
+                    .into_iter()
+                    .rev()
+                    .map(|c| (c, depth + 1)),
+            );
         }
+        order
+    }
+
+    /// Set the included flag of a region and everything nested within it
+    fn set_included_tree(&mut self, index: usize, included: bool) {
+        let mut stack = alloc::vec![index];
+        while let Some(i) = stack.pop() {
+            self.regions[i].included = included;
+            stack.extend(self.children(i));
+        }
+    }
+
+    /// Check if any region containing region `index` is included
+    fn has_included_ancestor(&self, index: usize) -> bool {
+        let mut current = index;
+        while let Some(parent) = self.parent(current) {
+            if self.regions[parent].included {
+                return true;
+            }
+            current = parent;
+        }
+        false
+    }
+
+    /// Mark a region as included, along with the regions nested within it
+    pub fn include_region(&mut self, name: &str) -> Result<(), LayoutError> {
+        let index = self.region_index(name).ok_or(LayoutError::RegionNotFound)?;
+        self.set_included_tree(index, true);
+        Ok(())
     }
 
-    /// Mark a region as excluded
+    /// Mark a region as excluded, along with the regions nested within it
+    ///
+    /// Included regions containing it are split up: they are replaced by
+    /// their other children, so the siblings stay included. Bytes of such a
+    /// parent that no child covers are excluded as well, with a warning.
     pub fn exclude_region(&mut self, name: &str) -> Result<(), LayoutError> {
-        match self.find_region_mut(name) {
-            Some(region) => {
-                region.included = false;
-                Ok(())
+        let index = self.region_index(name).ok_or(LayoutError::RegionNotFound)?;
+        self.set_included_tree(index, false);
+
+        let mut child = index;
+        while let Some(parent) = self.parent(child) {
+            if self.regions[parent].included {
+                for (start, end) in self.uncovered_by_children(parent) {
+                    log::warn!(
+                        "Excluding '{}' also excludes 0x{:08X}-0x{:08X} of '{}', which no region within it covers",
+                        self.regions[index].name,
+                        start,
+                        end,
+                        self.regions[parent].name
+                    );
+                }
+                self.regions[parent].included = false;
+                for sibling in self.children(parent) {
+                    if sibling != child {
+                        self.set_included_tree(sibling, true);
+                    }
+                }
             }
-            None => Err(LayoutError::RegionNotFound),
+            child = parent;
         }
+        Ok(())
     }
 
     /// Include all regions
@@ -182,8 +334,15 @@ impl Layout {
     }
 
     /// Get all included regions
+    ///
+    /// Regions nested within another included region are skipped, since the
+    /// outer region already covers them.
     pub fn included_regions(&self) -> impl Iterator<Item = &Region> {
-        self.regions.iter().filter(|r| r.included)
+        self.regions
+            .iter()
+            .enumerate()
+            .filter(|&(i, r)| r.included && !self.has_included_ancestor(i))
+            .map(|(_, r)| r)
     }
 
     /// Check if any regions are included
@@ -228,12 +387,11 @@ impl Layout {
             }
         }
 
-        // Check for overlapping regions and duplicate names
+        self.check_overlaps()?;
+
+        // Check for duplicate names
         for (i, r1) in self.regions.iter().enumerate() {
             for r2 in self.regions.iter().skip(i + 1) {
-                if r1.overlaps(r2) {
-                    return Err(LayoutError::OverlappingRegions);
-                }
                 if r1.name.eq_ignore_ascii_case(&r2.name) {
                     return Err(LayoutError::DuplicateRegionName);
                 }
@@ -243,6 +401,28 @@ impl Layout {
         Ok(())
     }
 
+    /// Check that no two regions overlap
+    ///
+    /// In FMAP layouts, a region nested entirely within another is allowed,
+    /// since that is how FMAP describes its hierarchy (e.g. RO_SECTION
+    /// within WP_RO). Unused regions (start > end) are skipped.
+    pub fn check_overlaps(&self) -> Result<(), LayoutError> {
+        let nesting = self.source == LayoutSource::Fmap;
+        let used = || self.regions.iter().filter(|r| r.start <= r.end);
+        for (i, a) in used().enumerate() {
+            for b in used().skip(i + 1) {
+                let nested = a.is_within(b) || b.is_within(a);
+                if a.overlaps(b) && !(nesting && nested) {
+                    return Err(LayoutError::OverlappingRegions {
+                        a: a.name.clone(),
+                        b: b.name.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Get dangerous regions that are included
     pub fn dangerous_included(&self) -> Vec<&Region> {
         self.regions
@@ -291,9 +471,21 @@ pub enum LayoutError {
     /// Region has invalid bounds (start > end)
     InvalidRegion,
     /// Two regions overlap
-    OverlappingRegions,
+    OverlappingRegions {
+        /// Name of the first region
+        a: alloc::string::String,
+        /// Name of the second region
+        b: alloc::string::String,
+    },
     /// Two regions have the same name
     DuplicateRegionName,
+    /// A region in a layout file is defined inconsistently
+    InvalidRegionDefinition {
+        /// Name of the region
+        region: alloc::string::String,
+        /// What is wrong with the definition
+        reason: alloc::string::String,
+    },
     /// Chip size doesn't match expected
     ChipSizeMismatch {
         /// Expected chip size
@@ -309,6 +501,14 @@ pub enum LayoutError {
     InvalidFmapSignature,
     /// FMAP version not supported
     UnsupportedFmapVersion,
+    /// Name doesn't fit the format's name field
+    NameTooLong(alloc::string::String),
+    /// No CBFS found in the image
+    NoCbfs,
+    /// No protective MBR or GPT header signature
+    InvalidGptSignature,
+    /// GPT header or partition entry CRC32 doesn't match
+    GptChecksumMismatch,
     /// I/O error
     IoError(alloc::string::String),
 }
@@ -319,9 +519,14 @@ impl std::fmt::Display for LayoutError {
         match self {
             Self::RegionNotFound => write!(f, "region not found"),
             Self::DuplicateRegionName => write!(f, "duplicate region name"),
+            Self::InvalidRegionDefinition { region, reason } => {
+                write!(f, "region '{}': {}", region, reason)
+            }
             Self::RegionOutOfBounds => write!(f, "region extends beyond chip size"),
             Self::InvalidRegion => write!(f, "invalid region bounds"),
-            Self::OverlappingRegions => write!(f, "overlapping regions"),
+            Self::OverlappingRegions { a, b } => {
+                write!(f, "regions '{}' and '{}' overlap", a, b)
+            }
             Self::ChipSizeMismatch { expected, actual } => {
                 write!(
                     f,
@@ -333,6 +538,10 @@ impl std::fmt::Display for LayoutError {
             Self::InvalidIfdSignature => write!(f, "invalid Intel Flash Descriptor signature"),
             Self::InvalidFmapSignature => write!(f, "invalid FMAP signature"),
             Self::UnsupportedFmapVersion => write!(f, "unsupported FMAP version"),
+            Self::NameTooLong(name) => write!(f, "name too long: {}", name),
+            Self::NoCbfs => write!(f, "no CBFS found"),
+            Self::InvalidGptSignature => write!(f, "no GUID Partition Table found"),
+            Self::GptChecksumMismatch => write!(f, "GPT checksum mismatch"),
             Self::IoError(msg) => write!(f, "I/O error: {}", msg),
         }
     }
@@ -340,3 +549,75 @@ impl std::fmt::Display for LayoutError {
 
 #[cfg(feature = "std")]
 impl std::error::Error for LayoutError {}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    /// Chromebook-style FMAP: WP_RO > RO_SECTION > FMAP/GBB, plus RW_A
+    fn fmap_layout() -> Layout {
+        let mut layout = Layout::with_source(LayoutSource::Fmap);
+        layout.add_region(Region::new("RW_A", 0x0000, 0x3FFF));
+        layout.add_region(Region::new("WP_RO", 0x4000, 0x7FFF));
+        layout.add_region(Region::new("FMAP", 0x4000, 0x4FFF));
+        layout.add_region(Region::new("RO_SECTION", 0x4000, 0x6FFF));
+        layout.add_region(Region::new("GBB", 0x5000, 0x6FFF));
+        layout.add_region(Region::new("RO_VPD", 0x7000, 0x7FFF));
+        layout
+    }
+
+    fn included_names(layout: &Layout) -> Vec<&str> {
+        layout.included_regions().map(|r| r.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_region_hierarchy() {
+        let layout = fmap_layout();
+        assert_eq!(layout.parent(0), None);
+        assert_eq!(layout.parent(2), Some(3));
+        assert_eq!(layout.parent(3), Some(1));
+        assert_eq!(layout.children(1), [3, 5]);
+        assert_eq!(layout.depth(2), 2);
+
+        let order: Vec<_> = layout.tree_order();
+        assert_eq!(order, [(0, 0), (1, 0), (3, 1), (2, 2), (4, 2), (5, 1)]);
+    }
+
+    #[test]
+    fn test_include_parent_and_exclude_child() {
+        let mut layout = fmap_layout();
+        layout.include_region("wp_ro").unwrap();
+        assert!(layout.find_region("GBB").unwrap().included);
+        assert_eq!(included_names(&layout), ["WP_RO"]);
+
+        // Excluding a nested region splits its included ancestors
+        layout.exclude_region("GBB").unwrap();
+        assert_eq!(included_names(&layout), ["FMAP", "RO_VPD"]);
+
+        // A leaf can be selected without its siblings
+        layout.exclude_all();
+        layout.include_region("FMAP").unwrap();
+        assert_eq!(included_names(&layout), ["FMAP"]);
+    }
+
+    #[test]
+    fn test_uncovered_by_children() {
+        let mut layout = fmap_layout();
+        assert_eq!(layout.uncovered_by_children(0), []);
+        assert_eq!(layout.uncovered_by_children(1), []);
+
+        // Without RO_VPD, the end of WP_RO is in no child
+        layout.regions.remove(5);
+        assert_eq!(layout.uncovered_by_children(1), [(0x7000, 0x7FFF)]);
+        layout.add_region(Region::new("RO_VPD", 0x7800, 0x7BFF));
+        assert_eq!(
+            layout.uncovered_by_children(1),
+            [(0x7000, 0x77FF), (0x7C00, 0x7FFF)]
+        );
+
+        // Splitting WP_RO drops those ranges along with GBB
+        layout.include_region("WP_RO").unwrap();
+        layout.exclude_region("GBB").unwrap();
+        assert_eq!(included_names(&layout), ["FMAP", "RO_VPD"]);
+    }
+}
@@ -8,6 +8,8 @@
 //!
 //! - `std` - Enable standard library support (includes `alloc`)
 //! - `alloc` - Enable heap allocation for features like chip name search
+//! - `embedded-hal` - [`programmer::EmbeddedHalSpi`], a `SpiMaster` over any
+//!   embedded-hal `SpiDevice`
 //!
 //! # Example
 //!
@@ -42,6 +44,7 @@ pub mod error;
 pub mod flash;
 #[cfg(feature = "alloc")]
 pub mod layout;
+pub mod otp;
 pub mod programmer;
 pub mod protocol;
 pub mod sfdp;
@@ -0,0 +1,248 @@
+//! Security register (OTP) support
+//!
+//! Many SPI NOR chips have a handful of small security registers next to
+//! the main array. They can be programmed and erased like ordinary flash
+//! until their lock bit is set, after which they are permanently read-only.
+//!
+//! This module implements the Winbond-style command set, used by chips with
+//! both the `OTP` and `SECURITY_REG` features. GigaDevice and many
+//! compatible parts share it:
+//!
+//! | Opcode | Command                   |
+//! |--------|---------------------------|
+//! | `0x48` | Read Security Register    |
+//! | `0x42` | Program Security Register |
+//! | `0x44` | Erase Security Register   |
+//!
+//! Registers are selected through address bits 12-15, with the byte offset
+//! within the register in the low bits. Commands always use 3-byte addresses:
+//!
+//! | Register | Address range       | Lock bit          |
+//! |----------|---------------------|-------------------|
+//! | 1        | 0x001000 - 0x0010FF | LB1 (SR2 bit 3)   |
+//! | 2        | 0x002000 - 0x0020FF | LB2 (SR2 bit 4)   |
+//! | 3        | 0x003000 - 0x0030FF | LB3 (SR2 bit 5)   |
+
+use crate::error::{Error, Result};
+use crate::programmer::SpiMaster;
+use crate::protocol::{self, CommandAddressing};
+use crate::spi::{AddressWidth, IoMode, SpiCommand, opcodes};
+use maybe_async::maybe_async;
+
+/// Security register layout of a chip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OtpLayout {
+    /// Number of security registers (numbered from 1)
+    pub count: u8,
+    /// Size of each register in bytes
+    pub size: u32,
+    /// Program page size within a register
+    pub page_size: u32,
+    /// Status register 2 bit locking register 1; register N uses the bit
+    /// `N - 1` positions above it
+    pub lock_bit: u8,
+}
+
+impl OtpLayout {
+    /// Standard Winbond layout: three 256-byte registers locked by SR2 LB1-LB3
+    pub const fn winbond_standard() -> Self {
+        Self {
+            count: 3,
+            size: 256,
+            page_size: 256,
+            lock_bit: 3,
+        }
+    }
+
+    /// Get the command address of `offset` within register `register`
+    ///
+    /// Returns `AddressOutOfBounds` if the register doesn't exist or `len`
+    /// bytes starting at `offset` don't fit in it.
+    pub fn address(&self, register: u8, offset: u32, len: usize) -> Result<u32> {
+        if register == 0 || register > self.count {
+            return Err(Error::AddressOutOfBounds);
+        }
+        if offset as u64 + len as u64 > self.size as u64 {
+            return Err(Error::AddressOutOfBounds);
+        }
+        Ok(((register as u32) << 12) | offset)
+    }
+
+    /// Get the status register 2 mask of the lock bit for `register`
+    pub fn lock_mask(&self, register: u8) -> Result<u8> {
+        if register == 0 || register > self.count {
+            return Err(Error::AddressOutOfBounds);
+        }
+        Ok(1 << (self.lock_bit + register - 1))
+    }
+}
+
+/// Read from a security register
+#[maybe_async]
+pub async fn read_security_register<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    layout: &OtpLayout,
+    register: u8,
+    offset: u32,
+    buf: &mut [u8],
+) -> Result<()> {
+    let addr = layout.address(register, offset, buf.len())?;
+    let max_read = master.max_read_len();
+    let mut done = 0;
+
+    while done < buf.len() {
+        let chunk_len = core::cmp::min(max_read, buf.len() - done);
+        let mut cmd = SpiCommand {
+            opcode: opcodes::RDSR_SEC,
+            address: Some(addr + done as u32),
+            address_width: AddressWidth::ThreeByte,
+            io_mode: IoMode::Single,
+            dummy_cycles: 8,
+            write_data: &[],
+            read_buf: &mut buf[done..done + chunk_len],
+        };
+        master.execute(&mut cmd).await?;
+        done += chunk_len;
+    }
+
+    Ok(())
+}
+
+/// Check whether a security register is locked
+#[maybe_async]
+pub async fn is_security_register_locked<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    layout: &OtpLayout,
+    register: u8,
+) -> Result<bool> {
+    let mask = layout.lock_mask(register)?;
+    let sr2 = protocol::read_status2(master).await?;
+    Ok(sr2 & mask != 0)
+}
+
+/// Program a security register
+///
+/// The target bytes should be erased first. Returns `RegionProtected` if
+/// the register is locked.
+#[maybe_async]
+pub async fn program_security_register<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    layout: &OtpLayout,
+    register: u8,
+    offset: u32,
+    data: &[u8],
+) -> Result<()> {
+    let addr = layout.address(register, offset, data.len())?;
+    if is_security_register_locked(master, layout, register).await? {
+        return Err(Error::RegionProtected);
+    }
+
+    let max_write = master.max_write_len();
+    let mut done = 0;
+
+    while done < data.len() {
+        let chunk_addr = addr + done as u32;
+        let page_remaining = (layout.page_size - (chunk_addr % layout.page_size)) as usize;
+        let chunk_len =
+            core::cmp::min(core::cmp::min(page_remaining, max_write), data.len() - done);
+        protocol::program_page_with_addressing(
+            master,
+            opcodes::PRSR,
+            chunk_addr,
+            &data[done..done + chunk_len],
+            CommandAddressing::ThreeByte,
+        )
+        .await?;
+        done += chunk_len;
+    }
+
+    Ok(())
+}
+
+/// Erase a security register
+///
+/// Returns `RegionProtected` if the register is locked.
+#[maybe_async]
+pub async fn erase_security_register<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    layout: &OtpLayout,
+    register: u8,
+) -> Result<()> {
+    let addr = layout.address(register, 0, 0)?;
+    if is_security_register_locked(master, layout, register).await? {
+        return Err(Error::RegionProtected);
+    }
+
+    protocol::erase_block(
+        master,
+        opcodes::ERSR,
+        addr,
+        CommandAddressing::ThreeByte,
+        protocol::BLOCK_ERASE_POLL_US,
+        protocol::BLOCK_ERASE_TIMEOUT_US,
+    )
+    .await
+}
+
+/// Permanently lock a security register
+///
+/// Sets the register's lock bit in status register 2. This cannot be
+/// undone: the register can never be programmed or erased again.
+///
+/// `use_ewsr` selects EWSR (0x50) instead of WREN before the status
+/// register write, as for [`crate::wp::WriteOptions::use_ewsr`].
+#[maybe_async]
+pub async fn lock_security_register<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    layout: &OtpLayout,
+    register: u8,
+    use_ewsr: bool,
+) -> Result<()> {
+    let mask = layout.lock_mask(register)?;
+    let sr1 = protocol::read_status1(master).await?;
+    let sr2 = protocol::read_status2(master).await?;
+    if sr2 & mask != 0 {
+        return Ok(());
+    }
+
+    // Write SR1 back unchanged; only the lock bit changes in SR2
+    if use_ewsr {
+        protocol::write_status12_ewsr(master, sr1, sr2 | mask).await?;
+    } else {
+        protocol::write_status12(master, sr1, sr2 | mask).await?;
+    }
+
+    if protocol::read_status2(master).await? & mask == 0 {
+        return Err(Error::VerifyError { addr: 0 });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_winbond_addresses() {
+        let layout = OtpLayout::winbond_standard();
+        assert_eq!(layout.address(1, 0, 256), Ok(0x1000));
+        assert_eq!(layout.address(2, 0x10, 16), Ok(0x2010));
+        assert_eq!(layout.address(3, 0xFF, 1), Ok(0x30FF));
+
+        assert_eq!(layout.address(0, 0, 1), Err(Error::AddressOutOfBounds));
+        assert_eq!(layout.address(4, 0, 1), Err(Error::AddressOutOfBounds));
+        assert_eq!(
+            layout.address(1, 0x80, 0x81),
+            Err(Error::AddressOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn test_winbond_lock_bits() {
+        let layout = OtpLayout::winbond_standard();
+        assert_eq!(layout.lock_mask(1), Ok(opcodes::SR2_LB1));
+        assert_eq!(layout.lock_mask(2), Ok(opcodes::SR2_LB2));
+        assert_eq!(layout.lock_mask(3), Ok(opcodes::SR2_LB3));
+        assert!(layout.lock_mask(4).is_err());
+    }
+}
@@ -158,6 +158,24 @@ pub mod single {
         }
     }
 
+    /// Clock the bus with CS held at the given level and MOSI high
+    ///
+    /// Used for mode-exit sequences (e.g. continuous-read or QPI exit) that
+    /// need clocks without an opcode. CS is deasserted afterwards.
+    pub fn send_clocks<M: BitbangSpiMaster + ?Sized>(
+        master: &mut M,
+        cycles: usize,
+        cs_active: bool,
+    ) {
+        master.set_cs(cs_active);
+        master.set_mosi(true);
+        run_clock(master, cycles);
+        master.set_sck(false);
+        master.half_period_delay();
+        master.set_cs(false);
+        master.half_period_delay();
+    }
+
     /// Write multiple bytes in single-wire mode
     pub fn write_bytes<M: BitbangSpiMaster + ?Sized>(master: &mut M, bytes: &[u8]) {
         for &byte in bytes {
@@ -282,3 +300,69 @@ pub fn features_for_dual() -> SpiFeatures {
 pub fn features_for_quad() -> SpiFeatures {
     SpiFeatures::FOUR_BYTE_ADDR | SpiFeatures::DUAL | SpiFeatures::QUAD | SpiFeatures::QPI
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bitbang master that counts SCK rising edges per CS state
+    #[derive(Default)]
+    struct EdgeCounter {
+        cs_active: bool,
+        sck: bool,
+        mosi: bool,
+        edges_cs_active: usize,
+        edges_cs_inactive: usize,
+        mosi_low_edges: usize,
+    }
+
+    impl BitbangSpiMaster for EdgeCounter {
+        fn set_cs(&mut self, active: bool) {
+            self.cs_active = active;
+        }
+
+        fn set_sck(&mut self, high: bool) {
+            if high && !self.sck {
+                if self.cs_active {
+                    self.edges_cs_active += 1;
+                } else {
+                    self.edges_cs_inactive += 1;
+                }
+                if !self.mosi {
+                    self.mosi_low_edges += 1;
+                }
+            }
+            self.sck = high;
+        }
+
+        fn set_mosi(&mut self, high: bool) {
+            self.mosi = high;
+        }
+
+        fn get_miso(&self) -> bool {
+            true
+        }
+
+        fn half_period_delay(&self) {}
+    }
+
+    #[test]
+    fn test_send_clocks_cs_asserted() {
+        let mut master = EdgeCounter::default();
+        single::send_clocks(&mut master, 8, true);
+
+        assert_eq!(master.edges_cs_active, 8);
+        assert_eq!(master.edges_cs_inactive, 0);
+        assert_eq!(master.mosi_low_edges, 0);
+        assert!(!master.cs_active, "CS should be released afterwards");
+    }
+
+    #[test]
+    fn test_send_clocks_cs_deasserted() {
+        let mut master = EdgeCounter::default();
+        single::send_clocks(&mut master, 16, false);
+
+        assert_eq!(master.edges_cs_active, 0);
+        assert_eq!(master.edges_cs_inactive, 16);
+    }
+}
@@ -0,0 +1,68 @@
+//! SPI master wrapper that limits transfer sizes
+
+use super::{CsLevel, SpiFeatures, SpiMaster};
+use crate::error::Result;
+use crate::spi::SpiCommand;
+use maybe_async::maybe_async;
+
+/// SPI master wrapper that caps the size of read and write transfers
+///
+/// Flash reads and writes are split into transfers of at most
+/// `max_read_len()` and `max_write_len()` bytes. This wrapper lowers both
+/// to `limit`, but never raises them above what the wrapped master supports.
+/// Smaller transfers can help diagnose programmers that stall on large ones.
+///
+/// With no limit, the master's own limits are used unchanged.
+pub struct ChunkLimit<M> {
+    inner: M,
+    limit: Option<usize>,
+}
+
+impl<M: SpiMaster> ChunkLimit<M> {
+    /// Wrap a master, capping its transfers at `limit` bytes
+    pub fn new(inner: M, limit: Option<usize>) -> Self {
+        Self {
+            inner,
+            limit: limit.map(|limit| limit.max(1)),
+        }
+    }
+
+    fn clamp(&self, len: usize) -> usize {
+        self.limit.map_or(len, |limit| limit.min(len))
+    }
+}
+
+#[maybe_async(AFIT)]
+impl<M: SpiMaster> SpiMaster for ChunkLimit<M> {
+    fn features(&self) -> SpiFeatures {
+        self.inner.features()
+    }
+
+    fn max_read_len(&self) -> usize {
+        self.clamp(self.inner.max_read_len())
+    }
+
+    fn max_write_len(&self) -> usize {
+        self.clamp(self.inner.max_write_len())
+    }
+
+    async fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> Result<()> {
+        self.inner.execute(cmd).await
+    }
+
+    async fn execute_batch(&mut self, cmds: &mut [SpiCommand<'_>]) -> Result<()> {
+        self.inner.execute_batch(cmds).await
+    }
+
+    fn probe_opcode(&self, opcode: u8) -> bool {
+        self.inner.probe_opcode(opcode)
+    }
+
+    async fn delay_us(&mut self, us: u32) {
+        self.inner.delay_us(us).await
+    }
+
+    async fn send_clocks(&mut self, cycles: u32, cs: CsLevel) -> Result<()> {
+        self.inner.send_clocks(cycles, cs).await
+    }
+}
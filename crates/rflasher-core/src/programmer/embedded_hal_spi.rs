@@ -0,0 +1,198 @@
+//! `SpiMaster` adapter for embedded-hal SPI devices
+//!
+//! [`EmbeddedHalSpi`] turns any embedded-hal 1.0 `SpiDevice` into a
+//! [`SpiMaster`], so the probing, erase and smart write logic of this crate
+//! can run on a microcontroller (STM32, nRF, RP2040, ...) with the flash
+//! chip on one of its SPI buses.
+//!
+//! Each [`SpiCommand`] becomes a single `SpiDevice::transaction()`: the
+//! opcode, address and dummy bytes are written first, followed by the write
+//! data or a read into the command's buffer. The device asserts CS for the
+//! whole transaction, as SPI flash requires.
+//!
+//! With the `is_sync` feature the blocking `embedded_hal` traits are used,
+//! otherwise the `embedded_hal_async` ones.
+//!
+//! Only single I/O (1-1-1) is supported, since `SpiDevice` has no notion of
+//! dual or quad transfers.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // `spi` implements embedded_hal::spi::SpiDevice, `delay` DelayNs
+//! let master = EmbeddedHalSpi::new(spi, delay);
+//! let mut device = SpiFlashDevice::new(master, FlashContext::new(chip));
+//! device.read(0, &mut buf)?;
+//! ```
+
+use super::{SpiFeatures, SpiMaster};
+use crate::error::{Error, Result};
+use crate::spi::{SpiCommand, check_io_mode_supported};
+use embedded_hal::spi::Operation;
+use maybe_async::maybe_async;
+
+#[cfg(feature = "is_sync")]
+use embedded_hal::{delay::DelayNs, spi::SpiDevice};
+#[cfg(not(feature = "is_sync"))]
+use embedded_hal_async::{delay::DelayNs, spi::SpiDevice};
+
+/// Longest command header: opcode, 4 address bytes and up to 255 dummy
+/// cycles rounded up to bytes
+const MAX_HEADER_LEN: usize = 1 + 4 + 32;
+
+/// Default maximum data length of a single transaction
+const DEFAULT_MAX_TRANSFER: usize = 4096;
+
+/// [`SpiMaster`] over an embedded-hal `SpiDevice`
+pub struct EmbeddedHalSpi<S, D> {
+    spi: S,
+    delay: D,
+    max_transfer: usize,
+}
+
+impl<S: SpiDevice, D: DelayNs> EmbeddedHalSpi<S, D> {
+    /// Wrap an SPI device with the flash chip on it, and a delay provider
+    /// used while polling the chip
+    pub fn new(spi: S, delay: D) -> Self {
+        Self {
+            spi,
+            delay,
+            max_transfer: DEFAULT_MAX_TRANSFER,
+        }
+    }
+
+    /// Set the maximum data length of a single read or write transaction
+    ///
+    /// Defaults to 4 KiB. Lower it if the SPI peripheral or its DMA can't
+    /// handle transfers that long.
+    pub fn with_max_transfer(mut self, len: usize) -> Self {
+        self.max_transfer = len.max(1);
+        self
+    }
+
+    /// Release the SPI device and delay provider
+    pub fn release(self) -> (S, D) {
+        (self.spi, self.delay)
+    }
+}
+
+#[maybe_async(AFIT)]
+impl<S: SpiDevice, D: DelayNs> SpiMaster for EmbeddedHalSpi<S, D> {
+    fn features(&self) -> SpiFeatures {
+        // Addresses are just header bytes, so any width can be sent
+        SpiFeatures::FOUR_BYTE_ADDR
+    }
+
+    fn max_read_len(&self) -> usize {
+        self.max_transfer
+    }
+
+    fn max_write_len(&self) -> usize {
+        self.max_transfer
+    }
+
+    async fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> Result<()> {
+        check_io_mode_supported(cmd.io_mode, self.features())?;
+
+        let mut header = [0u8; MAX_HEADER_LEN];
+        let header_len = cmd.encode_header(&mut header);
+        let header = &header[..header_len];
+
+        // Empty phases are left out, so the transaction only contains what
+        // is actually clocked on the bus
+        let result = match (cmd.write_data.is_empty(), cmd.read_buf.is_empty()) {
+            (true, true) => self.spi.transaction(&mut [Operation::Write(header)]).await,
+            (false, true) => {
+                self.spi
+                    .transaction(&mut [Operation::Write(header), Operation::Write(cmd.write_data)])
+                    .await
+            }
+            (true, false) => {
+                self.spi
+                    .transaction(&mut [Operation::Write(header), Operation::Read(cmd.read_buf)])
+                    .await
+            }
+            (false, false) => {
+                self.spi
+                    .transaction(&mut [
+                        Operation::Write(header),
+                        Operation::Write(cmd.write_data),
+                        Operation::Read(cmd.read_buf),
+                    ])
+                    .await
+            }
+        };
+
+        result.map_err(|_| Error::SpiTransferFailed)
+    }
+
+    async fn delay_us(&mut self, us: u32) {
+        self.delay.delay_us(us).await
+    }
+}
+
+#[cfg(all(test, feature = "alloc", feature = "is_sync"))]
+mod tests {
+    use super::*;
+    use crate::spi::{IoMode, opcodes};
+    use alloc::vec;
+    use embedded_hal_mock::eh1::delay::NoopDelay;
+    use embedded_hal_mock::eh1::spi::{Mock, Transaction};
+
+    #[test]
+    fn test_commands_become_transactions() {
+        let expectations = [
+            // RDID: opcode, then a read
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![opcodes::RDID]),
+            Transaction::read_vec(vec![0xEF, 0x40, 0x18]),
+            Transaction::transaction_end(),
+            // Page program: opcode and address, then the data
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![opcodes::PP, 0x12, 0x34, 0x56]),
+            Transaction::write_vec(vec![0xAA, 0x55]),
+            Transaction::transaction_end(),
+            // WREN: opcode only
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![opcodes::WREN]),
+            Transaction::transaction_end(),
+            // Fast read with 4-byte address: dummy byte after the address
+            Transaction::transaction_start(),
+            Transaction::write_vec(vec![0x0B, 0x01, 0x00, 0x00, 0x00, 0xFF]),
+            Transaction::read_vec(vec![0x5A, 0xA5]),
+            Transaction::transaction_end(),
+        ];
+        let spi = Mock::new(&expectations);
+        let mut master = EmbeddedHalSpi::new(spi, NoopDelay::new());
+
+        let mut id = [0u8; 3];
+        master
+            .execute(&mut SpiCommand::read_reg(opcodes::RDID, &mut id))
+            .unwrap();
+        assert_eq!(id, [0xEF, 0x40, 0x18]);
+
+        master
+            .execute(&mut SpiCommand::write_3b(
+                opcodes::PP,
+                0x123456,
+                &[0xAA, 0x55],
+            ))
+            .unwrap();
+        master
+            .execute(&mut SpiCommand::simple(opcodes::WREN))
+            .unwrap();
+
+        let mut buf = [0u8; 2];
+        master
+            .execute(&mut SpiCommand::read_4b(0x0B, 0x0100_0000, &mut buf).with_dummy_cycles(8))
+            .unwrap();
+        assert_eq!(buf, [0x5A, 0xA5]);
+
+        // Multi-I/O modes can't be expressed through SpiDevice
+        let mut cmd = SpiCommand::read_3b(0xEB, 0, &mut buf).with_io_mode(IoMode::QuadIo);
+        assert_eq!(master.execute(&mut cmd), Err(Error::IoModeNotSupported));
+
+        let (mut spi, _) = master.release();
+        spi.done();
+    }
+}
@@ -0,0 +1,79 @@
+//! SPI master wrapper that limits multi-I/O modes
+
+use super::{CsLevel, SpiFeatures, SpiMaster};
+use crate::error::Result;
+use crate::spi::SpiCommand;
+use maybe_async::maybe_async;
+
+/// Widest data bus used for flash reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BusWidth {
+    /// Single I/O only (1-1-1)
+    Single,
+    /// Up to dual I/O (1-1-2, 1-2-2)
+    Dual,
+    /// Up to quad I/O (1-1-4, 1-4-4)
+    Quad,
+}
+
+/// SPI master wrapper that hides dual and quad support above a bus width
+///
+/// Read modes are chosen from the master's [`SpiFeatures`], so masking them
+/// forces slower modes without touching the flash code. With
+/// [`BusWidth::Single`] every read uses a single-line command, which helps
+/// debugging wiring or programmer issues with multi-I/O.
+///
+/// With no limit, the master's own features are used unchanged.
+pub struct IoLimit<M> {
+    inner: M,
+    width: Option<BusWidth>,
+}
+
+impl<M: SpiMaster> IoLimit<M> {
+    /// Wrap a master, limiting it to `width`
+    pub fn new(inner: M, width: Option<BusWidth>) -> Self {
+        Self { inner, width }
+    }
+}
+
+#[maybe_async(AFIT)]
+impl<M: SpiMaster> SpiMaster for IoLimit<M> {
+    fn features(&self) -> SpiFeatures {
+        let features = self.inner.features();
+        match self.width {
+            None | Some(BusWidth::Quad) => features,
+            Some(BusWidth::Dual) => features - SpiFeatures::QUAD - SpiFeatures::QPI,
+            Some(BusWidth::Single) => {
+                features - SpiFeatures::DUAL - SpiFeatures::QUAD - SpiFeatures::QPI
+            }
+        }
+    }
+
+    fn max_read_len(&self) -> usize {
+        self.inner.max_read_len()
+    }
+
+    fn max_write_len(&self) -> usize {
+        self.inner.max_write_len()
+    }
+
+    async fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> Result<()> {
+        self.inner.execute(cmd).await
+    }
+
+    async fn execute_batch(&mut self, cmds: &mut [SpiCommand<'_>]) -> Result<()> {
+        self.inner.execute_batch(cmds).await
+    }
+
+    fn probe_opcode(&self, opcode: u8) -> bool {
+        self.inner.probe_opcode(opcode)
+    }
+
+    async fn delay_us(&mut self, us: u32) {
+        self.inner.delay_us(us).await
+    }
+
+    async fn send_clocks(&mut self, cycles: u32, cs: CsLevel) -> Result<()> {
+        self.inner.send_clocks(cycles, cs).await
+    }
+}
@@ -4,7 +4,15 @@
 //! to interact with flash chips.
 
 pub mod bitbang;
+mod chunk_limit;
+#[cfg(feature = "embedded-hal")]
+mod embedded_hal_spi;
+mod io_limit;
 mod traits;
 
 pub use bitbang::{BitbangDualIo, BitbangQuadIo, BitbangSpiMaster};
+pub use chunk_limit::ChunkLimit;
+#[cfg(feature = "embedded-hal")]
+pub use embedded_hal_spi::EmbeddedHalSpi;
+pub use io_limit::{BusWidth, IoLimit};
 pub use traits::*;
@@ -4,7 +4,7 @@
 //! - By default, traits are async (suitable for WASM/web, Embassy, tokio)
 //! - With the `is_sync` feature, traits become synchronous
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::spi::SpiCommand;
 use bitflags::bitflags;
 use maybe_async::maybe_async;
@@ -44,6 +44,17 @@ impl Default for SpiFeatures {
     }
 }
 
+/// Chip select level held while sending raw clock cycles
+///
+/// See [`SpiMaster::send_clocks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsLevel {
+    /// CS asserted (driven low) while clocking
+    Asserted,
+    /// CS deasserted (held high) while clocking
+    Deasserted,
+}
+
 /// SPI Master trait (sync or async depending on `is_sync` feature)
 ///
 /// This trait represents a programmer that can execute SPI commands.
@@ -119,6 +130,20 @@ pub trait SpiMaster {
     /// to single I/O mode and optionally log a warning.
     async fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> Result<()>;
 
+    /// Execute several SPI commands back to back
+    ///
+    /// Each command gets its own CS assertion, as with `execute`. Used for
+    /// sequences that need no status check in between, like WREN followed
+    /// by a page program. Programmers that can queue commands into one
+    /// transfer should override this; the default executes them in order
+    /// and stops at the first error.
+    async fn execute_batch(&mut self, cmds: &mut [SpiCommand<'_>]) -> Result<()> {
+        for cmd in cmds {
+            self.execute(cmd).await?;
+        }
+        Ok(())
+    }
+
     /// Check if an opcode is supported by this programmer
     ///
     /// Some programmers (like Intel internal) have restrictions on which
@@ -129,6 +154,17 @@ pub trait SpiMaster {
 
     /// Delay for the specified number of microseconds
     async fn delay_us(&mut self, us: u32);
+
+    /// Send raw clock cycles with no opcode or data
+    ///
+    /// Exiting continuous-read mode or resetting a chip stuck in QPI can
+    /// need a number of clocks with CS held at a given level and the data
+    /// lines high, which a `SpiCommand` cannot express. Programmers with
+    /// direct control over CS and SCK should override this; the default
+    /// returns `Error::OpcodeNotSupported`.
+    async fn send_clocks(&mut self, _cycles: u32, _cs: CsLevel) -> Result<()> {
+        Err(Error::OpcodeNotSupported)
+    }
 }
 
 /// Opaque master trait for programmers with restricted access
@@ -183,6 +219,10 @@ impl SpiMaster for alloc::boxed::Box<dyn SpiMaster + Send> {
         (**self).execute(cmd)
     }
 
+    fn execute_batch(&mut self, cmds: &mut [SpiCommand<'_>]) -> Result<()> {
+        (**self).execute_batch(cmds)
+    }
+
     fn probe_opcode(&self, opcode: u8) -> bool {
         (**self).probe_opcode(opcode)
     }
@@ -190,6 +230,47 @@ impl SpiMaster for alloc::boxed::Box<dyn SpiMaster + Send> {
     fn delay_us(&mut self, us: u32) {
         (**self).delay_us(us)
     }
+
+    fn send_clocks(&mut self, cycles: u32, cs: CsLevel) -> Result<()> {
+        (**self).send_clocks(cycles, cs)
+    }
+}
+
+// Blanket impl for borrowed SPI masters, so a flash device can be built on
+// top of a master the caller keeps owning (sync mode only)
+#[cfg(feature = "is_sync")]
+impl<M: SpiMaster + ?Sized> SpiMaster for &mut M {
+    fn features(&self) -> SpiFeatures {
+        (**self).features()
+    }
+
+    fn max_read_len(&self) -> usize {
+        (**self).max_read_len()
+    }
+
+    fn max_write_len(&self) -> usize {
+        (**self).max_write_len()
+    }
+
+    fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> Result<()> {
+        (**self).execute(cmd)
+    }
+
+    fn execute_batch(&mut self, cmds: &mut [SpiCommand<'_>]) -> Result<()> {
+        (**self).execute_batch(cmds)
+    }
+
+    fn probe_opcode(&self, opcode: u8) -> bool {
+        (**self).probe_opcode(opcode)
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        (**self).delay_us(us)
+    }
+
+    fn send_clocks(&mut self, cycles: u32, cs: CsLevel) -> Result<()> {
+        (**self).send_clocks(cycles, cs)
+    }
 }
 
 /// Helper function for implementing `SpiMaster::execute()`.
@@ -19,7 +19,7 @@
 //! - **QPI (4-4-4)**: Everything in quad mode
 
 use crate::error::{Error, Result};
-use crate::programmer::{SpiFeatures, SpiMaster};
+use crate::programmer::{CsLevel, SpiFeatures, SpiMaster};
 use crate::spi::{AddressWidth, IoMode, SpiCommand, opcodes};
 use maybe_async::maybe_async;
 
@@ -36,6 +36,13 @@ const PAGE_PROGRAM_TIMEOUT_US: u32 = 10_000;
 const CHIP_ERASE_POLL_US: u32 = 1_000_000;
 /// Timeout for chip erase completion (microseconds)
 const CHIP_ERASE_TIMEOUT_US: u32 = 200_000_000;
+/// Poll interval while waiting for an operation left over from a previous session (microseconds)
+const BUSY_ON_OPEN_POLL_US: u32 = 100_000;
+/// Timeout for an operation left over from a previous session, long enough
+/// for an interrupted block erase (microseconds)
+const BUSY_ON_OPEN_TIMEOUT_US: u32 = 10_000_000;
+/// Time for entering or leaving deep power-down (tDP, tRES, microseconds)
+const DEEP_POWER_DOWN_DELAY_US: u32 = 50;
 /// Poll interval for block erase completion (microseconds)
 pub const BLOCK_ERASE_POLL_US: u32 = 10_000;
 /// Timeout for block erase completion (microseconds)
@@ -87,6 +94,75 @@ pub async fn read_jedec_id<M: SpiMaster + ?Sized>(master: &mut M) -> Result<(u8,
     Ok((manufacturer, device))
 }
 
+/// Check whether a JEDEC manufacturer ID looks like a real chip answered
+///
+/// 0x00 and 0xFF are what a floating or held MISO line reads back.
+pub fn is_valid_jedec_manufacturer(manufacturer: u8) -> bool {
+    manufacturer != 0x00 && manufacturer != 0xFF
+}
+
+/// Read the JEDEC ID from a chip that is in QPI (4-4-4) mode
+///
+/// Tries RDID (Winbond and most others) and then QPIID (Macronix, ISSI)
+/// on four lines. Returns `None` if neither answers with a plausible ID.
+#[maybe_async]
+pub async fn read_jedec_id_qpi<M: SpiMaster + ?Sized>(master: &mut M) -> Result<Option<(u8, u16)>> {
+    for opcode in [opcodes::RDID, opcodes::QPIID] {
+        let mut buf = [0u8; 3];
+        let mut cmd = SpiCommand::read_reg(opcode, &mut buf);
+        cmd.io_mode = IoMode::Qpi;
+        master.execute(&mut cmd).await?;
+
+        if is_valid_jedec_manufacturer(buf[0]) {
+            let device = ((buf[1] as u16) << 8) | (buf[2] as u16);
+            return Ok(Some((buf[0], device)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Read the legacy manufacturer and device ID (REMS, 0x90)
+///
+/// Returns (manufacturer_id, device_id). The one-byte device ID is usually
+/// one less than the capacity byte of the chip's JEDEC device ID.
+#[maybe_async]
+pub async fn read_rems<M: SpiMaster + ?Sized>(master: &mut M) -> Result<(u8, u8)> {
+    let mut buf = [0u8; 2];
+    let mut cmd = SpiCommand::read_3b(opcodes::REMS, 0, &mut buf);
+    master.execute(&mut cmd).await?;
+    Ok((buf[0], buf[1]))
+}
+
+/// Leave QPI mode
+///
+/// Sends both the Winbond (0xFF) and Macronix (0xF5) exit commands in
+/// 4-4-4 mode. Once the chip is back in SPI mode, the second command is
+/// only two clocks on IO0 and is ignored.
+#[maybe_async]
+pub async fn exit_qpi<M: SpiMaster + ?Sized>(master: &mut M) -> Result<()> {
+    for opcode in [opcodes::RSTQIO, opcodes::RSTQIO_MX] {
+        let mut cmd = SpiCommand::simple(opcode);
+        cmd.io_mode = IoMode::Qpi;
+        master.execute(&mut cmd).await?;
+    }
+    Ok(())
+}
+
+/// Leave continuous-read (XIP) mode
+///
+/// A chip left in continuous-read mode by a fast dual or quad I/O read
+/// takes the next bytes as an address rather than an opcode. Clocking 16
+/// cycles with CS asserted and IO0 high sets the mode bits to 0xFF, which
+/// ends the mode; a chip in normal SPI mode ignores the clocks.
+///
+/// Returns `Error::OpcodeNotSupported` if the programmer can't send raw
+/// clocks.
+#[maybe_async]
+pub async fn exit_continuous_read<M: SpiMaster + ?Sized>(master: &mut M) -> Result<()> {
+    master.send_clocks(16, CsLevel::Asserted).await
+}
+
 /// Read the status register 1
 #[maybe_async]
 pub async fn read_status1<M: SpiMaster + ?Sized>(master: &mut M) -> Result<u8> {
@@ -138,6 +214,36 @@ pub async fn write_disable<M: SpiMaster + ?Sized>(master: &mut M) -> Result<()>
     master.execute(&mut cmd).await
 }
 
+/// Enter deep power-down (DP, 0xB9)
+///
+/// Supported by chips with the `DEEP_POWER_DOWN` feature. Until released
+/// with [`release_power_down`], the chip ignores every other command,
+/// including RDID.
+#[maybe_async]
+pub async fn deep_power_down<M: SpiMaster + ?Sized>(master: &mut M) -> Result<()> {
+    let mut cmd = SpiCommand::simple(opcodes::DP);
+    master.execute(&mut cmd).await?;
+    // tDP is at most a few microseconds
+    master.delay_us(DEEP_POWER_DOWN_DELAY_US).await;
+    Ok(())
+}
+
+/// Release from deep power-down (RES, 0xAB)
+///
+/// Returns the electronic signature byte the chip clocks out after three
+/// dummy bytes. Many chips return their legacy device ID there, others
+/// leave the bus undriven (0xFF). Chips that weren't powered down ignore
+/// the release and just return the signature.
+#[maybe_async]
+pub async fn release_power_down<M: SpiMaster + ?Sized>(master: &mut M) -> Result<u8> {
+    let mut buf = [0u8; 1];
+    let mut cmd = SpiCommand::read_3b(opcodes::RES, 0, &mut buf);
+    master.execute(&mut cmd).await?;
+    // The chip accepts commands again after tRES1/tRES2
+    master.delay_us(DEEP_POWER_DOWN_DELAY_US).await;
+    Ok(buf[0])
+}
+
 /// Wait for the WIP (Write In Progress) bit to clear
 ///
 /// Polls the status register until the Write In Progress bit clears.
@@ -303,18 +409,21 @@ pub async fn program_page_with_addressing<M: SpiMaster + ?Sized>(
         set_extended_address(master, features, (addr >> 24) as u8).await?;
     }
 
-    write_enable(master).await?;
-
-    let mut cmd = SpiCommand {
-        opcode,
-        address: Some(addr),
-        address_width: addressing.address_width(),
-        io_mode: IoMode::Single,
-        dummy_cycles: 0,
-        write_data: data,
-        read_buf: &mut [],
-    };
-    master.execute(&mut cmd).await?;
+    // WREN and the program go out together, no status check is needed
+    // in between
+    let mut cmds = [
+        SpiCommand::simple(opcodes::WREN),
+        SpiCommand {
+            opcode,
+            address: Some(addr),
+            address_width: addressing.address_width(),
+            io_mode: IoMode::Single,
+            dummy_cycles: 0,
+            write_data: data,
+            read_buf: &mut [],
+        },
+    ];
+    master.execute_batch(&mut cmds).await?;
 
     // Page program: poll every 10us, timeout after 10ms (typical is 0.7-5ms)
     wait_ready(master, PAGE_PROGRAM_POLL_US, PAGE_PROGRAM_TIMEOUT_US).await
@@ -474,35 +583,44 @@ pub async fn erase_block<M: SpiMaster + ?Sized>(
         set_extended_address(master, features, (addr >> 24) as u8).await?;
     }
 
-    write_enable(master).await?;
-
-    let mut cmd = SpiCommand {
-        opcode,
-        address: Some(addr),
-        address_width: addressing.address_width(),
-        io_mode: IoMode::Single,
-        dummy_cycles: 0,
-        write_data: &[],
-        read_buf: &mut [],
-    };
-    master.execute(&mut cmd).await?;
+    let mut cmds = [
+        SpiCommand::simple(opcodes::WREN),
+        SpiCommand {
+            opcode,
+            address: Some(addr),
+            address_width: addressing.address_width(),
+            io_mode: IoMode::Single,
+            dummy_cycles: 0,
+            write_data: &[],
+            read_buf: &mut [],
+        },
+    ];
+    master.execute_batch(&mut cmds).await?;
 
     wait_ready(master, poll_delay_us, timeout_us).await
 }
 
-/// Erase the entire chip
+/// Erase the entire chip with `opcode` (CE, 0x60 or 0xC7)
 ///
 /// Chip erase typically takes 25-100s for large chips.
-/// We poll every 1s with a 200s timeout.
+/// We poll every 1s and wait for at least 200s, or `timeout_us` if longer.
 #[maybe_async]
-pub async fn chip_erase<M: SpiMaster + ?Sized>(master: &mut M) -> Result<()> {
+pub async fn chip_erase<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    opcode: u8,
+    timeout_us: u32,
+) -> Result<()> {
     write_enable(master).await?;
 
-    let mut cmd = SpiCommand::simple(opcodes::CE_C7);
+    let mut cmd = SpiCommand::simple(opcode);
     master.execute(&mut cmd).await?;
 
-    // Chip erase: poll every 1s, timeout after 200s
-    wait_ready(master, CHIP_ERASE_POLL_US, CHIP_ERASE_TIMEOUT_US).await
+    wait_ready(
+        master,
+        CHIP_ERASE_POLL_US,
+        timeout_us.max(CHIP_ERASE_TIMEOUT_US),
+    )
+    .await
 }
 
 /// Enter 4-byte address mode with the plain B7h instruction.
@@ -545,6 +663,80 @@ pub async fn set_extended_address<M: SpiMaster + ?Sized>(
     master.execute(&mut cmd).await
 }
 
+/// Read the Spansion bank address register (BRRD, 0x16)
+#[maybe_async]
+pub async fn read_bank_register<M: SpiMaster + ?Sized>(master: &mut M) -> Result<u8> {
+    let mut buf = [0u8; 1];
+    let mut cmd = SpiCommand::read_reg(opcodes::BRRD, &mut buf);
+    master.execute(&mut cmd).await?;
+    Ok(buf[0])
+}
+
+/// Write the Spansion bank address register (BRWR, 0x17)
+///
+/// The bank register is volatile and doesn't need WREN.
+#[maybe_async]
+pub async fn write_bank_register<M: SpiMaster + ?Sized>(master: &mut M, value: u8) -> Result<()> {
+    let data = [value];
+    let mut cmd = SpiCommand::write_reg(opcodes::BRWR, &data);
+    master.execute(&mut cmd).await
+}
+
+/// Read the vendor-specific configuration register described by the chip features
+///
+/// `CONFIG_REG` chips (Macronix) are read with RDCR (0x15), `FUNCTION_REG`
+/// chips (ISSI) with RDFR (0x48). Returns `ChipNotSupported` otherwise.
+#[maybe_async]
+pub async fn read_config_register<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    features: crate::chip::Features,
+) -> Result<u8> {
+    use crate::chip::Features;
+
+    let opcode = if features.contains(Features::CONFIG_REG) {
+        opcodes::RDCR_MX
+    } else if features.contains(Features::FUNCTION_REG) {
+        opcodes::RDFR
+    } else {
+        return Err(Error::ChipNotSupported);
+    };
+    let mut buf = [0u8; 1];
+    let mut cmd = SpiCommand::read_reg(opcode, &mut buf);
+    master.execute(&mut cmd).await?;
+    Ok(buf[0])
+}
+
+/// Write the vendor-specific configuration register described by the chip features
+///
+/// Macronix has no separate write command: the configuration register is
+/// the second byte of WRSR, so status register 1 is read and written back
+/// unchanged. ISSI function register bits are OTP on most parts, so
+/// callers should only set bits they mean to set permanently.
+#[maybe_async]
+pub async fn write_config_register<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    features: crate::chip::Features,
+    value: u8,
+) -> Result<()> {
+    use crate::chip::Features;
+
+    if features.contains(Features::CONFIG_REG) {
+        let sr1 = read_status1(master).await?;
+        write_enable(master).await?;
+        let data = [sr1, value];
+        let mut cmd = SpiCommand::write_reg(opcodes::WRCR_MX, &data);
+        master.execute(&mut cmd).await?;
+    } else if features.contains(Features::FUNCTION_REG) {
+        write_enable(master).await?;
+        let data = [value];
+        let mut cmd = SpiCommand::write_reg(opcodes::WRFR, &data);
+        master.execute(&mut cmd).await?;
+    } else {
+        return Err(Error::ChipNotSupported);
+    }
+    wait_ready(master, WRSR_POLL_US, WRSR_TIMEOUT_US).await
+}
+
 /// Enter 4-byte address mode using the method described by the chip features.
 #[maybe_async]
 pub async fn enter_4byte_mode_with_features<M: SpiMaster + ?Sized>(
@@ -597,6 +789,38 @@ pub async fn software_reset<M: SpiMaster + ?Sized>(master: &mut M) -> Result<()>
     Ok(())
 }
 
+/// Return the chip to its power-on state before probing
+///
+/// Sends the software reset sequence RSTEN (0x66) + RST (0x99), which also
+/// leaves 4-byte address mode. If the programmer supports QPI, the reset
+/// and the QPI exit commands are first sent on four lines too, for chips
+/// left in QPI mode by a previous session. A reset aborts any program or
+/// erase still in progress.
+///
+/// Before any of that, programmers that can send raw clocks end
+/// continuous-read mode with [`exit_continuous_read`]; otherwise the
+/// reset opcodes would be taken as an address.
+///
+/// Not all chips support software reset. Those that don't ignore the
+/// commands, so this only fails if the programmer does.
+#[maybe_async]
+pub async fn reset_before_probe<M: SpiMaster + ?Sized>(master: &mut M) -> Result<()> {
+    match exit_continuous_read(master).await {
+        Ok(()) | Err(Error::OpcodeNotSupported) => {}
+        Err(e) => return Err(e),
+    }
+    if master.features().contains(SpiFeatures::QPI) {
+        for opcode in [opcodes::RSTEN, opcodes::RST] {
+            let mut cmd = SpiCommand::simple(opcode);
+            cmd.io_mode = IoMode::Qpi;
+            master.execute(&mut cmd).await?;
+        }
+        master.delay_us(100).await;
+        exit_qpi(master).await?;
+    }
+    software_reset(master).await
+}
+
 /// Read SFDP (Serial Flash Discoverable Parameters)
 #[maybe_async]
 pub async fn read_sfdp<M: SpiMaster + ?Sized>(
@@ -640,6 +864,45 @@ pub async fn is_busy<M: SpiMaster + ?Sized>(master: &mut M) -> Result<bool> {
     Ok(status & opcodes::SR1_WIP != 0)
 }
 
+/// Wait for an operation left running by a previous session
+///
+/// If a previous session was interrupted in the middle of an erase or
+/// program (crash, USB disconnect), the chip may still have WIP set when it
+/// is opened again and ignore the first commands. This checks WIP and, if
+/// set, waits up to 10 s for it to clear, enough for a block erase.
+///
+/// This is best effort: a failed status read or a WIP bit that doesn't
+/// clear, e.g. a status byte stuck at 0x01, is only logged, and probing
+/// goes on. A status of 0xFF is treated as "no chip" (floating MISO) rather
+/// than busy, so probing an empty socket does not stall.
+///
+/// Returns `true` if the chip was busy and had to be waited for.
+#[maybe_async]
+pub async fn wait_ready_on_open<M: SpiMaster + ?Sized>(master: &mut M) -> bool {
+    let status = match read_status1(master).await {
+        Ok(status) => status,
+        Err(e) => {
+            log::warn!("Failed to read the status register on open: {}", e);
+            return false;
+        }
+    };
+    if status == 0xFF || status & opcodes::SR1_WIP == 0 {
+        return false;
+    }
+
+    log::warn!(
+        "Flash is busy (SR1=0x{:02X}), possibly recovering from an interrupted \
+         prior operation; waiting for it to complete",
+        status
+    );
+    match wait_ready(master, BUSY_ON_OPEN_POLL_US, BUSY_ON_OPEN_TIMEOUT_US).await {
+        Ok(()) => log::info!("Flash is ready"),
+        Err(e) => log::warn!("Flash still busy, probing anyway: {}", e),
+    }
+
+    true
+}
+
 // ============================================================================
 // Multi-I/O Read Functions
 // ============================================================================
@@ -862,12 +1125,37 @@ pub enum QuadEnableMethod {
     Sr2Bit1WriteSr,
     /// QE is bit 6 of SR1
     Sr1Bit6,
-    /// QE is bit 7 of SR2 (use special sequence)
+    /// QE is bit 7 of SR2, read with 0x3F and written with 0x3E
     Sr2Bit7,
     /// QE is bit 1 of SR2, use dedicated 0x31 command
     Sr2Bit1WriteSr2,
 }
 
+impl From<crate::sfdp::QuadEnableRequirement> for QuadEnableMethod {
+    fn from(qer: crate::sfdp::QuadEnableRequirement) -> Self {
+        use crate::sfdp::QuadEnableRequirement as Qer;
+        match qer {
+            Qer::None => Self::None,
+            Qer::Sr2Bit1_WriteCmd01 | Qer::Sr2Bit1_WriteCmd01_StatusSplit => Self::Sr2Bit1WriteSr,
+            Qer::Sr1Bit6_WriteCmd01 => Self::Sr1Bit6,
+            Qer::Sr2Bit7_WriteCmdSpecial => Self::Sr2Bit7,
+            Qer::Sr2Bit1_WriteCmd31 => Self::Sr2Bit1WriteSr2,
+        }
+    }
+}
+
+impl core::fmt::Display for QuadEnableMethod {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::Sr2Bit1WriteSr => "SR2 bit 1 (written with 01h)",
+            Self::Sr1Bit6 => "SR1 bit 6 (written with 01h)",
+            Self::Sr2Bit7 => "SR2 bit 7 (written with 3Eh)",
+            Self::Sr2Bit1WriteSr2 => "SR2 bit 1 (written with 31h)",
+        })
+    }
+}
+
 /// Enable quad mode using the appropriate method for the chip
 #[maybe_async]
 pub async fn enable_quad_mode<M: SpiMaster + ?Sized>(
@@ -895,11 +1183,11 @@ pub async fn enable_quad_mode<M: SpiMaster + ?Sized>(
         }
         QuadEnableMethod::Sr2Bit7 => {
             // QE is bit 7 of SR2 - use special sequence
-            let sr2 = read_status2(master).await?;
+            let sr2 = read_status2_alt(master).await?;
             if sr2 & 0x80 != 0 {
                 return Ok(()); // Already enabled
             }
-            write_status2_direct(master, sr2 | 0x80).await
+            write_status2_alt(master, sr2 | 0x80).await
         }
         QuadEnableMethod::Sr2Bit1WriteSr2 => {
             // QE is bit 1 of SR2, use dedicated 0x31 command
@@ -936,11 +1224,11 @@ pub async fn disable_quad_mode<M: SpiMaster + ?Sized>(
             write_status1(master, sr1 & !0x40).await
         }
         QuadEnableMethod::Sr2Bit7 => {
-            let sr2 = read_status2(master).await?;
+            let sr2 = read_status2_alt(master).await?;
             if sr2 & 0x80 == 0 {
                 return Ok(()); // Already disabled
             }
-            write_status2_direct(master, sr2 & !0x80).await
+            write_status2_alt(master, sr2 & !0x80).await
         }
         QuadEnableMethod::Sr2Bit1WriteSr2 => {
             let sr2 = read_status2(master).await?;
@@ -963,6 +1251,25 @@ async fn write_status2_direct<M: SpiMaster + ?Sized>(master: &mut M, value: u8)
     wait_ready(master, WRSR_POLL_US, WRSR_TIMEOUT_US).await
 }
 
+/// Read SR2 using opcode 0x3F, on chips with QE in bit 7
+#[maybe_async]
+async fn read_status2_alt<M: SpiMaster + ?Sized>(master: &mut M) -> Result<u8> {
+    let mut buf = [0u8; 1];
+    let mut cmd = SpiCommand::read_reg(opcodes::RDSR2_ALT, &mut buf);
+    master.execute(&mut cmd).await?;
+    Ok(buf[0])
+}
+
+/// Write SR2 using opcode 0x3E, on chips with QE in bit 7
+#[maybe_async]
+async fn write_status2_alt<M: SpiMaster + ?Sized>(master: &mut M, value: u8) -> Result<()> {
+    write_enable(master).await?;
+    let data = [value];
+    let mut cmd = SpiCommand::write_reg(opcodes::WRSR2_ALT, &data);
+    master.execute(&mut cmd).await?;
+    wait_ready(master, WRSR_POLL_US, WRSR_TIMEOUT_US).await
+}
+
 /// Check if quad mode is enabled
 #[maybe_async]
 pub async fn is_quad_enabled<M: SpiMaster + ?Sized>(
@@ -980,7 +1287,7 @@ pub async fn is_quad_enabled<M: SpiMaster + ?Sized>(
             Ok(sr1 & 0x40 != 0)
         }
         QuadEnableMethod::Sr2Bit7 => {
-            let sr2 = read_status2(master).await?;
+            let sr2 = read_status2_alt(master).await?;
             Ok(sr2 & 0x80 != 0)
         }
     }
@@ -1025,7 +1332,10 @@ pub async fn exit_qpi_mode<M: SpiMaster + ?Sized>(master: &mut M, opcode: u8) ->
 ///
 /// Returns the I/O mode, opcode, and whether the selected opcode is a native
 /// 4-byte-address opcode. Prefers higher bandwidth modes:
-/// Quad I/O > Quad Out > Dual I/O > Dual Out > Single.
+/// Quad I/O > Quad Out > Dual I/O > Dual Out > Fast Read > Read.
+///
+/// Quad modes may need the Quad Enable bit set first, see
+/// [`quad_enable_method`] and `FlashContext::quad_enable_method`.
 pub fn select_read_mode(
     master_features: SpiFeatures,
     chip_features: crate::chip::Features,
@@ -1069,6 +1379,9 @@ pub fn select_read_mode(
             }
         }
 
+        if chip_features.supports_4ba_fast_read() && opcode_supported(opcodes::FAST_READ_4B) {
+            return (IoMode::Single, opcodes::FAST_READ_4B, true);
+        }
         if chip_features.supports_4ba_read() && opcode_supported(opcodes::READ_4B) {
             return (IoMode::Single, opcodes::READ_4B, true);
         }
@@ -1109,5 +1422,25 @@ pub fn select_read_mode(
         }
     }
 
+    if chip_features.contains(crate::chip::Features::FAST_READ)
+        && opcode_supported(opcodes::FAST_READ)
+    {
+        return (IoMode::Single, opcodes::FAST_READ, false);
+    }
+
     (IoMode::Single, opcodes::READ, false)
 }
+
+/// Get the Quad Enable method described by the chip features
+///
+/// `QE_SR2` chips have QE in bit 1 of status register 2, written together
+/// with SR1 using a two-byte WRSR, which nearly all of them accept.
+///
+/// Returns `None` if the features don't say where the QE bit is. That is
+/// not the same as [`QuadEnableMethod::None`], which only SFDP reports for
+/// chips that really have no QE bit.
+pub fn quad_enable_method(chip_features: crate::chip::Features) -> Option<QuadEnableMethod> {
+    chip_features
+        .contains(crate::chip::Features::QE_SR2)
+        .then_some(QuadEnableMethod::Sr2Bit1WriteSr)
+}
@@ -232,6 +232,36 @@ fn parse_bfpt_erase_types(dword8: u32, dword9: u32, params: &mut BasicFlashParam
     params.erase_types[3] = SfdpEraseType::from_raw(et4_size, et4_opcode);
 }
 
+/// Convert a typical SFDP time to the maximum in microseconds
+///
+/// `count` is the 5-bit "count - 1" field, `unit_us` the unit it counts,
+/// and the maximum is `2 * (multiplier + 1)` times the typical time.
+fn sfdp_max_time_us(count: u32, unit_us: u64, multiplier: u32) -> u32 {
+    let typical = (count as u64 + 1) * unit_us;
+    let max = typical * 2 * (multiplier as u64 + 1);
+    u32::try_from(max).unwrap_or(u32::MAX)
+}
+
+/// Parse Basic Flash Parameter Table DWORD 10
+///
+/// Contains the erase times of the erase types from DWORDs 8-9.
+fn parse_bfpt_dword10(dword: u32, params: &mut BasicFlashParams) {
+    // Bits [3:0] - Multiplier from typical to maximum erase time
+    let multiplier = dword & 0x0F;
+
+    // Each erase type has a 7-bit time starting at bit 4: [4:0] count,
+    // [6:5] units (1ms, 16ms, 128ms, 1s)
+    const UNITS_US: [u64; 4] = [1_000, 16_000, 128_000, 1_000_000];
+    for (i, erase_type) in params.erase_types.iter_mut().enumerate() {
+        if !erase_type.is_valid() {
+            continue;
+        }
+        let time = (dword >> (4 + 7 * i)) & 0x7F;
+        let unit_us = UNITS_US[(time >> 5) as usize];
+        erase_type.max_time_us = sfdp_max_time_us(time & 0x1F, unit_us, multiplier);
+    }
+}
+
 /// Parse Basic Flash Parameter Table DWORD 11
 ///
 /// Contains page size and timing information.
@@ -244,6 +274,14 @@ fn parse_bfpt_dword11(dword: u32, params: &mut BasicFlashParams) {
         // Default to 256 bytes if not specified
         params.page_size = 256;
     }
+
+    // Bits [3:0]   - Multiplier from typical to maximum program/erase time
+    // Bits [28:24] - Chip erase typical time count
+    // Bits [30:29] - Chip erase units (16ms, 256ms, 4s, 64s)
+    const UNITS_US: [u64; 4] = [16_000, 256_000, 4_000_000, 64_000_000];
+    let multiplier = dword & 0x0F;
+    let unit_us = UNITS_US[((dword >> 29) & 0x03) as usize];
+    params.chip_erase_max_time_us = sfdp_max_time_us((dword >> 24) & 0x1F, unit_us, multiplier);
 }
 
 /// Parse Basic Flash Parameter Table DWORD 15
@@ -252,7 +290,7 @@ fn parse_bfpt_dword11(dword: u32, params: &mut BasicFlashParams) {
 fn parse_bfpt_dword15(dword: u32, params: &mut BasicFlashParams) {
     // Bits [22:20] - Quad Enable Requirements
     let qer = ((dword >> 20) & 0x07) as u8;
-    params.quad_enable = QuadEnableRequirement::from_bfpt(qer);
+    params.quad_enable = Some(QuadEnableRequirement::from_bfpt(qer));
 }
 
 /// Parse Basic Flash Parameter Table DWORD 16
@@ -316,8 +354,8 @@ async fn parse_bfpt<M: SpiMaster + ?Sized>(
 
     // Parse extended DWORDs if available (JESD216A+, 16+ DWORDs)
     if len >= 44 {
-        // DWORD 11
-        parse_bfpt_dword11(get_dword(40), &mut params);
+        parse_bfpt_dword10(get_dword(36), &mut params); // DWORD 10
+        parse_bfpt_dword11(get_dword(40), &mut params); // DWORD 11
     }
 
     // Parse JESD216B+ additions (DWORDs 15-16)
@@ -433,6 +471,20 @@ pub async fn probe<M: SpiMaster + ?Sized>(master: &mut M) -> Result<SfdpInfo> {
                     info.four_byte_addr_table = Some(table);
                 }
             }
+            // Status, Control and Configuration Register Map
+            PARAM_ID_SCCR_MAP => {
+                let map = SccrMap {
+                    revision: param_header.revision,
+                    len_dwords: param_header.length_dwords as usize,
+                };
+                log::debug!(
+                    "Found SCCR map: rev {}.{}, {} DWORDs",
+                    map.revision.major,
+                    map.revision.minor,
+                    map.len_dwords
+                );
+                info.sccr_map = Some(map);
+            }
             // Other tables we might support in the future
             _ => {
                 log::trace!(
@@ -467,12 +519,35 @@ pub async fn is_supported<M: SpiMaster + ?Sized>(master: &mut M) -> bool {
     signature == SFDP_SIGNATURE
 }
 
+/// Read the raw SFDP region
+///
+/// Reads the header and parameter headers to find the end of the last
+/// parameter table, then returns all bytes from address 0 up to that point.
+/// Useful for dumping SFDP data of unknown chips.
+#[cfg(feature = "alloc")]
+#[maybe_async]
+pub async fn read_raw<M: SpiMaster + ?Sized>(master: &mut M) -> Result<Vec<u8>> {
+    let header = parse_header(master).await?;
+    let num_headers = header.num_param_headers().min(MAX_PARAMETER_HEADERS);
+
+    let mut end = 0x08 + num_headers * 8;
+    for i in 0..num_headers {
+        let param_header = read_param_header(master, i).await?;
+        let table_end = param_header.table_pointer as usize + param_header.length_bytes();
+        end = end.max(table_end.min(MAX_RAW_SFDP_SIZE));
+    }
+
+    let mut data = alloc::vec![0u8; end];
+    read_sfdp(master, 0, &mut data).await?;
+    Ok(data)
+}
+
 // ============================================================================
 // Conversion to FlashChip
 // ============================================================================
 
 #[cfg(feature = "alloc")]
-use alloc::{string::String, vec::Vec};
+use alloc::{format, string::String, vec::Vec};
 
 #[cfg(feature = "alloc")]
 use crate::chip::{EraseBlock, EraseRegion, Features, FlashChip, WriteGranularity};
@@ -480,8 +555,8 @@ use crate::chip::{EraseBlock, EraseRegion, Features, FlashChip, WriteGranularity
 /// Convert SFDP info to a FlashChip structure
 ///
 /// This creates a FlashChip populated with data discovered from SFDP.
-/// The vendor is set to "SFDP" and name to "Unknown" since SFDP doesn't
-/// provide chip identification (use JEDEC ID for that).
+/// SFDP doesn't provide chip identification, so the vendor is set to
+/// "Unknown" and the name is derived from the JEDEC ID (e.g. "JEDEC C2:2017").
 #[cfg(feature = "alloc")]
 pub fn to_flash_chip(info: &SfdpInfo, jedec_manufacturer: u8, jedec_device: u16) -> FlashChip {
     let params = &info.basic_params;
@@ -561,13 +636,55 @@ pub fn to_flash_chip(info: &SfdpInfo, jedec_manufacturer: u8, jedec_device: u16)
     } else {
         features |= Features::WRSR_EWSR;
     }
-    if params.quad_enable.is_needed() {
+    // QE_SR2 only describes QE in bit 1 of SR2, the context takes other
+    // locations from SFDP directly
+    if params.quad_enable.is_some_and(|qer| {
+        matches!(
+            qer,
+            QuadEnableRequirement::Sr2Bit1_WriteCmd01
+                | QuadEnableRequirement::Sr2Bit1_WriteCmd31
+                | QuadEnableRequirement::Sr2Bit1_WriteCmd01_StatusSplit
+        )
+    }) {
         features |= Features::QE_SR2;
     }
 
-    // Build erase blocks from SFDP data.
-    // Each BFPT erase type covers the entire chip uniformly; the optional 4BA
-    // instruction table supplies a native 4-byte opcode for the same erase type.
+    let erase_blocks = sfdp_erase_blocks(info);
+
+    // Determine write granularity
+    let write_granularity = if params.write_granularity_64 {
+        WriteGranularity::Page
+    } else {
+        WriteGranularity::Byte
+    };
+
+    FlashChip {
+        vendor: String::from("Unknown"),
+        name: format!("JEDEC {:02X}:{:04X}", jedec_manufacturer, jedec_device),
+        aliases: Vec::new(),
+        jedec_manufacturer,
+        jedec_device,
+        total_size: params.density_bytes as u32,
+        page_size: params.page_size as u16,
+        features,
+        voltage_min_mv: 2700, // Default, SFDP doesn't specify voltage
+        voltage_max_mv: 3600,
+        write_granularity,
+        erase_blocks,
+        tested: Default::default(),
+        wp_bits: None,
+        wp_decoder: Default::default(),
+    }
+}
+
+/// Build the erase blocks SFDP describes, smallest first
+///
+/// Each BFPT erase type covers the entire chip uniformly; the optional 4BA
+/// instruction table supplies a native 4-byte opcode for the same erase type.
+/// Chip erase isn't described by SFDP, so it is never included.
+#[cfg(feature = "alloc")]
+pub fn sfdp_erase_blocks(info: &SfdpInfo) -> Vec<EraseBlock> {
+    let params = &info.basic_params;
     let total_size = params.density_bytes as u32;
     let mut erase_blocks: Vec<EraseBlock> = params
         .erase_types
@@ -578,12 +695,7 @@ pub fn to_flash_chip(info: &SfdpInfo, jedec_manufacturer: u8, jedec_device: u16)
             let opcode_4b = info
                 .four_byte_addr_table
                 .as_ref()
-                .filter(|table| {
-                    table
-                        .instructions
-                        .supports(FourByteAddrInstructions::ERASE_TYPE_1 << type_index)
-                })
-                .and_then(|table| table.erase_opcodes.opcode_for_type(type_index))
+                .and_then(|table| table.erase_opcode(type_index))
                 .filter(|&opcode| opcode != et.opcode);
             EraseBlock::with_regions_and_4b(
                 et.opcode,
@@ -595,28 +707,7 @@ pub fn to_flash_chip(info: &SfdpInfo, jedec_manufacturer: u8, jedec_device: u16)
 
     // Sort by size (smallest first)
     erase_blocks.sort_by_key(|eb| eb.min_block_size());
-
-    // Determine write granularity
-    let write_granularity = if params.write_granularity_64 {
-        WriteGranularity::Page
-    } else {
-        WriteGranularity::Byte
-    };
-
-    FlashChip {
-        vendor: String::from("SFDP"),
-        name: String::from("Unknown"),
-        jedec_manufacturer,
-        jedec_device,
-        total_size: params.density_bytes as u32,
-        page_size: params.page_size as u16,
-        features,
-        voltage_min_mv: 2700, // Default, SFDP doesn't specify voltage
-        voltage_max_mv: 3600,
-        write_granularity,
-        erase_blocks,
-        tested: Default::default(),
-    }
+    erase_blocks
 }
 
 // ============================================================================
@@ -673,6 +764,19 @@ pub enum SfdpMismatch {
     },
 }
 
+#[cfg(feature = "alloc")]
+impl SfdpMismatch {
+    /// Whether this mismatch is about the erase blocks
+    pub fn is_erase_block(&self) -> bool {
+        matches!(
+            self,
+            Self::MissingEraseBlock { .. }
+                | Self::ExtraEraseBlock { .. }
+                | Self::EraseBlockOpcode { .. }
+        )
+    }
+}
+
 #[cfg(feature = "alloc")]
 impl core::fmt::Display for SfdpMismatch {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
@@ -818,6 +922,22 @@ pub fn compare_with_chip(info: &SfdpInfo, chip: &FlashChip) -> Vec<SfdpMismatch>
     mismatches
 }
 
+/// Pick the database chip that best matches the SFDP data
+///
+/// Used to tell apart chips sharing a JEDEC ID: the candidate with the
+/// fewest mismatches wins, earlier candidates winning ties. Returns the
+/// chip together with its mismatches, or `None` if there are no candidates.
+#[cfg(feature = "alloc")]
+pub fn best_match<'a>(
+    info: &SfdpInfo,
+    candidates: impl IntoIterator<Item = &'a FlashChip>,
+) -> Option<(&'a FlashChip, Vec<SfdpMismatch>)> {
+    candidates
+        .into_iter()
+        .map(|chip| (chip, compare_with_chip(info, chip)))
+        .min_by_key(|(_, mismatches)| mismatches.len())
+}
+
 /// Result of probing SFDP and optionally matching with database
 #[cfg(feature = "std")]
 #[derive(Debug)]
@@ -890,17 +1010,12 @@ pub async fn probe_with_database<M: SpiMaster + ?Sized>(
     // Probe SFDP
     let sfdp = probe(master).await?;
 
-    // Look up in database
-    let database_chip = db
-        .find_by_jedec_id(jedec_manufacturer, jedec_device)
-        .cloned();
-
-    // Compare if we have a database entry
-    let mismatches = if let Some(ref chip) = database_chip {
-        compare_with_chip(&sfdp, chip)
-    } else {
-        Vec::new()
-    };
+    // Look up in database, using SFDP to pick among chips sharing the ID
+    let (database_chip, mismatches) =
+        match best_match(&sfdp, db.find_by_jedec_id(jedec_manufacturer, jedec_device)) {
+            Some((chip, mismatches)) => (Some(chip.clone()), mismatches),
+            None => (None, Vec::new()),
+        };
 
     Ok(SfdpProbeResult {
         sfdp,
@@ -987,6 +1102,30 @@ mod tests {
         assert_eq!(params.erase_types[3].opcode, 0xD8);
     }
 
+    #[test]
+    fn test_erase_time_parsing() {
+        let mut params = BasicFlashParams::default();
+        parse_bfpt_erase_types(0x520F_200C, 0x0000_D810, &mut params);
+
+        // Multiplier 2 (max = 6 * typical), then per type [6:5] units and
+        // [4:0] count - 1: 5 * 16ms, 10 * 16ms, 2 * 128ms and an unused
+        // fourth type
+        let dword10: u32 = 2 | (0x24 << 4) | (0x29 << 11) | (0x41 << 18) | (0x61 << 25);
+        parse_bfpt_dword10(dword10, &mut params);
+
+        assert_eq!(params.erase_types[0].max_time_us, 480_000);
+        assert_eq!(params.erase_types[1].max_time_us, 960_000);
+        assert_eq!(params.erase_types[2].max_time_us, 1_536_000);
+        assert_eq!(params.erase_types[3].max_time_us, 0);
+
+        // Multiplier 2, 256-byte pages, chip erase 10 * 4s
+        let dword11: u32 = 2 | (8 << 4) | (9 << 24) | (2 << 29);
+        parse_bfpt_dword11(dword11, &mut params);
+
+        assert_eq!(params.page_size, 256);
+        assert_eq!(params.chip_erase_max_time_us, 240_000_000);
+    }
+
     #[test]
     fn test_address_mode() {
         assert!(!AddressMode::ThreeByteOnly.requires_4byte());
@@ -1160,6 +1299,21 @@ mod tests {
             !params.requires_4byte_addr(),
             "8 MiB chip should not require 4-byte addressing"
         );
+
+        // The SFDP 1.0 BFPT has no DWORD 15, so QE is left to the database
+        assert_eq!(params.quad_enable, None);
+        assert!(info.sccr_map.is_none());
+    }
+
+    #[test]
+    #[cfg(all(feature = "alloc", feature = "is_sync"))]
+    fn test_read_raw_covers_all_tables() {
+        let mut mock = MockSfdpFlash::new();
+        let raw = read_raw(&mut mock).expect("SFDP dump should succeed");
+
+        // The Macronix table is the last one and ends at 0x58
+        assert_eq!(raw.len(), MX25L6436E_SFDP.len());
+        assert_eq!(raw[..], MX25L6436E_SFDP[..]);
     }
 
     #[test]
@@ -1173,6 +1327,8 @@ mod tests {
 
         assert_eq!(chip.jedec_manufacturer, 0xC2);
         assert_eq!(chip.jedec_device, 0x2017);
+        assert_eq!(chip.vendor, "Unknown");
+        assert_eq!(chip.name, "JEDEC C2:2017");
         assert_eq!(chip.total_size, 8 * 1024 * 1024);
 
         // Should have erase blocks
@@ -1319,6 +1475,74 @@ mod tests {
         assert_eq!(opcodes.opcode_for_type(3), None); // 0x00 means not supported
     }
 
+    #[test]
+    fn test_4byte_addr_table_erase_opcode() {
+        let table = FourByteAddrTable {
+            revision: SfdpRevision::default(),
+            // Erase types 1 and 3 supported with 4-byte addresses
+            instructions: FourByteAddrInstructions::from_dword1((1 << 9) | (1 << 11)),
+            erase_opcodes: FourByteAddrEraseOpcodes::from_dword2(0xDC_DC_5C_21),
+        };
+
+        assert_eq!(table.erase_opcode(0), Some(0x21));
+        // Opcode present in DWORD 2 but not advertised in DWORD 1
+        assert_eq!(table.erase_opcode(1), None);
+        assert_eq!(table.erase_opcode(2), Some(0xDC));
+        assert_eq!(table.erase_opcode(3), None);
+        assert_eq!(table.erase_opcode(4), None);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_to_flash_chip_uses_4byte_addr_table() {
+        let mut basic_params = BasicFlashParams {
+            density_bytes: 32 * 1024 * 1024,
+            page_size: 256,
+            address_mode: AddressMode::ThreeOrFourByte,
+            ..Default::default()
+        };
+        basic_params.erase_types[0] = SfdpEraseType::from_raw(12, 0x20);
+        basic_params.erase_types[1] = SfdpEraseType::from_raw(15, 0x52);
+        basic_params.erase_types[2] = SfdpEraseType::from_raw(16, 0xD8);
+
+        let info = SfdpInfo {
+            basic_params,
+            four_byte_addr_table: Some(FourByteAddrTable {
+                revision: SfdpRevision::default(),
+                instructions: FourByteAddrInstructions::from_dword1(
+                    FourByteAddrInstructions::READ_1S_1S_1S
+                        | FourByteAddrInstructions::FAST_READ_1S_1S_1S
+                        | FourByteAddrInstructions::PAGE_PROGRAM_1S_1S_1S
+                        | FourByteAddrInstructions::ERASE_TYPE_1
+                        | FourByteAddrInstructions::ERASE_TYPE_3,
+                ),
+                erase_opcodes: FourByteAddrEraseOpcodes::from_dword2(0x00_DC_5C_21),
+            }),
+            ..Default::default()
+        };
+
+        let chip = to_flash_chip(&info, 0xEF, 0x4019);
+        assert!(
+            chip.features
+                .contains(crate::chip::Features::FOUR_BYTE_NATIVE)
+        );
+        assert!(
+            chip.features
+                .contains(crate::chip::Features::FOUR_BYTE_PROGRAM)
+        );
+
+        let opcodes: Vec<(u8, Option<u8>)> = chip
+            .erase_blocks
+            .iter()
+            .map(|eb| (eb.opcode, eb.opcode_4b))
+            .collect();
+        // 32K erase has an opcode in DWORD 2 but isn't flagged in DWORD 1
+        assert_eq!(
+            opcodes,
+            [(0x20, Some(0x21)), (0x52, None), (0xD8, Some(0xDC))]
+        );
+    }
+
     #[test]
     #[cfg(feature = "is_sync")]
     fn test_mx25l6436e_fast_read_params() {
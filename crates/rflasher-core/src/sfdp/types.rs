@@ -14,6 +14,9 @@ pub const MAX_PARAMETER_HEADERS: usize = 16;
 /// Maximum parameter table size in bytes (256 DWORDs * 4)
 pub const MAX_PARAMETER_TABLE_SIZE: usize = 1024;
 
+/// Upper bound for a raw SFDP dump, guards against garbage table pointers
+pub const MAX_RAW_SFDP_SIZE: usize = 64 * 1024;
+
 // ============================================================================
 // Parameter IDs (MSB << 8 | LSB)
 // ============================================================================
@@ -38,6 +41,7 @@ pub const PARAM_ID_SCCR_MAP: u16 = 0xFF87;
 /// Contains the opcode, number of mode clocks, and number of dummy/wait cycles
 /// needed for a specific fast read mode.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct FastReadParams {
     /// Instruction opcode (0x00 if not supported)
     pub opcode: u8,
@@ -99,6 +103,7 @@ impl FastReadParams {
 
 /// SFDP revision information
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct SfdpRevision {
     /// Major revision number
     pub major: u8,
@@ -143,6 +148,7 @@ impl core::fmt::Display for SfdpRevision {
 
 /// SFDP header structure (first 8 bytes at address 0x00)
 #[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct SfdpHeader {
     /// SFDP signature (should be 0x50444653)
     pub signature: u32,
@@ -187,6 +193,7 @@ impl SfdpHeader {
 
 /// Parameter header structure (8 bytes each, starting at address 0x08)
 #[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct ParameterHeader {
     /// Parameter ID (MSB << 8 | LSB)
     pub id: u16,
@@ -236,6 +243,7 @@ impl ParameterHeader {
 
 /// Flash addressing mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub enum AddressMode {
     /// 3-byte addressing only (up to 16 MiB)
     #[default]
@@ -274,11 +282,14 @@ impl AddressMode {
 
 /// Erase type from SFDP (up to 4 types supported)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct SfdpEraseType {
     /// Erase opcode
     pub opcode: u8,
     /// Erase size in bytes (0 if not supported)
     pub size: u32,
+    /// Maximum erase time in microseconds (0 if not published, JESD216A+)
+    pub max_time_us: u32,
 }
 
 impl SfdpEraseType {
@@ -295,6 +306,7 @@ impl SfdpEraseType {
             Self {
                 opcode,
                 size: 1u32 << size_exp,
+                max_time_us: 0,
             }
         }
     }
@@ -306,6 +318,7 @@ impl SfdpEraseType {
 
 /// Write enable instruction required before volatile status register write
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub enum WriteEnableForVolatileSr {
     /// Use WREN (0x06) instruction
     #[default]
@@ -322,6 +335,7 @@ pub enum WriteEnableForVolatileSr {
 ///
 /// Different manufacturers use different methods to enable quad I/O mode.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 #[allow(non_camel_case_types)]
 pub enum QuadEnableRequirement {
     /// No QE bit; device does not have a QE bit
@@ -365,6 +379,7 @@ impl QuadEnableRequirement {
 
 /// Methods to enter 4-byte address mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct FourByteEntryMethods {
     /// Bit field of supported methods
     pub methods: u8,
@@ -404,6 +419,7 @@ impl FourByteEntryMethods {
 
 /// Soft reset sequence support
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct SoftResetSupport {
     /// Bit field of supported reset methods
     pub methods: u8,
@@ -438,6 +454,7 @@ impl SoftResetSupport {
 ///
 /// Contains the key information extracted from the BFPT.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct BasicFlashParams {
     /// Parameter table revision
     pub revision: SfdpRevision,
@@ -473,8 +490,8 @@ pub struct BasicFlashParams {
     pub dtr_clocking: bool,
 
     // Advanced features (JESD216B+, DWORD 15-16)
-    /// Quad enable requirements
-    pub quad_enable: QuadEnableRequirement,
+    /// Quad enable requirements, `None` if the BFPT is too short to say
+    pub quad_enable: Option<QuadEnableRequirement>,
     /// 4-byte address entry methods
     pub four_byte_entry: FourByteEntryMethods,
     /// Soft reset support
@@ -482,6 +499,8 @@ pub struct BasicFlashParams {
 
     /// 4KB erase opcode (0xFF if not supported)
     pub erase_4k_opcode: u8,
+    /// Maximum chip erase time in microseconds (0 if not published, JESD216A+)
+    pub chip_erase_max_time_us: u32,
 
     // Fast read instruction parameters (JESD216, DWORDs 3, 4, 6, 7)
     /// 1S-1S-4S fast read parameters (DWORD 3 high)
@@ -551,6 +570,7 @@ impl BasicFlashParams {
 ///
 /// Indicates which commands are supported using native 4-byte addressing.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct FourByteAddrInstructions {
     /// Bit field of supported instructions
     pub flags: u32,
@@ -647,6 +667,7 @@ impl FourByteAddrInstructions {
 ///
 /// Contains the 4-byte address erase opcodes for each erase type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct FourByteAddrEraseOpcodes {
     /// Erase opcode for type 1 (0x00 if not supported)
     pub erase_type_1: u8,
@@ -683,6 +704,7 @@ impl FourByteAddrEraseOpcodes {
 
 /// Complete 4-Byte Address Instruction Table
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct FourByteAddrTable {
     /// Table revision
     pub revision: SfdpRevision,
@@ -734,6 +756,37 @@ impl FourByteAddrTable {
             None
         }
     }
+
+    /// Get the native 4-byte erase opcode for a BFPT erase type index (0-3)
+    ///
+    /// Returns `None` unless DWORD 1 advertises 4-byte support for the erase
+    /// type and DWORD 2 provides a non-zero opcode for it.
+    pub fn erase_opcode(&self, type_index: usize) -> Option<u8> {
+        if type_index >= 4
+            || !self
+                .instructions
+                .supports(FourByteAddrInstructions::ERASE_TYPE_1 << type_index)
+        {
+            return None;
+        }
+        self.erase_opcodes.opcode_for_type(type_index)
+    }
+}
+
+// ============================================================================
+// Status, Control and Configuration Register Map (JESD216F+)
+// ============================================================================
+
+/// Status, Control and Configuration Register Map
+///
+/// Only the table's presence is recorded, its fields aren't decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
+pub struct SccrMap {
+    /// Table revision
+    pub revision: SfdpRevision,
+    /// Table length in DWORDs
+    pub len_dwords: usize,
 }
 
 // ============================================================================
@@ -742,6 +795,7 @@ impl FourByteAddrTable {
 
 /// Complete SFDP information parsed from a flash chip
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize))]
 pub struct SfdpInfo {
     /// SFDP header
     pub header: SfdpHeader,
@@ -751,6 +805,8 @@ pub struct SfdpInfo {
     pub num_param_headers: usize,
     /// 4-Byte Address Instruction Table (if present)
     pub four_byte_addr_table: Option<FourByteAddrTable>,
+    /// Status, Control and Configuration Register Map (if present)
+    pub sccr_map: Option<SccrMap>,
 }
 
 impl SfdpInfo {
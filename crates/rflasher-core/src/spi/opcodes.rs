@@ -46,6 +46,10 @@ pub const WRSR: u8 = 0x01;
 pub const WRSR2: u8 = 0x31;
 /// Write Status Register 3
 pub const WRSR3: u8 = 0x11;
+/// Alternate Read Status Register 2 (QE in bit 7)
+pub const RDSR2_ALT: u8 = 0x3F;
+/// Alternate Write Status Register 2 (QE in bit 7)
+pub const WRSR2_ALT: u8 = 0x3E;
 
 // ============================================================================
 // Identification
@@ -104,6 +108,23 @@ pub const QOR_4B: u8 = 0x6C;
 /// Quad I/O Read with 4-byte address
 pub const QIOR_4B: u8 = 0xEC;
 
+/// Array reads followed by dummy cycles
+///
+/// Controllers that can't clock dummy cycles must refuse these in
+/// `probe_opcode`, so that reads fall back to READ (0x03).
+pub const DUMMY_CYCLE_READS: &[u8] = &[
+    FAST_READ,
+    FAST_READ_4B,
+    DOR,
+    DIOR,
+    QOR,
+    QIOR,
+    DOR_4B,
+    DIOR_4B,
+    QOR_4B,
+    QIOR_4B,
+];
+
 // ============================================================================
 // Page Program
 // ============================================================================
@@ -188,6 +209,10 @@ pub const RDSR_SEC: u8 = 0x48;
 pub const EQIO: u8 = 0x38;
 /// Reset QPI Mode / Exit QPI Mode
 pub const RSTQIO: u8 = 0xFF;
+/// Exit QPI Mode (Macronix)
+pub const RSTQIO_MX: u8 = 0xF5;
+/// Read JEDEC ID in QPI mode (Macronix, ISSI)
+pub const QPIID: u8 = 0xAF;
 
 // ============================================================================
 // Software Reset
@@ -214,6 +239,45 @@ pub const SUSPEND: u8 = 0x75;
 /// Erase/Program Resume
 pub const RESUME: u8 = 0x7A;
 
+// ============================================================================
+// Vendor-specific registers
+// ============================================================================
+
+/// Read Configuration Register (Macronix; shares the RDSR3 opcode)
+pub const RDCR_MX: u8 = 0x15;
+/// Write Configuration Register (Macronix): WRSR with SR1 followed by CR
+pub const WRCR_MX: u8 = WRSR;
+/// Read Security Register (Macronix)
+pub const RDSCUR: u8 = 0x2B;
+/// Write Security Register (Macronix) - sets the permanent lock-down bits
+pub const WRSCUR: u8 = 0x2F;
+/// Enter Secured OTP (Macronix)
+pub const ENSO: u8 = 0xB1;
+/// Exit Secured OTP (Macronix)
+pub const EXSO: u8 = 0xC1;
+
+/// Read Function Register (ISSI; shares the Winbond RDSR_SEC opcode)
+pub const RDFR: u8 = 0x48;
+/// Write Function Register (ISSI; shares the Winbond PRSR opcode)
+pub const WRFR: u8 = 0x42;
+/// Read Read Parameters (ISSI)
+pub const RDRP: u8 = 0x61;
+/// Set Read Parameters, volatile (ISSI)
+pub const SRPV: u8 = 0x63;
+/// Set Read Parameters, non-volatile (ISSI)
+pub const SRPNV: u8 = 0x65;
+
+/// Bank Register Read (Spansion; same as RDEAR_ALT)
+pub const BRRD: u8 = RDEAR_ALT;
+/// Bank Register Write (Spansion; same as WREAR_ALT)
+pub const BRWR: u8 = WREAR_ALT;
+/// Bank Register Access (Spansion; shares the DP opcode)
+pub const BRAC: u8 = 0xB9;
+/// Read Configuration Register 1 (Spansion; shares the RDSR2 opcode)
+pub const RDCR_SP: u8 = 0x35;
+/// Clear Status Register 1 - resets the erase/program fail flags (Spansion)
+pub const CLSR: u8 = 0x30;
+
 // ============================================================================
 // Status register bit definitions
 // ============================================================================
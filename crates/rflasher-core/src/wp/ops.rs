@@ -11,8 +11,17 @@ use super::types::{
 use crate::error::Error;
 use crate::programmer::SpiMaster;
 use crate::protocol;
+use crate::spi::{AddressWidth, IoMode, SpiCommand};
 use maybe_async::maybe_async;
 
+/// Atmel/Adesto status register bits 5-2: all ones is a global protect,
+/// all zeros a global unprotect, anything else leaves the sectors alone
+const ATMEL_GLOBAL_PROTECT: u8 = 0x3C;
+/// Atmel/Adesto Read Sector Protection Register opcode
+const ATMEL_READ_SECTOR_PROTECTION: u8 = 0x3C;
+/// Atmel/Adesto sector protection granularity
+const ATMEL_SECTOR_SIZE: u32 = 64 * 1024;
+
 /// Write protection result type with detailed error information
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WpError {
@@ -32,6 +41,8 @@ pub enum WpError {
     RangeListUnavailable,
     /// Write Protect Selection (WPS) bit is set, indicating per-sector mode
     UnsupportedState,
+    /// Refused to set a power-cycle or permanent lock without `allow_permanent`
+    PermanentLockRefused,
     /// SPI communication error
     SpiError(Error),
 }
@@ -55,6 +66,9 @@ impl core::fmt::Display for WpError {
             WpError::UnsupportedState => {
                 write!(f, "WPS bit set, per-sector protection mode not supported")
             }
+            WpError::PermanentLockRefused => {
+                write!(f, "refusing to set a power-cycle or permanent lock")
+            }
             WpError::SpiError(e) => write!(f, "SPI error: {}", e),
         }
     }
@@ -141,11 +155,95 @@ pub async fn read_wp_config<M: SpiMaster + ?Sized>(
 ) -> WpResult<WpConfig> {
     let bits = read_wp_bits(master, bit_map).await?;
     let mode = bits.mode();
-    let range = decode_range(&bits, total_size, decoder);
+    let range = if decoder == RangeDecoder::Atmel && bits.bp_value() & 0b11 == 0b01 {
+        // Only some sectors are protected; ask the sectors themselves
+        read_atmel_sector_range(master, total_size).await?
+    } else {
+        decode_range(&bits, total_size, decoder)
+    };
 
     Ok(WpConfig::new(mode, range))
 }
 
+/// Read the range covered by protected sectors on an Atmel/Adesto chip
+///
+/// Logs a warning if the protected sectors aren't contiguous, since the
+/// returned range then also includes unprotected ones.
+#[maybe_async]
+async fn read_atmel_sector_range<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    total_size: u32,
+) -> WpResult<WpRange> {
+    let mut first = None;
+    let mut end = 0;
+    let mut gap = false;
+    let mut split = false;
+
+    for addr in (0..total_size).step_by(ATMEL_SECTOR_SIZE as usize) {
+        let mut state = [0u8];
+        let mut cmd = SpiCommand {
+            opcode: ATMEL_READ_SECTOR_PROTECTION,
+            address: Some(addr),
+            address_width: AddressWidth::ThreeByte,
+            io_mode: IoMode::Single,
+            dummy_cycles: 0,
+            write_data: &[],
+            read_buf: &mut state,
+        };
+        master.execute(&mut cmd).await?;
+
+        if state[0] != 0 {
+            if first.is_none() {
+                first = Some(addr);
+            }
+            end = addr.saturating_add(ATMEL_SECTOR_SIZE).min(total_size);
+            split |= gap;
+        } else if first.is_some() {
+            gap = true;
+        }
+    }
+
+    let Some(start) = first else {
+        return Ok(WpRange::none());
+    };
+    if split {
+        log::warn!(
+            "Protected sectors are not contiguous; reporting 0x{:08x}-0x{:08x}",
+            start,
+            end - 1
+        );
+    }
+    Ok(WpRange::new(start, end - start))
+}
+
+/// Globally protect or unprotect all sectors of an Atmel/Adesto chip
+#[maybe_async]
+async fn write_atmel_global<M: SpiMaster + ?Sized>(
+    master: &mut M,
+    protect: bool,
+    options: WriteOptions,
+) -> WpResult<()> {
+    let sr1 = protocol::read_status1(master).await?;
+    let value = if protect {
+        sr1 | ATMEL_GLOBAL_PROTECT
+    } else {
+        sr1 & !ATMEL_GLOBAL_PROTECT
+    };
+
+    if options.use_ewsr {
+        protocol::write_status1_ewsr(master, value).await?;
+    } else {
+        protocol::write_status1(master, value).await?;
+    }
+
+    // Bits 5 and 4 read back as EPE and WPP, so only the SWP bits are checked
+    let swp = (protocol::read_status1(master).await? >> 2) & 0b11;
+    if swp != if protect { 0b11 } else { 0 } {
+        return Err(WpError::VerifyFailed);
+    }
+    Ok(())
+}
+
 /// Build status register values from WpBits
 fn build_register_values(bits: &WpBits, bit_map: &WpRegBitMap) -> (u8, u8, u8) {
     let mut sr1: u8 = 0;
@@ -259,6 +357,12 @@ pub struct WriteOptions {
     /// Required for legacy SST25 chips. Set automatically by `SpiFlashDevice`
     /// when the chip has the `WRSR_EWSR` feature flag.
     pub use_ewsr: bool,
+    /// Allow setting the status register lock bit (SRL)
+    ///
+    /// The power-cycle and permanent modes lock the status register until the
+    /// next power cycle or forever, so writes that set SRL are refused with
+    /// `PermanentLockRefused` unless this is set.
+    pub allow_permanent: bool,
 }
 
 /// Write WP bits to the chip
@@ -269,6 +373,14 @@ pub async fn write_wp_bits<M: SpiMaster + ?Sized>(
     bit_map: &WpRegBitMap,
     options: WriteOptions,
 ) -> WpResult<()> {
+    // Setting SRL can't be undone; writing back an already set one is fine
+    if bits.srl == Some(1)
+        && !options.allow_permanent
+        && read_bit(master, &bit_map.srl).await? != Some(1)
+    {
+        return Err(WpError::PermanentLockRefused);
+    }
+
     // Read current values
     let (curr_sr1, curr_sr2, _curr_sr3) = read_current_registers(master).await?;
 
@@ -326,13 +438,14 @@ pub async fn set_wp_mode<M: SpiMaster + ?Sized>(
     bit_map: &WpRegBitMap,
     options: WriteOptions,
 ) -> WpResult<()> {
-    // Only Disabled and Hardware modes can be set programmatically
     let (srp, srl) = match mode {
         WpMode::Disabled => (0, 0),
         WpMode::Hardware => (1, 0),
-        WpMode::PowerCycle | WpMode::Permanent => {
-            return Err(WpError::ModeUnsupported);
+        WpMode::PowerCycle | WpMode::Permanent if !options.allow_permanent => {
+            return Err(WpError::PermanentLockRefused);
         }
+        WpMode::PowerCycle => (0, 1),
+        WpMode::Permanent => (1, 1),
     };
 
     let mut bits = WpBits::empty();
@@ -382,6 +495,11 @@ pub async fn set_wp_range<M: SpiMaster + ?Sized>(
     let new_bits = find_bits_for_range(range, total_size, &template, decoder)
         .ok_or(WpError::RangeUnsupported)?;
 
+    // SWP is a summary; the sectors change only through a global protect
+    if decoder == RangeDecoder::Atmel {
+        return write_atmel_global(master, new_bits.bp_value() != 0, options).await;
+    }
+
     // Preserve SRP/SRL from current configuration
     let mut write_bits = new_bits;
     write_bits.srp = current_bits.srp;
@@ -412,8 +530,15 @@ pub async fn write_wp_config<M: SpiMaster + ?Sized>(
 pub async fn disable_wp<M: SpiMaster + ?Sized>(
     master: &mut M,
     bit_map: &WpRegBitMap,
+    decoder: RangeDecoder,
     options: WriteOptions,
 ) -> WpResult<()> {
+    if decoder == RangeDecoder::Atmel {
+        // SPRL has to be cleared before the sectors can be unprotected
+        set_wp_mode(master, WpMode::Disabled, bit_map, options).await?;
+        return write_atmel_global(master, false, options).await;
+    }
+
     let mut bits = WpBits::empty();
 
     // Set all BP bits to 0
@@ -467,6 +592,46 @@ pub fn get_available_ranges(
     super::ranges::get_all_ranges(&template, total_size, decoder)
 }
 
+#[cfg(feature = "alloc")]
+/// Find the smallest encodable protection range that covers a layout region
+///
+/// `available` lists the ranges the chip can encode, as returned by
+/// [`get_available_ranges`] for its register layout and [`RangeDecoder`].
+/// BP granularity often forces protecting more than the region itself; a
+/// warning with the extra amount is logged in that case.
+///
+/// Returns `RangeUnsupported` if the region doesn't fit the chip or no
+/// available range covers it.
+pub fn range_for_region(
+    region: &crate::layout::Region,
+    total_size: u32,
+    available: &[WpRange],
+) -> WpResult<WpRange> {
+    if region.start > region.end || region.end >= total_size {
+        return Err(WpError::RangeUnsupported);
+    }
+
+    let target = WpRange::from_start_end(region.start, region.end);
+    let range = available
+        .iter()
+        .filter(|r| r.start <= target.start && r.end() >= target.end())
+        .min_by_key(|r| r.len)
+        .copied()
+        .ok_or(WpError::RangeUnsupported)?;
+
+    if range.len > target.len {
+        log::warn!(
+            "Region '{}' can't be protected exactly: protecting 0x{:08x}-0x{:08x}, {} bytes more than the region",
+            region.name,
+            range.start,
+            range.end() - 1,
+            range.len - target.len
+        );
+    }
+
+    Ok(range)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -510,4 +675,26 @@ mod tests {
         assert_eq!(sr1 & 0b10000000, 0b10000000); // SRP set
         assert_eq!(sr2, 0); // No SR2 bits set
     }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn test_range_for_region() {
+        use crate::layout::Region;
+
+        let total = 16 * 1024 * 1024;
+        let ranges =
+            get_available_ranges(&WpRegBitMap::winbond_standard(), total, RangeDecoder::Spi25);
+        let covering = |start, end| range_for_region(&Region::new("r", start, end), total, &ranges);
+
+        // Exactly encodable: lowest 4K sector
+        assert_eq!(covering(0, 0xFFF), Ok(WpRange::new(0, 0x1000)));
+        // 8K at 0x1000 needs the lowest 16K
+        assert_eq!(covering(0x1000, 0x2FFF), Ok(WpRange::new(0, 0x4000)));
+        // Top 10M is covered by the complement of the lowest 2M
+        assert_eq!(
+            covering(0x600000, 0xFFFFFF),
+            Ok(WpRange::new(0x200000, 0xE00000))
+        );
+        assert_eq!(covering(0, total), Err(WpError::RangeUnsupported));
+    }
 }
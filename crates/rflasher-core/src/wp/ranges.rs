@@ -42,6 +42,7 @@ pub fn decode_range(bits: &WpBits, total_size: u32, decoder: RangeDecoder) -> Wp
         RangeDecoder::Spi25_64kBlock => decode_range_spi25_64k_block(bits, total_size),
         RangeDecoder::Spi25BitCmp => decode_range_spi25_bit_cmp(bits, total_size),
         RangeDecoder::Spi25_2xBlock => decode_range_spi25_2x_block(bits, total_size),
+        RangeDecoder::Atmel => decode_range_atmel(bits, total_size),
     }
 }
 
@@ -88,6 +89,39 @@ pub fn decode_range_spi25_2x_block(bits: &WpBits, total_size: u32) -> WpRange {
     decode_range_generic(bits, total_size, false, true)
 }
 
+/// Atmel/Adesto AT25 range decoding
+///
+/// AT25DF-style chips have a protection register per sector instead of a
+/// BP range. The two SWP bits (mapped as BP0/BP1) only summarize them: 00
+/// means no sector is protected, 11 all sectors, and 01 some. The status
+/// register can't tell which, so the latter decodes as the whole chip;
+/// [`read_wp_config`](super::read_wp_config) reads the sector protection
+/// registers to find the actual range.
+pub fn decode_range_atmel(bits: &WpBits, total_size: u32) -> WpRange {
+    match bits.bp_value() & 0b11 {
+        0 => WpRange::none(),
+        _ => WpRange::full(total_size),
+    }
+}
+
+/// Find SWP bits for an Atmel/Adesto range
+///
+/// Only a global protect (the whole chip) or a global unprotect can be
+/// set through the status register.
+fn encode_range_atmel(target: &WpRange, total_size: u32, template: &WpBits) -> Option<WpBits> {
+    let swp = if target.len == 0 {
+        0
+    } else if target.start == 0 && target.len == total_size {
+        0b11
+    } else {
+        return None;
+    };
+
+    let mut bits = *template;
+    bits.set_bp_value(swp, template.bp_count);
+    Some(bits)
+}
+
 /// Generic range decoding implementation
 ///
 /// # Algorithm
@@ -193,6 +227,10 @@ pub fn find_bits_for_range(
     template: &WpBits,
     decoder: RangeDecoder,
 ) -> Option<WpBits> {
+    if decoder == RangeDecoder::Atmel {
+        return encode_range_atmel(target, total_size, template);
+    }
+
     let bp_count = template.bp_count;
     let max_bp: u8 = if bp_count > 0 { (1 << bp_count) - 1 } else { 0 };
 
@@ -430,6 +468,48 @@ mod tests {
         assert_eq!(range.len, 4 * 1024);
     }
 
+    /// WP bits as read from an AT25DF status register byte
+    fn atmel_bits(sr1: u8) -> WpBits {
+        let mut bits = WpBits::empty();
+        bits.set_bp_value((sr1 >> 2) & 0b11, 2);
+        bits.srp = Some(sr1 >> 7);
+        bits
+    }
+
+    #[test]
+    fn test_atmel_decode() {
+        let total = 4 * 1024 * 1024; // AT25DF321A
+        let decode = |sr1| decode_range(&atmel_bits(sr1), total, RangeDecoder::Atmel);
+
+        // Power-up default: all sectors protected, WP# high (WPP=1)
+        assert_eq!(decode(0x1C), WpRange::full(total));
+        // After global unprotect
+        assert_eq!(decode(0x10), WpRange::none());
+        // Sector protection registers locked (SPRL=1)
+        assert_eq!(decode(0x9C), WpRange::full(total));
+        // EPE set after a failed program doesn't affect the range
+        assert_eq!(decode(0x30), WpRange::none());
+        // Some sectors protected
+        assert_eq!(decode(0x14), WpRange::full(total));
+    }
+
+    #[test]
+    fn test_atmel_encode() {
+        let total = 4 * 1024 * 1024;
+        let template = atmel_bits(0x10);
+        let encode = |range| find_bits_for_range(&range, total, &template, RangeDecoder::Atmel);
+
+        assert_eq!(encode(WpRange::full(total)).unwrap().bp_value(), 0b11);
+        assert_eq!(encode(WpRange::none()).unwrap().bp_value(), 0);
+        assert!(encode(WpRange::new(0, total / 2)).is_none());
+
+        #[cfg(feature = "alloc")]
+        {
+            let ranges = get_all_ranges(&template, total, RangeDecoder::Atmel);
+            assert_eq!(ranges, [WpRange::none(), WpRange::full(total)]);
+        }
+    }
+
     #[test]
     fn test_wp_range_overlaps() {
         let range = WpRange::new(1000, 500);
@@ -208,6 +208,7 @@ impl core::fmt::Display for WpRange {
 
 /// Which status register a bit is located in
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
 pub enum StatusRegister {
     /// Status Register 1 (read with RDSR 0x05)
     Status1,
@@ -221,6 +222,7 @@ pub enum StatusRegister {
 
 /// Writability of a register bit
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
 pub enum BitWritability {
     /// Bit is not present on this chip
     #[default]
@@ -235,6 +237,7 @@ pub enum BitWritability {
 
 /// Information about a single register bit
 #[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
 pub struct RegBitInfo {
     /// Which register the bit is in
     pub reg: Option<StatusRegister>,
@@ -282,6 +285,7 @@ impl RegBitInfo {
 /// This structure describes where all write protection-related bits
 /// are located in a chip's status registers.
 #[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "std", derive(serde::Serialize, serde::Deserialize))]
 pub struct WpRegBitMap {
     /// Status Register Protect (SRP0/SRP)
     pub srp: RegBitInfo,
@@ -374,4 +378,7 @@ pub enum RangeDecoder {
     Spi25BitCmp,
     /// Double coefficient for chips with extra BP bit
     Spi25_2xBlock,
+    /// Atmel/Adesto AT25DF-style per-sector protection with global
+    /// protect/unprotect; BP0/BP1 map to the SWP bits
+    Atmel,
 }
@@ -17,7 +17,7 @@ use nusb::MaybeFuture;
 use nusb::transfer::{Buffer, Bulk, In, Out};
 use rflasher_core::error::{Error as CoreError, Result as CoreResult};
 use rflasher_core::programmer::{OpaqueMaster, SpiFeatures, SpiMaster};
-use rflasher_core::spi::{SpiCommand, check_io_mode_supported, opcodes};
+use rflasher_core::spi::{IoMode, SpiCommand, check_io_mode_supported, opcodes};
 
 use crate::error::{DediprogError, Result};
 use crate::protocol::*;
@@ -96,7 +96,8 @@ pub struct DediprogConfig {
     pub device_index: usize,
     /// Device ID to search for (e.g., "SF123456")
     pub device_id: Option<String>,
-    /// Target flash (1 or 2 for dual-chip programmers)
+    /// Target flash (`target=1|2`, 2 needs a dual-socket SF600-class
+    /// programmer)
     pub target: Target,
     /// SPI speed index (0=24MHz, 1=12MHz, etc.)
     pub spi_speed_index: usize,
@@ -137,8 +138,9 @@ pub fn parse_options(options: &[(&str, &str)]) -> Result<DediprogConfig> {
                 let t: u8 = value
                     .parse()
                     .map_err(|_| DediprogError::InvalidParameter(format!("target: {}", value)))?;
-                config.target = Target::from_value(t)
-                    .ok_or_else(|| DediprogError::InvalidParameter(format!("target: {}", value)))?;
+                config.target = Target::from_socket(t).ok_or_else(|| {
+                    DediprogError::InvalidParameter(format!("target: {} (must be 1 or 2)", value))
+                })?;
             }
             "spispeed" => {
                 config.spi_speed_index = parse_spi_speed(value).ok_or_else(|| {
@@ -197,8 +199,14 @@ pub struct Dediprog {
     io_mode: DpIoMode,
     /// Configured maximum I/O mode
     max_io_mode: DpIoMode,
+    /// I/O mode for bulk reads (set after probing)
+    read_io_mode: DpIoMode,
     /// Flash size in bytes (set after probing, needed for OpaqueMaster)
     flash_size: Option<u32>,
+    /// Selected target flash
+    target: Target,
+    /// LED state last set
+    led: Led,
 }
 
 impl Dediprog {
@@ -293,7 +301,10 @@ impl Dediprog {
             protocol: Protocol::Unknown,
             io_mode: DpIoMode::Single,
             max_io_mode: config.io_mode,
+            read_io_mode: DpIoMode::Single,
             flash_size: None,
+            target: Target::ApplicationFlash1,
+            led: Led::Invalid,
         };
 
         dediprog.init_device(config)?;
@@ -339,6 +350,10 @@ impl std::fmt::Display for DediprogDeviceInfo {
 #[cfg(feature = "is_sync")]
 impl Drop for Dediprog {
     fn drop(&mut self) {
+        // A finished write leaves the busy LED on, a failed one keeps the error LED
+        if self.led == Led::Busy {
+            let _ = self.set_led(Led::None);
+        }
         // Reset I/O mode
         let _ = self.set_io_mode(DpIoMode::Single);
         // Turn off voltage
@@ -429,7 +444,10 @@ impl Dediprog {
             protocol: Protocol::Unknown,
             io_mode: DpIoMode::Single,
             max_io_mode: config.io_mode,
+            read_io_mode: DpIoMode::Single,
             flash_size: None,
+            target: Target::ApplicationFlash1,
+            led: Led::Invalid,
         };
 
         dediprog.init_device(&config).await?;
@@ -438,6 +456,9 @@ impl Dediprog {
 
     /// Shutdown: turn off voltage and reset I/O mode (WASM equivalent of Drop)
     pub async fn shutdown(&mut self) {
+        if self.led == Led::Busy {
+            let _ = self.set_led(Led::None).await;
+        }
         let _ = self.set_io_mode(DpIoMode::Single).await;
         let _ = self.set_voltage(0).await;
     }
@@ -477,6 +498,15 @@ impl Dediprog {
             ));
         }
 
+        // Multi-I/O needs SF600-class hardware; don't silently fall back
+        let multi_io = self.device_type.is_sf600_class() && self.protocol >= Protocol::V2;
+        if config.io_mode != DpIoMode::Single && !multi_io {
+            return Err(DediprogError::Unsupported(format!(
+                "{:?} reads need an SF600 or newer with protocol V2+ ({} uses {:?})",
+                config.io_mode, self.device_type, self.protocol
+            )));
+        }
+
         log::info!(
             "Dediprog {}: firmware {:X}.{:X}.{:X}, protocol {:?}",
             self.device_type,
@@ -487,7 +517,7 @@ impl Dediprog {
         );
 
         // Initialize the device
-        self.set_leds(Led::All).await?;
+        self.set_led(Led::All).await?;
 
         // Set target, speed, and voltage
         self.set_target(config.target).await?;
@@ -499,14 +529,9 @@ impl Dediprog {
             self.leave_standalone_mode().await?;
         }
 
-        // Determine multi-I/O support
-        if self.device_type.is_sf600_class() && self.protocol >= Protocol::V2 {
-            self.max_io_mode = config.io_mode;
-        } else {
-            self.max_io_mode = DpIoMode::Single;
-        }
+        self.max_io_mode = config.io_mode;
 
-        self.set_leds(Led::None).await?;
+        self.set_led(Led::None).await?;
 
         Ok(())
     }
@@ -636,35 +661,56 @@ impl Dediprog {
         Ok(())
     }
 
-    /// Set the LED state
+    /// Set the status LEDs on the programmer
+    ///
+    /// Writes through rflasher light [`Led::Busy`] and, if they fail,
+    /// [`Led::Error`] by themselves; this is for showing other states, e.g.
+    /// [`Led::Pass`] once a whole operation has succeeded.
     #[maybe_async]
-    async fn set_leds(&mut self, led: Led) -> Result<()> {
-        if self.protocol >= Protocol::V2 {
-            // New protocol: value contains LED state
-            let leds = ((led as u8) ^ 7) as u16;
-            self.control_write(Command::SetIoLed, leds << 8, 0, &[])
-                .await?;
-        } else {
-            // Old protocol: index contains LED state
-            let leds = if self.firmware_version < firmware_version(5, 0, 0) {
-                // Very old firmware has different LED mapping
-                let l = led as u8;
-                ((l & 4) >> 2) | ((l & 1) << 2)
-            } else {
-                led as u8
-            };
-            let target_leds = leds ^ 7;
-            self.control_write(Command::SetIoLed, 0x9, target_leds as u16, &[])
-                .await?;
-        }
+    pub async fn set_led(&mut self, led: Led) -> Result<()> {
+        let (value, index) = led_request(self.protocol, self.firmware_version, led)
+            .ok_or_else(|| DediprogError::InvalidParameter(format!("LED state {:?}", led)))?;
+        self.control_write(Command::SetIoLed, value, index, &[])
+            .await?;
+        self.led = led;
         Ok(())
     }
 
-    /// Set the target flash
+    /// Get the LED state last set, [`Led::Invalid`] before the first one
+    pub fn led(&self) -> Led {
+        self.led
+    }
+
+    /// Show an LED state, unless it is already shown
+    ///
+    /// The LEDs are informational, so a failure is only logged.
+    #[maybe_async]
+    async fn indicate(&mut self, led: Led) {
+        if self.led == led {
+            return;
+        }
+        if let Err(e) = self.set_led(led).await {
+            log::debug!("Failed to set the Dediprog LEDs to {:?}: {}", led, e);
+        }
+    }
+
+    /// Select the target flash that the following transactions go to
+    ///
+    /// The second application flash is only wired on dual-socket
+    /// SF600-class programmers, SF100/SF200 reject it.
     #[maybe_async]
-    async fn set_target(&mut self, target: Target) -> Result<()> {
+    pub async fn set_target(&mut self, target: Target) -> Result<()> {
+        if target == Target::ApplicationFlash2 && !self.device_type.is_sf600_class() {
+            return Err(DediprogError::Unsupported(format!(
+                "target 2 needs a dual-socket SF600 or newer ({} has a single target)",
+                self.device_type
+            )));
+        }
+
+        log::debug!("Selecting target flash {}", target);
         self.control_write(Command::SetTarget, target as u16, 0, &[])
             .await?;
+        self.target = target;
         Ok(())
     }
 
@@ -957,9 +1003,14 @@ impl Dediprog {
         self.device_type
     }
 
-    /// Get the device string
-    pub fn device_string(&self) -> &str {
-        &self.device_string
+    /// Get the device string, with the selected target flash
+    pub fn device_string(&self) -> String {
+        format!("{}, target {}", self.device_string, self.target)
+    }
+
+    /// Get the selected target flash
+    pub fn target(&self) -> Target {
+        self.target
     }
 
     /// Get the firmware version (encoded)
@@ -977,99 +1028,21 @@ impl Dediprog {
         self.flash_size = Some(size);
     }
 
+    /// Set the I/O mode for bulk reads
+    ///
+    /// Call after probing with a mode the chip supports, e.g. from
+    /// [`rflasher_core::flash::read_io_mode`], which also sets the Quad
+    /// Enable bit for quad modes. Modes wider than `features()` advertises
+    /// are narrowed to what the programmer can do.
+    pub fn set_read_io_mode(&mut self, mode: IoMode) {
+        self.read_io_mode = bulk_read_io_mode(SpiMaster::features(self), mode);
+        log::debug!("Dediprog bulk reads use {:?}", self.read_io_mode);
+    }
+
     // =========================================================================
     // Bulk Read/Write (CMD_READ/CMD_WRITE with USB bulk transfers)
     // =========================================================================
 
-    /// Prepare a read/write command packet for the given protocol version.
-    ///
-    /// Returns the command packet size. The packet is written into `cmd_buf`.
-    /// `value` and `idx` are the USB control transfer wValue/wIndex fields.
-    #[allow(clippy::too_many_arguments)]
-    fn prepare_rw_cmd(
-        &self,
-        cmd_buf: &mut [u8; MAX_CMD_SIZE],
-        value: &mut u16,
-        idx: &mut u16,
-        is_read: bool,
-        mode: u8,
-        start: u32,
-        count: u16,
-    ) -> Result<usize> {
-        // Common header (all protocol versions)
-        cmd_buf[0] = (count & 0xFF) as u8;
-        cmd_buf[1] = ((count >> 8) & 0xFF) as u8;
-        cmd_buf[2] = 0; // RFU
-        cmd_buf[3] = mode;
-        cmd_buf[4] = 0; // Opcode (overridden below for V2/V3)
-
-        match self.protocol {
-            Protocol::V1 => {
-                // V1: address in wValue/wIndex, 5-byte command packet
-                if start >> 24 != 0 {
-                    return Err(DediprogError::Unsupported(
-                        "4-byte address not supported on V1 protocol".to_string(),
-                    ));
-                }
-                *value = (start & 0xFFFF) as u16;
-                *idx = ((start >> 16) & 0xFF) as u16;
-                Ok(5)
-            }
-            Protocol::V2 => {
-                *value = 0;
-                *idx = 0;
-
-                if is_read {
-                    // For V2 reads, use standard read mode with fast read opcode
-                    // The firmware handles the SPI read command internally
-                    cmd_buf[3] = ReadMode::Fast as u8;
-                    cmd_buf[4] = opcodes::FAST_READ;
-                } else {
-                    // For V2 writes, use page program mode
-                    cmd_buf[3] = WriteMode::PagePgm as u8;
-                    cmd_buf[4] = 0;
-                }
-
-                cmd_buf[5] = 0; // RFU
-                cmd_buf[6] = (start & 0xFF) as u8;
-                cmd_buf[7] = ((start >> 8) & 0xFF) as u8;
-                cmd_buf[8] = ((start >> 16) & 0xFF) as u8;
-                cmd_buf[9] = ((start >> 24) & 0xFF) as u8;
-                Ok(10)
-            }
-            Protocol::V3 => {
-                *value = 0;
-                *idx = 0;
-
-                cmd_buf[5] = 0; // RFU
-                cmd_buf[6] = (start & 0xFF) as u8;
-                cmd_buf[7] = ((start >> 8) & 0xFF) as u8;
-                cmd_buf[8] = ((start >> 16) & 0xFF) as u8;
-                cmd_buf[9] = ((start >> 24) & 0xFF) as u8;
-
-                if is_read {
-                    cmd_buf[3] = ReadMode::Configurable as u8;
-                    cmd_buf[4] = opcodes::FAST_READ;
-                    cmd_buf[10] = if start >> 24 != 0 { 4 } else { 3 }; // address length
-                    cmd_buf[11] = 4; // dummy half-cycles (8 clocks / 2 for fast read)
-                    Ok(12)
-                } else {
-                    cmd_buf[3] = WriteMode::PagePgm as u8;
-                    cmd_buf[4] = 0;
-                    // Page size (256 bytes) as 32-bit LE
-                    cmd_buf[10] = 0x00;
-                    cmd_buf[11] = 0x01;
-                    cmd_buf[12] = 0x00;
-                    cmd_buf[13] = 0x00;
-                    Ok(14)
-                }
-            }
-            Protocol::Unknown => Err(DediprogError::Unsupported(
-                "Unknown protocol version".to_string(),
-            )),
-        }
-    }
-
     /// Bulk read from flash using CMD_READ + USB bulk IN transfers.
     ///
     /// Start and len MUST be 512-byte aligned. Uses a single large URB so the
@@ -1084,24 +1057,21 @@ impl Dediprog {
 
         let count = (len / BULK_CHUNK_SIZE) as u16;
 
-        // Set I/O mode for reads (single for now, can add multi-IO later)
-        self.set_io_mode(DpIoMode::Single).await?;
+        // Use dual/quad reads when enabled with iomode= and the chip supports them
+        let io_mode = self.read_io_mode;
+        self.set_io_mode(io_mode).await?;
 
         // Build and send the CMD_READ command packet
-        let mut cmd_buf = [0u8; MAX_CMD_SIZE];
-        let mut value: u16 = 0;
-        let mut idx: u16 = 0;
-        let cmd_len = self.prepare_rw_cmd(
-            &mut cmd_buf,
-            &mut value,
-            &mut idx,
+        let cmd = prepare_rw_cmd(
+            self.protocol,
             true,
             ReadMode::Std as u8,
+            io_mode,
             start,
             count,
         )?;
 
-        self.control_write_raw(Command::Read as u8, value, idx, &cmd_buf[..cmd_len])
+        self.control_write_raw(Command::Read as u8, cmd.value, cmd.index, cmd.packet())
             .await?;
 
         // Submit a single large bulk IN transfer for the entire read.
@@ -1154,20 +1124,16 @@ impl Dediprog {
         self.set_io_mode(DpIoMode::Single).await?;
 
         // Build and send the CMD_WRITE command packet
-        let mut cmd_buf = [0u8; MAX_CMD_SIZE];
-        let mut value: u16 = 0;
-        let mut idx: u16 = 0;
-        let cmd_len = self.prepare_rw_cmd(
-            &mut cmd_buf,
-            &mut value,
-            &mut idx,
+        let cmd = prepare_rw_cmd(
+            self.protocol,
             false,
             WriteMode::PagePgm as u8,
+            DpIoMode::Single,
             start,
             count,
         )?;
 
-        self.control_write_raw(Command::Write as u8, value, idx, &cmd_buf[..cmd_len])
+        self.control_write_raw(Command::Write as u8, cmd.value, cmd.index, cmd.packet())
             .await?;
 
         // Build a single padded buffer: for each 256-byte page, write 256 data + 256 0xFF.
@@ -1263,135 +1229,147 @@ impl Dediprog {
         }
         Ok(())
     }
-}
-
-// ---------------------------------------------------------------------------
-// OpaqueMaster trait implementation
-// ---------------------------------------------------------------------------
-
-#[maybe_async(AFIT)]
-impl OpaqueMaster for Dediprog {
-    fn size(&self) -> usize {
-        self.flash_size.unwrap_or(0) as usize
-    }
 
-    async fn read(&mut self, addr: u32, buf: &mut [u8]) -> CoreResult<()> {
-        let len = buf.len();
+    /// Write to the flash, in bulk where aligned
+    #[maybe_async]
+    async fn write_flash(&mut self, addr: u32, data: &[u8]) -> CoreResult<()> {
+        const PAGE_SIZE: usize = 256;
+        let len = data.len();
         if len == 0 {
             return Ok(());
         }
 
         // Split into: head residue + aligned bulk + tail residue
-        let chunk_size = BULK_CHUNK_SIZE;
-        let head_residue = if !(addr as usize).is_multiple_of(chunk_size) {
-            len.min(chunk_size - (addr as usize % chunk_size))
+        // Bulk writes require 256-byte (page) alignment
+        let head_residue = if !(addr as usize).is_multiple_of(PAGE_SIZE) {
+            len.min(PAGE_SIZE - (addr as usize % PAGE_SIZE))
         } else {
             0
         };
 
-        // Head: slow read for unaligned start
+        // Head: slow write for unaligned start
         if head_residue > 0 {
-            self.slow_read(addr, &mut buf[..head_residue])
+            self.slow_write(addr, &data[..head_residue])
                 .await
-                .map_err(|_| CoreError::ReadError { addr })?;
+                .map_err(|_| CoreError::WriteError { addr })?;
         }
 
         // Aligned bulk portion
         let bulk_start = addr + head_residue as u32;
         let remaining = len - head_residue;
-        let bulk_len = (remaining / chunk_size) * chunk_size;
+        let bulk_len = (remaining / PAGE_SIZE) * PAGE_SIZE;
 
         if bulk_len > 0 {
             // Split into chunks that fit in a single USB buffer.
-            let max_blocks = (MAX_BLOCK_COUNT as usize).min(MAX_READ_BLOCKS);
+            // Each page is 512 bytes on the wire (256 data + 256 padding), so
+            // MAX_WRITE_PAGES pages = MAX_WRITE_PAGES * 512 bytes USB buffer.
+            let max_pages = (MAX_BLOCK_COUNT as usize).min(MAX_WRITE_PAGES);
             let mut bulk_offset = 0usize;
             while bulk_offset < bulk_len {
-                let this_len = (bulk_len - bulk_offset).min(max_blocks * chunk_size);
-                let this_len = (this_len / chunk_size) * chunk_size;
+                let this_len = (bulk_len - bulk_offset).min(max_pages * PAGE_SIZE);
+                let this_len = (this_len / PAGE_SIZE) * PAGE_SIZE;
                 if this_len == 0 {
                     break;
                 }
-                let buf_start = head_residue + bulk_offset;
-                let read_addr = bulk_start + bulk_offset as u32;
-                self.bulk_read_flash(read_addr, &mut buf[buf_start..buf_start + this_len])
+                let data_start = head_residue + bulk_offset;
+                let write_addr = bulk_start + bulk_offset as u32;
+                self.bulk_write_flash(write_addr, &data[data_start..data_start + this_len])
                     .await
-                    .map_err(|_| CoreError::ReadError { addr: read_addr })?;
+                    .map_err(|_| CoreError::WriteError { addr: write_addr })?;
                 bulk_offset += this_len;
             }
         }
 
-        // Tail: slow read for remaining bytes
+        // Tail: slow write for remaining bytes
         let tail_start = head_residue + bulk_len;
         if tail_start < len {
             let tail_addr = addr + tail_start as u32;
-            self.slow_read(tail_addr, &mut buf[tail_start..])
+            self.slow_write(tail_addr, &data[tail_start..])
                 .await
-                .map_err(|_| CoreError::ReadError { addr: tail_addr })?;
+                .map_err(|_| CoreError::WriteError { addr: tail_addr })?;
         }
 
         Ok(())
     }
+}
 
-    async fn write(&mut self, addr: u32, data: &[u8]) -> CoreResult<()> {
-        const PAGE_SIZE: usize = 256;
-        let len = data.len();
+// ---------------------------------------------------------------------------
+// OpaqueMaster trait implementation
+// ---------------------------------------------------------------------------
+
+#[maybe_async(AFIT)]
+impl OpaqueMaster for Dediprog {
+    fn size(&self) -> usize {
+        self.flash_size.unwrap_or(0) as usize
+    }
+
+    async fn read(&mut self, addr: u32, buf: &mut [u8]) -> CoreResult<()> {
+        let len = buf.len();
         if len == 0 {
             return Ok(());
         }
 
         // Split into: head residue + aligned bulk + tail residue
-        // Bulk writes require 256-byte (page) alignment
-        let head_residue = if !(addr as usize).is_multiple_of(PAGE_SIZE) {
-            len.min(PAGE_SIZE - (addr as usize % PAGE_SIZE))
+        let chunk_size = BULK_CHUNK_SIZE;
+        let head_residue = if !(addr as usize).is_multiple_of(chunk_size) {
+            len.min(chunk_size - (addr as usize % chunk_size))
         } else {
             0
         };
 
-        // Head: slow write for unaligned start
+        // Head: slow read for unaligned start
         if head_residue > 0 {
-            self.slow_write(addr, &data[..head_residue])
+            self.slow_read(addr, &mut buf[..head_residue])
                 .await
-                .map_err(|_| CoreError::WriteError { addr })?;
+                .map_err(|_| CoreError::ReadError { addr })?;
         }
 
         // Aligned bulk portion
         let bulk_start = addr + head_residue as u32;
         let remaining = len - head_residue;
-        let bulk_len = (remaining / PAGE_SIZE) * PAGE_SIZE;
+        let bulk_len = (remaining / chunk_size) * chunk_size;
 
         if bulk_len > 0 {
             // Split into chunks that fit in a single USB buffer.
-            // Each page is 512 bytes on the wire (256 data + 256 padding), so
-            // MAX_WRITE_PAGES pages = MAX_WRITE_PAGES * 512 bytes USB buffer.
-            let max_pages = (MAX_BLOCK_COUNT as usize).min(MAX_WRITE_PAGES);
+            let max_blocks = (MAX_BLOCK_COUNT as usize).min(MAX_READ_BLOCKS);
             let mut bulk_offset = 0usize;
             while bulk_offset < bulk_len {
-                let this_len = (bulk_len - bulk_offset).min(max_pages * PAGE_SIZE);
-                let this_len = (this_len / PAGE_SIZE) * PAGE_SIZE;
+                let this_len = (bulk_len - bulk_offset).min(max_blocks * chunk_size);
+                let this_len = (this_len / chunk_size) * chunk_size;
                 if this_len == 0 {
                     break;
                 }
-                let data_start = head_residue + bulk_offset;
-                let write_addr = bulk_start + bulk_offset as u32;
-                self.bulk_write_flash(write_addr, &data[data_start..data_start + this_len])
+                let buf_start = head_residue + bulk_offset;
+                let read_addr = bulk_start + bulk_offset as u32;
+                self.bulk_read_flash(read_addr, &mut buf[buf_start..buf_start + this_len])
                     .await
-                    .map_err(|_| CoreError::WriteError { addr: write_addr })?;
+                    .map_err(|_| CoreError::ReadError { addr: read_addr })?;
                 bulk_offset += this_len;
             }
         }
 
-        // Tail: slow write for remaining bytes
+        // Tail: slow read for remaining bytes
         let tail_start = head_residue + bulk_len;
         if tail_start < len {
             let tail_addr = addr + tail_start as u32;
-            self.slow_write(tail_addr, &data[tail_start..])
+            self.slow_read(tail_addr, &mut buf[tail_start..])
                 .await
-                .map_err(|_| CoreError::WriteError { addr: tail_addr })?;
+                .map_err(|_| CoreError::ReadError { addr: tail_addr })?;
         }
 
         Ok(())
     }
 
+    async fn write(&mut self, addr: u32, data: &[u8]) -> CoreResult<()> {
+        // Light the busy LED while programming, and the error LED on failure
+        self.indicate(Led::Busy).await;
+        let result = self.write_flash(addr, data).await;
+        if result.is_err() {
+            self.indicate(Led::Error).await;
+        }
+        result
+    }
+
     async fn erase(&mut self, _addr: u32, _len: u32) -> CoreResult<()> {
         // Erase is not supported through the opaque path.
         // The HybridFlashDevice adapter uses SpiMaster for erase operations,
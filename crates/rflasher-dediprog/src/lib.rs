@@ -43,10 +43,12 @@
 //!
 //! - `device=N` or `index=N`: Select the Nth device (0-indexed)
 //! - `id=SFXXXXXX`: Select device by serial number
-//! - `target=1|2`: Select target flash for dual-chip programmers
+//! - `target=1|2`: Select target flash (2 only on dual-socket SF600-class
+//!   programmers, rejected on SF100/SF200)
 //! - `spispeed=24M|12M|8M|3M|2.18M|1.5M|750k|375k`: SPI clock speed
 //! - `voltage=0|1.8|2.5|3.5` or `1800mV`: Target voltage
-//! - `iomode=single|dual|quad`: Maximum I/O mode (SF600+ only)
+//! - `iomode=single|dual|quad`: Maximum I/O mode for bulk reads (SF600+ only,
+//!   rejected on SF100/SF200)
 //!
 //! # Example with options
 //!
@@ -63,6 +65,13 @@
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 
+//! # Status LEDs
+//!
+//! The busy LED is lit while writing and the error LED when a write fails,
+//! so the programmer shows what is going on without looking at the screen.
+//! `Dediprog::set_led` sets them directly, e.g. to light the pass LED
+//! once a whole operation has been verified.
+
 #![cfg_attr(not(any(feature = "std", feature = "wasm")), no_std)]
 
 #[cfg(any(feature = "std", feature = "wasm"))]
@@ -79,4 +88,4 @@ pub use device::{Dediprog, DediprogConfig, parse_options};
 #[cfg(any(feature = "std", feature = "wasm"))]
 pub use error::{DediprogError, Result};
 #[cfg(any(feature = "std", feature = "wasm"))]
-pub use protocol::{DeviceType, Protocol};
+pub use protocol::{DeviceType, Led, Protocol, Target};
@@ -5,6 +5,10 @@
 
 #![allow(dead_code)]
 
+use rflasher_core::spi::opcodes;
+
+use crate::error::{DediprogError, Result};
+
 // USB device identifiers
 pub const DEDIPROG_USB_VENDOR: u16 = 0x0483;
 pub const DEDIPROG_USB_PRODUCT: u16 = 0xDADA;
@@ -190,18 +194,46 @@ pub enum Command {
     ReadPrj = 0x66,
 }
 
-/// LED states
+/// LED states, a bit per LED
 #[repr(i8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Led {
+    /// Unknown state, can't be set
     Invalid = -1,
+    /// All LEDs off
     None = 0,
+    /// Green pass LED
     Pass = 1,
+    /// Orange busy LED
     Busy = 2,
+    /// Red error LED
     Error = 4,
+    /// All LEDs on
     All = 7,
 }
 
+/// Get the (value, index) of a CMD_SET_IO_LED request for an LED state
+///
+/// The LEDs are active low. Protocol V2+ takes the state in the high byte
+/// of the value, older firmware in the index, and firmware before 5.0.0
+/// has the pass and error LEDs swapped (and no busy LED). Returns `None` for
+/// [`Led::Invalid`].
+pub fn led_request(protocol: Protocol, firmware: u32, led: Led) -> Option<(u16, u16)> {
+    if led == Led::Invalid {
+        return None;
+    }
+    let bits = led as u8;
+    if protocol >= Protocol::V2 {
+        return Some((((bits ^ 7) as u16) << 8, 0));
+    }
+    let bits = if firmware < firmware_version(5, 0, 0) {
+        ((bits & 4) >> 2) | ((bits & 1) << 2)
+    } else {
+        bits
+    };
+    Some((0x9, (bits ^ 7) as u16))
+}
+
 /// Target flash type
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -213,18 +245,28 @@ pub enum Target {
 }
 
 impl Target {
-    /// Parse target from string/number
-    pub fn from_value(v: u8) -> Option<Self> {
-        match v {
-            0 => Some(Target::ApplicationFlash1),
-            1 => Some(Target::FlashCard),
+    /// Parse the `target=1|2` option, numbering the application flash
+    /// sockets from 1
+    pub fn from_socket(n: u8) -> Option<Self> {
+        match n {
+            1 => Some(Target::ApplicationFlash1),
             2 => Some(Target::ApplicationFlash2),
-            3 => Some(Target::Socket),
             _ => None,
         }
     }
 }
 
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::ApplicationFlash1 => write!(f, "1"),
+            Target::ApplicationFlash2 => write!(f, "2"),
+            Target::FlashCard => write!(f, "card"),
+            Target::Socket => write!(f, "socket"),
+        }
+    }
+}
+
 /// Read mode for bulk reads
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -263,6 +305,131 @@ pub enum StandaloneMode {
     Leave = 1,
 }
 
+/// CMD_READ/CMD_WRITE packet
+///
+/// Built by [`prepare_rw_cmd`]. `value` and `index` are the USB control
+/// transfer wValue/wIndex fields; the packet is sent as the control
+/// transfer payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RwCmd {
+    pub value: u16,
+    pub index: u16,
+    buf: [u8; MAX_CMD_SIZE],
+    len: usize,
+}
+
+impl RwCmd {
+    /// The control transfer payload
+    pub fn packet(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+/// Read opcode and dummy clocks for a bulk read in the given I/O mode
+///
+/// Returns `None` for QPI, which bulk reads don't support.
+pub fn bulk_read_op(io_mode: DpIoMode) -> Option<(u8, u8)> {
+    match io_mode {
+        DpIoMode::Single => Some((opcodes::FAST_READ, 8)),
+        DpIoMode::DualOut => Some((opcodes::DOR, 8)),
+        DpIoMode::DualIo => Some((opcodes::DIOR, 4)),
+        DpIoMode::QuadOut => Some((opcodes::QOR, 8)),
+        DpIoMode::QuadIo => Some((opcodes::QIOR, 6)),
+        DpIoMode::Qpi => None,
+    }
+}
+
+/// Prepare a CMD_READ or CMD_WRITE packet for the given protocol version.
+///
+/// `mode` is the read/write mode byte used by V1; V2 and V3 pick their own.
+/// Reads use the fast read opcode for `io_mode`: V2 selects it with the
+/// opcode byte alone, V3 additionally encodes address length and dummy
+/// half-cycles. V1 only supports single I/O.
+pub fn prepare_rw_cmd(
+    protocol: Protocol,
+    is_read: bool,
+    mode: u8,
+    io_mode: DpIoMode,
+    start: u32,
+    count: u16,
+) -> Result<RwCmd> {
+    let mut buf = [0u8; MAX_CMD_SIZE];
+
+    // Common header (all protocol versions)
+    buf[0] = (count & 0xFF) as u8;
+    buf[1] = ((count >> 8) & 0xFF) as u8;
+    buf[2] = 0; // RFU
+    buf[3] = mode;
+    buf[4] = 0; // Opcode (overridden below for V2/V3)
+
+    let (read_opcode, dummy_cycles) = bulk_read_op(io_mode).ok_or_else(|| {
+        DediprogError::Unsupported(format!("{:?} bulk reads are not supported", io_mode))
+    })?;
+
+    let (value, index, len) = match protocol {
+        Protocol::V1 => {
+            // V1: address in wValue/wIndex, 5-byte command packet
+            if start >> 24 != 0 {
+                return Err(DediprogError::Unsupported(
+                    "4-byte address not supported on V1 protocol".to_string(),
+                ));
+            }
+            if is_read && io_mode != DpIoMode::Single {
+                return Err(DediprogError::Unsupported(
+                    "Multi-I/O reads not supported on V1 protocol".to_string(),
+                ));
+            }
+            ((start & 0xFFFF) as u16, ((start >> 16) & 0xFF) as u16, 5)
+        }
+        Protocol::V2 => {
+            if is_read {
+                // For V2 reads, use standard read mode with fast read opcode
+                // The firmware handles the SPI read command internally
+                buf[3] = ReadMode::Fast as u8;
+                buf[4] = read_opcode;
+            } else {
+                // For V2 writes, use page program mode
+                buf[3] = WriteMode::PagePgm as u8;
+                buf[4] = 0;
+            }
+
+            buf[5] = 0; // RFU
+            buf[6..10].copy_from_slice(&start.to_le_bytes());
+            (0, 0, 10)
+        }
+        Protocol::V3 => {
+            buf[5] = 0; // RFU
+            buf[6..10].copy_from_slice(&start.to_le_bytes());
+
+            if is_read {
+                buf[3] = ReadMode::Configurable as u8;
+                buf[4] = read_opcode;
+                buf[10] = if start >> 24 != 0 { 4 } else { 3 }; // address length
+                buf[11] = dummy_cycles / 2; // dummy half-cycles
+                (0, 0, 12)
+            } else {
+                buf[3] = WriteMode::PagePgm as u8;
+                buf[4] = 0;
+                // Page size (256 bytes) as 32-bit LE
+                buf[10..14].copy_from_slice(&256u32.to_le_bytes());
+                (0, 0, 14)
+            }
+        }
+        Protocol::Unknown => {
+            return Err(DediprogError::Unsupported(
+                "Unknown protocol version".to_string(),
+            ));
+        }
+    };
+
+    Ok(RwCmd {
+        value,
+        index,
+        buf,
+        len,
+    })
+}
+
 /// Dediprog I/O mode for multi-I/O commands
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -289,6 +456,21 @@ impl From<rflasher_core::spi::IoMode> for DpIoMode {
     }
 }
 
+/// I/O mode for bulk reads: `mode` if the programmer supports it, else single
+///
+/// QPI is never used, bulk reads don't put the chip in QPI mode.
+pub fn bulk_read_io_mode(
+    features: rflasher_core::programmer::SpiFeatures,
+    mode: rflasher_core::spi::IoMode,
+) -> DpIoMode {
+    use rflasher_core::spi::{IoMode, check_io_mode_supported};
+    if mode == IoMode::Qpi || check_io_mode_supported(mode, features).is_err() {
+        DpIoMode::Single
+    } else {
+        mode.into()
+    }
+}
+
 /// SPI speed settings
 #[derive(Debug, Clone, Copy)]
 pub struct SpiSpeed {
@@ -408,6 +590,27 @@ pub fn parse_voltage(s: &str) -> Option<u16> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rflasher_core::programmer::SpiFeatures;
+    use rflasher_core::spi::IoMode;
+
+    #[test]
+    fn test_bulk_read_io_mode() {
+        let quad = SpiFeatures::DUAL | SpiFeatures::QUAD;
+        assert_eq!(bulk_read_io_mode(quad, IoMode::QuadIo), DpIoMode::QuadIo);
+        assert_eq!(bulk_read_io_mode(quad, IoMode::DualOut), DpIoMode::DualOut);
+        // The chip's mode is kept even if the programmer could go wider
+        assert_eq!(bulk_read_io_mode(quad, IoMode::Single), DpIoMode::Single);
+        assert_eq!(bulk_read_io_mode(quad, IoMode::Qpi), DpIoMode::Single);
+        // Modes the programmer can't do fall back to single
+        assert_eq!(
+            bulk_read_io_mode(SpiFeatures::DUAL, IoMode::QuadOut),
+            DpIoMode::Single
+        );
+        assert_eq!(
+            bulk_read_io_mode(SpiFeatures::empty(), IoMode::DualIo),
+            DpIoMode::Single
+        );
+    }
 
     #[test]
     fn test_parse_voltage() {
@@ -421,6 +624,15 @@ mod tests {
         assert_eq!(parse_voltage("2.5"), Some(2500));
     }
 
+    #[test]
+    fn test_target_from_socket() {
+        assert_eq!(Target::from_socket(1), Some(Target::ApplicationFlash1));
+        assert_eq!(Target::from_socket(2), Some(Target::ApplicationFlash2));
+        assert_eq!(Target::from_socket(0), None);
+        assert_eq!(Target::from_socket(3), None);
+        assert_eq!(Target::ApplicationFlash2.to_string(), "2");
+    }
+
     #[test]
     fn test_device_type_from_string() {
         assert_eq!(
@@ -472,4 +684,120 @@ mod tests {
             Protocol::V3
         );
     }
+
+    #[test]
+    fn test_led_request() {
+        let new = firmware_version(7, 2, 30);
+        assert_eq!(led_request(Protocol::V2, new, Led::Busy), Some((0x0500, 0)));
+        assert_eq!(led_request(Protocol::V3, new, Led::None), Some((0x0700, 0)));
+        assert_eq!(
+            led_request(Protocol::V1, firmware_version(5, 5, 0), Led::Pass),
+            Some((0x9, 6))
+        );
+        // Firmware before 5.0.0 swaps the pass and error LEDs
+        assert_eq!(
+            led_request(Protocol::V1, firmware_version(4, 0, 0), Led::Pass),
+            Some((0x9, 3))
+        );
+        assert_eq!(led_request(Protocol::V2, new, Led::Invalid), None);
+    }
+
+    #[test]
+    fn test_rw_cmd_v1() {
+        let cmd = prepare_rw_cmd(
+            Protocol::V1,
+            true,
+            ReadMode::Std as u8,
+            DpIoMode::Single,
+            0x12_3400,
+            2,
+        )
+        .unwrap();
+        assert_eq!(cmd.value, 0x3400);
+        assert_eq!(cmd.index, 0x12);
+        assert_eq!(cmd.packet(), &[0x02, 0x00, 0x00, ReadMode::Std as u8, 0x00]);
+
+        // No multi-I/O or 4-byte addresses on V1
+        assert!(
+            prepare_rw_cmd(
+                Protocol::V1,
+                true,
+                ReadMode::Std as u8,
+                DpIoMode::DualOut,
+                0,
+                1
+            )
+            .is_err()
+        );
+        assert!(
+            prepare_rw_cmd(
+                Protocol::V1,
+                true,
+                ReadMode::Std as u8,
+                DpIoMode::Single,
+                1 << 24,
+                1
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_rw_cmd_v2() {
+        let cmd = prepare_rw_cmd(
+            Protocol::V2,
+            true,
+            ReadMode::Std as u8,
+            DpIoMode::DualOut,
+            0x0012_3400,
+            1,
+        )
+        .unwrap();
+        assert_eq!((cmd.value, cmd.index), (0, 0));
+        assert_eq!(
+            cmd.packet(),
+            &[
+                0x01,
+                0x00,
+                0x00,
+                ReadMode::Fast as u8,
+                opcodes::DOR,
+                0x00,
+                0x00,
+                0x34,
+                0x12,
+                0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rw_cmd_v3() {
+        let cmd = prepare_rw_cmd(
+            Protocol::V3,
+            true,
+            ReadMode::Std as u8,
+            DpIoMode::QuadIo,
+            0x0012_3400,
+            1,
+        )
+        .unwrap();
+        assert_eq!(cmd.packet().len(), 12);
+        assert_eq!(cmd.packet()[3], ReadMode::Configurable as u8);
+        assert_eq!(cmd.packet()[4], opcodes::QIOR);
+        assert_eq!(cmd.packet()[10], 3); // address length
+        assert_eq!(cmd.packet()[11], 3); // 6 dummy clocks
+
+        let cmd = prepare_rw_cmd(
+            Protocol::V3,
+            false,
+            WriteMode::PagePgm as u8,
+            DpIoMode::Single,
+            0,
+            4,
+        )
+        .unwrap();
+        assert_eq!(cmd.packet().len(), 14);
+        assert_eq!(&cmd.packet()[10..], &[0x00, 0x01, 0x00, 0x00]);
+    }
 }
@@ -2,6 +2,42 @@
 //!
 //! This crate provides a dummy flash programmer that emulates a flash chip
 //! in memory. It's useful for testing and development without real hardware.
+//!
+//! The emulated chip also answers RDSFDP with a JESD216B Basic Flash
+//! Parameter Table matching its configuration, so SFDP-based probing can be
+//! tested end-to-end.
+//!
+//! Program and erase operations can optionally take simulated time (see
+//! [`DummyTiming`]): the chip then reports WIP until enough `delay_us` has
+//! elapsed, and ignores everything but status reads in the meantime.
+//!
+//! Write protection follows the Winbond status register layout: the
+//! BP/TB/SEC/CMP bits protect a range decoded like [`rflasher_core::wp`]
+//! does, and SRP0/SRL together with the emulated WP# pin decide whether the
+//! status registers can be written.
+//!
+//! DP (0xB9) puts the chip into deep power-down, where it ignores
+//! everything but RES (0xAB).
+//!
+//! Array reads are accepted in every single, dual and quad mode, and the
+//! last one is recorded (see [`DummyFlash::last_read`]) so read mode
+//! selection can be tested. Every erase is logged as well (see
+//! [`DummyFlash::erases`]). ULBPR (0x98), the SST26 global unprotect,
+//! clears the BP bits.
+//!
+//! Page programs wrap around at the page boundary like on a real chip, and
+//! transfers longer than the configured limits fail like on a programmer
+//! with smaller buffers. [`DummyFlash::fail_after`] makes programs and
+//! erases fail from some point on, to test interrupted writes.
+//!
+//! EQIO (0x38) enters QPI mode, which RSTQIO (0xFF) leaves again. In QPI
+//! mode only 4-4-4 commands are seen, outside of it only the others.
+//! Chips without RDID (see [`DummyConfig::rdid`]) can still be identified
+//! with REMS (0x90) and RES (0xAB).
+//!
+//! Two vendor registers are emulated as well: a volatile Spansion-style
+//! bank register (BRRD 0x16 / BRWR 0x17) and an ISSI-style function
+//! register (RDFR 0x48 / WRFR 0x42) whose bits can only be set, like OTP.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
@@ -13,9 +49,30 @@ use alloc::vec;
 #[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 
+#[cfg(feature = "alloc")]
+use rflasher_core::chip::{EraseBlock, Features, FlashChip, WriteGranularity};
 use rflasher_core::error::{Error, Result};
-use rflasher_core::programmer::{SpiFeatures, SpiMaster};
-use rflasher_core::spi::{SpiCommand, opcodes};
+#[cfg(feature = "alloc")]
+use rflasher_core::flash::{FlashContext, SpiFlashDevice};
+use rflasher_core::programmer::{CsLevel, SpiFeatures, SpiMaster};
+use rflasher_core::spi::{IoMode, SpiCommand, opcodes};
+#[cfg(feature = "alloc")]
+use rflasher_core::wp::{
+    RangeDecoder, RegBitInfo, StatusRegister, WpBits, WpMode, WpRange, WpRegBitMap, decode_range,
+};
+
+/// Simulated operation durations for the dummy flash
+///
+/// All durations default to zero, which completes operations instantly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DummyTiming {
+    /// Page program time in microseconds
+    pub page_program_us: u32,
+    /// Sector and block erase time in microseconds
+    pub erase_us: u32,
+    /// Chip erase time in microseconds
+    pub chip_erase_us: u32,
+}
 
 /// Configuration for the dummy flash
 #[derive(Debug, Clone)]
@@ -24,12 +81,37 @@ pub struct DummyConfig {
     pub manufacturer_id: u8,
     /// JEDEC device ID
     pub device_id: u16,
+    /// Electronic signature returned by RES (0xAB)
+    pub electronic_signature: u8,
     /// Flash size in bytes
     pub size: usize,
     /// Page size for programming
     pub page_size: usize,
     /// Sector size for smallest erase
     pub sector_size: usize,
+    /// Largest array read per command, longer reads fail like on a
+    /// programmer with a smaller transfer buffer
+    pub max_read_len: usize,
+    /// Largest page program per command, longer ones fail the same way
+    pub max_write_len: usize,
+    /// Answer RDID (0x9F), which chips predating JEDEC IDs don't
+    pub rdid: bool,
+    /// Clock dummy cycles, which the internal controllers can't. Without
+    /// them fast reads are refused like on Intel swseq.
+    pub dummy_cycles: bool,
+    /// Simulated program/erase timing
+    pub timing: DummyTiming,
+    /// Capabilities advertised to the flash code
+    pub spi_features: SpiFeatures,
+    /// Opcodes the programmer can't send, refused by `probe_opcode` and
+    /// `execute`
+    pub unsupported_opcodes: &'static [u8],
+    /// SFDP data returned by RDSFDP, starting at SFDP address 0
+    ///
+    /// `None` generates a basic table from the size and page size above.
+    /// An empty blob emulates a chip without SFDP.
+    #[cfg(feature = "alloc")]
+    pub sfdp: Option<Vec<u8>>,
 }
 
 impl Default for DummyConfig {
@@ -37,13 +119,116 @@ impl Default for DummyConfig {
         Self {
             manufacturer_id: 0xEF, // Winbond
             device_id: 0x4018,     // W25Q128FV
+            electronic_signature: 0x17,
             size: 16 * 1024 * 1024,
             page_size: 256,
             sector_size: 4096,
+            max_read_len: 4096,
+            max_write_len: 256,
+            rdid: true,
+            dummy_cycles: true,
+            timing: DummyTiming::default(),
+            spi_features: SpiFeatures::FOUR_BYTE_ADDR | SpiFeatures::DUAL | SpiFeatures::QUAD,
+            unsupported_opcodes: &[],
+            #[cfg(feature = "alloc")]
+            sfdp: None,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl DummyConfig {
+    /// Chip description matching the emulation, to use it without probing
+    ///
+    /// Lists the 4 KiB (0x20), 32 KiB (0x52) and 64 KiB (0xD8) erases and
+    /// the Winbond write protection layout.
+    pub fn flash_chip(&self) -> FlashChip {
+        let size = self.size as u32;
+        FlashChip {
+            vendor: "Dummy".into(),
+            name: "DUMMY".into(),
+            aliases: Vec::new(),
+            jedec_manufacturer: self.manufacturer_id,
+            jedec_device: self.device_id,
+            total_size: size,
+            page_size: self.page_size as u16,
+            features: Features::empty(),
+            voltage_min_mv: 2700,
+            voltage_max_mv: 3600,
+            write_granularity: WriteGranularity::Page,
+            erase_blocks: vec![
+                EraseBlock::with_count(opcodes::SE_20, 4096, size / 4096),
+                EraseBlock::with_count(opcodes::BE_52, 32768, size / 32768),
+                EraseBlock::with_count(opcodes::BE_D8, 65536, size / 65536),
+            ],
+            tested: Default::default(),
+            wp_bits: Some(WpRegBitMap::winbond_standard()),
+            wp_decoder: RangeDecoder::Spi25,
         }
     }
 }
 
+/// Build an SFDP blob with a single JESD216B Basic Flash Parameter Table
+///
+/// The table describes the erase types and opcodes `DummyFlash` implements
+/// (4 KiB 0x20, 32 KiB 0x52, 64 KiB 0xD8), the given size and page size,
+/// 3/4-byte addressing with EN4B for chips above 16 MiB, and the 66h/99h
+/// soft reset. No fast read modes are advertised.
+#[cfg(feature = "alloc")]
+pub fn default_sfdp(size: usize, page_size: usize) -> Vec<u8> {
+    const BFPT_DWORDS: u8 = 16;
+    const BFPT_PTR: u32 = 0x10;
+
+    let large = size > 16 * 1024 * 1024;
+    let mut bfpt = [0u32; BFPT_DWORDS as usize];
+
+    // DWORD 1: 4 KiB erase with 0x20, 64-byte write granularity, address bytes
+    bfpt[0] = 0xFF00_0000 | (0x20 << 8) | (1 << 2) | 0x01;
+    if large {
+        bfpt[0] |= 0b01 << 17;
+    }
+
+    // DWORD 2: density in bits, as N-1 or 2^N above 2 Gbit
+    let bits = size as u64 * 8;
+    bfpt[1] = if bits <= 1 << 31 {
+        (bits - 1) as u32
+    } else {
+        (1 << 31) | bits.trailing_zeros()
+    };
+
+    // DWORDs 3-7: no fast read modes
+    bfpt[4] = 0xFFFF_FFEE;
+    bfpt[5] = 0x0000_FFFF;
+    bfpt[6] = 0x0000_FFFF;
+
+    // DWORDs 8-9: erase types
+    bfpt[7] = (0x52 << 24) | (15 << 16) | (0x20 << 8) | 12;
+    bfpt[8] = (0xD8 << 8) | 16;
+
+    // DWORD 11: page size
+    bfpt[10] = (page_size.trailing_zeros() & 0x0F) << 4;
+
+    // DWORD 16: 66h/99h soft reset, EN4B/EX4B for 4-byte mode
+    bfpt[15] = 0x10 << 8;
+    if large {
+        bfpt[15] |= 0x01 << 24;
+    }
+
+    // SFDP header: signature, revision 1.6, one parameter header
+    let mut sfdp = Vec::with_capacity(BFPT_PTR as usize + bfpt.len() * 4);
+    sfdp.extend_from_slice(b"SFDP");
+    sfdp.extend_from_slice(&[0x06, 0x01, 0x00, 0xFF]);
+
+    // BFPT parameter header: ID 0xFF00, revision 1.6
+    sfdp.extend_from_slice(&[0x00, 0x06, 0x01, BFPT_DWORDS]);
+    sfdp.extend_from_slice(&BFPT_PTR.to_le_bytes()[..3]);
+    sfdp.push(0xFF);
+    for dword in bfpt {
+        sfdp.extend_from_slice(&dword.to_le_bytes());
+    }
+    sfdp
+}
+
 /// Dummy flash programmer
 ///
 /// Emulates a flash chip in memory for testing purposes.
@@ -51,11 +236,32 @@ impl Default for DummyConfig {
 pub struct DummyFlash {
     config: DummyConfig,
     data: Vec<u8>,
+    sfdp: Vec<u8>,
     status_reg1: u8,
     status_reg2: u8,
     status_reg3: u8,
     write_enabled: bool,
     in_4byte_mode: bool,
+    /// Remaining simulated busy time in microseconds
+    busy_us: u32,
+    /// WP# pin driven low (asserted)
+    wp_pin: bool,
+    /// Opcode and I/O mode of the last array read
+    last_read: Option<(u8, IoMode)>,
+    /// Opcode and address of every erase
+    erases: Vec<(u8, u32)>,
+    /// Every `send_clocks` call, as cycles and CS level
+    clocks: Vec<(u32, CsLevel)>,
+    /// In QPI mode, only seeing 4-4-4 commands
+    in_qpi: bool,
+    /// Programs and erases left before they fail, see `fail_after`
+    ops_left: Option<usize>,
+    /// In deep power-down, only answering RES
+    powered_down: bool,
+    /// Bank register, cleared by a power cycle
+    bank_reg: u8,
+    /// Function register, bits are one-time programmable
+    function_reg: u8,
 }
 
 #[cfg(feature = "alloc")]
@@ -63,14 +269,29 @@ impl DummyFlash {
     /// Create a new dummy flash with the given configuration
     pub fn new(config: DummyConfig) -> Self {
         let data = vec![0xFF; config.size];
+        let sfdp = config
+            .sfdp
+            .clone()
+            .unwrap_or_else(|| default_sfdp(config.size, config.page_size));
         Self {
             config,
             data,
+            sfdp,
             status_reg1: 0,
             status_reg2: 0,
             status_reg3: 0,
             write_enabled: false,
             in_4byte_mode: false,
+            busy_us: 0,
+            wp_pin: false,
+            last_read: None,
+            erases: Vec::new(),
+            clocks: Vec::new(),
+            in_qpi: false,
+            ops_left: None,
+            powered_down: false,
+            bank_reg: 0,
+            function_reg: 0,
         }
     }
 
@@ -87,6 +308,14 @@ impl DummyFlash {
         flash
     }
 
+    /// Wrap the emulated chip in a flash device
+    ///
+    /// The device uses the chip described by [`DummyConfig::flash_chip`].
+    pub fn into_device(self) -> SpiFlashDevice<Self> {
+        let ctx = FlashContext::new(self.config.flash_chip());
+        SpiFlashDevice::new(self, ctx)
+    }
+
     /// Get a reference to the flash data
     pub fn data(&self) -> &[u8] {
         &self.data
@@ -102,6 +331,143 @@ impl DummyFlash {
         &self.config
     }
 
+    /// Check if a simulated program or erase is still in progress
+    pub fn is_busy(&self) -> bool {
+        self.busy_us > 0
+    }
+
+    /// Opcode and I/O mode of the last array read, if any
+    pub fn last_read(&self) -> Option<(u8, IoMode)> {
+        self.last_read
+    }
+
+    /// Opcode and address of every successful erase, oldest first
+    pub fn erases(&self) -> &[(u8, u32)] {
+        &self.erases
+    }
+
+    /// Raw clocks sent with `send_clocks`, oldest first
+    pub fn clocks(&self) -> &[(u32, CsLevel)] {
+        &self.clocks
+    }
+
+    /// Check if the chip is in 4-byte address mode
+    pub fn is_4byte_mode(&self) -> bool {
+        self.in_4byte_mode
+    }
+
+    /// Check if the chip is in deep power-down
+    pub fn is_powered_down(&self) -> bool {
+        self.powered_down
+    }
+
+    /// Check if the chip is in QPI mode
+    pub fn is_qpi_mode(&self) -> bool {
+        self.in_qpi
+    }
+
+    /// Let `ops` more page programs and erases through, then fail all others
+    ///
+    /// Emulates a programmer losing the connection mid-write. The failing
+    /// commands return `Error::ProgrammerError`; `None` stops failing.
+    pub fn fail_after(&mut self, ops: Option<usize>) {
+        self.ops_left = ops;
+    }
+
+    /// Count a program or erase against `fail_after`
+    fn take_op(&mut self) -> Result<()> {
+        match &mut self.ops_left {
+            Some(0) => Err(Error::ProgrammerError),
+            Some(left) => {
+                *left -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Drive the emulated WP# pin, `true` meaning asserted (low)
+    pub fn set_wp_pin(&mut self, asserted: bool) {
+        self.wp_pin = asserted;
+    }
+
+    /// Emulate a power cycle
+    ///
+    /// Clears the volatile state: write enable, 4-byte and QPI mode, a
+    /// pending busy period, deep power-down, the bank register and the SRL
+    /// (power-cycle lock) bit.
+    pub fn power_cycle(&mut self) {
+        self.write_enabled = false;
+        self.in_4byte_mode = false;
+        self.in_qpi = false;
+        self.busy_us = 0;
+        self.powered_down = false;
+        self.bank_reg = 0;
+        if self.wp_bits().mode() == WpMode::PowerCycle {
+            self.status_reg2 &= !(1 << Self::WP_MAP.srl.bit_index);
+        }
+    }
+
+    /// Register layout used for write protection
+    const WP_MAP: WpRegBitMap = WpRegBitMap::winbond_standard();
+
+    /// Current write protection bits, decoded from the status registers
+    pub fn wp_bits(&self) -> WpBits {
+        let read = |info: &RegBitInfo| {
+            let reg = match info.reg? {
+                StatusRegister::Status1 => self.status_reg1,
+                StatusRegister::Status2 | StatusRegister::Config => self.status_reg2,
+                StatusRegister::Status3 => self.status_reg3,
+            };
+            Some((reg >> info.bit_index) & 1)
+        };
+
+        let map = &Self::WP_MAP;
+        let mut bits = WpBits {
+            srp: read(&map.srp),
+            srl: read(&map.srl),
+            cmp: read(&map.cmp),
+            sec: read(&map.sec),
+            tb: read(&map.tb),
+            bp_count: map.bp_count(),
+            ..WpBits::empty()
+        };
+        for i in 0..bits.bp_count {
+            bits.bp[i] = read(&map.bp[i]).unwrap_or(0);
+        }
+        bits
+    }
+
+    /// Range currently protected by the block protect bits
+    pub fn protected_range(&self) -> WpRange {
+        decode_range(&self.wp_bits(), self.data.len() as u32, RangeDecoder::Spi25)
+    }
+
+    fn check_unprotected(&self, addr: usize, len: usize) -> Result<()> {
+        if self.protected_range().overlaps(addr as u32, len as u32) {
+            return Err(Error::WriteProtected);
+        }
+        Ok(())
+    }
+
+    /// Check if WRSR is accepted under the current SRP/SRL mode
+    fn status_writable(&self) -> bool {
+        match self.wp_bits().mode() {
+            WpMode::Disabled => true,
+            WpMode::Hardware => !self.wp_pin,
+            WpMode::PowerCycle | WpMode::Permanent => false,
+        }
+    }
+
+    /// Status register 1 as seen by RDSR, with WIP reflecting the busy state
+    fn status1(&self) -> u8 {
+        if self.is_busy() {
+            self.status_reg1 | opcodes::SR1_WIP
+        } else {
+            self.status_reg1 & !opcodes::SR1_WIP
+        }
+    }
+
     fn get_address(&self, cmd: &SpiCommand<'_>) -> Option<u32> {
         cmd.address
     }
@@ -110,15 +476,30 @@ impl DummyFlash {
         let addr = self.get_address(cmd).unwrap_or(0) as usize;
         let len = cmd.read_buf.len();
 
+        if len > self.config.max_read_len {
+            return Err(Error::SpiTransferFailed);
+        }
         if addr + len > self.data.len() {
             return Err(Error::AddressOutOfBounds);
         }
 
         cmd.read_buf.copy_from_slice(&self.data[addr..addr + len]);
+        self.last_read = Some((cmd.opcode, cmd.io_mode));
+        Ok(())
+    }
+
+    fn handle_read_sfdp(&mut self, cmd: &mut SpiCommand<'_>) -> Result<()> {
+        let addr = self.get_address(cmd).unwrap_or(0) as usize;
+
+        // Reads past the end of the SFDP data return erased bytes
+        for (i, byte) in cmd.read_buf.iter_mut().enumerate() {
+            *byte = self.sfdp.get(addr + i).copied().unwrap_or(0xFF);
+        }
         Ok(())
     }
 
     fn handle_page_program(&mut self, cmd: &SpiCommand<'_>) -> Result<()> {
+        self.take_op()?;
         if !self.write_enabled {
             return Err(Error::WriteProtected);
         }
@@ -126,20 +507,29 @@ impl DummyFlash {
         let addr = self.get_address(cmd).unwrap_or(0) as usize;
         let data = cmd.write_data;
 
+        if data.len() > self.config.max_write_len {
+            return Err(Error::SpiTransferFailed);
+        }
         if addr + data.len() > self.data.len() {
             return Err(Error::AddressOutOfBounds);
         }
+        self.check_unprotected(addr, data.len())?;
 
-        // Flash programming: can only change 1 -> 0
+        // Flash programming: can only change 1 -> 0, wrapping around within
+        // the page
+        let page_size = self.config.page_size;
+        let page_start = addr - addr % page_size;
         for (i, &byte) in data.iter().enumerate() {
-            self.data[addr + i] &= byte;
+            self.data[page_start + (addr - page_start + i) % page_size] &= byte;
         }
 
         self.write_enabled = false;
+        self.busy_us = self.config.timing.page_program_us;
         Ok(())
     }
 
     fn handle_sector_erase(&mut self, cmd: &SpiCommand<'_>, erase_size: usize) -> Result<()> {
+        self.take_op()?;
         if !self.write_enabled {
             return Err(Error::WriteProtected);
         }
@@ -152,22 +542,33 @@ impl DummyFlash {
         if aligned_addr + erase_size > self.data.len() {
             return Err(Error::AddressOutOfBounds);
         }
+        self.check_unprotected(aligned_addr, erase_size)?;
 
         // Erase sets all bytes to 0xFF
         self.data[aligned_addr..aligned_addr + erase_size].fill(0xFF);
 
+        self.erases.push((cmd.opcode, aligned_addr as u32));
         self.write_enabled = false;
+        self.busy_us = self.config.timing.erase_us;
         Ok(())
     }
 
-    fn handle_chip_erase(&mut self) -> Result<()> {
+    fn handle_chip_erase(&mut self, opcode: u8) -> Result<()> {
+        self.take_op()?;
         if !self.write_enabled {
             return Err(Error::WriteProtected);
         }
 
+        // Chip erase is refused if any part of the chip is protected
+        if self.protected_range().is_protected() {
+            return Err(Error::WriteProtected);
+        }
+
         self.data.fill(0xFF);
 
+        self.erases.push((opcode, 0));
         self.write_enabled = false;
+        self.busy_us = self.config.timing.chip_erase_us;
         Ok(())
     }
 }
@@ -175,24 +576,69 @@ impl DummyFlash {
 #[cfg(feature = "alloc")]
 impl SpiMaster for DummyFlash {
     fn features(&self) -> SpiFeatures {
-        SpiFeatures::FOUR_BYTE_ADDR | SpiFeatures::DUAL | SpiFeatures::QUAD
+        self.config.spi_features
     }
 
     fn max_read_len(&self) -> usize {
-        4096
+        self.config.max_read_len
     }
 
     fn max_write_len(&self) -> usize {
-        self.config.page_size
+        self.config.max_write_len
+    }
+
+    fn probe_opcode(&self, opcode: u8) -> bool {
+        if !self.config.dummy_cycles && opcodes::DUMMY_CYCLE_READS.contains(&opcode) {
+            return false;
+        }
+        !self.config.unsupported_opcodes.contains(&opcode)
     }
 
     fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> Result<()> {
-        // Note: DummyFlash accepts all I/O modes since it's an in-memory emulator.
-        // The io_mode field is ignored because we just simulate the flash behavior
-        // without actually transferring data on physical wires.
+        if !self.probe_opcode(cmd.opcode) {
+            return Err(Error::OpcodeNotSupported);
+        }
+        if !self.config.dummy_cycles && cmd.dummy_cycles > 0 {
+            return Err(Error::OpcodeNotSupported);
+        }
+
+        // Note: DummyFlash accepts all single, dual and quad modes since it's
+        // an in-memory emulator. Only QPI is modal: a chip in the other mode
+        // doesn't see a valid command, and reads see an undriven bus.
+        if (cmd.io_mode == IoMode::Qpi) != self.in_qpi {
+            cmd.read_buf.fill(0xFF);
+            return Ok(());
+        }
+
+        // A powered-down chip only wakes up on RES
+        if self.powered_down && cmd.opcode != opcodes::RES {
+            log::warn!(
+                "dummy: opcode 0x{:02X} ignored, flash in deep power-down",
+                cmd.opcode
+            );
+            cmd.read_buf.fill(0xFF);
+            return Ok(());
+        }
+
+        // A busy chip only answers status reads; everything else is dropped
+        // and reads see an undriven bus.
+        if self.is_busy() && !matches!(cmd.opcode, opcodes::RDSR | opcodes::RDSR2 | opcodes::RDSR3)
+        {
+            log::warn!(
+                "dummy: opcode 0x{:02X} ignored, flash busy for {} more us",
+                cmd.opcode,
+                self.busy_us
+            );
+            cmd.read_buf.fill(0xFF);
+            return Ok(());
+        }
 
         match cmd.opcode {
             // JEDEC ID
+            opcodes::RDID if !self.config.rdid => {
+                cmd.read_buf.fill(0xFF);
+                Ok(())
+            }
             opcodes::RDID => {
                 if cmd.read_buf.len() >= 3 {
                     cmd.read_buf[0] = self.config.manufacturer_id;
@@ -205,7 +651,7 @@ impl SpiMaster for DummyFlash {
             // Status register read
             opcodes::RDSR => {
                 if !cmd.read_buf.is_empty() {
-                    cmd.read_buf[0] = self.status_reg1;
+                    cmd.read_buf[0] = self.status1();
                 }
                 Ok(())
             }
@@ -222,14 +668,43 @@ impl SpiMaster for DummyFlash {
                 Ok(())
             }
 
-            // Status register write
+            // Status register write, dropped while SRP/SRL lock the registers
             opcodes::WRSR => {
                 if self.write_enabled {
-                    if !cmd.write_data.is_empty() {
-                        self.status_reg1 = cmd.write_data[0];
+                    if self.status_writable() {
+                        if !cmd.write_data.is_empty() {
+                            self.status_reg1 = cmd.write_data[0];
+                        }
+                        if cmd.write_data.len() >= 2 {
+                            self.status_reg2 = cmd.write_data[1];
+                        }
                     }
-                    if cmd.write_data.len() >= 2 {
-                        self.status_reg2 = cmd.write_data[1];
+                    self.write_enabled = false;
+                }
+                Ok(())
+            }
+
+            // Bank register, written without WREN
+            opcodes::BRRD => {
+                cmd.read_buf.fill(self.bank_reg);
+                Ok(())
+            }
+            opcodes::BRWR => {
+                if let Some(&value) = cmd.write_data.first() {
+                    self.bank_reg = value;
+                }
+                Ok(())
+            }
+
+            // Function register, bits can be set but not cleared
+            opcodes::RDFR => {
+                cmd.read_buf.fill(self.function_reg);
+                Ok(())
+            }
+            opcodes::WRFR => {
+                if self.write_enabled {
+                    if let Some(&value) = cmd.write_data.first() {
+                        self.function_reg |= value;
                     }
                     self.write_enabled = false;
                 }
@@ -249,6 +724,13 @@ impl SpiMaster for DummyFlash {
             // Read commands
             opcodes::READ | opcodes::FAST_READ => self.handle_read(cmd),
             opcodes::READ_4B | opcodes::FAST_READ_4B => self.handle_read(cmd),
+            opcodes::DOR | opcodes::DIOR | opcodes::QOR | opcodes::QIOR => self.handle_read(cmd),
+            opcodes::DOR_4B | opcodes::DIOR_4B | opcodes::QOR_4B | opcodes::QIOR_4B => {
+                self.handle_read(cmd)
+            }
+
+            // SFDP
+            opcodes::RDSFDP => self.handle_read_sfdp(cmd),
 
             // Page program
             opcodes::PP => self.handle_page_program(cmd),
@@ -258,7 +740,16 @@ impl SpiMaster for DummyFlash {
             opcodes::SE_20 | opcodes::SE_21 => self.handle_sector_erase(cmd, 4 * 1024),
             opcodes::BE_52 | opcodes::BE_5C => self.handle_sector_erase(cmd, 32 * 1024),
             opcodes::BE_D8 | opcodes::BE_DC => self.handle_sector_erase(cmd, 64 * 1024),
-            opcodes::CE_60 | opcodes::CE_C7 => self.handle_chip_erase(),
+            opcodes::CE_60 | opcodes::CE_C7 => self.handle_chip_erase(cmd.opcode),
+
+            // SST26 global unprotect, emulated on the BP bits
+            opcodes::ULBPR => {
+                if self.write_enabled {
+                    self.status_reg1 &= !(opcodes::SR1_BP0 | opcodes::SR1_BP1 | opcodes::SR1_BP2);
+                    self.write_enabled = false;
+                }
+                Ok(())
+            }
 
             // 4-byte address mode
             opcodes::EN4B => {
@@ -270,23 +761,100 @@ impl SpiMaster for DummyFlash {
                 Ok(())
             }
 
-            // Software reset
-            opcodes::RSTEN | opcodes::RST => Ok(()),
+            // Software reset, back to power-on volatile state
+            opcodes::RSTEN => Ok(()),
+            opcodes::RST => {
+                self.write_enabled = false;
+                self.in_4byte_mode = false;
+                Ok(())
+            }
+
+            // Deep power-down
+            opcodes::DP => {
+                self.powered_down = true;
+                Ok(())
+            }
+            opcodes::RES => {
+                self.powered_down = false;
+                cmd.read_buf.fill(self.config.electronic_signature);
+                Ok(())
+            }
+
+            // Legacy manufacturer and device ID
+            opcodes::REMS => {
+                if cmd.read_buf.len() >= 2 {
+                    cmd.read_buf[0] = self.config.manufacturer_id;
+                    cmd.read_buf[1] = self.config.electronic_signature;
+                }
+                Ok(())
+            }
+
+            // QPI mode
+            opcodes::EQIO => {
+                self.in_qpi = true;
+                Ok(())
+            }
+            opcodes::RSTQIO if self.in_qpi => {
+                self.in_qpi = false;
+                Ok(())
+            }
 
             // Unknown opcode
             _ => Err(Error::OpcodeNotSupported),
         }
     }
 
-    fn delay_us(&mut self, _us: u32) {
-        // No delay needed for in-memory operations
+    fn delay_us(&mut self, us: u32) {
+        // No real delay needed for in-memory operations, just advance the
+        // simulated clock
+        self.busy_us = self.busy_us.saturating_sub(us);
+    }
+
+    fn send_clocks(&mut self, cycles: u32, cs: CsLevel) -> Result<()> {
+        self.clocks.push((cycles, cs));
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rflasher_core::chip::ChipDatabase;
+    use rflasher_core::chip::{EraseBlock, Features, FlashChip, WriteGranularity};
+    use rflasher_core::flash::unified::{NoProgress, smart_write};
+    use rflasher_core::flash::{self, FlashDevice};
+    use rflasher_core::programmer::{BusWidth, ChunkLimit, IoLimit};
     use rflasher_core::protocol;
+    use rflasher_core::sfdp::{self, AddressMode};
+
+    fn test_context(features: Features) -> FlashContext {
+        FlashContext::new(FlashChip {
+            vendor: "Winbond".into(),
+            name: "W25Q128FV".into(),
+            aliases: Vec::new(),
+            jedec_manufacturer: 0xEF,
+            jedec_device: 0x4018,
+            total_size: 16 * 1024 * 1024,
+            page_size: 256,
+            features,
+            voltage_min_mv: 2700,
+            voltage_max_mv: 3600,
+            write_granularity: WriteGranularity::Page,
+            erase_blocks: vec![EraseBlock::with_count(opcodes::SE_20, 4096, 4096)],
+            tested: Default::default(),
+            wp_bits: None,
+            wp_decoder: Default::default(),
+        })
+    }
+
+    /// Read through `flash::read` and return the opcode and mode it used
+    fn read_mode(flash: &mut DummyFlash, features: Features) -> (u8, IoMode) {
+        flash.data_mut()[0x100..0x104].copy_from_slice(&[1, 2, 3, 4]);
+        let mut buf = [0u8; 4];
+        flash::read(flash, &test_context(features), 0x100, &mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4]);
+        flash.last_read().unwrap()
+    }
 
     #[test]
     fn test_read_jedec_id() {
@@ -341,4 +909,830 @@ mod tests {
         flash.execute(&mut cmd).unwrap();
         assert!(buf.iter().all(|&b| b == 0xFF));
     }
+
+    #[test]
+    fn test_sfdp_default() {
+        let mut flash = DummyFlash::new_default();
+        let info = sfdp::probe(&mut flash).unwrap();
+
+        assert_eq!(info.total_size(), 16 * 1024 * 1024);
+        assert_eq!(info.page_size(), 256);
+        assert_eq!(info.basic_params.address_mode, AddressMode::ThreeByteOnly);
+        assert_eq!(info.basic_params.erase_4k_opcode, opcodes::SE_20);
+
+        let erase: Vec<_> = info
+            .basic_params
+            .sorted_erase_types()
+            .iter()
+            .map(|et| (et.size, et.opcode))
+            .collect();
+        assert_eq!(
+            erase,
+            [
+                (4096, opcodes::SE_20),
+                (32768, opcodes::BE_52),
+                (65536, opcodes::BE_D8)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sfdp_large() {
+        let config = DummyConfig {
+            size: 64 * 1024 * 1024,
+            ..DummyConfig::default()
+        };
+        let mut flash = DummyFlash::new(config);
+        let info = sfdp::probe(&mut flash).unwrap();
+
+        assert_eq!(info.total_size(), 64 * 1024 * 1024);
+        assert_eq!(info.basic_params.address_mode, AddressMode::ThreeOrFourByte);
+    }
+
+    #[test]
+    fn test_sfdp_disabled() {
+        let config = DummyConfig {
+            sfdp: Some(Vec::new()),
+            ..DummyConfig::default()
+        };
+        let mut flash = DummyFlash::new(config);
+        assert!(!sfdp::is_supported(&mut flash));
+    }
+
+    #[test]
+    fn test_busy_until_delay() {
+        let config = DummyConfig {
+            timing: DummyTiming {
+                page_program_us: 100,
+                ..DummyTiming::default()
+            },
+            ..DummyConfig::default()
+        };
+        let mut flash = DummyFlash::new(config);
+
+        protocol::write_enable(&mut flash).unwrap();
+        let mut cmd = SpiCommand::write_3b(opcodes::PP, 0, &[0x00]);
+        flash.execute(&mut cmd).unwrap();
+
+        let status = protocol::read_status1(&mut flash).unwrap();
+        assert_ne!(status & opcodes::SR1_WIP, 0);
+
+        // Not enough polls to cover the program time
+        assert_eq!(
+            protocol::wait_ready(&mut flash, 10, 50),
+            Err(Error::Timeout)
+        );
+
+        protocol::wait_ready(&mut flash, 10, 1000).unwrap();
+        assert!(!flash.is_busy());
+        assert_eq!(flash.data()[0], 0x00);
+    }
+
+    #[test]
+    fn test_busy_ignores_commands() {
+        let config = DummyConfig {
+            timing: DummyTiming {
+                erase_us: 1000,
+                ..DummyTiming::default()
+            },
+            ..DummyConfig::default()
+        };
+        let mut flash = DummyFlash::new(config);
+
+        protocol::write_enable(&mut flash).unwrap();
+        let mut cmd = SpiCommand::erase_3b(opcodes::SE_20, 0);
+        flash.execute(&mut cmd).unwrap();
+
+        // A program issued without polling is dropped
+        protocol::write_enable(&mut flash).unwrap();
+        let mut cmd = SpiCommand::write_3b(opcodes::PP, 0, &[0x00]);
+        flash.execute(&mut cmd).unwrap();
+
+        flash.delay_us(1000);
+        assert_eq!(flash.data()[0], 0xFF);
+    }
+
+    #[test]
+    fn test_block_protect() {
+        let mut flash = DummyFlash::new_default();
+
+        // BP0 protects the top of the chip
+        protocol::write_status1(&mut flash, opcodes::SR1_BP0).unwrap();
+        let range = flash.protected_range();
+        assert!(range.is_protected());
+        assert_eq!(range.end() as usize, flash.data().len());
+
+        protocol::write_enable(&mut flash).unwrap();
+        let mut cmd = SpiCommand::write_3b(opcodes::PP, range.start, &[0x00]);
+        assert_eq!(flash.execute(&mut cmd), Err(Error::WriteProtected));
+
+        protocol::write_enable(&mut flash).unwrap();
+        let mut cmd = SpiCommand::erase_3b(opcodes::SE_20, range.start);
+        assert_eq!(flash.execute(&mut cmd), Err(Error::WriteProtected));
+
+        protocol::write_enable(&mut flash).unwrap();
+        let mut cmd = SpiCommand::simple(opcodes::CE_C7);
+        assert_eq!(flash.execute(&mut cmd), Err(Error::WriteProtected));
+
+        // Below the protected range is still writable
+        protocol::write_enable(&mut flash).unwrap();
+        let mut cmd = SpiCommand::write_3b(opcodes::PP, 0, &[0x00]);
+        flash.execute(&mut cmd).unwrap();
+    }
+
+    #[test]
+    fn test_hardware_wp() {
+        let mut flash = DummyFlash::new_default();
+
+        // SRP0 set: the WP# pin now locks the status register
+        protocol::write_status1(&mut flash, opcodes::SR1_SRP0).unwrap();
+        assert_eq!(flash.wp_bits().mode(), WpMode::Hardware);
+
+        flash.set_wp_pin(true);
+        protocol::write_status1(&mut flash, 0).unwrap();
+        assert_eq!(
+            protocol::read_status1(&mut flash).unwrap(),
+            opcodes::SR1_SRP0
+        );
+
+        flash.set_wp_pin(false);
+        protocol::write_status1(&mut flash, 0).unwrap();
+        assert_eq!(protocol::read_status1(&mut flash).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_read_mode_fastest_common() {
+        let mut flash = DummyFlash::new_default();
+        let quad = Features::FAST_READ | Features::DUAL_IO | Features::QUAD_IO | Features::QE_SR2;
+        assert_eq!(read_mode(&mut flash, quad), (opcodes::QIOR, IoMode::QuadIo));
+        assert_eq!(
+            read_mode(&mut flash, Features::FAST_READ | Features::DUAL_IO),
+            (opcodes::DIOR, IoMode::DualIo)
+        );
+        assert_eq!(
+            read_mode(&mut flash, Features::FAST_READ),
+            (opcodes::FAST_READ, IoMode::Single)
+        );
+        assert_eq!(
+            read_mode(&mut flash, Features::empty()),
+            (opcodes::READ, IoMode::Single)
+        );
+    }
+
+    #[test]
+    fn test_read_mode_limited_by_programmer() {
+        let quad = Features::FAST_READ | Features::DUAL_IO | Features::QUAD_IO | Features::QE_SR2;
+
+        let config = DummyConfig {
+            spi_features: SpiFeatures::DUAL_IN | SpiFeatures::QUAD_IN,
+            ..DummyConfig::default()
+        };
+        let mut flash = DummyFlash::new(config);
+        assert_eq!(read_mode(&mut flash, quad), (opcodes::QOR, IoMode::QuadOut));
+
+        let config = DummyConfig {
+            spi_features: SpiFeatures::DUAL_IN,
+            ..DummyConfig::default()
+        };
+        let mut flash = DummyFlash::new(config);
+        assert_eq!(read_mode(&mut flash, quad), (opcodes::DOR, IoMode::DualOut));
+
+        // Single I/O only, as with --iomode single
+        let config = DummyConfig {
+            spi_features: SpiFeatures::FOUR_BYTE_ADDR,
+            ..DummyConfig::default()
+        };
+        let mut flash = DummyFlash::new(config);
+        assert_eq!(
+            read_mode(&mut flash, quad),
+            (opcodes::FAST_READ, IoMode::Single)
+        );
+    }
+
+    #[test]
+    fn test_read_mode_without_dummy_cycles() {
+        // Like Intel swseq or AMD SPI100, which can't clock dummy cycles
+        let config = DummyConfig {
+            dummy_cycles: false,
+            ..DummyConfig::default()
+        };
+        let mut flash = DummyFlash::new(config);
+        let quad = Features::FAST_READ | Features::DUAL_IO | Features::QUAD_IO | Features::QE_SR2;
+        assert_eq!(read_mode(&mut flash, quad), (opcodes::READ, IoMode::Single));
+        assert_eq!(
+            read_mode(&mut flash, Features::FAST_READ),
+            (opcodes::READ, IoMode::Single)
+        );
+    }
+
+    #[test]
+    fn test_read_mode_sets_quad_enable() {
+        let mut flash = DummyFlash::new_default();
+        let features = Features::QUAD_IO | Features::QE_SR2;
+        assert_eq!(
+            read_mode(&mut flash, features),
+            (opcodes::QIOR, IoMode::QuadIo)
+        );
+        assert_ne!(
+            protocol::read_status2(&mut flash).unwrap() & opcodes::SR2_QE,
+            0
+        );
+
+        // A locked status register keeps QE clear, so reads fall back
+        let mut flash = DummyFlash::new_default();
+        protocol::write_status12(&mut flash, 0, opcodes::SR2_SRP1).unwrap();
+        let features = Features::FAST_READ | Features::QUAD_IO | Features::QE_SR2;
+        assert_eq!(
+            read_mode(&mut flash, features),
+            (opcodes::FAST_READ, IoMode::Single)
+        );
+    }
+
+    #[test]
+    fn test_read_mode_unknown_quad_enable() {
+        // Without QE_SR2 or SFDP the QE bit can't be set, so no quad reads
+        let mut flash = DummyFlash::new_default();
+        let features = Features::FAST_READ | Features::DUAL_IO | Features::QUAD_IO;
+        assert_eq!(
+            read_mode(&mut flash, features),
+            (opcodes::DIOR, IoMode::DualIo)
+        );
+        assert_eq!(
+            protocol::read_status2(&mut flash).unwrap() & opcodes::SR2_QE,
+            0
+        );
+
+        // SFDP saying the chip has no QE bit allows quad without setting one
+        let mut ctx = test_context(features);
+        ctx.quad_enable = Some(protocol::QuadEnableMethod::None);
+        let mut buf = [0u8; 4];
+        flash::read(&mut flash, &ctx, 0, &mut buf).unwrap();
+        assert_eq!(flash.last_read(), Some((opcodes::QIOR, IoMode::QuadIo)));
+    }
+
+    #[test]
+    fn test_read_mode_negotiated_once() {
+        // Locked until power cycle, so setting QE fails on the first read
+        let mut flash = DummyFlash::new_default();
+        protocol::write_status12(&mut flash, 0, opcodes::SR2_SRP1).unwrap();
+        let features = Features::FAST_READ | Features::QUAD_IO | Features::QE_SR2;
+        let mut device = SpiFlashDevice::new(flash, test_context(features));
+
+        let mut buf = [0u8; 4];
+        device.read(0, &mut buf).unwrap();
+        assert_eq!(
+            device.master().last_read(),
+            Some((opcodes::FAST_READ, IoMode::Single))
+        );
+
+        // Later reads reuse the mode instead of retrying the QE write
+        device.master().power_cycle();
+        device.read(0x1000, &mut buf).unwrap();
+        assert_eq!(
+            device.master().last_read(),
+            Some((opcodes::FAST_READ, IoMode::Single))
+        );
+
+        // Touching the context negotiates again, now with QE writable
+        device.context_mut();
+        device.read(0x2000, &mut buf).unwrap();
+        assert_eq!(
+            device.master().last_read(),
+            Some((opcodes::QIOR, IoMode::QuadIo))
+        );
+    }
+
+    #[test]
+    fn test_deep_power_down() {
+        let mut flash = DummyFlash::new_default();
+        protocol::deep_power_down(&mut flash).unwrap();
+        assert!(flash.is_powered_down());
+
+        let (mfr, _) = protocol::read_jedec_id(&mut flash).unwrap();
+        assert!(!protocol::is_valid_jedec_manufacturer(mfr));
+
+        assert_eq!(protocol::release_power_down(&mut flash).unwrap(), 0x17);
+        assert!(!flash.is_powered_down());
+        assert_eq!(protocol::read_jedec_id(&mut flash).unwrap(), (0xEF, 0x4018));
+    }
+
+    #[test]
+    fn test_reset_before_probe() {
+        let mut flash = DummyFlash::new_default();
+        protocol::enter_4byte_mode(&mut flash).unwrap();
+        assert!(flash.is_4byte_mode());
+
+        protocol::reset_before_probe(&mut flash).unwrap();
+        assert!(!flash.is_4byte_mode());
+        // Continuous-read mode is ended first
+        assert_eq!(flash.clocks(), &[(16, CsLevel::Asserted)]);
+    }
+
+    /// Master counting transfers to the programmer, one per command or batch
+    struct RoundTrips<M> {
+        inner: M,
+        trips: usize,
+    }
+
+    impl<M: SpiMaster> SpiMaster for RoundTrips<M> {
+        fn features(&self) -> SpiFeatures {
+            self.inner.features()
+        }
+
+        fn max_read_len(&self) -> usize {
+            self.inner.max_read_len()
+        }
+
+        fn max_write_len(&self) -> usize {
+            self.inner.max_write_len()
+        }
+
+        fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> Result<()> {
+            self.trips += 1;
+            self.inner.execute(cmd)
+        }
+
+        fn execute_batch(&mut self, cmds: &mut [SpiCommand<'_>]) -> Result<()> {
+            self.trips += 1;
+            self.inner.execute_batch(cmds)
+        }
+
+        fn delay_us(&mut self, us: u32) {
+            self.inner.delay_us(us)
+        }
+    }
+
+    #[test]
+    fn test_program_and_erase_batch_wren() {
+        let mut master = RoundTrips {
+            inner: DummyFlash::new_default(),
+            trips: 0,
+        };
+
+        // WREN + PP in one batch, then a single RDSR poll
+        protocol::program_page_3b(&mut master, 0x100, &[0x12, 0x34]).unwrap();
+        assert_eq!(master.trips, 2);
+        assert_eq!(&master.inner.data()[0x100..0x102], &[0x12, 0x34]);
+
+        master.trips = 0;
+        protocol::erase_block(
+            &mut master,
+            opcodes::SE_20,
+            0,
+            protocol::CommandAddressing::ThreeByte,
+            0,
+            1,
+        )
+        .unwrap();
+        assert_eq!(master.trips, 2);
+        assert_eq!(master.inner.data()[0x100], 0xFF);
+    }
+
+    #[test]
+    fn test_power_cycle_lock() {
+        let mut flash = DummyFlash::new_default();
+
+        // SRL set: locked until power cycle
+        protocol::write_status12(&mut flash, 0, 0x01).unwrap();
+        assert_eq!(flash.wp_bits().mode(), WpMode::PowerCycle);
+
+        protocol::write_status1(&mut flash, opcodes::SR1_BP0).unwrap();
+        assert!(!flash.protected_range().is_protected());
+
+        flash.power_cycle();
+        protocol::write_status1(&mut flash, opcodes::SR1_BP0).unwrap();
+        assert!(flash.protected_range().is_protected());
+    }
+
+    #[test]
+    fn test_bank_register() {
+        let mut flash = DummyFlash::new_default();
+        protocol::write_bank_register(&mut flash, 0x81).unwrap();
+        assert_eq!(protocol::read_bank_register(&mut flash).unwrap(), 0x81);
+
+        // Volatile, like the Spansion BAR
+        flash.power_cycle();
+        assert_eq!(protocol::read_bank_register(&mut flash).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_config_register() {
+        let mut flash = DummyFlash::new_default();
+
+        // ISSI function register: WREN + WRFR, bits only get set
+        let issi = Features::FUNCTION_REG;
+        protocol::write_config_register(&mut flash, issi, 0x02).unwrap();
+        protocol::write_config_register(&mut flash, issi, 0x10).unwrap();
+        assert_eq!(
+            protocol::read_config_register(&mut flash, issi).unwrap(),
+            0x12
+        );
+
+        // Macronix: CR is the second WRSR byte, SR1 is written back as is.
+        // The dummy keeps that byte in SR2.
+        let macronix = Features::CONFIG_REG;
+        protocol::write_status1(&mut flash, opcodes::SR1_BP0).unwrap();
+        protocol::write_config_register(&mut flash, macronix, 0x40).unwrap();
+        assert_eq!(
+            protocol::read_status1(&mut flash).unwrap(),
+            opcodes::SR1_BP0
+        );
+        assert_eq!(protocol::read_status2(&mut flash).unwrap(), 0x40);
+        // RDCR shares the RDSR3 opcode
+        assert_eq!(
+            protocol::read_config_register(&mut flash, macronix).unwrap(),
+            0
+        );
+
+        assert_eq!(
+            protocol::read_config_register(&mut flash, Features::empty()),
+            Err(Error::ChipNotSupported)
+        );
+        assert_eq!(
+            protocol::write_config_register(&mut flash, Features::empty(), 0),
+            Err(Error::ChipNotSupported)
+        );
+    }
+
+    /// Context for a chip that can also be erased with CE (0xC7)
+    fn chip_erase_context(features: Features) -> FlashContext {
+        let mut ctx = test_context(features);
+        let size = ctx.chip.total_size;
+        ctx.chip
+            .erase_blocks
+            .push(EraseBlock::with_count(opcodes::CE_C7, size, 1));
+        ctx
+    }
+
+    #[test]
+    fn test_chip_erase() {
+        let mut flash = DummyFlash::new_default();
+        flash.data_mut()[..0x100].fill(0x00);
+        let last = flash.data().len() - 1;
+        flash.data_mut()[last] = 0x00;
+        // Refuses CE, unless the SST26 global unprotect clears it
+        protocol::write_status1(&mut flash, opcodes::SR1_BP0).unwrap();
+
+        let ctx = chip_erase_context(Features::SST26_BPR);
+        let mut device = SpiFlashDevice::new(flash, ctx);
+        device.chip_erase().unwrap();
+
+        let (flash, _) = device.into_parts();
+        assert_eq!(flash.erases(), [(opcodes::CE_C7, 0)]);
+        assert!(!flash.protected_range().is_protected());
+        assert!(flash.data().iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn test_chip_erase_unsupported_opcode() {
+        let config = DummyConfig {
+            unsupported_opcodes: &[opcodes::CE_60, opcodes::CE_C7],
+            ..Default::default()
+        };
+        let mut flash = DummyFlash::new(config);
+        flash.data_mut()[..0x100].fill(0x00);
+
+        let mut device = SpiFlashDevice::new(flash, chip_erase_context(Features::empty()));
+        device.chip_erase().unwrap();
+
+        // Erased block by block instead
+        let (flash, _) = device.into_parts();
+        assert!(flash.erases().iter().all(|&(op, _)| op == opcodes::SE_20));
+        assert!(flash.data().iter().all(|&b| b == 0xFF));
+    }
+
+    /// Database holding only the emulated W25Q128FV
+    fn w25q128_db() -> ChipDatabase {
+        let mut db = ChipDatabase::empty();
+        db.load_ron(
+            r#"
+            (
+                vendor: "Winbond",
+                manufacturer_id: 0xEF,
+                chips: [
+                    (
+                        name: "W25Q128FV",
+                        device_id: 0x4018,
+                        total_size: MiB(16),
+                        page_size: 256,
+                        erase_blocks: [
+                            (opcode: 0x20, regions: [(size: KiB(4), count: 4096)]),
+                        ],
+                    ),
+                ],
+            )
+            "#,
+        )
+        .unwrap();
+        db
+    }
+
+    /// Dummy flash without SFDP, so probing relies on the ID commands
+    fn no_sfdp_config() -> DummyConfig {
+        DummyConfig {
+            sfdp: Some(Vec::new()),
+            ..Default::default()
+        }
+    }
+
+    /// Dummy flash without SFDP that doesn't answer RDID unless `rdid`, nor
+    /// the `unsupported` opcodes
+    fn id_flash(rdid: bool, unsupported: &'static [u8]) -> DummyFlash {
+        DummyFlash::new(DummyConfig {
+            rdid,
+            unsupported_opcodes: unsupported,
+            ..no_sfdp_config()
+        })
+    }
+
+    #[test]
+    fn test_write_chunks_to_max_write_len() {
+        let config = DummyConfig {
+            max_write_len: 16,
+            ..Default::default()
+        };
+        let mut flash = DummyFlash::new(config);
+
+        // Start mid-page so the first chunks also have to stop at the
+        // boundary, where a longer program would wrap around
+        let data: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        flash::write(&mut flash, &test_context(Features::empty()), 0xF8, &data).unwrap();
+
+        assert_eq!(flash.data()[0xF8..0xF8 + data.len()], data[..]);
+    }
+
+    #[test]
+    fn test_probe_waits_for_interrupted_operation() {
+        let config = DummyConfig {
+            timing: DummyTiming {
+                erase_us: 100_000,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut flash = DummyFlash::new(config);
+
+        // An erase left running by a previous session
+        protocol::write_enable(&mut flash).unwrap();
+        let mut cmd = SpiCommand::erase_3b(opcodes::SE_20, 0);
+        flash.execute(&mut cmd).unwrap();
+        assert!(flash.is_busy());
+
+        let result = flash::probe_detailed(&mut flash, &w25q128_db()).unwrap();
+
+        assert!(!flash.is_busy());
+        assert_eq!(
+            (result.jedec_manufacturer, result.jedec_device),
+            (0xEF, 0x4018)
+        );
+        assert!(result.from_database);
+    }
+
+    #[test]
+    fn test_probe_with_wip_stuck() {
+        let config = DummyConfig {
+            timing: DummyTiming {
+                chip_erase_us: u32::MAX,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut flash = DummyFlash::new(config);
+
+        // A chip that never leaves the busy state gives up after a bounded
+        // wait instead of the chip erase timeout
+        protocol::write_enable(&mut flash).unwrap();
+        flash
+            .execute(&mut SpiCommand::simple(opcodes::CE_C7))
+            .unwrap();
+        assert_eq!(
+            flash::probe_detailed(&mut flash, &w25q128_db()).unwrap_err(),
+            Error::ChipNotFound
+        );
+        assert!(u32::MAX - flash.busy_us <= 10_000_000);
+
+        // A failing status read doesn't stop the probe either
+        let config = DummyConfig {
+            unsupported_opcodes: &[opcodes::RDSR],
+            ..Default::default()
+        };
+        let mut flash = DummyFlash::new(config);
+        let result = flash::probe_detailed(&mut flash, &w25q128_db()).unwrap();
+        assert_eq!(
+            (result.jedec_manufacturer, result.jedec_device),
+            (0xEF, 0x4018)
+        );
+    }
+
+    #[test]
+    fn test_probe_identifies_chip_in_qpi_mode() {
+        let config = DummyConfig {
+            spi_features: SpiFeatures::QUAD | SpiFeatures::QPI,
+            ..no_sfdp_config()
+        };
+        let mut flash = DummyFlash::new(config);
+        flash
+            .execute(&mut SpiCommand::simple(opcodes::EQIO))
+            .unwrap();
+
+        let result = flash::probe_detailed(&mut flash, &w25q128_db()).unwrap();
+
+        assert_eq!(
+            (result.jedec_manufacturer, result.jedec_device),
+            (0xEF, 0x4018)
+        );
+        assert!(result.from_database);
+        // The chip was left in SPI mode for the rest of the session
+        assert!(!flash.is_qpi_mode());
+    }
+
+    #[test]
+    fn test_probe_skips_qpi_without_programmer_support() {
+        let config = DummyConfig {
+            spi_features: SpiFeatures::QUAD,
+            ..no_sfdp_config()
+        };
+        let mut flash = DummyFlash::new(config);
+        flash
+            .execute(&mut SpiCommand::simple(opcodes::EQIO))
+            .unwrap();
+
+        assert_eq!(
+            flash::probe_detailed(&mut flash, &w25q128_db()).unwrap_err(),
+            Error::ChipNotFound
+        );
+        // No 4-line command got the chip out of QPI mode
+        assert!(flash.is_qpi_mode());
+    }
+
+    #[test]
+    fn test_force_probe_fallbacks() {
+        let db = w25q128_db();
+
+        // Matched by manufacturer and capacity from the REMS ID
+        let mut flash = id_flash(false, &[]);
+        assert_eq!(
+            flash::probe_detailed(&mut flash, &db).unwrap_err(),
+            Error::ChipNotFound
+        );
+        let result = flash::force_probe(&mut flash, &db).unwrap();
+        assert_eq!(result.chip.name, "W25Q128FV");
+        assert_eq!(
+            (result.jedec_manufacturer, result.jedec_device),
+            (0xEF, 0x17)
+        );
+
+        // Woken up by RES and then identified by RDID
+        let mut flash = id_flash(true, &[]);
+        flash.execute(&mut SpiCommand::simple(opcodes::DP)).unwrap();
+        let result = flash::force_probe(&mut flash, &db).unwrap();
+        assert!(!flash.is_powered_down());
+        assert_eq!(
+            (result.jedec_manufacturer, result.jedec_device),
+            (0xEF, 0x4018)
+        );
+
+        // Nothing identifies a chip missing from the database
+        let mut flash = id_flash(false, &[]);
+        assert_eq!(
+            flash::force_probe(&mut flash, &ChipDatabase::empty()).unwrap_err(),
+            Error::ChipNotFound
+        );
+    }
+
+    #[test]
+    fn test_force_probe_skips_failing_method() {
+        // REMS can't be sent, RES and then RDID still identify the chip
+        let mut flash = id_flash(true, &[opcodes::REMS]);
+        flash.execute(&mut SpiCommand::simple(opcodes::DP)).unwrap();
+        let result = flash::force_probe(&mut flash, &w25q128_db()).unwrap();
+        assert_eq!(
+            (result.jedec_manufacturer, result.jedec_device),
+            (0xEF, 0x4018)
+        );
+
+        // With every method failing, the last error is returned
+        let mut flash = id_flash(false, &[opcodes::REMS, opcodes::RES]);
+        assert_eq!(
+            flash::force_probe(&mut flash, &w25q128_db()).unwrap_err(),
+            Error::ChipNotFound
+        );
+    }
+
+    #[test]
+    fn test_io_limit_masks_features() {
+        let flash = || DummyFlash::new_default();
+        let all = flash().features();
+        assert_eq!(IoLimit::new(flash(), None).features(), all);
+        assert_eq!(IoLimit::new(flash(), Some(BusWidth::Quad)).features(), all);
+        assert_eq!(
+            IoLimit::new(flash(), Some(BusWidth::Dual)).features(),
+            SpiFeatures::FOUR_BYTE_ADDR | SpiFeatures::DUAL
+        );
+        assert_eq!(
+            IoLimit::new(flash(), Some(BusWidth::Single)).features(),
+            SpiFeatures::FOUR_BYTE_ADDR
+        );
+    }
+
+    #[test]
+    fn test_chunk_limit_clamps_to_master() {
+        let limited = ChunkLimit::new(DummyFlash::new_default(), Some(1024));
+        assert_eq!(limited.max_read_len(), 1024);
+        assert_eq!(limited.max_write_len(), 256);
+
+        let unlimited = ChunkLimit::new(DummyFlash::new_default(), None);
+        assert_eq!(unlimited.max_read_len(), 4096);
+        assert_eq!(unlimited.max_write_len(), 256);
+
+        let zero = ChunkLimit::new(DummyFlash::new_default(), Some(0));
+        assert_eq!(zero.max_write_len(), 1);
+    }
+
+    #[test]
+    fn test_permanent_lock_guard() {
+        use rflasher_core::wp::{
+            WpError, WriteOptions, read_wp_bits, set_wp_mode, set_wp_range, write_wp_bits,
+        };
+
+        let bit_map = WpRegBitMap::winbond_standard();
+        let mut flash = DummyFlash::new_default();
+
+        for mode in [WpMode::PowerCycle, WpMode::Permanent] {
+            assert_eq!(
+                set_wp_mode(&mut flash, mode, &bit_map, WriteOptions::default()),
+                Err(WpError::PermanentLockRefused)
+            );
+        }
+        let mut bits = WpBits::empty();
+        bits.srl = Some(1);
+        assert_eq!(
+            write_wp_bits(&mut flash, &bits, &bit_map, WriteOptions::default()),
+            Err(WpError::PermanentLockRefused)
+        );
+        assert_eq!(flash.wp_bits().mode(), WpMode::Disabled);
+
+        let options = WriteOptions {
+            allow_permanent: true,
+            ..Default::default()
+        };
+        set_wp_mode(&mut flash, WpMode::Permanent, &bit_map, options).unwrap();
+        assert_eq!(
+            read_wp_bits(&mut flash, &bit_map).unwrap().mode(),
+            WpMode::Permanent
+        );
+
+        // Changing the range keeps the existing lock without needing the option
+        set_wp_range(
+            &mut flash,
+            &WpRange::none(),
+            &bit_map,
+            16 * 1024 * 1024,
+            RangeDecoder::Spi25,
+            WriteOptions::default(),
+        )
+        .unwrap();
+    }
+
+    /// Dummy flash of `size` with 4K, 32K and 64K erases, filled with `fill`
+    fn block_erase_device(size: usize, fill: u8) -> SpiFlashDevice<DummyFlash> {
+        let mut flash = DummyFlash::new(DummyConfig {
+            size,
+            ..Default::default()
+        });
+        flash.data_mut().fill(fill);
+        flash.into_device()
+    }
+
+    #[test]
+    fn test_smart_write_coalesces_dirty_sectors_into_64k_erase() {
+        // Every 4KB sector of the first 64KB block has bits that must go 0 -> 1
+        let mut dev = block_erase_device(1024 * 1024, 0x00);
+        let mut data = vec![0x00; 1024 * 1024];
+        data[..65536].fill(0x5A);
+
+        smart_write(&mut dev, &data, &mut NoProgress).unwrap();
+
+        let (flash, _) = dev.into_parts();
+        assert_eq!(flash.erases(), [(opcodes::BE_D8, 0)]);
+        assert_eq!(flash.data(), data);
+    }
+
+    #[test]
+    fn test_smart_write_uses_small_erases_at_unaligned_edges() {
+        // 64KB of dirty sectors starting one sector into the chip: the first
+        // block is mostly dirty and gets promoted, the spill-over sector doesn't
+        let mut dev = block_erase_device(1024 * 1024, 0x00);
+        let mut data = vec![0x00; 1024 * 1024];
+        data[0x1000..0x11000].fill(0x5A);
+
+        smart_write(&mut dev, &data, &mut NoProgress).unwrap();
+
+        let (flash, _) = dev.into_parts();
+        assert_eq!(
+            flash.erases(),
+            [(opcodes::BE_D8, 0), (opcodes::SE_20, 0x10000)]
+        );
+        assert_eq!(flash.data(), data);
+    }
 }
@@ -3,8 +3,10 @@
 //! This is similar to flashprog's `struct flashctx` which combines
 //! chip information and programmer access into a single handle.
 
+use crate::io::{FlashReader, FlashWriter};
 use rflasher_core::chip::FlashChip;
 use rflasher_core::flash::{FlashContext, FlashDevice, ProbeResult};
+use rflasher_core::otp::OtpLayout;
 use rflasher_core::sfdp::{SfdpInfo, SfdpMismatch};
 use rflasher_core::wp::{WpConfig, WpMode, WpRange, WpResult, WriteOptions};
 
@@ -31,6 +33,9 @@ pub struct ChipInfo {
     pub sfdp: Option<SfdpInfo>,
     /// Mismatches between SFDP and database
     pub mismatches: Vec<SfdpMismatch>,
+    /// Whether operations use the SFDP erase blocks instead of the ones in
+    /// `chip` (see `OpenOptions::prefer_sfdp`)
+    pub sfdp_erase_blocks: bool,
 }
 
 impl From<&FlashContext> for ChipInfo {
@@ -46,6 +51,7 @@ impl From<&FlashContext> for ChipInfo {
             from_database: true,
             sfdp: None,
             mismatches: Vec::new(),
+            sfdp_erase_blocks: false,
         }
     }
 }
@@ -63,6 +69,7 @@ impl From<ProbeResult> for ChipInfo {
             from_database: result.from_database,
             sfdp: result.sfdp,
             mismatches: result.mismatches,
+            sfdp_erase_blocks: false,
         }
     }
 }
@@ -81,6 +88,8 @@ pub struct FlashHandle {
     device: Box<dyn FlashDevice>,
     /// Chip information (only available for SPI programmers where we probed)
     chip_info: Option<ChipInfo>,
+    /// Programmer-specific details for display, as (label, value) pairs
+    programmer_info: Vec<(&'static str, String)>,
 }
 
 impl FlashHandle {
@@ -89,6 +98,7 @@ impl FlashHandle {
         Self {
             device,
             chip_info: Some(chip_info),
+            programmer_info: Vec::new(),
         }
     }
 
@@ -97,9 +107,23 @@ impl FlashHandle {
         Self {
             device,
             chip_info: None,
+            programmer_info: Vec::new(),
         }
     }
 
+    /// Attach a programmer-specific detail (e.g. the MTD device type)
+    pub(crate) fn with_programmer_info(mut self, label: &'static str, value: String) -> Self {
+        self.programmer_info.push((label, value));
+        self
+    }
+
+    /// Programmer-specific details reported at open time
+    ///
+    /// Empty for programmers that don't report anything beyond the chip.
+    pub fn programmer_info(&self) -> &[(&'static str, String)] {
+        &self.programmer_info
+    }
+
     /// Get chip information, if available
     ///
     /// Returns `Some` for SPI programmers where we successfully probed the chip.
@@ -150,6 +174,20 @@ impl FlashHandle {
         self.device.as_mut()
     }
 
+    /// Get a [`std::io::Read`] + [`std::io::Seek`] adapter over the flash
+    pub fn reader(&mut self) -> FlashReader<'_> {
+        FlashReader::new(self)
+    }
+
+    /// Get a [`std::io::Write`] + [`std::io::Seek`] adapter over the flash
+    ///
+    /// Writes are buffered and go through the smart-write path, erasing
+    /// only what changes. Buffered data is written on `flush()`, on seek and
+    /// on drop; see [`FlashWriter`].
+    pub fn writer(&mut self) -> FlashWriter<'_> {
+        FlashWriter::new(self)
+    }
+
     /// Search for and read FMAP layout from flash
     ///
     /// This uses a binary search strategy (checking power-of-2 aligned offsets)
@@ -224,6 +262,56 @@ impl FlashHandle {
     }
 }
 
+// =============================================================================
+// Security Register (OTP) Support
+// =============================================================================
+
+impl FlashHandle {
+    /// Get the security register layout, if the chip has OTP registers
+    pub fn otp_layout(&self) -> Option<OtpLayout> {
+        self.device.otp_layout()
+    }
+
+    /// Read from a security register
+    pub fn read_otp(
+        &mut self,
+        register: u8,
+        offset: u32,
+        buf: &mut [u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.device
+            .read_otp(register, offset, buf)
+            .map_err(Into::into)
+    }
+
+    /// Program a security register (must be erased first)
+    pub fn write_otp(
+        &mut self,
+        register: u8,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.device
+            .write_otp(register, offset, data)
+            .map_err(Into::into)
+    }
+
+    /// Erase a security register
+    pub fn erase_otp(&mut self, register: u8) -> Result<(), Box<dyn std::error::Error>> {
+        self.device.erase_otp(register).map_err(Into::into)
+    }
+
+    /// Check whether a security register is locked
+    pub fn is_otp_locked(&mut self, register: u8) -> Result<bool, Box<dyn std::error::Error>> {
+        self.device.is_otp_locked(register).map_err(Into::into)
+    }
+
+    /// Permanently lock a security register
+    pub fn lock_otp(&mut self, register: u8) -> Result<(), Box<dyn std::error::Error>> {
+        self.device.lock_otp(register).map_err(Into::into)
+    }
+}
+
 /// Implement FmapSearchable for FlashHandle to enable generic FMAP search
 impl rflasher_core::layout::FmapSearchable for FlashHandle {
     fn size(&self) -> u32 {
@@ -0,0 +1,300 @@
+//! `std::io` adapters for FlashHandle
+//!
+//! [`FlashReader`] and [`FlashWriter`] let flash be used like a file, e.g.
+//! with `io::copy` or parsers that take a `Read + Seek`. Both track their
+//! own position, starting at offset 0.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use rflasher_core::flash::unified::{NoProgress, smart_write_region};
+
+use crate::handle::FlashHandle;
+
+/// Buffered writes are flushed in chunks aligned to this size, so each
+/// erase block is read, erased and programmed once rather than per write
+const WRITE_FLUSH_SIZE: usize = 64 * 1024;
+
+/// Resolve a seek against the current position and flash size
+fn seek_position(pos: u64, size: u64, to: SeekFrom) -> io::Result<u64> {
+    let (base, offset) = match to {
+        SeekFrom::Start(offset) => return Ok(offset),
+        SeekFrom::End(offset) => (size, offset),
+        SeekFrom::Current(offset) => (pos, offset),
+    };
+    base.checked_add_signed(offset).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "seek to a negative or overflowing position",
+        )
+    })
+}
+
+/// Reader over the flash contents
+///
+/// Reads past the end of the flash return 0 bytes, like reading past the
+/// end of a file.
+pub struct FlashReader<'a> {
+    handle: &'a mut FlashHandle,
+    pos: u64,
+}
+
+impl<'a> FlashReader<'a> {
+    pub(crate) fn new(handle: &'a mut FlashHandle) -> Self {
+        Self { handle, pos: 0 }
+    }
+}
+
+impl Read for FlashReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.handle.size() as u64;
+        if self.pos >= size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let len = buf.len().min((size - self.pos) as usize);
+        self.handle
+            .read(self.pos as u32, &mut buf[..len])
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for FlashReader<'_> {
+    fn seek(&mut self, to: SeekFrom) -> io::Result<u64> {
+        self.pos = seek_position(self.pos, self.handle.size() as u64, to)?;
+        Ok(self.pos)
+    }
+}
+
+/// Buffered writer to the flash
+///
+/// Writes go through the smart-write path, which only erases and programs
+/// blocks whose contents change. They are buffered and written out in
+/// 64 KiB-aligned chunks, when seeking elsewhere, on [`Write::flush`] and
+/// on drop. Errors during the flush on drop can only be logged, so call
+/// `flush()` to see them. A `write` whose data can't be written out is
+/// rolled back, so it can be retried after fixing the cause.
+///
+/// Writing past the end of the flash fails.
+pub struct FlashWriter<'a> {
+    handle: &'a mut FlashHandle,
+    pos: u64,
+    /// Pending data, to be written at `pos - buf.len()`
+    buf: Vec<u8>,
+}
+
+impl<'a> FlashWriter<'a> {
+    pub(crate) fn new(handle: &'a mut FlashHandle) -> Self {
+        Self {
+            handle,
+            pos: 0,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Write out the first `len` buffered bytes
+    fn write_out(&mut self, len: usize) -> io::Result<()> {
+        let start = self.pos - self.buf.len() as u64;
+        smart_write_region(
+            self.handle.as_device_mut(),
+            start as u32,
+            &self.buf[..len],
+            &mut NoProgress,
+        )
+        .map_err(|e| io::Error::other(e.to_string()))?;
+        self.buf.drain(..len);
+        Ok(())
+    }
+}
+
+impl Write for FlashWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        let size = self.handle.size() as u64;
+        if self.pos >= size {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                "write past the end of the flash",
+            ));
+        }
+
+        let len = data.len().min((size - self.pos) as usize);
+        self.buf.extend_from_slice(&data[..len]);
+        self.pos += len as u64;
+
+        // Write out whole aligned chunks, keeping the partial one buffered
+        let aligned_end = self.pos - self.pos % WRITE_FLUSH_SIZE as u64;
+        let start = self.pos - self.buf.len() as u64;
+        if aligned_end > start
+            && self.buf.len() >= WRITE_FLUSH_SIZE
+            && let Err(e) = self.write_out((aligned_end - start) as usize)
+        {
+            // Nothing of this write was accepted, so don't keep it buffered
+            self.buf.truncate(self.buf.len() - len);
+            self.pos -= len as u64;
+            return Err(e);
+        }
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.write_out(self.buf.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl Seek for FlashWriter<'_> {
+    fn seek(&mut self, to: SeekFrom) -> io::Result<u64> {
+        let pos = seek_position(self.pos, self.handle.size() as u64, to)?;
+        if pos != self.pos {
+            self.flush()?;
+            self.pos = pos;
+        }
+        Ok(self.pos)
+    }
+}
+
+impl Drop for FlashWriter<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            log::error!("Failed to write buffered flash data: {}", e);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "dummy"))]
+mod tests {
+    use super::*;
+    use crate::open_flash;
+    use rflasher_core::chip::ChipDatabase;
+    use rflasher_core::wp::WriteOptions;
+
+    fn open() -> FlashHandle {
+        open_flash("dummy", &ChipDatabase::new()).unwrap()
+    }
+
+    /// The dummy as a W25Q128FV with its block protection bits
+    fn open_with_wp() -> FlashHandle {
+        let mut db = ChipDatabase::empty();
+        db.load_ron(
+            r#"
+            (
+                vendor: "Winbond",
+                manufacturer_id: 0xEF,
+                chips: [
+                    (
+                        name: "W25Q128FV",
+                        device_id: 0x4018,
+                        total_size: MiB(16),
+                        features: (status_reg_2: true, wp_tb: true, wp_sec: true, wp_cmp: true),
+                        erase_blocks: [
+                            (opcode: 0x20, regions: [(size: KiB(4), count: 4096)]),
+                            (opcode: 0xD8, regions: [(size: KiB(64), count: 256)]),
+                        ],
+                    ),
+                ],
+            )
+            "#,
+        )
+        .unwrap();
+        open_flash("dummy", &db).unwrap()
+    }
+
+    #[test]
+    fn test_reader_seek_and_eof() {
+        let mut handle = open();
+        let size = handle.size() as u64;
+        handle.write(0x1000, b"rflasher").unwrap();
+
+        let mut reader = handle.reader();
+        assert_eq!(reader.seek(SeekFrom::Start(0x1000)).unwrap(), 0x1000);
+        let mut buf = [0u8; 8];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"rflasher");
+
+        assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), size - 2);
+        let mut tail = Vec::new();
+        assert_eq!(reader.read_to_end(&mut tail).unwrap(), 2);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert!(reader.seek(SeekFrom::Current(-(size as i64) - 1)).is_err());
+    }
+
+    #[test]
+    fn test_writer_buffers_until_flush() {
+        let mut handle = open();
+        let data: Vec<u8> = (0..WRITE_FLUSH_SIZE + 300).map(|i| i as u8).collect();
+
+        {
+            let mut writer = handle.writer();
+            writer.seek(SeekFrom::Start(0x100)).unwrap();
+            writer.write_all(&data).unwrap();
+            // The first aligned chunk is written out, the rest is pending
+            assert_eq!(writer.buf.len(), (0x100 + data.len()) % WRITE_FLUSH_SIZE);
+            writer.seek(SeekFrom::End(-4)).unwrap();
+            writer.write_all(b"tail").unwrap();
+            assert!(writer.write(b"!").is_err());
+        }
+
+        let mut readback = vec![0u8; data.len()];
+        handle.read(0x100, &mut readback).unwrap();
+        assert_eq!(readback, data);
+
+        let mut tail = [0u8; 4];
+        handle.read(handle.size() - 4, &mut tail).unwrap();
+        assert_eq!(&tail, b"tail");
+    }
+
+    #[test]
+    fn test_writer_past_end() {
+        let mut handle = open();
+        let size = handle.size() as u64;
+
+        let mut writer = handle.writer();
+        assert_eq!(
+            writer.seek(SeekFrom::Start(size + 0x10)).unwrap(),
+            size + 0x10
+        );
+        // An empty write succeeds anywhere, a real one fails
+        assert_eq!(writer.write(&[]).unwrap(), 0);
+        assert!(writer.write(b"x").is_err());
+        assert!(writer.buf.is_empty());
+    }
+
+    #[test]
+    fn test_writer_rolls_back_failed_write() {
+        let mut handle = open_with_wp();
+        // Protect the smallest range covering the first flush chunk
+        let protected = handle
+            .get_available_wp_ranges()
+            .into_iter()
+            .filter(|r| r.start == 0 && r.len >= WRITE_FLUSH_SIZE as u32)
+            .min_by_key(|r| r.len)
+            .unwrap();
+        handle
+            .set_wp_range(&protected, WriteOptions::default())
+            .unwrap();
+        let data = vec![0x5A; WRITE_FLUSH_SIZE];
+
+        let mut writer = handle.writer();
+        writer.write_all(&data[..0x100]).unwrap();
+        assert!(writer.write(&data[0x100..]).is_err());
+        // The failed write is neither buffered nor counted
+        assert_eq!(writer.buf.len(), 0x100);
+        assert_eq!(writer.pos, 0x100);
+
+        writer.handle.disable_wp(WriteOptions::default()).unwrap();
+        writer.write_all(&data[0x100..]).unwrap();
+        assert!(writer.buf.is_empty());
+        drop(writer);
+
+        let mut readback = vec![0u8; data.len()];
+        handle.read(0, &mut readback).unwrap();
+        assert_eq!(readback, data);
+    }
+}
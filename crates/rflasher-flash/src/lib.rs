@@ -51,13 +51,20 @@
 //! ```
 
 mod handle;
+mod io;
+#[cfg(feature = "plugin")]
+pub mod plugin;
 mod registry;
+mod trace;
 
 pub use handle::{ChipInfo, FlashHandle};
+pub use io::{FlashReader, FlashWriter};
 pub use registry::{
-    BoxedSpiMaster, ProgrammerInfo, ProgrammerParams, available_programmers, open_flash,
+    BoxedSpiMaster, OpenOptions, ProgrammerInfo, ProgrammerParams, available_programmers,
+    is_opaque_programmer, open_flash, open_flash_with_chip, open_flash_with_options,
     open_spi_programmer, parse_programmer_params, programmer_names_short,
 };
+pub use trace::SpiTrace;
 
 // Re-export core types that CLI needs
 pub use rflasher_core::flash::FlashDevice;
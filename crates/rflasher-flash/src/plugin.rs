@@ -0,0 +1,454 @@
+//! Dynamically loaded SPI programmer plugins
+//!
+//! A plugin is a shared library that drives a programmer rflasher has no
+//! built-in driver for. It is selected with `-p plugin:path=./myprog.so` and
+//! exposes a single C-ABI entry point that fills in a [`RflasherSpiPlugin`]
+//! vtable. rflasher wraps that vtable into a regular `SpiMaster`, so all of the
+//! flash logic (probing, smart write, layouts, write protection) works
+//! unchanged on top of it.
+//!
+//! # C ABI (version 1)
+//!
+//! ```c
+//! #define RFLASHER_SPI_PLUGIN_ABI_VERSION 1
+//!
+//! struct rflasher_spi_command {
+//!     uint8_t opcode;
+//!     uint8_t address_width;      /* 0 (no address), 3 or 4 bytes */
+//!     uint8_t io_mode;            /* RFLASHER_IO_* */
+//!     uint8_t dummy_cycles;
+//!     uint32_t address;           /* valid if address_width != 0 */
+//!     const uint8_t *write_data;  /* bytes sent after the dummy cycles */
+//!     size_t write_len;
+//!     uint8_t *read_buf;          /* bytes read after write_data */
+//!     size_t read_len;
+//! };
+//!
+//! struct rflasher_spi_plugin {
+//!     uint32_t abi_version;       /* must be RFLASHER_SPI_PLUGIN_ABI_VERSION */
+//!     void *ctx;                  /* passed back to every callback */
+//!     uint32_t (*features)(void *ctx);        /* SpiFeatures bits */
+//!     size_t (*max_read_len)(void *ctx);
+//!     size_t (*max_write_len)(void *ctx);
+//!     int32_t (*execute)(void *ctx, struct rflasher_spi_command *cmd);
+//!     void (*delay_us)(void *ctx, uint32_t us);   /* optional */
+//!     void (*destroy)(void *ctx);                 /* optional */
+//! };
+//!
+//! /* Returns 0 on success. `args` is the remaining programmer parameters
+//!  * as "key=value,key=value" (without `path`), never NULL. */
+//! int32_t rflasher_spi_plugin_init(const char *args,
+//!                                  struct rflasher_spi_plugin *out);
+//! ```
+//!
+//! `execute` returns 0 on success or one of the negative `RFLASHER_ERR_*`
+//! codes. The callbacks are never called concurrently, but may be called
+//! from a different thread than the one that ran `rflasher_spi_plugin_init`.
+//! All pointers in a command are only valid for the duration of the call.
+
+use std::ffi::{CString, c_char, c_void};
+
+use rflasher_core::error::{Error as CoreError, Result as CoreResult};
+use rflasher_core::programmer::{SpiFeatures, SpiMaster};
+use rflasher_core::spi::{IoMode, SpiCommand};
+
+/// ABI version implemented by this loader
+pub const RFLASHER_SPI_PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Name of the entry point every plugin must export
+pub const RFLASHER_SPI_PLUGIN_INIT: &[u8] = b"rflasher_spi_plugin_init\0";
+
+/// `io_mode` values in [`RflasherSpiCommand`]
+pub const RFLASHER_IO_SINGLE: u8 = 0;
+/// 1-1-2
+pub const RFLASHER_IO_DUAL_OUT: u8 = 1;
+/// 1-2-2
+pub const RFLASHER_IO_DUAL_IO: u8 = 2;
+/// 1-1-4
+pub const RFLASHER_IO_QUAD_OUT: u8 = 3;
+/// 1-4-4
+pub const RFLASHER_IO_QUAD_IO: u8 = 4;
+/// 4-4-4
+pub const RFLASHER_IO_QPI: u8 = 5;
+
+/// Generic transfer failure
+pub const RFLASHER_ERR_TRANSFER: i32 = -1;
+/// The programmer can't execute this opcode or I/O mode
+pub const RFLASHER_ERR_UNSUPPORTED: i32 = -2;
+/// The transfer timed out
+pub const RFLASHER_ERR_TIMEOUT: i32 = -3;
+
+/// A single SPI transaction as seen by the plugin
+#[repr(C)]
+#[derive(Debug)]
+pub struct RflasherSpiCommand {
+    pub opcode: u8,
+    pub address_width: u8,
+    pub io_mode: u8,
+    pub dummy_cycles: u8,
+    pub address: u32,
+    pub write_data: *const u8,
+    pub write_len: usize,
+    pub read_buf: *mut u8,
+    pub read_len: usize,
+}
+
+/// Vtable filled in by `rflasher_spi_plugin_init`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct RflasherSpiPlugin {
+    pub abi_version: u32,
+    pub ctx: *mut c_void,
+    pub features: Option<unsafe extern "C" fn(ctx: *mut c_void) -> u32>,
+    pub max_read_len: Option<unsafe extern "C" fn(ctx: *mut c_void) -> usize>,
+    pub max_write_len: Option<unsafe extern "C" fn(ctx: *mut c_void) -> usize>,
+    pub execute:
+        Option<unsafe extern "C" fn(ctx: *mut c_void, cmd: *mut RflasherSpiCommand) -> i32>,
+    pub delay_us: Option<unsafe extern "C" fn(ctx: *mut c_void, us: u32)>,
+    pub destroy: Option<unsafe extern "C" fn(ctx: *mut c_void)>,
+}
+
+impl RflasherSpiPlugin {
+    /// An empty vtable for the plugin to fill in
+    fn zeroed() -> Self {
+        Self {
+            abi_version: 0,
+            ctx: std::ptr::null_mut(),
+            features: None,
+            max_read_len: None,
+            max_write_len: None,
+            execute: None,
+            delay_us: None,
+            destroy: None,
+        }
+    }
+}
+
+type PluginInitFn = unsafe extern "C" fn(args: *const c_char, out: *mut RflasherSpiPlugin) -> i32;
+
+fn io_mode_to_raw(mode: IoMode) -> u8 {
+    match mode {
+        IoMode::Single => RFLASHER_IO_SINGLE,
+        IoMode::DualOut => RFLASHER_IO_DUAL_OUT,
+        IoMode::DualIo => RFLASHER_IO_DUAL_IO,
+        IoMode::QuadOut => RFLASHER_IO_QUAD_OUT,
+        IoMode::QuadIo => RFLASHER_IO_QUAD_IO,
+        IoMode::Qpi => RFLASHER_IO_QPI,
+    }
+}
+
+fn status_to_result(status: i32) -> CoreResult<()> {
+    match status {
+        0 => Ok(()),
+        RFLASHER_ERR_TRANSFER => Err(CoreError::SpiTransferFailed),
+        RFLASHER_ERR_UNSUPPORTED => Err(CoreError::OpcodeNotSupported),
+        RFLASHER_ERR_TIMEOUT => Err(CoreError::SpiTimeout),
+        _ => Err(CoreError::ProgrammerError),
+    }
+}
+
+/// `SpiMaster` backed by a plugin vtable
+pub struct PluginSpiMaster {
+    plugin: RflasherSpiPlugin,
+    execute: unsafe extern "C" fn(ctx: *mut c_void, cmd: *mut RflasherSpiCommand) -> i32,
+    features: SpiFeatures,
+    max_read_len: usize,
+    max_write_len: usize,
+    /// Keeps the shared library mapped while the vtable is in use.
+    /// Must be dropped after `destroy` has been called.
+    _library: Option<libloading::Library>,
+}
+
+// SAFETY: the plugin ABI requires callbacks to be callable from any thread
+// as long as they're not called concurrently, which `&mut self` guarantees.
+unsafe impl Send for PluginSpiMaster {}
+
+impl PluginSpiMaster {
+    /// Load a plugin from a shared library
+    ///
+    /// # Arguments
+    /// * `path` - Path to the shared library
+    /// * `args` - Programmer parameters passed to the plugin's init function
+    pub fn load(path: &str, args: &str) -> Result<Self, String> {
+        let args = CString::new(args).map_err(|_| "Plugin arguments contain a NUL byte")?;
+
+        // SAFETY: loading a library runs its initializers; the user
+        // explicitly asked for this library to be loaded.
+        let library = unsafe { libloading::Library::new(path) }
+            .map_err(|e| format!("Failed to load plugin '{}': {}", path, e))?;
+
+        let mut plugin = RflasherSpiPlugin::zeroed();
+        // SAFETY: the symbol type is defined by the plugin ABI
+        let status = unsafe {
+            let init: libloading::Symbol<PluginInitFn> =
+                library.get(RFLASHER_SPI_PLUGIN_INIT).map_err(|e| {
+                    format!(
+                        "'{}' is not an rflasher plugin (missing rflasher_spi_plugin_init): {}",
+                        path, e
+                    )
+                })?;
+            init(args.as_ptr(), &mut plugin)
+        };
+        if status != 0 {
+            return Err(format!(
+                "Plugin '{}' failed to initialize (error {})",
+                path, status
+            ));
+        }
+
+        // SAFETY: the vtable was filled in by the plugin's init function
+        unsafe { Self::from_raw(plugin, Some(library)) }
+    }
+
+    /// Wrap an already initialized plugin vtable
+    ///
+    /// # Safety
+    /// All function pointers in `plugin` must be valid for as long as the
+    /// returned master is alive, and must follow the plugin ABI.
+    unsafe fn from_raw(
+        plugin: RflasherSpiPlugin,
+        library: Option<libloading::Library>,
+    ) -> Result<Self, String> {
+        let release = |plugin: &RflasherSpiPlugin| {
+            if let Some(destroy) = plugin.destroy {
+                // SAFETY: guaranteed by the caller
+                unsafe { destroy(plugin.ctx) };
+            }
+        };
+
+        if plugin.abi_version != RFLASHER_SPI_PLUGIN_ABI_VERSION {
+            release(&plugin);
+            return Err(format!(
+                "Plugin ABI version {} is not supported (expected {})",
+                plugin.abi_version, RFLASHER_SPI_PLUGIN_ABI_VERSION
+            ));
+        }
+
+        let (Some(features), Some(max_read_len), Some(max_write_len), Some(execute)) = (
+            plugin.features,
+            plugin.max_read_len,
+            plugin.max_write_len,
+            plugin.execute,
+        ) else {
+            release(&plugin);
+            return Err(
+                "Plugin vtable is incomplete (features, max_read_len, max_write_len and \
+                 execute are required)"
+                    .into(),
+            );
+        };
+
+        // SAFETY: guaranteed by the caller. The limits are queried once,
+        // since `SpiMaster` reports them through `&self`.
+        let (features, max_read_len, max_write_len) = unsafe {
+            (
+                SpiFeatures::from_bits_truncate(features(plugin.ctx)),
+                max_read_len(plugin.ctx),
+                max_write_len(plugin.ctx),
+            )
+        };
+
+        log::debug!(
+            "Plugin features: {:?}, max read {} bytes, max write {} bytes",
+            features,
+            max_read_len,
+            max_write_len
+        );
+
+        Ok(Self {
+            plugin,
+            execute,
+            features,
+            max_read_len,
+            max_write_len,
+            _library: library,
+        })
+    }
+}
+
+impl Drop for PluginSpiMaster {
+    fn drop(&mut self) {
+        if let Some(destroy) = self.plugin.destroy {
+            // SAFETY: the library is still loaded, it's dropped after this
+            unsafe { destroy(self.plugin.ctx) };
+        }
+    }
+}
+
+impl SpiMaster for PluginSpiMaster {
+    fn features(&self) -> SpiFeatures {
+        self.features
+    }
+
+    fn max_read_len(&self) -> usize {
+        self.max_read_len
+    }
+
+    fn max_write_len(&self) -> usize {
+        self.max_write_len
+    }
+
+    fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> CoreResult<()> {
+        let mut raw = RflasherSpiCommand {
+            opcode: cmd.opcode,
+            address_width: match cmd.address {
+                Some(_) => cmd.address_width.bytes(),
+                None => 0,
+            },
+            io_mode: io_mode_to_raw(cmd.io_mode),
+            dummy_cycles: cmd.dummy_cycles,
+            address: cmd.address.unwrap_or(0),
+            write_data: cmd.write_data.as_ptr(),
+            write_len: cmd.write_data.len(),
+            read_buf: cmd.read_buf.as_mut_ptr(),
+            read_len: cmd.read_buf.len(),
+        };
+
+        // SAFETY: the buffers outlive the call and the vtable was validated
+        status_to_result(unsafe { (self.execute)(self.plugin.ctx, &mut raw) })
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        match self.plugin.delay_us {
+            // SAFETY: the vtable was validated when the plugin was loaded
+            Some(delay_us) => unsafe { delay_us(self.plugin.ctx, us) },
+            None => std::thread::sleep(std::time::Duration::from_micros(us as u64)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rflasher_core::spi::AddressWidth;
+
+    /// State of the mock "C side" plugin
+    #[derive(Default)]
+    struct MockPlugin {
+        opcode: u8,
+        address_width: u8,
+        io_mode: u8,
+        dummy_cycles: u8,
+        address: u32,
+        written: Vec<u8>,
+        delays: Vec<u32>,
+        destroyed: bool,
+    }
+
+    unsafe extern "C" fn mock_features(_ctx: *mut c_void) -> u32 {
+        (SpiFeatures::FOUR_BYTE_ADDR | SpiFeatures::DUAL_IN).bits()
+    }
+
+    unsafe extern "C" fn mock_max_read_len(_ctx: *mut c_void) -> usize {
+        64
+    }
+
+    unsafe extern "C" fn mock_max_write_len(_ctx: *mut c_void) -> usize {
+        32
+    }
+
+    unsafe extern "C" fn mock_execute(ctx: *mut c_void, cmd: *mut RflasherSpiCommand) -> i32 {
+        let state = unsafe { &mut *(ctx as *mut MockPlugin) };
+        let cmd = unsafe { &mut *cmd };
+
+        if cmd.opcode == 0xAB {
+            return RFLASHER_ERR_UNSUPPORTED;
+        }
+
+        state.opcode = cmd.opcode;
+        state.address_width = cmd.address_width;
+        state.io_mode = cmd.io_mode;
+        state.dummy_cycles = cmd.dummy_cycles;
+        state.address = cmd.address;
+        state.written =
+            unsafe { std::slice::from_raw_parts(cmd.write_data, cmd.write_len) }.to_vec();
+
+        let read = unsafe { std::slice::from_raw_parts_mut(cmd.read_buf, cmd.read_len) };
+        for (i, b) in read.iter_mut().enumerate() {
+            *b = (cmd.address as u8).wrapping_add(i as u8);
+        }
+        0
+    }
+
+    unsafe extern "C" fn mock_delay_us(ctx: *mut c_void, us: u32) {
+        let state = unsafe { &mut *(ctx as *mut MockPlugin) };
+        state.delays.push(us);
+    }
+
+    unsafe extern "C" fn mock_destroy(ctx: *mut c_void) {
+        let state = unsafe { &mut *(ctx as *mut MockPlugin) };
+        state.destroyed = true;
+    }
+
+    fn mock_vtable(state: &mut MockPlugin) -> RflasherSpiPlugin {
+        RflasherSpiPlugin {
+            abi_version: RFLASHER_SPI_PLUGIN_ABI_VERSION,
+            ctx: state as *mut MockPlugin as *mut c_void,
+            features: Some(mock_features),
+            max_read_len: Some(mock_max_read_len),
+            max_write_len: Some(mock_max_write_len),
+            execute: Some(mock_execute),
+            delay_us: Some(mock_delay_us),
+            destroy: Some(mock_destroy),
+        }
+    }
+
+    #[test]
+    fn test_plugin_marshals_transaction() {
+        let mut state = MockPlugin::default();
+        let mut master = unsafe { PluginSpiMaster::from_raw(mock_vtable(&mut state), None) }
+            .expect("valid vtable");
+
+        assert_eq!(
+            master.features(),
+            SpiFeatures::FOUR_BYTE_ADDR | SpiFeatures::DUAL_IN
+        );
+        assert_eq!(master.max_read_len(), 64);
+        assert_eq!(master.max_write_len(), 32);
+
+        let mut buf = [0u8; 4];
+        let mut cmd = SpiCommand {
+            opcode: 0x3C,
+            address: Some(0x0102_0304),
+            address_width: AddressWidth::FourByte,
+            io_mode: IoMode::DualOut,
+            dummy_cycles: 8,
+            write_data: &[0xAA, 0x55],
+            read_buf: &mut buf,
+        };
+        master.execute(&mut cmd).unwrap();
+        master.delay_us(42);
+        drop(master);
+
+        assert_eq!(state.opcode, 0x3C);
+        assert_eq!(state.address_width, 4);
+        assert_eq!(state.io_mode, RFLASHER_IO_DUAL_OUT);
+        assert_eq!(state.dummy_cycles, 8);
+        assert_eq!(state.address, 0x0102_0304);
+        assert_eq!(state.written, [0xAA, 0x55]);
+        assert_eq!(buf, [0x04, 0x05, 0x06, 0x07]);
+        assert_eq!(state.delays, [42]);
+        assert!(state.destroyed);
+    }
+
+    #[test]
+    fn test_plugin_error_codes_and_validation() {
+        let mut state = MockPlugin::default();
+        let mut master = unsafe { PluginSpiMaster::from_raw(mock_vtable(&mut state), None) }
+            .expect("valid vtable");
+
+        let mut cmd = SpiCommand::simple(0xAB);
+        assert_eq!(master.execute(&mut cmd), Err(CoreError::OpcodeNotSupported));
+        drop(master);
+
+        let mut bad_version = mock_vtable(&mut state);
+        bad_version.abi_version = 2;
+        assert!(unsafe { PluginSpiMaster::from_raw(bad_version, None) }.is_err());
+
+        let mut incomplete = mock_vtable(&mut state);
+        incomplete.execute = None;
+        state.destroyed = false;
+        assert!(unsafe { PluginSpiMaster::from_raw(incomplete, None) }.is_err());
+        assert!(state.destroyed);
+    }
+}
@@ -4,14 +4,16 @@
 //! It completely hides SpiMaster and OpaqueMaster from the public API.
 
 use crate::handle::{ChipInfo, FlashHandle};
-use rflasher_core::chip::ChipDatabase;
+use crate::trace::SpiTrace;
+use rflasher_core::chip::{ChipDatabase, FlashChip};
 #[allow(unused_imports)] // Used in feature-gated code
 use rflasher_core::flash::FlashDevice;
 use rflasher_core::flash::{
-    HybridFlashDevice, OpaqueFlashDevice, ProbeResult, SpiFlashDevice, probe_detailed,
+    HybridFlashDevice, OpaqueFlashDevice, ProbeResult, SpiFlashDevice, force_probe, probe_detailed,
+    probe_forced,
 };
 use rflasher_core::layout::parse_ifd;
-use rflasher_core::programmer::OpaqueMaster;
+use rflasher_core::programmer::{BusWidth, ChunkLimit, IoLimit, OpaqueMaster, SpiMaster};
 use rflasher_core::sfdp::SfdpMismatch;
 use std::collections::HashMap;
 
@@ -107,21 +109,211 @@ fn parse_speed_khz(s: &str) -> Option<u32> {
     s.parse().ok()
 }
 
+/// How the chip behind an SPI programmer is identified and accessed
+#[derive(Clone, Copy)]
+struct ChipSelect<'a> {
+    db: &'a ChipDatabase,
+    /// Chip chosen by name, used instead of looking up the JEDEC ID
+    forced: Option<&'a FlashChip>,
+    /// Largest SPI transfer for reads and writes, see [`OpenOptions`]
+    chunk_size: Option<usize>,
+    /// Widest bus used for reads, see [`OpenOptions`]
+    io_mode: Option<BusWidth>,
+    /// Software reset the chip before probing, see [`OpenOptions`]
+    reset_before_probe: bool,
+    /// Fall back to other ID commands and SFDP, see [`OpenOptions`]
+    aggressive_probe: bool,
+    /// Use SFDP's erase blocks when the database differs, see [`OpenOptions`]
+    prefer_sfdp: bool,
+    /// Multiplier for erase timeouts, see [`OpenOptions`]
+    erase_timeout_scale: Option<f32>,
+    /// Log every SPI command, see [`OpenOptions`]
+    trace_spi: bool,
+}
+
+impl ChipSelect<'_> {
+    /// Probe the chip, using the forced definition if there is one
+    fn probe<M>(&self, master: &mut M) -> Result<ProbeResult, Box<dyn std::error::Error>>
+    where
+        M: rflasher_core::programmer::SpiMaster + ?Sized,
+    {
+        if self.reset_before_probe {
+            log::info!("Resetting the chip before probing");
+            rflasher_core::protocol::reset_before_probe(master)?;
+        }
+
+        let Some(chip) = self.forced else {
+            if self.aggressive_probe {
+                return Ok(force_probe(master, self.db)?);
+            }
+            return Ok(probe_detailed(master, self.db)?);
+        };
+
+        log::info!("Using forced chip definition {} {}", chip.vendor, chip.name);
+        let result = probe_forced(master, chip)?;
+
+        // A wrong size would silently truncate or wrap reads and writes
+        if let Some(sfdp) = &result.sfdp
+            && sfdp.total_size() != chip.total_size as u64
+        {
+            return Err(format!(
+                "Forced chip {} is {} bytes, but the chip's SFDP reports {} bytes",
+                chip.name,
+                chip.total_size,
+                sfdp.total_size()
+            )
+            .into());
+        }
+
+        Ok(result)
+    }
+
+    /// Whether the SFDP erase blocks replace the database's for this chip
+    fn uses_sfdp_erase_blocks(&self, result: &ProbeResult) -> bool {
+        self.prefer_sfdp && result.sfdp.is_some() && result.has_erase_mismatches()
+    }
+
+    /// Create the context for a probed chip, with erase timeouts extended
+    /// by the chip's SFDP erase times and scaled by `erase_timeout_scale`,
+    /// and the Quad Enable method from SFDP if it reports one
+    ///
+    /// With `prefer_sfdp`, the erase blocks come from SFDP if they differ
+    /// from the database's.
+    fn flash_context(&self, result: &ProbeResult) -> rflasher_core::flash::FlashContext {
+        let chip = match result.chip_with_sfdp_erase_blocks() {
+            Some(chip) if self.uses_sfdp_erase_blocks(result) => {
+                log::info!("Using the erase blocks reported by SFDP instead of the database's");
+                chip
+            }
+            _ => {
+                if result.sfdp.is_some() && result.has_erase_mismatches() {
+                    log::warn!(
+                        "Using the database's erase blocks, pass --prefer-sfdp to use SFDP's"
+                    );
+                }
+                result.chip.clone()
+            }
+        };
+        let mut ctx = result.context_for(chip);
+        if let Some(scale) = self.erase_timeout_scale {
+            log::info!("Scaling erase timeouts by {}", scale);
+            ctx.erase_timeouts.scale = scale;
+        }
+        ctx
+    }
+
+    /// Chip information for a probed chip, see [`ChipInfo::sfdp_erase_blocks`]
+    fn chip_info(&self, result: ProbeResult) -> ChipInfo {
+        let sfdp_erase_blocks = self.uses_sfdp_erase_blocks(&result);
+        ChipInfo {
+            sfdp_erase_blocks,
+            ..ChipInfo::from(result)
+        }
+    }
+
+    /// Warn that the chunk size and I/O mode can't be applied to a
+    /// programmer that doesn't transfer data through plain SPI commands
+    fn warn_spi_options_ignored(&self, programmer: &str) {
+        if let Some(chunk_size) = self.chunk_size {
+            log::warn!(
+                "Ignoring chunk size {}: {} uses its own transfer sizes",
+                chunk_size,
+                programmer
+            );
+        }
+        if let Some(io_mode) = self.io_mode {
+            log::warn!(
+                "Ignoring I/O mode {:?}: {} chooses its own read commands",
+                io_mode,
+                programmer
+            );
+        }
+    }
+
+    /// Warn that erase timeouts can't be changed on a programmer that
+    /// erases on its own
+    fn warn_erase_timeout_ignored(&self, programmer: &str) {
+        if let Some(scale) = self.erase_timeout_scale {
+            log::warn!(
+                "Ignoring erase timeout scale {}: {} times erases itself",
+                scale,
+                programmer
+            );
+        }
+    }
+
+    /// Warn that SPI commands can't be traced on a programmer without an
+    /// SPI bus
+    fn warn_trace_ignored(&self, programmer: &str) {
+        if self.trace_spi {
+            log::warn!(
+                "Ignoring --trace-spi: {} doesn't send SPI commands",
+                programmer
+            );
+        }
+    }
+
+    /// Warn that a forced chip can't be used with an opaque programmer
+    fn warn_forced_ignored(&self, programmer: &str) {
+        if let Some(chip) = self.forced {
+            log::warn!(
+                "Ignoring --chip {}: {} doesn't expose the SPI bus, so the chip can't be chosen",
+                chip.name,
+                programmer
+            );
+        }
+    }
+}
+
+/// Resolve a chip name given with `--chip` and check it is usable
+fn find_forced_chip<'a>(
+    db: &'a ChipDatabase,
+    name: &str,
+) -> Result<&'a FlashChip, Box<dyn std::error::Error>> {
+    let chip = db.find_by_name(name).ok_or_else(|| {
+        format!(
+            "Unknown chip '{}'. Use list-chips to see the supported chips.",
+            name
+        )
+    })?;
+
+    if !chip.total_size.is_power_of_two() || chip.total_size < 4096 {
+        return Err(format!(
+            "Chip {} has an implausible size of {} bytes",
+            chip.name, chip.total_size
+        )
+        .into());
+    }
+
+    Ok(chip)
+}
+
 /// Common probe and create handle logic for SPI programmers
 fn probe_and_create_handle<M>(
     master: M,
-    db: &ChipDatabase,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>>
 where
     M: rflasher_core::programmer::SpiMaster + 'static,
 {
-    let mut master = master;
-    let result = probe_detailed(&mut master, db)?;
+    let master = SpiTrace::new(master, chips.trace_spi);
+    let mut master = ChunkLimit::new(IoLimit::new(master, chips.io_mode), chips.chunk_size);
+    if let Some(io_mode) = chips.io_mode {
+        log::info!("Limiting reads to {:?} I/O", io_mode);
+    }
+    if chips.chunk_size.is_some() {
+        log::info!(
+            "Using chunk size of {} bytes for reads, {} bytes for writes",
+            master.max_read_len(),
+            master.max_write_len()
+        );
+    }
+    let result = chips.probe(&mut master)?;
 
     log_probe_result(&result);
 
-    let chip_info = ChipInfo::from(result);
-    let ctx = rflasher_core::flash::FlashContext::new(chip_info.chip.clone().unwrap());
+    let ctx = chips.flash_context(&result);
+    let chip_info = chips.chip_info(result);
     let device = SpiFlashDevice::new(master, ctx);
     Ok(FlashHandle::with_chip_info(Box::new(device), chip_info))
 }
@@ -179,6 +371,18 @@ pub fn parse_programmer_params(s: &str) -> Result<ProgrammerParams, Box<dyn std:
     })
 }
 
+/// Check whether a programmer specification names an opaque programmer
+///
+/// Opaque programmers (internal in hardware sequencing mode, linux_mtd) only
+/// expose address-based read/write/erase, so raw SPI commands such as RDSFDP
+/// can't be sent through them.
+pub fn is_opaque_programmer(programmer: &str) -> bool {
+    let name = programmer
+        .split_once(':')
+        .map_or(programmer, |(name, _)| name);
+    matches!(name, "internal" | "linux_mtd" | "linux-mtd" | "mtd")
+}
+
 /// A boxed SPI master for use with the REPL
 pub type BoxedSpiMaster = Box<dyn rflasher_core::programmer::SpiMaster + Send>;
 
@@ -204,8 +408,11 @@ pub fn open_spi_programmer(programmer: &str) -> Result<BoxedSpiMaster, Box<dyn s
 
         #[cfg(feature = "ch341a")]
         "ch341a" | "ch341a_spi" => {
+            use rflasher_ch341a::{parse_options, Ch341a};
             log::info!("Opening CH341A programmer for REPL...");
-            let master = rflasher_ch341a::Ch341a::open().map_err(|e| {
+            let options = params.as_option_pairs();
+            let config = parse_options(&options).map_err(|e| format!("Invalid CH341A parameters: {}", e))?;
+            let master = Ch341a::open_with_config(config).map_err(|e| {
                 format!(
                     "Failed to open CH341A: {}\nMake sure the device is connected and you have permissions.",
                     e
@@ -375,6 +582,14 @@ pub fn open_spi_programmer(programmer: &str) -> Result<BoxedSpiMaster, Box<dyn s
             Ok(Box::new(master))
         }
 
+        #[cfg(feature = "plugin")]
+        "plugin" => {
+            log::info!("Opening plugin programmer for REPL...");
+            let (path, args) = plugin_params(&params)?;
+            let master = crate::plugin::PluginSpiMaster::load(path, &args)?;
+            Ok(Box::new(master))
+        }
+
         // Internal and MTD are opaque-only or not SPI-based
         #[cfg(feature = "internal")]
         "internal" => {
@@ -420,50 +635,152 @@ pub fn open_spi_programmer(programmer: &str) -> Result<BoxedSpiMaster, Box<dyn s
 pub fn open_flash(
     programmer: &str,
     db: &ChipDatabase,
+) -> Result<FlashHandle, Box<dyn std::error::Error>> {
+    open_flash_with_chip(programmer, db, None)
+}
+
+/// Open a programmer and use the named chip instead of probing for one
+///
+/// `chip` is looked up by name or alias in `db`. The JEDEC ID is still
+/// read and a mismatch with the chip only warned about, so this works for
+/// chips with a missing, generic or shared ID. With `None` this is the same
+/// as [`open_flash`].
+pub fn open_flash_with_chip(
+    programmer: &str,
+    db: &ChipDatabase,
+    chip: Option<&str>,
+) -> Result<FlashHandle, Box<dyn std::error::Error>> {
+    open_flash_with_options(
+        programmer,
+        db,
+        OpenOptions {
+            chip,
+            ..Default::default()
+        },
+    )
+}
+
+/// Options for [`open_flash_with_options`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenOptions<'a> {
+    /// Chip name or alias to use instead of the probed one, as for
+    /// [`open_flash_with_chip`]
+    pub chip: Option<&'a str>,
+    /// Largest SPI transfer to use for flash reads and writes
+    ///
+    /// Clamped to the programmer's own limits. Programmers with their own
+    /// bulk transfer path (Dediprog, sunxi FEL, opaque programmers) ignore
+    /// it with a warning.
+    pub chunk_size: Option<usize>,
+    /// Widest bus to use for flash reads
+    ///
+    /// By default the fastest read mode supported by both the programmer
+    /// and the chip is used. Ignored with a warning by the same programmers
+    /// as `chunk_size`.
+    pub io_mode: Option<BusWidth>,
+    /// Software reset the chip before reading its JEDEC ID
+    ///
+    /// For chips left in QPI or 4-byte mode by a previous session. Chips
+    /// without software reset ignore it.
+    pub reset_before_probe: bool,
+    /// Use the erase blocks SFDP reports when they differ from the database's
+    ///
+    /// For chip revisions whose database entry is stale. Without it, the
+    /// database wins and the differences are only warned about.
+    pub prefer_sfdp: bool,
+    /// Identify chips that don't answer RDID by REMS, RES or SFDP
+    ///
+    /// For odd or partially bricked chips, see
+    /// [`rflasher_core::flash::force_probe`]. Ignored with a forced chip.
+    pub aggressive_probe: bool,
+    /// Multiplier for block erase timeouts
+    ///
+    /// Applied on top of the built-in timeouts, which are extended to the
+    /// chip's SFDP maximum erase times when it has them. For slow or worn
+    /// chips that time out mid-erase. Opaque programmers ignore it with a
+    /// warning.
+    pub erase_timeout_scale: Option<f32>,
+    /// Log every SPI command sent to the programmer at trace level
+    ///
+    /// Shows the opcode, address, I/O mode and the lengths of the write
+    /// and read data, with the data itself cut short. Opaque programmers
+    /// ignore it with a warning.
+    pub trace_spi: bool,
+}
+
+/// Open a programmer with the given [`OpenOptions`]
+pub fn open_flash_with_options(
+    programmer: &str,
+    db: &ChipDatabase,
+    options: OpenOptions<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     let params = parse_programmer_params(programmer)?;
+    let forced = options
+        .chip
+        .map(|name| find_forced_chip(db, name))
+        .transpose()?;
+    let chips = ChipSelect {
+        db,
+        forced,
+        chunk_size: options.chunk_size,
+        io_mode: options.io_mode,
+        reset_before_probe: options.reset_before_probe,
+        aggressive_probe: options.aggressive_probe,
+        prefer_sfdp: options.prefer_sfdp,
+        erase_timeout_scale: options.erase_timeout_scale,
+        trace_spi: options.trace_spi,
+    };
 
     match params.name.as_str() {
         #[cfg(feature = "dummy")]
-        "dummy" => open_dummy(db),
+        "dummy" => open_dummy(chips),
 
         #[cfg(feature = "ch341a")]
-        "ch341a" | "ch341a_spi" => open_ch341a(&params, db),
+        "ch341a" | "ch341a_spi" => open_ch341a(&params, chips),
 
         #[cfg(feature = "ch347")]
-        "ch347" | "ch347_spi" => open_ch347(&params, db),
+        "ch347" | "ch347_spi" => open_ch347(&params, chips),
 
         #[cfg(feature = "dediprog")]
-        "dediprog" | "dediprog_spi" => open_dediprog(&params, db),
+        "dediprog" | "dediprog_spi" => open_dediprog(&params, chips),
 
         #[cfg(feature = "serprog")]
-        "serprog" => open_serprog(&params, db),
+        "serprog" => open_serprog(&params, chips),
 
         #[cfg(feature = "ftdi")]
-        "ftdi" | "ft2232_spi" | "ft4232_spi" => open_ftdi(&params, db),
+        "ftdi" | "ft2232_spi" | "ft4232_spi" => open_ftdi(&params, chips),
 
         #[cfg(feature = "ft4222")]
-        "ft4222" | "ft4222_spi" => open_ft4222(&params, db),
+        "ft4222" | "ft4222_spi" => open_ft4222(&params, chips),
 
         #[cfg(feature = "linux-spi")]
-        "linux_spi" | "linux-spi" | "spidev" => open_linux_spi(&params, db),
+        "linux_spi" | "linux-spi" | "spidev" => open_linux_spi(&params, chips),
 
         #[cfg(feature = "linux-mtd")]
-        "linux_mtd" | "linux-mtd" | "mtd" => open_linux_mtd(&params),
+        "linux_mtd" | "linux-mtd" | "mtd" => {
+            chips.warn_forced_ignored("linux_mtd");
+            chips.warn_spi_options_ignored("linux_mtd");
+            chips.warn_erase_timeout_ignored("linux_mtd");
+            chips.warn_trace_ignored("linux_mtd");
+            open_linux_mtd(&params)
+        }
 
         #[cfg(feature = "linux-gpio")]
         "linux_gpio_spi" | "linux-gpio-spi" | "linux_gpio" | "linux-gpio" => {
-            open_linux_gpio_spi(&params, db)
+            open_linux_gpio_spi(&params, chips)
         }
 
         #[cfg(feature = "internal")]
-        "internal" => open_internal(&params, db),
+        "internal" => open_internal(&params, chips),
 
         #[cfg(feature = "raiden")]
-        "raiden_debug_spi" | "raiden" | "raiden_spi" => open_raiden(&params, db),
+        "raiden_debug_spi" | "raiden" | "raiden_spi" => open_raiden(&params, chips),
 
         #[cfg(feature = "sunxi-fel")]
-        "sunxi_fel" | "sunxi-fel" | "fel" => open_sunxi_fel(&params, db),
+        "sunxi_fel" | "sunxi-fel" | "fel" => open_sunxi_fel(&params, chips),
+
+        #[cfg(feature = "plugin")]
+        "plugin" => open_plugin(&params, chips),
 
         _ => Err(format!("Unknown programmer: {}", params.name).into()),
     }
@@ -495,32 +812,39 @@ fn get_flash_size_from_ifd(
 // These handle the details of each programmer type and return a FlashHandle
 
 #[cfg(feature = "dummy")]
-fn open_dummy(db: &ChipDatabase) -> Result<FlashHandle, Box<dyn std::error::Error>> {
+fn open_dummy(chips: ChipSelect<'_>) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     let master = rflasher_dummy::DummyFlash::new_default();
-    probe_and_create_handle(master, db)
+    probe_and_create_handle(master, chips)
 }
 
 #[cfg(feature = "ch341a")]
 fn open_ch341a(
-    _params: &ProgrammerParams,
-    db: &ChipDatabase,
+    params: &ProgrammerParams,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
+    use rflasher_ch341a::{Ch341a, parse_options};
+
     log::info!("Opening CH341A programmer...");
 
-    let master = rflasher_ch341a::Ch341a::open().map_err(|e| {
+    let options = params.as_option_pairs();
+
+    let config =
+        parse_options(&options).map_err(|e| format!("Invalid CH341A parameters: {}", e))?;
+
+    let master = Ch341a::open_with_config(config).map_err(|e| {
         format!(
             "Failed to open CH341A: {}\nMake sure the device is connected and you have permissions.",
             e
         )
     })?;
 
-    probe_and_create_handle(master, db)
+    probe_and_create_handle(master, chips)
 }
 
 #[cfg(feature = "ch347")]
 fn open_ch347(
     params: &ProgrammerParams,
-    db: &ChipDatabase,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     use rflasher_ch347::{Ch347, parse_options};
 
@@ -537,24 +861,25 @@ fn open_ch347(
         )
     })?;
 
-    probe_and_create_handle(master, db)
+    probe_and_create_handle(master, chips)
 }
 
 #[cfg(feature = "dediprog")]
 fn open_dediprog(
     params: &ProgrammerParams,
-    db: &ChipDatabase,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     use rflasher_dediprog::{Dediprog, parse_options};
 
     log::info!("Opening Dediprog programmer...");
+    chips.warn_spi_options_ignored("dediprog");
 
     let options = params.as_option_pairs();
 
     let config =
         parse_options(&options).map_err(|e| format!("Invalid Dediprog parameters: {}", e))?;
 
-    let mut master = Dediprog::open_with_config(config).map_err(|e| {
+    let master = Dediprog::open_with_config(config).map_err(|e| {
         format!(
             "Failed to open Dediprog: {}\n\
              Make sure the device is connected and you have USB permissions.",
@@ -569,13 +894,17 @@ fn open_dediprog(
     );
 
     // Probe the flash chip via SpiMaster
-    let result = probe_detailed(&mut master, db)?;
+    let mut master = SpiTrace::new(master, chips.trace_spi);
+    let result = chips.probe(&mut master)?;
     log_probe_result(&result);
-    let chip_info = ChipInfo::from(result);
-    let ctx = rflasher_core::flash::FlashContext::new(chip_info.chip.clone().unwrap());
+    let ctx = chips.flash_context(&result);
+    let chip_info = chips.chip_info(result);
 
     // Set flash size so OpaqueMaster bulk read/write knows the bounds
-    master.set_flash_size(ctx.total_size() as u32);
+    master.inner_mut().set_flash_size(ctx.total_size() as u32);
+    // Bulk reads only use dual/quad modes the chip supports, with QE set
+    let read_io_mode = rflasher_core::flash::read_io_mode(&mut master, &ctx);
+    master.inner_mut().set_read_io_mode(read_io_mode);
 
     // Use HybridFlashDevice: OpaqueMaster for fast bulk read/write (CMD_READ/CMD_WRITE),
     // SpiMaster for erase, status register access, and write protection
@@ -586,7 +915,7 @@ fn open_dediprog(
 #[cfg(feature = "serprog")]
 fn open_serprog(
     params: &ProgrammerParams,
-    db: &ChipDatabase,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     use rflasher_serprog::SerprogConnection;
 
@@ -619,10 +948,25 @@ fn open_serprog(
         parsed
     });
     let cs: Option<u8> = params.params.get("cs").and_then(|v| v.parse().ok());
+    let reconnect: u32 = params
+        .params
+        .get("reconnect")
+        .map(|v| {
+            v.parse()
+                .map_err(|_| format!("Invalid serprog reconnect value '{}'", v))
+        })
+        .transpose()?
+        .unwrap_or(0);
 
     // Open connection and create device with concrete type
     match conn {
         SerprogConnection::Serial { device, baud } => {
+            if reconnect > 0 {
+                log::warn!(
+                    "Ignoring reconnect={}: only TCP connections can reconnect",
+                    reconnect
+                );
+            }
             let transport = rflasher_serprog::SerialTransport::open(&device, baud)
                 .map_err(|e| format!("Failed to open serial port {}: {}", device, e))?;
             let mut serprog = rflasher_serprog::Serprog::new(transport)
@@ -640,13 +984,14 @@ fn open_serprog(
                     .map_err(|e| format!("Failed to set chip select: {}", e))?;
             }
 
-            probe_and_create_handle(serprog, db)
+            probe_and_create_handle(serprog, chips)
         }
         SerprogConnection::Tcp { host, port } => {
             let transport = rflasher_serprog::TcpTransport::connect(&host, port)
                 .map_err(|e| format!("Failed to connect to {}:{}: {}", host, port, e))?;
             let mut serprog = rflasher_serprog::Serprog::new(transport)
                 .map_err(|e| format!("Failed to initialize serprog: {}", e))?;
+            serprog.set_reconnect_retries(reconnect);
 
             if let Some(speed_khz) = spispeed {
                 // Convert kHz to Hz
@@ -660,7 +1005,7 @@ fn open_serprog(
                     .map_err(|e| format!("Failed to set chip select: {}", e))?;
             }
 
-            probe_and_create_handle(serprog, db)
+            probe_and_create_handle(serprog, chips)
         }
     }
 }
@@ -668,7 +1013,7 @@ fn open_serprog(
 #[cfg(feature = "ftdi")]
 fn open_ftdi(
     params: &ProgrammerParams,
-    db: &ChipDatabase,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     use rflasher_ftdi::{Ftdi, parse_options};
 
@@ -688,13 +1033,13 @@ fn open_ftdi(
         )
     })?;
 
-    probe_and_create_handle(master, db)
+    probe_and_create_handle(master, chips)
 }
 
 #[cfg(feature = "ft4222")]
 fn open_ft4222(
     params: &ProgrammerParams,
-    db: &ChipDatabase,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     use rflasher_ft4222::{Ft4222, parse_options};
 
@@ -718,13 +1063,13 @@ fn open_ft4222(
         master.actual_speed_khz()
     );
 
-    probe_and_create_handle(master, db)
+    probe_and_create_handle(master, chips)
 }
 
 #[cfg(feature = "linux-spi")]
 fn open_linux_spi(
     params: &ProgrammerParams,
-    db: &ChipDatabase,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     use rflasher_linux_spi::{LinuxSpi, parse_options};
 
@@ -744,7 +1089,7 @@ fn open_linux_spi(
         )
     })?;
 
-    probe_and_create_handle(master, db)
+    probe_and_create_handle(master, chips)
 }
 
 #[cfg(feature = "linux-mtd")]
@@ -777,15 +1122,16 @@ fn open_linux_mtd(params: &ProgrammerParams) -> Result<FlashHandle, Box<dyn std:
         erase_size
     );
 
+    let mtd_type = mtd.info().mtd_type.clone();
     let mut device = OpaqueFlashDevice::new(mtd, flash_size);
     device.set_erase_block_size(erase_size);
-    Ok(FlashHandle::without_chip_info(Box::new(device)))
+    Ok(FlashHandle::without_chip_info(Box::new(device)).with_programmer_info("MTD type", mtd_type))
 }
 
 #[cfg(feature = "linux-gpio")]
 fn open_linux_gpio_spi(
     params: &ProgrammerParams,
-    db: &ChipDatabase,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     use rflasher_linux_gpio::{LinuxGpioSpi, parse_options};
 
@@ -805,13 +1151,13 @@ fn open_linux_gpio_spi(
         )
     })?;
 
-    probe_and_create_handle(master, db)
+    probe_and_create_handle(master, chips)
 }
 
 #[cfg(feature = "internal")]
 fn open_internal(
     params: &ProgrammerParams,
-    db: &ChipDatabase,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     use rflasher_internal::{InternalOptions, InternalProgrammer, SpiMode};
 
@@ -840,9 +1186,13 @@ fn open_internal(
     // Hardware sequencing: opaque operations only
     if programmer.mode() == SpiMode::SoftwareSequencing {
         log::info!("Using SPI mode (swseq allows chip probing)");
-        probe_and_create_handle(programmer, db)
+        probe_and_create_handle(programmer, chips)
     } else {
         log::info!("Using opaque mode (hwseq - no chip probing available)");
+        chips.warn_forced_ignored("internal in hwseq mode");
+        chips.warn_spi_options_ignored("internal in hwseq mode");
+        chips.warn_erase_timeout_ignored("internal in hwseq mode");
+        chips.warn_trace_ignored("internal in hwseq mode");
         let flash_size = get_flash_size_from_ifd(&mut programmer)?;
         log::info!("Flash size: {} bytes (from IFD)", flash_size);
 
@@ -854,7 +1204,7 @@ fn open_internal(
 #[cfg(feature = "raiden")]
 fn open_raiden(
     params: &ProgrammerParams,
-    db: &ChipDatabase,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     use rflasher_raiden::{RaidenDebugSpi, parse_options};
 
@@ -874,17 +1224,18 @@ fn open_raiden(
         )
     })?;
 
-    probe_and_create_handle(master, db)
+    probe_and_create_handle(master, chips)
 }
 
 #[cfg(feature = "sunxi-fel")]
 fn open_sunxi_fel(
     _params: &ProgrammerParams,
-    db: &ChipDatabase,
+    chips: ChipSelect<'_>,
 ) -> Result<FlashHandle, Box<dyn std::error::Error>> {
     log::info!("Opening sunxi FEL programmer...");
+    chips.warn_spi_options_ignored("sunxi_fel");
 
-    let mut master = rflasher_sunxi_fel::SunxiFel::open().map_err(|e| {
+    let master = rflasher_sunxi_fel::SunxiFel::open().map_err(|e| {
         format!(
             "Failed to open sunxi FEL device: {}\n\
              Make sure the device is in FEL mode (hold FEL button while plugging in USB)\n\
@@ -896,14 +1247,16 @@ fn open_sunxi_fel(
     log::info!("Connected to: {}", master.soc_name());
 
     // Probe the flash chip via SpiMaster
-    let result = probe_detailed(&mut master, db)?;
+    let mut master = SpiTrace::new(master, chips.trace_spi);
+    let result = chips.probe(&mut master)?;
     log_probe_result(&result);
-    let chip_info = ChipInfo::from(result);
-    let ctx = rflasher_core::flash::FlashContext::new(chip_info.chip.clone().unwrap());
+    let ctx = chips.flash_context(&result);
+    let chip_info = chips.chip_info(result);
 
     // Configure OpaqueMaster with chip info discovered during probe
-    master.set_use_4byte_addr(ctx.total_size() > 16 * 1024 * 1024);
-    master.set_erase_blocks(ctx.chip.erase_blocks().to_vec());
+    let fel = master.inner_mut();
+    fel.set_use_4byte_addr(ctx.total_size() > 16 * 1024 * 1024);
+    fel.set_erase_blocks(ctx.chip.erase_blocks().to_vec());
 
     // Use HybridFlashDevice: OpaqueMaster for fast bulk read/write/erase
     // (batched SPI commands with on-SoC busy-wait), SpiMaster for WP and
@@ -912,6 +1265,38 @@ fn open_sunxi_fel(
     Ok(FlashHandle::with_chip_info(Box::new(device), chip_info))
 }
 
+/// Split plugin parameters into the library path and the arguments passed
+/// on to the plugin (all remaining parameters, as `key=value,...`)
+#[cfg(feature = "plugin")]
+fn plugin_params(params: &ProgrammerParams) -> Result<(&str, String), Box<dyn std::error::Error>> {
+    let path = params
+        .params
+        .get("path")
+        .ok_or("plugin requires a library path (plugin:path=./myprog.so)")?;
+
+    let mut args: Vec<_> = params
+        .params
+        .iter()
+        .filter(|(k, _)| k.as_str() != "path")
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect();
+    args.sort();
+
+    Ok((path, args.join(",")))
+}
+
+#[cfg(feature = "plugin")]
+fn open_plugin(
+    params: &ProgrammerParams,
+    chips: ChipSelect<'_>,
+) -> Result<FlashHandle, Box<dyn std::error::Error>> {
+    let (path, args) = plugin_params(params)?;
+    log::info!("Loading programmer plugin {}...", path);
+
+    let master = crate::plugin::PluginSpiMaster::load(path, &args)?;
+    probe_and_create_handle(master, chips)
+}
+
 // Programmer information and listing
 /// Information about a programmer
 pub struct ProgrammerInfo {
@@ -939,7 +1324,7 @@ pub fn available_programmers() -> Vec<ProgrammerInfo> {
     programmers.push(ProgrammerInfo {
         name: "ch341a",
         aliases: &["ch341a_spi"],
-        description: "CH341A USB SPI programmer (VID:1a86 PID:5512)",
+        description: "CH341A USB SPI programmer (VID:1a86 PID:5512) (spispeed=<khz>,dual=<on|off>)",
     });
 
     #[cfg(feature = "ch347")]
@@ -969,14 +1354,14 @@ pub fn available_programmers() -> Vec<ProgrammerInfo> {
     programmers.push(ProgrammerInfo {
         name: "ftdi",
         aliases: &["ft2232_spi", "ft4232_spi"],
-        description: "FTDI MPSSE programmer (FT2232H/FT4232H/FT232H) (type=<dev>,port=<A-D>)",
+        description: "FTDI MPSSE programmer (FT2232H/FT4232H/FT232H) (type=<dev>,port=<A-D>,divisor=<n>,spispeed=<khz>,csgpiol=<0-3>)",
     });
 
     #[cfg(feature = "ft4222")]
     programmers.push(ProgrammerInfo {
         name: "ft4222",
         aliases: &["ft4222_spi"],
-        description: "FTDI FT4222H USB SPI programmer (spispeed=<khz>,cs=<0-3>)",
+        description: "FTDI FT4222H USB SPI programmer (spispeed=<khz>,cs=<0-3>,iomode=<single|dual|quad>,mode=<0-3>)",
     });
 
     #[cfg(feature = "linux-spi")]
@@ -1004,7 +1389,7 @@ pub fn available_programmers() -> Vec<ProgrammerInfo> {
     programmers.push(ProgrammerInfo {
         name: "internal",
         aliases: &[],
-        description: "Intel PCH internal SPI/FWH controller (ich_spi_mode=<auto|swseq|hwseq>)",
+        description: "Intel PCH internal SPI/FWH controller (ich_spi_mode=<auto|swseq|hwseq>, force_bad=1)",
     });
 
     #[cfg(feature = "raiden")]
@@ -1021,6 +1406,13 @@ pub fn available_programmers() -> Vec<ProgrammerInfo> {
         description: "Allwinner sunxi FEL USB SPI NOR programmer (VID:1F3A PID:EFE8)",
     });
 
+    #[cfg(feature = "plugin")]
+    programmers.push(ProgrammerInfo {
+        name: "plugin",
+        aliases: &[],
+        description: "SPI programmer from a shared library plugin (path=<lib.so>,...)",
+    });
+
     programmers
 }
 
@@ -1033,3 +1425,142 @@ pub fn programmer_names_short() -> String {
     let names: Vec<&str> = programmers.iter().map(|p| p.name).collect();
     names.join(", ")
 }
+
+#[cfg(all(test, feature = "dummy"))]
+mod tests {
+    use super::*;
+
+    /// The dummy's W25Q128FV, with a stale erase block list: no 32 KiB
+    /// erase and the wrong 64 KiB opcode
+    fn stale_db() -> ChipDatabase {
+        let mut db = ChipDatabase::empty();
+        db.load_ron(
+            r#"
+            (
+                vendor: "Winbond",
+                manufacturer_id: 0xEF,
+                chips: [
+                    (
+                        name: "W25Q128FV",
+                        device_id: 0x4018,
+                        total_size: MiB(16),
+                        page_size: 256,
+                        erase_blocks: [
+                            (opcode: 0x20, regions: [(size: KiB(4), count: 4096)]),
+                            (opcode: 0xD9, regions: [(size: KiB(64), count: 256)]),
+                            (opcode: 0xC7, regions: [(size: MiB(16), count: 1)]),
+                        ],
+                    ),
+                ],
+            )
+            "#,
+        )
+        .unwrap();
+        db
+    }
+
+    /// The dummy's W25Q128FV, a Macronix chip of the same size with a
+    /// different ID, and one of the wrong size
+    fn forced_db() -> ChipDatabase {
+        let mut db = ChipDatabase::empty();
+        for (vendor, mfr, name, device_id, size) in [
+            ("Winbond", 0xEF, "W25Q128FV", 0x4018, "MiB(16)"),
+            ("Macronix", 0xC2, "MX25L12835F", 0x2018, "MiB(16)"),
+            ("Macronix", 0xC2, "MX25L6405", 0x2017, "MiB(8)"),
+        ] {
+            db.load_ron(&format!(
+                r#"
+                (
+                    vendor: "{vendor}",
+                    manufacturer_id: {mfr},
+                    chips: [
+                        (
+                            name: "{name}",
+                            device_id: {device_id},
+                            total_size: {size},
+                            page_size: 256,
+                            erase_blocks: [
+                                (opcode: 0x20, regions: [(size: KiB(4), count: 1)]),
+                            ],
+                        ),
+                    ],
+                )
+                "#
+            ))
+            .unwrap();
+        }
+        db
+    }
+
+    fn erase_opcodes(handle: &mut FlashHandle) -> Vec<u8> {
+        let device = handle.as_device_mut();
+        device.erase_blocks().iter().map(|eb| eb.opcode).collect()
+    }
+
+    #[test]
+    fn test_prefer_sfdp_erase_blocks() {
+        let db = stale_db();
+
+        let mut handle = open_flash("dummy", &db).unwrap();
+        let info = handle.chip_info().unwrap();
+        assert!(info.mismatches.iter().any(|m| m.is_erase_block()));
+        assert!(!info.sfdp_erase_blocks);
+        assert_eq!(erase_opcodes(&mut handle), [0x20, 0xD9, 0xC7]);
+
+        let options = OpenOptions {
+            prefer_sfdp: true,
+            ..Default::default()
+        };
+        let mut handle = open_flash_with_options("dummy", &db, options).unwrap();
+        assert!(handle.chip_info().unwrap().sfdp_erase_blocks);
+        // SFDP's blocks, plus the database's chip erase
+        assert_eq!(erase_opcodes(&mut handle), [0x20, 0x52, 0xD8, 0xC7]);
+
+        // The dummy only knows the SFDP opcodes, so 64 KiB erases now work
+        handle.write(0x10000, &[0x00; 16]).unwrap();
+        handle.erase(0x10000, 0x10000).unwrap();
+        let mut buf = [0u8; 16];
+        handle.read(0x10000, &mut buf).unwrap();
+        assert_eq!(buf, [0xFF; 16]);
+    }
+
+    #[test]
+    fn test_forced_chip_matching_id() {
+        let db = forced_db();
+
+        let mut handle = open_flash_with_chip("dummy", &db, Some("W25Q128FV")).unwrap();
+        let info = handle.chip_info().unwrap();
+        assert_eq!(info.name, "W25Q128FV");
+        assert_eq!((info.jedec_manufacturer, info.jedec_device), (0xEF, 0x4018));
+        assert!(info.from_database);
+
+        handle.write(0, &[0x5A; 16]).unwrap();
+        let mut buf = [0u8; 16];
+        handle.read(0, &mut buf).unwrap();
+        assert_eq!(buf, [0x5A; 16]);
+    }
+
+    #[test]
+    fn test_forced_chip_mismatching_id() {
+        let db = forced_db();
+
+        // The forced chip is used, but the probed ID is kept
+        let handle = open_flash_with_chip("dummy", &db, Some("MX25L12835F")).unwrap();
+        let info = handle.chip_info().unwrap();
+        assert_eq!(info.vendor, "Macronix");
+        assert_eq!(info.name, "MX25L12835F");
+        assert_eq!((info.jedec_manufacturer, info.jedec_device), (0xEF, 0x4018));
+        assert_eq!(handle.size(), 16 * 1024 * 1024);
+
+        // SFDP catches a forced chip of the wrong size
+        let err = open_flash_with_chip("dummy", &db, Some("MX25L6405")).err();
+        assert!(
+            err.unwrap()
+                .to_string()
+                .contains("SFDP reports 16777216 bytes")
+        );
+
+        let err = open_flash_with_chip("dummy", &db, Some("NOPE")).err();
+        assert!(err.unwrap().to_string().starts_with("Unknown chip 'NOPE'"));
+    }
+}
@@ -0,0 +1,176 @@
+//! SPI transaction tracing
+//!
+//! [`SpiTrace`] sits between the flash code and a programmer and logs every
+//! [`SpiCommand`] at trace level, for debugging a programmer or chip that
+//! misbehaves. The programmers themselves stay free of logging in their
+//! transfer paths.
+
+use rflasher_core::error::Result;
+use rflasher_core::programmer::{CsLevel, OpaqueMaster, SpiFeatures, SpiMaster};
+use rflasher_core::spi::{AddressWidth, SpiCommand};
+
+/// Bytes of write data and read data shown per command, so that page
+/// programs and bulk reads don't flood the log
+const TRACE_DATA_BYTES: usize = 16;
+
+/// Format `data` as hex, cut off after [`TRACE_DATA_BYTES`]
+fn hex_preview(data: &[u8]) -> String {
+    let mut out = data
+        .iter()
+        .take(TRACE_DATA_BYTES)
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if data.len() > TRACE_DATA_BYTES {
+        out.push_str(&format!(" ... (+{} bytes)", data.len() - TRACE_DATA_BYTES));
+    }
+    out
+}
+
+/// One-line description of a command, including its read data
+fn describe(cmd: &SpiCommand<'_>) -> String {
+    let mut out = format!("op=0x{:02X}", cmd.opcode);
+    if let Some(addr) = cmd.address {
+        let bytes = match cmd.address_width {
+            AddressWidth::FourByte => "4B",
+            _ => "3B",
+        };
+        out.push_str(&format!(" addr=0x{:08X}/{}", addr, bytes));
+    }
+    out.push_str(&format!(" io={:?}", cmd.io_mode));
+    if cmd.dummy_cycles > 0 {
+        out.push_str(&format!(" dummy={}", cmd.dummy_cycles));
+    }
+    out.push_str(&format!(" write={}", cmd.write_data.len()));
+    if !cmd.write_data.is_empty() {
+        out.push_str(&format!(" [{}]", hex_preview(cmd.write_data)));
+    }
+    out.push_str(&format!(" read={}", cmd.read_buf.len()));
+    if !cmd.read_buf.is_empty() {
+        out.push_str(&format!(" [{}]", hex_preview(cmd.read_buf)));
+    }
+    out
+}
+
+/// Programmer wrapper that logs each SPI command at trace level
+///
+/// Commands are logged after they complete, so the read data is included,
+/// along with the error if the command failed. Everything else is forwarded
+/// unchanged. Opaque reads, writes and erases are forwarded without
+/// logging, since they aren't SPI commands.
+///
+/// With tracing disabled, this is a plain pass-through.
+pub struct SpiTrace<M> {
+    inner: M,
+    enabled: bool,
+}
+
+impl<M> SpiTrace<M> {
+    /// Wrap a programmer, logging its commands if `enabled`
+    pub fn new(inner: M, enabled: bool) -> Self {
+        Self { inner, enabled }
+    }
+
+    /// Get a mutable reference to the wrapped programmer
+    pub fn inner_mut(&mut self) -> &mut M {
+        &mut self.inner
+    }
+}
+
+impl<M: SpiMaster> SpiMaster for SpiTrace<M> {
+    fn features(&self) -> SpiFeatures {
+        self.inner.features()
+    }
+
+    fn max_read_len(&self) -> usize {
+        self.inner.max_read_len()
+    }
+
+    fn max_write_len(&self) -> usize {
+        self.inner.max_write_len()
+    }
+
+    fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> Result<()> {
+        let result = self.inner.execute(cmd);
+        if self.enabled && log::log_enabled!(log::Level::Trace) {
+            match &result {
+                Ok(()) => log::trace!("SPI {}", describe(cmd)),
+                Err(e) => log::trace!("SPI {} failed: {}", describe(cmd), e),
+            }
+        }
+        result
+    }
+
+    fn execute_batch(&mut self, cmds: &mut [SpiCommand<'_>]) -> Result<()> {
+        let result = self.inner.execute_batch(cmds);
+        if self.enabled && log::log_enabled!(log::Level::Trace) {
+            for cmd in cmds.iter() {
+                log::trace!("SPI (batched) {}", describe(cmd));
+            }
+            if let Err(e) = &result {
+                log::trace!("SPI batch of {} failed: {}", cmds.len(), e);
+            }
+        }
+        result
+    }
+
+    fn probe_opcode(&self, opcode: u8) -> bool {
+        self.inner.probe_opcode(opcode)
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.inner.delay_us(us)
+    }
+
+    fn send_clocks(&mut self, cycles: u32, cs: CsLevel) -> Result<()> {
+        if self.enabled {
+            log::trace!("SPI {} clocks with CS {:?}", cycles, cs);
+        }
+        self.inner.send_clocks(cycles, cs)
+    }
+}
+
+impl<M: OpaqueMaster> OpaqueMaster for SpiTrace<M> {
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    fn read(&mut self, addr: u32, buf: &mut [u8]) -> Result<()> {
+        self.inner.read(addr, buf)
+    }
+
+    fn write(&mut self, addr: u32, data: &[u8]) -> Result<()> {
+        self.inner.write(addr, data)
+    }
+
+    fn erase(&mut self, addr: u32, len: u32) -> Result<()> {
+        self.inner.erase(addr, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rflasher_core::spi::IoMode;
+
+    #[test]
+    fn test_describe_truncates_data() {
+        let data = [0xABu8; 256];
+        let mut buf = [0u8; 2];
+        let cmd = SpiCommand {
+            opcode: 0x02,
+            address: Some(0x1000),
+            address_width: AddressWidth::ThreeByte,
+            io_mode: IoMode::Single,
+            dummy_cycles: 0,
+            write_data: &data,
+            read_buf: &mut buf,
+        };
+
+        let line = describe(&cmd);
+        assert!(line.starts_with("op=0x02 addr=0x00001000/3B io=Single write=256 [AB AB"));
+        assert!(line.contains("... (+240 bytes)"));
+        assert!(line.ends_with("read=2 [00 00]"));
+        assert_eq!(hex_preview(&[0x9F]), "9F");
+    }
+}
@@ -17,7 +17,7 @@ use nusb::transfer::{Buffer, Bulk, ControlIn, ControlOut, ControlType, In, Out,
 use nusb::{Endpoint, Interface};
 use rflasher_core::error::{Error as CoreError, Result as CoreResult};
 use rflasher_core::programmer::{SpiFeatures, SpiMaster};
-use rflasher_core::spi::SpiCommand;
+use rflasher_core::spi::{SpiCommand, check_io_mode_supported};
 
 use crate::error::{Ft4222Error, Result};
 use crate::protocol::*;
@@ -90,6 +90,8 @@ pub struct Ft4222 {
     out_ep: u8,
     /// Current I/O lines mode.
     io_lines: u8,
+    /// Multi-I/O mode in use, after checking that the device accepts it.
+    io_mode: IoMode,
     /// Cached bulk OUT endpoint.
     out_endpoint: Option<Endpoint<Bulk, Out>>,
     /// Cached bulk IN endpoint.
@@ -319,6 +321,7 @@ impl Ft4222 {
             in_ep,
             out_ep,
             io_lines: 1,
+            io_mode: IoMode::Single,
             out_endpoint: None,
             in_endpoint: None,
             in_max_packet_size: 0,
@@ -364,12 +367,14 @@ impl Ft4222 {
         self.reset().await?;
         self.set_sys_clock(self.clock_config.sys_clock).await?;
         self.configure_spi_master().await?;
+        self.io_mode = self.negotiate_io_mode(self.config.io_mode).await;
 
         log::info!(
-            "FT4222H configured: SPI clock = {} kHz, CS = {}, I/O mode = {:?}",
+            "FT4222H configured: SPI clock = {} kHz, CS = {}, mode = {}, I/O mode = {:?}",
             self.clock_config.spi_clock_khz(),
             self.config.cs,
-            self.config.io_mode
+            self.config.mode,
+            self.io_mode
         );
 
         Ok(())
@@ -490,6 +495,12 @@ impl Ft4222 {
     #[maybe_async]
     async fn configure_spi_master(&mut self) -> Result<()> {
         let cs = self.config.cs;
+        let (clk_idle, capture) = spi_mode_registers(self.config.mode).ok_or_else(|| {
+            Ft4222Error::InvalidParameter(format!(
+                "Invalid SPI mode: {} (must be 0-3)",
+                self.config.mode
+            ))
+        })?;
 
         self.config_request(FT4222_SPI_RESET_TRANSACTION, cs)
             .await?;
@@ -500,10 +511,9 @@ impl Ft4222 {
             self.clock_config.divisor.value() as u8,
         )
         .await?;
-        self.config_request(FT4222_SPI_SET_CLK_IDLE, FT4222_CLK_IDLE_LOW)
-            .await?;
-        self.config_request(FT4222_SPI_SET_CAPTURE, FT4222_CLK_CAPTURE_LEADING)
+        self.config_request(FT4222_SPI_SET_CLK_IDLE, clk_idle)
             .await?;
+        self.config_request(FT4222_SPI_SET_CAPTURE, capture).await?;
         self.config_request(FT4222_SPI_SET_CS_ACTIVE, FT4222_CS_ACTIVE_LOW)
             .await?;
         self.config_request(FT4222_SPI_SET_CS_MASK, 1 << cs).await?;
@@ -513,6 +523,39 @@ impl Ft4222 {
         Ok(())
     }
 
+    /// Check that the device accepts the requested I/O mode.
+    ///
+    /// Switches to the mode's line count and back to single I/O. If either
+    /// step fails, multi-I/O stays disabled and the device is left in
+    /// single mode.
+    #[maybe_async]
+    async fn negotiate_io_mode(&mut self, requested: IoMode) -> IoMode {
+        if requested == IoMode::Single {
+            return IoMode::Single;
+        }
+
+        let result = match self.set_io_lines(requested.lines()).await {
+            Ok(()) => self.set_io_lines(1).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => requested,
+            Err(e) => {
+                log::warn!(
+                    "FT4222H rejected {:?} I/O mode, using single I/O: {}",
+                    requested,
+                    e
+                );
+                let _ = self.config_request(FT4222_SPI_SET_IO_LINES, 1).await;
+                let _ = self
+                    .config_request(FT4222_SPI_RESET, FT4222_SPI_RESET_LINE_NUM)
+                    .await;
+                self.io_lines = 1;
+                IoMode::Single
+            }
+        }
+    }
+
     /// Change the active number of SPI I/O lines.
     #[maybe_async]
     async fn set_io_lines(&mut self, lines: u8) -> Result<()> {
@@ -759,7 +802,6 @@ impl Ft4222 {
     }
 
     /// Perform a multi-I/O SPI transfer (half duplex).
-    #[allow(dead_code)]
     #[maybe_async]
     async fn spi_transfer_multi(
         &mut self,
@@ -791,12 +833,8 @@ impl Ft4222 {
 
         self.set_io_lines(io_lines).await?;
 
-        let mut header = [0u8; MULTI_IO_HEADER_SIZE];
-        header[0] = MULTI_IO_MAGIC | (single_data.len() as u8 & 0x0F);
-        header[1] = (multi_write_data.len() & 0xFF) as u8;
-        header[2] = ((multi_write_data.len() >> 8) & 0xFF) as u8;
-        header[3] = (multi_read_len & 0xFF) as u8;
-        header[4] = ((multi_read_len >> 8) & 0xFF) as u8;
+        let header =
+            build_multi_io_header(single_data.len(), multi_write_data.len(), multi_read_len);
 
         let mut out_buf =
             Vec::with_capacity(MULTI_IO_HEADER_SIZE + single_data.len() + multi_write_data.len());
@@ -814,11 +852,53 @@ impl Ft4222 {
         }
     }
 
+    /// Execute a dual or quad command as one multi-I/O transaction.
+    ///
+    /// The single/multi split comes from [`multi_io_frame`]. The device is
+    /// put back into single I/O afterwards even if the transfer failed.
+    #[maybe_async]
+    async fn multi_io_transfer(&mut self, cmd: &mut SpiCommand<'_>) -> Result<()> {
+        let frame = multi_io_frame(cmd).ok_or_else(|| {
+            Ft4222Error::InvalidParameter(format!(
+                "I/O mode {:?} not supported by FT4222H",
+                cmd.io_mode
+            ))
+        })?;
+
+        let result = self
+            .spi_transfer_multi(
+                &frame.single,
+                &frame.multi_write,
+                cmd.read_buf.len(),
+                frame.lines,
+            )
+            .await;
+        let restore = self.set_io_lines(1).await;
+
+        let data = result?;
+        restore?;
+        if data.len() < cmd.read_buf.len() {
+            return Err(Ft4222Error::InvalidResponse(format!(
+                "Expected {} bytes, got {}",
+                cmd.read_buf.len(),
+                data.len()
+            )));
+        }
+        let read_len = cmd.read_buf.len();
+        cmd.read_buf.copy_from_slice(&data[..read_len]);
+        Ok(())
+    }
+
     /// Get the current SPI configuration.
     pub fn config(&self) -> &SpiConfig {
         &self.config
     }
 
+    /// Get the I/O mode in use (single if the requested mode was rejected).
+    pub fn io_mode(&self) -> IoMode {
+        self.io_mode
+    }
+
     /// Get the actual SPI clock speed in kHz.
     pub fn actual_speed_khz(&self) -> u32 {
         self.clock_config.spi_clock_khz()
@@ -828,7 +908,12 @@ impl Ft4222 {
 #[maybe_async(AFIT)]
 impl SpiMaster for Ft4222 {
     fn features(&self) -> SpiFeatures {
-        SpiFeatures::FOUR_BYTE_ADDR
+        let features = SpiFeatures::FOUR_BYTE_ADDR;
+        match self.io_mode {
+            IoMode::Single => features,
+            IoMode::Dual => features | SpiFeatures::DUAL,
+            IoMode::Quad => features | SpiFeatures::DUAL | SpiFeatures::QUAD,
+        }
     }
 
     fn max_read_len(&self) -> usize {
@@ -840,6 +925,15 @@ impl SpiMaster for Ft4222 {
     }
 
     async fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> CoreResult<()> {
+        check_io_mode_supported(cmd.io_mode, self.features())?;
+
+        if cmd.io_mode.is_multi_io() {
+            return self
+                .multi_io_transfer(cmd)
+                .await
+                .map_err(|_| CoreError::ProgrammerError);
+        }
+
         let header_len = cmd.header_len();
         let mut write_data = vec![0u8; header_len + cmd.write_data.len()];
         cmd.encode_header(&mut write_data);
@@ -922,6 +1016,7 @@ impl std::fmt::Display for Ft4222DeviceInfo {
 /// - `spispeed=<khz>`: Target SPI clock speed in kHz (default: 10000)
 /// - `cs=<0-3>`: Which chip select to use (default: 0)
 /// - `iomode=<single|dual|quad>`: I/O mode (default: single)
+/// - `mode=<0-3>`: SPI mode, i.e. CPOL/CPHA (default: 0)
 pub fn parse_options(options: &[(&str, &str)]) -> Result<SpiConfig> {
     let mut config = SpiConfig::default();
 
@@ -954,6 +1049,18 @@ pub fn parse_options(options: &[(&str, &str)]) -> Result<SpiConfig> {
                     ))
                 })?;
             }
+            "mode" => {
+                let mode: u8 = value.parse().map_err(|_| {
+                    Ft4222Error::InvalidParameter(format!("Invalid mode value: {}", value))
+                })?;
+                if spi_mode_registers(mode).is_none() {
+                    return Err(Ft4222Error::InvalidParameter(format!(
+                        "Invalid mode: {} (must be 0-3)",
+                        mode
+                    )));
+                }
+                config.mode = mode;
+            }
             _ => {
                 log::warn!("Unknown FT4222 option: {}={}", key, value);
             }
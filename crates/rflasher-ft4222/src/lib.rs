@@ -17,14 +17,15 @@
 //!
 //! - SPI Master mode with clock speeds from ~47 kHz to 40 MHz
 //! - Single I/O (1-1-1) mode - standard SPI
+//! - Dual (1-1-2, 1-2-2) and quad (1-1-4, 1-4-4) I/O reads when enabled
+//! - SPI modes 0-3 (CPOL/CPHA)
 //! - Up to 4 chip select outputs (depending on device mode)
 //! - 4-byte addressing for >16MB flash chips
 //! - Pure USB implementation (no LibFT4222 required)
 //!
 //! # Limitations
 //!
-//! - Dual/Quad I/O modes are supported by hardware but not yet implemented
-//! - Only SPI mode 0 (CPOL=0, CPHA=0) is currently supported
+//! - QPI (4-4-4) is not supported: the opcode is always sent on one line
 //!
 //! # Example
 //!
@@ -56,7 +57,18 @@
 //!
 //! - `spispeed=<kHz>` - Target SPI clock speed in kHz (default: 10000)
 //! - `cs=<0-3>` - Chip select line to use (default: 0)
-//! - `iomode=<single|dual|quad>` - I/O mode (default: single)
+//! - `iomode=<single|dual|quad>` - Widest I/O mode to use (default: single)
+//! - `mode=<0-3>` - SPI mode (default: 0)
+//!
+//! # Multi-I/O
+//!
+//! With `iomode=dual` or `iomode=quad` the driver checks at open time that
+//! the device accepts the extra I/O lines and only then advertises the
+//! matching dual/quad features. Each multi-I/O command is sent as one
+//! FT4222H multi-I/O transaction: the opcode (and, for 1-1-x modes, the
+//! address) on a single line, then the remaining phases on two or four
+//! lines. The device is switched back to single I/O after every such
+//! transaction.
 //!
 //! # SPI Clock Speed
 //!
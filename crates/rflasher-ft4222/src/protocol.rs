@@ -10,6 +10,8 @@
 // and potential future use
 #![allow(dead_code)]
 
+use rflasher_core::spi::SpiCommand;
+
 // ============================================================================
 // USB device identifiers
 // ============================================================================
@@ -71,6 +73,21 @@ pub const FT4222_CLK_CAPTURE_TRAILING: u8 = 1;
 pub const FT4222_CS_ACTIVE_LOW: u8 = 0;
 pub const FT4222_CS_ACTIVE_HIGH: u8 = 1;
 
+/// Clock idle level and capture edge for SPI mode `mode` (0-3)
+///
+/// Returns `(clk_idle, capture)` data bytes for `FT4222_SPI_SET_CLK_IDLE`
+/// and `FT4222_SPI_SET_CAPTURE`, or `None` for an invalid mode. CPOL is bit
+/// 1 of the mode number and CPHA is bit 0.
+pub fn spi_mode_registers(mode: u8) -> Option<(u8, u8)> {
+    match mode {
+        0 => Some((FT4222_CLK_IDLE_LOW, FT4222_CLK_CAPTURE_LEADING)),
+        1 => Some((FT4222_CLK_IDLE_LOW, FT4222_CLK_CAPTURE_TRAILING)),
+        2 => Some((FT4222_CLK_IDLE_HIGH, FT4222_CLK_CAPTURE_LEADING)),
+        3 => Some((FT4222_CLK_IDLE_HIGH, FT4222_CLK_CAPTURE_TRAILING)),
+        _ => None,
+    }
+}
+
 // ============================================================================
 // Buffer and transfer sizes
 // ============================================================================
@@ -275,6 +292,80 @@ impl IoMode {
     }
 }
 
+// ============================================================================
+// Multi-I/O framing
+// ============================================================================
+
+/// Build the 5-byte header that starts a multi-I/O transaction
+pub fn build_multi_io_header(
+    single_len: usize,
+    multi_write_len: usize,
+    multi_read_len: usize,
+) -> [u8; MULTI_IO_HEADER_SIZE] {
+    [
+        MULTI_IO_MAGIC | (single_len as u8 & 0x0F),
+        (multi_write_len & 0xFF) as u8,
+        ((multi_write_len >> 8) & 0xFF) as u8,
+        (multi_read_len & 0xFF) as u8,
+        ((multi_read_len >> 8) & 0xFF) as u8,
+    ]
+}
+
+/// Number of dummy bytes to clock out for `dummy_cycles` on `lines` lines
+pub fn dummy_bytes(dummy_cycles: u8, lines: u8) -> usize {
+    (dummy_cycles as usize * lines as usize).div_ceil(8)
+}
+
+/// Outgoing phases of a multi-I/O transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiIoFrame {
+    /// Bytes sent on one line (opcode, plus address for 1-1-x modes)
+    pub single: Vec<u8>,
+    /// Bytes sent on `lines` lines (address for 1-x-x modes, then data)
+    pub multi_write: Vec<u8>,
+    /// Number of I/O lines for the multi phases
+    pub lines: u8,
+}
+
+/// Split an SPI command into the single and multi-I/O write phases
+///
+/// The FT4222H only switches line count between the single and multi
+/// phases, so the opcode must go out on one line and the data phases share
+/// one width. That covers 1-1-2, 1-2-2, 1-1-4 and 1-4-4 but not QPI.
+pub fn multi_io_frame(cmd: &SpiCommand<'_>) -> Option<MultiIoFrame> {
+    let mode = cmd.io_mode;
+    if mode.cmd_lines() != 1 {
+        return None;
+    }
+    let addr_lines = mode.addr_lines();
+    let lines = mode.data_lines();
+
+    let addr_len = cmd.address_width.bytes() as usize;
+    let mut addr = vec![0xFFu8; addr_len + dummy_bytes(cmd.dummy_cycles, addr_lines)];
+    if let Some(address) = cmd.address {
+        cmd.address_width.encode(address, &mut addr);
+    }
+
+    let mut single = vec![cmd.opcode];
+    let mut multi_write = Vec::with_capacity(addr.len() + cmd.write_data.len());
+    if addr_lines == 1 {
+        single.extend_from_slice(&addr);
+    } else {
+        multi_write.extend_from_slice(&addr);
+    }
+    multi_write.extend_from_slice(cmd.write_data);
+
+    if single.len() > MULTI_IO_MAX_SINGLE {
+        return None;
+    }
+
+    Some(MultiIoFrame {
+        single,
+        multi_write,
+        lines,
+    })
+}
+
 // ============================================================================
 // SPI configuration
 // ============================================================================
@@ -288,6 +379,8 @@ pub struct SpiConfig {
     pub speed_khz: u32,
     /// I/O mode (single/dual/quad)
     pub io_mode: IoMode,
+    /// SPI mode (0-3, CPOL/CPHA)
+    pub mode: u8,
 }
 
 impl Default for SpiConfig {
@@ -296,6 +389,7 @@ impl Default for SpiConfig {
             cs: 0,
             speed_khz: DEFAULT_SPI_SPEED_KHZ,
             io_mode: IoMode::Single,
+            mode: 0,
         }
     }
 }
@@ -323,6 +417,14 @@ impl SpiConfig {
         self.io_mode = mode;
         self
     }
+
+    /// Set the SPI mode (0-3)
+    ///
+    /// Invalid modes are rejected when the device is opened.
+    pub fn with_mode(mut self, mode: u8) -> Self {
+        self.mode = mode;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -374,4 +476,65 @@ mod tests {
         assert_eq!(IoMode::parse("4"), Some(IoMode::Quad));
         assert_eq!(IoMode::parse("invalid"), None);
     }
+
+    #[test]
+    fn test_spi_mode_registers() {
+        assert_eq!(
+            spi_mode_registers(0),
+            Some((FT4222_CLK_IDLE_LOW, FT4222_CLK_CAPTURE_LEADING))
+        );
+        assert_eq!(
+            spi_mode_registers(1),
+            Some((FT4222_CLK_IDLE_LOW, FT4222_CLK_CAPTURE_TRAILING))
+        );
+        assert_eq!(
+            spi_mode_registers(2),
+            Some((FT4222_CLK_IDLE_HIGH, FT4222_CLK_CAPTURE_LEADING))
+        );
+        assert_eq!(
+            spi_mode_registers(3),
+            Some((FT4222_CLK_IDLE_HIGH, FT4222_CLK_CAPTURE_TRAILING))
+        );
+        assert_eq!(spi_mode_registers(4), None);
+        assert_eq!(SpiConfig::new().with_mode(3).mode, 3);
+    }
+
+    #[test]
+    fn test_multi_io_header() {
+        assert_eq!(
+            build_multi_io_header(5, 0x0102, 0x1000),
+            [0x85, 0x02, 0x01, 0x00, 0x10]
+        );
+        assert_eq!(build_multi_io_header(1, 0, 0), [0x81, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_multi_io_frame() {
+        use rflasher_core::spi::{IoMode as SpiIoMode, opcodes};
+
+        // 1-1-4: opcode, address and 8 dummy cycles all on one line
+        let mut buf = [0u8; 16];
+        let cmd = SpiCommand::read_3b(opcodes::QOR, 0x123456, &mut buf)
+            .with_io_mode(SpiIoMode::QuadOut)
+            .with_dummy_cycles(8);
+        let frame = multi_io_frame(&cmd).unwrap();
+        assert_eq!(frame.single, [opcodes::QOR, 0x12, 0x34, 0x56, 0xFF]);
+        assert!(frame.multi_write.is_empty());
+        assert_eq!(frame.lines, 4);
+
+        // 1-4-4: address and 6 dummy cycles (3 bytes at 4 bits/clock) on four lines
+        let mut buf = [0u8; 16];
+        let cmd = SpiCommand::read_3b(opcodes::QIOR, 0x123456, &mut buf)
+            .with_io_mode(SpiIoMode::QuadIo)
+            .with_dummy_cycles(6);
+        let frame = multi_io_frame(&cmd).unwrap();
+        assert_eq!(frame.single, [opcodes::QIOR]);
+        assert_eq!(frame.multi_write, [0x12, 0x34, 0x56, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(frame.lines, 4);
+
+        // QPI sends the opcode on four lines, which the FT4222H can't do
+        let mut buf = [0u8; 16];
+        let cmd = SpiCommand::read_3b(opcodes::QIOR, 0, &mut buf).with_io_mode(SpiIoMode::Qpi);
+        assert!(multi_io_frame(&cmd).is_none());
+    }
 }
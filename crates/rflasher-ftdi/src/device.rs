@@ -10,7 +10,7 @@ use ftdi::{BitMode, Device, Interface, find_by_vid_pid};
 use nusb::MaybeFuture;
 use rflasher_core::error::{Error as CoreError, Result as CoreResult};
 use rflasher_core::programmer::default_execute_with_vec;
-use rflasher_core::programmer::{SpiFeatures, SpiMaster};
+use rflasher_core::programmer::{CsLevel, SpiFeatures, SpiMaster};
 use rflasher_core::spi::SpiCommand;
 
 use crate::error::{FtdiError, Result};
@@ -245,6 +245,20 @@ impl Ftdi {
         }
     }
 
+    /// Clock `cycles` bits with MOSI high and CS held at `cs`
+    fn idle_clocks(&mut self, cycles: u32, cs: CsLevel) -> Result<()> {
+        let mut buf = Vec::new();
+        if cs == CsLevel::Asserted {
+            buf.extend_from_slice(&[SET_BITS_LOW, self.aux_bits, self.pindir]);
+        }
+        push_idle_clocks(&mut buf, cycles);
+        if cs == CsLevel::Asserted {
+            buf.extend_from_slice(&[SET_BITS_LOW, self.cs_bits | self.aux_bits, self.pindir]);
+        }
+        buf.push(SEND_IMMEDIATE);
+        self.send(&buf)
+    }
+
     /// Release I/O pins (set all as inputs)
     fn release_pins(&mut self) -> Result<()> {
         let buf = [SET_BITS_LOW, 0x00, 0x00];
@@ -321,6 +335,11 @@ impl SpiMaster for Ftdi {
         // The MPSSE doesn't have built-in delay commands for arbitrary times
         std::thread::sleep(Duration::from_micros(us as u64));
     }
+
+    fn send_clocks(&mut self, cycles: u32, cs: CsLevel) -> CoreResult<()> {
+        self.idle_clocks(cycles, cs)
+            .map_err(|_| CoreError::ProgrammerError)
+    }
 }
 
 /// Information about a connected FTDI device
@@ -359,9 +378,13 @@ impl std::fmt::Display for FtdiDeviceInfo {
 
 /// Parse programmer options from a string
 ///
-/// Format: "type=<type>,port=<A|B|C|D>,divisor=<N>,serial=<serial>,gpiol0=<H|L|C>"
+/// Format: "type=<type>,port=<A|B|C|D>,divisor=<N>,spispeed=<kHz>,serial=<serial>,gpiol0=<H|L|C>,csgpiol=<0-3>"
 pub fn parse_options(options: &[(&str, &str)]) -> Result<FtdiConfig> {
     let mut config = FtdiConfig::default();
+    // The clock depends on the device type, so it is set after all other
+    // options, whatever their order
+    let mut divisor = None;
+    let mut spispeed = None;
 
     for (key, value) in options {
         match *key {
@@ -382,7 +405,7 @@ pub fn parse_options(options: &[(&str, &str)]) -> Result<FtdiConfig> {
                 config.pindir_high = config.device_type.default_pindir_high();
                 config.divisor = config.device_type.default_divisor();
             }
-            "port" | "channel" => {
+            "port" | "channel" | "interface" => {
                 if value.len() != 1 {
                     return Err(FtdiError::InvalidChannel(format!(
                         "Invalid channel '{}': must be A, B, C, or D",
@@ -399,10 +422,20 @@ pub fn parse_options(options: &[(&str, &str)]) -> Result<FtdiConfig> {
                 config = config.interface(interface)?;
             }
             "divisor" => {
-                let divisor: u16 = value.parse().map_err(|_| {
+                divisor = Some(value.parse::<u16>().map_err(|_| {
                     FtdiError::InvalidParameter(format!("Invalid divisor '{}'", value))
+                })?);
+            }
+            "spispeed" => {
+                spispeed = Some(value.parse::<u32>().map_err(|_| {
+                    FtdiError::InvalidParameter(format!("Invalid spispeed '{}'", value))
+                })?);
+            }
+            "csgpiol" => {
+                let pin: u8 = value.parse().map_err(|_| {
+                    FtdiError::InvalidParameter(format!("Invalid csgpiol pin '{}'", value))
                 })?;
-                config = config.divisor(divisor)?;
+                config = config.cs_gpiol(pin)?;
             }
             "serial" => {
                 config.serial = Some(value.to_string());
@@ -428,5 +461,32 @@ pub fn parse_options(options: &[(&str, &str)]) -> Result<FtdiConfig> {
         }
     }
 
+    if let Some(divisor) = divisor {
+        config = config.divisor(divisor)?;
+    }
+    // spispeed= wins over divisor=
+    if let Some(khz) = spispeed {
+        config = config.spispeed_khz(khz)?;
+    }
+
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options_sets_clock_last() {
+        // The divisor follows the device type, wherever it is given
+        let config = parse_options(&[("spispeed", "1000"), ("type", "jtagkey")]).unwrap();
+        assert_eq!(config.divisor, 12);
+        let config = parse_options(&[("type", "jtagkey"), ("spispeed", "1000")]).unwrap();
+        assert_eq!(config.divisor, 12);
+
+        // spispeed= wins over divisor=
+        let config =
+            parse_options(&[("spispeed", "10000"), ("divisor", "8"), ("type", "232h")]).unwrap();
+        assert_eq!(config.divisor, 6);
+    }
+}
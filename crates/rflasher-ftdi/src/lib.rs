@@ -61,14 +61,17 @@
 //! When using the CLI, the following options are available:
 //!
 //! - `type=<device>` - Device type (2232h, 4232h, 232h, jtagkey, tumpa, etc.)
-//! - `port=<A|B|C|D>` - Channel to use (default: A)
+//! - `port=<A|B|C|D>` - Channel to use (default: A); `interface=` is an alias
 //! - `divisor=<N>` - Clock divisor (2-65536, even; default: 2)
+//! - `spispeed=<kHz>` - Target SPI clock, as an alternative to `divisor=`;
+//!   picks the fastest clock at or below it and wins over `divisor=`
 //! - `serial=<string>` - USB serial number filter
 //! - `description=<string>` - USB description filter
 //! - `gpiol0=<H|L|C>` - GPIOL0 mode (H=high, L=low, C=CS)
 //! - `gpiol1=<H|L|C>` - GPIOL1 mode
 //! - `gpiol2=<H|L|C>` - GPIOL2 mode
 //! - `gpiol3=<H|L|C>` - GPIOL3 mode
+//! - `csgpiol=<0-3>` - Use GPIOL<N> as an additional chip select
 //!
 //! # SPI Clock Speed
 //!
@@ -78,6 +81,9 @@
 //! SPI_clock = 60 MHz / divisor
 //! ```
 //!
+//! Adapters built around the FT2232C/D (JTAGkey, ARM-USB-OCD, ARM-USB-TINY,
+//! OpenMoko debug board) use a 12 MHz base clock instead.
+//!
 //! | Divisor | SPI Clock |
 //! |---------|-----------|
 //! | 2       | 30 MHz    |
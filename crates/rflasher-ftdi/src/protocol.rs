@@ -363,8 +363,15 @@ impl FtdiDeviceType {
 
     /// Whether this device supports 60 MHz base clock
     pub fn is_high_speed(&self) -> bool {
-        // All supported devices in this enum are high-speed 'H' variants
-        true
+        // These adapters are built around the FT2232C/D, whose MPSSE runs
+        // from 12 MHz and doesn't know the divide-by-5 command
+        !matches!(
+            self,
+            FtdiDeviceType::JtagKey
+                | FtdiDeviceType::ArmUsbOcd
+                | FtdiDeviceType::ArmUsbTiny
+                | FtdiDeviceType::OpenMokoDbg
+        )
     }
 
     /// Parse device type from string
@@ -696,6 +703,18 @@ impl FtdiConfig {
         Ok(self)
     }
 
+    /// Set the clock divisor from a target SPI speed in kHz
+    ///
+    /// Picks the smallest valid divisor whose clock doesn't exceed `khz`.
+    pub fn spispeed_khz(self, khz: u32) -> core::result::Result<Self, String> {
+        if khz == 0 {
+            return Err("Invalid spispeed 0: must be at least 1 kHz".into());
+        }
+        let divisor = self.base_clock_khz().div_ceil(khz).clamp(2, 65534);
+        let divisor = divisor + divisor % 2;
+        self.divisor(divisor as u16)
+    }
+
     /// Set a GPIOL pin mode
     ///
     /// `pin` is 0-3 (GPIOL0-GPIOL3)
@@ -744,14 +763,25 @@ impl FtdiConfig {
         Ok(self)
     }
 
+    /// Use GPIOL `pin` as an additional chip select
+    ///
+    /// Shorthand for `gpiol(pin, 'C')`, matching flashprog's `csgpiol=`.
+    pub fn cs_gpiol(self, pin: u8) -> core::result::Result<Self, String> {
+        self.gpiol(pin, 'C')
+    }
+
+    /// MPSSE base clock in kHz (60 MHz for 'H' devices, 12 MHz otherwise)
+    pub fn base_clock_khz(&self) -> u32 {
+        if self.device_type.is_high_speed() {
+            60_000
+        } else {
+            12_000
+        }
+    }
+
     /// Calculate the SPI clock frequency in MHz
     pub fn spi_clock_mhz(&self) -> f64 {
-        let base_clock = if self.device_type.is_high_speed() {
-            60.0
-        } else {
-            12.0
-        };
-        base_clock / self.divisor as f64
+        self.base_clock_khz() as f64 / 1000.0 / self.divisor as f64
     }
 
     /// Get the FTDI interface index (1-based for USB control requests)
@@ -759,3 +789,110 @@ impl FtdiConfig {
         self.interface.index() as u16 + 1
     }
 }
+
+// ============================================================================
+// MPSSE command helpers
+// ============================================================================
+
+/// Append MPSSE commands clocking `cycles` bits with MOSI held high
+///
+/// Whole bytes are written as 0xFF, up to 64 KiB per command, and the
+/// remaining bits with a single bit mode write. CS is left as it is.
+pub fn push_idle_clocks(buf: &mut Vec<u8>, cycles: u32) {
+    let mut bytes = cycles / 8;
+    while bytes > 0 {
+        let n = bytes.min(65536);
+        buf.push(MPSSE_DO_WRITE | MPSSE_WRITE_NEG);
+        buf.push(((n - 1) & 0xFF) as u8);
+        buf.push((((n - 1) >> 8) & 0xFF) as u8);
+        buf.resize(buf.len() + n as usize, 0xFF);
+        bytes -= n;
+    }
+
+    let bits = cycles % 8;
+    if bits > 0 {
+        buf.push(MPSSE_DO_WRITE | MPSSE_WRITE_NEG | MPSSE_BITMODE);
+        buf.push((bits - 1) as u8);
+        buf.push(0xFF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_idle_clocks() {
+        // Whole bytes and the remaining bits, all with MOSI high
+        let mut buf = Vec::new();
+        push_idle_clocks(&mut buf, 19);
+        assert_eq!(
+            buf,
+            [
+                MPSSE_DO_WRITE | MPSSE_WRITE_NEG,
+                0x01,
+                0x00,
+                0xFF,
+                0xFF,
+                MPSSE_DO_WRITE | MPSSE_WRITE_NEG | MPSSE_BITMODE,
+                0x02,
+                0xFF,
+            ]
+        );
+
+        // Byte writes are split at the 64 KiB command limit
+        let mut buf = Vec::new();
+        push_idle_clocks(&mut buf, (65536 + 1) * 8);
+        assert_eq!(buf.len(), 3 + 65536 + 3 + 1);
+        assert_eq!(buf[..3], [MPSSE_DO_WRITE | MPSSE_WRITE_NEG, 0xFF, 0xFF]);
+        assert_eq!(
+            buf[3 + 65536..3 + 65536 + 3],
+            [MPSSE_DO_WRITE | MPSSE_WRITE_NEG, 0x00, 0x00]
+        );
+
+        let mut buf = Vec::new();
+        push_idle_clocks(&mut buf, 0);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_spispeed_khz() {
+        let divisor = |device_type, khz| {
+            FtdiConfig::for_device(device_type)
+                .spispeed_khz(khz)
+                .unwrap()
+                .divisor
+        };
+
+        // 60 MHz base clock
+        assert_eq!(divisor(FtdiDeviceType::Ft232H, 30_000), 2);
+        assert_eq!(divisor(FtdiDeviceType::Ft232H, 10_000), 6);
+        assert_eq!(divisor(FtdiDeviceType::Ft232H, 1), 60_000);
+        // 12 MHz base clock
+        assert_eq!(divisor(FtdiDeviceType::JtagKey, 6_000), 2);
+        assert_eq!(divisor(FtdiDeviceType::JtagKey, 1_000), 12);
+        assert_eq!(divisor(FtdiDeviceType::JtagKey, 1), 12_000);
+
+        // Rounds down to the next even divisor's clock, and clamps to the
+        // fastest one
+        assert_eq!(divisor(FtdiDeviceType::Ft232H, 7_000), 10);
+        assert_eq!(divisor(FtdiDeviceType::JtagKey, 5_000), 4);
+        assert_eq!(divisor(FtdiDeviceType::Ft232H, 100_000), 2);
+        for khz in [1, 7, 333, 4_999, 14_999, 29_999] {
+            let config = FtdiConfig::for_device(FtdiDeviceType::Ft2232H)
+                .spispeed_khz(khz)
+                .unwrap();
+            assert!(
+                config.base_clock_khz() <= khz * config.divisor as u32,
+                "{} kHz",
+                khz
+            );
+        }
+
+        assert!(
+            FtdiConfig::for_device(FtdiDeviceType::Ft232H)
+                .spispeed_khz(0)
+                .is_err()
+        );
+    }
+}
@@ -14,7 +14,7 @@ use maybe_async::maybe_async;
 #[cfg(feature = "is_sync")]
 use nusb::MaybeFuture;
 use rflasher_core::error::{Error as CoreError, Result as CoreResult};
-use rflasher_core::programmer::{SpiFeatures, SpiMaster};
+use rflasher_core::programmer::{CsLevel, SpiFeatures, SpiMaster};
 use rflasher_core::spi::{SpiCommand, check_io_mode_supported};
 use rs_ftdi::FtdiDevice;
 
@@ -398,6 +398,21 @@ impl Ftdi {
         }
     }
 
+    /// Clock `cycles` bits with MOSI high and CS held at `cs`
+    #[maybe_async]
+    async fn idle_clocks(&mut self, cycles: u32, cs: CsLevel) -> Result<()> {
+        let mut buf = Vec::new();
+        if cs == CsLevel::Asserted {
+            buf.extend_from_slice(&[SET_BITS_LOW, self.aux_bits, self.pindir]);
+        }
+        push_idle_clocks(&mut buf, cycles);
+        if cs == CsLevel::Asserted {
+            buf.extend_from_slice(&[SET_BITS_LOW, self.cs_bits | self.aux_bits, self.pindir]);
+        }
+        buf.push(SEND_IMMEDIATE);
+        self.send(&buf).await
+    }
+
     /// Release I/O pins (set all as inputs)
     #[maybe_async]
     async fn release_pins(&mut self) -> Result<()> {
@@ -478,6 +493,12 @@ impl SpiMaster for Ftdi {
             }
         }
     }
+
+    async fn send_clocks(&mut self, cycles: u32, cs: CsLevel) -> CoreResult<()> {
+        self.idle_clocks(cycles, cs)
+            .await
+            .map_err(|_e| CoreError::ProgrammerError)
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -526,10 +547,14 @@ impl std::fmt::Display for FtdiDeviceInfo {
 
 /// Parse programmer options from a string
 ///
-/// Format: "type=<type>,port=<A|B|C|D>,divisor=<N>,serial=<serial>,gpiol0=<H|L|C>"
+/// Format: "type=<type>,port=<A|B|C|D>,divisor=<N>,spispeed=<kHz>,serial=<serial>,gpiol0=<H|L|C>,csgpiol=<0-3>"
 #[cfg(all(feature = "native", not(target_arch = "wasm32")))]
 pub fn parse_options(options: &[(&str, &str)]) -> Result<FtdiConfig> {
     let mut config = FtdiConfig::default();
+    // The clock depends on the device type, so it is set after all other
+    // options, whatever their order
+    let mut divisor = None;
+    let mut spispeed = None;
 
     for (key, value) in options {
         match *key {
@@ -550,7 +575,7 @@ pub fn parse_options(options: &[(&str, &str)]) -> Result<FtdiConfig> {
                 config.pindir_high = config.device_type.default_pindir_high();
                 config.divisor = config.device_type.default_divisor();
             }
-            "port" | "channel" => {
+            "port" | "channel" | "interface" => {
                 if value.len() != 1 {
                     return Err(FtdiError::InvalidChannel(format!(
                         "Invalid channel '{}': must be A, B, C, or D",
@@ -567,10 +592,20 @@ pub fn parse_options(options: &[(&str, &str)]) -> Result<FtdiConfig> {
                 config = config.interface(interface)?;
             }
             "divisor" => {
-                let divisor: u16 = value.parse().map_err(|_| {
+                divisor = Some(value.parse::<u16>().map_err(|_| {
                     FtdiError::InvalidParameter(format!("Invalid divisor '{}'", value))
+                })?);
+            }
+            "spispeed" => {
+                spispeed = Some(value.parse::<u32>().map_err(|_| {
+                    FtdiError::InvalidParameter(format!("Invalid spispeed '{}'", value))
+                })?);
+            }
+            "csgpiol" => {
+                let pin: u8 = value.parse().map_err(|_| {
+                    FtdiError::InvalidParameter(format!("Invalid csgpiol pin '{}'", value))
                 })?;
-                config = config.divisor(divisor)?;
+                config = config.cs_gpiol(pin)?;
             }
             "serial" => {
                 config.serial = Some(value.to_string());
@@ -596,5 +631,13 @@ pub fn parse_options(options: &[(&str, &str)]) -> Result<FtdiConfig> {
         }
     }
 
+    if let Some(divisor) = divisor {
+        config = config.divisor(divisor)?;
+    }
+    // spispeed= wins over divisor=
+    if let Some(khz) = spispeed {
+        config = config.spispeed_khz(khz)?;
+    }
+
     Ok(config)
 }
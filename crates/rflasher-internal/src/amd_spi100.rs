@@ -14,10 +14,13 @@
 //!
 //! The AMD SPI100 controller has:
 //! - A 71-byte FIFO for SPI commands and data
-//! - Memory-mapped flash access via ROM range registers
+//! - Memory-mapped flash access via ROM range registers, read a dword at a time
 //! - Support for various SPI modes (normal, dual I/O, quad I/O, fast read)
 //! - Configurable clock speeds
 //!
+//! Reads outside the memory-mapped window go through the FIFO, which takes up
+//! to 68 data bytes per command (67 with a 4-byte address).
+//!
 //! # References
 //!
 //! - flashprog/amd_spi100.c - Original C implementation
@@ -41,6 +44,8 @@ const SPI100_FIFO_SIZE: usize = 71;
 const SPI_FLASH_PAGE_SIZE: usize = 256;
 const SPI_FLASH_4K_SECTOR: u32 = 4096;
 const SPI_READY_POLL_LIMIT: u32 = 600_000;
+/// First address that needs 4-byte addressing
+const SPI_3BA_LIMIT: u32 = 1 << 24;
 
 /// Maximum data transfer for read operations
 /// Account for up to 4 address bytes
@@ -422,23 +427,37 @@ impl<H: HostAccess> Spi100Controller<H> {
             .as_ref()
             .ok_or(InternalError::Io("No memory mapping available"))?;
 
-        for (offset, byte) in dst.iter_mut().enumerate() {
+        // Bytes up to the first aligned dword, then whole dwords
+        let head = (start.next_multiple_of(4) - start).min(dst.len());
+        let (head_buf, rest) = dst.split_at_mut(head);
+        for (offset, byte) in head_buf.iter_mut().enumerate() {
             *byte = memory.read8(start + offset);
         }
 
+        let mut offset = start + head;
+        let mut words = rest.chunks_exact_mut(4);
+        for word in &mut words {
+            word.copy_from_slice(&memory.read32(offset).to_le_bytes());
+            offset += 4;
+        }
+        for byte in words.into_remainder() {
+            *byte = memory.read8(offset);
+            offset += 1;
+        }
+
         Ok(())
     }
 
     /// Read data from flash
     ///
     /// This uses memory-mapped access when available and falls back to
-    /// SPI commands for data outside the mapped range.
+    /// FIFO reads through the SPI100 engine for data outside the mapped range.
     pub fn read(&self, chip_size: u64, start: u32, buf: &mut [u8]) -> Result<(), InternalError> {
         let len = buf.len();
 
         // Don't consider memory mapping at all if 4BA chips are not mapped as expected
         if chip_size > 16 * 1024 * 1024 && self.no_4ba_mmap {
-            return self.default_spi_read(start, buf);
+            return self.fifo_read(start, buf);
         }
 
         // Where in the flash does the memory mapped part start?
@@ -451,7 +470,7 @@ impl<H: HostAccess> Spi100Controller<H> {
         // Use SPI100 engine for data outside the memory-mapped range
         if (current_start as i64) < mapped_start {
             let unmapped_len = len.min((mapped_start - current_start as i64) as usize);
-            self.default_spi_read(current_start, &mut buf[offset..offset + unmapped_len])?;
+            self.fifo_read(current_start, &mut buf[offset..offset + unmapped_len])?;
             current_start += unmapped_len as u32;
             offset += unmapped_len;
         }
@@ -465,26 +484,48 @@ impl<H: HostAccess> Spi100Controller<H> {
         Ok(())
     }
 
-    /// Default SPI read using command interface
-    fn default_spi_read(&self, start: u32, buf: &mut [u8]) -> Result<(), InternalError> {
-        // Use standard SPI READ command (0x03)
+    /// Read flash through the SPI100 FIFO
+    ///
+    /// Every transaction fills the FIFO with as much data as fits after the
+    /// address bytes and then advances the address, so a read costs one
+    /// command per 67-68 bytes. Addresses below 16 MiB use READ with a
+    /// 3-byte address; above that READ_4B is used.
+    fn fifo_read(&self, start: u32, buf: &mut [u8]) -> Result<(), InternalError> {
+        use rflasher_core::spi::opcodes;
+
         let len = buf.len();
         let mut offset = 0;
+        let mut transactions = 0;
 
         while offset < len {
-            let chunk_len = (len - offset).min(SPI100_MAX_DATA_READ);
             let addr = start + offset as u32;
 
-            let mut writearr = [0u8; 4];
-            writearr[0] = 0x03; // READ command
-            writearr[1] = (addr >> 16) as u8;
-            writearr[2] = (addr >> 8) as u8;
-            writearr[3] = addr as u8;
-
-            self.send_command(&writearr, &mut buf[offset..offset + chunk_len])?;
+            let mut writearr = [0u8; 5];
+            let (write_len, max_chunk) = if addr < SPI_3BA_LIMIT {
+                writearr[0] = opcodes::READ;
+                writearr[1..4].copy_from_slice(&addr.to_be_bytes()[1..]);
+                // A 3-byte address would wrap around at 16 MiB
+                let to_limit = (SPI_3BA_LIMIT - addr) as usize;
+                (4, (SPI100_FIFO_SIZE - 3).min(to_limit))
+            } else {
+                writearr[0] = opcodes::READ_4B;
+                writearr[1..5].copy_from_slice(&addr.to_be_bytes());
+                (5, SPI100_FIFO_SIZE - 4)
+            };
+
+            let chunk_len = (len - offset).min(max_chunk);
+            self.send_command(&writearr[..write_len], &mut buf[offset..offset + chunk_len])?;
             offset += chunk_len;
+            transactions += 1;
         }
 
+        log::trace!(
+            "FIFO read of {} bytes at {:#010x} took {} transactions",
+            len,
+            start,
+            transactions
+        );
+
         Ok(())
     }
 
@@ -743,13 +784,14 @@ impl<H: HostAccess> Spi100Controller<H> {
             write_len += data_len;
         }
 
-        // Dummy cycles are not directly supported by SPI100 FIFO interface.
-        // The controller handles this internally for known commands.
+        // The SPI100 FIFO interface can't clock dummy cycles, dropping them
+        // would shift the data read back
         if cmd.dummy_cycles > 0 {
             log::debug!(
-                "Dummy cycles ({}) will be handled by controller",
+                "Dummy cycles ({}) not supported by SPI100",
                 cmd.dummy_cycles
             );
+            return Err(CoreError::OpcodeNotSupported);
         }
 
         self.send_command(&writearr[..write_len], cmd.read_buf)
@@ -796,19 +838,24 @@ impl<H: HostAccess> Controller for Spi100Controller<H> {
             InternalError::PciAccess(_) | InternalError::MemoryMap { .. } => {
                 CoreError::ProgrammerError
             }
-            InternalError::AccessDenied { .. } => CoreError::RegionProtected,
+            InternalError::AccessDenied { .. } | InternalError::RegionLocked { .. } => {
+                CoreError::RegionProtected
+            }
             InternalError::Io(_) => CoreError::IoError,
             InternalError::ChipsetEnable(_) | InternalError::SpiInit(_) => {
                 CoreError::ProgrammerError
             }
             InternalError::InvalidDescriptor => CoreError::ProgrammerError,
+            InternalError::OutOfAddressRange { .. } => CoreError::AddressOutOfBounds,
             InternalError::NotSupported(_) => CoreError::OpcodeNotSupported,
         })
     }
 
     fn controller_write(&mut self, addr: u32, data: &[u8]) -> CoreResult<()> {
         self.write(addr, data).map_err(|e| match e {
-            InternalError::AccessDenied { .. } => CoreError::RegionProtected,
+            InternalError::AccessDenied { .. } | InternalError::RegionLocked { .. } => {
+                CoreError::RegionProtected
+            }
             InternalError::Io(_) => CoreError::WriteError { addr },
             InternalError::NotSupported(_) => CoreError::AddressOutOfBounds,
             _ => CoreError::ProgrammerError,
@@ -817,7 +864,9 @@ impl<H: HostAccess> Controller for Spi100Controller<H> {
 
     fn controller_erase(&mut self, addr: u32, len: u32) -> CoreResult<()> {
         self.erase(addr, len).map_err(|e| match e {
-            InternalError::AccessDenied { .. } => CoreError::RegionProtected,
+            InternalError::AccessDenied { .. } | InternalError::RegionLocked { .. } => {
+                CoreError::RegionProtected
+            }
             InternalError::Io(_) => {
                 CoreError::EraseError(rflasher_core::error::EraseFailure::CommandFailed { addr })
             }
@@ -846,8 +895,8 @@ impl<H: HostAccess> Controller for Spi100Controller<H> {
         self.execute_spi_command(cmd)
     }
 
-    fn probe_opcode(&self, _opcode: u8) -> bool {
-        true
+    fn probe_opcode(&self, opcode: u8) -> bool {
+        !rflasher_core::spi::opcodes::DUMMY_CYCLE_READS.contains(&opcode)
     }
 }
 
@@ -916,9 +965,10 @@ impl<H: HostAccess> SpiMaster for Spi100Controller<H> {
         self.execute_spi_command(cmd)
     }
 
-    fn probe_opcode(&self, _opcode: u8) -> bool {
-        // AMD SPI100 doesn't have opcode restrictions like Intel
-        true
+    fn probe_opcode(&self, opcode: u8) -> bool {
+        // AMD SPI100 doesn't have opcode restrictions like Intel, but it
+        // can't send the dummy cycles of fast reads
+        !rflasher_core::spi::opcodes::DUMMY_CYCLE_READS.contains(&opcode)
     }
 
     fn delay_us(&mut self, us: u32) {
@@ -933,10 +983,13 @@ fn map_amd_error(e: InternalError) -> CoreError {
         | InternalError::UnsupportedChipset { .. }
         | InternalError::MultipleChipsets => CoreError::ProgrammerNotReady,
         InternalError::PciAccess(_) | InternalError::MemoryMap { .. } => CoreError::ProgrammerError,
-        InternalError::AccessDenied { .. } => CoreError::RegionProtected,
+        InternalError::AccessDenied { .. } | InternalError::RegionLocked { .. } => {
+            CoreError::RegionProtected
+        }
         InternalError::Io(_) => CoreError::IoError,
         InternalError::ChipsetEnable(_) | InternalError::SpiInit(_) => CoreError::ProgrammerError,
         InternalError::InvalidDescriptor => CoreError::ProgrammerError,
+        InternalError::OutOfAddressRange { .. } => CoreError::AddressOutOfBounds,
         InternalError::NotSupported(_) => CoreError::OpcodeNotSupported,
     }
 }
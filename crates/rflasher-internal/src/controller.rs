@@ -5,6 +5,7 @@
 //! awkward enum matching.
 
 use crate::error::InternalError;
+use crate::ich_descriptors::RegionAccess;
 use crate::ichspi::SpiMode;
 use rflasher_core::error::Result as CoreResult;
 use rflasher_core::programmer::SpiFeatures;
@@ -42,6 +43,13 @@ pub trait Controller {
     /// Get a human-readable name for this controller type
     fn controller_name(&self) -> &'static str;
 
+    /// Get the flash regions and the host's access to them
+    ///
+    /// Only Intel controllers with a valid flash descriptor report regions.
+    fn region_access(&self) -> &[RegionAccess] {
+        &[]
+    }
+
     /// Get the controller sequencing mode reported through the high-level API.
     ///
     /// Controllers without Intel-style sequencing report software sequencing so
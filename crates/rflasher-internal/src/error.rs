@@ -25,8 +25,13 @@ pub enum InternalError {
     SpiInit(&'static str),
     /// Flash access denied by hardware
     AccessDenied { region: &'static str },
+    /// Write access to a flash region denied by the descriptor's FLMSTR
+    RegionLocked { region: &'static str, flmstr: u32 },
     /// Intel Flash Descriptor (IFD) not found or invalid
     InvalidDescriptor,
+    /// Flash access ends beyond what the controller can address in the
+    /// current mode
+    OutOfAddressRange { end: u64, limit: u64 },
     /// Operation not supported by this chipset
     NotSupported(&'static str),
     /// I/O error
@@ -83,7 +88,18 @@ impl fmt::Display for InternalError {
             Self::AccessDenied { region } => {
                 write!(f, "access denied to {} region", region)
             }
+            Self::RegionLocked { region, flmstr } => write!(
+                f,
+                "{} region is not writable by the host (FLMSTR1 = {:#010x})",
+                region, flmstr
+            ),
             Self::InvalidDescriptor => write!(f, "invalid Intel Flash Descriptor"),
+            Self::OutOfAddressRange { end, limit } => write!(
+                f,
+                "flash access up to {:#x} is beyond the {} MiB the SPI controller can address",
+                end,
+                limit >> 20
+            ),
             Self::NotSupported(msg) => write!(f, "not supported: {}", msg),
             Self::Io(msg) => write!(f, "I/O error: {}", msg),
         }
@@ -0,0 +1,213 @@
+//! Intel Flash Descriptor region and master access definitions
+//!
+//! The flash descriptor divides the flash into regions (descriptor, BIOS, ME,
+//! GbE, ...) and grants each bus master read and write access to them in
+//! its FLMSTR register. The SPI controller exposes the descriptor through
+//! the FDOC/FDOD observability registers, so the permissions can be checked
+//! before an operation instead of decoding a failed cycle afterwards.
+//!
+//! Ported from flashprog's ich_descriptors.c.
+
+/// Region names, indexed by FREG number
+pub const REGION_NAMES: [&str; 16] = [
+    "Flash Descriptor",
+    "BIOS",
+    "Management Engine",
+    "Gigabit Ethernet",
+    "Platform Data",
+    "Device Expansion",
+    "BIOS2",
+    "unknown",
+    "EC/BMC",
+    "Device Expansion 2",
+    "Innovation Engine",
+    "10GbE0",
+    "10GbE1",
+    "unknown",
+    "unknown",
+    "PTT",
+];
+
+/// Name of a flash region by FREG number
+pub fn region_name(index: usize) -> &'static str {
+    REGION_NAMES.get(index).copied().unwrap_or("unknown")
+}
+
+/// FDOC section select for the descriptor map (FLVALSIG, FLMAPx)
+pub const FDSS_MAP: u32 = 0;
+
+/// Index of FLMAP0 within the descriptor map section
+pub const FLMAP0: u32 = 1;
+
+/// FDOC section select for the component section (FLCOMP, FLILL, ...)
+pub const FDSS_COMPONENT: u32 = 1;
+
+/// Index of FLCOMP within the component section
+pub const FLCOMP: u32 = 0;
+
+/// FDOC section select for the master section (FLMSTRx)
+pub const FDSS_MASTER: u32 = 3;
+
+/// Index of FLMSTR1 (host CPU/BIOS) within the master section
+pub const FLMSTR_HOST: u32 = 0;
+
+/// Layout of the FLMSTR access bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MasterLayout {
+    /// Descriptor version 1 (pre-100 series): read access in bits 16-23,
+    /// write access in bits 24-31
+    V1,
+    /// Descriptor version 2 (100 series and later): read access in bits
+    /// 8-19, write access in bits 20-31, regions 12-15 in bits 0-7
+    V2,
+}
+
+/// Parsed FLMSTR register of one flash master
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlashMaster {
+    /// Raw FLMSTR value
+    pub flmstr: u32,
+    /// Read access bitmap, bit N = region N
+    pub read: u32,
+    /// Write access bitmap, bit N = region N
+    pub write: u32,
+}
+
+impl FlashMaster {
+    /// Decode an FLMSTR register value
+    pub const fn from_flmstr(flmstr: u32, layout: MasterLayout) -> Self {
+        let (read, write) = match layout {
+            MasterLayout::V1 => ((flmstr >> 16) & 0xff, (flmstr >> 24) & 0xff),
+            MasterLayout::V2 => (
+                ((flmstr >> 8) & 0xfff) | ((flmstr & 0xf) << 12),
+                ((flmstr >> 20) & 0xfff) | (((flmstr >> 4) & 0xf) << 12),
+            ),
+        };
+        Self {
+            flmstr,
+            read,
+            write,
+        }
+    }
+
+    /// Check if this master may read the given region
+    pub const fn can_read(&self, region: usize) -> bool {
+        region < 32 && (self.read >> region) & 1 != 0
+    }
+
+    /// Check if this master may write the given region
+    pub const fn can_write(&self, region: usize) -> bool {
+        region < 32 && (self.write >> region) & 1 != 0
+    }
+}
+
+/// Number of flash components from FLMAP0's NC field
+pub const fn component_count(flmap0: u32) -> usize {
+    ((flmap0 >> 8) & 0x3) as usize + 1
+}
+
+/// Size in bytes of a flash component from its FLCOMP density field
+///
+/// Chipsets before the 8 series use 3-bit fields (512 KiB to 16 MiB),
+/// later ones 4-bit fields (up to 64 MiB). Returns `None` for reserved
+/// encodings.
+pub const fn component_density(flcomp: u32, component: usize, new_density: bool) -> Option<u32> {
+    let (width, max) = if new_density { (4, 7) } else { (3, 5) };
+    let enc = (flcomp >> (component as u32 * width)) & ((1 << width) - 1);
+    if enc > max {
+        return None;
+    }
+    Some(1 << (19 + enc))
+}
+
+/// Access permissions of the host for one flash region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionAccess {
+    /// FREG number
+    pub index: usize,
+    /// Region name
+    pub name: &'static str,
+    /// First byte of the region
+    pub base: u32,
+    /// Last byte of the region (inclusive)
+    pub limit: u32,
+    /// Host may read the region
+    pub can_read: bool,
+    /// Host may write and erase the region
+    pub can_write: bool,
+}
+
+impl RegionAccess {
+    /// Check if the region overlaps `[addr, addr + len)`
+    pub fn overlaps(&self, addr: u32, len: u32) -> bool {
+        len != 0
+            && (addr as u64) <= self.limit as u64
+            && addr as u64 + len as u64 > self.base as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flmstr_v1() {
+        // Typical ICH9-era BIOS master: read FD/BIOS/GbE, write BIOS/GbE
+        let master = FlashMaster::from_flmstr(0x0a0b_0000, MasterLayout::V1);
+        assert_eq!(master.read, 0x0b);
+        assert_eq!(master.write, 0x0a);
+        assert!(master.can_read(0));
+        assert!(master.can_write(1));
+        assert!(!master.can_write(0));
+        assert!(!master.can_write(2));
+    }
+
+    #[test]
+    fn test_flmstr_v2() {
+        // Sunrise Point BIOS master: read FD/BIOS/GbE/PDR, write BIOS/GbE/PDR
+        let master = FlashMaster::from_flmstr(0x01a0_1b00, MasterLayout::V2);
+        assert_eq!(master.read, 0x01b);
+        assert_eq!(master.write, 0x01a);
+        assert!(!master.can_write(2));
+
+        // Extended regions 12-15 live in the low byte
+        let master = FlashMaster::from_flmstr(0x0000_0081, MasterLayout::V2);
+        assert!(master.can_read(12));
+        assert!(master.can_write(15));
+        assert!(!master.can_read(13));
+    }
+
+    #[test]
+    fn test_component_density() {
+        // ICH9-era: component 0 = 4 MiB, component 1 = 2 MiB
+        let flcomp = (2 << 3) | 3;
+        assert_eq!(component_density(flcomp, 0, false), Some(4 << 20));
+        assert_eq!(component_density(flcomp, 1, false), Some(2 << 20));
+        assert_eq!(component_density(6, 0, false), None);
+
+        // New density: 32 MiB and 64 MiB parts, 0xf is reserved
+        assert_eq!(component_density(0x76, 0, true), Some(32 << 20));
+        assert_eq!(component_density(0x76, 1, true), Some(64 << 20));
+        assert_eq!(component_density(0xf0, 1, true), None);
+
+        assert_eq!(component_count(0x0000_0003), 1);
+        assert_eq!(component_count(0x0000_0103), 2);
+    }
+
+    #[test]
+    fn test_region_overlaps() {
+        let region = RegionAccess {
+            index: 2,
+            name: region_name(2),
+            base: 0x1000,
+            limit: 0x1fff,
+            can_read: false,
+            can_write: false,
+        };
+        assert!(region.overlaps(0x0fff, 2));
+        assert!(region.overlaps(0x1fff, 1));
+        assert!(!region.overlaps(0x2000, 0x1000));
+        assert!(!region.overlaps(0x0000, 0x1000));
+        assert!(!region.overlaps(0x1000, 0));
+    }
+}
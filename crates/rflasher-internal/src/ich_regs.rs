@@ -81,6 +81,10 @@ pub const ICH9_REG_OPTYPE: usize = 0x96;
 pub const ICH9_REG_OPMENU: usize = 0x98;
 /// ICH9 BIOS Base Address Configuration (32 bits)
 pub const ICH9_REG_BBAR: usize = 0xA0;
+/// ICH9 Flash Descriptor Observability Control (32 bits)
+pub const ICH9_REG_FDOC: usize = 0xB0;
+/// ICH9 Flash Descriptor Observability Data (32 bits)
+pub const ICH9_REG_FDOD: usize = 0xB4;
 /// ICH8 Vendor Specific Component Capabilities (32 bits)
 pub const ICH8_REG_VSCC: usize = 0xC1;
 /// ICH9 Lower Vendor Specific Component Capabilities (32 bits)
@@ -90,6 +94,14 @@ pub const ICH9_REG_UVSCC: usize = 0xC8;
 /// ICH9 Flash Partition Boundary (32 bits)
 pub const ICH9_REG_FPB: usize = 0xD0;
 
+// FDOC bits
+/// Flash Descriptor Section Index (dword offset within the section)
+pub const FDOC_FDSI_OFF: u32 = 2;
+pub const FDOC_FDSI: u32 = 0x3f << FDOC_FDSI_OFF;
+/// Flash Descriptor Section Select
+pub const FDOC_FDSS_OFF: u32 = 12;
+pub const FDOC_FDSS: u32 = 0x3 << FDOC_FDSS_OFF;
+
 // HSFS bits
 /// Flash Cycle Done
 pub const HSFS_FDONE_OFF: u16 = 0;
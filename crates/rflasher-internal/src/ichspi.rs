@@ -31,7 +31,12 @@ use crate::error::InternalError;
 #[cfg(all(feature = "std", target_os = "linux"))]
 use crate::host::LinuxHost;
 use crate::host::{Bdf, HostAccess, MmioAccess, PciConfigAccess};
+use crate::ich_descriptors::{
+    FDSS_COMPONENT, FDSS_MAP, FDSS_MASTER, FLCOMP, FLMAP0, FLMSTR_HOST, FlashMaster, MasterLayout,
+    RegionAccess, component_count, component_density, region_name,
+};
 use crate::ich_regs::*;
+use alloc::vec::Vec;
 use rflasher_core::error::{Error as CoreError, Result as CoreResult};
 
 #[inline]
@@ -104,6 +109,14 @@ use rflasher_core::spi::SpiCommand;
 /// Maximum SPI data transfer size for hardware sequencing
 pub const HWSEQ_MAX_DATA: usize = 64;
 
+/// Software sequencing sends 3-byte addresses, so it can't go past 16 MiB
+const SWSEQ_ADDR_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// OPMENU slot reprogrammed when a command needs an opcode outside the table
+///
+/// Holds REMS in the default table, which is only needed while probing.
+const SCRATCH_OPCODE_SLOT: usize = 4;
+
 /// SPI controller operating mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum SpiMode {
@@ -229,11 +242,9 @@ struct HwseqData {
     only_4k: bool,
     /// HSFC FCYCLE field mask (differs between ICH9 and PCH100+)
     hsfc_fcycle: u16,
-    /// Size of flash component 0
-    #[allow(dead_code)]
+    /// Size of flash component 0 from FLCOMP (0 if unknown)
     size_comp0: u32,
-    /// Size of flash component 1
-    #[allow(dead_code)]
+    /// Size of flash component 1 from FLCOMP (0 if absent or unknown)
     size_comp1: u32,
 }
 
@@ -245,7 +256,6 @@ struct Opcode {
     /// Opcode type (read/write, with/without address)
     spi_type: u8,
     /// Atomic operation: 0 = none, 1 = preop0, 2 = preop1
-    #[allow(dead_code)]
     atomic: u8,
 }
 
@@ -341,6 +351,10 @@ pub struct IchSpiController<H: HostAccess> {
     opcodes: Option<Opcodes>,
     /// BBAR value
     bbar: u32,
+    /// Host master (FLMSTR1) permissions, if the descriptor restricts them
+    host_master: Option<FlashMaster>,
+    /// Enabled flash regions and the host's access to them
+    regions: Vec<RegionAccess>,
 }
 
 #[cfg(all(feature = "std", target_os = "linux"))]
@@ -434,6 +448,8 @@ impl<H: HostAccess> IchSpiController<H> {
             hwseq,
             opcodes: None,
             bbar: 0,
+            host_master: None,
+            regions: Vec::new(),
         };
 
         // Initialize the controller
@@ -612,7 +628,8 @@ impl<H: HostAccess> IchSpiController<H> {
         }
 
         // Check for descriptor override
-        if hsfs & HSFS_FDOPSS == 0 && self.desc_valid {
+        let desc_override = hsfs & HSFS_FDOPSS == 0;
+        if desc_override && self.desc_valid {
             log::info!(
                 "Flash Descriptor Override Strap-Pin is set. \
                        Master Section restrictions NOT in effect."
@@ -626,6 +643,7 @@ impl<H: HostAccess> IchSpiController<H> {
         if self.desc_valid {
             let hsfc = self.spibar.read16(ICH9_REG_HSFC);
             log::debug!("HSFC: {:#06x}", hsfc);
+            self.read_component_sizes();
         }
 
         // PCH100+ specific: read DLOCK and check SSEQ_LOCKDN
@@ -643,7 +661,7 @@ impl<H: HostAccess> IchSpiController<H> {
 
         // Read FRAP and handle access permissions if descriptor valid
         if self.desc_valid {
-            self.handle_access_permissions()?;
+            self.handle_access_permissions(desc_override)?;
         }
 
         // Handle protected ranges
@@ -676,6 +694,72 @@ impl<H: HostAccess> IchSpiController<H> {
         // Determine operating mode
         self.determine_mode()?;
 
+        // Refuse flash the chosen mode can't address instead of wrapping around
+        let flash_size = self.flash_size() as u64;
+        let limit = self.addr_limit();
+        if flash_size > limit {
+            log::error!(
+                "Flash components total {} MiB, but {} on {} can only address {} MiB",
+                flash_size >> 20,
+                self.mode,
+                self.generation,
+                limit >> 20
+            );
+            return Err(InternalError::OutOfAddressRange {
+                end: flash_size,
+                limit,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Read the flash component sizes from the descriptor's FLCOMP register
+    fn read_component_sizes(&mut self) {
+        let flmap0 = self.read_descriptor_reg(FDSS_MAP, FLMAP0);
+        let flcomp = self.read_descriptor_reg(FDSS_COMPONENT, FLCOMP);
+        log::debug!("FLMAP0: {:#010x}, FLCOMP: {:#010x}", flmap0, flcomp);
+
+        let new_density = self.generation.has_new_component_density();
+        self.hwseq.size_comp0 = component_density(flcomp, 0, new_density).unwrap_or(0);
+        self.hwseq.size_comp1 = if component_count(flmap0) > 1 {
+            component_density(flcomp, 1, new_density).unwrap_or(0)
+        } else {
+            0
+        };
+        log::debug!(
+            "Flash components: {} KiB + {} KiB",
+            self.hwseq.size_comp0 >> 10,
+            self.hwseq.size_comp1 >> 10
+        );
+    }
+
+    /// Total size of the flash components in the descriptor
+    ///
+    /// Returns 0 without a valid descriptor or with reserved density values.
+    pub fn flash_size(&self) -> u32 {
+        self.hwseq.size_comp0 + self.hwseq.size_comp1
+    }
+
+    /// First address the current mode can't reach
+    ///
+    /// Hardware sequencing is limited by the FADDR width, software
+    /// sequencing by the 3-byte addresses it sends.
+    fn addr_limit(&self) -> u64 {
+        if self.mode == SpiMode::HardwareSequencing {
+            self.hwseq.addr_mask as u64 + 1
+        } else {
+            SWSEQ_ADDR_LIMIT
+        }
+    }
+
+    /// Check that `[addr, addr + len)` is addressable in the current mode
+    fn check_addr_limit(&self, addr: u32, len: usize) -> Result<(), InternalError> {
+        let end = addr as u64 + len as u64;
+        let limit = self.addr_limit();
+        if end > limit {
+            return Err(InternalError::OutOfAddressRange { end, limit });
+        }
         Ok(())
     }
 
@@ -733,6 +817,60 @@ impl<H: HostAccess> IchSpiController<H> {
         opcodes
     }
 
+    /// Make an opcode available in the OPMENU table, returning its index
+    ///
+    /// An opcode that isn't in the table replaces the scratch slot. This
+    /// needs an unlocked configuration: HSFS.FLOCKDN (SPIS lockdown on ICH7)
+    /// also freezes PREOP, OPTYPE and OPMENU. A write opcode gets WREN as
+    /// its preop, since `execute` doesn't send WREN on its own.
+    fn ensure_opcode(&mut self, opcode: u8, spi_type: u8) -> Result<usize, InternalError> {
+        if let Some(index) = self.find_opcode_index(opcode) {
+            return Ok(index);
+        }
+
+        if self.locked {
+            log::error!(
+                "Opcode {:#04x} is not in OPMENU and the SPI configuration is locked down, \
+                 so OPMENU can't be reprogrammed",
+                opcode
+            );
+            return Err(InternalError::NotSupported(
+                "Opcode not in OPMENU, which is locked down",
+            ));
+        }
+
+        let mut opcodes = self
+            .opcodes
+            .clone()
+            .ok_or(InternalError::NotSupported("No opcode table"))?;
+        log::debug!(
+            "Reprogramming OPMENU slot {} from {:#04x} to {:#04x}",
+            SCRATCH_OPCODE_SLOT,
+            opcodes.opcode[SCRATCH_OPCODE_SLOT].opcode,
+            opcode
+        );
+        let is_write = spi_type == SPI_OPCODE_TYPE_WRITE_NO_ADDRESS
+            || spi_type == SPI_OPCODE_TYPE_WRITE_WITH_ADDRESS;
+        let atomic = match self.find_preop(JEDEC_WREN) {
+            Some(index) if is_write => index as u8 + 1,
+            _ => 0,
+        };
+        opcodes.opcode[SCRATCH_OPCODE_SLOT] = Opcode {
+            opcode,
+            spi_type,
+            atomic,
+        };
+
+        if self.generation == IchChipset::Ich7 {
+            self.program_ich7_opcodes(&opcodes)?;
+        } else {
+            self.program_opcodes(&opcodes)?;
+        }
+        self.opcodes = Some(opcodes);
+
+        Ok(SCRATCH_OPCODE_SLOT)
+    }
+
     /// Program opcodes to hardware registers
     fn program_opcodes(&self, opcodes: &Opcodes) -> Result<(), InternalError> {
         let preop = (opcodes.preop[0] as u16) | ((opcodes.preop[1] as u16) << 8);
@@ -760,11 +898,40 @@ impl<H: HostAccess> IchSpiController<H> {
         Ok(())
     }
 
-    /// Handle access permissions from FRAP/FREG
-    fn handle_access_permissions(&mut self) -> Result<(), InternalError> {
+    /// Read a descriptor dword through the FDOC/FDOD observability window
+    fn read_descriptor_reg(&self, section: u32, index: u32) -> u32 {
+        self.spibar.write32(
+            ICH9_REG_FDOC,
+            ((section << FDOC_FDSS_OFF) & FDOC_FDSS) | ((index << FDOC_FDSI_OFF) & FDOC_FDSI),
+        );
+        self.spibar.read32(ICH9_REG_FDOD)
+    }
+
+    /// Handle access permissions from FRAP/FREG and the descriptor's FLMSTR1
+    ///
+    /// With the descriptor override strap set, the master section isn't
+    /// enforced and FLMSTR1 is ignored.
+    fn handle_access_permissions(&mut self, desc_override: bool) -> Result<(), InternalError> {
         let frap = self.spibar.read32(ICH9_REG_FRAP);
         log::debug!("FRAP: {:#010x}", frap);
 
+        if !desc_override {
+            let layout = if self.generation.is_pch100_compatible() {
+                MasterLayout::V2
+            } else {
+                MasterLayout::V1
+            };
+            let flmstr = self.read_descriptor_reg(FDSS_MASTER, FLMSTR_HOST);
+            let master = FlashMaster::from_flmstr(flmstr, layout);
+            log::debug!(
+                "FLMSTR1: {:#010x} (read {:#06x}, write {:#06x})",
+                flmstr,
+                master.read,
+                master.write
+            );
+            self.host_master = Some(master);
+        }
+
         let brwa = ((frap >> 8) & 0xFF) as u8;
         let brra = (frap & 0xFF) as u8;
         log::debug!("BRWA: {:#04x}, BRRA: {:#04x}", brwa, brra);
@@ -790,6 +957,7 @@ impl<H: HostAccess> IchSpiController<H> {
 
         // Check each region's access permissions
         let mut restricted = false;
+        self.regions.clear();
         for i in 0..num_freg {
             let offset = if i < 12 {
                 ICH9_REG_FREG0 + i * 4
@@ -817,21 +985,40 @@ impl<H: HostAccess> IchSpiController<H> {
             } else {
                 true
             };
+            // FRAP reflects FLMSTR1 but only covers the first 8 regions on
+            // older layouts, so the descriptor gets the last word
+            let (can_read, can_write) = match &self.host_master {
+                Some(master) => (
+                    can_read && master.can_read(i),
+                    can_write && master.can_write(i),
+                ),
+                None => (can_read, can_write),
+            };
+            self.regions.push(RegionAccess {
+                index: i,
+                name: region_name(i),
+                base,
+                limit,
+                can_read,
+                can_write,
+            });
             let prot = AccessProtection::from_permissions(can_read, can_write);
 
             if prot != AccessProtection::None {
                 restricted = true;
                 log::info!(
-                    "FREG{}: Region {:#010x}-{:#010x} is {:?}",
+                    "FREG{}: {} region {:#010x}-{:#010x} is {:?}",
                     i,
+                    region_name(i),
                     base,
                     limit,
                     prot
                 );
             } else {
                 log::debug!(
-                    "FREG{}: Region {:#010x}-{:#010x} is read-write",
+                    "FREG{}: {} region {:#010x}-{:#010x} is read-write",
                     i,
+                    region_name(i),
                     base,
                     limit
                 );
@@ -991,6 +1178,13 @@ impl<H: HostAccess> IchSpiController<H> {
                     return Err(InternalError::InvalidDescriptor);
                 }
                 SpiMode::HardwareSequencing
+            } else if self.desc_valid && self.flash_size() as u64 > SWSEQ_ADDR_LIMIT {
+                // swseq only sends 3-byte addresses
+                log::info!(
+                    "Enabling hwseq because the flash is larger than {} MiB",
+                    SWSEQ_ADDR_LIMIT >> 20
+                );
+                SpiMode::HardwareSequencing
             } else if self.generation.defaults_to_hwseq() {
                 // PCH100+ defaults to hwseq
                 log::debug!("Enabling hwseq by default for {} series", self.generation);
@@ -1066,7 +1260,6 @@ impl<H: HostAccess> IchSpiController<H> {
     ///
     /// Returns the preop index (0 or 1) if found, None otherwise.
     /// This is used to detect if a command is a preop like WREN or EWSR.
-    #[allow(dead_code)]
     fn find_preop(&self, opcode: u8) -> Option<usize> {
         self.opcodes
             .as_ref()?
@@ -1081,6 +1274,9 @@ impl<H: HostAccess> IchSpiController<H> {
     /// we need to send WREN first. The Intel controller supports "atomic"
     /// operations where it automatically sends a preop before the main command.
     ///
+    /// Opcodes added by `ensure_opcode` carry their preop in the table,
+    /// the others are looked up in a list of known write opcodes.
+    ///
     /// Returns:
     /// - 0 = no preop needed
     /// - 1 = use preop[0] (typically WREN)
@@ -1091,6 +1287,12 @@ impl<H: HostAccess> IchSpiController<H> {
             None => return 0,
         };
 
+        if let Some(op) = opcodes.opcode.iter().find(|op| op.opcode == opcode)
+            && op.atomic != 0
+        {
+            return op.atomic;
+        }
+
         // List of opcodes that require WREN (preop[0])
         // These are write/erase operations that modify flash content or status
         let needs_wren = matches!(
@@ -1249,6 +1451,34 @@ impl<H: HostAccess> IchSpiController<H> {
         self.generation
     }
 
+    /// Get the enabled flash regions and the host's access to them
+    ///
+    /// Empty if the flash descriptor isn't valid.
+    pub fn region_access(&self) -> &[RegionAccess] {
+        &self.regions
+    }
+
+    /// Check that the host may write every region in `[addr, addr + len)`
+    fn check_region_writable(&self, addr: u32, len: u32) -> Result<(), InternalError> {
+        let Some(region) = self
+            .regions
+            .iter()
+            .find(|r| !r.can_write && r.overlaps(addr, len))
+        else {
+            return Ok(());
+        };
+
+        match &self.host_master {
+            Some(master) if !master.can_write(region.index) => Err(InternalError::RegionLocked {
+                region: region.name,
+                flmstr: master.flmstr,
+            }),
+            _ => Err(InternalError::AccessDenied {
+                region: region.name,
+            }),
+        }
+    }
+
     // ========================================================================
     // Hardware Sequencing Operations
     // ========================================================================
@@ -2424,6 +2654,11 @@ impl<H: HostAccess> Controller for IchSpiController<H> {
     }
 
     fn controller_read(&mut self, addr: u32, buf: &mut [u8], _chip_size: usize) -> CoreResult<()> {
+        if let Err(e) = self.check_addr_limit(addr, buf.len()) {
+            log::error!("Cannot read {:#010x}: {}", addr, e);
+            return Err(Self::map_internal_error(e));
+        }
+
         let result = if self.mode == SpiMode::HardwareSequencing {
             self.hwseq_read(addr, buf)
         } else if self.generation == IchChipset::Ich7 {
@@ -2435,6 +2670,15 @@ impl<H: HostAccess> Controller for IchSpiController<H> {
     }
 
     fn controller_write(&mut self, addr: u32, data: &[u8]) -> CoreResult<()> {
+        if let Err(e) = self.check_region_writable(addr, data.len() as u32) {
+            log::error!("Cannot write {:#010x}: {}", addr, e);
+            return Err(Self::map_internal_error(e));
+        }
+        if let Err(e) = self.check_addr_limit(addr, data.len()) {
+            log::error!("Cannot write {:#010x}: {}", addr, e);
+            return Err(Self::map_internal_error(e));
+        }
+
         let result = if self.mode == SpiMode::HardwareSequencing {
             self.hwseq_write(addr, data)
         } else if self.generation == IchChipset::Ich7 {
@@ -2446,6 +2690,15 @@ impl<H: HostAccess> Controller for IchSpiController<H> {
     }
 
     fn controller_erase(&mut self, addr: u32, len: u32) -> CoreResult<()> {
+        if let Err(e) = self.check_region_writable(addr, len) {
+            log::error!("Cannot erase {:#010x}: {}", addr, e);
+            return Err(Self::map_internal_error(e));
+        }
+        if let Err(e) = self.check_addr_limit(addr, len as usize) {
+            log::error!("Cannot erase {:#010x}: {}", addr, e);
+            return Err(Self::map_internal_error(e));
+        }
+
         let result = if self.mode == SpiMode::HardwareSequencing {
             self.hwseq_erase(addr, len)
         } else if self.generation == IchChipset::Ich7 {
@@ -2460,6 +2713,10 @@ impl<H: HostAccess> Controller for IchSpiController<H> {
         "Intel ICH/PCH"
     }
 
+    fn region_access(&self) -> &[RegionAccess] {
+        &self.regions
+    }
+
     fn spi_mode(&self) -> SpiMode {
         self.mode
     }
@@ -2481,12 +2738,18 @@ impl<H: HostAccess> Controller for IchSpiController<H> {
     fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> CoreResult<()> {
         use rflasher_core::spi::{AddressWidth, IoMode};
 
-        // SpiMaster requires software sequencing mode
+        // Hardware sequencing only knows read/write/erase cycles, so other
+        // commands go through the software sequencing registers when allowed
         if self.mode == SpiMode::HardwareSequencing {
-            log::warn!(
-                "SpiMaster::execute() called in hwseq mode - this mode doesn't support raw SPI commands"
-            );
-            return Err(CoreError::OpcodeNotSupported);
+            if self.swseq_locked {
+                log::error!(
+                    "Cannot send opcode {:#04x}: hwseq has no raw SPI commands and swseq \
+                     is locked (DLOCK.SSEQ_LOCKDN=1)",
+                    cmd.opcode
+                );
+                return Err(CoreError::OpcodeNotSupported);
+            }
+            log::debug!("Sending opcode {:#04x} via swseq in hwseq mode", cmd.opcode);
         }
 
         // WREN (0x06) and EWSR (0x50) are preops on Intel, not regular opcodes.
@@ -2557,6 +2820,19 @@ impl<H: HostAccess> Controller for IchSpiController<H> {
             write_len += data_len;
         }
 
+        // Add the opcode to OPMENU if it isn't there yet
+        let spi_type = match (
+            cmd.read_buf.is_empty(),
+            write_len > 1 && cmd.address.is_some(),
+        ) {
+            (true, true) => SPI_OPCODE_TYPE_WRITE_WITH_ADDRESS,
+            (true, false) => SPI_OPCODE_TYPE_WRITE_NO_ADDRESS,
+            (false, true) => SPI_OPCODE_TYPE_READ_WITH_ADDRESS,
+            (false, false) => SPI_OPCODE_TYPE_READ_NO_ADDRESS,
+        };
+        self.ensure_opcode(cmd.opcode, spi_type)
+            .map_err(Self::map_internal_error)?;
+
         // Execute the command - route to appropriate swseq implementation
         let result = if self.generation == IchChipset::Ich7 {
             self.ich7_swseq_send_command(&writearr[..write_len], cmd.read_buf)
@@ -2567,8 +2843,13 @@ impl<H: HostAccess> Controller for IchSpiController<H> {
     }
 
     fn probe_opcode(&self, opcode: u8) -> bool {
-        // In hwseq mode, no raw opcodes are available
-        if self.mode == SpiMode::HardwareSequencing {
+        // In hwseq mode, raw opcodes need the swseq fallback
+        if self.mode == SpiMode::HardwareSequencing && self.swseq_locked {
+            return false;
+        }
+
+        // swseq can't send dummy cycles, even for fast reads in OPMENU
+        if rflasher_core::spi::opcodes::DUMMY_CYCLE_READS.contains(&opcode) {
             return false;
         }
 
@@ -2588,12 +2869,15 @@ impl<H: HostAccess> IchSpiController<H> {
             InternalError::PciAccess(_) | InternalError::MemoryMap { .. } => {
                 CoreError::ProgrammerError
             }
-            InternalError::AccessDenied { .. } => CoreError::RegionProtected,
+            InternalError::AccessDenied { .. } | InternalError::RegionLocked { .. } => {
+                CoreError::RegionProtected
+            }
             InternalError::Io(_) => CoreError::IoError,
             InternalError::ChipsetEnable(_) | InternalError::SpiInit(_) => {
                 CoreError::ProgrammerError
             }
             InternalError::InvalidDescriptor => CoreError::ProgrammerError,
+            InternalError::OutOfAddressRange { .. } => CoreError::AddressOutOfBounds,
             InternalError::NotSupported(_) => CoreError::OpcodeNotSupported,
         }
     }
@@ -2617,7 +2901,7 @@ impl IchSpiController {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::host::tests::FakeHost;
+    use crate::host::tests::{FakeHost, FakeMmio};
     use crate::intel_pci::INTEL_CHIPSETS;
 
     fn detected_with_generation(generation: IchChipset) -> DetectedChipset {
@@ -2636,6 +2920,112 @@ mod tests {
         }
     }
 
+    /// Controller on a fake host with the default opcode table
+    #[cfg(target_os = "linux")]
+    fn fake_controller(generation: IchChipset) -> IchSpiController<FakeHost> {
+        let chipset = detected_with_generation(generation);
+        IchSpiController {
+            host: FakeHost::default(),
+            spibar: FakeMmio,
+            generation,
+            lpc_segment: chipset.domain,
+            lpc_bus: chipset.bus,
+            lpc_device: chipset.device,
+            lpc_function: chipset.function,
+            locked: false,
+            swseq_locked: false,
+            desc_valid: false,
+            requested_mode: SpiMode::SoftwareSequencing,
+            mode: SpiMode::SoftwareSequencing,
+            swseq: SwseqRegs {
+                ssfsc: ICH9_REG_SSFS,
+                preop: ICH9_REG_PREOP,
+                optype: ICH9_REG_OPTYPE,
+                opmenu: ICH9_REG_OPMENU,
+            },
+            ich7_swseq: Ich7SwseqRegs {
+                spis: ICH7_REG_SPIS,
+                spic: ICH7_REG_SPIC,
+                spia: ICH7_REG_SPIA,
+                spid0: ICH7_REG_SPID0,
+                preop: ICH7_REG_PREOP,
+                optype: ICH7_REG_OPTYPE,
+                opmenu: ICH7_REG_OPMENU,
+            },
+            hwseq: HwseqData {
+                addr_mask: ICH9_FADDR_FLA,
+                only_4k: false,
+                hsfc_fcycle: HSFC_FCYCLE,
+                size_comp0: 0,
+                size_comp1: 0,
+            },
+            opcodes: Some(Opcodes::default()),
+            bbar: 0,
+            host_master: None,
+            regions: Vec::new(),
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_ensure_opcode_write_gets_wren_preop() {
+        let mut controller = fake_controller(IchChipset::Ich9);
+
+        // Opcodes in the default table keep their slot
+        assert_eq!(
+            controller
+                .ensure_opcode(JEDEC_RDSR, SPI_OPCODE_TYPE_READ_NO_ADDRESS)
+                .unwrap(),
+            3
+        );
+
+        // WRSR2 isn't in the known write opcodes, so WREN comes from the table
+        assert_eq!(controller.get_atomic_for_opcode(0x31), 0);
+        assert_eq!(
+            controller
+                .ensure_opcode(0x31, SPI_OPCODE_TYPE_WRITE_NO_ADDRESS)
+                .unwrap(),
+            SCRATCH_OPCODE_SLOT
+        );
+        assert_eq!(controller.get_atomic_for_opcode(0x31), 1);
+
+        // A read opcode replacing it doesn't get a preop
+        assert_eq!(
+            controller
+                .ensure_opcode(0x35, SPI_OPCODE_TYPE_READ_NO_ADDRESS)
+                .unwrap(),
+            SCRATCH_OPCODE_SLOT
+        );
+        assert_eq!(controller.get_atomic_for_opcode(0x35), 0);
+        assert_eq!(controller.get_atomic_for_opcode(0x31), 0);
+
+        // WREN in the second preop slot
+        let mut controller = fake_controller(IchChipset::Ich7);
+        controller.opcodes.as_mut().unwrap().preop = [JEDEC_EWSR, JEDEC_WREN];
+        controller
+            .ensure_opcode(0x31, SPI_OPCODE_TYPE_WRITE_NO_ADDRESS)
+            .unwrap();
+        assert_eq!(controller.get_atomic_for_opcode(0x31), 2);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_ensure_opcode_locked() {
+        let mut controller = fake_controller(IchChipset::Ich9);
+        controller.locked = true;
+
+        assert_eq!(
+            controller
+                .ensure_opcode(JEDEC_RDID, SPI_OPCODE_TYPE_READ_NO_ADDRESS)
+                .unwrap(),
+            6
+        );
+        assert!(matches!(
+            controller.ensure_opcode(0x31, SPI_OPCODE_TYPE_WRITE_NO_ADDRESS),
+            Err(InternalError::NotSupported(_))
+        ));
+    }
+
     #[test]
     fn test_spimode_parse_is_no_alloc_case_insensitive() {
         assert_eq!(SpiMode::parse("AUTO"), Some(SpiMode::Auto));
@@ -31,6 +31,15 @@
 //! is detected, a warning will be logged. Users should report success or
 //! failure to help improve testing coverage.
 //!
+//! Chipsets known to be bad are refused. Pass `force_bad=1` to the internal
+//! programmer to try anyway.
+//!
+//! On Intel chipsets with a valid flash descriptor, the host's region
+//! permissions are read from FLMSTR1. Writes and erases that touch a region
+//! the host may not write (typically the ME or GbE region) are refused up
+//! front with [`InternalError::RegionLocked`]; see
+//! [`InternalProgrammer::region_access`].
+//!
 //! # References
 //!
 //! - flashprog/ichspi.c - Intel SPI controller implementation
@@ -49,6 +58,7 @@ pub mod chipset;
 pub mod controller;
 pub mod error;
 pub mod host;
+pub mod ich_descriptors;
 pub mod ich_regs;
 pub mod ichspi;
 pub mod intel_pci;
@@ -64,12 +74,14 @@ pub use error::{InternalError, PciAccessError};
 #[cfg(all(feature = "std", target_os = "linux"))]
 pub use host::LinuxHost;
 pub use host::{Bdf, DefaultPciAccess, HostAccess, MmioAccess, PciConfigAccess};
+pub use ich_descriptors::{FlashMaster, RegionAccess};
 pub use ichspi::{IchSpiController, SpiMode};
 pub use intel_pci::{INTEL_CHIPSETS, INTEL_VID, find_chipset};
 pub use pci::{
     DetectedAmdChipset, PciDevice, find_amd_chipset, find_amd_chipset_in_devices,
-    find_amd_chipset_in_iter, find_intel_chipset, find_intel_chipset_in_devices,
-    find_intel_chipset_in_iter, scan_for_amd_chipsets, scan_for_intel_chipsets, scan_pci_bus,
+    find_amd_chipset_in_iter, find_intel_chipset, find_intel_chipset_any,
+    find_intel_chipset_in_devices, find_intel_chipset_in_iter, scan_for_amd_chipsets,
+    scan_for_intel_chipsets, scan_pci_bus,
 };
 pub use physmap::PhysMap;
 pub use programmer::{InternalOptions, InternalProgrammer, programmer_info};
@@ -197,6 +209,44 @@ impl AnyDetectedChipset {
             Self::Amd(c) => c.log_warnings(),
         }
     }
+
+    /// Get the test status
+    pub fn status(&self) -> TestStatus {
+        match self {
+            Self::Intel(c) => c.status(),
+            Self::Amd(c) => c.status(),
+        }
+    }
+
+    /// Decide whether to continue on this chipset given the user's consent
+    ///
+    /// Untested and configuration-dependent chipsets proceed, with the
+    /// warnings from [`Self::log_warnings`]. Chipsets known to be bad are
+    /// refused unless `force_bad` is set.
+    pub fn check_status(&self, force_bad: bool) -> Result<()> {
+        match self.status() {
+            TestStatus::Bad if !force_bad => {
+                let (vendor_id, device_id, name) = match self {
+                    Self::Intel(c) => (c.enable.vendor_id, c.enable.device_id, c.name()),
+                    Self::Amd(c) => (c.enable.vendor_id, c.enable.device_id, c.name()),
+                };
+                Err(InternalError::UnsupportedChipset {
+                    vendor_id,
+                    device_id,
+                    name,
+                })
+            }
+            TestStatus::Bad => {
+                log::warn!(
+                    "Continuing on unsupported chipset {} {} because force_bad=1 was given",
+                    self.vendor(),
+                    self.name()
+                );
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 /// Scan for supported chipsets (Intel or AMD)
@@ -230,8 +280,21 @@ impl AnyDetectedChipset {
 /// }
 /// ```
 pub fn detect_chipset() -> Result<Option<AnyDetectedChipset>> {
+    let chipset = detect_chipset_any()?;
+    if let Some(chipset) = &chipset {
+        chipset.check_status(false)?;
+    }
+    Ok(chipset)
+}
+
+/// Scan for supported chipsets regardless of their test status
+///
+/// Like [`detect_chipset`], but chipsets known to be bad are returned
+/// rather than refused; use [`AnyDetectedChipset::check_status`] to apply
+/// the user's consent.
+pub fn detect_chipset_any() -> Result<Option<AnyDetectedChipset>> {
     // Try Intel first
-    if let Some(intel) = find_intel_chipset()? {
+    if let Some(intel) = find_intel_chipset_any()? {
         log::info!("Found Intel chipset: {} {}", intel.vendor(), intel.name());
         return Ok(Some(AnyDetectedChipset::Intel(intel)));
     }
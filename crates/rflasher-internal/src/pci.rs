@@ -296,8 +296,29 @@ pub fn scan_for_intel_chipsets() -> Result<alloc::vec::Vec<DetectedChipset>, Int
 }
 
 /// Find a single Intel chipset, warning about duplicates
+///
+/// Chipsets known to be bad are refused; use [`find_intel_chipset_any`] to
+/// get them anyway.
 #[cfg(all(feature = "std", target_os = "linux"))]
 pub fn find_intel_chipset() -> Result<Option<DetectedChipset>, InternalError> {
+    let chipset = find_intel_chipset_any()?;
+
+    if let Some(chipset) = &chipset
+        && chipset.enable.status.is_bad()
+    {
+        return Err(InternalError::UnsupportedChipset {
+            vendor_id: chipset.enable.vendor_id,
+            device_id: chipset.enable.device_id,
+            name: chipset.enable.device_name,
+        });
+    }
+
+    Ok(chipset)
+}
+
+/// Find a single Intel chipset regardless of its test status
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub fn find_intel_chipset_any() -> Result<Option<DetectedChipset>, InternalError> {
     let chipsets = scan_for_intel_chipsets()?;
 
     match chipsets.len() {
@@ -317,15 +338,6 @@ pub fn find_intel_chipset() -> Result<Option<DetectedChipset>, InternalError> {
             // Log any warnings
             chipset.log_warnings();
 
-            // Check if chipset is known bad
-            if chipset.enable.status.is_bad() {
-                return Err(InternalError::UnsupportedChipset {
-                    vendor_id: chipset.enable.vendor_id,
-                    device_id: chipset.enable.device_id,
-                    name: chipset.enable.device_name,
-                });
-            }
-
             Ok(Some(chipset))
         }
         _ => {
@@ -781,6 +793,13 @@ pub fn find_intel_chipset() -> Result<Option<DetectedChipset>, InternalError> {
     ))
 }
 
+#[cfg(not(all(feature = "std", target_os = "linux")))]
+pub fn find_intel_chipset_any() -> Result<Option<DetectedChipset>, InternalError> {
+    Err(InternalError::NotSupported(
+        "PCI scanning only supported on Linux",
+    ))
+}
+
 // =============================================================================
 // AMD Chipset Detection
 // =============================================================================
@@ -10,6 +10,7 @@ use crate::amd_enable::enable_amd_spi100_with_host;
 use crate::controller::Controller;
 use crate::error::InternalError;
 use crate::host::{Bdf, DefaultPciAccess};
+use crate::ich_descriptors::RegionAccess;
 use crate::ichspi::{IchSpiController, SpiMode};
 use crate::{AnyDetectedChipset, DetectedAmdChipset, DetectedChipset};
 
@@ -22,6 +23,8 @@ use rflasher_core::spi::SpiCommand;
 pub struct InternalOptions {
     /// SPI sequencing mode (auto, hwseq, swseq)
     pub mode: SpiMode,
+    /// Proceed even on chipsets known to be bad
+    pub force_bad: bool,
 }
 
 impl InternalOptions {
@@ -36,10 +39,17 @@ impl InternalOptions {
         self
     }
 
+    /// Allow operating on a chipset known to be bad
+    pub fn with_force_bad(mut self, force_bad: bool) -> Self {
+        self.force_bad = force_bad;
+        self
+    }
+
     /// Parse options from key-value pairs (from CLI)
     ///
     /// Supported options:
     /// - ich_spi_mode=auto|hwseq|swseq
+    /// - force_bad=0|1
     pub fn from_options(options: &[(&str, &str)]) -> Result<Self, InternalError> {
         let mut opts = Self::default();
 
@@ -50,6 +60,11 @@ impl InternalOptions {
                         "Invalid ich_spi_mode value (use: auto, hwseq, or swseq)",
                     ))?;
                 }
+                "force_bad" => {
+                    opts.force_bad = parse_flag(value).ok_or(InternalError::NotSupported(
+                        "Invalid force_bad value (use: 0 or 1)",
+                    ))?;
+                }
                 _ => {
                     log::warn!("Unknown internal programmer option: {}={}", key, value);
                 }
@@ -60,6 +75,15 @@ impl InternalOptions {
     }
 }
 
+/// Parse a boolean option value
+fn parse_flag(value: &str) -> Option<bool> {
+    match value {
+        "1" | "yes" | "true" | "on" => Some(true),
+        "0" | "no" | "false" | "off" => Some(false),
+        _ => None,
+    }
+}
+
 /// Internal programmer for Intel ICH/PCH and AMD SPI100 chipsets
 #[cfg(all(feature = "std", target_os = "linux"))]
 pub struct InternalProgrammer {
@@ -84,7 +108,8 @@ impl InternalProgrammer {
     /// Create a new internal programmer with explicit options
     pub fn with_options(options: InternalOptions) -> Result<Self, InternalError> {
         // Detect chipset
-        let chipset = crate::detect_chipset()?.ok_or(InternalError::NoChipset)?;
+        let chipset = crate::detect_chipset_any()?.ok_or(InternalError::NoChipset)?;
+        chipset.check_status(options.force_bad)?;
 
         match chipset {
             AnyDetectedChipset::Intel(intel_chipset) => {
@@ -106,9 +131,9 @@ impl InternalProgrammer {
             log::warn!("Could not enable BIOS writes: {}", e);
         }
 
-        // For now, we can't detect flash size without reading the IFD
-        // or probing the chip. Set to 0 and let caller determine size.
-        let flash_size = 0;
+        // Component sizes from the descriptor, 0 if there is none and the
+        // caller has to determine the size
+        let flash_size = controller.flash_size() as usize;
 
         Ok(Self {
             controller: Box::new(controller),
@@ -170,6 +195,15 @@ impl InternalProgrammer {
         self.controller.spi_mode()
     }
 
+    /// Get the flash regions and the host's access to them
+    ///
+    /// Writes and erases touching a region without write access are refused
+    /// with [`InternalError::RegionLocked`] logged. Empty for AMD and for
+    /// Intel chipsets without a valid flash descriptor.
+    pub fn region_access(&self) -> &[RegionAccess] {
+        self.controller.region_access()
+    }
+
     /// Check if writes are enabled (internal helper)
     fn writes_enabled(&self) -> bool {
         self.controller.writes_enabled()
@@ -205,12 +239,14 @@ impl OpaqueMaster for InternalProgrammer {
 
 /// SpiMaster implementation for raw SPI command execution
 ///
-/// This is only available in software sequencing mode. Hardware sequencing
-/// mode does not allow arbitrary SPI commands - use OpaqueMaster instead.
+/// Commands are sent through software sequencing. In hardware sequencing
+/// mode that is only possible while swseq isn't locked (DLOCK.SSEQ_LOCKDN);
+/// use OpaqueMaster for reads, writes and erases there.
 ///
 /// # Limitations
 ///
-/// - Only opcodes in the OPMENU table can be executed
+/// - Opcodes outside the OPMENU table are added to it, which fails once the
+///   SPI configuration is locked down (FLOCKDN)
 /// - Maximum 64 bytes per transfer
 /// - Only single I/O mode (no dual/quad)
 /// - No 4-byte addressing support (24-bit address max)
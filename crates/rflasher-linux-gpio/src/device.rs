@@ -14,6 +14,10 @@
 //! - **Single I/O (1-1-1)**: Uses MOSI for output and MISO for input
 //! - **Dual I/O**: Uses IO0 (MOSI) and IO1 (MISO) bidirectionally
 //! - **Quad I/O**: Uses IO0-IO3 bidirectionally (requires io2 and io3 pins)
+//!
+//! Outside quad phases IO2/IO3 are driven high so they act as inactive
+//! WP#/HOLD#. Dummy cycles are clocked at the address width with the lines
+//! driven high.
 
 use crate::error::{LinuxGpioError, Result};
 
@@ -22,8 +26,10 @@ use gpiocdev::request::{Config, Request};
 
 use rflasher_core::error::Result as CoreResult;
 use rflasher_core::programmer::bitbang::{self, BitbangDualIo, BitbangQuadIo, BitbangSpiMaster};
-use rflasher_core::programmer::{SpiFeatures, SpiMaster};
-use rflasher_core::spi::{IoMode, SpiCommand};
+use rflasher_core::programmer::{CsLevel, SpiFeatures, SpiMaster};
+use rflasher_core::spi::{IoMode, SpiCommand, check_io_mode_supported};
+
+use std::time::Instant;
 
 /// GPIO line indices
 #[derive(Debug, Clone, Copy)]
@@ -42,6 +48,12 @@ const MAX_LINES: usize = 6;
 /// Default half-period delay in nanoseconds (for ~100 kHz SPI clock)
 const DEFAULT_HALF_PERIOD_NS: u64 = 5000;
 
+/// Number of SCK writes timed when calibrating the bit delay
+const CALIBRATION_GPIO_WRITES: u32 = 1000;
+
+/// Number of delay loop iterations timed when calibrating the bit delay
+const CALIBRATION_LOOPS: u32 = 100_000;
+
 /// Configuration for opening a Linux GPIO SPI device
 #[derive(Debug, Clone)]
 pub struct LinuxGpioSpiConfig {
@@ -61,6 +73,11 @@ pub struct LinuxGpioSpiConfig {
     pub io3: Option<Offset>,
     /// Half-period delay in nanoseconds
     pub half_period_ns: u64,
+    /// Delay loop iterations per half period
+    ///
+    /// `None` calibrates the count against `half_period_ns` when the device
+    /// is opened; see [`LinuxGpioSpi::delay_loops`].
+    pub delay_loops: Option<u32>,
 }
 
 impl Default for LinuxGpioSpiConfig {
@@ -74,6 +91,7 @@ impl Default for LinuxGpioSpiConfig {
             io2: None,
             io3: None,
             half_period_ns: DEFAULT_HALF_PERIOD_NS,
+            delay_loops: None,
         }
     }
 }
@@ -110,7 +128,18 @@ impl LinuxGpioSpiConfig {
         self
     }
 
-    /// Set SPI speed in Hz (approximate, via half-period calculation)
+    /// Use a fixed number of delay loop iterations per half period
+    ///
+    /// Skips calibration at open time, e.g. to reuse a value obtained from
+    /// [`LinuxGpioSpi::delay_loops`] on the same host.
+    pub fn with_delay_loops(mut self, loops: u32) -> Self {
+        self.delay_loops = Some(loops);
+        self
+    }
+
+    /// Set SPI speed in Hz
+    ///
+    /// The delay needed to reach it is calibrated when the device is opened.
     pub fn with_speed_hz(mut self, hz: u32) -> Self {
         // half_period = 1 / (2 * frequency) in seconds
         // = 1_000_000_000 / (2 * frequency) in nanoseconds
@@ -124,6 +153,8 @@ impl LinuxGpioSpiConfig {
 /// Current I/O direction state for multi-IO pins
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum IoDirection {
+    /// IO0 drives MOSI, IO1 reads MISO, IO2/IO3 held high as WP#/HOLD#
+    Single,
     /// Lines are configured for output (write phase)
     Output,
     /// Lines are configured for input (read phase)
@@ -144,10 +175,12 @@ pub struct LinuxGpioSpi {
     offsets: [Offset; MAX_LINES],
     /// Number of I/O lines (2 for single/dual, 4 for quad)
     io_lines: usize,
-    /// Half-period delay in nanoseconds
-    half_period_ns: u64,
+    /// Delay loop iterations per half period
+    delay_loops: u32,
     /// Current direction of multi-IO lines
     io_direction: IoDirection,
+    /// Whether CS is currently asserted
+    cs_active: bool,
 }
 
 impl LinuxGpioSpi {
@@ -179,23 +212,9 @@ impl LinuxGpioSpi {
             2
         };
 
-        // Build line request configuration using gpiocdev
-        // Initial state: CS=1 (high/inactive), SCK=0 (low), MOSI=0, MISO=input
-        let mut req_config = Config::default();
-
-        // Configure output lines: CS, SCK, MOSI
-        req_config.with_line(config.cs).as_output(Value::Active); // CS starts high (inactive)
-        req_config.with_line(config.sck).as_output(Value::Inactive); // SCK starts low
-        req_config.with_line(config.mosi).as_output(Value::Inactive); // MOSI starts low
-
-        // Configure MISO as input
-        req_config.with_line(config.miso).as_input();
-
-        // Configure IO2 and IO3 as input if present
-        if io_lines == 4 {
-            req_config.with_line(config.io2.unwrap()).as_input();
-            req_config.with_line(config.io3.unwrap()).as_input();
-        }
+        // Initial state: CS=1 (high/inactive), SCK=0 (low), MOSI=0, MISO=input,
+        // IO2/IO3 (WP#/HOLD#) high
+        let req_config = Self::line_config(&offsets, io_lines, IoDirection::Single, false);
 
         // Request the lines
         let request = Request::from_config(req_config)
@@ -218,17 +237,71 @@ impl LinuxGpioSpi {
             }
         );
 
-        Ok(Self {
+        let mut spi = Self {
             request,
             offsets,
             io_lines,
-            half_period_ns: config.half_period_ns,
-            io_direction: IoDirection::Input, // Start with I/O lines as inputs
-        })
+            delay_loops: 0,
+            io_direction: IoDirection::Single,
+            cs_active: false,
+        };
+        spi.delay_loops = match config.delay_loops {
+            Some(loops) => loops,
+            None => spi.calibrate_delay(config.half_period_ns),
+        };
+
+        Ok(spi)
+    }
+
+    /// Delay loop iterations used per half clock period
+    pub fn delay_loops(&self) -> u32 {
+        self.delay_loops
+    }
+
+    /// Work out how many delay loop iterations make up a half period
+    ///
+    /// Each half period costs one GPIO write plus the delay, and on fast
+    /// cores the GPIO write alone can take longer than a sleep-free delay
+    /// loop of thousands of iterations. Both are timed here and the GPIO
+    /// cost is subtracted from `half_period_ns`. SCK is toggled with CS
+    /// deasserted, so the flash ignores it.
+    fn calibrate_delay(&mut self, half_period_ns: u64) -> u32 {
+        let start = Instant::now();
+        for i in 0..CALIBRATION_GPIO_WRITES {
+            BitbangSpiMaster::set_sck(self, i % 2 == 0);
+        }
+        BitbangSpiMaster::set_sck(self, false);
+        let gpio_ns = start.elapsed().as_nanos() as u64 / CALIBRATION_GPIO_WRITES as u64;
+
+        let start = Instant::now();
+        delay_loop(CALIBRATION_LOOPS);
+        let loops_ns = (start.elapsed().as_nanos() as u64).max(1);
+
+        let delay_ns = half_period_ns.saturating_sub(gpio_ns);
+        let loops = (delay_ns as u128 * CALIBRATION_LOOPS as u128 / loops_ns as u128)
+            .min(u32::MAX as u128) as u32;
+
+        if gpio_ns > half_period_ns {
+            log::warn!(
+                "linux_gpio_spi: GPIO writes take {} ns, SPI clock limited to ~{} kHz",
+                gpio_ns,
+                500_000 / gpio_ns.max(1)
+            );
+        }
+        log::debug!(
+            "linux_gpio_spi: Calibrated {} delay loops per {} ns half period (GPIO write {} ns)",
+            loops,
+            half_period_ns,
+            gpio_ns
+        );
+
+        loops
     }
 
     /// Perform an SPI transaction (single I/O mode)
     fn spi_transaction(&mut self, write_data: &[u8], read_buf: &mut [u8]) {
+        self.set_io_direction(IoDirection::Single);
+
         // Assert CS (active low)
         BitbangSpiMaster::set_cs(self, true);
 
@@ -245,50 +318,132 @@ impl LinuxGpioSpi {
         BitbangSpiMaster::half_period_delay(self);
     }
 
-    /// Configure I/O lines for output (multi-IO write phase)
-    fn configure_io_output(&mut self) {
-        if self.io_direction == IoDirection::Output {
-            return;
-        }
-
-        // Configure MOSI/IO0 and MISO/IO1 as outputs
+    /// Build a line configuration with the data lines set up for `direction`
+    ///
+    /// Reconfiguring a request replaces the settings of all its lines, so
+    /// CS is carried along at its current level. SCK is always low when the
+    /// data line direction changes.
+    fn line_config(
+        offsets: &[Offset; MAX_LINES],
+        io_lines: usize,
+        direction: IoDirection,
+        cs_active: bool,
+    ) -> Config {
         let mut cfg = Config::default();
-        cfg.with_line(self.offsets[Line::Mosi as usize])
-            .as_output(Value::Inactive);
-        cfg.with_line(self.offsets[Line::Miso as usize])
+        let cs = if cs_active {
+            Value::Inactive
+        } else {
+            Value::Active
+        };
+        cfg.with_line(offsets[Line::Cs as usize]).as_output(cs);
+        cfg.with_line(offsets[Line::Sck as usize])
             .as_output(Value::Inactive);
-        if self.io_lines == 4 {
-            cfg.with_line(self.offsets[Line::Io2 as usize])
-                .as_output(Value::Inactive);
-            cfg.with_line(self.offsets[Line::Io3 as usize])
-                .as_output(Value::Inactive);
+
+        let first = Line::Mosi as usize;
+        for (i, &offset) in offsets[first..first + io_lines].iter().enumerate() {
+            let output = match direction {
+                IoDirection::Output => Some(Value::Inactive),
+                IoDirection::Input => None,
+                IoDirection::Single => match i {
+                    0 => Some(Value::Inactive),
+                    1 => None,
+                    _ => Some(Value::Active),
+                },
+            };
+            match output {
+                Some(value) => {
+                    cfg.with_line(offset).as_output(value);
+                }
+                None => {
+                    cfg.with_line(offset).as_input();
+                }
+            }
         }
 
+        cfg
+    }
+
+    /// Switch the data lines to `direction` if they aren't already
+    fn set_io_direction(&mut self, direction: IoDirection) {
+        if self.io_direction == direction {
+            return;
+        }
+
+        let cfg = Self::line_config(&self.offsets, self.io_lines, direction, self.cs_active);
         if let Err(e) = self.request.reconfigure(&cfg) {
-            log::error!("Failed to configure I/O lines as output: {}", e);
+            log::error!("Failed to configure I/O lines for {:?}: {}", direction, e);
+        }
+        self.io_direction = direction;
+    }
+
+    /// Shift out `bytes` on 1, 2 or 4 data lines
+    fn write_phase(&mut self, lines: u8, bytes: &[u8]) {
+        if bytes.is_empty() {
+            return;
+        }
+        match lines {
+            1 => {
+                self.set_io_direction(IoDirection::Single);
+                bitbang::single::write_bytes(self, bytes);
+            }
+            2 => {
+                self.set_io_direction(IoDirection::Output);
+                bitbang::dual::write_bytes(self, bytes);
+            }
+            _ => {
+                self.set_io_direction(IoDirection::Output);
+                bitbang::quad::write_bytes(self, bytes);
+            }
         }
-        self.io_direction = IoDirection::Output;
     }
 
-    /// Configure I/O lines for input (multi-IO read phase)
-    fn configure_io_input(&mut self) {
-        if self.io_direction == IoDirection::Input {
+    /// Clock `cycles` dummy cycles with the data lines driven high
+    ///
+    /// Driving all ones makes any mode bits inside the dummy phase read as
+    /// 0xFF, so the flash never enters continuous read mode.
+    fn dummy_phase(&mut self, lines: u8, cycles: u8) {
+        if cycles == 0 {
+            return;
+        }
+        if lines == 1 {
+            self.set_io_direction(IoDirection::Single);
+            BitbangSpiMaster::set_mosi(self, true);
+            bitbang::single::run_clock(self, cycles as usize);
             return;
         }
 
-        // Configure MOSI/IO0 and MISO/IO1 as inputs
-        let mut cfg = Config::default();
-        cfg.with_line(self.offsets[Line::Mosi as usize]).as_input();
-        cfg.with_line(self.offsets[Line::Miso as usize]).as_input();
-        if self.io_lines == 4 {
-            cfg.with_line(self.offsets[Line::Io2 as usize]).as_input();
-            cfg.with_line(self.offsets[Line::Io3 as usize]).as_input();
+        self.set_io_direction(IoDirection::Output);
+        for _ in 0..cycles {
+            if lines == 2 {
+                BitbangDualIo::set_sck_set_dual_io(self, false, 0x3);
+            } else {
+                BitbangQuadIo::set_sck_set_quad_io(self, false, 0xF);
+            }
+            self.do_half_period_delay();
+            self.set_sck_val(true);
+            self.do_half_period_delay();
         }
+    }
 
-        if let Err(e) = self.request.reconfigure(&cfg) {
-            log::error!("Failed to configure I/O lines as input: {}", e);
+    /// Shift in `buf` on 1, 2 or 4 data lines
+    fn read_phase(&mut self, lines: u8, buf: &mut [u8]) {
+        if buf.is_empty() {
+            return;
+        }
+        match lines {
+            1 => {
+                self.set_io_direction(IoDirection::Single);
+                bitbang::single::read_bytes(self, buf);
+            }
+            2 => {
+                self.set_io_direction(IoDirection::Input);
+                bitbang::dual::read_bytes(self, buf);
+            }
+            _ => {
+                self.set_io_direction(IoDirection::Input);
+                bitbang::quad::read_bytes(self, buf);
+            }
         }
-        self.io_direction = IoDirection::Input;
     }
 
     /// Set dual I/O lines (IO0/IO1) values
@@ -417,79 +572,30 @@ impl LinuxGpioSpi {
     }
 
     /// Execute an SPI command with multi-IO support
+    ///
+    /// Each phase is shifted on the number of lines the I/O mode gives it,
+    /// with the data lines switched between output and input as needed.
     fn execute_multi_io(&mut self, cmd: &mut SpiCommand<'_>) -> CoreResult<()> {
-        // Build the header: opcode + address + dummy bytes
-        let header_len = cmd.header_len();
-        let mut header = vec![0u8; header_len];
-        cmd.encode_header(&mut header);
+        check_io_mode_supported(cmd.io_mode, self.features())?;
+        let mode = cmd.io_mode;
+
+        let mut addr_buf = [0u8; 4];
+        let addr: &[u8] = match cmd.address {
+            Some(address) => {
+                cmd.address_width.encode(address, &mut addr_buf);
+                &addr_buf[..cmd.address_width.bytes() as usize]
+            }
+            None => &[],
+        };
 
+        self.set_io_direction(IoDirection::Single);
         self.set_cs_active(true);
 
-        match cmd.io_mode {
-            IoMode::Single => {
-                // 1-1-1: All single-wire
-                bitbang::single::write_bytes(self, &header);
-                bitbang::single::write_bytes(self, cmd.write_data);
-                bitbang::single::read_bytes(self, cmd.read_buf);
-            }
-            IoMode::DualOut => {
-                // 1-1-2: Opcode+address single, data dual (read only)
-                bitbang::single::write_bytes(self, &header);
-                bitbang::single::write_bytes(self, cmd.write_data);
-                self.set_idle_io();
-                bitbang::dual::read_bytes(self, cmd.read_buf);
-            }
-            IoMode::DualIo => {
-                // 1-2-2: Opcode single, address+data dual
-                if !header.is_empty() {
-                    bitbang::single::write_byte(self, header[0]); // opcode
-                }
-                self.configure_io_output();
-                bitbang::dual::write_bytes(self, &header[1..]); // address + dummy
-                bitbang::dual::write_bytes(self, cmd.write_data);
-                self.set_idle_io();
-                bitbang::dual::read_bytes(self, cmd.read_buf);
-            }
-            IoMode::QuadOut if self.io_lines == 4 => {
-                // 1-1-4: Opcode+address single, data quad (read only)
-                bitbang::single::write_bytes(self, &header);
-                bitbang::single::write_bytes(self, cmd.write_data);
-                self.set_idle_io();
-                bitbang::quad::read_bytes(self, cmd.read_buf);
-            }
-            IoMode::QuadIo if self.io_lines == 4 => {
-                // 1-4-4: Opcode single, address+data quad
-                if !header.is_empty() {
-                    bitbang::single::write_byte(self, header[0]); // opcode
-                }
-                self.configure_io_output();
-                bitbang::quad::write_bytes(self, &header[1..]); // address + dummy
-                bitbang::quad::write_bytes(self, cmd.write_data);
-                self.set_idle_io();
-                bitbang::quad::read_bytes(self, cmd.read_buf);
-            }
-            IoMode::Qpi if self.io_lines == 4 => {
-                // 4-4-4: Everything quad
-                self.configure_io_output();
-                bitbang::quad::write_bytes(self, &header);
-                bitbang::quad::write_bytes(self, cmd.write_data);
-                self.set_idle_io();
-                bitbang::quad::read_bytes(self, cmd.read_buf);
-            }
-            // Fall back to single I/O for unsupported modes
-            _ => {
-                if cmd.io_mode != IoMode::Single {
-                    log::warn!(
-                        "linux_gpio_spi: {:?} mode not supported (io_lines={}), falling back to single I/O",
-                        cmd.io_mode,
-                        self.io_lines
-                    );
-                }
-                bitbang::single::write_bytes(self, &header);
-                bitbang::single::write_bytes(self, cmd.write_data);
-                bitbang::single::read_bytes(self, cmd.read_buf);
-            }
-        }
+        self.write_phase(mode.cmd_lines(), &[cmd.opcode]);
+        self.write_phase(mode.addr_lines(), addr);
+        self.dummy_phase(mode.addr_lines(), cmd.dummy_cycles);
+        self.write_phase(mode.data_lines(), cmd.write_data);
+        self.read_phase(mode.data_lines(), cmd.read_buf);
 
         // Deassert CS
         self.set_sck_val(false);
@@ -515,15 +621,14 @@ impl LinuxGpioSpi {
     /// Get half period delay value
     #[inline]
     fn do_half_period_delay(&self) {
-        if self.half_period_ns > 0 {
-            std::thread::sleep(std::time::Duration::from_nanos(self.half_period_ns));
-        }
+        delay_loop(self.delay_loops);
     }
 }
 
 // Implement BitbangSpiMaster trait
 impl BitbangSpiMaster for LinuxGpioSpi {
     fn set_cs(&mut self, active: bool) {
+        self.cs_active = active;
         // CS is active low
         let value = if active {
             Value::Inactive
@@ -570,9 +675,7 @@ impl BitbangSpiMaster for LinuxGpioSpi {
     }
 
     fn half_period_delay(&self) {
-        if self.half_period_ns > 0 {
-            std::thread::sleep(std::time::Duration::from_nanos(self.half_period_ns));
-        }
+        delay_loop(self.delay_loops);
     }
 }
 
@@ -580,7 +683,7 @@ impl BitbangSpiMaster for LinuxGpioSpi {
 impl BitbangDualIo for LinuxGpioSpi {
     fn set_sck_set_dual_io(&mut self, sck: bool, io: u8) {
         // Ensure we're in output mode
-        self.configure_io_output();
+        self.set_io_direction(IoDirection::Output);
 
         BitbangSpiMaster::set_sck(self, sck);
         self.set_dual_io(io);
@@ -592,7 +695,7 @@ impl BitbangDualIo for LinuxGpioSpi {
     }
 
     fn set_idle_io(&mut self) {
-        self.configure_io_input();
+        self.set_io_direction(IoDirection::Input);
     }
 }
 
@@ -607,7 +710,7 @@ impl BitbangQuadIo for LinuxGpioSpi {
         }
 
         // Ensure we're in output mode
-        self.configure_io_output();
+        self.set_io_direction(IoDirection::Output);
 
         BitbangSpiMaster::set_sck(self, sck);
         self.set_quad_io(io);
@@ -672,6 +775,24 @@ impl SpiMaster for LinuxGpioSpi {
     fn delay_us(&mut self, us: u32) {
         std::thread::sleep(std::time::Duration::from_micros(us as u64));
     }
+
+    fn send_clocks(&mut self, cycles: u32, cs: CsLevel) -> CoreResult<()> {
+        self.set_io_direction(IoDirection::Single);
+        bitbang::single::send_clocks(self, cycles as usize, cs == CsLevel::Asserted);
+        Ok(())
+    }
+}
+
+/// Busy-wait for `loops` iterations
+///
+/// Bit periods are far below the scheduler's sleep granularity, so the
+/// delay spins instead of sleeping.
+#[inline]
+fn delay_loop(loops: u32) {
+    for i in 0..loops {
+        std::hint::black_box(i);
+        std::hint::spin_loop();
+    }
 }
 
 /// Parse programmer options from a list of key-value pairs
@@ -686,7 +807,7 @@ impl SpiMaster for LinuxGpioSpi {
 /// - `miso=N` or `io1=N` - MISO GPIO line offset (required)
 /// - `io2=N` - IO2 GPIO line offset (optional, for quad mode)
 /// - `io3=N` - IO3 GPIO line offset (optional, for quad mode)
-/// - `spispeed=N` - SPI speed in kHz (optional, default ~100 kHz)
+/// - `spispeed=N` - SPI speed in kHz (optional, default 100 kHz, calibrated at open)
 pub fn parse_options(options: &[(&str, &str)]) -> std::result::Result<LinuxGpioSpiConfig, String> {
     let mut config = LinuxGpioSpiConfig::default();
     let mut have_cs = false;
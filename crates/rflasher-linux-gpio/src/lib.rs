@@ -43,10 +43,18 @@
 //! # Using gpiochip number instead of device path
 //! rflasher probe -p linux_gpio_spi:gpiochip=0,cs=25,sck=11,mosi=10,miso=9
 //!
-//! # With custom SPI speed (in kHz, approximate)
+//! # With custom SPI speed (in kHz)
 //! rflasher read -p linux_gpio_spi:dev=/dev/gpiochip0,cs=25,sck=11,mosi=10,miso=9,spispeed=500 -o flash.bin
 //! ```
 //!
+//! # Clock Speed
+//!
+//! The bit delay is a busy-wait loop calibrated when the device is opened:
+//! the cost of a GPIO write and of the loop itself are measured on the host
+//! and the loop count is chosen so each half clock period matches
+//! `spispeed`. If GPIO writes alone exceed the half period, a warning gives
+//! the highest clock the host can reach.
+//!
 //! # GPIO Pin Wiring
 //!
 //! Connect the SPI flash chip to GPIO pins as follows:
@@ -62,6 +70,15 @@
 //! | WP#       | 3.3V          | Write Protect (tie high to disable) |
 //! | HOLD#     | 3.3V          | Hold (tie high to disable) |
 //!
+//! For dual and quad reads and writes (1-1-2, 1-2-2, 1-1-4, 1-4-4, QPI),
+//! DO/MISO doubles as IO1. For quad modes, wire WP# and HOLD# to GPIOs
+//! given as `io2=` and `io3=` instead of tying them high; they're driven
+//! high whenever they aren't carrying data.
+//!
+//! ```bash
+//! rflasher read -p linux_gpio_spi:dev=/dev/gpiochip0,cs=25,sck=11,mosi=10,miso=9,io2=22,io3=23 -o flash.bin
+//! ```
+//!
 //! # System Requirements
 //!
 //! - Linux kernel 4.8+ with GPIO character device support
@@ -3,7 +3,7 @@
 use crate::error::{LinuxMtdError, Result};
 use log::{debug, info, warn};
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::FileExt;
 use std::os::unix::io::AsRawFd;
 use std::path::Path;
 
@@ -40,10 +40,16 @@ impl LinuxMtdConfig {
 pub struct MtdInfo {
     /// Device name from sysfs
     pub name: String,
+    /// Device type from sysfs ("nor", "nand", "mlc-nand", "ram", ...)
+    pub mtd_type: String,
+    /// Out-of-band (spare) bytes per page, 0 for NOR
+    pub oob_size: u64,
     /// Total size in bytes
     pub total_size: u64,
     /// Erase block size in bytes
     pub erase_size: u64,
+    /// Minimum write unit in bytes (1 for NOR)
+    pub write_size: u64,
     /// Number of erase regions (must be 0 for uniform erase)
     pub num_erase_regions: u64,
     /// Whether the device is writable
@@ -52,6 +58,13 @@ pub struct MtdInfo {
     pub requires_erase: bool,
 }
 
+impl MtdInfo {
+    /// Whether the device is NAND flash and may contain bad blocks
+    pub fn is_nand(&self) -> bool {
+        self.mtd_type.ends_with("nand")
+    }
+}
+
 /// Linux MTD device handle
 ///
 /// This struct wraps a Linux MTD device and implements the OpaqueMaster trait.
@@ -86,7 +99,7 @@ impl LinuxMtd {
     /// # Errors
     /// Returns an error if:
     /// - The device doesn't exist
-    /// - The device is not a NOR flash device
+    /// - The device is not NOR, NAND or RAM-backed (e.g. `mtdram`) flash
     /// - The device has non-uniform erase regions
     /// - The device cannot be opened
     pub fn open(config: &LinuxMtdConfig) -> Result<Self> {
@@ -101,24 +114,25 @@ impl LinuxMtd {
             )));
         }
 
-        // Check device type (must be "nor")
-        let dev_type = read_sysfs_string(&sysfs_path, "type")?;
-        if dev_type != "nor" {
+        // Read device information
+        let info = read_mtd_info(&sysfs_path)?;
+
+        // Check device type ("ram" is accepted for mtdram test devices)
+        if !matches!(info.mtd_type.as_str(), "nor" | "nand" | "mlc-nand" | "ram") {
             return Err(LinuxMtdError::NotNorFlash(format!(
-                "MTD device {} type is '{}', expected 'nor'",
-                dev_num, dev_type
+                "MTD device {} type is '{}', expected 'nor' or 'nand'",
+                dev_num, info.mtd_type
             )));
         }
 
-        // Read device information
-        let info = read_mtd_info(&sysfs_path)?;
-
         debug!(
-            "MTD{}: name='{}', size={}, erase_size={}, writable={}, requires_erase={}",
+            "MTD{}: name='{}', type={}, size={}, erase_size={}, oob_size={}, writable={}, requires_erase={}",
             dev_num,
             info.name,
+            info.mtd_type,
             info.total_size,
             info.erase_size,
+            info.oob_size,
             info.is_writable,
             info.requires_erase
         );
@@ -155,11 +169,85 @@ impl LinuxMtd {
             })?;
 
         info!(
-            "Opened {} successfully (name='{}', size={} bytes, erase_size={} bytes)",
-            dev_path, info.name, info.total_size, info.erase_size
+            "Opened {} successfully (name='{}', type={}, size={} bytes, erase_size={} bytes)",
+            dev_path, info.name, info.mtd_type, info.total_size, info.erase_size
         );
 
-        Ok(Self { file, info })
+        let mtd = Self { file, info };
+        if mtd.info.is_nand() {
+            let bad = mtd.bad_blocks(0, mtd.info.total_size as u32)?;
+            if !bad.is_empty() {
+                warn!(
+                    "MTD{} has {} bad block(s); operations touching them will be refused",
+                    dev_num,
+                    bad.len()
+                );
+            }
+        }
+
+        Ok(mtd)
+    }
+
+    /// Check whether the erase block containing `offset` is marked bad
+    ///
+    /// Always `false` for devices other than NAND, which have no bad block
+    /// table.
+    pub fn is_bad_block(&self, offset: u32) -> Result<bool> {
+        if !self.info.is_nand() {
+            return Ok(false);
+        }
+
+        let ofs = offset as libc::loff_t;
+        // SAFETY: valid file descriptor and a pointer to an initialized loff_t
+        let ret = unsafe { memgetbadblock(self.file.as_raw_fd(), &ofs) }
+            .map_err(|source| LinuxMtdError::BadBlockCheckFailed { offset, source })?;
+        Ok(ret != 0)
+    }
+
+    /// Offsets of the bad erase blocks within `addr..addr + len`
+    pub fn bad_blocks(&self, addr: u32, len: u32) -> Result<Vec<u32>> {
+        let erase_size = self.info.erase_size as u32;
+        let first = addr - addr % erase_size;
+        let end = addr as u64 + len as u64;
+
+        let mut bad = Vec::new();
+        let mut block = first as u64;
+        while block < end {
+            if self.is_bad_block(block as u32)? {
+                bad.push(block as u32);
+            }
+            block += erase_size as u64;
+        }
+        Ok(bad)
+    }
+
+    /// Refuse operations that touch bad blocks
+    ///
+    /// Flash images map 1:1 onto device offsets, so skipping a bad block
+    /// would shift everything after it. Failing is the only safe option;
+    /// returns the offset of the first bad block on failure.
+    fn check_bad_blocks(&self, addr: u32, len: usize) -> std::result::Result<(), u32> {
+        if !self.info.is_nand() || len == 0 {
+            return Ok(());
+        }
+        match self.bad_blocks(addr, len as u32) {
+            Ok(bad) => match bad.first() {
+                Some(&block) => {
+                    log::error!(
+                        "MTD range 0x{:08X}..0x{:08X} contains bad block at 0x{:08X}",
+                        addr,
+                        addr as u64 + len as u64,
+                        block
+                    );
+                    Err(block)
+                }
+                None => Ok(()),
+            },
+            Err(e) => {
+                log::error!("{}", e);
+                Err(addr)
+            }
+        }
     }
 
     /// Get the device information
@@ -176,6 +264,14 @@ impl LinuxMtd {
     pub fn erase_size(&self) -> u64 {
         self.info.erase_size
     }
+
+    /// Check that `addr..addr + len` lies within the device
+    fn check_range(&self, addr: u32, len: usize) -> rflasher_core::error::Result<()> {
+        if addr as u64 + len as u64 > self.info.total_size {
+            return Err(rflasher_core::error::Error::AddressOutOfBounds);
+        }
+        Ok(())
+    }
 }
 
 /// Read a string from a sysfs file and sanitize it
@@ -219,8 +315,9 @@ fn read_mtd_info(sysfs_path: &str) -> Result<MtdInfo> {
     let is_writable = (flags & mtd_flags::MTD_WRITEABLE) != 0;
     let requires_erase = (flags & mtd_flags::MTD_NO_ERASE) == 0;
 
-    // Read name
+    // Read name and type
     let name = read_sysfs_string(sysfs_path, "name")?;
+    let mtd_type = read_sysfs_string(sysfs_path, "type")?;
 
     // Read size
     let total_size = read_sysfs_int(sysfs_path, "size")?;
@@ -228,13 +325,22 @@ fn read_mtd_info(sysfs_path: &str) -> Result<MtdInfo> {
     // Read erase size
     let erase_size = read_sysfs_int(sysfs_path, "erasesize")?;
 
+    // Read minimum write size
+    let write_size = read_sysfs_int(sysfs_path, "writesize")?;
+
+    // Read OOB size (0 for NOR)
+    let oob_size = read_sysfs_int(sysfs_path, "oobsize")?;
+
     // Read number of erase regions
     let num_erase_regions = read_sysfs_int(sysfs_path, "numeraseregions")?;
 
     Ok(MtdInfo {
         name,
+        mtd_type,
+        oob_size,
         total_size,
         erase_size,
+        write_size,
         num_erase_regions,
         is_writable,
         requires_erase,
@@ -254,16 +360,18 @@ struct EraseInfo {
 // Using nix ioctl macros
 nix::ioctl_write_ptr!(memerase, b'M', 2, EraseInfo);
 
+// MEMGETBADBLOCK = _IOW('M', 11, __kernel_loff_t)
+nix::ioctl_write_ptr!(memgetbadblock, b'M', 11, libc::loff_t);
+
 impl rflasher_core::programmer::OpaqueMaster for LinuxMtd {
     fn size(&self) -> usize {
         self.info.total_size as usize
     }
 
     fn read(&mut self, addr: u32, buf: &mut [u8]) -> rflasher_core::error::Result<()> {
-        // Seek to the address
-        self.file
-            .seek(SeekFrom::Start(addr as u64))
-            .map_err(|_| rflasher_core::error::Error::ReadError { addr })?;
+        self.check_range(addr, buf.len())?;
+        self.check_bad_blocks(addr, buf.len())
+            .map_err(|addr| rflasher_core::error::Error::ReadError { addr })?;
 
         // Read in chunks aligned to erase block size for better performance
         let eb_size = self.info.erase_size as usize;
@@ -275,7 +383,7 @@ impl rflasher_core::programmer::OpaqueMaster for LinuxMtd {
             let step = std::cmp::min(eb_size - (pos % eb_size), buf.len() - offset);
 
             self.file
-                .read_exact(&mut buf[offset..offset + step])
+                .read_exact_at(&mut buf[offset..offset + step], pos as u64)
                 .map_err(|_| rflasher_core::error::Error::ReadError {
                     addr: addr + offset as u32,
                 })?;
@@ -290,11 +398,24 @@ impl rflasher_core::programmer::OpaqueMaster for LinuxMtd {
         if !self.info.is_writable {
             return Err(rflasher_core::error::Error::WriteProtected);
         }
-
-        // Seek to the address
-        self.file
-            .seek(SeekFrom::Start(addr as u64))
-            .map_err(|_| rflasher_core::error::Error::WriteError { addr })?;
+        self.check_range(addr, data.len())?;
+        self.check_bad_blocks(addr, data.len())
+            .map_err(|addr| rflasher_core::error::Error::WriteError { addr })?;
+
+        // The kernel rejects writes that aren't multiples of the write unit;
+        // catch that here with a clear message instead of failing mid-way
+        let write_size = self.info.write_size.max(1);
+        if !(addr as u64).is_multiple_of(write_size)
+            || !(data.len() as u64).is_multiple_of(write_size)
+        {
+            log::error!(
+                "MTD write of {} bytes at 0x{:08X} is not aligned to the {}-byte write size",
+                data.len(),
+                addr,
+                write_size
+            );
+            return Err(rflasher_core::error::Error::InvalidAlignment);
+        }
 
         // Write in chunks aligned to erase block size for better performance
         let chunksize = self.info.erase_size as usize;
@@ -306,14 +427,7 @@ impl rflasher_core::programmer::OpaqueMaster for LinuxMtd {
             let step = std::cmp::min(chunksize - (pos % chunksize), data.len() - offset);
 
             self.file
-                .write_all(&data[offset..offset + step])
-                .map_err(|_| rflasher_core::error::Error::WriteError {
-                    addr: addr + offset as u32,
-                })?;
-
-            // Flush after each chunk
-            self.file
-                .flush()
+                .write_all_at(&data[offset..offset + step], pos as u64)
                 .map_err(|_| rflasher_core::error::Error::WriteError {
                     addr: addr + offset as u32,
                 })?;
@@ -325,18 +439,36 @@ impl rflasher_core::programmer::OpaqueMaster for LinuxMtd {
     }
 
     fn erase(&mut self, addr: u32, len: u32) -> rflasher_core::error::Result<()> {
-        if !self.info.requires_erase {
-            // Device doesn't require erase (e.g., RAM-backed MTD)
-            return Ok(());
-        }
-
         if self.info.num_erase_regions != 0 {
             return Err(rflasher_core::error::Error::EraseError(
                 rflasher_core::error::EraseFailure::Unknown,
             ));
         }
+        self.check_range(addr, len as usize)?;
 
+        // MEMERASE works on whole erase blocks, so an unaligned request would
+        // wipe data outside the requested range
         let erase_size = self.info.erase_size as u32;
+        if !addr.is_multiple_of(erase_size) || !len.is_multiple_of(erase_size) {
+            log::error!(
+                "MTD erase of {} bytes at 0x{:08X} is not aligned to the {}-byte erase block size",
+                len,
+                addr,
+                erase_size
+            );
+            return Err(rflasher_core::error::Error::InvalidAlignment);
+        }
+
+        self.check_bad_blocks(addr, len as usize).map_err(|addr| {
+            rflasher_core::error::Error::EraseError(
+                rflasher_core::error::EraseFailure::CommandFailed { addr },
+            )
+        })?;
+
+        if !self.info.requires_erase {
+            // Device doesn't require erase (e.g., RAM-backed MTD)
+            return Ok(());
+        }
 
         // Erase block by block
         let mut offset = 0u32;
@@ -58,6 +58,14 @@ pub enum LinuxMtdError {
         source: nix::errno::Errno,
     },
 
+    /// MEMGETBADBLOCK ioctl failed
+    #[error("Bad block check at offset {offset:#x} failed: {source}")]
+    BadBlockCheckFailed {
+        offset: u32,
+        #[source]
+        source: nix::errno::Errno,
+    },
+
     /// Seek error
     #[error("Seek to offset {offset:#x} failed: {source}")]
     SeekFailed {
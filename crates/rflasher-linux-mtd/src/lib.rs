@@ -50,6 +50,26 @@
 //! rflasher write -p linux_mtd:dev=0 -i firmware.bin
 //! ```
 //!
+//! # Alignment
+//!
+//! Erases must cover whole erase blocks (`erasesize` in sysfs) and writes
+//! must be multiples of the device's write unit (`writesize`, 1 for NOR).
+//! Misaligned requests fail with `InvalidAlignment` before anything is
+//! sent to the kernel.
+//!
+//! # NAND Devices
+//!
+//! NAND MTDs (`nand`, `mlc-nand`) are supported as long as the range being
+//! accessed has no bad blocks. Blocks are checked with `MEMGETBADBLOCK`
+//! before every read, write and erase, and an operation touching a bad block
+//! fails instead of returning garbage or shifting the image; writes must
+//! also be whole pages. `rflasher info` shows the MTD type.
+//!
+//! # Testing
+//!
+//! `tests/mtdram.rs` exercises write and erase against the kernel's
+//! RAM-backed `mtdram` device when built with the `mtdram-test` feature.
+//!
 //! # System Requirements
 //!
 //! - Linux kernel with MTD support (`CONFIG_MTD`)
@@ -71,7 +91,7 @@
 //! cat /sys/class/mtd/mtd0/name
 //! cat /sys/class/mtd/mtd0/size
 //! cat /sys/class/mtd/mtd0/erasesize
-//! cat /sys/class/mtd/mtd0/type  # should be "nor"
+//! cat /sys/class/mtd/mtd0/type  # "nor", "nand" or "mlc-nand"
 //! ```
 
 pub mod device;
@@ -0,0 +1,95 @@
+//! Write and erase tests against a RAM-backed MTD device
+//!
+//! These need an `mtdram` device and write access to it, so they only build
+//! with the `mtdram-test` feature:
+//!
+//! ```bash
+//! sudo modprobe mtdram total_size=1024 erase_size=64
+//! cargo test -p rflasher-linux-mtd --features mtdram-test
+//! ```
+//!
+//! The device is found by its sysfs name; set `RFLASHER_MTD_DEV=N` to use
+//! a specific `/dev/mtdN` instead. If no device is found the tests pass
+//! without doing anything.
+
+#![cfg(feature = "mtdram-test")]
+
+use rflasher_core::error::Error;
+use rflasher_core::programmer::OpaqueMaster;
+use rflasher_linux_mtd::{LinuxMtd, LinuxMtdConfig};
+
+/// Name the mtdram driver registers its device under
+const MTDRAM_NAME: &str = "mtdram test device";
+
+fn find_mtdram() -> Option<u32> {
+    if let Ok(dev) = std::env::var("RFLASHER_MTD_DEV") {
+        return dev.parse().ok();
+    }
+
+    std::fs::read_dir("/sys/class/mtd")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find_map(|entry| {
+            let dir = entry.file_name().into_string().ok()?;
+            let num = dir.strip_prefix("mtd")?.parse().ok()?;
+            let name = std::fs::read_to_string(entry.path().join("name")).ok()?;
+            (name.trim() == MTDRAM_NAME).then_some(num)
+        })
+}
+
+fn open_mtdram() -> Option<LinuxMtd> {
+    let Some(dev) = find_mtdram() else {
+        eprintln!("no mtdram device found, skipping");
+        return None;
+    };
+    Some(LinuxMtd::open(&LinuxMtdConfig::new(dev)).expect("failed to open mtdram device"))
+}
+
+#[test]
+fn test_write_read_back() {
+    let Some(mut mtd) = open_mtdram() else {
+        return;
+    };
+    let eb = mtd.erase_size() as u32;
+
+    mtd.erase(0, 2 * eb).unwrap();
+    if mtd.info().requires_erase {
+        let mut buf = vec![0u8; 2 * eb as usize];
+        mtd.read(0, &mut buf).unwrap();
+        assert!(buf.iter().all(|&b| b == 0xFF));
+    }
+
+    // Straddle the erase block boundary
+    let data: Vec<u8> = (0..eb).map(|i| (i % 251) as u8).collect();
+    let addr = eb / 2;
+    mtd.write(addr, &data).unwrap();
+
+    let mut buf = vec![0u8; data.len()];
+    mtd.read(addr, &mut buf).unwrap();
+    assert_eq!(buf, data);
+}
+
+#[test]
+fn test_unaligned_erase_rejected() {
+    let Some(mut mtd) = open_mtdram() else {
+        return;
+    };
+    let eb = mtd.erase_size() as u32;
+
+    assert_eq!(mtd.erase(1, eb), Err(Error::InvalidAlignment));
+    assert_eq!(mtd.erase(0, eb + 1), Err(Error::InvalidAlignment));
+}
+
+#[test]
+fn test_out_of_range_rejected() {
+    let Some(mut mtd) = open_mtdram() else {
+        return;
+    };
+    let size = mtd.size() as u32;
+    let eb = mtd.erase_size() as u32;
+
+    assert_eq!(mtd.erase(size, eb), Err(Error::AddressOutOfBounds));
+    assert_eq!(mtd.write(size - 1, &[0, 0]), Err(Error::AddressOutOfBounds));
+    let mut buf = [0u8; 2];
+    assert_eq!(mtd.read(size - 1, &mut buf), Err(Error::AddressOutOfBounds));
+}
@@ -6,9 +6,8 @@
 use crate::error::{LinuxSpiError, Result};
 
 use rflasher_core::error::{Error as CoreError, Result as CoreResult};
-use rflasher_core::programmer::default_execute;
 use rflasher_core::programmer::{SpiFeatures, SpiMaster};
-use rflasher_core::spi::SpiCommand;
+use rflasher_core::spi::{SpiCommand, check_io_mode_supported};
 
 use std::fs::{File, OpenOptions};
 use std::os::unix::io::AsRawFd;
@@ -94,6 +93,9 @@ mod ioctl {
     /// Size of spi_ioc_transfer struct (for 64-bit systems)
     pub const SPI_IOC_TRANSFER_SIZE: usize = 32;
 
+    /// Most transfers passed to a single SPI_IOC_MESSAGE(n)
+    pub const MAX_TRANSFERS: usize = u8::MAX as usize;
+
     /// Calculate ioctl number for SPI_IOC_MESSAGE(n)
     pub fn spi_ioc_message(n: u8) -> libc::c_ulong {
         let size = (n as usize) * SPI_IOC_TRANSFER_SIZE;
@@ -122,6 +124,81 @@ struct SpiIocTransfer {
     _pad: u8,             // padding
 }
 
+impl SpiIocTransfer {
+    /// Transmit-only transfer of `buf`
+    fn tx(buf: &[u8], speed_hz: u32) -> Self {
+        Self {
+            tx_buf: buf.as_ptr() as u64,
+            len: buf.len() as u32,
+            speed_hz,
+            bits_per_word: 8,
+            ..Default::default()
+        }
+    }
+
+    /// Receive-only transfer into `buf`
+    fn rx(buf: &mut [u8], speed_hz: u32) -> Self {
+        Self {
+            rx_buf: buf.as_mut_ptr() as u64,
+            len: buf.len() as u32,
+            speed_hz,
+            bits_per_word: 8,
+            ..Default::default()
+        }
+    }
+}
+
+/// Transfers for one SPI command, queued into an SPI_IOC_MESSAGE
+///
+/// The header (opcode, address and dummy bytes) is the only part copied;
+/// write data and the read buffer are handed to the kernel in place.
+struct QueuedCommand {
+    /// Owns the buffer the first transfer points into
+    _header: Vec<u8>,
+    transfers: Vec<SpiIocTransfer>,
+}
+
+impl QueuedCommand {
+    fn new(cmd: &mut SpiCommand<'_>, speed_hz: u32) -> Self {
+        let mut header = vec![0u8; cmd.header_len()];
+        cmd.encode_header(&mut header);
+
+        // The header Vec's heap buffer doesn't move when the struct does,
+        // so its pointer stays valid for as long as `header` is alive
+        let mut transfers = vec![SpiIocTransfer::tx(&header, speed_hz)];
+        if !cmd.write_data.is_empty() {
+            transfers.push(SpiIocTransfer::tx(cmd.write_data, speed_hz));
+        }
+        if !cmd.read_buf.is_empty() {
+            transfers.push(SpiIocTransfer::rx(cmd.read_buf, speed_hz));
+        }
+
+        Self {
+            _header: header,
+            transfers,
+        }
+    }
+}
+
+/// Flatten queued commands into the transfers of one SPI_IOC_MESSAGE
+///
+/// `cs_change` is set on the last transfer of every command but the final
+/// one, so CS is released between commands as if each had its own ioctl.
+/// `delay_usecs` stays 0: the kernel already keeps CS deasserted for its
+/// default CS change delay (10 us), well over the chips' CS high time.
+fn batch_transfers(queued: &[QueuedCommand]) -> Vec<SpiIocTransfer> {
+    let mut transfers = Vec::new();
+    for (i, cmd) in queued.iter().enumerate() {
+        transfers.extend_from_slice(&cmd.transfers);
+        if i + 1 < queued.len()
+            && let Some(last) = transfers.last_mut()
+        {
+            last.cs_change = 1;
+        }
+    }
+    transfers
+}
+
 /// Configuration for opening a Linux SPI device
 #[derive(Debug, Clone)]
 pub struct LinuxSpiConfig {
@@ -256,79 +333,17 @@ impl LinuxSpi {
         Self::open(&LinuxSpiConfig::new(device))
     }
 
-    /// Perform an SPI transfer
-    ///
-    /// This implements the SPI_IOC_MESSAGE ioctl with two transfers:
-    /// 1. Write phase (transmit only)
-    /// 2. Read phase (receive only)
-    fn spi_transfer(&mut self, write_data: &[u8], read_buf: &mut [u8]) -> Result<()> {
-        let fd = self.file.as_raw_fd();
-
-        // Prepare transfer structures
-        // We use two transfers like the reference implementation:
-        // - First transfer: write (tx_buf set, rx_buf null)
-        // - Second transfer: read (tx_buf null, rx_buf set)
-
-        if write_data.is_empty() {
-            return Err(LinuxSpiError::InvalidParameter(
-                "Write data cannot be empty".into(),
-            ));
-        }
-
-        let transfers: Vec<SpiIocTransfer>;
-        let num_transfers: u8;
-
-        if read_buf.is_empty() {
-            // Write-only transfer
-            transfers = vec![SpiIocTransfer {
-                tx_buf: write_data.as_ptr() as u64,
-                rx_buf: 0,
-                len: write_data.len() as u32,
-                speed_hz: self.speed_hz,
-                delay_usecs: 0,
-                bits_per_word: 8,
-                cs_change: 0,
-                tx_nbits: 0,
-                rx_nbits: 0,
-                word_delay_usecs: 0,
-                _pad: 0,
-            }];
-            num_transfers = 1;
-        } else {
-            // Write then read
-            transfers = vec![
-                SpiIocTransfer {
-                    tx_buf: write_data.as_ptr() as u64,
-                    rx_buf: 0,
-                    len: write_data.len() as u32,
-                    speed_hz: self.speed_hz,
-                    delay_usecs: 0,
-                    bits_per_word: 8,
-                    cs_change: 0, // Keep CS asserted
-                    tx_nbits: 0,
-                    rx_nbits: 0,
-                    word_delay_usecs: 0,
-                    _pad: 0,
-                },
-                SpiIocTransfer {
-                    tx_buf: 0,
-                    rx_buf: read_buf.as_mut_ptr() as u64,
-                    len: read_buf.len() as u32,
-                    speed_hz: self.speed_hz,
-                    delay_usecs: 0,
-                    bits_per_word: 8,
-                    cs_change: 0,
-                    tx_nbits: 0,
-                    rx_nbits: 0,
-                    word_delay_usecs: 0,
-                    _pad: 0,
-                },
-            ];
-            num_transfers = 2;
+    /// Submit transfers as a single SPI_IOC_MESSAGE ioctl
+    fn submit(&mut self, transfers: &[SpiIocTransfer]) -> Result<()> {
+        if transfers.is_empty() || transfers.len() > ioctl::MAX_TRANSFERS {
+            return Err(LinuxSpiError::InvalidParameter(format!(
+                "Invalid number of transfers: {}",
+                transfers.len()
+            )));
         }
 
-        // Perform ioctl
-        let ioctl_num = ioctl::spi_ioc_message(num_transfers);
+        let fd = self.file.as_raw_fd();
+        let ioctl_num = ioctl::spi_ioc_message(transfers.len() as u8);
         let ret = unsafe { libc::ioctl(fd, ioctl_num, transfers.as_ptr()) };
 
         if ret < 0 {
@@ -379,10 +394,38 @@ impl SpiMaster for LinuxSpi {
     }
 
     fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> CoreResult<()> {
-        default_execute(cmd, self.features(), |write_data, read_buf| {
-            self.spi_transfer(write_data, read_buf)
-                .map_err(|_| CoreError::ProgrammerError)
-        })
+        check_io_mode_supported(cmd.io_mode, self.features())?;
+
+        // One ioctl per command: header, write data and read phases are
+        // separate transfers of the same message, with CS held throughout
+        let queued = QueuedCommand::new(cmd, self.speed_hz);
+        self.submit(&queued.transfers)
+            .map_err(|_| CoreError::ProgrammerError)
+    }
+
+    fn execute_batch(&mut self, cmds: &mut [SpiCommand<'_>]) -> CoreResult<()> {
+        for cmd in cmds.iter() {
+            check_io_mode_supported(cmd.io_mode, self.features())?;
+        }
+
+        let queued: Vec<_> = cmds
+            .iter_mut()
+            .map(|cmd| QueuedCommand::new(cmd, self.speed_hz))
+            .collect();
+        let transfers = batch_transfers(&queued);
+
+        // spidev refuses messages longer than its buffer in total, so a
+        // batch that doesn't fit goes out one command at a time
+        let total_len: usize = transfers.iter().map(|t| t.len as usize).sum();
+        if transfers.len() > ioctl::MAX_TRANSFERS || total_len > self.max_kernel_buf_size {
+            for cmd in cmds {
+                self.execute(cmd)?;
+            }
+            return Ok(());
+        }
+
+        self.submit(&transfers)
+            .map_err(|_| CoreError::ProgrammerError)
     }
 
     fn delay_us(&mut self, us: u32) {
@@ -448,3 +491,33 @@ pub fn parse_options(options: &[(&str, &str)]) -> std::result::Result<LinuxSpiCo
 
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rflasher_core::spi::opcodes;
+
+    #[test]
+    fn test_batch_releases_cs_between_commands() {
+        let data = [0xA5u8; 256];
+        let mut status = [0u8; 1];
+        let mut cmds = [
+            SpiCommand::simple(opcodes::WREN),
+            SpiCommand::write_3b(opcodes::PP, 0x1000, &data),
+            SpiCommand::read_reg(opcodes::RDSR, &mut status),
+        ];
+        let queued: Vec<_> = cmds
+            .iter_mut()
+            .map(|cmd| QueuedCommand::new(cmd, DEFAULT_SPEED_HZ))
+            .collect();
+        let transfers = batch_transfers(&queued);
+
+        // WREN: opcode; PP: header + data; RDSR: opcode + read
+        let lens: Vec<_> = transfers.iter().map(|t| t.len).collect();
+        assert_eq!(lens, [1, 4, 256, 1, 1]);
+        // CS toggles after WREN and after the program, not after RDSR
+        let cs: Vec<_> = transfers.iter().map(|t| t.cs_change).collect();
+        assert_eq!(cs, [1, 0, 1, 0, 0]);
+        assert!(transfers.iter().all(|t| t.delay_usecs == 0));
+    }
+}
@@ -45,6 +45,12 @@
 //! rflasher read -p linux_spi:dev=/dev/spidev0.0,mode=3 -o flash.bin
 //! ```
 //!
+//! # Performance
+//!
+//! Each SPI command is a single `SPI_IOC_MESSAGE` ioctl: the opcode/address
+//! header, write data and read phases are separate transfers of one message,
+//! so write data and read buffers are never copied.
+//!
 //! # System Requirements
 //!
 //! - Linux kernel with spidev support enabled (`CONFIG_SPI_SPIDEV`)
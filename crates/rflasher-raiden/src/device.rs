@@ -63,6 +63,34 @@ macro_rules! platform_sleep {
     }};
 }
 
+/// Whether a USB interface is a Raiden SPI bridge we can drive
+fn is_raiden_spi_interface(iface: &nusb::InterfaceInfo) -> bool {
+    iface.class() == 0xFF
+        && iface.subclass() == RAIDEN_SPI_SUBCLASS
+        && (iface.protocol() == PROTOCOL_V1 || iface.protocol() == PROTOCOL_V2)
+}
+
+/// Find the first IN and OUT endpoint of interface `interface_num`
+fn find_bulk_endpoints(
+    config: &nusb::descriptors::ConfigurationDescriptor<'_>,
+    interface_num: u8,
+) -> Option<(u8, u8)> {
+    let iface = config
+        .interface_alt_settings()
+        .find(|iface| iface.interface_number() == interface_num)?;
+
+    let mut in_ep = None;
+    let mut out_ep = None;
+    for ep in iface.endpoints() {
+        match ep.direction() {
+            nusb::transfer::Direction::In if in_ep.is_none() => in_ep = Some(ep.address()),
+            nusb::transfer::Direction::Out if out_ep.is_none() => out_ep = Some(ep.address()),
+            _ => {}
+        }
+    }
+    Some((in_ep?, out_ep?))
+}
+
 /// Configuration options for opening a Raiden device
 #[derive(Debug, Clone, Default)]
 pub struct RaidenConfig {
@@ -112,7 +140,7 @@ impl RaidenDebugSpi {
 
     /// Open a Raiden Debug SPI device with specific configuration.
     pub fn open_with_config(config: &RaidenConfig) -> Result<Self> {
-        let devices = Self::find_devices(config.serial.as_deref())?;
+        let devices = Self::find_devices(config.serial.as_deref(), Some(config.target))?;
 
         if devices.is_empty() {
             return Err(RaidenError::DeviceNotFound);
@@ -130,6 +158,13 @@ impl RaidenDebugSpi {
             device_info.address,
             device_info.protocol_version,
         );
+        log::info!(
+            "Using interface {} for target {} (IN EP 0x{:02X}, OUT EP 0x{:02X})",
+            device_info.interface_num,
+            config.target,
+            device_info.in_ep,
+            device_info.out_ep
+        );
 
         let device = device_info
             .info
@@ -163,8 +198,17 @@ impl RaidenDebugSpi {
     }
 
     /// Find all Raiden Debug SPI devices.
-    fn find_devices(serial_filter: Option<&str>) -> Result<Vec<RaidenDeviceInfo>> {
+    ///
+    /// With `target` set, each device contributes the SPI interface for
+    /// that target (see [`select_target_interface`]); devices without one
+    /// are skipped, and if that leaves nothing `TargetNotFound` is returned.
+    /// Without a target the first SPI interface is used.
+    fn find_devices(
+        serial_filter: Option<&str>,
+        target: Option<Target>,
+    ) -> Result<Vec<RaidenDeviceInfo>> {
         let mut devices = Vec::new();
+        let mut target_missing = false;
 
         for dev_info in nusb::list_devices().wait()? {
             if dev_info.vendor_id() != GOOGLE_VID {
@@ -181,69 +225,84 @@ impl RaidenDebugSpi {
                 }
             }
 
-            for iface_info in dev_info.interfaces() {
-                if iface_info.class() != 0xFF
-                    || iface_info.subclass() != RAIDEN_SPI_SUBCLASS
-                    || (iface_info.protocol() != PROTOCOL_V1
-                        && iface_info.protocol() != PROTOCOL_V2)
-                {
-                    continue;
-                }
-
-                let device = match dev_info.open().wait() {
-                    Ok(device) => device,
-                    Err(e) => {
-                        log::debug!("Failed to open device for endpoint discovery: {}", e);
-                        continue;
-                    }
-                };
-
-                let mut in_ep = None;
-                let mut out_ep = None;
+            let candidates: Vec<_> = dev_info
+                .interfaces()
+                .filter(|iface| is_raiden_spi_interface(iface))
+                .collect();
+            if candidates.is_empty() {
+                continue;
+            }
 
-                if let Ok(config) = device.active_configuration() {
-                    for iface in config.interface_alt_settings() {
-                        if iface.interface_number() != iface_info.interface_number() {
+            let index = match target {
+                Some(target) => {
+                    let names: Vec<_> = candidates.iter().map(|i| i.interface_string()).collect();
+                    match select_target_interface(target, &names) {
+                        Some(index) => index,
+                        None => {
+                            log::debug!(
+                                "Device at bus {} address {} has no SPI interface for target {} \
+                                 (interfaces: {:?})",
+                                dev_info.busnum(),
+                                dev_info.device_address(),
+                                target,
+                                names
+                            );
+                            target_missing = true;
                             continue;
                         }
-                        for ep in iface.endpoints() {
-                            match ep.direction() {
-                                nusb::transfer::Direction::In if in_ep.is_none() => {
-                                    in_ep = Some(ep.address())
-                                }
-                                nusb::transfer::Direction::Out if out_ep.is_none() => {
-                                    out_ep = Some(ep.address())
-                                }
-                                _ => {}
-                            }
-                        }
-                        break;
                     }
                 }
+                None => 0,
+            };
+            let iface_info = candidates[index];
 
-                if let (Some(in_ep), Some(out_ep)) = (in_ep, out_ep) {
-                    devices.push(RaidenDeviceInfo {
-                        info: dev_info.clone(),
-                        bus: dev_info.busnum(),
-                        address: dev_info.device_address(),
-                        serial: dev_info.serial_number().map(|s| s.to_string()),
-                        interface_num: iface_info.interface_number(),
-                        in_ep,
-                        out_ep,
-                        protocol_version: iface_info.protocol(),
-                    });
+            let device = match dev_info.open().wait() {
+                Ok(device) => device,
+                Err(e) => {
+                    log::debug!("Failed to open device for endpoint discovery: {}", e);
+                    continue;
                 }
+            };
 
-                break;
+            let endpoints = device
+                .active_configuration()
+                .ok()
+                .and_then(|config| find_bulk_endpoints(&config, iface_info.interface_number()));
+
+            if let Some((in_ep, out_ep)) = endpoints {
+                log::debug!(
+                    "Raiden SPI interface {} ({:?}): IN EP 0x{:02X}, OUT EP 0x{:02X}",
+                    iface_info.interface_number(),
+                    iface_info.interface_string(),
+                    in_ep,
+                    out_ep
+                );
+                devices.push(RaidenDeviceInfo {
+                    info: dev_info.clone(),
+                    bus: dev_info.busnum(),
+                    address: dev_info.device_address(),
+                    serial: dev_info.serial_number().map(|s| s.to_string()),
+                    interface_num: iface_info.interface_number(),
+                    in_ep,
+                    out_ep,
+                    protocol_version: iface_info.protocol(),
+                });
             }
         }
 
+        if devices.is_empty()
+            && target_missing
+            && let Some(target) = target
+        {
+            return Err(RaidenError::TargetNotFound(target));
+        }
+
         Ok(devices)
     }
 
     /// List all connected Raiden Debug SPI devices.
     pub fn list_devices() -> Result<Vec<RaidenDeviceInfo>> {
-        Self::find_devices(None)
+        Self::find_devices(None, None)
     }
 }
 
@@ -285,14 +344,17 @@ impl RaidenDebugSpi {
 
     /// Open a previously granted Raiden WebUSB device.
     pub async fn open(device_info: nusb::DeviceInfo, config: &RaidenConfig) -> Result<Self> {
-        let iface_info = device_info
+        let candidates: Vec<_> = device_info
             .interfaces()
-            .find(|iface| {
-                iface.class() == 0xFF
-                    && iface.subclass() == RAIDEN_SPI_SUBCLASS
-                    && (iface.protocol() == PROTOCOL_V1 || iface.protocol() == PROTOCOL_V2)
-            })
-            .ok_or(RaidenError::DeviceNotFound)?;
+            .filter(|iface| is_raiden_spi_interface(iface))
+            .collect();
+        if candidates.is_empty() {
+            return Err(RaidenError::DeviceNotFound);
+        }
+        let names: Vec<_> = candidates.iter().map(|i| i.interface_string()).collect();
+        let index = select_target_interface(config.target, &names)
+            .ok_or(RaidenError::TargetNotFound(config.target))?;
+        let iface_info = candidates[index];
 
         let interface_num = iface_info.interface_number();
         let protocol_version = iface_info.protocol();
@@ -306,32 +368,17 @@ impl RaidenDebugSpi {
             .active_configuration()
             .map_err(|e| RaidenError::OpenFailed(format!("Failed to get config: {}", e)))?;
 
-        let mut in_ep = None;
-        let mut out_ep = None;
-        for iface in config_desc.interface_alt_settings() {
-            if iface.interface_number() != interface_num {
-                continue;
-            }
-            for ep in iface.endpoints() {
-                match ep.direction() {
-                    nusb::transfer::Direction::In if in_ep.is_none() => in_ep = Some(ep.address()),
-                    nusb::transfer::Direction::Out if out_ep.is_none() => {
-                        out_ep = Some(ep.address())
-                    }
-                    _ => {}
-                }
-            }
-            break;
-        }
-
-        let (in_ep, out_ep) = match (in_ep, out_ep) {
-            (Some(in_ep), Some(out_ep)) => (in_ep, out_ep),
-            _ => {
-                return Err(RaidenError::OpenFailed(
-                    "Failed to discover Raiden bulk endpoints".to_string(),
-                ));
-            }
-        };
+        let (in_ep, out_ep) =
+            find_bulk_endpoints(&config_desc, interface_num).ok_or_else(|| {
+                RaidenError::OpenFailed("Failed to discover Raiden bulk endpoints".to_string())
+            })?;
+        log::info!(
+            "Using interface {} for target {} (IN EP 0x{:02X}, OUT EP 0x{:02X})",
+            interface_num,
+            config.target,
+            in_ep,
+            out_ep
+        );
 
         let interface = device
             .claim_interface(interface_num)
@@ -432,6 +479,16 @@ impl RaidenDebugSpi {
             let rsp = ResponseV2Config::from_bytes(&rsp_buf);
 
             if rsp.packet_id == PacketId::RspUsbSpiConfig as u16 {
+                if !rsp.has_usable_sizes() {
+                    log::warn!(
+                        "V2 config reports unusable sizes (max_write={}, max_read={}), \
+                         keeping V1 transfer sizes",
+                        rsp.max_write_count,
+                        rsp.max_read_count
+                    );
+                    return Ok(());
+                }
+
                 self.max_spi_write = rsp.max_write_count;
                 self.max_spi_read = rsp.max_read_count;
                 self.supports_full_duplex = rsp.supports_full_duplex();
@@ -667,7 +724,7 @@ impl SpiMaster for RaidenDebugSpi {
     }
 
     fn max_write_len(&self) -> usize {
-        self.max_spi_write as usize
+        self.max_spi_write as usize - SPI_CMD_HEADER_LEN
     }
 
     async fn execute(&mut self, cmd: &mut SpiCommand<'_>) -> CoreResult<()> {
@@ -2,6 +2,8 @@
 
 use std::fmt;
 
+use crate::protocol::Target;
+
 /// Result type for Raiden operations
 pub type Result<T> = std::result::Result<T, RaidenError>;
 
@@ -32,6 +34,8 @@ pub enum RaidenError {
     InvalidParameter(String),
     /// Unsupported protocol version
     UnsupportedProtocol(u8),
+    /// The bridge has several SPI interfaces and none is for this target
+    TargetNotFound(Target),
     /// Core library error
     Core(rflasher_core::error::Error),
 }
@@ -88,6 +92,9 @@ impl fmt::Display for RaidenError {
             RaidenError::UnsupportedProtocol(ver) => {
                 write!(f, "Unsupported protocol version: {}", ver)
             }
+            RaidenError::TargetNotFound(target) => {
+                write!(f, "No Raiden SPI interface found for target {}", target)
+            }
             RaidenError::Core(e) => write!(f, "Core error: {}", e),
         }
     }
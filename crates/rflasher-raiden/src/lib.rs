@@ -21,6 +21,10 @@
 //! - **V2**: Extended protocol supporting larger transfers and device capability querying
 //!
 //! The protocol version is determined by the USB interface protocol field.
+//! On V2 bridges the maximum read and write counts are queried at open time
+//! and used as the transaction size, so reads and page programs are no
+//! longer split into 62-byte pieces. If the bridge reports sizes too small
+//! to be useful, the V1 limits are kept.
 //!
 //! # Targets
 //!
@@ -30,6 +34,12 @@
 //! - **EC (Embedded Controller)**: EC firmware flash
 //! - **H1**: Security chip flash
 //!
+//! Most bridges have a single SPI interface and switch targets with the
+//! enable request. Bridges that expose one SPI interface per target (named
+//! "AP", "EC", ... in the interface string) are matched by that name; if
+//! none matches the requested target, opening fails with
+//! [`RaidenError::TargetNotFound`].
+//!
 //! # Example
 //!
 //! ```no_run
@@ -48,6 +48,12 @@ pub const RETRY_DELAY_MS: u64 = 100;
 /// Delay after enabling target for power/flash stabilization
 pub const ENABLE_DELAY_MS: u64 = 50;
 
+/// Bytes sent ahead of the data in a write command (opcode + 4-byte address)
+///
+/// The bridge limits the whole write phase, so the data length reported by
+/// `max_write_len()` has to leave room for the command header.
+pub const SPI_CMD_HEADER_LEN: usize = 5;
+
 // ===========================================================================
 // Protocol V1 Constants
 // ===========================================================================
@@ -237,6 +243,36 @@ impl Target {
             Target::ApCustom => ControlRequest::EnableApCustom,
         }
     }
+
+    /// Whether a USB interface string names this target
+    ///
+    /// Bridges with one SPI interface per target (e.g. Servo V4 with a
+    /// Servo Micro attached) label them "AP", "EC", "H1" and so on. The
+    /// string is split into words so e.g. "EC SPI" matches but "SPEC" doesn't.
+    pub fn matches_interface_name(&self, name: &str) -> bool {
+        let keywords: &[&str] = match self {
+            Target::Ap | Target::ApCustom => &["ap"],
+            Target::Ec => &["ec"],
+            Target::H1 => &["h1", "gsc", "cr50"],
+        };
+        name.split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|word| keywords.iter().any(|k| word.eq_ignore_ascii_case(k)))
+    }
+}
+
+/// Pick the SPI interface to use for `target` on one device
+///
+/// `names` holds the interface strings of the device's Raiden SPI
+/// interfaces in descriptor order. A lone interface serves every target, as
+/// the enable request selects the target. With several, the one whose name
+/// matches `target` is used; `None` means the device doesn't expose it.
+pub fn select_target_interface(target: Target, names: &[Option<&str>]) -> Option<usize> {
+    if names.len() == 1 {
+        return Some(0);
+    }
+    names
+        .iter()
+        .position(|name| name.is_some_and(|name| target.matches_interface_name(name)))
 }
 
 impl std::str::FromStr for Target {
@@ -606,4 +642,54 @@ impl ResponseV2Config {
     pub fn supports_full_duplex(&self) -> bool {
         self.feature_bitmap & features::FULL_DUPLEX != 0
     }
+
+    /// Whether the reported sizes allow a write command with data and a
+    /// non-empty read
+    pub fn has_usable_sizes(&self) -> bool {
+        self.max_write_count as usize > SPI_CMD_HEADER_LEN && self.max_read_count > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Target, interface names, expected interface
+    type Case = (Target, &'static [Option<&'static str>], Option<usize>);
+
+    #[test]
+    fn test_select_target_interface() {
+        let cases: &[Case] = &[
+            // A lone interface serves every target, named or not
+            (Target::Ec, &[None], Some(0)),
+            (Target::H1, &[Some("AP")], Some(0)),
+            // Servo micro: the EC and AP flashes on separate interfaces
+            (Target::Ap, &[Some("EC Flash"), Some("AP Flash")], Some(1)),
+            (Target::Ec, &[Some("EC Flash"), Some("AP Flash")], Some(0)),
+            (
+                Target::ApCustom,
+                &[Some("EC Flash"), Some("AP Flash")],
+                Some(1),
+            ),
+            // GSC aliases, matched case-insensitively on whole words
+            (Target::H1, &[Some("AP"), Some("cr50 spi")], Some(1)),
+            (Target::H1, &[Some("AP"), Some("GSC")], Some(1)),
+            // Keywords inside other words don't match
+            (Target::Ap, &[Some("Capture"), Some("EC")], None),
+            // Unnamed interfaces are skipped
+            (Target::Ec, &[None, Some("ec_spi")], Some(1)),
+            (Target::Ec, &[None, None], None),
+            (Target::Ap, &[Some("EC"), Some("H1")], None),
+            (Target::Ap, &[], None),
+        ];
+        for &(target, names, expected) in cases {
+            assert_eq!(
+                select_target_interface(target, names),
+                expected,
+                "{} in {:?}",
+                target,
+                names
+            );
+        }
+    }
 }
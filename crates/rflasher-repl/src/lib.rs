@@ -9,10 +9,13 @@
 //! - Raw SPI command execution (single, dual, quad I/O modes)
 //! - All standard SPI25 opcodes as constants
 //! - Helper functions for common operations (read-jedec-id, read-status, etc.)
+//! - SFDP parsing and status register decoding
+//! - Flash-level erase and smart write on a probed chip
 //! - Byte vector operations for data manipulation
 //! - Syntax highlighting and bracket matching
 //! - Command history with arrow key navigation
 //! - Tab completion for known functions
+//! - Loading a library of helper definitions before the interactive session
 //!
 //! # Example Session
 //!
@@ -29,6 +32,12 @@
 //! λ > (define data (make-bytes 256 #xAA))
 //! λ > (spi-write PP #x1000 data)
 //! => #t
+//!
+//! λ > (probe)
+//! => "W25Q80.V"
+//!
+//! λ > (smart-write #x1000 (make-bytes 16 0))
+//! => 16
 //! ```
 
 mod error;
@@ -41,6 +50,7 @@ pub use spi_module::{create_constants_module, create_spi_module};
 use crate::highlight::ReplHelper;
 use colored::Colorize;
 use directories::ProjectDirs;
+use rflasher_core::chip::ChipDatabase;
 use rflasher_core::programmer::SpiMaster;
 use rustyline::Editor;
 use rustyline::config::Configurer;
@@ -48,7 +58,7 @@ use rustyline::error::ReadlineError;
 use rustyline::history::FileHistory;
 use std::collections::HashSet;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use steel::rvals::SteelVal;
 use steel::steel_vm::engine::Engine;
@@ -101,6 +111,8 @@ fn collect_globals(_engine: &Engine) -> HashSet<InternedString> {
         "read-status1",
         "read-status2",
         "read-status3",
+        "read-bank-reg",
+        "write-bank-reg",
         "write-enable",
         "write-disable",
         "is-busy?",
@@ -111,6 +123,15 @@ fn collect_globals(_engine: &Engine) -> HashSet<InternedString> {
         "block-erase-64k",
         "page-program",
         "page-program-4b",
+        "read-sfdp",
+        "decode-status",
+        // Flash operations
+        "probe",
+        "chip-size",
+        "read-config-reg",
+        "write-config-reg",
+        "erase-region",
+        "smart-write",
         // Byte utilities
         "make-bytes",
         "random-bytes",
@@ -162,15 +183,15 @@ fn collect_globals(_engine: &Engine) -> HashSet<InternedString> {
     globals
 }
 
-/// Run the Steel REPL with a boxed SPI master
-pub fn run_repl_boxed(master: BoxedSpiMaster) -> Result<(), ReplError> {
+/// Create a Steel engine with the rflasher modules loaded
+fn create_engine(master: BoxedSpiMaster, db: ChipDatabase) -> Result<Engine, ReplError> {
     let mut engine = Engine::new();
 
     // Wrap master in Arc<Mutex> for thread-safe access from Steel
     let master = Arc::new(Mutex::new(master));
 
     // Register the SPI module
-    let module = spi_module::create_spi_module_boxed(Arc::clone(&master));
+    let module = spi_module::create_spi_module_boxed(master, db);
     engine.register_module(module);
 
     // Register the SPI25 constants module
@@ -183,10 +204,67 @@ pub fn run_repl_boxed(master: BoxedSpiMaster) -> Result<(), ReplError> {
             r#"
         (require-builtin rflasher/spi)
         (require-builtin rflasher/spi25)
+
+        ; (read-sfdp) parses the tables, (read-sfdp addr len) reads raw bytes
+        (define (read-sfdp . args)
+          (if (null? args)
+              (sfdp-info)
+              (apply read-sfdp-raw args)))
     "#,
         )
         .map_err(|e| ReplError::SteelError(format!("{}", e)))?;
 
+    Ok(engine)
+}
+
+/// Evaluate a user prelude file in the engine
+///
+/// Errors are reported with the file name but are not fatal, so a broken
+/// helper library doesn't prevent the REPL from starting. Definitions made
+/// before the failing expression stay available.
+fn load_prelude(engine: &mut Engine, path: &Path) {
+    let source = match std::fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!(
+                "{}: failed to read {}: {}",
+                "Error".bright_red().bold(),
+                path.display(),
+                e
+            );
+            return;
+        }
+    };
+
+    match engine.run(source) {
+        Ok(_) => println!("Loaded {}", path.display()),
+        Err(e) => eprintln!(
+            "{} in {}: {}",
+            "Error".bright_red().bold(),
+            path.display(),
+            e
+        ),
+    }
+}
+
+/// Run the Steel REPL with a boxed SPI master
+///
+/// `db` is used by `(probe)` to identify the chip.
+pub fn run_repl_boxed(master: BoxedSpiMaster, db: ChipDatabase) -> Result<(), ReplError> {
+    run_repl_with_prelude_boxed(master, db, None)
+}
+
+/// Run the Steel REPL with a boxed SPI master, loading `prelude` first
+///
+/// The prelude is a Scheme file evaluated before the first prompt, so the
+/// functions and variables it defines are available interactively.
+pub fn run_repl_with_prelude_boxed(
+    master: BoxedSpiMaster,
+    db: ChipDatabase,
+    prelude: Option<&Path>,
+) -> Result<(), ReplError> {
+    let mut engine = create_engine(master, db)?;
+
     // Collect globals for completion/highlighting
     let globals = Arc::new(Mutex::new(collect_globals(&engine)));
 
@@ -213,6 +291,11 @@ pub fn run_repl_boxed(master: BoxedSpiMaster) -> Result<(), ReplError> {
     );
     println!();
 
+    if let Some(path) = prelude {
+        load_prelude(&mut engine, path);
+        println!();
+    }
+
     // Create the prompt
     let prompt = format!("{} ", "λ >".bright_green().bold());
 
@@ -310,30 +393,31 @@ fn print_help() {
 ///
 /// This takes ownership of the SPI master and provides it to the Scheme
 /// environment for executing SPI commands.
-pub fn run_repl<M: SpiMaster + Send + 'static>(master: M) -> Result<(), ReplError> {
-    run_repl_boxed(Box::new(master))
+pub fn run_repl<M: SpiMaster + Send + 'static>(
+    master: M,
+    db: ChipDatabase,
+) -> Result<(), ReplError> {
+    run_repl_boxed(Box::new(master), db)
 }
 
-/// Run a Steel script with a boxed SPI master
-pub fn run_script_boxed(master: BoxedSpiMaster, script: String) -> Result<(), ReplError> {
-    let mut engine = Engine::new();
-
-    let master = Arc::new(Mutex::new(master));
-
-    let module = spi_module::create_spi_module_boxed(Arc::clone(&master));
-    engine.register_module(module);
-
-    let constants_module = spi_module::create_constants_module();
-    engine.register_module(constants_module);
+/// Run the Steel REPL with the given SPI master, loading `prelude` first
+///
+/// See [`run_repl_with_prelude_boxed`].
+pub fn run_repl_with_prelude<M: SpiMaster + Send + 'static>(
+    master: M,
+    db: ChipDatabase,
+    prelude: &Path,
+) -> Result<(), ReplError> {
+    run_repl_with_prelude_boxed(Box::new(master), db, Some(prelude))
+}
 
-    engine
-        .run(
-            r#"
-        (require-builtin rflasher/spi)
-        (require-builtin rflasher/spi25)
-    "#,
-        )
-        .map_err(|e| ReplError::SteelError(format!("{}", e)))?;
+/// Run a Steel script with a boxed SPI master
+pub fn run_script_boxed(
+    master: BoxedSpiMaster,
+    db: ChipDatabase,
+    script: String,
+) -> Result<(), ReplError> {
+    let mut engine = create_engine(master, db)?;
 
     match engine.run(script) {
         Ok(results) => {
@@ -355,7 +439,8 @@ pub fn run_script_boxed(master: BoxedSpiMaster, script: String) -> Result<(), Re
 /// Run a Steel script file with the given SPI master
 pub fn run_script<M: SpiMaster + Send + 'static>(
     master: M,
+    db: ChipDatabase,
     script: String,
 ) -> Result<(), ReplError> {
-    run_script_boxed(Box::new(master), script)
+    run_script_boxed(Box::new(master), db, script)
 }
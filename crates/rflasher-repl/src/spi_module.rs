@@ -1,10 +1,18 @@
 //! SPI module for Steel Scheme
 //!
-//! This module exposes raw SPI commands and helpers to the Scheme environment.
+//! This module exposes raw SPI commands and helpers to the Scheme environment,
+//! plus a few flash-level operations that work on the chip found by `(probe)`.
 
+use rflasher_core::chip::ChipDatabase;
+use rflasher_core::flash::unified::{self, NoProgress};
+use rflasher_core::flash::{FlashContext, SpiFlashDevice, probe_detailed};
+use rflasher_core::layout::Region;
 use rflasher_core::programmer::SpiMaster;
+use rflasher_core::protocol;
+use rflasher_core::sfdp::{self, AddressMode, SfdpInfo};
 use rflasher_core::spi::opcodes;
 use rflasher_core::spi::{AddressWidth, IoMode, SpiCommand};
+use rflasher_core::wp::{RegBitInfo, StatusRegister, WpRegBitMap};
 use std::sync::{Arc, Mutex};
 use steel::rvals::SteelVal;
 use steel::steel_vm::builtin::BuiltInModule;
@@ -16,21 +24,39 @@ pub type BoxedSpiMaster = Box<dyn SpiMaster + Send>;
 /// Type alias for the shared SPI master
 type SharedMaster<M> = Arc<Mutex<M>>;
 
+/// Flash context cached by `(probe)`
+type SharedContext = Arc<Mutex<Option<FlashContext>>>;
+
 /// Create the SPI module with functions bound to a boxed SPI master
-pub fn create_spi_module_boxed(master: Arc<Mutex<BoxedSpiMaster>>) -> BuiltInModule {
-    create_spi_module(master)
+pub fn create_spi_module_boxed(
+    master: Arc<Mutex<BoxedSpiMaster>>,
+    db: ChipDatabase,
+) -> BuiltInModule {
+    create_spi_module(master, db)
 }
 
 /// Create the SPI module with functions bound to the given master
-pub fn create_spi_module<M: SpiMaster + Send + 'static>(master: SharedMaster<M>) -> BuiltInModule {
+///
+/// `db` is used by `(probe)` to identify the chip for the flash-level
+/// functions.
+pub fn create_spi_module<M: SpiMaster + Send + 'static>(
+    master: SharedMaster<M>,
+    db: ChipDatabase,
+) -> BuiltInModule {
     let mut module = BuiltInModule::new("rflasher/spi");
 
     // Register SPI commands that need the master
     register_spi_commands(&mut module, &master);
 
+    // Register flash-level operations that need a probed chip
+    register_flash_commands(&mut module, &master, Arc::new(db));
+
     // Register byte utilities (don't need master)
     register_byte_utilities(&mut module);
 
+    // Register register decoding helpers (don't need master)
+    module.register_fn("decode-status", |reg: isize| decode_status(reg as u8));
+
     // Help function - named rflasher-help to avoid conflict with Steel's built-in help
     module.register_fn("rflasher-help", || {
         print_help();
@@ -212,10 +238,13 @@ fn register_spi_commands<M: SpiMaster + Send + 'static>(
     module.register_fn("release-power-down", move || write_simple(&m, opcodes::RDP));
 
     let m = Arc::clone(master);
-    module.register_fn("read-sfdp", move |addr: isize, len: isize| {
+    module.register_fn("read-sfdp-raw", move |addr: isize, len: isize| {
         read_sfdp(&m, addr as u32, len as usize)
     });
 
+    let m = Arc::clone(master);
+    module.register_fn("sfdp-info", move || sfdp_info(&m));
+
     let m = Arc::clone(master);
     module.register_fn("is-busy?", move || is_busy(&m));
 
@@ -239,6 +268,14 @@ fn register_spi_commands<M: SpiMaster + Send + 'static>(
         write_status(&m, opcodes::WRSR3, value as u8)
     });
 
+    let m = Arc::clone(master);
+    module.register_fn("read-bank-reg", move || read_bank_reg(&m));
+
+    let m = Arc::clone(master);
+    module.register_fn("write-bank-reg", move |value: isize| {
+        write_bank_reg(&m, value as u8)
+    });
+
     let m = Arc::clone(master);
     module.register_fn("sector-erase", move |addr: isize| {
         erase_block(&m, opcodes::SE_20, addr as u32, false)
@@ -266,6 +303,39 @@ fn register_spi_commands<M: SpiMaster + Send + 'static>(
     });
 }
 
+/// Register flash-level functions that share the context cached by `(probe)`
+fn register_flash_commands<M: SpiMaster + Send + 'static>(
+    module: &mut BuiltInModule,
+    master: &SharedMaster<M>,
+    db: Arc<ChipDatabase>,
+) {
+    let ctx: SharedContext = Arc::new(Mutex::new(None));
+
+    let (m, c) = (Arc::clone(master), Arc::clone(&ctx));
+    module.register_fn("probe", move || probe(&m, &db, &c));
+
+    let c = Arc::clone(&ctx);
+    module.register_fn("chip-size", move || chip_size(&c));
+
+    let (m, c) = (Arc::clone(master), Arc::clone(&ctx));
+    module.register_fn("read-config-reg", move || read_config_reg(&m, &c));
+
+    let (m, c) = (Arc::clone(master), Arc::clone(&ctx));
+    module.register_fn("write-config-reg", move |value: isize| {
+        write_config_reg(&m, &c, value as u8)
+    });
+
+    let (m, c) = (Arc::clone(master), Arc::clone(&ctx));
+    module.register_fn("erase-region", move |start: isize, len: isize| {
+        erase_region(&m, &c, start as u32, len as u32)
+    });
+
+    let (m, c) = (Arc::clone(master), Arc::clone(&ctx));
+    module.register_fn("smart-write", move |addr: isize, data: SteelVal| {
+        smart_write(&m, &c, addr as u32, data)
+    });
+}
+
 /// Register byte vector utility functions (independent of master)
 fn register_byte_utilities(module: &mut BuiltInModule) {
     module.register_fn("make-bytes", |len: isize, fill: isize| -> SteelVal {
@@ -449,6 +519,28 @@ pub fn create_constants_module() -> BuiltInModule {
     module.register_value("SUSPEND", SteelVal::IntV(opcodes::SUSPEND as isize));
     module.register_value("RESUME", SteelVal::IntV(opcodes::RESUME as isize));
 
+    // Macronix registers
+    module.register_value("RDCR_MX", SteelVal::IntV(opcodes::RDCR_MX as isize));
+    module.register_value("WRCR_MX", SteelVal::IntV(opcodes::WRCR_MX as isize));
+    module.register_value("RDSCUR", SteelVal::IntV(opcodes::RDSCUR as isize));
+    module.register_value("WRSCUR", SteelVal::IntV(opcodes::WRSCUR as isize));
+    module.register_value("ENSO", SteelVal::IntV(opcodes::ENSO as isize));
+    module.register_value("EXSO", SteelVal::IntV(opcodes::EXSO as isize));
+
+    // ISSI registers
+    module.register_value("RDFR", SteelVal::IntV(opcodes::RDFR as isize));
+    module.register_value("WRFR", SteelVal::IntV(opcodes::WRFR as isize));
+    module.register_value("RDRP", SteelVal::IntV(opcodes::RDRP as isize));
+    module.register_value("SRPV", SteelVal::IntV(opcodes::SRPV as isize));
+    module.register_value("SRPNV", SteelVal::IntV(opcodes::SRPNV as isize));
+
+    // Spansion registers
+    module.register_value("BRRD", SteelVal::IntV(opcodes::BRRD as isize));
+    module.register_value("BRWR", SteelVal::IntV(opcodes::BRWR as isize));
+    module.register_value("BRAC", SteelVal::IntV(opcodes::BRAC as isize));
+    module.register_value("RDCR_SP", SteelVal::IntV(opcodes::RDCR_SP as isize));
+    module.register_value("CLSR", SteelVal::IntV(opcodes::CLSR as isize));
+
     // Status register 1 bits
     module.register_value("SR1_WIP", SteelVal::IntV(opcodes::SR1_WIP as isize));
     module.register_value("SR1_WEL", SteelVal::IntV(opcodes::SR1_WEL as isize));
@@ -724,6 +816,20 @@ fn read_status<M: SpiMaster>(master: &SharedMaster<M>, opcode: u8) -> Result<isi
     Ok(buf[0] as isize)
 }
 
+fn read_bank_reg<M: SpiMaster>(master: &SharedMaster<M>) -> Result<isize, String> {
+    let mut m = master.lock().map_err(|e| format!("lock error: {}", e))?;
+
+    let value = protocol::read_bank_register(&mut *m).map_err(|e| format!("SPI error: {}", e))?;
+    Ok(value as isize)
+}
+
+fn write_bank_reg<M: SpiMaster>(master: &SharedMaster<M>, value: u8) -> Result<bool, String> {
+    let mut m = master.lock().map_err(|e| format!("lock error: {}", e))?;
+
+    protocol::write_bank_register(&mut *m, value).map_err(|e| format!("SPI error: {}", e))?;
+    Ok(true)
+}
+
 fn write_simple<M: SpiMaster>(master: &SharedMaster<M>, opcode: u8) -> Result<bool, String> {
     let mut m = master.lock().map_err(|e| format!("lock error: {}", e))?;
 
@@ -780,6 +886,126 @@ fn read_sfdp<M: SpiMaster>(
     Ok(bytes_to_steel(&buf))
 }
 
+/// Parse the SFDP tables into an assoc list, or `#f` if there are none
+fn sfdp_info<M: SpiMaster>(master: &SharedMaster<M>) -> Result<SteelVal, String> {
+    let mut m = master.lock().map_err(|e| format!("lock error: {}", e))?;
+
+    if !sfdp::is_supported(&mut *m) {
+        return Ok(SteelVal::BoolV(false));
+    }
+
+    match sfdp::probe(&mut *m) {
+        Ok(info) => Ok(sfdp_to_steel(&info)),
+        // Signature present but no usable BFPT
+        Err(rflasher_core::error::Error::ChipNotSupported) => Ok(SteelVal::BoolV(false)),
+        Err(e) => Err(format!("SFDP error: {}", e)),
+    }
+}
+
+fn sfdp_to_steel(info: &SfdpInfo) -> SteelVal {
+    let bfpt = &info.basic_params;
+
+    let address_mode = match bfpt.address_mode {
+        AddressMode::ThreeByteOnly => "3-byte",
+        AddressMode::ThreeOrFourByte => "3-or-4-byte",
+        AddressMode::FourByteOnly => "4-byte",
+    };
+
+    let erase_types = bfpt
+        .sorted_erase_types()
+        .iter()
+        .map(|et| {
+            list(vec![
+                SteelVal::IntV(et.size as isize),
+                SteelVal::IntV(et.opcode as isize),
+            ])
+        })
+        .collect();
+
+    let fast_read = [
+        (bfpt.fast_read_112, "1-1-2"),
+        (bfpt.fast_read_122, "1-2-2"),
+        (bfpt.fast_read_114, "1-1-4"),
+        (bfpt.fast_read_144, "1-4-4"),
+        (bfpt.fast_read_222, "2-2-2"),
+        (bfpt.fast_read_444, "4-4-4"),
+    ]
+    .iter()
+    .filter(|(supported, _)| *supported)
+    .map(|(_, mode)| symbol(mode))
+    .collect();
+
+    list(vec![
+        entry(
+            "revision",
+            SteelVal::StringV(info.header.revision.to_string().into()),
+        ),
+        entry("size", SteelVal::IntV(info.total_size() as isize)),
+        entry("page-size", SteelVal::IntV(info.page_size() as isize)),
+        entry("address-mode", symbol(address_mode)),
+        entry("erase-types", list(erase_types)),
+        entry("fast-read", list(fast_read)),
+        entry(
+            "quad-enable",
+            SteelVal::BoolV(bfpt.quad_enable.is_some_and(|qer| qer.is_needed())),
+        ),
+        entry(
+            "4ba-table",
+            SteelVal::BoolV(info.four_byte_addr_table.is_some()),
+        ),
+    ])
+}
+
+/// Decode an SR1 value into the symbols of the bits that are set
+///
+/// Bit positions follow the standard Winbond layout used by the write
+/// protection code.
+fn decode_status(reg: u8) -> SteelVal {
+    let map = WpRegBitMap::winbond_standard();
+    let is_set = |info: &RegBitInfo| {
+        info.is_present()
+            && info.reg == Some(StatusRegister::Status1)
+            && reg & (1 << info.bit_index) != 0
+    };
+
+    let mut bits = Vec::new();
+    if reg & opcodes::SR1_WIP != 0 {
+        bits.push("wip");
+    }
+    if reg & opcodes::SR1_WEL != 0 {
+        bits.push("wel");
+    }
+    for (i, name) in ["bp0", "bp1", "bp2", "bp3"].into_iter().enumerate() {
+        if is_set(&map.bp[i]) {
+            bits.push(name);
+        }
+    }
+    if is_set(&map.tb) {
+        bits.push("tb");
+    }
+    if is_set(&map.sec) {
+        bits.push("sec");
+    }
+    if is_set(&map.srp) {
+        bits.push("srp0");
+    }
+
+    list(bits.into_iter().map(symbol).collect())
+}
+
+fn symbol(name: &str) -> SteelVal {
+    SteelVal::SymbolV(name.into())
+}
+
+fn list(items: Vec<SteelVal>) -> SteelVal {
+    SteelVal::ListV(items.into_iter().collect())
+}
+
+/// A `(key value)` pair for an assoc list
+fn entry(key: &str, value: SteelVal) -> SteelVal {
+    list(vec![symbol(key), value])
+}
+
 fn is_busy<M: SpiMaster>(master: &SharedMaster<M>) -> Result<bool, String> {
     let status = read_status(master, opcodes::RDSR)?;
     Ok((status & (opcodes::SR1_WIP as isize)) != 0)
@@ -883,6 +1109,129 @@ fn chip_erase<M: SpiMaster>(master: &SharedMaster<M>) -> Result<bool, String> {
     Ok(true)
 }
 
+// =============================================================================
+// Flash-level operations
+// =============================================================================
+
+fn probe<M: SpiMaster>(
+    master: &SharedMaster<M>,
+    db: &ChipDatabase,
+    ctx: &SharedContext,
+) -> Result<String, String> {
+    let mut m = master.lock().map_err(|e| format!("lock error: {}", e))?;
+
+    let result = probe_detailed(&mut *m, db).map_err(|e| format!("probe error: {}", e))?;
+    let name = result.chip.name.clone();
+
+    *ctx.lock().map_err(|e| format!("lock error: {}", e))? = Some(result.into_context());
+
+    Ok(name)
+}
+
+fn chip_size(ctx: &SharedContext) -> Result<isize, String> {
+    let ctx = ctx.lock().map_err(|e| format!("lock error: {}", e))?;
+    ctx.as_ref()
+        .map(|c| c.total_size() as isize)
+        .ok_or_else(|| "no chip probed, run (probe) first".to_string())
+}
+
+/// Features of the probed chip
+fn chip_features(ctx: &SharedContext) -> Result<rflasher_core::chip::Features, String> {
+    let ctx = ctx.lock().map_err(|e| format!("lock error: {}", e))?;
+    ctx.as_ref()
+        .map(|c| c.chip.features)
+        .ok_or_else(|| "no chip probed, run (probe) first".to_string())
+}
+
+/// Error message for a configuration register access
+fn config_reg_error(e: rflasher_core::error::Error) -> String {
+    match e {
+        rflasher_core::error::Error::ChipNotSupported => {
+            "the probed chip has no configuration or function register".to_string()
+        }
+        e => format!("SPI error: {}", e),
+    }
+}
+
+fn read_config_reg<M: SpiMaster>(
+    master: &SharedMaster<M>,
+    ctx: &SharedContext,
+) -> Result<isize, String> {
+    let features = chip_features(ctx)?;
+    let mut m = master.lock().map_err(|e| format!("lock error: {}", e))?;
+
+    let value = protocol::read_config_register(&mut *m, features).map_err(config_reg_error)?;
+    Ok(value as isize)
+}
+
+fn write_config_reg<M: SpiMaster>(
+    master: &SharedMaster<M>,
+    ctx: &SharedContext,
+    value: u8,
+) -> Result<bool, String> {
+    let features = chip_features(ctx)?;
+    let mut m = master.lock().map_err(|e| format!("lock error: {}", e))?;
+
+    protocol::write_config_register(&mut *m, features, value).map_err(config_reg_error)?;
+    Ok(true)
+}
+
+/// Run a flash-level operation on the probed chip
+///
+/// The device borrows the master for the duration of `op`; any context
+/// change it makes (such as the address mode) is kept for the next call.
+fn with_device<M: SpiMaster, T>(
+    master: &SharedMaster<M>,
+    ctx: &SharedContext,
+    op: impl FnOnce(&mut SpiFlashDevice<&mut M>) -> rflasher_core::error::Result<T>,
+) -> Result<T, String> {
+    let mut ctx = ctx.lock().map_err(|e| format!("lock error: {}", e))?;
+    let flash_ctx = ctx
+        .clone()
+        .ok_or_else(|| "no chip probed, run (probe) first".to_string())?;
+    let mut m = master.lock().map_err(|e| format!("lock error: {}", e))?;
+
+    let mut device = SpiFlashDevice::new(&mut *m, flash_ctx);
+    let result = op(&mut device);
+    *ctx = Some(device.into_context());
+
+    result.map_err(|e| format!("flash error: {}", e))
+}
+
+fn erase_region<M: SpiMaster>(
+    master: &SharedMaster<M>,
+    ctx: &SharedContext,
+    start: u32,
+    len: u32,
+) -> Result<bool, String> {
+    if len == 0 {
+        return Err("erase-region: length must be non-zero".to_string());
+    }
+    let end = start
+        .checked_add(len - 1)
+        .ok_or_else(|| "erase-region: range overflows".to_string())?;
+    let region = Region::new("repl", start, end);
+
+    with_device(master, ctx, |device| unified::erase_region(device, &region))?;
+
+    Ok(true)
+}
+
+fn smart_write<M: SpiMaster>(
+    master: &SharedMaster<M>,
+    ctx: &SharedContext,
+    addr: u32,
+    data: SteelVal,
+) -> Result<isize, String> {
+    let bytes = steel_to_bytes(&data)?;
+
+    let stats = with_device(master, ctx, |device| {
+        unified::smart_write_region(device, addr, &bytes, &mut NoProgress)
+    })?;
+
+    Ok(stats.bytes_written as isize)
+}
+
 fn print_help() {
     println!(
         r#"
@@ -939,6 +1288,8 @@ HIGH-LEVEL HELPERS
 (write-status1 value)   Write status register 1.
 (write-status2 value)   Write status register 2.
 (write-status3 value)   Write status register 3.
+(read-bank-reg)         Read the Spansion bank register (BRRD).
+(write-bank-reg value)  Write the Spansion bank register (BRWR).
 (write-enable)          Send Write Enable command.
 (write-disable)         Send Write Disable command.
 (is-busy?)              Check if WIP bit is set.
@@ -955,7 +1306,28 @@ HIGH-LEVEL HELPERS
 (reset)                 Send Reset command.
 (deep-power-down)       Enter deep power-down mode.
 (release-power-down)    Release from deep power-down.
-(read-sfdp addr len)    Read SFDP data.
+(read-sfdp)             Parse SFDP into an assoc list (size, page-size,
+                        address-mode, erase-types, fast-read, ...), or #f
+                        if the chip has no SFDP.
+(read-sfdp addr len)    Read raw SFDP bytes.
+(decode-status reg)     Decode an SR1 value into the set bits, e.g.
+                        (decode-status (read-status1)) => (wel bp0 srp0).
+
+FLASH OPERATIONS
+----------------
+(probe)                 Identify the chip, returns its name. Required by
+                        the functions below.
+(chip-size)             Size of the probed chip in bytes.
+(read-config-reg)       Read the Macronix configuration register (RDCR)
+                        or the ISSI function register (RDFR).
+(write-config-reg value)
+                        Write that register. ISSI function register bits
+                        are OTP on most parts.
+(erase-region start len)
+                        Erase len bytes from start, preserving data outside
+                        the range in partially covered erase blocks.
+(smart-write addr data) Write data at addr, erasing and programming only
+                        what differs. Returns the number of bytes written.
 
 BYTE UTILITIES
 --------------
@@ -1010,6 +1382,11 @@ EXAMPLES
 > (equal? data (spi-read READ #x1000 256))
 #t
 
+; Patch a few bytes without touching the rest of the sector
+> (probe)
+"W25Q80.V"
+> (smart-write #x1010 (list->bytes '(1 2 3 4)))
+
 (quit) or (exit) to exit the REPL.
 "#
     );
@@ -10,7 +10,7 @@ use crate::transport::Transport;
 
 use maybe_async::maybe_async;
 use rflasher_core::error::{Error as CoreError, Result as CoreResult};
-use rflasher_core::programmer::{SpiFeatures, SpiMaster};
+use rflasher_core::programmer::{CsLevel, SpiFeatures, SpiMaster};
 use rflasher_core::spi::{SpiCommand, check_io_mode_supported};
 
 /// Serprog programmer
@@ -24,6 +24,26 @@ pub struct Serprog<T: Transport> {
     info: ProgrammerInfo,
     /// Whether automatic command checking is enabled
     auto_check: bool,
+    /// How often a command is retried after reconnecting the transport
+    reconnect_retries: u32,
+    /// Programmer settings to restore after a reconnect
+    session: Session,
+}
+
+/// Settings made on the programmer that a reconnect has to re-apply
+#[derive(Debug, Clone, Copy, Default)]
+struct Session {
+    /// Output driver (and reset) state from S_CMD_S_PIN_STATE
+    pin_state: Option<bool>,
+    /// SPI clock from S_CMD_S_SPI_FREQ
+    spi_freq: Option<u32>,
+    /// Chip select from S_CMD_S_SPI_CS
+    spi_cs: Option<u8>,
+}
+
+/// Whether an error may be a dropped connection rather than a protocol error
+fn is_transient(e: &SerprogError) -> bool {
+    matches!(e, SerprogError::IoError(_) | SerprogError::Timeout)
 }
 
 impl<T: Transport> Serprog<T> {
@@ -40,6 +60,8 @@ impl<T: Transport> Serprog<T> {
             transport,
             info: ProgrammerInfo::default(),
             auto_check: false,
+            reconnect_retries: 0,
+            session: Session::default(),
         };
 
         // Synchronize protocol
@@ -84,17 +106,19 @@ impl<T: Transport> Serprog<T> {
 
         // Query optional parameters
         if let Ok(buf) = serprog.do_command_ret::<3>(S_CMD_Q_WRNMAXLEN).await {
-            serprog.info.max_write_n = u24_to_u32(&buf);
+            serprog.info.max_write_n = u24_from_le(&buf);
             log::debug!(
-                "serprog: Maximum write-n length is {}",
+                "serprog: Maximum write-n length is {}, using {}-byte chunks",
+                serprog.info.max_write_n,
                 serprog.info.effective_max_write()
             );
         }
 
         if let Ok(buf) = serprog.do_command_ret::<3>(S_CMD_Q_RDNMAXLEN).await {
-            serprog.info.max_read_n = u24_to_u32(&buf);
+            serprog.info.max_read_n = u24_from_le(&buf);
             log::debug!(
-                "serprog: Maximum read-n length is {}",
+                "serprog: Maximum read-n length is {}, using {}-byte chunks",
+                serprog.info.max_read_n,
                 serprog.info.effective_max_read()
             );
         }
@@ -124,6 +148,7 @@ impl<T: Transport> Serprog<T> {
                 .await
                 .is_ok()
         {
+            serprog.session.pin_state = Some(true);
             log::debug!("serprog: Output drivers enabled");
         }
 
@@ -150,6 +175,7 @@ impl<T: Transport> Serprog<T> {
             .await?;
 
         let actual_freq = u32::from_le_bytes(ret_buf);
+        self.session.spi_freq = Some(freq_hz);
         log::info!(
             "serprog: Requested SPI frequency {} Hz, set to {} Hz",
             freq_hz,
@@ -167,11 +193,45 @@ impl<T: Transport> Serprog<T> {
         }
 
         self.do_command(S_CMD_S_SPI_CS, &[cs], &mut []).await?;
+        self.session.spi_cs = Some(cs);
         log::debug!("serprog: Using chip select {}", cs);
 
         Ok(())
     }
 
+    /// Drive the programmer's pin state (S_CMD_S_PIN_STATE)
+    ///
+    /// `true` enables the SPI output drivers and `false` puts them into
+    /// high impedance, so the target can use the bus again. The drivers are
+    /// already enabled on open and disabled on shutdown.
+    #[maybe_async]
+    pub async fn set_pin_state(&mut self, enabled: bool) -> Result<()> {
+        if !self.info.supports_cmd(S_CMD_S_PIN_STATE) {
+            return Err(SerprogError::CommandNotSupported(S_CMD_S_PIN_STATE));
+        }
+
+        self.do_command(S_CMD_S_PIN_STATE, &[enabled as u8], &mut [])
+            .await?;
+        self.session.pin_state = Some(enabled);
+        log::debug!(
+            "serprog: Pin state {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+
+        Ok(())
+    }
+
+    /// Retry commands that fail with an I/O error up to `retries` times,
+    /// reconnecting the transport before each retry
+    ///
+    /// After reconnecting, the protocol is resynchronized and the bus type,
+    /// pin state, SPI clock and chip select are set again, so the retried
+    /// command starts from a clean state. Only transports that can
+    /// reconnect (TCP) retry; the default of 0 never retries.
+    pub fn set_reconnect_retries(&mut self, retries: u32) {
+        self.reconnect_retries = retries;
+    }
+
     /// Get programmer information
     pub fn info(&self) -> &ProgrammerInfo {
         &self.info
@@ -200,6 +260,32 @@ impl<T: Transport> Serprog<T> {
         Ok(())
     }
 
+    /// Clock `bytes` bytes with MOSI high and CS held at `cs`
+    ///
+    /// Holding CS deasserted needs S_CMD_S_CS_MODE. Without it, only
+    /// clocking with CS asserted, as a plain SPI operation, is possible.
+    #[maybe_async]
+    async fn idle_clocks(&mut self, bytes: usize, cs: CsLevel) -> Result<()> {
+        let data = vec![0xFF; bytes];
+        if !self.info.supports_cmd(S_CMD_S_CS_MODE) {
+            if cs == CsLevel::Deasserted {
+                return Err(SerprogError::CommandNotSupported(S_CMD_S_CS_MODE));
+            }
+            return self.spi_op(&data, &mut []).await;
+        }
+
+        let mode = match cs {
+            CsLevel::Asserted => cs_mode::SELECTED,
+            CsLevel::Deasserted => cs_mode::DESELECTED,
+        };
+        self.do_command(S_CMD_S_CS_MODE, &[mode], &mut []).await?;
+        let result = self.spi_op(&data, &mut []).await;
+        // Always return to automatic CS, even if the clocks failed
+        self.do_command(S_CMD_S_CS_MODE, &[cs_mode::AUTO], &mut [])
+            .await?;
+        result
+    }
+
     /// Disable output drivers (called on drop in sync mode)
     #[maybe_async]
     pub async fn shutdown(&mut self) {
@@ -298,9 +384,77 @@ impl<T: Transport> Serprog<T> {
         Ok(false)
     }
 
-    /// Execute a serprog command
+    /// Reconnect the transport and bring the programmer back to the state
+    /// the session left it in
+    ///
+    /// Returns false if the transport can't reconnect. Only uses
+    /// [`Self::do_command_once`], so a failure here doesn't recurse into
+    /// another reconnect.
+    #[maybe_async]
+    async fn reconnect(&mut self) -> Result<bool> {
+        if !self.transport.reconnect().await? {
+            return Ok(false);
+        }
+
+        // A reply to the interrupted command may still be in flight
+        self.synchronize().await?;
+
+        let mut buf = [0u8; 2];
+        self.do_command_once(S_CMD_Q_IFACE, &[], &mut buf).await?;
+        let version = u16::from_le_bytes(buf);
+        if version != SERPROG_PROTOCOL_VERSION {
+            return Err(SerprogError::UnsupportedVersion(version));
+        }
+
+        let bt = self.info.bustypes;
+        self.do_command_once(S_CMD_S_BUSTYPE, &[bt], &mut [])
+            .await?;
+
+        let session = self.session;
+        if let Some(enabled) = session.pin_state {
+            self.do_command_once(S_CMD_S_PIN_STATE, &[enabled as u8], &mut [])
+                .await?;
+        }
+        if let Some(freq_hz) = session.spi_freq {
+            let mut ret_buf = [0u8; 4];
+            self.do_command_once(S_CMD_S_SPI_FREQ, &freq_hz.to_le_bytes(), &mut ret_buf)
+                .await?;
+        }
+        if let Some(cs) = session.spi_cs {
+            self.do_command_once(S_CMD_S_SPI_CS, &[cs], &mut []).await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Execute a serprog command, reconnecting and retrying on I/O errors
+    /// as configured with [`Self::set_reconnect_retries`]
     #[maybe_async]
     async fn do_command(&mut self, cmd: u8, params: &[u8], ret_buf: &mut [u8]) -> Result<()> {
+        let mut retries = self.reconnect_retries;
+        loop {
+            let err = match self.do_command_once(cmd, params, ret_buf).await {
+                Err(e) if retries > 0 && is_transient(&e) => e,
+                result => return result,
+            };
+            retries -= 1;
+
+            log::warn!(
+                "serprog: Command 0x{:02X} failed ({}), reconnecting",
+                cmd,
+                err
+            );
+            match self.reconnect().await {
+                Ok(true) => log::info!("serprog: Resynchronized, retrying command 0x{:02X}", cmd),
+                Ok(false) => return Err(err),
+                Err(e) => log::warn!("serprog: Reconnect failed: {}", e),
+            }
+        }
+    }
+
+    /// Execute a serprog command once
+    #[maybe_async]
+    async fn do_command_once(&mut self, cmd: u8, params: &[u8], ret_buf: &mut [u8]) -> Result<()> {
         // Check command availability
         if self.auto_check && !self.info.supports_cmd(cmd) {
             log::debug!("serprog: Command 0x{:02X} not supported", cmd);
@@ -448,9 +602,17 @@ impl<T: Transport> SpiMaster for Serprog<T> {
             let _ = us;
         }
     }
-}
 
-/// Convert a 24-bit little-endian value to u32
-fn u24_to_u32(buf: &[u8; 3]) -> u32 {
-    (buf[0] as u32) | ((buf[1] as u32) << 8) | ((buf[2] as u32) << 16)
+    async fn send_clocks(&mut self, cycles: u32, cs: CsLevel) -> CoreResult<()> {
+        // O_SPIOP only clocks whole bytes
+        if !cycles.is_multiple_of(8) {
+            return Err(CoreError::OpcodeNotSupported);
+        }
+        self.idle_clocks(cycles as usize / 8, cs)
+            .await
+            .map_err(|e| match e {
+                SerprogError::CommandNotSupported(_) => CoreError::OpcodeNotSupported,
+                _ => CoreError::ProgrammerError,
+            })
+    }
 }
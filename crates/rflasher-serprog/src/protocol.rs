@@ -97,6 +97,21 @@ pub mod cs_mode {
     pub const DESELECTED: u8 = 0x02;
 }
 
+/// Chunk size used when the programmer reports a max read/write-n of 0
+///
+/// Some firmwares report 0 to mean "no limit within one command"; taken
+/// literally that would be a 16 MiB transfer, so fall back to something
+/// every serial buffer copes with.
+pub const DEFAULT_MAX_N: usize = 4096;
+
+/// Largest opcode + address header sent ahead of write data in S_CMD_O_SPIOP
+const SPI_HEADER_MAX: usize = 5;
+
+/// Decode a 24-bit little-endian value, as returned by the S_CMD_Q_*NMAXLEN queries
+pub fn u24_from_le(buf: &[u8; 3]) -> u32 {
+    (buf[0] as u32) | ((buf[1] as u32) << 8) | ((buf[2] as u32) << 16)
+}
+
 /// Supported commands bitmap
 #[derive(Debug, Clone)]
 pub struct CommandMap {
@@ -149,9 +164,9 @@ pub struct ProgrammerInfo {
     pub serbuf_size: u16,
     /// Operation buffer size
     pub opbuf_size: u16,
-    /// Maximum write-n length (0 = 2^24)
+    /// Maximum write-n length (0 = not reported)
     pub max_write_n: u32,
-    /// Maximum read-n length (0 = 2^24)
+    /// Maximum read-n length (0 = not reported)
     pub max_read_n: u32,
     /// Supported commands bitmap
     pub cmdmap: CommandMap,
@@ -179,19 +194,26 @@ impl ProgrammerInfo {
         core::str::from_utf8(&self.name[..len]).unwrap_or("(invalid)")
     }
 
-    /// Get the effective max write length
+    /// Get the effective max write data length per SPI operation
+    ///
+    /// The write-n limit covers the opcode and address too, so room for the
+    /// largest header is subtracted. A limit of 0 means [`DEFAULT_MAX_N`].
     pub fn effective_max_write(&self) -> usize {
         if self.max_write_n == 0 {
-            (1 << 24) - 1
+            DEFAULT_MAX_N
         } else {
-            self.max_write_n as usize
+            (self.max_write_n as usize)
+                .saturating_sub(SPI_HEADER_MAX)
+                .max(1)
         }
     }
 
-    /// Get the effective max read length
+    /// Get the effective max read length per SPI operation
+    ///
+    /// A limit of 0 means [`DEFAULT_MAX_N`].
     pub fn effective_max_read(&self) -> usize {
         if self.max_read_n == 0 {
-            (1 << 24) - 1
+            DEFAULT_MAX_N
         } else {
             self.max_read_n as usize
         }
@@ -207,3 +229,26 @@ impl ProgrammerInfo {
         self.cmdmap.is_supported(cmd)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_len_from_query() {
+        let mut info = ProgrammerInfo::default();
+        // S_CMD_Q_RDNMAXLEN reply: ACK, then 0x000800 little-endian
+        let reply = [S_ACK, 0x00, 0x08, 0x00];
+        info.max_read_n = u24_from_le(reply[1..].try_into().unwrap());
+        assert_eq!(info.effective_max_read(), 0x800);
+
+        info.max_write_n = u24_from_le(&[0x00, 0x01, 0x00]);
+        assert_eq!(info.effective_max_write(), 0x100 - 5);
+
+        // 0 means "unlimited": use the default instead of 16 MiB
+        info.max_read_n = u24_from_le(&[0, 0, 0]);
+        info.max_write_n = 0;
+        assert_eq!(info.effective_max_read(), DEFAULT_MAX_N);
+        assert_eq!(info.effective_max_write(), DEFAULT_MAX_N);
+    }
+}
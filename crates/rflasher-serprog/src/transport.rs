@@ -37,6 +37,13 @@ pub trait Transport {
 
     /// Flush any buffered data
     async fn flush(&mut self) -> Result<()>;
+
+    /// Re-establish a dropped connection
+    ///
+    /// Returns false if the transport can't reconnect, the default.
+    async fn reconnect(&mut self) -> Result<bool> {
+        Ok(false)
+    }
 }
 
 #[cfg(all(feature = "std", feature = "is_sync"))]
@@ -141,12 +148,17 @@ pub mod tcp {
     use super::*;
     use maybe_async::maybe_async;
     use std::io::{Read, Write};
-    use std::net::TcpStream;
+    use std::net::{Shutdown, TcpStream};
     use std::time::Duration;
 
+    /// Pause before reconnecting, giving a blipping network time to recover
+    const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+
     /// TCP socket transport
     pub struct TcpTransport {
         stream: TcpStream,
+        /// Server address, kept for reconnecting
+        addr: String,
     }
 
     impl TcpTransport {
@@ -155,7 +167,16 @@ pub mod tcp {
             let addr = format!("{}:{}", host, port);
             log::info!("Connecting to serprog server at {}", addr);
 
-            let stream = TcpStream::connect(&addr)
+            let stream = Self::open_stream(&addr)?;
+
+            log::info!("Connected to serprog server at {}", addr);
+
+            Ok(Self { stream, addr })
+        }
+
+        /// Open a socket to `addr` with the default options and timeouts
+        fn open_stream(addr: &str) -> Result<TcpStream> {
+            let stream = TcpStream::connect(addr)
                 .map_err(|e| SerprogError::ConnectionFailed(e.to_string()))?;
 
             // Set TCP_NODELAY to reduce latency
@@ -175,9 +196,7 @@ pub mod tcp {
                     SerprogError::ConnectionFailed(format!("Failed to set write timeout: {}", e))
                 })?;
 
-            log::info!("Connected to serprog server at {}", addr);
-
-            Ok(Self { stream })
+            Ok(stream)
         }
     }
 
@@ -232,6 +251,15 @@ pub mod tcp {
             self.stream.flush()?;
             Ok(())
         }
+
+        async fn reconnect(&mut self) -> Result<bool> {
+            let _ = self.stream.shutdown(Shutdown::Both);
+            std::thread::sleep(RECONNECT_DELAY);
+
+            self.stream = Self::open_stream(&self.addr)?;
+            log::warn!("Reconnected to serprog server at {}", self.addr);
+            Ok(true)
+        }
     }
 }
 
@@ -10,7 +10,7 @@ use rflasher_ch347::{Ch347, SpiSpeed};
 use rflasher_core::chip::{ChipDatabase, FlashChip};
 use rflasher_core::flash::unified::{WriteProgress, WriteStats, smart_write};
 use rflasher_core::flash::{
-    FlashContext, FlashDevice, HybridFlashDevice, ProbeResult, SpiFlashDevice,
+    FlashContext, FlashDevice, HybridFlashDevice, ProbeResult, SpiFlashDevice, read_io_mode,
 };
 use rflasher_dediprog::{Dediprog, DediprogConfig};
 use rflasher_ft4222::{Ft4222, SpiConfig as Ft4222SpiConfig};
@@ -170,7 +170,7 @@ macro_rules! with_programmer {
 /// for operations that need chip-level read/write/erase.
 ///
 /// For Dediprog: creates [`HybridFlashDevice`] (fast bulk read/write via
-/// `OpaqueMaster`) and calls `set_flash_size()` first.
+/// `OpaqueMaster`) and calls `set_flash_size()` and `set_read_io_mode()` first.
 /// For all others: creates [`SpiFlashDevice`].
 ///
 /// The body receives `$device` as `&mut impl FlashDevice`. The macro handles
@@ -216,6 +216,8 @@ macro_rules! with_flash_device {
             }
             Programmer::Dediprog(mut master) => {
                 master.set_flash_size($ctx_flash.total_size() as u32);
+                let read_io_mode = read_io_mode(&mut master, &$ctx_flash).await;
+                master.set_read_io_mode(read_io_mode);
                 let mut $device = HybridFlashDevice::new(master, $ctx_flash);
                 let result = { $body };
                 let (master, _) = $device.into_parts();
@@ -250,10 +252,12 @@ enum AsyncMessage {
     ProbeComplete(Box<ProbeResult>),
     /// Probe failed
     ProbeFailed(String),
-    /// Read completed
-    ReadComplete(Vec<u8>),
+    /// Read completed, `download` saves the data as a file
+    ReadComplete { data: Vec<u8>, download: bool },
     /// Read failed
     ReadFailed(String),
+    /// File loaded, `write` writes it to the chip
+    FileLoaded { data: Vec<u8>, write: bool },
     /// Write completed
     WriteComplete(WriteStats),
     /// Write failed
@@ -664,16 +668,28 @@ impl RflasherApp {
                     self.chip_info = None;
                     self.status.error(format!("Probe failed: {}", err));
                 }
-                AsyncMessage::ReadComplete(data) => {
+                AsyncMessage::ReadComplete { data, download } => {
                     self.operation = OperationState::Idle;
                     let size = data.len();
                     self.file_buffer = Some(data);
                     self.status.success(format!("Read {} bytes", size));
+                    if download {
+                        self.spawn_file_save();
+                    }
                 }
                 AsyncMessage::ReadFailed(err) => {
                     self.operation = OperationState::Idle;
                     self.status.error(format!("Read failed: {}", err));
                 }
+                AsyncMessage::FileLoaded { data, write } => {
+                    self.file_buffer = Some(data);
+                    if write && self.is_busy() {
+                        self.status
+                            .error("Not writing the file, another operation is running");
+                    } else if write {
+                        self.spawn_write();
+                    }
+                }
                 AsyncMessage::WriteComplete(stats) => {
                     self.operation = OperationState::Idle;
                     self.status.success(format!(
@@ -1198,7 +1214,9 @@ impl RflasherApp {
         });
     }
 
-    fn spawn_read(&mut self) {
+    /// Read the whole chip into the file buffer, then offer it as a
+    /// download if `download` is set
+    fn spawn_read(&mut self, download: bool) {
         let Some(ref chip_info) = self.chip_info else {
             self.status.error("No chip detected");
             return;
@@ -1274,7 +1292,10 @@ impl RflasherApp {
                             shared
                                 .borrow_mut()
                                 .messages
-                                .push(AsyncMessage::ReadComplete(buf));
+                                .push(AsyncMessage::ReadComplete {
+                                    data: buf,
+                                    download,
+                                });
                         }
                         Some(e) => {
                             shared
@@ -1891,12 +1912,20 @@ impl RflasherApp {
         ui.add_enabled_ui(connected && has_chip && !busy, |ui| {
             ui.horizontal(|ui| {
                 if ui.button("Read").clicked() {
-                    self.spawn_read();
+                    self.spawn_read(false);
                 }
                 if ui.button("Write").clicked() {
                     self.spawn_write();
                 }
             });
+            ui.horizontal(|ui| {
+                if ui.button("Read to File...").clicked() {
+                    self.spawn_read(true);
+                }
+                if ui.button("Write from File...").clicked() {
+                    self.spawn_file_load(true);
+                }
+            });
             ui.horizontal(|ui| {
                 if ui.button("Erase").clicked() {
                     self.spawn_erase();
@@ -1985,7 +2014,7 @@ impl RflasherApp {
 
         // Load file button - will be implemented with file dialog
         if ui.button("Load File...").clicked() {
-            self.spawn_file_load();
+            self.spawn_file_load(false);
         }
 
         // Save file button (only if we have data)
@@ -1996,7 +2025,9 @@ impl RflasherApp {
         });
     }
 
-    fn spawn_file_load(&mut self) {
+    /// Load a file into the file buffer, then write it to the chip if
+    /// `write` is set
+    fn spawn_file_load(&mut self, write: bool) {
         let shared = self.shared.clone();
         let ctx = self.ctx.clone();
 
@@ -2013,7 +2044,7 @@ impl RflasherApp {
                     shared
                         .borrow_mut()
                         .messages
-                        .push(AsyncMessage::ReadComplete(data));
+                        .push(AsyncMessage::FileLoaded { data, write });
                 }
                 Err(e) => {
                     shared.borrow_mut().messages.push(AsyncMessage::Log(
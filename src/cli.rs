@@ -1,6 +1,7 @@
 //! CLI argument parsing
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use rflasher_core::programmer::BusWidth;
 use rflasher_flash::programmer_names_short;
 use std::path::PathBuf;
 
@@ -12,6 +13,96 @@ fn programmer_help() -> String {
     )
 }
 
+/// Parse `--erase-timeout-scale`, which must be a positive finite number
+fn parse_erase_timeout_scale(s: &str) -> Result<f32, String> {
+    let scale: f32 = s.parse().map_err(|e| format!("{}", e))?;
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err("must be a positive number".into());
+    }
+    Ok(scale)
+}
+
+/// Parse a flash address or length, in hex (`0x` prefix) or decimal
+fn parse_address(s: &str) -> Result<u32, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|e| format!("{}", e))
+}
+
+/// Flash image file format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImageFormat {
+    /// Raw binary, one byte per flash address
+    Binary,
+    /// Intel HEX (.hex)
+    Ihex,
+    /// Motorola S-record (.srec, .s19, .s28, .s37)
+    Srec,
+}
+
+/// Digest algorithm for `digest`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum DigestAlgorithm {
+    /// CRC-32 (IEEE 802.3, as printed by `crc32` and `zip`)
+    Crc32,
+    /// SHA-256 (as printed by `sha256sum`)
+    #[default]
+    Sha256,
+}
+
+/// Verification after `write`, for `--verify`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum VerifyMode {
+    /// Read back everything written and report the first mismatch
+    #[default]
+    #[value(alias = "true")]
+    Full,
+    /// Read back each written region and report which ones failed
+    Regions,
+    /// Don't verify
+    #[value(alias = "false")]
+    None,
+}
+
+/// Read I/O mode for `--iomode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum IoModeArg {
+    /// Fastest mode supported by both the programmer and the chip
+    #[default]
+    Auto,
+    /// Single I/O only
+    Single,
+    /// Up to dual I/O
+    Dual,
+    /// Up to quad I/O
+    Quad,
+}
+
+impl IoModeArg {
+    /// Widest bus allowed, `None` for no limit
+    #[allow(dead_code)]
+    pub fn bus_width(self) -> Option<BusWidth> {
+        match self {
+            Self::Auto => None,
+            Self::Single => Some(BusWidth::Single),
+            Self::Dual => Some(BusWidth::Dual),
+            Self::Quad => Some(BusWidth::Quad),
+        }
+    }
+}
+
+/// Output format for `layout show`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum LayoutFormat {
+    /// Human-readable region table
+    #[default]
+    Table,
+    /// flashrom layout file (for `flashrom -l`)
+    Flashrom,
+}
+
 #[derive(Parser)]
 #[command(name = "rflasher")]
 #[command(author, version, about = "Flash chip programmer", long_about = None)]
@@ -25,6 +116,52 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub chip_db: Option<PathBuf>,
 
+    /// Don't show progress bars (also disabled when stderr isn't a terminal)
+    #[arg(long, global = true)]
+    pub no_progress: bool,
+
+    /// Read each chunk up to this many times, taking a majority vote when
+    /// the copies disagree (for marginal connections; 1 reads once)
+    #[arg(long, global = true, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..))]
+    pub read_tries: u8,
+
+    /// Largest SPI transfer in bytes for flash reads and writes, clamped to
+    /// the programmer's limits (smaller values can help with USB stalls)
+    #[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+    pub chunk_size: Option<u32>,
+
+    /// Widest I/O mode for flash reads (`single` forces plain single-line
+    /// reads, for debugging)
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub iomode: IoModeArg,
+
+    /// Software reset the chip (0x66 + 0x99, plus the QPI exit where the
+    /// programmer supports QPI) before probing, for chips stuck in QPI or
+    /// 4-byte mode. Not all chips support software reset
+    #[arg(long, global = true)]
+    pub reset_before_probe: bool,
+
+    /// Use the erase blocks from the chip's SFDP when they differ from the
+    /// chip database, for chip revisions the database is out of date for
+    #[arg(long, global = true)]
+    pub prefer_sfdp: bool,
+
+    /// Multiply every block erase timeout by this factor, for slow or worn
+    /// chips that time out mid-erase (timeouts already follow the chip's
+    /// SFDP erase times when it has them)
+    #[arg(long, global = true, value_parser = parse_erase_timeout_scale)]
+    pub erase_timeout_scale: Option<f32>,
+
+    /// Log every SPI command (opcode, address, I/O mode, data lengths) for
+    /// debugging a programmer or chip, with long data cut short
+    #[arg(long, global = true)]
+    pub trace_spi: bool,
+
+    /// Print JSON instead of tables (probe, info, sfdp, list-chips,
+    /// list-programmers)
+    #[arg(long, global = true)]
+    pub json: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -33,17 +170,21 @@ pub struct Cli {
 #[derive(clap::Args, Debug, Clone, Default)]
 pub struct LayoutArgs {
     /// Layout file (TOML format)
-    #[arg(long, conflicts_with_all = ["ifd", "fmap"])]
+    #[arg(long, conflicts_with_all = ["ifd", "fmap", "gpt"])]
     pub layout: Option<PathBuf>,
 
     /// Read layout from Intel Flash Descriptor (IFD) in flash
-    #[arg(long, conflicts_with_all = ["layout", "fmap"])]
+    #[arg(long, conflicts_with_all = ["layout", "fmap", "gpt"])]
     pub ifd: bool,
 
     /// Read layout from FMAP structure in flash
-    #[arg(long, conflicts_with_all = ["layout", "ifd"])]
+    #[arg(long, conflicts_with_all = ["layout", "ifd", "gpt"])]
     pub fmap: bool,
 
+    /// Read layout from GUID Partition Table (GPT) in flash
+    #[arg(long, conflicts_with_all = ["layout", "ifd", "fmap"])]
+    pub gpt: bool,
+
     /// Include only these regions (comma-separated, requires layout)
     #[arg(long, value_delimiter = ',')]
     pub include: Vec<String>,
@@ -61,7 +202,7 @@ impl LayoutArgs {
     /// Check if any layout source is specified
     #[allow(dead_code)]
     pub fn has_layout_source(&self) -> bool {
-        self.layout.is_some() || self.ifd || self.fmap
+        self.layout.is_some() || self.ifd || self.fmap || self.gpt
     }
 
     /// Check if region filtering is requested
@@ -71,6 +212,31 @@ impl LayoutArgs {
     }
 }
 
+/// Absolute address range options, an alternative to a layout
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct RangeArgs {
+    /// Start address of the range to operate on (hex with 0x, or decimal)
+    #[arg(
+        long,
+        requires = "length",
+        value_parser = parse_address,
+        conflicts_with_all = ["layout", "ifd", "fmap", "gpt", "include", "exclude", "region"]
+    )]
+    pub start: Option<u32>,
+
+    /// Length in bytes of the range to operate on (requires --start)
+    #[arg(long, requires = "start", value_parser = parse_address)]
+    pub length: Option<u32>,
+}
+
+impl RangeArgs {
+    /// The selected `(start, length)`, if any
+    #[allow(dead_code)]
+    pub fn range(&self) -> Option<(u32, u32)> {
+        self.start.zip(self.length)
+    }
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Probe for flash chip
@@ -78,6 +244,11 @@ pub enum Commands {
         /// Programmer to use
         #[arg(short, long, help = programmer_help())]
         programmer: String,
+
+        /// If the chip doesn't answer RDID, try REMS (0x90), RES (0xAB) and
+        /// SFDP to identify it, for recovering odd or bricked chips
+        #[arg(long)]
+        aggressive: bool,
     },
 
     /// Read flash contents to file
@@ -90,17 +261,25 @@ pub enum Commands {
         #[arg(short, long)]
         output: PathBuf,
 
-        /// Chip name (optional, auto-detected if not specified)
+        /// Output file format (default: detected from the file extension)
+        #[arg(long, value_enum)]
+        format: Option<ImageFormat>,
+
+        /// Chip name or alias to use instead of the probed one (see list-chips)
         #[arg(short, long)]
         chip: Option<String>,
 
         #[command(flatten)]
         layout: LayoutArgs,
+
+        #[command(flatten)]
+        range: RangeArgs,
     },
 
     /// Write file to flash
     ///
-    /// When writing with a layout (--ifd, --fmap, or --layout), the input file
+    /// When writing with a layout (--ifd, --fmap, --gpt, or --layout) or an
+    /// address range (--start and --length, a single region), the input file
     /// is interpreted based on its size:
     ///
     /// - Multiple regions: File must be full chip size. Data is extracted from
@@ -114,6 +293,9 @@ pub enum Commands {
     ///   portion is written.
     ///
     /// - Single region with region size < file < chip size: Error (ambiguous).
+    ///
+    /// With --layout-from-image, the layout is the FMAP inside the input
+    /// file, which must be a full chip image whose FMAP matches the chip size.
     Write {
         /// Programmer to use
         #[arg(short, long, help = programmer_help())]
@@ -123,37 +305,109 @@ pub enum Commands {
         #[arg(short, long)]
         input: PathBuf,
 
-        /// Chip name (optional, auto-detected if not specified)
+        /// Input file format (default: detected from the file extension)
+        #[arg(long, value_enum)]
+        format: Option<ImageFormat>,
+
+        /// Chip name or alias to use instead of the probed one (see list-chips)
         #[arg(short, long)]
         chip: Option<String>,
 
-        /// Verify after writing
-        #[arg(long, default_value = "true")]
-        verify: bool,
+        /// Verify after writing: everything at once, each layout region on
+        /// its own, or not at all
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            num_args = 0..=1,
+            default_missing_value = "full"
+        )]
+        verify: VerifyMode,
 
         /// Don't erase before writing
         #[arg(long)]
         no_erase: bool,
 
+        /// Show which blocks would be erased and written without changing
+        /// the flash
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Save the write plan to `<input>.resume`, so an interrupted write
+        /// continues where it stopped when run again with --resume; the input
+        /// and layout options must be unchanged
+        #[arg(long, conflicts_with = "dry_run")]
+        resume: bool,
+
+        /// Pad an image smaller than the chip with 0xFF, erasing the rest of
+        /// the chip instead of leaving it unchanged
+        #[arg(
+            long,
+            conflicts_with_all = ["layout", "ifd", "fmap", "gpt", "include", "exclude", "region", "start"]
+        )]
+        pad: bool,
+
+        /// Use the FMAP in the input file as layout, writing only the
+        /// selected regions and keeping the rest of the chip
+        #[arg(
+            long,
+            conflicts_with_all = ["layout", "ifd", "fmap", "gpt", "start", "pad"]
+        )]
+        layout_from_image: bool,
+
         #[command(flatten)]
         layout: LayoutArgs,
+
+        #[command(flatten)]
+        range: RangeArgs,
     },
 
     /// Erase flash chip
+    ///
+    /// An address range given with --start and --length must be aligned to
+    /// the chip's smallest erase block.
     Erase {
         /// Programmer to use
         #[arg(short, long, help = programmer_help())]
         programmer: String,
 
-        /// Chip name (optional, auto-detected if not specified)
+        /// Chip name or alias to use instead of the probed one (see list-chips)
         #[arg(short, long)]
         chip: Option<String>,
 
+        /// Show which blocks would be erased without changing the flash
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Erase the whole chip with a single chip erase command (CE), which
+        /// is much faster than erasing block by block on some programmers
+        #[arg(
+            long,
+            conflicts_with_all = ["layout", "ifd", "fmap", "gpt", "include", "exclude", "region", "start"]
+        )]
+        chip_erase: bool,
+
         #[command(flatten)]
         layout: LayoutArgs,
+
+        #[command(flatten)]
+        range: RangeArgs,
+    },
+
+    /// Check that the flash chip is fully erased
+    Blankcheck {
+        /// Programmer to use
+        #[arg(short, long, help = programmer_help())]
+        programmer: String,
+
+        /// Chip name or alias to use instead of the probed one (see list-chips)
+        #[arg(short, long)]
+        chip: Option<String>,
     },
 
     /// Verify flash contents against file
+    ///
+    /// With a layout or address range, the file is interpreted as for write.
     Verify {
         /// Programmer to use
         #[arg(short, long, help = programmer_help())]
@@ -163,7 +417,60 @@ pub enum Commands {
         #[arg(short, long)]
         input: PathBuf,
 
-        /// Chip name (optional, auto-detected if not specified)
+        /// Chip name or alias to use instead of the probed one (see list-chips)
+        #[arg(short, long)]
+        chip: Option<String>,
+
+        #[command(flatten)]
+        layout: LayoutArgs,
+
+        #[command(flatten)]
+        range: RangeArgs,
+    },
+
+    /// Show where flash contents differ from a file
+    ///
+    /// A flash-sized file is compared against the selected regions; a smaller
+    /// file is compared against the start of a single selected region.
+    Diff {
+        /// Programmer to use
+        #[arg(short, long, help = programmer_help())]
+        programmer: String,
+
+        /// Input file path to compare against
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Input file format (default: detected from the file extension)
+        #[arg(long, value_enum)]
+        format: Option<ImageFormat>,
+
+        /// Chip name or alias to use instead of the probed one (see list-chips)
+        #[arg(short, long)]
+        chip: Option<String>,
+
+        /// Show small differing ranges byte by byte
+        #[arg(long)]
+        hex: bool,
+
+        #[command(flatten)]
+        layout: LayoutArgs,
+    },
+
+    /// Print a digest of the flash contents without saving them
+    ///
+    /// Selected regions are hashed in address order, so `--region` gives the
+    /// digest of a region extracted to its own file.
+    Digest {
+        /// Programmer to use
+        #[arg(short, long, help = programmer_help())]
+        programmer: String,
+
+        /// Digest algorithm
+        #[arg(long, value_enum, default_value_t)]
+        algo: DigestAlgorithm,
+
+        /// Chip name or alias to use instead of the probed one (see list-chips)
         #[arg(short, long)]
         chip: Option<String>,
 
@@ -177,9 +484,25 @@ pub enum Commands {
         #[arg(short, long, help = programmer_help())]
         programmer: String,
 
-        /// Chip name (optional, auto-detected if not specified)
+        /// Chip name or alias to use instead of the probed one (see list-chips)
         #[arg(short, long)]
         chip: Option<String>,
+
+        /// Also list the Intel Flash Descriptor soft straps
+        #[arg(long)]
+        straps: bool,
+    },
+
+    /// Dump the chip's SFDP (Serial Flash Discoverable Parameters) data
+    Sfdp {
+        /// Programmer to use
+        #[arg(short, long, help = programmer_help())]
+        programmer: String,
+
+        /// Write only the raw SFDP bytes to stdout (`--json` prints the
+        /// parsed structures instead)
+        #[arg(long, conflicts_with = "json")]
+        raw: bool,
     },
 
     /// List supported programmers
@@ -200,6 +523,38 @@ pub enum Commands {
     #[command(subcommand, name = "wp", alias = "write-protect")]
     Wp(WpCommands),
 
+    /// Security register (OTP) operations
+    #[command(subcommand)]
+    Otp(OtpCommands),
+
+    /// Put the chip into deep power-down (DP, 0xB9)
+    PowerDown {
+        /// Programmer to use
+        #[arg(short, long, help = programmer_help())]
+        programmer: String,
+    },
+
+    /// Release the chip from deep power-down (RES, 0xAB)
+    ///
+    /// Wakes a chip left asleep by an earlier session, which otherwise
+    /// doesn't answer probes.
+    PowerUp {
+        /// Programmer to use
+        #[arg(short, long, help = programmer_help())]
+        programmer: String,
+    },
+
+    /// Check the wiring and programmer without changing the chip
+    ///
+    /// Reads the JEDEC ID and status register and reads the start of the
+    /// chip twice to check that it returns the same data. Needs an SPI
+    /// programmer.
+    Selftest {
+        /// Programmer to use
+        #[arg(short, long, help = programmer_help())]
+        programmer: String,
+    },
+
     /// Start Scheme REPL for scripting SPI commands
     #[cfg(feature = "repl")]
     Repl {
@@ -210,6 +565,10 @@ pub enum Commands {
         /// Script file to run instead of interactive REPL
         #[arg(short, long)]
         script: Option<std::path::PathBuf>,
+
+        /// Scheme file to load before the interactive REPL starts
+        #[arg(short, long, conflicts_with = "script")]
+        load: Option<std::path::PathBuf>,
     },
 }
 
@@ -222,7 +581,7 @@ pub enum WpCommands {
         #[arg(short, long, help = programmer_help())]
         programmer: String,
 
-        /// Chip name (optional, auto-detected if not specified)
+        /// Chip name or alias to use instead of the probed one (see list-chips)
         #[arg(short, long)]
         chip: Option<String>,
     },
@@ -233,7 +592,7 @@ pub enum WpCommands {
         #[arg(short, long, help = programmer_help())]
         programmer: String,
 
-        /// Chip name (optional, auto-detected if not specified)
+        /// Chip name or alias to use instead of the probed one (see list-chips)
         #[arg(short, long)]
         chip: Option<String>,
     },
@@ -244,13 +603,21 @@ pub enum WpCommands {
         #[arg(short, long, help = programmer_help())]
         programmer: String,
 
-        /// Chip name (optional, auto-detected if not specified)
+        /// Chip name or alias to use instead of the probed one (see list-chips)
         #[arg(short, long)]
         chip: Option<String>,
 
         /// Make changes volatile (lost on power cycle)
         #[arg(long)]
         temporary: bool,
+
+        /// Lock the status register permanently (irreversible)
+        #[arg(long, conflicts_with = "temporary")]
+        permanent: bool,
+
+        /// Confirm that the status register should be locked forever
+        #[arg(long, requires = "permanent")]
+        yes_i_am_sure: bool,
     },
 
     /// Disable hardware write protection
@@ -259,7 +626,7 @@ pub enum WpCommands {
         #[arg(short, long, help = programmer_help())]
         programmer: String,
 
-        /// Chip name (optional, auto-detected if not specified)
+        /// Chip name or alias to use instead of the probed one (see list-chips)
         #[arg(short, long)]
         chip: Option<String>,
 
@@ -274,7 +641,7 @@ pub enum WpCommands {
         #[arg(short, long, help = programmer_help())]
         programmer: String,
 
-        /// Chip name (optional, auto-detected if not specified)
+        /// Chip name or alias to use instead of the probed one (see list-chips)
         #[arg(short, long)]
         chip: Option<String>,
 
@@ -292,7 +659,7 @@ pub enum WpCommands {
         #[arg(short, long, help = programmer_help())]
         programmer: String,
 
-        /// Chip name (optional, auto-detected if not specified)
+        /// Chip name or alias to use instead of the probed one (see list-chips)
         #[arg(short, long)]
         chip: Option<String>,
 
@@ -308,6 +675,67 @@ pub enum WpCommands {
     },
 }
 
+/// Security register (OTP) subcommands
+#[derive(Subcommand)]
+pub enum OtpCommands {
+    /// Read a security register to a file
+    Read {
+        /// Programmer to use
+        #[arg(short, long, help = programmer_help())]
+        programmer: String,
+
+        /// Chip name or alias to use instead of the probed one (see list-chips)
+        #[arg(short, long)]
+        chip: Option<String>,
+
+        /// Security register number (starting at 1)
+        #[arg(short, long)]
+        register: u8,
+
+        /// Output file path
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Erase a security register and program it from a file
+    Write {
+        /// Programmer to use
+        #[arg(short, long, help = programmer_help())]
+        programmer: String,
+
+        /// Chip name or alias to use instead of the probed one (see list-chips)
+        #[arg(short, long)]
+        chip: Option<String>,
+
+        /// Security register number (starting at 1)
+        #[arg(short, long)]
+        register: u8,
+
+        /// Input file path (at most the register size)
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+
+    /// Permanently lock a security register (irreversible)
+    Lock {
+        /// Programmer to use
+        #[arg(short, long, help = programmer_help())]
+        programmer: String,
+
+        /// Chip name or alias to use instead of the probed one (see list-chips)
+        #[arg(short, long)]
+        chip: Option<String>,
+
+        /// Security register number (starting at 1)
+        #[arg(short, long)]
+        register: u8,
+
+        /// Confirm that the register should be locked forever
+        #[arg(long)]
+        yes_i_am_sure: bool,
+    },
+}
+
 /// Layout-related subcommands
 #[derive(Subcommand)]
 pub enum LayoutCommands {
@@ -316,6 +744,10 @@ pub enum LayoutCommands {
         /// Layout file (TOML format)
         #[arg(short, long)]
         file: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: LayoutFormat,
     },
 
     /// Extract layout from flash image (IFD or FMAP)
@@ -340,17 +772,44 @@ pub enum LayoutCommands {
         output: Option<PathBuf>,
     },
 
-    /// Extract FMAP layout from image
+    /// Extract FMAP layout from image, or build an FMAP from a layout
     Fmap {
         /// Input file (flash image)
+        #[arg(short, long, required_unless_present = "from")]
+        input: Option<PathBuf>,
+
+        /// Build an FMAP binary from this layout file (TOML format) instead
+        #[arg(long, conflicts_with = "input", requires = "output")]
+        from: Option<PathBuf>,
+
+        /// Output file: TOML layout when extracting (optional - prints to stdout
+        /// if not specified), FMAP binary with --from
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Extract GPT partition layout from image
+    Gpt {
+        /// Input file (disk or flash image)
         #[arg(short, long)]
         input: PathBuf,
 
-        /// Output layout file (TOML format, optional - prints to stdout if not specified)
+        /// Output layout file (optional - prints to stdout if not specified)
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
 
+    /// List the files in a coreboot image's CBFS
+    Cbfs {
+        /// Input file (flash image)
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// FMAP region holding the CBFS (default: COREBOOT)
+        #[arg(long)]
+        region: Option<String>,
+    },
+
     /// Create a new layout file template
     Create {
         /// Output layout file
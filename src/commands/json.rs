@@ -0,0 +1,141 @@
+//! Machine-readable JSON output
+//!
+//! Serializable views of what `probe`, `info`, `list-chips` and
+//! `list-programmers` print, emitted instead of the tables with `--json`.
+
+use rflasher_core::chip::{ChipDatabase, EraseBlock, FlashChip};
+use rflasher_flash::{ChipInfo, FlashHandle, available_programmers};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Chip database entry
+#[derive(Serialize)]
+struct ChipJson<'a> {
+    vendor: &'a str,
+    name: &'a str,
+    aliases: &'a [String],
+    jedec_manufacturer: u8,
+    jedec_device: u16,
+    total_size: u32,
+    page_size: u16,
+    voltage_min_mv: u16,
+    voltage_max_mv: u16,
+    erase_blocks: &'a [EraseBlock],
+    features: Vec<&'static str>,
+}
+
+impl<'a> From<&'a FlashChip> for ChipJson<'a> {
+    fn from(chip: &'a FlashChip) -> Self {
+        Self {
+            vendor: &chip.vendor,
+            name: &chip.name,
+            aliases: &chip.aliases,
+            jedec_manufacturer: chip.jedec_manufacturer,
+            jedec_device: chip.jedec_device,
+            total_size: chip.total_size,
+            page_size: chip.page_size,
+            voltage_min_mv: chip.voltage_min_mv,
+            voltage_max_mv: chip.voltage_max_mv,
+            erase_blocks: chip.erase_blocks(),
+            features: chip.features.iter_names().map(|(name, _)| name).collect(),
+        }
+    }
+}
+
+/// Chip identified on an SPI programmer
+#[derive(Serialize)]
+struct DetectedChipJson<'a> {
+    vendor: &'a str,
+    name: &'a str,
+    jedec_manufacturer: u8,
+    jedec_device: u16,
+    total_size: u32,
+    page_size: u16,
+    from_database: bool,
+    sfdp: bool,
+    /// Database entry, or the entry built from SFDP
+    details: Option<ChipJson<'a>>,
+    sfdp_mismatches: Vec<String>,
+}
+
+impl<'a> From<&'a ChipInfo> for DetectedChipJson<'a> {
+    fn from(info: &'a ChipInfo) -> Self {
+        Self {
+            vendor: &info.vendor,
+            name: &info.name,
+            jedec_manufacturer: info.jedec_manufacturer,
+            jedec_device: info.jedec_device,
+            total_size: info.total_size,
+            page_size: info.page_size,
+            from_database: info.from_database,
+            sfdp: info.sfdp.is_some(),
+            details: info.chip.as_ref().map(ChipJson::from),
+            sfdp_mismatches: info.mismatches.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+}
+
+/// Opened flash device, as shown by `probe` and `info`
+#[derive(Serialize)]
+struct DeviceJson<'a> {
+    size: u32,
+    /// Not available on opaque programmers
+    chip: Option<DetectedChipJson<'a>>,
+    programmer: BTreeMap<&'static str, &'a str>,
+}
+
+/// Programmer compiled into this build
+#[derive(Serialize)]
+struct ProgrammerJson {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    description: &'static str,
+}
+
+/// Serialize a value to stdout as pretty-printed JSON
+fn print_json<T: Serialize>(value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Print the opened flash device
+pub fn print_device(handle: &FlashHandle) -> Result<(), Box<dyn std::error::Error>> {
+    print_json(&DeviceJson {
+        size: handle.size(),
+        chip: handle.chip_info().map(DetectedChipJson::from),
+        programmer: handle
+            .programmer_info()
+            .iter()
+            .map(|(label, value)| (*label, value.as_str()))
+            .collect(),
+    })
+}
+
+/// Print the chip database, optionally filtered by vendor
+pub fn list_chips(
+    db: &ChipDatabase,
+    vendor_filter: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let chips: Vec<ChipJson> = db
+        .iter()
+        .filter(|chip| {
+            vendor_filter
+                .is_none_or(|vendor| chip.vendor.to_lowercase().contains(&vendor.to_lowercase()))
+        })
+        .map(ChipJson::from)
+        .collect();
+    print_json(&chips)
+}
+
+/// Print the programmers enabled in this build
+pub fn list_programmers() -> Result<(), Box<dyn std::error::Error>> {
+    let programmers: Vec<ProgrammerJson> = available_programmers()
+        .iter()
+        .map(|p| ProgrammerJson {
+            name: p.name,
+            aliases: p.aliases,
+            description: p.description,
+        })
+        .collect();
+    print_json(&programmers)
+}
@@ -1,13 +1,17 @@
 //! Layout command implementations
 
-use rflasher_core::layout::{Layout, LayoutSource, has_fmap, has_ifd};
+use crate::cli::LayoutFormat;
+use rflasher_core::layout::{Layout, LayoutSource, has_fmap, has_gpt, has_ifd};
 use std::fs;
 use std::path::Path;
 
 /// Show layout from a file
-pub fn cmd_show(file: &Path) -> Result<(), Box<dyn std::error::Error>> {
+pub fn cmd_show(file: &Path, format: LayoutFormat) -> Result<(), Box<dyn std::error::Error>> {
     let layout = Layout::from_toml_file(file)?;
-    print_layout(&layout);
+    match format {
+        LayoutFormat::Table => print_layout(&layout),
+        LayoutFormat::Flashrom => print!("{}", layout.to_flashrom_string()),
+    }
     Ok(())
 }
 
@@ -21,8 +25,11 @@ pub fn cmd_extract(input: &Path, output: &Path) -> Result<(), Box<dyn std::error
     } else if has_fmap(&data) {
         println!("Detected FMAP");
         Layout::from_fmap(&data)?
+    } else if has_gpt(&data) {
+        println!("Detected GPT");
+        Layout::from_gpt(&data)?
     } else {
-        return Err("No IFD or FMAP found in image".into());
+        return Err("No IFD, FMAP or GPT found in image".into());
     };
 
     print_layout(&layout);
@@ -76,6 +83,73 @@ pub fn cmd_fmap(input: &Path, output: Option<&Path>) -> Result<(), Box<dyn std::
     Ok(())
 }
 
+/// Extract GPT partition layout from image
+pub fn cmd_gpt(input: &Path, output: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let data = fs::read(input)?;
+
+    if !has_gpt(&data) {
+        return Err("No GUID Partition Table found in image".into());
+    }
+
+    let layout = Layout::from_gpt(&data)?;
+    print_layout(&layout);
+
+    if let Some(out) = output {
+        layout.to_toml_file(out)?;
+        println!("\nSaved layout to {:?}", out);
+    } else {
+        println!("\n--- TOML Output ---\n");
+        println!("{}", layout.to_toml_string()?);
+    }
+
+    Ok(())
+}
+
+/// Build an FMAP binary from a TOML layout
+pub fn cmd_fmap_build(from: &Path, output: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use rflasher_core::layout::build_fmap;
+
+    let layout = Layout::from_toml_file(from)?;
+    print_layout(&layout);
+
+    let fmap = build_fmap(&layout)?;
+    fs::write(output, &fmap)?;
+    println!("\nWrote {} byte FMAP to {:?}", fmap.len(), output);
+
+    Ok(())
+}
+
+/// List the files in a coreboot image's CBFS
+pub fn cmd_cbfs(input: &Path, region: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    use rflasher_core::layout::list_cbfs;
+
+    let data = fs::read(input)?;
+    let files = list_cbfs(&data, region)?;
+
+    println!(
+        "{:<40} {:<16} {:>10} {:>10}",
+        "Name", "Type", "Offset", "Size"
+    );
+    println!("{:-<79}", "");
+
+    for file in &files {
+        let name = if file.is_empty() {
+            "(empty)"
+        } else {
+            file.name.as_str()
+        };
+        println!(
+            "{:<40} {:<16} {:#010X} {:>10}",
+            name,
+            file.type_name(),
+            file.offset,
+            file.size
+        );
+    }
+
+    Ok(())
+}
+
 /// Create a new layout file template
 pub fn cmd_create(output: &Path, size: &str) -> Result<(), Box<dyn std::error::Error>> {
     let chip_size = parse_size(size)?;
@@ -149,6 +223,8 @@ pub fn print_layout(layout: &Layout) {
             LayoutSource::Toml => "TOML file",
             LayoutSource::Ifd => "Intel Flash Descriptor",
             LayoutSource::Fmap => "FMAP",
+            LayoutSource::Gpt => "GUID Partition Table",
+            LayoutSource::Flashrom => "flashrom layout file",
             LayoutSource::Manual => "Manual",
         }
     );
@@ -164,13 +240,16 @@ pub fn print_layout(layout: &Layout) {
     );
     println!("{:-<74}", "");
 
-    for region in &layout.regions {
+    // Nested regions (e.g. FMAP areas) are indented below their parent
+    for (index, depth) in layout.tree_order() {
+        let region = &layout.regions[index];
         let size = region.size();
         let size_str = super::format_size(size);
+        let name = format!("{}{}", "  ".repeat(depth), region.name);
 
         println!(
             "{:<20} {:#010X} {:#010X} {:>10} {:>8} {:>8}",
-            region.name,
+            name,
             region.start,
             region.end,
             size_str,
@@ -15,8 +15,14 @@
 //! - SPI: Uses JEDEC ID probing
 //! - Opaque: Uses Intel Flash Descriptor
 
+pub mod json;
 pub mod layout;
 mod list;
+pub mod otp;
+pub mod power;
+pub mod resume;
+pub mod selftest;
+pub mod sfdp;
 pub mod unified;
 pub mod wp;
 
@@ -0,0 +1,112 @@
+//! Security register (OTP) command implementations
+
+use rflasher_core::otp::OtpLayout;
+use rflasher_flash::FlashHandle;
+use std::error::Error;
+use std::path::Path;
+
+/// Get the chip's security register layout and check the register number
+fn otp_layout(handle: &FlashHandle, register: u8) -> Result<OtpLayout, Box<dyn Error>> {
+    let layout = handle
+        .otp_layout()
+        .ok_or("Security registers are not supported for this chip")?;
+    if register == 0 || register > layout.count {
+        return Err(format!(
+            "Invalid security register {} (chip has registers 1-{})",
+            register, layout.count
+        )
+        .into());
+    }
+    Ok(layout)
+}
+
+/// Read a security register to a file
+pub fn cmd_read(
+    handle: &mut FlashHandle,
+    register: u8,
+    output: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let layout = otp_layout(handle, register)?;
+
+    let mut buf = vec![0u8; layout.size as usize];
+    handle
+        .read_otp(register, 0, &mut buf)
+        .map_err(|e| format!("Failed to read security register {}: {}", register, e))?;
+    std::fs::write(output, &buf)?;
+
+    let locked = handle.is_otp_locked(register)?;
+    println!(
+        "Read {} bytes from security register {} ({}) to {:?}",
+        buf.len(),
+        register,
+        if locked { "locked" } else { "unlocked" },
+        output
+    );
+
+    Ok(())
+}
+
+/// Erase a security register and program it from a file
+pub fn cmd_write(
+    handle: &mut FlashHandle,
+    register: u8,
+    input: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let layout = otp_layout(handle, register)?;
+
+    let data = std::fs::read(input)?;
+    if data.len() > layout.size as usize {
+        return Err(format!(
+            "Input is {} bytes, but security registers are {} bytes",
+            data.len(),
+            layout.size
+        )
+        .into());
+    }
+    if handle.is_otp_locked(register)? {
+        return Err(format!("Security register {} is locked", register).into());
+    }
+
+    println!("Erasing security register {}...", register);
+    handle
+        .erase_otp(register)
+        .map_err(|e| format!("Failed to erase security register {}: {}", register, e))?;
+
+    println!("Writing {} bytes...", data.len());
+    handle
+        .write_otp(register, 0, &data)
+        .map_err(|e| format!("Failed to write security register {}: {}", register, e))?;
+
+    println!("Verifying...");
+    let mut readback = vec![0u8; data.len()];
+    handle.read_otp(register, 0, &mut readback)?;
+    if let Some(offset) = readback.iter().zip(&data).position(|(a, b)| a != b) {
+        return Err(format!(
+            "Verification failed at offset 0x{:x}: expected 0x{:02x}, got 0x{:02x}",
+            offset, data[offset], readback[offset]
+        )
+        .into());
+    }
+
+    println!("Security register {} written successfully", register);
+    Ok(())
+}
+
+/// Permanently lock a security register
+///
+/// The caller is responsible for getting the user's confirmation first.
+pub fn cmd_lock(handle: &mut FlashHandle, register: u8) -> Result<(), Box<dyn Error>> {
+    otp_layout(handle, register)?;
+
+    if handle.is_otp_locked(register)? {
+        println!("Security register {} is already locked", register);
+        return Ok(());
+    }
+
+    handle
+        .lock_otp(register)
+        .map_err(|e| format!("Failed to lock security register {}: {}", register, e))?;
+
+    println!("Security register {} is now permanently locked", register);
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! Deep power-down commands
+//!
+//! `power-down` sends DP (0xB9) and `power-up` sends RES (0xAB). Both talk
+//! to the programmer directly instead of probing first, since a chip in
+//! deep power-down doesn't answer RDID.
+
+use rflasher_core::chip::{ChipDatabase, Features};
+use rflasher_core::protocol;
+use rflasher_flash::{BoxedSpiMaster, is_opaque_programmer, open_spi_programmer};
+
+/// Open the programmer's SPI bus, refusing opaque programmers
+fn open_master(programmer: &str) -> Result<BoxedSpiMaster, Box<dyn std::error::Error>> {
+    if is_opaque_programmer(programmer) {
+        return Err("Deep power-down is not available on opaque programmers".into());
+    }
+    open_spi_programmer(programmer)
+}
+
+/// Read the JEDEC ID, or `None` if no chip answers
+fn read_id(master: &mut BoxedSpiMaster) -> Result<Option<(u8, u16)>, Box<dyn std::error::Error>> {
+    let (manufacturer, device) = protocol::read_jedec_id(&mut **master)
+        .map_err(|e| format!("Failed to read JEDEC ID: {}", e))?;
+    Ok(protocol::is_valid_jedec_manufacturer(manufacturer).then_some((manufacturer, device)))
+}
+
+/// Put the chip into deep power-down
+pub fn cmd_power_down(
+    programmer: &str,
+    db: &ChipDatabase,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut master = open_master(programmer)?;
+
+    match read_id(&mut master)? {
+        Some((manufacturer, device)) => match db.find_by_jedec_id(manufacturer, device).next() {
+            Some(chip) if !chip.features.contains(Features::DEEP_POWER_DOWN) => log::warn!(
+                "{} {} is not known to support deep power-down, sending DP anyway",
+                chip.vendor,
+                chip.name
+            ),
+            Some(_) => {}
+            None => log::warn!(
+                "Chip {:02X}:{:04X} not in database, sending DP anyway",
+                manufacturer,
+                device
+            ),
+        },
+        None => log::warn!("No chip answers RDID, it may already be in deep power-down"),
+    }
+
+    protocol::deep_power_down(&mut *master)
+        .map_err(|e| format!("Failed to enter deep power-down: {}", e))?;
+
+    if read_id(&mut master)?.is_some() {
+        return Err("Chip still answers RDID, it doesn't seem to support deep power-down".into());
+    }
+    println!("Chip is in deep power-down, wake it with `rflasher power-up`");
+    Ok(())
+}
+
+/// Release the chip from deep power-down
+pub fn cmd_power_up(programmer: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut master = open_master(programmer)?;
+
+    let signature = protocol::release_power_down(&mut *master)
+        .map_err(|e| format!("Failed to release deep power-down: {}", e))?;
+    println!("Electronic signature: 0x{:02X}", signature);
+
+    match read_id(&mut master)? {
+        Some((manufacturer, device)) => {
+            println!(
+                "Chip is awake, JEDEC ID {:02X}:{:04X}",
+                manufacturer, device
+            );
+            Ok(())
+        }
+        None => Err("No chip answers RDID after the release".into()),
+    }
+}
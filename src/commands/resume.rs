@@ -0,0 +1,652 @@
+//! Resumable writes
+//!
+//! With `write --resume`, writes that change the flash are planned up
+//! front: smart write runs against a recorder that captures its erases and
+//! writes, and the resulting steps are saved to a sidecar file next to the
+//! input (`<input>.resume`). The completion cursor in the sidecar is updated
+//! after every step, and the file is removed once the write has finished.
+//! If the sidecar can't be saved, e.g. in a read-only directory, the write
+//! goes on with a warning, but can't be resumed.
+//!
+//! If a write is interrupted (for example by a USB disconnect), running the
+//! same `write --resume` again loads the plan, reads back the steps that
+//! were already carried out to check that they took effect, and continues
+//! with the outstanding steps only, without reading the whole chip again.
+//!
+//! # Sidecar format
+//!
+//! The sidecar is a JSON object:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "flash_size": 16777216,
+//!   "image_sha256": "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+//!   "bytes_changed": 4100,
+//!   "done": 1,
+//!   "steps": [
+//!     { "op": "erase", "addr": 65536, "len": 65536 },
+//!     { "op": "write", "addr": 65536, "len": 4096 },
+//!     { "op": "write", "addr": 69632, "len": 4, "data": "deadbeef" }
+//!   ]
+//! }
+//! ```
+//!
+//! `done` is the number of steps already carried out. Writes take their data
+//! from the image at the same address, except for data preserved around
+//! partially erased blocks, which is stored inline as hex in `data`.
+//!
+//! `image_sha256` is the digest of the image as laid out on the chip, so the
+//! input file and the layout options must be identical on resume; a
+//! different image is refused.
+
+use rflasher_core::chip::{EraseBlock, WriteGranularity};
+use rflasher_core::error::{Error as CoreError, Result as CoreResult};
+use rflasher_core::flash::FlashDevice;
+use rflasher_core::flash::unified::{self, WriteProgress, WriteStats};
+use rflasher_core::layout::Layout;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Version of the sidecar format written by this build
+const SIDECAR_VERSION: u32 = 1;
+
+/// Single erase or write of a saved plan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Step {
+    /// Erase `len` bytes at `addr`
+    Erase { addr: u32, len: u32 },
+    /// Program `len` bytes at `addr`, from `data` if present or else from
+    /// the image at the same address
+    Write {
+        addr: u32,
+        len: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none", with = "hex_bytes")]
+        data: Option<Vec<u8>>,
+    },
+}
+
+impl Step {
+    /// Address and length of the step
+    fn range(&self) -> (u32, u32) {
+        match self {
+            Step::Erase { addr, len } | Step::Write { addr, len, .. } => (*addr, *len),
+        }
+    }
+
+    /// Data programmed by a write step
+    fn data<'a>(&'a self, image: &'a [u8]) -> &'a [u8] {
+        match self {
+            Step::Erase { .. } => &[],
+            Step::Write {
+                data: Some(data), ..
+            } => data,
+            Step::Write { addr, len, .. } => &image[*addr as usize..(*addr + *len) as usize],
+        }
+    }
+
+    /// Apply the step to `buf`, which holds the flash contents at `addr`
+    fn apply(&self, image: &[u8], addr: u32, buf: &mut [u8]) {
+        let (step_addr, step_len) = self.range();
+        let start = step_addr.max(addr);
+        let end = (step_addr as u64 + step_len as u64).min(addr as u64 + buf.len() as u64);
+        if start as u64 >= end {
+            return;
+        }
+        let dest = &mut buf[(start - addr) as usize..(end - addr as u64) as usize];
+        match self {
+            Step::Erase { .. } => dest.fill(0xFF),
+            Step::Write { .. } => {
+                let offset = (start - step_addr) as usize;
+                dest.copy_from_slice(&self.data(image)[offset..offset + dest.len()]);
+            }
+        }
+    }
+}
+
+/// Hex encoding for inline write data
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer, de};
+
+    pub fn serialize<S: Serializer>(data: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        let hex: String = data
+            .iter()
+            .flatten()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        s.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        let hex = String::deserialize(d)?;
+        if !hex.len().is_multiple_of(2) {
+            return Err(de::Error::custom("odd number of hex digits"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(de::Error::custom))
+            .collect::<Result<Vec<u8>, _>>()
+            .map(Some)
+    }
+}
+
+/// Saved write plan and completion cursor
+#[derive(Debug, Serialize, Deserialize)]
+struct Plan {
+    version: u32,
+    flash_size: u32,
+    image_sha256: String,
+    bytes_changed: usize,
+    done: usize,
+    steps: Vec<Step>,
+}
+
+impl Plan {
+    fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read write plan {:?}: {}", path, e))?;
+        let plan: Plan = serde_json::from_str(&text)
+            .map_err(|e| format!("Invalid write plan {:?}: {}", path, e))?;
+        if plan.version != SIDECAR_VERSION {
+            return Err(format!(
+                "Write plan {:?} has unsupported version {}",
+                path, plan.version
+            )
+            .into());
+        }
+        if plan.done > plan.steps.len() {
+            return Err(format!("Invalid write plan {:?}: cursor past the end", path).into());
+        }
+        for step in &plan.steps {
+            let (addr, len) = step.range();
+            if addr
+                .checked_add(len)
+                .is_none_or(|end| end > plan.flash_size)
+            {
+                return Err(format!(
+                    "Invalid write plan {:?}: step at 0x{:08X} of {} bytes is past the end of the flash",
+                    path, addr, len
+                )
+                .into());
+            }
+            if let Step::Write {
+                data: Some(data), ..
+            } = step
+                && data.len() != len as usize
+            {
+                return Err(format!(
+                    "Invalid write plan {:?}: write at 0x{:08X} has {} bytes of data for {} bytes",
+                    path,
+                    addr,
+                    data.len(),
+                    len
+                )
+                .into());
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Save the plan, replacing the previous sidecar atomically
+    ///
+    /// The new file is synced before the rename, so a crash leaves either
+    /// the old or the new plan behind.
+    fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(serde_json::to_string(self)?.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Save the plan, or warn and stop checkpointing if that fails
+    ///
+    /// Losing the checkpoint only costs the ability to resume, so it
+    /// doesn't abort the write.
+    fn checkpoint(&self, path: &Path, checkpointing: &mut bool) {
+        if !*checkpointing {
+            return;
+        }
+        if let Err(e) = self.save(path) {
+            log::warn!(
+                "Failed to save write plan {:?}, the write can't be resumed: {}",
+                path,
+                e
+            );
+            *checkpointing = false;
+        }
+    }
+}
+
+/// Path of the sidecar file for writing `input`
+pub fn sidecar_path(input: &Path) -> PathBuf {
+    let mut path = input.as_os_str().to_owned();
+    path.push(".resume");
+    path.into()
+}
+
+/// SHA-256 of the chip image, as hex
+fn image_digest(image: &[u8]) -> String {
+    Sha256::digest(image)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Device wrapper that records the erases and writes of a smart write
+///
+/// Unlike `DryRunDevice`, reads reflect the steps recorded so far, so data
+/// preserved around an erase is captured as it would be on the real chip.
+struct PlanRecorder<'a, D: FlashDevice + ?Sized> {
+    inner: &'a mut D,
+    image: &'a [u8],
+    steps: Vec<Step>,
+}
+
+impl<D: FlashDevice + ?Sized> FlashDevice for PlanRecorder<'_, D> {
+    fn size(&self) -> u32 {
+        self.inner.size()
+    }
+
+    fn erase_granularity(&self) -> u32 {
+        self.inner.erase_granularity()
+    }
+
+    fn write_granularity(&self) -> WriteGranularity {
+        self.inner.write_granularity()
+    }
+
+    fn erase_blocks(&self) -> &[EraseBlock] {
+        self.inner.erase_blocks()
+    }
+
+    fn page_size(&self) -> u32 {
+        self.inner.page_size()
+    }
+
+    fn read(&mut self, addr: u32, buf: &mut [u8]) -> CoreResult<()> {
+        self.inner.read(addr, buf)?;
+        for step in &self.steps {
+            step.apply(self.image, addr, buf);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, addr: u32, data: &[u8]) -> CoreResult<()> {
+        if !self.inner.is_valid_range(addr, data.len()) {
+            return Err(CoreError::AddressOutOfBounds);
+        }
+        let from_image = &self.image[addr as usize..addr as usize + data.len()];
+        self.steps.push(Step::Write {
+            addr,
+            len: data.len() as u32,
+            data: (data != from_image).then(|| data.to_vec()),
+        });
+        Ok(())
+    }
+
+    fn erase(&mut self, addr: u32, len: u32) -> CoreResult<()> {
+        if !self.inner.is_valid_range(addr, len as usize) {
+            return Err(CoreError::AddressOutOfBounds);
+        }
+        self.steps.push(Step::Erase { addr, len });
+        Ok(())
+    }
+
+    fn is_valid_range(&self, addr: u32, len: usize) -> bool {
+        self.inner.is_valid_range(addr, len)
+    }
+}
+
+/// Progress wrapper that only forwards the reading phase
+///
+/// Recorded erases and writes complete instantly, so their progress is
+/// reported when the plan is carried out instead.
+struct ReadPhase<'a, P: WriteProgress>(&'a mut P);
+
+impl<P: WriteProgress> WriteProgress for ReadPhase<'_, P> {
+    fn reading(&mut self, total_bytes: usize) {
+        self.0.reading(total_bytes);
+    }
+    fn read_progress(&mut self, bytes_read: usize) {
+        self.0.read_progress(bytes_read);
+    }
+    fn erasing(&mut self, _blocks_to_erase: usize, _bytes_to_erase: usize) {}
+    fn erase_progress(&mut self, _blocks_erased: usize, _bytes_erased: usize) {}
+    fn writing(&mut self, _bytes_to_write: usize) {}
+    fn write_progress(&mut self, _bytes_written: usize) {}
+    fn complete(&mut self, _stats: &WriteStats) {}
+}
+
+/// Carry out the outstanding steps of `plan`, saving the cursor after each
+/// while `checkpointing`
+fn run_steps<D: FlashDevice + ?Sized, P: WriteProgress>(
+    device: &mut D,
+    plan: &mut Plan,
+    image: &[u8],
+    sidecar: &Path,
+    mut checkpointing: bool,
+    progress: &mut P,
+) -> Result<WriteStats, Box<dyn Error>> {
+    let mut stats = WriteStats {
+        bytes_changed: plan.bytes_changed,
+        flash_modified: plan.done < plan.steps.len(),
+        ..Default::default()
+    };
+
+    // Writes restoring preserved data are interleaved with the erases; only
+    // the writes after the last erase get their own progress phase
+    let pending = &plan.steps[plan.done..];
+    let write_start = pending
+        .iter()
+        .rposition(|s| matches!(s, Step::Erase { .. }))
+        .map_or(plan.done, |i| plan.done + i + 1);
+    let (erase_count, erase_bytes) = pending
+        .iter()
+        .filter_map(|s| match s {
+            Step::Erase { len, .. } => Some(*len as usize),
+            Step::Write { .. } => None,
+        })
+        .fold((0, 0), |(n, bytes), len| (n + 1, bytes + len));
+    let write_bytes: usize = plan.steps[write_start..]
+        .iter()
+        .map(|s| s.data(image).len())
+        .sum();
+
+    if erase_count > 0 {
+        progress.erasing(erase_count, erase_bytes);
+    }
+    let mut phase_written = 0;
+    for i in plan.done..plan.steps.len() {
+        let step = &plan.steps[i];
+        if i == write_start && write_bytes > 0 {
+            progress.writing(write_bytes);
+        }
+        match step {
+            Step::Erase { addr, len } => {
+                device.erase(*addr, *len)?;
+                stats.erases_performed += 1;
+                stats.bytes_erased += *len as usize;
+                progress.erase_progress(stats.erases_performed, stats.bytes_erased);
+            }
+            Step::Write { addr, .. } => {
+                let data = step.data(image);
+                device.write(*addr, data)?;
+                stats.writes_performed += 1;
+                stats.bytes_written += data.len();
+                if i >= write_start {
+                    phase_written += data.len();
+                    progress.write_progress(phase_written);
+                }
+            }
+        }
+        plan.done = i + 1;
+        plan.checkpoint(sidecar, &mut checkpointing);
+    }
+
+    // Also removes a stale plan if checkpointing stopped part way
+    if sidecar.exists()
+        && let Err(e) = std::fs::remove_file(sidecar)
+    {
+        log::warn!("Failed to remove finished write plan {:?}: {}", sidecar, e);
+    }
+    progress.complete(&stats);
+    Ok(stats)
+}
+
+/// Smart write the included regions of `layout`, checkpointing to `sidecar`
+///
+/// Any plan left in `sidecar` by an earlier interrupted write is replaced,
+/// since the new plan is made from the current flash contents.
+pub fn write<D: FlashDevice + ?Sized, P: WriteProgress>(
+    device: &mut D,
+    layout: &Layout,
+    image: &[u8],
+    sidecar: &Path,
+    progress: &mut P,
+) -> Result<WriteStats, Box<dyn Error>> {
+    let mut recorder = PlanRecorder {
+        inner: &mut *device,
+        image,
+        steps: Vec::new(),
+    };
+    let planned = unified::smart_write_by_layout(
+        &mut recorder,
+        layout,
+        image,
+        &mut ReadPhase(&mut *progress),
+    )?;
+    let steps = recorder.steps;
+    if steps.is_empty() {
+        progress.complete(&planned);
+        return Ok(planned);
+    }
+
+    let mut plan = Plan {
+        version: SIDECAR_VERSION,
+        flash_size: device.size(),
+        image_sha256: image_digest(image),
+        bytes_changed: planned.bytes_changed,
+        done: 0,
+        steps,
+    };
+    let mut checkpointing = true;
+    plan.checkpoint(sidecar, &mut checkpointing);
+    run_steps(device, &mut plan, image, sidecar, checkpointing, progress)
+}
+
+/// Continue the write saved in `sidecar`
+///
+/// The steps already carried out are read back first; if any of them didn't
+/// take effect, the write has to be started again without resuming.
+pub fn resume<D: FlashDevice + ?Sized, P: WriteProgress>(
+    device: &mut D,
+    image: &[u8],
+    sidecar: &Path,
+    progress: &mut P,
+) -> Result<WriteStats, Box<dyn Error>> {
+    if !sidecar.exists() {
+        return Err(format!("No interrupted write to resume ({:?} not found)", sidecar).into());
+    }
+    let mut plan = Plan::load(sidecar)?;
+    if plan.flash_size != device.size() || image.len() != device.size() as usize {
+        return Err(format!(
+            "Write plan is for a {} byte flash, but this one is {} bytes",
+            plan.flash_size,
+            device.size()
+        )
+        .into());
+    }
+    if plan.image_sha256 != image_digest(image) {
+        return Err(
+            "Input image differs from the one being written when the write was interrupted".into(),
+        );
+    }
+
+    verify_done(device, &plan, image)?;
+    println!(
+        "Resuming write: {} of {} steps already done",
+        plan.done,
+        plan.steps.len()
+    );
+    run_steps(device, &mut plan, image, sidecar, true, progress)
+}
+
+/// Check that the writes already carried out are on the chip
+///
+/// Later erases may have cleared part of a write, so each is compared
+/// against its data with the rest of the completed steps applied on top.
+fn verify_done<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    plan: &Plan,
+    image: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let done = &plan.steps[..plan.done];
+    for (i, step) in done.iter().enumerate() {
+        let Step::Write { addr, .. } = step else {
+            continue;
+        };
+        let mut expected = step.data(image).to_vec();
+        for later in &done[i + 1..] {
+            later.apply(image, *addr, &mut expected);
+        }
+        let mut actual = vec![0u8; expected.len()];
+        device.read(*addr, &mut actual)?;
+        if let Some(offset) = actual.iter().zip(&expected).position(|(a, b)| a != b) {
+            return Err(format!(
+                "Completed write at 0x{:08X} doesn't match the plan: 0x{:02X} at 0x{:08X}, expected 0x{:02X}; write again without --resume",
+                addr,
+                actual[offset],
+                *addr as usize + offset,
+                expected[offset]
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rflasher_core::flash::SpiFlashDevice;
+    use rflasher_core::flash::unified::NoProgress;
+    use rflasher_core::layout::Region;
+    use rflasher_dummy::{DummyConfig, DummyFlash};
+
+    const SIZE: usize = 0x10000;
+
+    /// Dummy flash of SIZE with 4 KiB sectors
+    fn dummy_device(fill: impl Fn(usize) -> u8) -> SpiFlashDevice<DummyFlash> {
+        let config = DummyConfig {
+            size: SIZE,
+            ..DummyConfig::default()
+        };
+        let data: Vec<u8> = (0..SIZE).map(fill).collect();
+        DummyFlash::with_data(config, &data).into_device()
+    }
+
+    /// Two regions sharing the sector at 0x1000, so each write has to
+    /// preserve the other region's half of it
+    fn layout() -> Layout {
+        let mut layout = Layout::new();
+        layout.add_region(Region::new("a", 0x0000, 0x17FF));
+        layout.add_region(Region::new("b", 0x1800, 0x2FFF));
+        layout.include_all();
+        layout
+    }
+
+    fn sidecar(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rflasher-{}-{}.resume", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_resume_interrupted_write() {
+        let old = |i: usize| (i * 7) as u8;
+        let image: Vec<u8> = (0..SIZE).map(|i| (i * 13 + 1) as u8).collect();
+        let path = sidecar("interrupted");
+
+        let mut device = dummy_device(old);
+        device.master().fail_after(Some(20));
+        assert!(write(&mut device, &layout(), &image, &path, &mut NoProgress).is_err());
+        let plan = Plan::load(&path).unwrap();
+        assert!(plan.done > 0 && plan.done < plan.steps.len());
+        // The unaligned regions need data preserved around the shared sector
+        assert!(
+            plan.steps
+                .iter()
+                .any(|s| matches!(s, Step::Write { data: Some(_), .. }))
+        );
+
+        // A different image is refused
+        let mut other = image.clone();
+        other[0] ^= 1;
+        assert!(resume(&mut dummy_device(old), &other, &path, &mut NoProgress).is_err());
+
+        device.master().fail_after(None);
+        let stats = resume(&mut device, &image, &path, &mut NoProgress).unwrap();
+        assert!(stats.flash_modified);
+        let data = device.master().data();
+        assert_eq!(data[..0x3000], image[..0x3000]);
+        assert!((0x3000..SIZE).all(|i| data[i] == old(i)));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_resume_checks_completed_steps() {
+        let image = vec![0x5Au8; SIZE];
+        let path = sidecar("checks");
+
+        // Erased chip: one write per region and no erases. Region a's page
+        // programs go through, then b fails.
+        let mut device = dummy_device(|_| 0xFF);
+        device.master().fail_after(Some(0x1800 / 256));
+        assert!(write(&mut device, &layout(), &image, &path, &mut NoProgress).is_err());
+
+        // A completed write that didn't stick must not be skipped
+        let plan = Plan::load(&path).unwrap();
+        let Some(Step::Write { addr, .. }) = plan.steps[..plan.done]
+            .iter()
+            .find(|s| matches!(s, Step::Write { .. }))
+        else {
+            panic!("no completed write in {:?}", plan.steps);
+        };
+        device.master().data_mut()[*addr as usize] = 0xFF;
+        device.master().fail_after(None);
+        assert!(resume(&mut device, &image, &path, &mut NoProgress).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(resume(&mut device, &image, &path, &mut NoProgress).is_err());
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_steps() {
+        let path = sidecar("invalid");
+        let load = |steps: &str| {
+            let text = format!(
+                r#"{{"version":1,"flash_size":65536,"image_sha256":"","bytes_changed":0,"done":0,"steps":[{}]}}"#,
+                steps
+            );
+            std::fs::write(&path, text).unwrap();
+            Plan::load(&path).map(|plan| plan.steps)
+        };
+
+        assert_eq!(
+            load(r#"{"op":"write","addr":61440,"len":4096}"#).unwrap(),
+            [Step::Write {
+                addr: 0xF000,
+                len: 0x1000,
+                data: None
+            }]
+        );
+        // Past the end of the flash
+        assert!(load(r#"{"op":"write","addr":61440,"len":4097}"#).is_err());
+        // Overflowing the address space
+        assert!(load(r#"{"op":"erase","addr":4294967295,"len":2}"#).is_err());
+        // Inline data shorter than the write
+        assert!(load(r#"{"op":"write","addr":0,"len":4,"data":"dead"}"#).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_without_sidecar() {
+        // A plan that can't be saved doesn't stop the write
+        let image: Vec<u8> = (0..SIZE).map(|i| (i * 13 + 1) as u8).collect();
+        let path = std::env::temp_dir()
+            .join(format!("rflasher-missing-{}", std::process::id()))
+            .join("image.bin.resume");
+
+        let mut device = dummy_device(|_| 0xFF);
+        let stats = write(&mut device, &layout(), &image, &path, &mut NoProgress).unwrap();
+        assert!(stats.flash_modified);
+        assert_eq!(device.master().data()[..0x3000], image[..0x3000]);
+        assert!(!path.exists());
+    }
+}
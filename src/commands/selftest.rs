@@ -0,0 +1,133 @@
+//! Programmer self-test
+//!
+//! `selftest` checks the wiring and the programmer with commands that don't
+//! change the chip: RDID, RDSR and a repeated read that must return the
+//! same data both times. It talks to the SPI master directly, so a chip
+//! that fails to probe can still be tested.
+
+use rflasher_core::chip::ChipDatabase;
+use rflasher_core::programmer::SpiMaster;
+use rflasher_core::protocol;
+use rflasher_flash::{BoxedSpiMaster, is_opaque_programmer, open_spi_programmer};
+
+/// Bytes read twice by the stability check
+const STABILITY_READ_SIZE: usize = 4096;
+
+/// Outcome of one check: what was seen, or why it failed
+type CheckResult = Result<String, String>;
+
+/// Read the JEDEC ID and look it up in the database
+fn check_jedec_id(master: &mut BoxedSpiMaster, db: &ChipDatabase) -> CheckResult {
+    let (manufacturer, device) =
+        protocol::read_jedec_id(&mut **master).map_err(|e| e.to_string())?;
+    let id = format!("{:02X}:{:04X}", manufacturer, device);
+    if !protocol::is_valid_jedec_manufacturer(manufacturer) {
+        return Err(format!("{}, no chip answers (check wiring and power)", id));
+    }
+    Ok(match db.find_by_jedec_id(manufacturer, device).next() {
+        Some(chip) => format!("{} ({} {})", id, chip.vendor, chip.name),
+        None => format!("{} (not in database)", id),
+    })
+}
+
+/// Read status register 1, which reads 0xFF with MISO floating
+fn check_status(master: &mut BoxedSpiMaster) -> CheckResult {
+    let status = protocol::read_status1(&mut **master).map_err(|e| e.to_string())?;
+    if status == 0xFF {
+        return Err("SR1=0xFF, MISO may be floating".into());
+    }
+    Ok(format!("SR1=0x{:02X}", status))
+}
+
+/// Read the start of the chip in transfers the master supports
+fn read_start<M: SpiMaster + ?Sized>(master: &mut M, buf: &mut [u8]) -> Result<(), String> {
+    let chunk_size = master.max_read_len().max(1);
+    for (i, chunk) in buf.chunks_mut(chunk_size).enumerate() {
+        protocol::read_3b(master, (i * chunk_size) as u32, chunk).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Read the start of the chip twice, which must return the same data
+///
+/// Differences point at marginal wiring or too high an SPI clock.
+fn check_read_stability<M: SpiMaster + ?Sized>(master: &mut M) -> CheckResult {
+    let mut first = vec![0u8; STABILITY_READ_SIZE];
+    let mut second = vec![0u8; STABILITY_READ_SIZE];
+    read_start(master, &mut first)?;
+    read_start(master, &mut second)?;
+
+    let differing = first.iter().zip(&second).filter(|(a, b)| a != b).count();
+    if differing > 0 {
+        return Err(format!(
+            "{} of {} bytes differ between two reads (try a lower SPI speed)",
+            differing, STABILITY_READ_SIZE
+        ));
+    }
+    if first.iter().all(|&b| b == first[0]) {
+        return Ok(format!(
+            "{} bytes identical, all 0x{:02X}",
+            STABILITY_READ_SIZE, first[0]
+        ));
+    }
+    Ok(format!("{} bytes identical", STABILITY_READ_SIZE))
+}
+
+/// Run the self-test and print the result of each check
+pub fn cmd_selftest(programmer: &str, db: &ChipDatabase) -> Result<(), Box<dyn std::error::Error>> {
+    if is_opaque_programmer(programmer) {
+        return Err("Self-test needs an SPI programmer, opaque programmers have no SPI bus".into());
+    }
+    let mut master = open_spi_programmer(programmer)?;
+
+    let checks: [(&str, CheckResult); 3] = [
+        ("JEDEC ID", check_jedec_id(&mut master, db)),
+        ("Status register", check_status(&mut master)),
+        ("Read stability", check_read_stability(&mut *master)),
+    ];
+
+    println!("Self-test of {}:", programmer);
+    let mut failed = 0;
+    for (name, result) in &checks {
+        match result {
+            Ok(detail) => println!("  {:16} PASS  {}", name, detail),
+            Err(detail) => {
+                failed += 1;
+                println!("  {:16} FAIL  {}", name, detail);
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} checks failed", failed, checks.len()).into());
+    }
+    println!("All checks passed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rflasher_dummy::{DummyConfig, DummyFlash};
+
+    #[test]
+    fn test_read_stability_in_small_transfers() {
+        let config = DummyConfig {
+            max_read_len: 256,
+            ..DummyConfig::default()
+        };
+        let data: Vec<u8> = (0..STABILITY_READ_SIZE).map(|i| (i / 3) as u8).collect();
+        let mut flash = DummyFlash::with_data(config, &data);
+
+        assert_eq!(
+            check_read_stability(&mut flash),
+            Ok(format!("{} bytes identical", STABILITY_READ_SIZE))
+        );
+
+        let mut erased = DummyFlash::new_default();
+        assert_eq!(
+            check_read_stability(&mut erased),
+            Ok(format!("{} bytes identical, all 0xFF", STABILITY_READ_SIZE))
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! SFDP dump command
+//!
+//! Reads the raw SFDP region with RDSFDP (0x5A) and shows it as a hex dump
+//! with a decoded header summary, as raw bytes, or as JSON.
+
+use rflasher_core::sfdp::{self, ParameterHeader, SfdpHeader, SfdpInfo};
+use rflasher_flash::{is_opaque_programmer, open_spi_programmer};
+use std::io::Write;
+
+/// Output format of the `sfdp` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SfdpOutput {
+    /// Decoded summary followed by a hex dump
+    Summary,
+    /// Raw bytes only, for piping to a file
+    Raw,
+    /// Parsed structures as JSON
+    Json,
+}
+
+/// Parsed SFDP structures, as emitted by `--json`
+#[derive(serde::Serialize)]
+struct SfdpJson<'a> {
+    header: &'a SfdpHeader,
+    parameter_headers: &'a [ParameterHeader],
+    parsed: Option<&'a SfdpInfo>,
+}
+
+/// Split a raw SFDP dump into its header and parameter headers
+fn parse_headers(raw: &[u8]) -> (SfdpHeader, Vec<ParameterHeader>) {
+    let header = SfdpHeader::parse(raw[..8].try_into().unwrap());
+    let param_headers = raw[8..]
+        .chunks_exact(8)
+        .take(header.num_param_headers())
+        .map(|chunk| ParameterHeader::parse(chunk.try_into().unwrap()))
+        .collect();
+    (header, param_headers)
+}
+
+/// Format a 16-bytes-per-line hex dump with ASCII column
+fn hex_dump(data: &[u8]) -> String {
+    data.chunks(16)
+        .enumerate()
+        .map(|(line, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:06X}: {:<47}  {}\n", line * 16, hex.join(" "), ascii)
+        })
+        .collect()
+}
+
+/// Run the SFDP dump command
+pub fn cmd_sfdp(programmer: &str, output: SfdpOutput) -> Result<(), Box<dyn std::error::Error>> {
+    if is_opaque_programmer(programmer) {
+        println!("SFDP not available on opaque programmers");
+        return Ok(());
+    }
+
+    let mut master = open_spi_programmer(programmer)?;
+    let raw = sfdp::read_raw(&mut *master).map_err(|e| format!("Failed to read SFDP: {}", e))?;
+
+    if output == SfdpOutput::Raw {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&raw)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+
+    let (header, param_headers) = parse_headers(&raw);
+
+    if output == SfdpOutput::Json {
+        let parsed = sfdp::probe(&mut *master).ok();
+        let json = SfdpJson {
+            header: &header,
+            parameter_headers: &param_headers,
+            parsed: parsed.as_ref(),
+        };
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        return Ok(());
+    }
+
+    println!("SFDP Header");
+    println!("===========");
+    println!(
+        "Signature:       0x{:08X}{}",
+        header.signature,
+        if header.is_valid() { " (SFDP)" } else { "" }
+    );
+    println!(
+        "Revision:        {}.{}",
+        header.revision.major, header.revision.minor
+    );
+    println!("Param headers:   {}", header.num_param_headers());
+    println!("Access protocol: 0x{:02X}", header.access_protocol);
+    println!();
+
+    println!("Parameter Headers");
+    println!("=================");
+    for (i, ph) in param_headers.iter().enumerate() {
+        println!(
+            "  #{}: ID 0x{:04X}{}, rev {}.{}, {} DWORDs at 0x{:06X}",
+            i,
+            ph.id,
+            match ph.id {
+                sfdp::PARAM_ID_BASIC => " (BFPT)",
+                sfdp::PARAM_ID_SCCR_MAP => " (SCCR)",
+                _ => "",
+            },
+            ph.revision.major,
+            ph.revision.minor,
+            ph.length_dwords,
+            ph.table_pointer
+        );
+    }
+    println!();
+
+    println!("Raw SFDP ({} bytes)", raw.len());
+    println!("==================");
+    print!("{}", hex_dump(&raw));
+
+    Ok(())
+}
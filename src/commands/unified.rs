@@ -3,15 +3,54 @@
 //! These commands work the same way regardless of whether the underlying
 //! programmer is SPI-based or opaque.
 
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use rflasher_core::flash::unified::{WriteProgress, WriteStats};
-use rflasher_core::flash::{FlashDevice, unified};
+use super::resume;
+use crate::cli::{DigestAlgorithm, ImageFormat, VerifyMode};
+use crate::format::SparseImage;
+use crate::format::ihex::{self, IhexWriter};
+use crate::format::srec::{self, SrecWriter};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use rflasher_core::flash::unified::{
+    DryRunDevice, PlannedOp, VerifyMismatch, VerifyingDevice, WriteProgress, WriteRange,
+    WriteStats, get_all_write_ranges,
+};
+use rflasher_core::flash::{self, FlashDevice, unified};
 use rflasher_core::layout::Layout;
+use sha2::{Digest, Sha256};
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
+/// Whether progress bars are drawn, see [`set_progress_enabled`]
+static PROGRESS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enable or disable progress bars for all commands
+///
+/// When disabled, bars are created hidden so the summary lines printed by
+/// each command are the only output.
+pub fn set_progress_enabled(enabled: bool) {
+    PROGRESS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Create a progress bar, hidden if progress bars are disabled
+fn new_progress_bar(total: u64) -> ProgressBar {
+    if PROGRESS_ENABLED.load(Ordering::Relaxed) {
+        ProgressBar::new(total)
+    } else {
+        ProgressBar::hidden()
+    }
+}
+
+/// Create a spinner, hidden if progress bars are disabled
+fn new_spinner() -> ProgressBar {
+    if PROGRESS_ENABLED.load(Ordering::Relaxed) {
+        ProgressBar::new_spinner()
+    } else {
+        ProgressBar::hidden()
+    }
+}
+
 // =============================================================================
 // Helper functions
 // =============================================================================
@@ -34,6 +73,107 @@ fn read_file(path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     Ok(data)
 }
 
+/// Parse an address-based input image
+fn load_sparse(
+    path: &Path,
+    format: ImageFormat,
+) -> Result<SparseImage, Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)?;
+    let (sparse, kind) = match format {
+        ImageFormat::Ihex => (ihex::parse(&text).map_err(|e| e.to_string()), "Intel HEX"),
+        ImageFormat::Srec => (srec::parse(&text).map_err(|e| e.to_string()), "S-record"),
+        ImageFormat::Binary => unreachable!("binary images are not address-based"),
+    };
+    let sparse = sparse.map_err(|e| format!("{:?}: {}", path, e))?;
+    println!(
+        "Read {} data bytes from {} file {:?}",
+        sparse.data_len(),
+        kind,
+        path
+    );
+    Ok(sparse)
+}
+
+/// Load an input image in the given format
+///
+/// Address-based formats are expanded to a full flash-size image with
+/// unspecified addresses filled with 0xFF.
+pub fn load_image(
+    path: &Path,
+    format: ImageFormat,
+    flash_size: u32,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    match format {
+        ImageFormat::Binary => read_file(path),
+        ImageFormat::Ihex | ImageFormat::Srec => {
+            Ok(load_sparse(path, format)?.to_flash_image(flash_size)?)
+        }
+    }
+}
+
+/// Expected image contents, fetched one chunk at a time
+///
+/// Lets verify and diff compare the flash against an input file without
+/// holding a flash-sized copy of the file in memory.
+enum ImageSource<'a> {
+    /// Image already in memory
+    Memory(&'a [u8]),
+    /// Raw binary file and its length, read at file offsets
+    File(File, usize),
+    /// Address-based image, expanded to the given flash size on demand
+    Sparse(SparseImage, u32),
+}
+
+impl ImageSource<'_> {
+    /// Open an input image in the given format without loading it whole
+    fn open(
+        path: &Path,
+        format: ImageFormat,
+        flash_size: u32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        match format {
+            ImageFormat::Binary => {
+                let file = File::open(path)?;
+                let len = file.metadata()?.len() as usize;
+                println!("Comparing against {} bytes from {:?}", len, path);
+                Ok(ImageSource::File(file, len))
+            }
+            ImageFormat::Ihex | ImageFormat::Srec => {
+                let sparse = load_sparse(path, format)?;
+                sparse.check_fits(flash_size)?;
+                Ok(ImageSource::Sparse(sparse, flash_size))
+            }
+        }
+    }
+
+    /// Image size in bytes
+    fn len(&self) -> usize {
+        match self {
+            ImageSource::Memory(data) => data.len(),
+            ImageSource::File(_, len) => *len,
+            ImageSource::Sparse(_, flash_size) => *flash_size as usize,
+        }
+    }
+
+    /// Check if the image has no bytes
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fill `buf` with the image bytes starting at `offset`
+    fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> std::io::Result<()> {
+        match self {
+            ImageSource::Memory(data) => buf.copy_from_slice(&data[offset..offset + buf.len()]),
+            ImageSource::File(file, _) => {
+                file.seek(SeekFrom::Start(offset as u64))?;
+                file.read_exact(buf)?;
+            }
+            ImageSource::Sparse(sparse, _) => sparse.fill(offset as u32, buf),
+        }
+        Ok(())
+    }
+}
+
 /// Create a standard progress bar style
 fn create_progress_bar_style() -> Result<ProgressStyle, Box<dyn std::error::Error>> {
     Ok(ProgressStyle::default_bar()
@@ -46,7 +186,7 @@ fn create_progress_bar_with_phase(
     total: u64,
     phase: &str,
 ) -> Result<ProgressBar, Box<dyn std::error::Error>> {
-    let pb = ProgressBar::new(total);
+    let pb = new_progress_bar(total);
     pb.set_style(
         ProgressStyle::default_bar()
             .template(&format!(
@@ -77,8 +217,55 @@ fn display_included_regions(included: &[&rflasher_core::layout::Region], action:
     });
 }
 
+/// Print the operations recorded by a dry run
+fn print_dry_run(ops: &[PlannedOp]) {
+    let erases: Vec<_> = ops
+        .iter()
+        .filter_map(|op| match *op {
+            PlannedOp::Erase { addr, len } => Some((addr, len)),
+            PlannedOp::Write { .. } => None,
+        })
+        .collect();
+    let writes: Vec<_> = ops
+        .iter()
+        .filter_map(|op| match *op {
+            PlannedOp::Write { addr, len } => Some((addr, len)),
+            PlannedOp::Erase { .. } => None,
+        })
+        .collect();
+
+    println!();
+    println!(
+        "Dry run: would erase {} block(s) ({} bytes):",
+        erases.len(),
+        erases.iter().map(|&(_, len)| len as u64).sum::<u64>()
+    );
+    for (addr, len) in &erases {
+        println!(
+            "  0x{:08X} - 0x{:08X} ({})",
+            addr,
+            addr + len - 1,
+            super::format_size(*len)
+        );
+    }
+    println!(
+        "Dry run: would write {} bytes in {} chunk(s):",
+        writes.iter().map(|&(_, len)| len as u64).sum::<u64>(),
+        writes.len()
+    );
+    for (addr, len) in &writes {
+        println!(
+            "  0x{:08X} - 0x{:08X} ({} bytes)",
+            addr,
+            addr + len - 1,
+            len
+        );
+    }
+    println!("No changes were made to the flash.");
+}
+
 /// Create a layout covering the entire flash
-fn full_flash_layout(flash_size: u32) -> Layout {
+pub fn full_flash_layout(flash_size: u32) -> Layout {
     use rflasher_core::layout::{LayoutSource, Region};
 
     let mut layout = Layout::with_source(LayoutSource::Manual);
@@ -88,6 +275,54 @@ fn full_flash_layout(flash_size: u32) -> Layout {
     layout
 }
 
+/// Create a layout with a single region of `len` bytes at `start`
+///
+/// This lets `--start`/`--length` go through the same region code as a
+/// layout file, including the handling of unaligned region boundaries.
+pub fn range_layout(
+    flash_size: u32,
+    start: u32,
+    len: u32,
+) -> Result<Layout, Box<dyn std::error::Error>> {
+    use rflasher_core::layout::{LayoutSource, Region};
+
+    if len == 0 {
+        return Err("Range length must not be zero".into());
+    }
+    let end = start
+        .checked_add(len - 1)
+        .filter(|&end| end < flash_size)
+        .ok_or_else(|| {
+            format!(
+                "Range 0x{:08X} + 0x{:X} bytes extends beyond the flash chip (0x{:X} bytes)",
+                start, len, flash_size
+            )
+        })?;
+
+    let mut layout = Layout::with_source(LayoutSource::Manual);
+    let mut region = Region::new("range", start, end);
+    region.included = true;
+    layout.add_region(region);
+    Ok(layout)
+}
+
+/// Check that a range is aligned to the device's smallest erase block
+pub fn check_erase_alignment<D: FlashDevice + ?Sized>(
+    device: &D,
+    start: u32,
+    len: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let granularity = device.erase_granularity();
+    if granularity > 1 && (!start.is_multiple_of(granularity) || !len.is_multiple_of(granularity)) {
+        return Err(format!(
+            "Erase range 0x{:08X} + 0x{:X} bytes is not aligned to the {}-byte erase block",
+            start, len, granularity
+        )
+        .into());
+    }
+    Ok(())
+}
+
 // =============================================================================
 // Progress reporting
 // =============================================================================
@@ -101,8 +336,13 @@ pub struct IndicatifProgress {
 
 impl IndicatifProgress {
     pub fn new() -> Self {
+        let multi = if PROGRESS_ENABLED.load(Ordering::Relaxed) {
+            MultiProgress::new()
+        } else {
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        };
         Self {
-            multi: MultiProgress::new(),
+            multi,
             current_bar: None,
             phase: "",
         }
@@ -112,13 +352,13 @@ impl IndicatifProgress {
         self.phase = phase;
         let pb = self.multi.add(
             create_progress_bar_with_phase(total, phase)
-                .unwrap_or_else(|_| ProgressBar::new(total)),
+                .unwrap_or_else(|_| new_progress_bar(total)),
         );
         self.current_bar = Some(pb);
     }
 
     fn create_spinner(&mut self, message: String) {
-        let pb = self.multi.add(ProgressBar::new_spinner());
+        let pb = self.multi.add(new_spinner());
         pb.set_style(create_spinner_style().unwrap_or_else(|_| ProgressStyle::default_spinner()));
         pb.set_message(message);
         pb.enable_steady_tick(Duration::from_millis(100));
@@ -198,13 +438,53 @@ impl WriteProgress for IndicatifProgress {
 /// Default chunk size for reading (4 KiB)
 const READ_CHUNK_SIZE: usize = 4096;
 
+/// Stream the full flash image to `out`, one chunk at a time
+///
+/// Included regions are read from the device, everything else is filled
+/// with 0xFF. At most one chunk is held in memory.
+///
+/// Returns the number of bytes read from included regions.
+fn read_to_writer<D: FlashDevice + ?Sized, W: Write>(
+    device: &mut D,
+    included: &[&rflasher_core::layout::Region],
+    out: &mut W,
+    pb: &ProgressBar,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let flash_size = device.size() as usize;
+    let mut buf = vec![0u8; READ_CHUNK_SIZE];
+    let mut bytes_read = 0usize;
+
+    for offset in (0..flash_size).step_by(READ_CHUNK_SIZE) {
+        let chunk_end = std::cmp::min(offset + READ_CHUNK_SIZE, flash_size);
+        let chunk = &mut buf[..chunk_end - offset];
+        chunk.fill(0xFF);
+
+        for region in included {
+            let start = std::cmp::max(region.start as usize, offset);
+            let end = std::cmp::min(region.end as usize + 1, chunk_end);
+            if start >= end {
+                continue;
+            }
+
+            device.read(start as u32, &mut chunk[start - offset..end - offset])?;
+            bytes_read += end - start;
+            pb.set_position(bytes_read as u64);
+        }
+
+        out.write_all(chunk)?;
+    }
+
+    Ok(bytes_read)
+}
+
 /// Run the unified read command
 pub fn run_read<D: FlashDevice + ?Sized>(
     device: &mut D,
     output: &Path,
+    format: ImageFormat,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let layout = full_flash_layout(device.size());
-    run_read_with_layout(device, output, &layout)
+    run_read_with_layout(device, output, &layout, format)
 }
 
 /// Run the unified read command with layout
@@ -212,6 +492,7 @@ pub fn run_read_with_layout<D: FlashDevice + ?Sized>(
     device: &mut D,
     output: &Path,
     layout: &Layout,
+    format: ImageFormat,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let flash_size = device.size();
     print_flash_size(flash_size);
@@ -227,40 +508,31 @@ pub fn run_read_with_layout<D: FlashDevice + ?Sized>(
     // Calculate total bytes to read
     let total_bytes: usize = included.iter().map(|r| r.size() as usize).sum();
 
-    // Allocate buffer for full chip (fill with 0xFF for non-included regions)
-    let mut data = vec![0xFFu8; flash_size as usize];
-
     // Create progress bar
-    let pb = ProgressBar::new(total_bytes as u64);
+    let pb = new_progress_bar(total_bytes as u64);
     pb.set_style(create_progress_bar_style()?);
 
-    // Read each included region
-    let bytes_read = included
-        .iter()
-        .flat_map(|region| {
-            (region.start..=region.end)
-                .step_by(READ_CHUNK_SIZE)
-                .map(move |offset| (region, offset))
-        })
-        .try_fold(0usize, |bytes_read, (region, offset)| {
-            let remaining = (region.end - offset + 1) as usize;
-            let chunk_size = std::cmp::min(READ_CHUNK_SIZE, remaining);
-            let chunk = &mut data[offset as usize..offset as usize + chunk_size];
-
-            device.read(offset, chunk)?;
-
-            let new_bytes_read = bytes_read + chunk_size;
-            pb.set_position(new_bytes_read as u64);
-            Ok::<_, Box<dyn std::error::Error>>(new_bytes_read)
-        })?;
+    // Stream to the file chunk by chunk, so large chips are never held in memory
+    let mut file = File::create(output)?;
+    let bytes_read = match format {
+        ImageFormat::Binary => read_to_writer(device, &included, &mut file, &pb)?,
+        ImageFormat::Ihex => {
+            let mut writer = IhexWriter::new(BufWriter::new(file));
+            let bytes_read = read_to_writer(device, &included, &mut writer, &pb)?;
+            writer.finish()?;
+            bytes_read
+        }
+        ImageFormat::Srec => {
+            let mut writer = SrecWriter::new(BufWriter::new(file), flash_size)?;
+            let bytes_read = read_to_writer(device, &included, &mut writer, &pb)?;
+            writer.finish()?;
+            bytes_read
+        }
+    };
 
     pb.finish_with_message("Read complete");
 
-    // Write to file
-    let mut file = File::create(output)?;
-    file.write_all(&data)?;
-
-    println!("Wrote {} bytes to {:?}", data.len(), output);
+    println!("Wrote {} bytes to {:?}", flash_size, output);
     println!(
         "  ({} bytes from included regions, rest filled with 0xFF)",
         bytes_read
@@ -273,44 +545,119 @@ pub fn run_read_with_layout<D: FlashDevice + ?Sized>(
 // Write operations
 // =============================================================================
 
-/// Run the unified write command
-pub fn run_write<D: FlashDevice + ?Sized>(
-    device: &mut D,
-    input: &Path,
-    do_verify: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut layout = full_flash_layout(device.size());
-    run_write_with_layout(device, input, &mut layout, do_verify)
+/// How a write is verified
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteVerify {
+    /// Don't verify
+    None,
+    /// Read back all written regions after the write has finished
+    ReadBack,
+    /// Read back each written region after the write has finished, and
+    /// report the result of each one
+    PerRegion,
+    /// Read back each chunk right after writing or erasing it, in a single
+    /// pass
+    PerChunk,
 }
 
-/// Run the unified write command with layout
-pub fn run_write_with_layout<D: FlashDevice + ?Sized>(
+impl WriteVerify {
+    /// Pick the verify mode for a write
+    ///
+    /// Opaque programmers (no probed chip) are verified per chunk for
+    /// `--verify=full`, since a separate read-back pass is as slow as the
+    /// write itself there.
+    pub fn new(verify: VerifyMode, opaque: bool) -> Self {
+        match (verify, opaque) {
+            (VerifyMode::None, _) => WriteVerify::None,
+            (VerifyMode::Regions, _) => WriteVerify::PerRegion,
+            (VerifyMode::Full, true) => WriteVerify::PerChunk,
+            (VerifyMode::Full, false) => WriteVerify::ReadBack,
+        }
+    }
+}
+
+/// Run the unified write command
+///
+/// An image larger than the flash is rejected. A smaller one is written at
+/// offset 0, leaving the rest of the flash unchanged, or with `pad` it is
+/// padded with 0xFF to the flash size so the rest is erased.
+pub fn run_write<D: FlashDevice + ?Sized>(
     device: &mut D,
     input: &Path,
-    layout: &mut Layout,
-    do_verify: bool,
+    format: ImageFormat,
+    verify: WriteVerify,
+    dry_run: bool,
+    resume: bool,
+    pad: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let flash_size = device.size();
     print_flash_size(flash_size);
 
-    // Read input file
-    let file_data = read_file(input)?;
+    let mut file_data = load_image(input, format, flash_size)?;
     let file_size = file_data.len();
-
-    // Display included regions
-    let included: Vec<_> = layout.included_regions().collect();
-    if included.is_empty() {
-        return Err("No regions selected for writing. Use --include to select regions.".into());
+    if file_size > flash_size as usize {
+        return Err(format!(
+            "Image is larger than the flash chip: {:?} has {} bytes, the chip {} bytes",
+            input, file_size, flash_size
+        )
+        .into());
+    }
+    if file_size < flash_size as usize {
+        if pad {
+            println!(
+                "Padding image ({} bytes) with 0xFF to the chip size ({} bytes)",
+                file_size, flash_size
+            );
+            file_data.resize(flash_size as usize, 0xFF);
+        } else {
+            println!(
+                "Note: Image ({} bytes) is smaller than the chip ({} bytes), writing it at \
+                 offset 0 and leaving the rest unchanged (use --pad to erase the rest)",
+                file_size, flash_size
+            );
+        }
     }
 
-    display_included_regions(&included, "Writing");
+    let layout = full_flash_layout(flash_size);
+    write_image(device, input, file_data, &layout, verify, dry_run, resume)
+}
 
-    // Check for readonly regions
-    let readonly = layout.readonly_included();
-    if !readonly.is_empty() {
-        let names: Vec<_> = readonly.iter().map(|r| r.name.as_str()).collect();
-        return Err(format!("Cannot write to readonly region(s): {}", names.join(", ")).into());
+/// Smart write, checkpointed to `sidecar` with `resume`
+///
+/// With `resume`, a write left in `sidecar` by an interrupted run is
+/// continued, and otherwise a new plan is saved there. Without it, nothing
+/// is written next to the input.
+fn checkpointed_write<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    layout: &Layout,
+    image: &[u8],
+    sidecar: &Path,
+    resume: bool,
+    progress: &mut IndicatifProgress,
+) -> Result<WriteStats, Box<dyn std::error::Error>> {
+    if !resume {
+        Ok(unified::smart_write_by_layout(
+            device, layout, image, progress,
+        )?)
+    } else if sidecar.exists() {
+        resume::resume(device, image, sidecar, progress)
+    } else {
+        resume::write(device, layout, image, sidecar, progress)
     }
+}
+
+/// Work out where an input file of `file_size` bytes goes in the layout
+///
+/// See the `write` command help for how the file size is interpreted.
+/// Returns the layout to use with the file (a single region is cut short
+/// when the file is smaller), the flash address of the file's first byte
+/// and the number of bytes it covers.
+fn place_file(
+    layout: &Layout,
+    file_size: usize,
+    flash_size: u32,
+) -> Result<(Layout, u32, usize), Box<dyn std::error::Error>> {
+    let included: Vec<_> = layout.included_regions().collect();
 
     // Validate file size
     if file_size > flash_size as usize {
@@ -329,58 +676,200 @@ pub fn run_write_with_layout<D: FlashDevice + ?Sized>(
         .into());
     }
 
-    let (image, effective_write_size) = if file_size == flash_size as usize {
+    if file_size == flash_size as usize {
         // Full flash image
-        (file_data, included.iter().map(|r| r.size() as usize).sum())
-    } else {
-        // Single region, file <= region size
-        let region = &included[0];
-        let region_size = region.size() as usize;
-
-        if file_size > region_size {
-            return Err(format!(
-                "File size ({} bytes) larger than region '{}' ({} bytes) but smaller than flash size",
-                file_size, region.name, region_size
-            )
-            .into());
-        }
+        let effective_size = included.iter().map(|r| r.size() as usize).sum();
+        return Ok((layout.clone(), 0, effective_size));
+    }
 
-        let mut chip_image = vec![0xFFu8; flash_size as usize];
-        let dest_start = region.start as usize;
-        chip_image[dest_start..dest_start + file_size].copy_from_slice(&file_data);
+    // Single region, file <= region size
+    let region = &included[0];
+    let region_size = region.size() as usize;
 
-        if file_size < region_size {
-            println!(
-                "Note: File ({} bytes) is smaller than region ({} bytes)",
-                file_size, region_size
-            );
-        }
+    if file_size > region_size {
+        return Err(format!(
+            "File size ({} bytes) larger than region '{}' ({} bytes) but smaller than flash size",
+            file_size, region.name, region_size
+        )
+        .into());
+    }
 
-        (chip_image, file_size)
-    };
+    // run_write explains short images for the whole flash itself
+    if file_size < region_size && region_size < flash_size as usize {
+        println!(
+            "Note: File ({} bytes) is smaller than region ({} bytes)",
+            file_size, region_size
+        );
+    }
 
     // Adjust layout if file is smaller than region
-    let effective_layout = if included.len() == 1 && file_size < included[0].size() as usize {
-        let region = &included[0];
-        let mut modified_layout = layout.clone();
+    let mut effective_layout = layout.clone();
+    if file_size < region_size {
         let actual_end = region.start + file_size as u32 - 1;
-        modified_layout.update_region_end(&region.name, actual_end)?;
-        modified_layout
+        effective_layout.update_region_end(&region.name, actual_end)?;
+    }
+
+    Ok((effective_layout, region.start, file_size))
+}
+
+/// Place an input file in a flash-sized image according to the layout
+///
+/// Returns the image, the layout to use with it and the number of bytes
+/// it covers, as for [`place_file`].
+fn image_for_layout(
+    layout: &Layout,
+    file_data: Vec<u8>,
+    flash_size: u32,
+) -> Result<(Vec<u8>, Layout, usize), Box<dyn std::error::Error>> {
+    let (effective_layout, base, effective_size) = place_file(layout, file_data.len(), flash_size)?;
+
+    let image = if base == 0 && file_data.len() == flash_size as usize {
+        file_data
     } else {
-        layout.clone()
+        let mut chip_image = vec![0xFFu8; flash_size as usize];
+        let dest_start = base as usize;
+        chip_image[dest_start..dest_start + file_data.len()].copy_from_slice(&file_data);
+        chip_image
     };
 
+    Ok((image, effective_layout, effective_size))
+}
+
+/// Run the unified write command with layout
+///
+/// With `dry_run`, the erases and writes are planned from the current flash
+/// contents and printed, but not performed. With `resume`, the plan is
+/// checkpointed next to the input so that running the same write again
+/// continues it if it was interrupted (see [`super::resume`]).
+pub fn run_write_with_layout<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    input: &Path,
+    format: ImageFormat,
+    layout: &mut Layout,
+    verify: WriteVerify,
+    dry_run: bool,
+    resume: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let flash_size = device.size();
+    print_flash_size(flash_size);
+
+    // Read input file
+    let file_data = load_image(input, format, flash_size)?;
+    write_image(device, input, file_data, layout, verify, dry_run, resume)
+}
+
+/// Get the layout of the FMAP embedded in a full flash image
+///
+/// This lets a coreboot image be written region by region without a
+/// layout file. The FMAP has to describe a flash of the chip's size, so its
+/// regions line up with the chip.
+pub fn image_fmap_layout(
+    file_data: &[u8],
+    flash_size: u32,
+) -> Result<Layout, Box<dyn std::error::Error>> {
+    use rflasher_core::layout::{fmap_size, parse_fmap};
+
+    if file_data.len() != flash_size as usize {
+        return Err(format!(
+            "Image with an FMAP layout must be exactly flash size ({} bytes), got {} bytes",
+            flash_size,
+            file_data.len()
+        )
+        .into());
+    }
+    let layout =
+        parse_fmap(file_data).map_err(|e| format!("No usable FMAP in the input file: {}", e))?;
+    if let Some(size) = fmap_size(file_data)
+        && size != flash_size
+    {
+        return Err(format!(
+            "The image's FMAP describes a {} byte flash, but the chip has {} bytes",
+            size, flash_size
+        )
+        .into());
+    }
+    layout.validate(flash_size)?;
+    Ok(layout)
+}
+
+/// Write a loaded image to the included regions of a layout
+///
+/// `input` is the image's path, used for the resume plan next to it.
+pub fn write_image<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    input: &Path,
+    file_data: Vec<u8>,
+    layout: &Layout,
+    verify: WriteVerify,
+    dry_run: bool,
+    resume: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let flash_size = device.size();
+
+    // Display included regions
+    let included: Vec<_> = layout.included_regions().collect();
+    if included.is_empty() {
+        return Err("No regions selected for writing. Use --include to select regions.".into());
+    }
+
+    display_included_regions(&included, "Writing");
+
+    // Check for readonly regions
+    let readonly = layout.readonly_included();
+    if !readonly.is_empty() {
+        let names: Vec<_> = readonly.iter().map(|r| r.name.as_str()).collect();
+        return Err(format!("Cannot write to readonly region(s): {}", names.join(", ")).into());
+    }
+
+    let (image, effective_layout, effective_write_size) =
+        image_for_layout(layout, file_data, flash_size)?;
+
     // Smart write using layout
     let mut progress = IndicatifProgress::new();
-    let stats = unified::smart_write_by_layout(device, &effective_layout, &image, &mut progress)?;
+    if dry_run {
+        let mut dry_run = DryRunDevice::new(device);
+        unified::smart_write_by_layout(&mut dry_run, &effective_layout, &image, &mut progress)?;
+        print_dry_run(dry_run.ops());
+        return Ok(());
+    }
+    let sidecar = resume::sidecar_path(input);
+    let stats = if verify == WriteVerify::PerChunk {
+        // Each chunk is read back as soon as it is written
+        let mut verifying = VerifyingDevice::new(device);
+        checkpointed_write(
+            &mut verifying,
+            &effective_layout,
+            &image,
+            &sidecar,
+            resume,
+            &mut progress,
+        )?
+    } else {
+        checkpointed_write(
+            device,
+            &effective_layout,
+            &image,
+            &sidecar,
+            resume,
+            &mut progress,
+        )?
+    };
 
     // Verify if requested
-    if do_verify {
-        if stats.flash_modified {
+    match verify {
+        WriteVerify::ReadBack if stats.flash_modified => {
             verify_by_layout(device, &effective_layout, &image)?;
-        } else {
+        }
+        WriteVerify::PerRegion if stats.flash_modified => {
+            verify_each_region(device, &effective_layout, &image)?;
+        }
+        WriteVerify::ReadBack | WriteVerify::PerRegion => {
             println!("Skipping verification - no changes were made");
         }
+        WriteVerify::PerChunk if stats.flash_modified => {
+            println!("Verification passed!");
+        }
+        WriteVerify::PerChunk | WriteVerify::None => {}
     }
 
     println!(
@@ -398,15 +887,20 @@ pub fn run_write_with_layout<D: FlashDevice + ?Sized>(
 /// Run the unified erase command
 pub fn run_erase<D: FlashDevice + ?Sized>(
     device: &mut D,
+    dry_run: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let layout = full_flash_layout(device.size());
-    run_erase_with_layout(device, &layout)
+    run_erase_with_layout(device, &layout, dry_run)
 }
 
 /// Run the unified erase command with layout
+///
+/// With `dry_run`, the erase blocks (and the write-back of data preserved
+/// around unaligned regions) are printed instead of performed.
 pub fn run_erase_with_layout<D: FlashDevice + ?Sized>(
     device: &mut D,
     layout: &Layout,
+    dry_run: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     print_flash_size(device.size());
 
@@ -432,7 +926,16 @@ pub fn run_erase_with_layout<D: FlashDevice + ?Sized>(
         );
     });
 
-    let pb = ProgressBar::new_spinner();
+    if dry_run {
+        let mut dry_run = DryRunDevice::new(device);
+        included
+            .iter()
+            .try_for_each(|region| unified::erase_region(&mut dry_run, region))?;
+        print_dry_run(dry_run.ops());
+        return Ok(());
+    }
+
+    let pb = new_spinner();
     pb.set_style(create_spinner_style()?);
     pb.enable_steady_tick(Duration::from_millis(100));
 
@@ -446,73 +949,221 @@ pub fn run_erase_with_layout<D: FlashDevice + ?Sized>(
     Ok(())
 }
 
+/// Run the unified erase command with a single chip erase
+///
+/// Devices without a chip erase command erase the whole flash block by
+/// block instead, see `FlashDevice::chip_erase`.
+pub fn run_chip_erase<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    print_flash_size(device.size());
+
+    if dry_run {
+        let mut dry_run = DryRunDevice::new(device);
+        dry_run.chip_erase()?;
+        print_dry_run(dry_run.ops());
+        return Ok(());
+    }
+
+    let pb = new_spinner();
+    pb.set_style(create_spinner_style()?);
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb.set_message("Erasing entire chip...");
+
+    device.chip_erase()?;
+
+    pb.finish_with_message("Erase complete");
+
+    Ok(())
+}
+
 // =============================================================================
-// Verify operations
+// Blank check
 // =============================================================================
 
-/// Compare a chunk with expected data and return detailed error on mismatch
-fn verify_chunk(
-    chunk: &[u8],
-    expected_chunk: &[u8],
-    base_offset: usize,
-    region_name: Option<&str>,
+/// Run the unified blank-check command
+///
+/// Streams the chip through `flash::is_blank` one chunk at a time and fails
+/// with the first non-erased offset if the chip isn't fully erased.
+pub fn run_blankcheck<D: FlashDevice + ?Sized>(
+    device: &mut D,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    chunk
-        .iter()
-        .zip(expected_chunk.iter())
-        .enumerate()
-        .find(|(_, (a, b))| a != b)
-        .map_or(Ok(()), |(i, (a, b))| {
-            let error_msg = match region_name {
-                Some(name) => format!(
-                    "Verification failed in region '{}' at offset 0x{:08X}: expected 0x{:02X}, got 0x{:02X}",
-                    name,
-                    base_offset + i,
-                    b,
-                    a
-                ),
-                None => format!(
-                    "Verification failed at offset 0x{:08X}: expected 0x{:02X}, got 0x{:02X}",
-                    base_offset + i,
-                    b,
-                    a
-                ),
-            };
-            Err(error_msg.into())
-        })
+    let flash_size = device.size();
+    print_flash_size(flash_size);
+
+    let pb = create_progress_bar_with_phase(flash_size as u64, "Checking")?;
+
+    let mut offset = 0u32;
+    while offset < flash_size {
+        let len = std::cmp::min(READ_CHUNK_SIZE as u32, flash_size - offset);
+        if let Some((addr, value)) = flash::is_blank(device, offset, len)? {
+            pb.abandon_with_message("Not blank");
+            return Err(format!(
+                "Flash is not blank: byte at offset 0x{:08X} is 0x{:02X}",
+                addr, value
+            )
+            .into());
+        }
+        offset += len;
+        pb.set_position(offset as u64);
+    }
+
+    pb.finish_with_message("Blank check passed");
+    println!("Flash is blank ({} bytes checked)", flash_size);
+
+    Ok(())
+}
+
+// =============================================================================
+// Digest
+// =============================================================================
+
+/// Streaming state of a [`DigestAlgorithm`]
+enum Hasher {
+    Crc32(crc32fast::Hasher),
+    Sha256(Sha256),
 }
 
-/// Verify flash contents against expected data
-pub fn verify_flash<D: FlashDevice + ?Sized>(
+impl Hasher {
+    fn new(algo: DigestAlgorithm) -> Self {
+        match algo {
+            DigestAlgorithm::Crc32 => Hasher::Crc32(crc32fast::Hasher::new()),
+            DigestAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Crc32(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+        }
+    }
+
+    /// Finish and format the digest as lowercase hex
+    fn finish(self) -> String {
+        match self {
+            Hasher::Crc32(h) => format!("{:08x}", h.finalize()),
+            Hasher::Sha256(h) => h.finalize().iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+/// Hash the included regions in address order, one chunk at a time
+///
+/// Returns the hex digest and the number of bytes hashed.
+fn digest_regions<D: FlashDevice + ?Sized>(
     device: &mut D,
-    expected: &[u8],
-) -> Result<(), Box<dyn std::error::Error>> {
-    let total_size = expected.len();
+    included: &[&rflasher_core::layout::Region],
+    algo: DigestAlgorithm,
+    pb: &ProgressBar,
+) -> Result<(String, u64), Box<dyn std::error::Error>> {
+    let mut hasher = Hasher::new(algo);
     let mut buf = vec![0u8; READ_CHUNK_SIZE];
+    let mut done = 0u64;
+
+    for region in included {
+        let end = region.end as u64 + 1;
+        let mut addr = region.start as u64;
+        while addr < end {
+            let len = std::cmp::min(READ_CHUNK_SIZE as u64, end - addr) as usize;
+            let chunk = &mut buf[..len];
+            device.read(addr as u32, chunk)?;
+            hasher.update(chunk);
+            addr += len as u64;
+            done += len as u64;
+            pb.set_position(done);
+        }
+    }
 
-    let pb = create_progress_bar_with_phase(total_size as u64, "Verifying")?;
+    Ok((hasher.finish(), done))
+}
 
-    let result = (0..total_size)
-        .step_by(READ_CHUNK_SIZE)
-        .try_for_each(|offset| {
-            let chunk_size = std::cmp::min(READ_CHUNK_SIZE, total_size - offset);
-            let chunk = &mut buf[..chunk_size];
+/// Run the unified digest command
+///
+/// The included regions are streamed through the hasher, so nothing but a
+/// single chunk is held in memory.
+pub fn run_digest<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    layout: &Layout,
+    algo: DigestAlgorithm,
+) -> Result<(), Box<dyn std::error::Error>> {
+    print_flash_size(device.size());
+
+    let mut included: Vec<_> = layout.included_regions().collect();
+    if included.is_empty() {
+        return Err("No regions selected for hashing. Use --include to select regions.".into());
+    }
+    included.sort_by_key(|r| r.start);
+    display_included_regions(&included, "Hashing");
+
+    let total_bytes: u64 = included.iter().map(|r| r.size() as u64).sum();
+    let pb = create_progress_bar_with_phase(total_bytes, "Hashing")?;
+    let (digest, hashed) = digest_regions(device, &included, algo, &pb)?;
+    pb.finish_and_clear();
+
+    let name = match algo {
+        DigestAlgorithm::Crc32 => "CRC-32",
+        DigestAlgorithm::Sha256 => "SHA-256",
+    };
+    println!("{} of {} bytes: {}", name, hashed, digest);
+
+    Ok(())
+}
+
+// =============================================================================
+// Verify operations
+// =============================================================================
+
+/// Describe a verify mismatch, including the region it was found in
+fn mismatch_error(mismatch: &VerifyMismatch, region_name: Option<&str>) -> String {
+    let location = match region_name {
+        Some(name) => format!("in region '{}' at offset", name),
+        None => "at offset".to_string(),
+    };
+    format!(
+        "Verification failed {} 0x{:08X}: expected 0x{:02X}, got 0x{:02X} ({} byte(s) differ)",
+        location, mismatch.addr, mismatch.expected, mismatch.actual, mismatch.count
+    )
+}
 
-            device.read(offset as u32, chunk)?;
+/// Verify the first `len` bytes of flash against data from `reader`
+///
+/// Both sides are compared one chunk at a time, so neither the flash
+/// contents nor the expected image are held in memory as a whole.
+/// The whole range is always compared so the report includes the total
+/// number of differing bytes.
+fn verify_from_reader<D: FlashDevice + ?Sized, R: Read>(
+    device: &mut D,
+    reader: &mut R,
+    len: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut expected_buf = vec![0u8; READ_CHUNK_SIZE];
 
-            // Compare
-            let expected_chunk = &expected[offset..offset + chunk_size];
-            verify_chunk(chunk, expected_chunk, offset, None)?;
+    let pb = create_progress_bar_with_phase(len as u64, "Verifying")?;
+
+    let result = (0..len)
+        .step_by(READ_CHUNK_SIZE)
+        .try_fold(None, |mismatch, offset| {
+            let chunk_size = std::cmp::min(READ_CHUNK_SIZE, len - offset);
+            let expected_chunk = &mut expected_buf[..chunk_size];
+
+            reader.read_exact(expected_chunk)?;
+            let chunk_mismatch = unified::verify_detailed(device, expected_chunk, offset as u32)?;
 
             pb.set_position((offset + chunk_size) as u64);
-            Ok::<_, Box<dyn std::error::Error>>(())
+            Ok::<_, Box<dyn std::error::Error>>(VerifyMismatch::merge(mismatch, chunk_mismatch))
         });
 
     match result {
-        Ok(()) => {
+        Ok(None) => {
             pb.finish_with_message("Verification passed");
             Ok(())
         }
+        Ok(Some(mismatch)) => {
+            pb.abandon_with_message("Verification failed!");
+            Err(mismatch_error(&mismatch, None).into())
+        }
         Err(e) => {
             pb.abandon_with_message("Verification failed!");
             Err(e)
@@ -528,68 +1179,703 @@ pub fn run_verify<D: FlashDevice + ?Sized>(
     let flash_size = device.size();
     print_flash_size(flash_size);
 
-    // Read input file
-    let expected = read_file(input)?;
+    // Open input file; its contents are streamed during verification
+    let mut file = File::open(input)?;
+    let file_size = file.metadata()?.len();
 
     // Validate size
-    if expected.len() > flash_size as usize {
+    if file_size > flash_size as u64 {
         return Err(format!(
             "File size ({} bytes) exceeds flash size ({} bytes)",
-            expected.len(),
-            flash_size
+            file_size, flash_size
         )
         .into());
     }
 
-    verify_flash(device, &expected)?;
+    verify_from_reader(device, &mut file, file_size as usize)?;
     println!("Verification passed!");
 
     Ok(())
 }
 
+/// Run the unified verify command with layout
+///
+/// The file is placed as for a write with the same layout, and only the
+/// included regions are compared.
+pub fn run_verify_with_layout<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    input: &Path,
+    layout: &Layout,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let flash_size = device.size();
+    print_flash_size(flash_size);
+
+    let included: Vec<_> = layout.included_regions().collect();
+    if included.is_empty() {
+        return Err("No regions selected for verifying. Use --include to select regions.".into());
+    }
+    display_included_regions(&included, "Verifying");
+
+    // Open input file; its contents are streamed during verification
+    let mut image = ImageSource::open(input, ImageFormat::Binary, flash_size)?;
+    let (effective_layout, base, _) = place_file(layout, image.len(), flash_size)?;
+    verify_layout_from(device, &effective_layout, &mut image, base)?;
+    println!("Verification passed!");
+
+    Ok(())
+}
+
+/// Compare the included regions against expected data
+///
+/// `expected` holds the data from flash address `base` on and must cover
+/// the regions. It is read one chunk at a time. Returns the first mismatch
+/// and the number of differing bytes of each region that differs, in
+/// layout order.
+fn region_mismatches<'r, D: FlashDevice + ?Sized>(
+    device: &mut D,
+    included: &[&'r rflasher_core::layout::Region],
+    expected: &mut ImageSource,
+    base: u32,
+    pb: &ProgressBar,
+) -> Result<Vec<(&'r str, VerifyMismatch)>, Box<dyn std::error::Error>> {
+    let mut mismatches = Vec::new();
+    let mut bytes_verified = 0usize;
+    let mut expected_buf = vec![0u8; READ_CHUNK_SIZE];
+
+    for &region in included {
+        let mut region_mismatch = None;
+        for offset in (region.start..=region.end).step_by(READ_CHUNK_SIZE) {
+            let chunk_size = std::cmp::min(READ_CHUNK_SIZE, (region.end - offset + 1) as usize);
+            let expected_chunk = &mut expected_buf[..chunk_size];
+            expected.read_at((offset - base) as usize, expected_chunk)?;
+
+            let chunk_mismatch = unified::verify_detailed(device, expected_chunk, offset)?;
+            region_mismatch = VerifyMismatch::merge(region_mismatch, chunk_mismatch);
+
+            bytes_verified += chunk_size;
+            pb.set_position(bytes_verified as u64);
+        }
+        if let Some(mismatch) = region_mismatch {
+            mismatches.push((region.name.as_str(), mismatch));
+        }
+    }
+
+    Ok(mismatches)
+}
+
 /// Verify included regions against expected data
 pub fn verify_by_layout<D: FlashDevice + ?Sized>(
     device: &mut D,
     layout: &Layout,
     expected: &[u8],
+) -> Result<(), Box<dyn std::error::Error>> {
+    verify_layout_from(device, layout, &mut ImageSource::Memory(expected), 0)
+}
+
+/// Verify included regions against data placed at flash address `base`
+fn verify_layout_from<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    layout: &Layout,
+    expected: &mut ImageSource,
+    base: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let included: Vec<_> = layout.included_regions().collect();
+    let total_bytes: usize = included.iter().map(|r| r.size() as usize).sum();
+
+    let pb = create_progress_bar_with_phase(total_bytes as u64, "Verifying")?;
+
+    let mismatches = match region_mismatches(device, &included, expected, base, &pb) {
+        Ok(mismatches) => mismatches,
+        Err(e) => {
+            pb.abandon_with_message("Verification failed!");
+            return Err(e);
+        }
+    };
+
+    // Report the first mismatch with the total number of differing bytes
+    match mismatches.first() {
+        None => {
+            pb.finish_with_message("Verification passed");
+            Ok(())
+        }
+        Some(&(region_name, mismatch)) => {
+            pb.abandon_with_message("Verification failed!");
+            let mismatch = VerifyMismatch {
+                count: mismatches.iter().map(|(_, m)| m.count).sum(),
+                ..mismatch
+            };
+            Err(mismatch_error(&mismatch, Some(region_name)).into())
+        }
+    }
+}
+
+/// Verify included regions against expected data, reporting each region
+///
+/// Unlike [`verify_by_layout`], every region is listed as passed or failed,
+/// so a write where only some regions failed (e.g. a locked ME region)
+/// shows which ones. The error names all failing regions.
+pub fn verify_each_region<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    layout: &Layout,
+    expected: &[u8],
 ) -> Result<(), Box<dyn std::error::Error>> {
     let included: Vec<_> = layout.included_regions().collect();
     let total_bytes: usize = included.iter().map(|r| r.size() as usize).sum();
 
     let pb = create_progress_bar_with_phase(total_bytes as u64, "Verifying")?;
 
+    let mut expected = ImageSource::Memory(expected);
+    let mismatches = match region_mismatches(device, &included, &mut expected, 0, &pb) {
+        Ok(mismatches) => mismatches,
+        Err(e) => {
+            pb.abandon_with_message("Verification failed!");
+            return Err(e);
+        }
+    };
+    if mismatches.is_empty() {
+        pb.finish_with_message("Verification passed");
+    } else {
+        pb.abandon_with_message("Verification failed!");
+    }
+
+    for region in &included {
+        match mismatches.iter().find(|(name, _)| *name == region.name) {
+            Some((_, mismatch)) => println!(
+                "  {:<20} FAIL  at 0x{:08X}: expected 0x{:02X}, got 0x{:02X} ({} byte(s) differ)",
+                region.name, mismatch.addr, mismatch.expected, mismatch.actual, mismatch.count
+            ),
+            None => println!("  {:<20} PASS", region.name),
+        }
+    }
+
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<_> = mismatches.iter().map(|(name, _)| *name).collect();
+    Err(format!(
+        "Verification failed in {} of {} region(s): {}",
+        names.len(),
+        included.len(),
+        names.join(", ")
+    )
+    .into())
+}
+
+// =============================================================================
+// Diff operations
+// =============================================================================
+
+/// Maximum number of differing ranges listed by `diff`
+const DIFF_LIST_MAX: usize = 64;
+
+/// Largest differing range shown side by side with `diff --hex`
+const DIFF_HEX_MAX: u32 = 256;
+
+/// Find the ranges where the included regions differ from `image`
+///
+/// `image` is placed at flash address `base`; only the parts of the regions
+/// it covers are compared, one chunk at a time. Returned ranges use
+/// absolute flash addresses, each tagged with its region.
+fn diff_ranges<'r, D: FlashDevice + ?Sized>(
+    device: &mut D,
+    included: &[&'r rflasher_core::layout::Region],
+    image: &mut ImageSource,
+    base: u32,
+    pb: &ProgressBar,
+) -> Result<Vec<(&'r str, WriteRange)>, Box<dyn std::error::Error>> {
+    let image_end = base + image.len() as u32 - 1;
     let mut buf = vec![0u8; READ_CHUNK_SIZE];
+    let mut want_buf = vec![0u8; READ_CHUNK_SIZE];
+    let mut ranges: Vec<(&str, WriteRange)> = Vec::new();
+    let mut bytes_compared = 0usize;
+
+    for &region in included {
+        let start = std::cmp::max(region.start, base);
+        let end = std::cmp::min(region.end, image_end);
+        if start > end {
+            continue;
+        }
 
-    let result = included
+        for offset in (start..=end).step_by(READ_CHUNK_SIZE) {
+            let chunk_size = std::cmp::min(READ_CHUNK_SIZE, (end - offset + 1) as usize);
+            let have = &mut buf[..chunk_size];
+            device.read(offset, have)?;
+            let want = &mut want_buf[..chunk_size];
+            image.read_at((offset - base) as usize, want)?;
+
+            for range in get_all_write_ranges(have, want) {
+                let range = WriteRange {
+                    start: offset + range.start,
+                    len: range.len,
+                };
+                // Join ranges split by a chunk boundary
+                match ranges.last_mut() {
+                    Some((name, last))
+                        if *name == region.name && last.start + last.len == range.start =>
+                    {
+                        last.len += range.len;
+                    }
+                    _ => ranges.push((region.name.as_str(), range)),
+                }
+            }
+
+            bytes_compared += chunk_size;
+            pb.set_position(bytes_compared as u64);
+        }
+    }
+
+    Ok(ranges)
+}
+
+/// Print a differing range as flash and file bytes side by side
+fn print_hex_diff<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    range: &WriteRange,
+    image: &mut ImageSource,
+    base: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut have = vec![0u8; range.len as usize];
+    device.read(range.start, &mut have)?;
+    let mut want = vec![0u8; range.len as usize];
+    image.read_at((range.start - base) as usize, &mut want)?;
+
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    for (row, (have, want)) in have.chunks(16).zip(want.chunks(16)).enumerate() {
+        println!(
+            "    0x{:08X}  flash: {:<47}  file: {}",
+            range.start + row as u32 * 16,
+            hex(have),
+            hex(want)
+        );
+    }
+    Ok(())
+}
+
+/// Run the unified diff command
+///
+/// Compares the included regions against `input` and lists the ranges that
+/// differ. A full-size image is compared address for address; a smaller one
+/// is compared against the start of the single included region, as `write`
+/// would place it. With `hex`, small ranges are also shown byte by byte.
+pub fn run_diff<D: FlashDevice + ?Sized>(
+    device: &mut D,
+    input: &Path,
+    format: ImageFormat,
+    layout: &Layout,
+    hex: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let flash_size = device.size();
+    print_flash_size(flash_size);
+
+    // The image is read one chunk at a time while comparing
+    let mut image = ImageSource::open(input, format, flash_size)?;
+    if image.is_empty() {
+        return Err("Input file is empty".into());
+    }
+
+    let included: Vec<_> = layout.included_regions().collect();
+    if included.is_empty() {
+        return Err("No regions selected for comparing. Use --include to select regions.".into());
+    }
+    display_included_regions(&included, "Comparing");
+
+    let base = if image.len() == flash_size as usize {
+        0
+    } else if included.len() == 1 && image.len() <= included[0].size() as usize {
+        included[0].start
+    } else {
+        return Err(format!(
+            "File ({} bytes) must be exactly flash size ({} bytes), or fit a single selected region",
+            image.len(),
+            flash_size
+        )
+        .into());
+    };
+
+    let image_end = base as u64 + image.len() as u64;
+    let total_bytes: u64 = included
         .iter()
-        .flat_map(|region| {
-            (region.start..=region.end)
-                .step_by(READ_CHUNK_SIZE)
-                .map(move |offset| (region, offset))
+        .map(|r| {
+            let start = std::cmp::max(r.start as u64, base as u64);
+            let end = std::cmp::min(r.end as u64 + 1, image_end);
+            end.saturating_sub(start)
         })
-        .try_fold(0usize, |bytes_verified, (region, offset)| {
-            let chunk_size = std::cmp::min(READ_CHUNK_SIZE, (region.end - offset + 1) as usize);
-            let chunk = &mut buf[..chunk_size];
+        .sum();
 
-            device.read(offset, chunk)?;
+    let pb = create_progress_bar_with_phase(total_bytes, "Comparing")?;
+    let ranges = diff_ranges(device, &included, &mut image, base, &pb)?;
+    pb.finish_and_clear();
 
-            // Compare
-            let expected_chunk = &expected[offset as usize..offset as usize + chunk_size];
-            verify_chunk(chunk, expected_chunk, offset as usize, Some(&region.name))?;
+    if ranges.is_empty() {
+        println!("No differences ({} bytes compared)", total_bytes);
+        return Ok(());
+    }
 
-            let new_bytes_verified = bytes_verified + chunk_size;
-            pb.set_position(new_bytes_verified as u64);
-            Ok::<_, Box<dyn std::error::Error>>(new_bytes_verified)
-        });
+    let differing: u64 = ranges.iter().map(|(_, r)| r.len as u64).sum();
+    println!(
+        "{} byte(s) differ in {} range(s) ({} bytes compared):",
+        differing,
+        ranges.len(),
+        total_bytes
+    );
+    for (name, range) in ranges.iter().take(DIFF_LIST_MAX) {
+        println!(
+            "  0x{:08X} - 0x{:08X} ({} bytes) in {}",
+            range.start,
+            range.start + range.len - 1,
+            range.len,
+            name
+        );
+        if hex && range.len <= DIFF_HEX_MAX {
+            print_hex_diff(device, range, &mut image, base)?;
+        }
+    }
+    if ranges.len() > DIFF_LIST_MAX {
+        println!("  ... and {} more range(s)", ranges.len() - DIFF_LIST_MAX);
+    }
 
-    match result {
-        Ok(_) => {
-            pb.finish_with_message("Verification passed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rflasher_core::flash::SpiFlashDevice;
+    use rflasher_dummy::{DummyConfig, DummyFlash};
+
+    const SIZE: u32 = 16 * 1024 * 1024;
+
+    fn pattern(addr: usize) -> u8 {
+        (addr ^ (addr >> 8) ^ (addr >> 16)) as u8
+    }
+
+    /// Dummy flash holding the pattern
+    fn pattern_flash() -> SpiFlashDevice<DummyFlash> {
+        let config = DummyConfig {
+            size: SIZE as usize,
+            ..DummyConfig::default()
+        };
+        let data: Vec<u8> = (0..SIZE as usize).map(pattern).collect();
+        DummyFlash::with_data(config, &data).into_device()
+    }
+
+    /// Writer that checks the stream against the pattern without storing it
+    #[derive(Default)]
+    struct CheckingWriter {
+        pos: usize,
+        largest_write: usize,
+    }
+
+    impl Write for CheckingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.largest_write = self.largest_write.max(buf.len());
+            for (i, b) in buf.iter().enumerate() {
+                assert_eq!(*b, pattern(self.pos + i), "mismatch at {}", self.pos + i);
+            }
+            self.pos += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
             Ok(())
         }
-        Err(e) => {
-            pb.abandon_with_message("Verification failed!");
-            Err(e)
+    }
+
+    /// Reader producing the expected pattern without storing it
+    #[derive(Default)]
+    struct PatternReader {
+        pos: usize,
+        largest_read: usize,
+    }
+
+    impl Read for PatternReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.largest_read = self.largest_read.max(buf.len());
+            for (i, b) in buf.iter_mut().enumerate() {
+                *b = pattern(self.pos + i);
+            }
+            self.pos += buf.len();
+            Ok(buf.len())
+        }
+    }
+
+    // The whole chip passes through one chunk-sized buffer at a time
+    #[test]
+    fn test_read_streams_in_chunks() {
+        let mut device = pattern_flash();
+        let layout = full_flash_layout(SIZE);
+        let included: Vec<_> = layout.included_regions().collect();
+        let mut out = CheckingWriter::default();
+        let pb = ProgressBar::hidden();
+
+        let bytes = read_to_writer(&mut device, &included, &mut out, &pb).unwrap();
+
+        assert_eq!(bytes, SIZE as usize);
+        assert_eq!(out.pos, SIZE as usize);
+        assert!(out.largest_write <= READ_CHUNK_SIZE);
+    }
+
+    #[test]
+    fn test_verify_streams_in_chunks() {
+        let mut device = pattern_flash();
+        let mut reader = PatternReader::default();
+
+        verify_from_reader(&mut device, &mut reader, SIZE as usize).unwrap();
+
+        assert_eq!(reader.pos, SIZE as usize);
+        assert!(reader.largest_read <= READ_CHUNK_SIZE);
+    }
+
+    #[test]
+    fn test_read_fills_excluded_regions() {
+        use rflasher_core::layout::{LayoutSource, Region};
+
+        let mut device = pattern_flash();
+        let mut layout = Layout::with_source(LayoutSource::Manual);
+        let mut region = Region::new("bios", 0x1800, 0x27FF);
+        region.included = true;
+        layout.add_region(region);
+        let included: Vec<_> = layout.included_regions().collect();
+
+        struct Collect(Vec<u8>);
+        impl Write for Collect {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.0.len() < 0x4000 {
+                    self.0.extend_from_slice(buf);
+                }
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
         }
+
+        let mut out = Collect(Vec::new());
+        let bytes =
+            read_to_writer(&mut device, &included, &mut out, &ProgressBar::hidden()).unwrap();
+
+        assert_eq!(bytes, 0x1000);
+        assert!(out.0[..0x1800].iter().all(|&b| b == 0xFF));
+        assert!((0x1800..0x2800).all(|i| out.0[i] == pattern(i)));
+        assert!(out.0[0x2800..0x4000].iter().all(|&b| b == 0xFF));
+    }
+
+    #[test]
+    fn test_digest_regions() {
+        use rflasher_core::layout::Region;
+
+        let mut device = pattern_flash();
+        let pb = ProgressBar::hidden();
+        // Spans a read chunk boundary and ends mid-chunk
+        let low = Region::new("low", 0x800, 0x1FFF);
+        let high = Region::new("high", 0x3000, 0x3100);
+
+        let expected: Vec<u8> = (0x800..0x2000).chain(0x3000..0x3101).map(pattern).collect();
+        let (digest, hashed) =
+            digest_regions(&mut device, &[&low, &high], DigestAlgorithm::Sha256, &pb).unwrap();
+        assert_eq!(hashed, expected.len() as u64);
+        let sha: String = Sha256::digest(&expected)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert_eq!(digest, sha);
+
+        let (digest, _) =
+            digest_regions(&mut device, &[&low, &high], DigestAlgorithm::Crc32, &pb).unwrap();
+        assert_eq!(digest, format!("{:08x}", crc32fast::hash(&expected)));
+    }
+
+    #[test]
+    fn test_diff_ranges() {
+        use rflasher_core::layout::Region;
+
+        let mut device = pattern_flash();
+        let mut image: Vec<u8> = (0..0x3000).map(pattern).collect();
+        // One range across a read chunk boundary, one single byte
+        for b in &mut image[0xFFE..0x1003] {
+            *b = !*b;
+        }
+        image[0x2000] = !image[0x2000];
+
+        let low = Region::new("low", 0, 0x1FFF);
+        let high = Region::new("high", 0x2000, 0x3FFF);
+        let pb = ProgressBar::hidden();
+        let mut source = ImageSource::Memory(&image);
+        let ranges = diff_ranges(&mut device, &[&low, &high], &mut source, 0, &pb).unwrap();
+        assert_eq!(
+            ranges,
+            [
+                (
+                    "low",
+                    WriteRange {
+                        start: 0xFFE,
+                        len: 5
+                    }
+                ),
+                (
+                    "high",
+                    WriteRange {
+                        start: 0x2000,
+                        len: 1
+                    }
+                ),
+            ]
+        );
+
+        // A region-sized image is compared at the region's address
+        let mut source = ImageSource::Memory(&image[0x2000..]);
+        let ranges = diff_ranges(&mut device, &[&high], &mut source, 0x2000, &pb).unwrap();
+        assert_eq!(
+            ranges,
+            [(
+                "high",
+                WriteRange {
+                    start: 0x2000,
+                    len: 1
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_diff_sparse_image() {
+        use rflasher_core::layout::Region;
+
+        // Unspecified bytes compare as erased
+        let mut sparse = SparseImage::default();
+        sparse.push(0x1000, &(0x1000..0x1800).map(pattern).collect::<Vec<_>>());
+        sparse.push(0x1FFF, &[!pattern(0x1FFF)]);
+        let mut source = ImageSource::Sparse(sparse, SIZE);
+
+        let mut device = pattern_flash();
+        device.master().data_mut()[0x1800..0x2000].fill(0xFF);
+        let region = Region::new("bios", 0x1000, 0x1FFF);
+        let pb = ProgressBar::hidden();
+        let ranges = diff_ranges(&mut device, &[&region], &mut source, 0, &pb).unwrap();
+        assert_eq!(
+            ranges,
+            [(
+                "bios",
+                WriteRange {
+                    start: 0x1FFF,
+                    len: 1
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_verify_with_layout_streams_file() {
+        use rflasher_core::layout::{LayoutSource, Region};
+
+        let mut layout = Layout::with_source(LayoutSource::Manual);
+        let mut region = Region::new("bios", 0x1000, 0x2FFF);
+        region.included = true;
+        layout.add_region(region);
+
+        // A file smaller than the region is compared at the region start
+        let path = std::env::temp_dir().join(format!("rflasher-verify-{}.bin", std::process::id()));
+        let mut file_data: Vec<u8> = (0x1000..0x2800).map(pattern).collect();
+        std::fs::write(&path, &file_data).unwrap();
+        let mut device = pattern_flash();
+        let passed = run_verify_with_layout(&mut device, &path, &layout);
+
+        file_data[0x1234] = !file_data[0x1234];
+        std::fs::write(&path, &file_data).unwrap();
+        let failed = run_verify_with_layout(&mut device, &path, &layout);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(passed.is_ok());
+        let err = failed.unwrap_err().to_string();
+        assert!(err.contains("0x00002234"), "{}", err);
+    }
+
+    #[test]
+    fn test_region_mismatches() {
+        use rflasher_core::layout::Region;
+
+        let mut device = pattern_flash();
+        let mut image: Vec<u8> = (0..0x4000).map(pattern).collect();
+        // Two differing bytes in separate read chunks of the same region
+        image[0x2800] = !image[0x2800];
+        image[0x3FFF] = !image[0x3FFF];
+
+        let bios = Region::new("bios", 0, 0x1FFF);
+        let me = Region::new("me", 0x2000, 0x3FFF);
+        let pb = ProgressBar::hidden();
+        let mut source = ImageSource::Memory(&image);
+        let mismatches =
+            region_mismatches(&mut device, &[&bios, &me], &mut source, 0, &pb).unwrap();
+        assert_eq!(
+            mismatches,
+            [(
+                "me",
+                VerifyMismatch {
+                    addr: 0x2800,
+                    expected: image[0x2800],
+                    actual: pattern(0x2800),
+                    count: 2,
+                }
+            )]
+        );
+
+        assert!(
+            region_mismatches(&mut device, &[&bios], &mut source, 0, &pb)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_range_layout() {
+        let layout = range_layout(0x10000, 0x1000, 0x800).unwrap();
+        let regions: Vec<_> = layout.included_regions().collect();
+        assert_eq!((regions[0].start, regions[0].end), (0x1000, 0x17FF));
+
+        assert!(range_layout(0x10000, 0x1000, 0).is_err());
+        assert!(range_layout(0x10000, 0xF000, 0x1001).is_err());
+        assert!(range_layout(0x10000, u32::MAX, 2).is_err());
+        assert!(range_layout(0x10000, 0xF000, 0x1000).is_ok());
+
+        let device = pattern_flash();
+        assert!(check_erase_alignment(&device, 0x1000, 0x2000).is_ok());
+        assert!(check_erase_alignment(&device, 0x1000, 0x800).is_err());
+        assert!(check_erase_alignment(&device, 0x800, 0x1000).is_err());
+
+        // A file smaller than the range only covers its own bytes
+        let (image, layout, size) = image_for_layout(&layout, vec![0; 0x10], 0x10000).unwrap();
+        let region = layout.included_regions().next().unwrap();
+        assert_eq!((region.start, region.end, size), (0x1000, 0x100F, 0x10));
+        assert_eq!(
+            image[0x1000..0x1011],
+            [[0u8; 0x10].as_slice(), &[0xFF]].concat()
+        );
+    }
+
+    #[test]
+    fn test_image_fmap_layout() {
+        use rflasher_core::layout::{LayoutSource, Region, build_fmap};
+
+        let mut layout = Layout::with_source(LayoutSource::Manual);
+        layout.chip_size = Some(0x10000);
+        layout.add_region(Region::new("FMAP", 0, 0xFFF));
+        layout.add_region(Region::new("COREBOOT", 0x1000, 0xFFFF));
+        let fmap = build_fmap(&layout).unwrap();
+
+        let mut image = vec![0xFF; 0x10000];
+        image[..fmap.len()].copy_from_slice(&fmap);
+        let parsed = image_fmap_layout(&image, 0x10000).unwrap();
+        assert!(parsed.find_region("COREBOOT").is_some());
+
+        // The FMAP must describe the chip, not just a prefix of it
+        image.resize(0x20000, 0xFF);
+        assert!(image_fmap_layout(&image, 0x20000).is_err());
+        assert!(image_fmap_layout(&image[..0x8000], 0x8000).is_err());
     }
 }
@@ -1,6 +1,6 @@
 //! Write protection command implementations
 
-use rflasher_core::wp::{WpMode, WpRange, WriteOptions};
+use rflasher_core::wp::{self, WpMode, WpRange, WriteOptions};
 use rflasher_flash::FlashHandle;
 use std::error::Error;
 
@@ -136,24 +136,41 @@ pub fn cmd_list(handle: &mut FlashHandle) -> Result<(), Box<dyn Error>> {
 }
 
 /// Enable hardware write protection
-pub fn cmd_enable(handle: &mut FlashHandle, temporary: bool) -> Result<(), Box<dyn Error>> {
+///
+/// With `permanent`, the status register is locked forever instead; the
+/// caller is responsible for getting the user's confirmation first.
+pub fn cmd_enable(
+    handle: &mut FlashHandle,
+    temporary: bool,
+    permanent: bool,
+) -> Result<(), Box<dyn Error>> {
     if !handle.wp_supported() {
         return Err("Write protection operations are not supported for this chip".into());
     }
 
     let options = WriteOptions {
         volatile: temporary,
+        allow_permanent: permanent,
         ..Default::default()
     };
+    let mode = if permanent {
+        WpMode::Permanent
+    } else {
+        WpMode::Hardware
+    };
 
     handle
-        .set_wp_mode(WpMode::Hardware, options)
+        .set_wp_mode(mode, options)
         .map_err(|e| format!("Failed to enable write protection: {}", e))?;
 
-    println!(
-        "Hardware write protection enabled{}.",
-        if temporary { " (temporary)" } else { "" }
-    );
+    if permanent {
+        println!("Status register is now permanently locked.");
+    } else {
+        println!(
+            "Hardware write protection enabled{}.",
+            if temporary { " (temporary)" } else { "" }
+        );
+    }
     Ok(())
 }
 
@@ -237,12 +254,19 @@ pub fn cmd_region(
         .find(|r| r.name == region_name)
         .ok_or_else(|| format!("Region '{}' not found in layout", region_name))?;
 
-    let range = WpRange::new(region.start, region.end - region.start + 1);
+    let total_size = handle.size();
+    let range = wp::range_for_region(region, total_size, &handle.get_available_wp_ranges())
+        .map_err(|e| {
+            format!(
+                "No protection range covers region '{}' (0x{:08x}-0x{:08x}): {}",
+                region_name, region.start, region.end, e
+            )
+        })?;
+    let extra = range.len - region.size();
     let options = WriteOptions {
         volatile: temporary,
         ..Default::default()
     };
-    let total_size = handle.size();
 
     handle.set_wp_range(&range, options).map_err(|e| {
         format!(
@@ -259,5 +283,11 @@ pub fn cmd_region(
         format_range(&range, total_size),
         if temporary { " (temporary)" } else { "" }
     );
+    if extra > 0 {
+        println!(
+            "Note: {} bytes (0x{:x}) outside the region are also protected due to BP granularity.",
+            extra, extra
+        );
+    }
     Ok(())
 }
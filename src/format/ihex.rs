@@ -0,0 +1,251 @@
+//! Intel HEX parsing and generation
+//!
+//! Supports data (00), end-of-file (01), extended segment address (02) and
+//! extended linear address (04) records. Start address records (03, 05) are
+//! accepted and ignored, since they mean nothing for a flash image.
+
+use super::{SparseImage, decode_hex};
+use std::io::{self, Write};
+
+/// Data bytes per record when generating Intel HEX
+const BYTES_PER_RECORD: usize = 16;
+
+const RECORD_DATA: u8 = 0x00;
+const RECORD_EOF: u8 = 0x01;
+const RECORD_EXT_SEGMENT: u8 = 0x02;
+const RECORD_START_SEGMENT: u8 = 0x03;
+const RECORD_EXT_LINEAR: u8 = 0x04;
+const RECORD_START_LINEAR: u8 = 0x05;
+
+/// Errors from parsing an Intel HEX file
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum IhexError {
+    /// Line doesn't start with ':' or has malformed hex digits
+    #[error("line {line}: malformed record")]
+    Malformed { line: usize },
+    /// Byte count doesn't match the record length
+    #[error("line {line}: record length mismatch")]
+    LengthMismatch { line: usize },
+    /// Checksum byte doesn't match the record contents
+    #[error("line {line}: checksum mismatch")]
+    Checksum { line: usize },
+    /// Unknown record type
+    #[error("line {line}: unsupported record type 0x{record_type:02X}")]
+    UnsupportedRecord { line: usize, record_type: u8 },
+    /// Data record extends past the 32-bit address space
+    #[error("line {line}: data beyond 4 GiB address space")]
+    AddressOverflow { line: usize },
+    /// File ended without an end-of-file record
+    #[error("missing end-of-file record")]
+    MissingEof,
+}
+
+/// Parse an Intel HEX file into its data records
+pub fn parse(text: &str) -> Result<SparseImage, IhexError> {
+    let mut image = SparseImage::default();
+    let mut base = 0u32;
+
+    for (idx, raw_line) in text.lines().enumerate() {
+        let line = idx + 1;
+        let record = raw_line.trim();
+        if record.is_empty() {
+            continue;
+        }
+
+        let bytes = record
+            .strip_prefix(':')
+            .and_then(decode_hex)
+            .ok_or(IhexError::Malformed { line })?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(IhexError::LengthMismatch { line });
+        }
+        if bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0 {
+            return Err(IhexError::Checksum { line });
+        }
+
+        let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let record_type = bytes[3];
+        let data = &bytes[4..bytes.len() - 1];
+
+        match record_type {
+            RECORD_DATA => {
+                let addr = base
+                    .checked_add(offset)
+                    .filter(|addr| addr.checked_add(data.len() as u32).is_some())
+                    .ok_or(IhexError::AddressOverflow { line })?;
+                image.push(addr, data);
+            }
+            RECORD_EOF => return Ok(image),
+            RECORD_EXT_SEGMENT | RECORD_EXT_LINEAR => {
+                if data.len() != 2 {
+                    return Err(IhexError::LengthMismatch { line });
+                }
+                let value = u16::from_be_bytes([data[0], data[1]]) as u32;
+                base = if record_type == RECORD_EXT_LINEAR {
+                    value << 16
+                } else {
+                    value << 4
+                };
+            }
+            RECORD_START_SEGMENT | RECORD_START_LINEAR => {}
+            _ => return Err(IhexError::UnsupportedRecord { line, record_type }),
+        }
+    }
+
+    Err(IhexError::MissingEof)
+}
+
+/// Write one record line, computing its checksum
+fn write_record<W: Write>(
+    out: &mut W,
+    record_type: u8,
+    offset: u16,
+    data: &[u8],
+) -> io::Result<()> {
+    let mut record = Vec::with_capacity(data.len() + 5);
+    record.push(data.len() as u8);
+    record.extend_from_slice(&offset.to_be_bytes());
+    record.push(record_type);
+    record.extend_from_slice(data);
+    let checksum = record
+        .iter()
+        .fold(0u8, |sum, &b| sum.wrapping_add(b))
+        .wrapping_neg();
+    record.push(checksum);
+
+    let mut line = String::with_capacity(record.len() * 2 + 2);
+    line.push(':');
+    for b in &record {
+        line.push_str(&format!("{:02X}", b));
+    }
+    writeln!(out, "{}", line)
+}
+
+/// Streaming Intel HEX encoder
+///
+/// Bytes written to it are taken as consecutive flash contents starting at
+/// address 0 and emitted as 16-byte data records, with an extended linear
+/// address record at every 64 KiB boundary. Call [`IhexWriter::finish`] to
+/// flush the last record and write the end-of-file record.
+pub struct IhexWriter<W: Write> {
+    inner: W,
+    addr: u32,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> IhexWriter<W> {
+    /// Create an encoder writing records to `inner`
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            addr: 0,
+            buf: Vec::with_capacity(BYTES_PER_RECORD),
+        }
+    }
+
+    fn flush_record(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        if self.addr & 0xFFFF == 0 && self.addr != 0 {
+            let upper = ((self.addr >> 16) as u16).to_be_bytes();
+            write_record(&mut self.inner, RECORD_EXT_LINEAR, 0, &upper)?;
+        }
+        write_record(&mut self.inner, RECORD_DATA, self.addr as u16, &self.buf)?;
+        self.addr = self.addr.wrapping_add(self.buf.len() as u32);
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Flush pending data, write the end-of-file record and return the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush_record()?;
+        write_record(&mut self.inner, RECORD_EOF, 0, &[])?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for IhexWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        for &b in data {
+            self.buf.push(b);
+            if self.buf.len() == BYTES_PER_RECORD {
+                self.flush_record()?;
+            }
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_data_and_eof() {
+        let text = ":0400100001020304E2\n:00000001FF\n";
+        let image = parse(text).unwrap();
+        assert_eq!(image.segments, [(0x10, vec![1, 2, 3, 4])]);
+    }
+
+    #[test]
+    fn test_parse_extended_linear_address() {
+        // Data at 0x0001_0000 via an extended linear address record
+        let text = ":020000040001F9\n:02000000AA55FF\n:00000001FF\n";
+        let image = parse(text).unwrap();
+        assert_eq!(image.segments, [(0x1_0000, vec![0xAA, 0x55])]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse(":0400100001020304E3\n:00000001FF\n"),
+            Err(IhexError::Checksum { line: 1 })
+        );
+        assert_eq!(
+            parse("0400100001020304E2\n"),
+            Err(IhexError::Malformed { line: 1 })
+        );
+        assert_eq!(parse(":0400100001020304E2\n"), Err(IhexError::MissingEof));
+        assert_eq!(
+            parse(":00000006FA\n"),
+            Err(IhexError::UnsupportedRecord {
+                line: 1,
+                record_type: 0x06
+            })
+        );
+    }
+
+    #[test]
+    fn test_records_outside_chip_rejected() {
+        let text = ":020000040001F9\n:02000000AA55FF\n:00000001FF\n";
+        let image = parse(text).unwrap();
+        assert!(image.to_flash_image(0x1_0000).is_err());
+        assert!(image.to_flash_image(0x2_0000).is_ok());
+    }
+
+    #[test]
+    fn test_writer_round_trip() {
+        let data: Vec<u8> = (0..0x2_0010u32).map(|i| (i % 253) as u8).collect();
+
+        let mut writer = IhexWriter::new(Vec::new());
+        // Uneven write sizes must not affect the record layout
+        for chunk in data.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        assert!(text.starts_with(":10000000"));
+        assert!(text.contains(":020000040001F9\n"));
+        assert!(text.ends_with(":00000001FF\n"));
+
+        let image = parse(&text).unwrap();
+        assert_eq!(image.segments.len(), 1);
+        assert_eq!(image.segments[0], (0, data));
+    }
+}
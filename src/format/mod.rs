@@ -0,0 +1,179 @@
+//! Image file formats for read and write
+//!
+//! Raw binary images map file offsets 1:1 onto flash addresses. The
+//! address-based formats (Intel HEX, Motorola S-record) describe sparse data
+//! records instead;
+//! they are parsed into a [`SparseImage`] and expanded to a full flash image
+//! with unspecified addresses left erased (0xFF).
+
+pub mod ihex;
+pub mod srec;
+
+use crate::cli::ImageFormat;
+use std::path::Path;
+
+/// Pick the image format for a file
+///
+/// An explicit `--format` wins; otherwise the file extension decides, and
+/// anything unrecognised is treated as raw binary.
+pub fn resolve_format(explicit: Option<ImageFormat>, path: &Path) -> ImageFormat {
+    if let Some(format) = explicit {
+        return format;
+    }
+
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("hex" | "ihex") => ImageFormat::Ihex,
+        Some("srec" | "s19" | "s28" | "s37" | "mot") => ImageFormat::Srec,
+        _ => ImageFormat::Binary,
+    }
+}
+
+/// Decode a string of hex digit pairs
+fn decode_hex(digits: &str) -> Option<Vec<u8>> {
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Data parsed from an address-based image file
+///
+/// Stored as runs of contiguous bytes in file order; later records
+/// overwrite earlier ones when expanded.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SparseImage {
+    /// `(start address, bytes)` runs
+    pub segments: Vec<(u32, Vec<u8>)>,
+}
+
+impl SparseImage {
+    /// Add data at `addr`, extending the last run when contiguous
+    pub fn push(&mut self, addr: u32, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        if let Some((start, bytes)) = self.segments.last_mut()
+            && *start as u64 + bytes.len() as u64 == addr as u64
+        {
+            bytes.extend_from_slice(data);
+            return;
+        }
+        self.segments.push((addr, data.to_vec()));
+    }
+
+    /// Number of data bytes in the image
+    pub fn data_len(&self) -> usize {
+        self.segments.iter().map(|(_, bytes)| bytes.len()).sum()
+    }
+
+    /// Check that all data lies within a `flash_size` byte flash
+    pub fn check_fits(&self, flash_size: u32) -> Result<(), String> {
+        for (start, bytes) in &self.segments {
+            let end = *start as u64 + bytes.len() as u64;
+            if end > flash_size as u64 {
+                return Err(format!(
+                    "Image data at 0x{:08X}-0x{:08X} is outside the flash ({} bytes)",
+                    start,
+                    end - 1,
+                    flash_size
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy the image bytes starting at `addr` into `buf`
+    ///
+    /// Bytes without data are filled with 0xFF, so any part of the expanded
+    /// image can be produced without expanding all of it.
+    pub fn fill(&self, addr: u32, buf: &mut [u8]) {
+        buf.fill(0xFF);
+        let buf_end = addr as u64 + buf.len() as u64;
+        for (start, bytes) in &self.segments {
+            let seg_start = *start as u64;
+            let seg_end = seg_start + bytes.len() as u64;
+            let from = std::cmp::max(seg_start, addr as u64);
+            let to = std::cmp::min(seg_end, buf_end);
+            if from < to {
+                buf[(from - addr as u64) as usize..(to - addr as u64) as usize].copy_from_slice(
+                    &bytes[(from - seg_start) as usize..(to - seg_start) as usize],
+                );
+            }
+        }
+    }
+
+    /// Expand to a `flash_size` byte image, filling gaps with 0xFF
+    ///
+    /// Fails if any data lies beyond the end of the flash.
+    pub fn to_flash_image(&self, flash_size: u32) -> Result<Vec<u8>, String> {
+        self.check_fits(flash_size)?;
+        let mut image = vec![0xFFu8; flash_size as usize];
+        self.fill(0, &mut image);
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_format() {
+        assert_eq!(resolve_format(None, Path::new("fw.HEX")), ImageFormat::Ihex);
+        assert_eq!(resolve_format(None, Path::new("fw.s37")), ImageFormat::Srec);
+        assert_eq!(
+            resolve_format(None, Path::new("fw.bin")),
+            ImageFormat::Binary
+        );
+        assert_eq!(
+            resolve_format(Some(ImageFormat::Binary), Path::new("fw.hex")),
+            ImageFormat::Binary
+        );
+    }
+
+    #[test]
+    fn test_sparse_image_merges_and_fills() {
+        let mut sparse = SparseImage::default();
+        sparse.push(0x10, &[1, 2]);
+        sparse.push(0x12, &[3]);
+        sparse.push(0x20, &[4]);
+        assert_eq!(sparse.segments.len(), 2);
+        assert_eq!(sparse.data_len(), 4);
+
+        let image = sparse.to_flash_image(0x40).unwrap();
+        assert_eq!(&image[0x10..0x13], &[1, 2, 3]);
+        assert_eq!(image[0x20], 4);
+        assert!(image[..0x10].iter().all(|&b| b == 0xFF));
+
+        assert!(sparse.to_flash_image(0x20).is_err());
+    }
+
+    #[test]
+    fn test_sparse_image_fill_window() {
+        let mut sparse = SparseImage::default();
+        sparse.push(0x10, &[1, 2, 3]);
+        sparse.push(0x08, &[9]);
+
+        // A window cutting through the first run
+        let mut buf = [0u8; 4];
+        sparse.fill(0x11, &mut buf);
+        assert_eq!(buf, [2, 3, 0xFF, 0xFF]);
+
+        // Later records overwrite earlier ones, as when expanded
+        sparse.push(0x12, &[7]);
+        sparse.fill(0x0E, &mut buf);
+        assert_eq!(buf, [0xFF, 0xFF, 1, 2]);
+        let image = sparse.to_flash_image(0x20).unwrap();
+        let mut window = [0u8; 0x20];
+        sparse.fill(0, &mut window);
+        assert_eq!(image, window);
+        assert_eq!(image[0x12], 7);
+    }
+}
@@ -0,0 +1,289 @@
+//! Motorola S-record parsing and generation
+//!
+//! Data records may use any address width (S1/S2/S3). S0 headers are
+//! ignored, S5/S6 record counts are checked against the number of data
+//! records, and an S7/S8/S9 record terminates the file.
+
+use super::{SparseImage, decode_hex};
+use std::io::{self, Write};
+
+/// Data bytes per record when generating S-records
+const BYTES_PER_RECORD: usize = 16;
+
+/// Errors from parsing an S-record file
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum SrecError {
+    /// Line doesn't start with 'S' or has malformed hex digits
+    #[error("line {line}: malformed record")]
+    Malformed { line: usize },
+    /// Byte count doesn't match the record length
+    #[error("line {line}: record length mismatch")]
+    LengthMismatch { line: usize },
+    /// Checksum byte doesn't match the record contents
+    #[error("line {line}: checksum mismatch")]
+    Checksum { line: usize },
+    /// Unknown record type
+    #[error("line {line}: unsupported record type S{record_type}")]
+    UnsupportedRecord { line: usize, record_type: char },
+    /// S5/S6 count doesn't match the number of data records seen so far
+    #[error("line {line}: record count {expected} doesn't match {actual} data records")]
+    RecordCount {
+        line: usize,
+        expected: u32,
+        actual: u32,
+    },
+    /// Data record extends past the 32-bit address space
+    #[error("line {line}: data beyond 4 GiB address space")]
+    AddressOverflow { line: usize },
+    /// File ended without an S7/S8/S9 termination record
+    #[error("missing termination record")]
+    MissingTermination,
+}
+
+/// Number of address bytes used by a record type
+fn address_len(record_type: char) -> Option<usize> {
+    match record_type {
+        '0' | '1' | '5' | '9' => Some(2),
+        '2' | '6' | '8' => Some(3),
+        '3' | '7' => Some(4),
+        _ => None,
+    }
+}
+
+/// Parse an S-record file into its data records
+pub fn parse(text: &str) -> Result<SparseImage, SrecError> {
+    let mut image = SparseImage::default();
+    let mut data_records = 0u32;
+
+    for (idx, raw_line) in text.lines().enumerate() {
+        let line = idx + 1;
+        let record = raw_line.trim();
+        if record.is_empty() {
+            continue;
+        }
+
+        let mut chars = record.chars();
+        if chars.next() != Some('S') {
+            return Err(SrecError::Malformed { line });
+        }
+        let record_type = chars.next().ok_or(SrecError::Malformed { line })?;
+        let addr_len =
+            address_len(record_type).ok_or(SrecError::UnsupportedRecord { line, record_type })?;
+
+        let bytes = decode_hex(chars.as_str()).ok_or(SrecError::Malformed { line })?;
+        if bytes.len() < addr_len + 2 || bytes.len() != bytes[0] as usize + 1 {
+            return Err(SrecError::LengthMismatch { line });
+        }
+        if bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) != 0xFF {
+            return Err(SrecError::Checksum { line });
+        }
+
+        let addr = bytes[1..=addr_len]
+            .iter()
+            .fold(0u32, |acc, &b| (acc << 8) | b as u32);
+        let data = &bytes[addr_len + 1..bytes.len() - 1];
+
+        match record_type {
+            '1' | '2' | '3' => {
+                if addr.checked_add(data.len() as u32).is_none() {
+                    return Err(SrecError::AddressOverflow { line });
+                }
+                image.push(addr, data);
+                data_records += 1;
+            }
+            '5' | '6' if addr != data_records => {
+                return Err(SrecError::RecordCount {
+                    line,
+                    expected: addr,
+                    actual: data_records,
+                });
+            }
+            '7' | '8' | '9' => return Ok(image),
+            // S0 header, or a record count that matches
+            _ => {}
+        }
+    }
+
+    Err(SrecError::MissingTermination)
+}
+
+/// Streaming S-record encoder
+///
+/// Bytes written to it are taken as consecutive flash contents starting at
+/// address 0 and emitted as 16-byte data records. The address width is
+/// picked from the flash size: S1 up to 64 KiB, S2 up to 16 MiB and S3
+/// beyond. Call [`SrecWriter::finish`] to flush the last record and write
+/// the record count and matching S9/S8/S7 termination record.
+pub struct SrecWriter<W: Write> {
+    inner: W,
+    /// Data record type ('1', '2' or '3')
+    data_type: char,
+    addr: u32,
+    records: u32,
+    buf: Vec<u8>,
+}
+
+/// Write one record line, computing its byte count and checksum
+fn write_record<W: Write>(
+    out: &mut W,
+    record_type: char,
+    addr: u32,
+    data: &[u8],
+) -> io::Result<()> {
+    let addr_len = address_len(record_type).unwrap_or(4);
+    let mut record = Vec::with_capacity(addr_len + data.len() + 2);
+    record.push((addr_len + data.len() + 1) as u8);
+    record.extend_from_slice(&addr.to_be_bytes()[4 - addr_len..]);
+    record.extend_from_slice(data);
+    let checksum = !record.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    record.push(checksum);
+
+    let mut line = String::with_capacity(record.len() * 2 + 2);
+    line.push('S');
+    line.push(record_type);
+    for b in &record {
+        line.push_str(&format!("{:02X}", b));
+    }
+    writeln!(out, "{}", line)
+}
+
+impl<W: Write> SrecWriter<W> {
+    /// Create an encoder for a flash of `flash_size` bytes writing to `inner`
+    pub fn new(inner: W, flash_size: u32) -> io::Result<Self> {
+        let data_type = match flash_size {
+            0..=0x1_0000 => '1',
+            0x1_0001..=0x100_0000 => '2',
+            _ => '3',
+        };
+        let mut writer = Self {
+            inner,
+            data_type,
+            addr: 0,
+            records: 0,
+            buf: Vec::with_capacity(BYTES_PER_RECORD),
+        };
+        write_record(&mut writer.inner, '0', 0, b"rflasher")?;
+        Ok(writer)
+    }
+
+    fn flush_record(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        write_record(&mut self.inner, self.data_type, self.addr, &self.buf)?;
+        self.addr = self.addr.wrapping_add(self.buf.len() as u32);
+        self.records += 1;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Flush pending data, write the count and termination records and
+    /// return the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush_record()?;
+        if self.records <= 0xFFFF {
+            write_record(&mut self.inner, '5', self.records, &[])?;
+        } else if self.records <= 0xFF_FFFF {
+            write_record(&mut self.inner, '6', self.records, &[])?;
+        }
+        let termination = match self.data_type {
+            '1' => '9',
+            '2' => '8',
+            _ => '7',
+        };
+        write_record(&mut self.inner, termination, 0, &[])?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for SrecWriter<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        for &b in data {
+            self.buf.push(b);
+            if self.buf.len() == BYTES_PER_RECORD {
+                self.flush_record()?;
+            }
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_s1_records() {
+        let text = "S00600004844521B\nS107001001020304DE\nS5030001FB\nS9030000FC\n";
+        let image = parse(text).unwrap();
+        assert_eq!(image.segments, [(0x10, vec![1, 2, 3, 4])]);
+    }
+
+    #[test]
+    fn test_parse_s3_records() {
+        let text = "S30801000000AA55FFF8\nS70500000000FA\n";
+        let image = parse(text).unwrap();
+        assert_eq!(image.segments, [(0x0100_0000, vec![0xAA, 0x55, 0xFF])]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("S00600004844521B\nS107001001020304DF\nS9030000FC\n"),
+            Err(SrecError::Checksum { line: 2 })
+        );
+        assert_eq!(
+            parse("S107001001020304DE\n"),
+            Err(SrecError::MissingTermination)
+        );
+        assert_eq!(
+            parse("S107001001020304DE\nS5030002FA\n"),
+            Err(SrecError::RecordCount {
+                line: 2,
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(
+            parse("S4030000FC\n"),
+            Err(SrecError::UnsupportedRecord {
+                line: 1,
+                record_type: '4'
+            })
+        );
+        assert_eq!(
+            parse(":00000001FF\n"),
+            Err(SrecError::Malformed { line: 1 })
+        );
+    }
+
+    fn round_trip(flash_size: u32, len: u32, first_record: &str, termination: &str) {
+        let data: Vec<u8> = (0..len).map(|i| (i % 253) as u8).collect();
+
+        let mut writer = SrecWriter::new(Vec::new(), flash_size).unwrap();
+        for chunk in data.chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        let text = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[0].starts_with("S0"));
+        assert!(lines[1].starts_with(first_record));
+        assert!(lines.last().unwrap().starts_with(termination));
+
+        let image = parse(&text).unwrap();
+        assert_eq!(image.segments, [(0, data)]);
+    }
+
+    #[test]
+    fn test_writer_picks_address_width() {
+        round_trip(0x1_0000, 0x1_0000, "S113", "S9");
+        round_trip(0x2_0000, 0x2_0000, "S214", "S8");
+        round_trip(0x200_0000, 0x1000, "S315", "S7");
+    }
+}
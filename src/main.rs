@@ -15,21 +15,30 @@
 
 mod cli;
 mod commands;
+mod format;
 
 use clap::Parser;
-use cli::{Cli, Commands, LayoutArgs, LayoutCommands, WpCommands};
+use cli::{Cli, Commands, LayoutArgs, LayoutCommands, OtpCommands, RangeArgs, WpCommands};
 use rflasher_core::chip::ChipDatabase;
-use rflasher_flash::{FlashHandle, open_flash};
+use rflasher_core::flash::unified::RetryingReadDevice;
+use rflasher_flash::{FlashDevice, FlashHandle, OpenOptions, open_flash_with_options};
 
 use rflasher_core::layout::Layout;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 
 fn main() {
-    // Initialize logger
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
-
     let cli = Cli::parse();
 
+    // Initialize logger
+    let mut logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    if cli.trace_spi {
+        // SPI commands are logged at trace level, whatever the verbosity
+        logger.filter_module("rflasher_flash::trace", log::LevelFilter::Trace);
+    }
+    logger.init();
+
     if let Err(e) = run(cli) {
         eprintln!("Error: {}", e);
         std::process::exit(1);
@@ -37,175 +46,410 @@ fn main() {
 }
 
 fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
-    // Set log level based on verbosity
+    // Set log level based on verbosity, keeping trace level for --trace-spi
     match cli.verbose {
         0 => {} // default (info)
-        1 => log::set_max_level(log::LevelFilter::Debug),
+        1 if !cli.trace_spi => log::set_max_level(log::LevelFilter::Debug),
         _ => log::set_max_level(log::LevelFilter::Trace),
     }
 
+    commands::unified::set_progress_enabled(!cli.no_progress && std::io::stderr().is_terminal());
+
     // Load chip database
     let db = load_chip_database(cli.chip_db.as_deref())
         .map_err(|e| format!("Failed to load chip database: {}", e))?;
 
     log::info!("Loaded {} chip definitions", db.len());
 
+    let options = OpenOptions {
+        chunk_size: cli.chunk_size.map(|size| size as usize),
+        io_mode: cli.iomode.bus_width(),
+        reset_before_probe: cli.reset_before_probe,
+        prefer_sfdp: cli.prefer_sfdp,
+        erase_timeout_scale: cli.erase_timeout_scale,
+        trace_spi: cli.trace_spi,
+        ..Default::default()
+    };
+    let open = |programmer: &str, chip: Option<&str>| {
+        open_flash_with_options(programmer, &db, OpenOptions { chip, ..options })
+    };
+
     match cli.command {
-        Commands::Probe { programmer } => {
+        Commands::Probe {
+            programmer,
+            aggressive,
+        } => {
             // Probe doesn't use the device, just shows info
-            let _handle = open_flash(&programmer, &db)?;
+            let handle = open_flash_with_options(
+                &programmer,
+                &db,
+                OpenOptions {
+                    aggressive_probe: aggressive,
+                    ..options
+                },
+            )?;
+            if cli.json {
+                commands::json::print_device(&handle)?;
+            }
             Ok(())
         }
         Commands::Read {
             programmer,
             output,
-            chip: _,
+            format,
+            chip,
             layout,
+            range,
         } => {
-            let mut handle = open_flash(&programmer, &db)?;
-            if layout.has_layout_source() || layout.has_region_filter() {
-                let mut layout_obj = load_layout(&mut handle, &layout)?;
-                apply_region_filters(&mut layout_obj, &layout)?;
-                commands::unified::run_read_with_layout(
-                    handle.as_device_mut(),
-                    &output,
-                    &layout_obj,
-                )
+            let format = format::resolve_format(format, &output);
+            let mut handle = open(&programmer, chip.as_deref())?;
+            if let Some(layout_obj) = selected_layout(&mut handle, &layout, &range)? {
+                with_read_tries(&mut handle, cli.read_tries, |device| {
+                    commands::unified::run_read_with_layout(device, &output, &layout_obj, format)
+                })
             } else {
-                commands::unified::run_read(handle.as_device_mut(), &output)
+                with_read_tries(&mut handle, cli.read_tries, |device| {
+                    commands::unified::run_read(device, &output, format)
+                })
             }
         }
         Commands::Write {
             programmer,
             input,
-            chip: _,
+            format,
+            chip,
             verify,
             no_erase: _,
+            dry_run,
+            resume,
+            pad,
+            layout_from_image,
             layout,
+            range,
         } => {
-            let mut handle = open_flash(&programmer, &db)?;
-            if layout.has_layout_source() || layout.has_region_filter() {
-                let mut layout_obj = load_layout(&mut handle, &layout)?;
+            let format = format::resolve_format(format, &input);
+            let mut handle = open(&programmer, chip.as_deref())?;
+            let verify = commands::unified::WriteVerify::new(verify, handle.chip_info().is_none());
+            if layout_from_image {
+                let file_data = commands::unified::load_image(&input, format, handle.size())?;
+                let mut layout_obj =
+                    commands::unified::image_fmap_layout(&file_data, handle.size())?;
+                log::info!(
+                    "Found FMAP with {} regions in {:?}",
+                    layout_obj.len(),
+                    input
+                );
+                commands::layout::print_layout(&layout_obj);
                 apply_region_filters(&mut layout_obj, &layout)?;
-                commands::unified::run_write_with_layout(
-                    handle.as_device_mut(),
-                    &input,
-                    &mut layout_obj,
-                    verify,
-                )
+                with_read_tries(&mut handle, cli.read_tries, |device| {
+                    commands::unified::write_image(
+                        device,
+                        &input,
+                        file_data,
+                        &layout_obj,
+                        verify,
+                        dry_run,
+                        resume,
+                    )
+                })
+            } else if let Some(mut layout_obj) = selected_layout(&mut handle, &layout, &range)? {
+                with_read_tries(&mut handle, cli.read_tries, |device| {
+                    commands::unified::run_write_with_layout(
+                        device,
+                        &input,
+                        format,
+                        &mut layout_obj,
+                        verify,
+                        dry_run,
+                        resume,
+                    )
+                })
             } else {
-                commands::unified::run_write(handle.as_device_mut(), &input, verify)
+                with_read_tries(&mut handle, cli.read_tries, |device| {
+                    commands::unified::run_write(
+                        device, &input, format, verify, dry_run, resume, pad,
+                    )
+                })
             }
         }
         Commands::Erase {
             programmer,
-            chip: _,
+            chip,
+            dry_run,
+            chip_erase,
             layout,
+            range,
         } => {
-            let mut handle = open_flash(&programmer, &db)?;
-            if layout.has_layout_source() || layout.has_region_filter() {
-                let mut layout_obj = load_layout(&mut handle, &layout)?;
-                apply_region_filters(&mut layout_obj, &layout)?;
-                commands::unified::run_erase_with_layout(handle.as_device_mut(), &layout_obj)
+            let mut handle = open(&programmer, chip.as_deref())?;
+            if let Some((start, len)) = range.range() {
+                commands::unified::check_erase_alignment(handle.as_device_mut(), start, len)?;
+            }
+            if chip_erase {
+                commands::unified::run_chip_erase(handle.as_device_mut(), dry_run)
+            } else if let Some(layout_obj) = selected_layout(&mut handle, &layout, &range)? {
+                commands::unified::run_erase_with_layout(
+                    handle.as_device_mut(),
+                    &layout_obj,
+                    dry_run,
+                )
             } else {
-                commands::unified::run_erase(handle.as_device_mut())
+                commands::unified::run_erase(handle.as_device_mut(), dry_run)
             }
         }
+        Commands::Blankcheck { programmer, chip } => {
+            let mut handle = open(&programmer, chip.as_deref())?;
+            with_read_tries(&mut handle, cli.read_tries, |device| {
+                commands::unified::run_blankcheck(device)
+            })
+        }
         Commands::Verify {
             programmer,
             input,
-            chip: _,
-            layout: _,
+            chip,
+            layout,
+            range,
         } => {
-            let mut handle = open_flash(&programmer, &db)?;
-            commands::unified::run_verify(handle.as_device_mut(), &input)
+            let mut handle = open(&programmer, chip.as_deref())?;
+            if let Some(layout_obj) = selected_layout(&mut handle, &layout, &range)? {
+                with_read_tries(&mut handle, cli.read_tries, |device| {
+                    commands::unified::run_verify_with_layout(device, &input, &layout_obj)
+                })
+            } else {
+                with_read_tries(&mut handle, cli.read_tries, |device| {
+                    commands::unified::run_verify(device, &input)
+                })
+            }
+        }
+        Commands::Diff {
+            programmer,
+            input,
+            format,
+            chip,
+            hex,
+            layout,
+        } => {
+            let format = format::resolve_format(format, &input);
+            let mut handle = open(&programmer, chip.as_deref())?;
+            let layout_obj = if layout.has_layout_source() || layout.has_region_filter() {
+                let mut layout_obj = load_layout(&mut handle, &layout)?;
+                apply_region_filters(&mut layout_obj, &layout)?;
+                layout_obj
+            } else {
+                commands::unified::full_flash_layout(handle.size())
+            };
+            with_read_tries(&mut handle, cli.read_tries, |device| {
+                commands::unified::run_diff(device, &input, format, &layout_obj, hex)
+            })
+        }
+        Commands::Digest {
+            programmer,
+            algo,
+            chip,
+            layout,
+        } => {
+            let mut handle = open(&programmer, chip.as_deref())?;
+            let layout_obj = if layout.has_layout_source() || layout.has_region_filter() {
+                let mut layout_obj = load_layout(&mut handle, &layout)?;
+                apply_region_filters(&mut layout_obj, &layout)?;
+                layout_obj
+            } else {
+                commands::unified::full_flash_layout(handle.size())
+            };
+            with_read_tries(&mut handle, cli.read_tries, |device| {
+                commands::unified::run_digest(device, &layout_obj, algo)
+            })
         }
         Commands::Info {
             programmer,
-            chip: _,
+            chip,
+            straps,
         } => {
-            let mut handle = open_flash(&programmer, &db)?;
+            if cli.json && straps {
+                return Err("--straps can't be combined with --json".into());
+            }
+            let mut handle = open(&programmer, chip.as_deref())?;
+            if cli.json {
+                return commands::json::print_device(&handle);
+            }
             print_chip_info(&mut handle);
+            if straps {
+                print_ifd_straps(&mut handle);
+            }
             Ok(())
         }
+        Commands::Sfdp { programmer, raw } => {
+            use commands::sfdp::SfdpOutput;
+            let output = match (raw, cli.json) {
+                (true, _) => SfdpOutput::Raw,
+                (_, true) => SfdpOutput::Json,
+                _ => SfdpOutput::Summary,
+            };
+            commands::sfdp::cmd_sfdp(&programmer, output)
+        }
         Commands::ListProgrammers => {
+            if cli.json {
+                return commands::json::list_programmers();
+            }
             commands::list_programmers();
             Ok(())
         }
         Commands::ListChips { vendor } => {
+            if cli.json {
+                return commands::json::list_chips(&db, vendor.as_deref());
+            }
             commands::list_chips(&db, vendor.as_deref());
             Ok(())
         }
         Commands::Layout(subcmd) => match subcmd {
-            LayoutCommands::Show { file } => commands::layout::cmd_show(&file),
+            LayoutCommands::Show { file, format } => commands::layout::cmd_show(&file, format),
             LayoutCommands::Extract { input, output } => {
                 commands::layout::cmd_extract(&input, &output)
             }
             LayoutCommands::Ifd { input, output } => {
                 commands::layout::cmd_ifd(&input, output.as_deref())
             }
-            LayoutCommands::Fmap { input, output } => {
-                commands::layout::cmd_fmap(&input, output.as_deref())
+            LayoutCommands::Fmap {
+                input,
+                from,
+                output,
+            } => match (input, from) {
+                (_, Some(from)) => {
+                    let output = output.ok_or("--from requires --output")?;
+                    commands::layout::cmd_fmap_build(&from, &output)
+                }
+                (Some(input), None) => commands::layout::cmd_fmap(&input, output.as_deref()),
+                (None, None) => Err("--input or --from is required".into()),
+            },
+            LayoutCommands::Gpt { input, output } => {
+                commands::layout::cmd_gpt(&input, output.as_deref())
+            }
+            LayoutCommands::Cbfs { input, region } => {
+                commands::layout::cmd_cbfs(&input, region.as_deref())
             }
             LayoutCommands::Create { output, size } => commands::layout::cmd_create(&output, &size),
         },
         Commands::Wp(subcmd) => match subcmd {
-            WpCommands::Status {
-                programmer,
-                chip: _,
-            } => {
-                let mut handle = open_flash(&programmer, &db)?;
+            WpCommands::Status { programmer, chip } => {
+                let mut handle = open(&programmer, chip.as_deref())?;
                 commands::wp::cmd_status(&mut handle)
             }
-            WpCommands::List {
-                programmer,
-                chip: _,
-            } => {
-                let mut handle = open_flash(&programmer, &db)?;
+            WpCommands::List { programmer, chip } => {
+                let mut handle = open(&programmer, chip.as_deref())?;
                 commands::wp::cmd_list(&mut handle)
             }
             WpCommands::Enable {
                 programmer,
-                chip: _,
+                chip,
                 temporary,
+                permanent,
+                yes_i_am_sure,
             } => {
-                let mut handle = open_flash(&programmer, &db)?;
-                commands::wp::cmd_enable(&mut handle, temporary)
+                // Refuse before touching the hardware
+                if permanent && !yes_i_am_sure {
+                    return Err(
+                        "A permanent lock can never be undone; pass --yes-i-am-sure to proceed"
+                            .into(),
+                    );
+                }
+                let mut handle = open(&programmer, chip.as_deref())?;
+                commands::wp::cmd_enable(&mut handle, temporary, permanent)
             }
             WpCommands::Disable {
                 programmer,
-                chip: _,
+                chip,
                 temporary,
             } => {
-                let mut handle = open_flash(&programmer, &db)?;
+                let mut handle = open(&programmer, chip.as_deref())?;
                 commands::wp::cmd_disable(&mut handle, temporary)
             }
             WpCommands::Range {
                 programmer,
-                chip: _,
+                chip,
                 temporary,
                 range,
             } => {
-                let mut handle = open_flash(&programmer, &db)?;
+                let mut handle = open(&programmer, chip.as_deref())?;
                 commands::wp::cmd_range(&mut handle, &range, temporary)
             }
             WpCommands::Region {
                 programmer,
-                chip: _,
+                chip,
                 temporary,
                 layout,
                 region_name,
             } => {
-                let mut handle = open_flash(&programmer, &db)?;
+                let mut handle = open(&programmer, chip.as_deref())?;
                 let layout_obj = load_layout(&mut handle, &layout)?;
                 commands::wp::cmd_region(&mut handle, &layout_obj, &region_name, temporary)
             }
         },
+        Commands::Otp(subcmd) => match subcmd {
+            OtpCommands::Read {
+                programmer,
+                chip,
+                register,
+                output,
+            } => {
+                let mut handle = open(&programmer, chip.as_deref())?;
+                commands::otp::cmd_read(&mut handle, register, &output)
+            }
+            OtpCommands::Write {
+                programmer,
+                chip,
+                register,
+                input,
+            } => {
+                let mut handle = open(&programmer, chip.as_deref())?;
+                commands::otp::cmd_write(&mut handle, register, &input)
+            }
+            OtpCommands::Lock {
+                programmer,
+                chip,
+                register,
+                yes_i_am_sure,
+            } => {
+                // Refuse before touching the hardware
+                if !yes_i_am_sure {
+                    return Err(format!(
+                        "Locking security register {} is permanent; pass --yes-i-am-sure to proceed",
+                        register
+                    )
+                    .into());
+                }
+                let mut handle = open(&programmer, chip.as_deref())?;
+                commands::otp::cmd_lock(&mut handle, register)
+            }
+        },
+        Commands::PowerDown { programmer } => commands::power::cmd_power_down(&programmer, &db),
+        Commands::PowerUp { programmer } => commands::power::cmd_power_up(&programmer),
+        Commands::Selftest { programmer } => commands::selftest::cmd_selftest(&programmer, &db),
         #[cfg(feature = "repl")]
-        Commands::Repl { programmer, script } => {
-            commands::repl::cmd_repl(&programmer, script.as_deref())
-        }
+        Commands::Repl {
+            programmer,
+            script,
+            load,
+        } => commands::repl::cmd_repl(&programmer, script.as_deref(), load.as_deref(), &db),
+    }
+}
+
+/// Run a command on the handle's device, reading each chunk up to `tries` times
+///
+/// Warns afterwards if any chunk read back inconsistently, since that points
+/// at a marginal connection even when the retries recovered the data.
+fn with_read_tries<T>(
+    handle: &mut FlashHandle,
+    tries: u8,
+    f: impl FnOnce(&mut dyn FlashDevice) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let mut device = RetryingReadDevice::new(handle.as_device_mut(), tries);
+    let result = f(&mut device);
+    if device.mismatches() > 0 {
+        log::warn!(
+            "{} chunk(s) read back differently and had to be re-read; the connection may be marginal",
+            device.mismatches()
+        );
     }
+    result
 }
 
 /// Load the chip database from the specified path or default locations
@@ -261,13 +505,29 @@ fn load_layout(
     handle: &mut FlashHandle,
     args: &LayoutArgs,
 ) -> Result<Layout, Box<dyn std::error::Error>> {
-    use rflasher_core::layout::parse_ifd;
+    use rflasher_core::layout::{GPT_HEADER_PROBE_SIZE, gpt_size, parse_gpt, parse_ifd};
 
     if let Some(path) = &args.layout {
         // Load from TOML file
         let layout = Layout::from_toml_file(path)?;
         log::info!("Loaded layout from {:?}", path);
         Ok(layout)
+    } else if args.gpt {
+        // The header tells how much of the start of the flash the table needs
+        log::info!("Reading GUID Partition Table from chip...");
+        let size = handle.size() as usize;
+        let mut header = vec![0u8; GPT_HEADER_PROBE_SIZE.min(size)];
+        handle.as_device_mut().read(0, &mut header)?;
+        let table_size = gpt_size(&header)?;
+        if table_size > size {
+            return Err("GPT partition entries extend beyond the flash chip".into());
+        }
+        let mut table = vec![0u8; table_size];
+        handle.as_device_mut().read(0, &mut table)?;
+        let layout = parse_gpt(&table)?;
+        log::info!("Found GPT with {} regions", layout.len());
+        commands::layout::print_layout(&layout);
+        Ok(layout)
     } else if args.ifd || args.fmap {
         if args.ifd {
             // IFD is always at the beginning, so we only need to read the header
@@ -287,12 +547,31 @@ fn load_layout(
             Ok(layout)
         }
     } else if args.has_region_filter() {
-        Err("Layout source required (--layout, --ifd, or --fmap) when using --include, --exclude, or --region".into())
+        Err("Layout source required (--layout, --ifd, --fmap, or --gpt) when using --include, --exclude, or --region".into())
     } else {
         Err("No layout source specified".into())
     }
 }
 
+/// Layout selected by --start/--length or the layout options
+///
+/// Returns `None` when neither is given, for operations on the whole chip.
+fn selected_layout(
+    handle: &mut FlashHandle,
+    args: &LayoutArgs,
+    range: &RangeArgs,
+) -> Result<Option<Layout>, Box<dyn std::error::Error>> {
+    if let Some((start, len)) = range.range() {
+        return commands::unified::range_layout(handle.size(), start, len).map(Some);
+    }
+    if !args.has_layout_source() && !args.has_region_filter() {
+        return Ok(None);
+    }
+    let mut layout = load_layout(handle, args)?;
+    apply_region_filters(&mut layout, args)?;
+    Ok(Some(layout))
+}
+
 /// Apply region filters (--include, --exclude, --region) to a layout
 fn apply_region_filters(
     layout: &mut Layout,
@@ -321,6 +600,73 @@ fn apply_region_filters(
     Ok(())
 }
 
+fn print_ifd_straps(handle: &mut FlashHandle) {
+    use rflasher_core::layout::parse_ifd_straps;
+
+    let mut header = [0u8; 4096];
+    if let Err(e) = handle.as_device_mut().read(0, &mut header) {
+        println!();
+        println!("Note: Failed to read flash descriptor: {}", e);
+        return;
+    }
+
+    let Ok(straps) = parse_ifd_straps(&header) else {
+        println!();
+        println!("Note: No Intel Flash Descriptor found.");
+        return;
+    };
+
+    if let Some(platform) = straps.platform {
+        println!();
+        println!("Platform: {}", platform.name());
+    }
+
+    for (title, section) in [
+        ("PCH soft straps", &straps.pch),
+        ("Processor soft straps", &straps.processor),
+    ] {
+        println!();
+        println!("{}:", title);
+        if section.is_empty() {
+            println!("  (none)");
+        }
+        for strap in section {
+            println!(
+                "  {:12} @ 0x{:03X}: 0x{:08X}",
+                strap.name, strap.offset, strap.value
+            );
+            for field in &strap.fields {
+                println!(
+                    "    {:18} = {:<2} {}",
+                    field.name, field.value, field.description
+                );
+            }
+        }
+    }
+}
+
+fn print_erase_blocks(blocks: &[rflasher_core::chip::EraseBlock]) {
+    for eb in blocks {
+        if eb.is_uniform() {
+            // Uniform erase block - single size
+            let size = eb.uniform_size().unwrap_or(0);
+            println!(
+                "  Opcode 0x{:02X}: {}",
+                eb.opcode,
+                commands::format_size(size)
+            );
+        } else {
+            // Non-uniform erase block - show all regions
+            let regions: Vec<String> = eb
+                .regions()
+                .iter()
+                .map(|r| format!("{}x{}", r.count, commands::format_size(r.size)))
+                .collect();
+            println!("  Opcode 0x{:02X}: {}", eb.opcode, regions.join(" + "));
+        }
+    }
+}
+
 fn print_chip_info(handle: &mut FlashHandle) {
     use rflasher_core::layout::parse_ifd;
 
@@ -351,8 +697,14 @@ fn print_chip_info(handle: &mut FlashHandle) {
         println!("Page size:       {} bytes", info.page_size);
 
         // Show SFDP status
-        if info.sfdp.is_some() {
+        if let Some(sfdp) = &info.sfdp {
             println!("SFDP:            Supported");
+            if let Some(sccr) = &sfdp.sccr_map {
+                println!(
+                    "SCCR map:        rev {}.{}, {} DWORDs",
+                    sccr.revision.major, sccr.revision.minor, sccr.len_dwords
+                );
+            }
         } else {
             println!("SFDP:            Not detected");
         }
@@ -366,28 +718,48 @@ fn print_chip_info(handle: &mut FlashHandle) {
                 chip.voltage_max_mv as f32 / 1000.0
             );
             println!();
-            println!("Erase blocks:");
-            for eb in chip.erase_blocks() {
-                if eb.is_uniform() {
-                    // Uniform erase block - single size
-                    let size = eb.uniform_size().unwrap_or(0);
-                    println!(
-                        "  Opcode 0x{:02X}: {}",
-                        eb.opcode,
-                        commands::format_size(size)
-                    );
+            // When SFDP disagrees with the database, show both sets
+            let sfdp_blocks = info
+                .sfdp
+                .as_ref()
+                .filter(|_| info.mismatches.iter().any(|m| m.is_erase_block()))
+                .map(rflasher_core::sfdp::sfdp_erase_blocks);
+            match &sfdp_blocks {
+                Some(_) if info.sfdp_erase_blocks => {
+                    println!("Erase blocks (database, not used with --prefer-sfdp):")
+                }
+                Some(_) => println!("Erase blocks (database, used):"),
+                None => println!("Erase blocks:"),
+            }
+            print_erase_blocks(chip.erase_blocks());
+            if let Some(blocks) = &sfdp_blocks {
+                println!();
+                if info.sfdp_erase_blocks {
+                    println!("Erase blocks (SFDP, used):");
                 } else {
-                    // Non-uniform erase block - show all regions
-                    let regions: Vec<String> = eb
-                        .regions()
-                        .iter()
-                        .map(|r| format!("{}x{}", r.count, commands::format_size(r.size)))
-                        .collect();
-                    println!("  Opcode 0x{:02X}: {}", eb.opcode, regions.join(" + "));
+                    println!("Erase blocks (SFDP, used with --prefer-sfdp):");
                 }
+                print_erase_blocks(blocks);
             }
             println!();
             println!("Features:        {:?}", chip.features);
+
+            // The context prefers the QE location from SFDP, see
+            // FlashContext::quad_enable_method
+            let sfdp_qe = info
+                .sfdp
+                .as_ref()
+                .and_then(|sfdp| sfdp.basic_params.quad_enable);
+            match sfdp_qe {
+                Some(qer) => println!(
+                    "Quad Enable:     {} (SFDP)",
+                    rflasher_core::protocol::QuadEnableMethod::from(qer)
+                ),
+                None => match rflasher_core::protocol::quad_enable_method(chip.features) {
+                    Some(method) => println!("Quad Enable:     {} (database)", method),
+                    None => println!("Quad Enable:     unknown, quad reads not used"),
+                },
+            }
         }
 
         // Show SFDP mismatches if any
@@ -432,4 +804,14 @@ fn print_chip_info(handle: &mut FlashHandle) {
             }
         }
     }
+
+    if !handle.programmer_info().is_empty() {
+        println!();
+        println!("Programmer Information");
+        println!("======================");
+        println!();
+        for (label, value) in handle.programmer_info() {
+            println!("{:<17}{}", format!("{}:", label), value);
+        }
+    }
 }